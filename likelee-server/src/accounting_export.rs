@@ -0,0 +1,888 @@
+//! Accounting exports.
+//!
+//! Turns an agency's issued invoices, invoice payments, credit notes,
+//! approved expenses and paid payouts into balanced double-entry journal
+//! entries, written out as a plain journal CSV, a QuickBooks IIF file or a
+//! Xero manual-journal CSV. Account names come from the agency's mapping in
+//! `agency_accounting_settings`, falling back to `AccountMapping::default`.
+//!
+//! A period that has ended is closed: its first export stores the entries
+//! and the mapping used in `agency_accounting_exports`, and every later
+//! export of the same period is written from that copy. Editing a record or
+//! the mapping afterwards does not change a closed period's file.
+
+use crate::{
+    audit::{self, AuditEvent},
+    auth::AuthUser,
+    config::AppState,
+    errors::{AppError, AppResult},
+    repositories::{
+        fetch, AgencyPayoutRequest, Invoice, InvoiceCreditNote, InvoiceFilter, InvoicePayment,
+        InvoiceStatus, RepoError,
+    },
+};
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+
+pub const SETTINGS_TABLE: &str = "agency_accounting_settings";
+pub const EXPORTS_TABLE: &str = "agency_accounting_exports";
+
+/// Ledger accounts the entries post to. Values are account names for the
+/// journal and QuickBooks exports and account codes for Xero.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountMapping {
+    pub accounts_receivable: String,
+    pub bank: String,
+    pub commission_income: String,
+    pub talent_payable: String,
+    pub sales_tax_payable: String,
+    pub reimbursed_expenses: String,
+    /// Invoice discounts, and rounding left over from tax-inclusive pricing.
+    pub discounts: String,
+    pub credit_notes: String,
+    /// Expenses whose category has no entry in `expense_categories`.
+    pub expenses: String,
+    pub expense_categories: BTreeMap<String, String>,
+    /// The agency's Likelee balance that payouts are drawn from.
+    pub platform_balance: String,
+    pub payout_fees: String,
+    /// Xero needs a tax rate on every journal line.
+    pub xero_tax_rate: String,
+}
+
+impl Default for AccountMapping {
+    fn default() -> Self {
+        Self {
+            accounts_receivable: "Accounts Receivable".to_string(),
+            bank: "Checking".to_string(),
+            commission_income: "Commission Income".to_string(),
+            talent_payable: "Talent Payable".to_string(),
+            sales_tax_payable: "Sales Tax Payable".to_string(),
+            reimbursed_expenses: "Reimbursable Expenses".to_string(),
+            discounts: "Discounts and Adjustments".to_string(),
+            credit_notes: "Sales Returns and Allowances".to_string(),
+            expenses: "Operating Expenses".to_string(),
+            expense_categories: BTreeMap::new(),
+            platform_balance: "Likelee Balance".to_string(),
+            payout_fees: "Payout Fees".to_string(),
+            xero_tax_rate: "Tax Exempt".to_string(),
+        }
+    }
+}
+
+impl AccountMapping {
+    fn expense_account(&self, category: &str) -> &str {
+        self.expense_categories
+            .get(category)
+            .or_else(|| self.expense_categories.get(&category.to_ascii_lowercase()))
+            .unwrap_or(&self.expenses)
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        let names = [
+            &self.accounts_receivable,
+            &self.bank,
+            &self.commission_income,
+            &self.talent_payable,
+            &self.sales_tax_payable,
+            &self.reimbursed_expenses,
+            &self.discounts,
+            &self.credit_notes,
+            &self.expenses,
+            &self.platform_balance,
+            &self.payout_fees,
+        ];
+        if names
+            .into_iter()
+            .chain(self.expense_categories.values())
+            .any(|name| name.trim().is_empty() || name.contains(['\t', '\n', '\r']))
+        {
+            return Err(AppError::BadRequest("invalid_account".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Journal,
+    QuickBooks,
+    Xero,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "journal" | "csv" => Some(Self::Journal),
+            "iif" | "quickbooks" => Some(Self::QuickBooks),
+            "xero" => Some(Self::Xero),
+            _ => None,
+        }
+    }
+
+    fn file_name(&self, from: NaiveDate, to: NaiveDate) -> String {
+        match self {
+            Self::Journal => format!("journal-{from}-{to}.csv"),
+            Self::QuickBooks => format!("quickbooks-{from}-{to}.iif"),
+            Self::Xero => format!("xero-{from}-{to}.csv"),
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Journal | Self::Xero => "text/csv; charset=utf-8",
+            Self::QuickBooks => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalLine {
+    pub account: String,
+    pub debit_cents: i64,
+    pub credit_cents: i64,
+}
+
+/// One balanced transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub date: NaiveDate,
+    /// `invoice`, `payment`, `credit_note`, `expense` or `payout`.
+    pub source: String,
+    pub source_id: String,
+    pub reference: String,
+    pub memo: String,
+    pub currency: String,
+    pub lines: Vec<JournalLine>,
+}
+
+impl JournalEntry {
+    fn new(
+        date: NaiveDate,
+        source: &str,
+        source_id: &str,
+        reference: String,
+        memo: String,
+        currency: &str,
+    ) -> Self {
+        Self {
+            date,
+            source: source.to_string(),
+            source_id: source_id.to_string(),
+            reference,
+            memo,
+            currency: currency.to_ascii_uppercase(),
+            lines: Vec::new(),
+        }
+    }
+
+    fn debit(mut self, account: &str, cents: i64) -> Self {
+        self.post(account, cents);
+        self
+    }
+
+    fn credit(mut self, account: &str, cents: i64) -> Self {
+        self.post(account, -cents);
+        self
+    }
+
+    /// Positive amounts debit, negative ones credit; zero lines are dropped.
+    fn post(&mut self, account: &str, cents: i64) {
+        if cents == 0 {
+            return;
+        }
+        self.lines.push(JournalLine {
+            account: account.to_string(),
+            debit_cents: cents.max(0),
+            credit_cents: (-cents).max(0),
+        });
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        let debits: i64 = self.lines.iter().map(|l| l.debit_cents).sum();
+        let credits: i64 = self.lines.iter().map(|l| l.credit_cents).sum();
+        debits == credits
+    }
+}
+
+/// Issuing an invoice: the client owes the total, split into the agency's
+/// commission, the talent's share, recharged expenses and tax. Whatever
+/// the total is short of those (the discount, plus rounding on
+/// tax-inclusive prices) goes to the discounts account.
+pub fn invoice_entry(invoice: &Invoice, accounts: &AccountMapping) -> JournalEntry {
+    let total = invoice.total_cents as i64;
+    let fee = invoice.agency_fee_cents as i64;
+    let talent = invoice.talent_net_cents as i64;
+    let expenses = invoice.expenses_cents as i64;
+    let tax = invoice.tax_cents as i64;
+    JournalEntry::new(
+        invoice.invoice_date,
+        "invoice",
+        &invoice.id,
+        invoice.invoice_number.clone(),
+        format!(
+            "Invoice {} to {}",
+            invoice.invoice_number, invoice.bill_to_company
+        ),
+        &invoice.currency,
+    )
+    .debit(&accounts.accounts_receivable, total)
+    .debit(&accounts.discounts, fee + talent + expenses + tax - total)
+    .credit(&accounts.commission_income, fee)
+    .credit(&accounts.talent_payable, talent)
+    .credit(&accounts.reimbursed_expenses, expenses)
+    .credit(&accounts.sales_tax_payable, tax)
+}
+
+/// Minimal invoice fields payments and credit notes are labelled with.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InvoiceRef {
+    pub id: String,
+    pub invoice_number: String,
+    pub currency: String,
+}
+
+pub fn payment_entry(
+    payment: &InvoicePayment,
+    invoice: &InvoiceRef,
+    accounts: &AccountMapping,
+) -> JournalEntry {
+    let memo = match payment
+        .reference
+        .as_deref()
+        .filter(|r| !r.trim().is_empty())
+    {
+        Some(reference) => format!(
+            "Payment for invoice {} ({}, {reference})",
+            invoice.invoice_number, payment.method
+        ),
+        None => format!(
+            "Payment for invoice {} ({})",
+            invoice.invoice_number, payment.method
+        ),
+    };
+    let amount = payment.amount_cents as i64;
+    JournalEntry::new(
+        payment.paid_on,
+        "payment",
+        &payment.id,
+        invoice.invoice_number.clone(),
+        memo,
+        &invoice.currency,
+    )
+    .debit(&accounts.bank, amount)
+    .credit(&accounts.accounts_receivable, amount)
+}
+
+pub fn credit_note_entry(
+    note: &InvoiceCreditNote,
+    invoice: &InvoiceRef,
+    accounts: &AccountMapping,
+) -> JournalEntry {
+    let amount = note.amount_cents as i64;
+    JournalEntry::new(
+        note.issued_on,
+        "credit_note",
+        &note.id,
+        note.credit_note_number.clone(),
+        format!(
+            "Credit note {} on invoice {}: {}",
+            note.credit_note_number, invoice.invoice_number, note.reason
+        ),
+        &invoice.currency,
+    )
+    .debit(&accounts.credit_notes, amount)
+    .credit(&accounts.accounts_receivable, amount)
+}
+
+/// A row of `agency_expenses`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AgencyExpense {
+    pub id: String,
+    pub name: String,
+    pub category: String,
+    pub expense_date: NaiveDate,
+    pub amount_cents: i64,
+    pub currency: String,
+}
+
+pub fn expense_entry(expense: &AgencyExpense, accounts: &AccountMapping) -> JournalEntry {
+    JournalEntry::new(
+        expense.expense_date,
+        "expense",
+        &expense.id,
+        expense.name.clone(),
+        format!("{} ({})", expense.name, expense.category),
+        &expense.currency,
+    )
+    .debit(
+        accounts.expense_account(&expense.category),
+        expense.amount_cents,
+    )
+    .credit(&accounts.bank, expense.amount_cents)
+}
+
+/// A paid payout: the balance drops by the amount, the bank receives it net
+/// of the platform fee.
+pub fn payout_entry(
+    payout: &AgencyPayoutRequest,
+    date: NaiveDate,
+    fee_cents: i64,
+    accounts: &AccountMapping,
+) -> JournalEntry {
+    JournalEntry::new(
+        date,
+        "payout",
+        &payout.id,
+        payout
+            .stripe_payout_id
+            .clone()
+            .or_else(|| payout.stripe_transfer_id.clone())
+            .unwrap_or_else(|| payout.id.clone()),
+        format!("Payout to bank ({})", payout.payout_method),
+        &payout.currency,
+    )
+    .debit(&accounts.bank, payout.amount_cents - fee_cents)
+    .debit(&accounts.payout_fees, fee_cents)
+    .credit(&accounts.platform_balance, payout.amount_cents)
+}
+
+/// Orders entries the same way on every export.
+pub fn sort_entries(entries: &mut [JournalEntry]) {
+    let rank = |source: &str| match source {
+        "invoice" => 0,
+        "credit_note" => 1,
+        "payment" => 2,
+        "expense" => 3,
+        _ => 4,
+    };
+    entries.sort_by(|a, b| {
+        (a.date, rank(&a.source), &a.reference, &a.source_id).cmp(&(
+            b.date,
+            rank(&b.source),
+            &b.reference,
+            &b.source_id,
+        ))
+    });
+}
+
+/// Cents as a plain decimal amount, e.g. `-1234.50`.
+pub(crate) fn amount(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let abs = cents.unsigned_abs();
+    format!("{sign}{}.{:02}", abs / 100, abs % 100)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub(crate) fn csv_row(fields: &[&str]) -> String {
+    let mut row = fields
+        .iter()
+        .map(|f| csv_field(f))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push_str("\r\n");
+    row
+}
+
+/// IIF is tab separated with no quoting.
+fn iif_field(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ").replace('"', "'")
+}
+
+pub fn render(format: ExportFormat, entries: &[JournalEntry], accounts: &AccountMapping) -> String {
+    match format {
+        ExportFormat::Journal => render_journal(entries),
+        ExportFormat::QuickBooks => render_iif(entries),
+        ExportFormat::Xero => render_xero(entries, accounts),
+    }
+}
+
+fn render_journal(entries: &[JournalEntry]) -> String {
+    let mut out = csv_row(&[
+        "date",
+        "entry",
+        "source",
+        "reference",
+        "account",
+        "memo",
+        "debit",
+        "credit",
+        "currency",
+    ]);
+    for (n, entry) in entries.iter().enumerate() {
+        let date = entry.date.to_string();
+        let number = (n + 1).to_string();
+        for line in &entry.lines {
+            let cell = |cents: i64| {
+                if cents == 0 {
+                    String::new()
+                } else {
+                    amount(cents)
+                }
+            };
+            let (debit, credit) = (cell(line.debit_cents), cell(line.credit_cents));
+            out.push_str(&csv_row(&[
+                &date,
+                &number,
+                &entry.source,
+                &entry.reference,
+                &line.account,
+                &entry.memo,
+                &debit,
+                &credit,
+                &entry.currency,
+            ]));
+        }
+    }
+    out
+}
+
+/// QuickBooks Desktop general journal transactions: the first line of each
+/// entry is the TRNS row, the rest are SPL rows. Debits are positive.
+fn render_iif(entries: &[JournalEntry]) -> String {
+    let mut out = String::from(
+        "!TRNS\tTRNSTYPE\tDATE\tACCNT\tAMOUNT\tDOCNUM\tMEMO\r\n\
+         !SPL\tTRNSTYPE\tDATE\tACCNT\tAMOUNT\tDOCNUM\tMEMO\r\n\
+         !ENDTRNS\r\n",
+    );
+    for entry in entries {
+        let date = entry.date.format("%m/%d/%Y").to_string();
+        for (i, line) in entry.lines.iter().enumerate() {
+            out.push_str(&format!(
+                "{}\tGENERAL JOURNAL\t{date}\t{}\t{}\t{}\t{}\r\n",
+                if i == 0 { "TRNS" } else { "SPL" },
+                iif_field(&line.account),
+                amount(line.debit_cents - line.credit_cents),
+                iif_field(&entry.reference),
+                iif_field(&entry.memo),
+            ));
+        }
+        out.push_str("ENDTRNS\r\n");
+    }
+    out
+}
+
+/// Xero manual journal import: one row per line, debits positive, with the
+/// entry's reference and memo as the narration.
+fn render_xero(entries: &[JournalEntry], accounts: &AccountMapping) -> String {
+    let mut out = csv_row(&[
+        "*Narration",
+        "*Date",
+        "Description",
+        "*AccountCode",
+        "*TaxRate",
+        "*Amount",
+    ]);
+    for entry in entries {
+        let narration = format!("{} {}", entry.reference, entry.memo);
+        let date = entry.date.format("%d/%m/%Y").to_string();
+        for line in &entry.lines {
+            out.push_str(&csv_row(&[
+                &narration,
+                &date,
+                &entry.memo,
+                &line.account,
+                &accounts.xero_tax_rate,
+                &amount(line.debit_cents - line.credit_cents),
+            ]));
+        }
+    }
+    out
+}
+
+async fn load_accounts(state: &AppState, agency_id: &str) -> Result<AccountMapping, RepoError> {
+    #[derive(Deserialize)]
+    struct Row {
+        accounts: AccountMapping,
+    }
+    let rows: Vec<Row> = fetch(
+        state
+            .pg
+            .from(SETTINGS_TABLE)
+            .select("accounts")
+            .eq("agency_id", agency_id),
+    )
+    .await?;
+    Ok(rows
+        .into_iter()
+        .next()
+        .map(|r| r.accounts)
+        .unwrap_or_default())
+}
+
+/// GET /api/accounting/accounts
+pub async fn get_accounts(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> AppResult<Json<AccountMapping>> {
+    Ok(Json(load_accounts(&state, &user.id).await?))
+}
+
+/// PUT /api/accounting/accounts: replaces the mapping; omitted accounts take
+/// their defaults. Closed periods keep the mapping they were exported with.
+pub async fn update_accounts(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(accounts): Json<AccountMapping>,
+) -> AppResult<Json<AccountMapping>> {
+    accounts.validate()?;
+    let before = load_accounts(&state, &user.id).await?;
+    let body = json!({
+        "agency_id": user.id,
+        "accounts": accounts,
+        "updated_at": Utc::now().to_rfc3339(),
+    });
+    let _: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from(SETTINGS_TABLE)
+            .upsert(body.to_string())
+            .on_conflict("agency_id"),
+    )
+    .await?;
+    audit::record(
+        &state,
+        AuditEvent::new(
+            &user.id,
+            "accounting_accounts.updated",
+            SETTINGS_TABLE,
+            &user.id,
+        )
+        .actor(&user)
+        .title("Accounting export accounts updated")
+        .change(&before, &accounts),
+    )
+    .await;
+    Ok(Json(accounts))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// A stored export of a closed period.
+#[derive(Debug, Deserialize)]
+struct ClosedPeriod {
+    accounts: AccountMapping,
+    entries: Vec<JournalEntry>,
+}
+
+/// Builds the period's entries from the current records.
+pub async fn build_entries(
+    state: &AppState,
+    agency_id: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    accounts: &AccountMapping,
+) -> Result<Vec<JournalEntry>, RepoError> {
+    let mut entries = Vec::new();
+
+    let filter = InvoiceFilter {
+        status: None,
+        date_start: Some(from.to_string()),
+        date_end: Some(to.to_string()),
+    };
+    let invoices = state
+        .repos
+        .invoices
+        .list_for_agency(agency_id, &filter)
+        .await?;
+    entries.extend(
+        invoices
+            .iter()
+            .filter(|i| !matches!(i.status, InvoiceStatus::Draft | InvoiceStatus::Void))
+            .map(|i| invoice_entry(i, accounts)),
+    );
+
+    let payments: Vec<InvoicePayment> = fetch(
+        state
+            .pg
+            .from("agency_invoice_payments")
+            .select("*")
+            .eq("agency_id", agency_id)
+            .gte("paid_on", from.to_string())
+            .lte("paid_on", to.to_string()),
+    )
+    .await?;
+    let credit_notes: Vec<InvoiceCreditNote> = fetch(
+        state
+            .pg
+            .from("agency_invoice_credit_notes")
+            .select("*")
+            .eq("agency_id", agency_id)
+            .gte("issued_on", from.to_string())
+            .lte("issued_on", to.to_string()),
+    )
+    .await?;
+    let mut invoice_ids: Vec<&str> = payments
+        .iter()
+        .map(|p| p.invoice_id.as_str())
+        .chain(credit_notes.iter().map(|c| c.invoice_id.as_str()))
+        .collect();
+    invoice_ids.sort_unstable();
+    invoice_ids.dedup();
+    let refs: HashMap<String, InvoiceRef> = if invoice_ids.is_empty() {
+        HashMap::new()
+    } else {
+        let rows: Vec<InvoiceRef> = fetch(
+            state
+                .pg
+                .from("agency_invoices")
+                .select("id,invoice_number,currency")
+                .eq("agency_id", agency_id)
+                .in_("id", invoice_ids),
+        )
+        .await?;
+        rows.into_iter().map(|r| (r.id.clone(), r)).collect()
+    };
+    for payment in &payments {
+        if let Some(invoice) = refs.get(&payment.invoice_id) {
+            entries.push(payment_entry(payment, invoice, accounts));
+        }
+    }
+    for note in &credit_notes {
+        if let Some(invoice) = refs.get(&note.invoice_id) {
+            entries.push(credit_note_entry(note, invoice, accounts));
+        }
+    }
+
+    let expenses: Vec<AgencyExpense> = fetch(
+        state
+            .pg
+            .from("agency_expenses")
+            .select("id,name,category,expense_date,amount_cents,currency")
+            .eq("agency_id", agency_id)
+            .in_("status", ["approved", "reimbursed"])
+            .gte("expense_date", from.to_string())
+            .lte("expense_date", to.to_string()),
+    )
+    .await?;
+    entries.extend(expenses.iter().map(|e| expense_entry(e, accounts)));
+
+    // Payouts are dated by when they were paid out.
+    let next_day = to + Days::new(1);
+    let payouts: Vec<AgencyPayoutRequest> = fetch(
+        state
+            .pg
+            .from("agency_payout_requests")
+            .select("id,agency_id,amount_cents,currency,payout_method,status,requested_at,processed_at,stripe_transfer_id,stripe_payout_id,failure_reason,fee_cents,net_cents,fee_schedule_version")
+            .eq("agency_id", agency_id)
+            .eq("status", "paid")
+            .gte("processed_at", from.to_string())
+            .lt("processed_at", next_day.to_string()),
+    )
+    .await?;
+    for payout in &payouts {
+        let date = payout
+            .processed_at
+            .map(|at: DateTime<Utc>| at.date_naive())
+            .unwrap_or(to);
+        let fee =
+            crate::payouts::agency_payout_fee_cents(state, payout.fee_cents, payout.amount_cents);
+        entries.push(payout_entry(payout, date, fee, accounts));
+    }
+
+    sort_entries(&mut entries);
+    Ok(entries)
+}
+
+/// The stored entries for a closed period, storing them on first export.
+async fn closed_period(
+    state: &AppState,
+    user: &AuthUser,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> AppResult<(AccountMapping, Vec<JournalEntry>)> {
+    let stored = || async {
+        let rows: Vec<ClosedPeriod> = fetch(
+            state
+                .pg
+                .from(EXPORTS_TABLE)
+                .select("accounts,entries")
+                .eq("agency_id", &user.id)
+                .eq("period_start", from.to_string())
+                .eq("period_end", to.to_string()),
+        )
+        .await?;
+        Ok::<_, RepoError>(rows.into_iter().next())
+    };
+    if let Some(period) = stored().await? {
+        return Ok((period.accounts, period.entries));
+    }
+
+    let accounts = load_accounts(state, &user.id).await?;
+    let entries = build_entries(state, &user.id, from, to, &accounts).await?;
+    let row = json!({
+        "agency_id": user.id,
+        "period_start": from.to_string(),
+        "period_end": to.to_string(),
+        "accounts": accounts,
+        "entries": entries,
+        "entry_count": entries.len(),
+        "created_by": user.id,
+    });
+    let inserted: Result<Vec<serde_json::Value>, RepoError> =
+        fetch(state.pg.from(EXPORTS_TABLE).insert(row.to_string())).await;
+    match inserted {
+        Ok(rows) => {
+            let id = rows
+                .first()
+                .and_then(|r| r.get("id"))
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            audit::record(
+                state,
+                AuditEvent::new(&user.id, "accounting_period.closed", EXPORTS_TABLE, id)
+                    .actor(user)
+                    .title(format!("Accounting period {from} to {to} closed")),
+            )
+            .await;
+            Ok((accounts, entries))
+        }
+        // Another export stored the period first; use its copy.
+        Err(RepoError::Db { status: 409, .. }) => match stored().await? {
+            Some(period) => Ok((period.accounts, period.entries)),
+            None => Err(AppError::Conflict("export_in_progress".to_string())),
+        },
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// GET /api/accounting/export?format=journal|iif|xero&from=&to=: the
+/// period's journal as a file. Periods ending before today are closed and
+/// always export the same file; the current period is built live.
+pub async fn export(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(q): Query<ExportQuery>,
+) -> AppResult<Response> {
+    let format = ExportFormat::parse(q.format.as_deref().unwrap_or("journal").trim())
+        .ok_or_else(|| AppError::BadRequest("invalid_format".to_string()))?;
+    let parse = |value: Option<&str>| {
+        value
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| {
+                NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d")
+                    .map_err(|_| AppError::BadRequest("invalid_date_range".to_string()))
+            })
+            .transpose()
+    };
+    let today = Utc::now().date_naive();
+    let to = parse(q.to.as_deref())?.unwrap_or(today);
+    let from = parse(q.from.as_deref())?.unwrap_or_else(|| to.with_day(1).unwrap_or(to));
+    if from > to {
+        return Err(AppError::BadRequest("invalid_date_range".to_string()));
+    }
+
+    let (accounts, entries) = if to < today {
+        closed_period(&state, &user, from, to).await?
+    } else {
+        let accounts = load_accounts(&state, &user.id).await?;
+        let entries = build_entries(&state, &user.id, from, to, &accounts).await?;
+        (accounts, entries)
+    };
+    let body = render(format, &entries, &accounts);
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", format.file_name(from, to)),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice() -> Invoice {
+        Invoice {
+            status: InvoiceStatus::Sent,
+            bill_to_company: "Acme, Inc.".into(),
+            currency: "usd".into(),
+            tax_rate_bps: 1000,
+            discount_cents: 1000,
+            subtotal_cents: 110000,
+            expenses_cents: 5000,
+            tax_cents: 10900,
+            total_cents: 124900,
+            agency_fee_cents: 22000,
+            talent_net_cents: 88000,
+            ..Invoice::fixture()
+        }
+    }
+
+    #[test]
+    fn invoice_entries_balance_with_the_discount_on_its_own_account() {
+        let accounts = AccountMapping::default();
+        let entry = invoice_entry(&invoice(), &accounts);
+        assert!(entry.is_balanced());
+        let line = |account: &str| {
+            entry
+                .lines
+                .iter()
+                .find(|l| l.account == account)
+                .map(|l| l.debit_cents - l.credit_cents)
+        };
+        assert_eq!(line("Accounts Receivable"), Some(124900));
+        assert_eq!(line("Discounts and Adjustments"), Some(1000));
+        assert_eq!(line("Commission Income"), Some(-22000));
+        assert_eq!(line("Talent Payable"), Some(-88000));
+        assert_eq!(line("Sales Tax Payable"), Some(-10900));
+        assert_eq!(entry.currency, "USD");
+    }
+
+    #[test]
+    fn renders_each_format() {
+        let accounts = AccountMapping::default();
+        let entries = vec![invoice_entry(&invoice(), &accounts)];
+
+        let journal = render(ExportFormat::Journal, &entries, &accounts);
+        let mut rows = journal.lines();
+        assert_eq!(
+            rows.next(),
+            Some("date,entry,source,reference,account,memo,debit,credit,currency")
+        );
+        assert_eq!(
+            rows.next(),
+            Some("2026-09-01,1,invoice,INV-1,Accounts Receivable,\"Invoice INV-1 to Acme, Inc.\",1249.00,,USD")
+        );
+
+        let iif = render(ExportFormat::QuickBooks, &entries, &accounts);
+        let rows: Vec<&str> = iif.lines().collect();
+        assert_eq!(
+            rows[3],
+            "TRNS\tGENERAL JOURNAL\t09/01/2026\tAccounts Receivable\t1249.00\tINV-1\tInvoice INV-1 to Acme, Inc."
+        );
+        assert!(rows[5].starts_with("SPL\tGENERAL JOURNAL\t09/01/2026\tCommission Income\t-220.00"));
+        assert_eq!(rows.last(), Some(&"ENDTRNS"));
+
+        let xero = render(ExportFormat::Xero, &entries, &accounts);
+        assert!(xero.lines().nth(1).unwrap().ends_with(
+            ",01/09/2026,\"Invoice INV-1 to Acme, Inc.\",Accounts Receivable,Tax Exempt,1249.00"
+        ));
+    }
+}
@@ -1,4 +1,9 @@
-use crate::{auth::AuthUser, config::AppState, errors::sanitize_db_error};
+use crate::{
+    auth::AuthUser,
+    config::AppState,
+    errors::sanitize_db_error,
+    storage::{Bucket, StorageError},
+};
 use axum::extract::Multipart;
 use axum::extract::Query;
 use axum::{extract::Path, extract::State, http::StatusCode, Json};
@@ -572,38 +577,7 @@ pub async fn get_agency_storage_file_signed_url(
         "missing storage_path".into(),
     ))?;
 
-    let url = format!(
-        "{}/storage/v1/object/sign/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let body = serde_json::json!({ "expiresIn": 3600 });
-    let http = reqwest::Client::new();
-    let sign_resp = http
-        .post(&url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-
-    if !sign_resp.status().is_success() {
-        let msg = sign_resp.text().await.unwrap_or_default();
-        return Err((StatusCode::BAD_GATEWAY, format!("sign url failed: {msg}")));
-    }
-
-    let signed_json: serde_json::Value = sign_resp
-        .json()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    let signed_path = signed_json
-        .get("signedURL")
-        .and_then(|v| v.as_str())
-        .ok_or((StatusCode::BAD_GATEWAY, "invalid sign response".into()))?;
-    let full_url = format!("{}/storage/v1{}", state.supabase_url, signed_path);
+    let full_url = state.storage.signed_url(bucket, path, 3600).await?;
     Ok(Json(serde_json::json!({ "url": full_url })))
 }
 
@@ -662,18 +636,9 @@ pub async fn upload_agency_storage_file(
     }
 
     let fname = file_name.unwrap_or_else(|| "upload.bin".to_string());
-    let sanitized = fname
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>();
+    let sanitized = crate::storage::sanitize_file_name(&fname);
 
-    let bucket = state.supabase_bucket_private.clone();
+    let bucket = state.bucket(Bucket::Private);
     let folder_segment = folder_id.clone().unwrap_or_else(|| "root".to_string());
     let path = format!(
         "agencies/{}/storage/{}/{}_{}",
@@ -683,33 +648,21 @@ pub async fn upload_agency_storage_file(
         sanitized
     );
 
-    let storage_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::new();
-    let mut req = http
-        .post(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
+    let mime_type = Some(crate::storage::sniff_content_type(
+        &bytes,
+        mime_type.as_deref(),
+        Some(&fname),
+    ));
+    state
+        .storage
+        .put(
+            &bucket,
+            &path,
+            bytes.into(),
+            mime_type.as_deref().unwrap_or_default(),
+            false,
         )
-        .header("apikey", state.supabase_service_key.clone());
-    if let Some(ct) = mime_type.as_ref().filter(|s| !s.is_empty()) {
-        req = req.header("content-type", ct.clone());
-    }
-    let up = req
-        .body(bytes)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    if !up.status().is_success() {
-        let msg = up.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("storage upload failed: {msg}"),
-        ));
-    }
+        .await?;
 
     let public_url = None;
     let insert = serde_json::json!({
@@ -748,7 +701,7 @@ pub async fn upload_agency_storage_file(
         id,
         file_name: fname,
         public_url,
-        storage_bucket: state.supabase_bucket_private.clone(),
+        storage_bucket: state.bucket(Bucket::Private),
         storage_path: insert
             .get("storage_path")
             .and_then(|v| v.as_str())
@@ -808,28 +761,10 @@ pub async fn delete_agency_storage_file(
         ))?
         .to_string();
 
-    // 2) Delete object from Supabase Storage
-    let storage_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::new();
-    let del = http
-        .delete(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    if !del.status().is_success() {
-        let msg = del.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("storage delete failed: {msg}"),
-        ));
+    // 2) Delete object from storage; an already-missing object is not an error.
+    match state.storage.delete(&bucket, &path).await {
+        Ok(()) | Err(StorageError::NotFound) => {}
+        Err(e) => return Err(e.into()),
     }
 
     // 3) Delete metadata row
@@ -884,19 +819,10 @@ pub async fn upload_agency_file(
         return Err((StatusCode::BAD_REQUEST, "missing file".into()));
     }
     let fname = file_name.unwrap_or_else(|| "upload.bin".to_string());
-    let sanitized = fname
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>();
+    let sanitized = crate::storage::sanitize_file_name(&fname);
 
     // Storage target
-    let bucket = state.supabase_bucket_private.clone();
+    let bucket = state.bucket(Bucket::Private);
     let path = format!(
         "agencies/{}/files/{}_{}",
         user.id,
@@ -904,30 +830,11 @@ pub async fn upload_agency_file(
         sanitized
     );
 
-    // Upload to Supabase Storage using service key
-    let storage_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::new();
-    let up = http
-        .post(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .body(bytes)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    if !up.status().is_success() {
-        let msg = up.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("storage upload failed: {msg}"),
-        ));
-    }
+    let content_type = crate::storage::sniff_content_type(&bytes, None, Some(&fname));
+    state
+        .storage
+        .put(&bucket, &path, bytes.into(), &content_type, false)
+        .await?;
 
     // For private bucket, no public URL; keep None
     let public_url = None;
@@ -1233,41 +1140,7 @@ pub async fn get_client_file_signed_url(
         "missing storage_path".into(),
     ))?;
 
-    // 2. Request signed URL from Supabase Storage
-    let url = format!(
-        "{}/storage/v1/object/sign/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let body = serde_json::json!({ "expiresIn": 3600 }); // 1 hour
-    let http = reqwest::Client::new();
-    let sign_resp = http
-        .post(&url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-
-    if !sign_resp.status().is_success() {
-        let msg = sign_resp.text().await.unwrap_or_default();
-        return Err((StatusCode::BAD_GATEWAY, format!("sign url failed: {msg}")));
-    }
-
-    let signed_json: serde_json::Value = sign_resp
-        .json()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-
-    let signed_path = signed_json
-        .get("signedURL")
-        .and_then(|v| v.as_str())
-        .ok_or((StatusCode::BAD_GATEWAY, "invalid sign response".into()))?;
-
-    let full_url = format!("{}/storage/v1{}", state.supabase_url, signed_path);
+    let full_url = state.storage.signed_url(bucket, path, 3600).await?;
 
     Ok(Json(serde_json::json!({ "url": full_url })))
 }
@@ -1301,19 +1174,10 @@ pub async fn upload_client_file(
         return Err((StatusCode::BAD_REQUEST, "missing file".into()));
     }
     let fname = file_name.unwrap_or_else(|| "upload.bin".to_string());
-    let sanitized = fname
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>();
+    let sanitized = crate::storage::sanitize_file_name(&fname);
 
     // Storage target
-    let bucket = state.supabase_bucket_private.clone();
+    let bucket = state.bucket(Bucket::Private);
     let path = format!(
         "agencies/{}/clients/{}/files/{}_{}",
         user.id,
@@ -1322,30 +1186,11 @@ pub async fn upload_client_file(
         sanitized
     );
 
-    // Upload to Supabase Storage using service key
-    let storage_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::new();
-    let up = http
-        .post(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .body(bytes)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    if !up.status().is_success() {
-        let msg = up.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("storage upload failed: {msg}"),
-        ));
-    }
+    let content_type = crate::storage::sniff_content_type(&bytes, None, Some(&fname));
+    state
+        .storage
+        .put(&bucket, &path, bytes.into(), &content_type, false)
+        .await?;
 
     let public_url = None;
 
@@ -1615,7 +1460,7 @@ pub async fn delete_talent_asset(
     let file_resp = state
         .pg
         .from("agency_files")
-        .select("storage_path")
+        .select("storage_bucket,storage_path")
         .eq("id", &asset_id)
         .eq("talent_id", &talent_id)
         .single()
@@ -1642,27 +1487,17 @@ pub async fn delete_talent_asset(
         )
     })?;
 
-    // 3. Delete the file from Supabase Storage.
-    let storage_url = format!("{}/storage/v1/object/{}", state.supabase_url, storage_path);
-    let http = reqwest::Client::new();
-    let delete_resp = http
-        .delete(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-
-    if !delete_resp.status().is_success() {
+    // 3. Delete the file from storage.
+    let storage_bucket = file_json["storage_bucket"]
+        .as_str()
+        .map(String::from)
+        .unwrap_or_else(|| state.bucket(Bucket::Public));
+    if let Err(e) = state.storage.delete(&storage_bucket, storage_path).await {
         // Log the error but proceed to delete the DB record anyway.
         // It's better to have a dangling DB record than an orphaned file.
-        let error_body = delete_resp.text().await.unwrap_or_default();
         tracing::error!(
             "Failed to delete file from storage: {}. Path: {}",
-            error_body,
+            e,
             storage_path
         );
     }
@@ -1738,19 +1573,10 @@ pub async fn upload_talent_asset(
     }
 
     let fname = file_name.unwrap_or_else(|| "upload.bin".to_string());
-    let sanitized = fname
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>();
+    let sanitized = crate::storage::sanitize_file_name(&fname);
 
     // 3. Storage target (PUBLIC bucket for packages)
-    let bucket = state.supabase_bucket_public.clone();
+    let bucket = state.bucket(Bucket::Public);
     let path = format!(
         "agencies/{}/talents/{}/assets/{}_{}",
         user.id,
@@ -1759,40 +1585,15 @@ pub async fn upload_talent_asset(
         sanitized
     );
 
-    // 4. Upload to Supabase Storage
-    let storage_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::new();
-    let up = http
-        .post(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .header(
-            "content-type",
-            content_type.unwrap_or_else(|| "application/octet-stream".to_string()),
-        )
-        .body(bytes)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-
-    if !up.status().is_success() {
-        let msg = up.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("storage upload failed: {msg}"),
-        ));
-    }
+    // 4. Upload to storage
+    let content_type =
+        crate::storage::sniff_content_type(&bytes, content_type.as_deref(), Some(&fname));
+    state
+        .storage
+        .put(&bucket, &path, bytes.into(), &content_type, false)
+        .await?;
 
-    let public_url = format!(
-        "{}/storage/v1/object/public/{}/{}",
-        state.supabase_url, bucket, path
-    );
+    let public_url = state.storage.public_url(&bucket, &path);
 
     // 5. Insert row into agency_files
     let insert = serde_json::json!({
@@ -1,4 +1,4 @@
-use crate::email::send_email_core_with_from_name;
+use crate::email::{enqueue, OutgoingEmail};
 use crate::errors::sanitize_db_error;
 use crate::{
     auth::{AuthUser, RoleGuard},
@@ -208,20 +208,17 @@ pub async fn share_comp_card(
                 agency = safe_agency_name,
             );
 
-            match send_email_core_with_from_name(
-                &state,
-                &email,
-                &subject,
-                &body,
-                true,
-                None,
-                Some(&from_name),
-            )
-            .await
-            {
-                Ok(()) => sent += 1,
-                Err((code, err)) => {
-                    failed.push(json!({"error":"send_failed","status":code.as_u16(),"detail":err,"to":email}));
+            let outgoing = OutgoingEmail::new(&email, &subject, &body)
+                .html(true)
+                .from_name(Some(&from_name))
+                .category("comp_card")
+                .agency(&user.id)
+                .client(r.get("id").and_then(|v| v.as_str()));
+            match enqueue(&state, outgoing).await {
+                Ok(_) => sent += 1,
+                Err(e) => {
+                    let (code, err) = e.into_parts();
+                    failed.push(json!({"error":"send_failed","status":code.as_u16(),"detail":err.0["error"],"to":email}));
                 }
             }
         }
@@ -1650,6 +1650,20 @@ pub async fn create_talent(
         "status": "ok"
     });
 
+    crate::audit::record(
+        &state,
+        crate::audit::AuditEvent::new(
+            &effective_agency_id,
+            "talent.added",
+            "agency_users",
+            &talent_id,
+        )
+        .actor(&user)
+        .title(format!("{} added to roster", payload.full_name))
+        .change(&(), &identity_payload),
+    )
+    .await;
+
     tracing::info!("Successfully created talent: {}", payload.full_name);
 
     Ok(Json(inserted_val))
@@ -1788,6 +1802,16 @@ pub async fn update_talent(
         }
     }
 
+    let before: Vec<serde_json::Value> = crate::repositories::fetch(
+        state
+            .pg
+            .from("agency_users")
+            .select("*")
+            .eq("id", &id)
+            .eq("role", "talent"),
+    )
+    .await?;
+
     let resp = state
         .pg
         .from("agency_users")
@@ -1806,6 +1830,19 @@ pub async fn update_talent(
     let updated_text = resp.text().await.unwrap_or_default();
     let updated_val: serde_json::Value = serde_json::from_str(&updated_text).unwrap_or(json!([]));
     if let Some(first) = updated_val.as_array().and_then(|arr| arr.first()) {
+        crate::audit::record(
+            &state,
+            crate::audit::AuditEvent::new(
+                &effective_agency_id,
+                "talent.updated",
+                "agency_users",
+                &id,
+            )
+            .actor(&user)
+            .title("Talent profile updated")
+            .change(&before.first(), first),
+        )
+        .await;
         let creator_id = first.get("creator_id").and_then(|v| v.as_str());
         let next_status = payload
             .status
@@ -651,8 +651,11 @@ pub async fn create_for_agency(
     let body = lines.join("\n");
 
     // Best-effort email send
-    let _ =
-        crate::email::send_plain_text_email(&state, &email, &subject, &body, Some(&agency_name));
+    let outgoing = crate::email::OutgoingEmail::new(&email, &subject, &body)
+        .from_name(Some(&agency_name))
+        .category("talent_invite")
+        .agency(&user.id);
+    let _ = crate::email::enqueue(&state, outgoing).await;
 
     Ok(Json(json!({
         "status": "ok",
@@ -17,6 +17,7 @@ pub struct AnalyticsDashboard {
     pub ai_usage: AIUsageMetrics,
     pub monthly_trends: Vec<MonthlyTrend>,
     pub consent_status: ConsentStatusBreakdown,
+    pub receivables: ReceivablesMetrics,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -88,6 +89,83 @@ pub struct ConsentStatusBreakdown {
     pub verified: i64,
 }
 
+/// Invoice balances still to be collected, and what came in over the last 30
+/// days. Balances are net of payments and credit notes.
+#[derive(Debug, Serialize, Default)]
+pub struct ReceivablesMetrics {
+    pub outstanding_cents: i64,
+    pub outstanding_formatted: String,
+    pub overdue_cents: i64,
+    pub partially_paid_invoices: i64,
+    pub collected_30d_cents: i64,
+    pub credited_30d_cents: i64,
+}
+
+async fn fetch_rows(
+    builder: postgrest::Builder,
+) -> Result<Vec<serde_json::Value>, (StatusCode, String)> {
+    let resp = builder
+        .execute()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let text = resp.text().await.unwrap_or_else(|_| "[]".to_string());
+    Ok(serde_json::from_str(&text).unwrap_or_default())
+}
+
+async fn receivables(
+    state: &AppState,
+    agency_id: &str,
+    since: &str,
+) -> Result<ReceivablesMetrics, (StatusCode, String)> {
+    let invoices = fetch_rows(
+        state
+            .pg
+            .from("agency_invoices")
+            .select("status,total_cents,amount_paid_cents,amount_credited_cents")
+            .eq("agency_id", agency_id)
+            .in_("status", vec!["sent", "partially_paid", "overdue"]),
+    )
+    .await?;
+    let payments = fetch_rows(
+        state
+            .pg
+            .from("agency_invoice_payments")
+            .select("amount_cents")
+            .eq("agency_id", agency_id)
+            .gte("paid_on", &since[..10]),
+    )
+    .await?;
+    let credit_notes = fetch_rows(
+        state
+            .pg
+            .from("agency_invoice_credit_notes")
+            .select("amount_cents")
+            .eq("agency_id", agency_id)
+            .gte("issued_on", &since[..10]),
+    )
+    .await?;
+
+    let cents =
+        |row: &serde_json::Value, key: &str| row.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+    let mut metrics = ReceivablesMetrics::default();
+    for inv in &invoices {
+        let balance = (cents(inv, "total_cents")
+            - cents(inv, "amount_paid_cents")
+            - cents(inv, "amount_credited_cents"))
+        .max(0);
+        metrics.outstanding_cents += balance;
+        match inv.get("status").and_then(|v| v.as_str()) {
+            Some("overdue") => metrics.overdue_cents += balance,
+            Some("partially_paid") => metrics.partially_paid_invoices += 1,
+            _ => {}
+        }
+    }
+    metrics.outstanding_formatted = format_currency(metrics.outstanding_cents);
+    metrics.collected_30d_cents = payments.iter().map(|p| cents(p, "amount_cents")).sum();
+    metrics.credited_30d_cents = credit_notes.iter().map(|c| cents(c, "amount_cents")).sum();
+    Ok(metrics)
+}
+
 /// GET /api/agency/analytics/dashboard
 pub async fn get_analytics_dashboard(
     State(state): State<AppState>,
@@ -326,6 +404,7 @@ pub async fn get_analytics_dashboard(
                 total: ai_total_talents,
                 verified: ai_verified_count,
             },
+            receivables: receivables(&state, agency_id, &thirty_days_ago).await?,
         }));
     }
 
@@ -657,6 +736,7 @@ pub async fn get_analytics_dashboard(
             total: total_talents,
             verified: verified_count,
         },
+        receivables: receivables(&state, agency_id, &thirty_days_ago).await?,
     }))
 }
 
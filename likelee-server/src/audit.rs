@@ -0,0 +1,251 @@
+//! Audit trail for agency mutations, stored in `activity_events`.
+//!
+//! Handlers describe a change with [`AuditEvent`] and hand it to [`record`]
+//! once the mutation has succeeded. Each row carries the actor, the subject
+//! (`subject_table` / `subject_id`), a field-level diff and the request id
+//! assigned by `errors::request_context`, so a support ticket quoting a
+//! request id leads straight to the change it made.
+
+use crate::auth::{AuthUser, RoleGuard};
+use crate::config::AppState;
+use crate::errors::{current_request_id, AppResult};
+use crate::repositories::{fetch, RepoError};
+use axum::extract::{Query, State};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tracing::warn;
+
+const TABLE: &str = "activity_events";
+
+const COLUMNS: &str = "id,agency_id,type,subject_table,subject_id,title,subtitle,actor_id,actor_role,changes,request_id,created_at";
+
+/// Bookkeeping columns that change on every write and say nothing about it.
+const IGNORED_FIELDS: [&str; 2] = ["created_at", "updated_at"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEvent {
+    pub id: String,
+    pub agency_id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub subject_table: Option<String>,
+    pub subject_id: Option<String>,
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    pub actor_id: Option<String>,
+    pub actor_role: Option<String>,
+    /// `{ field: { "before": .., "after": .. } }` for every changed field.
+    pub changes: Option<Value>,
+    pub request_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One audited change, e.g.
+/// `AuditEvent::new(agency, "booking.cancelled", "bookings", id).actor(&user).change(&old, &new)`.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    agency_id: String,
+    event_type: String,
+    subject_table: String,
+    subject_id: String,
+    title: Option<String>,
+    subtitle: Option<String>,
+    actor_id: Option<String>,
+    actor_role: Option<String>,
+    before: Value,
+    after: Value,
+}
+
+impl AuditEvent {
+    pub fn new(agency_id: &str, event_type: &str, subject_table: &str, subject_id: &str) -> Self {
+        Self {
+            agency_id: agency_id.to_string(),
+            event_type: event_type.to_string(),
+            subject_table: subject_table.to_string(),
+            subject_id: subject_id.to_string(),
+            title: None,
+            subtitle: None,
+            actor_id: None,
+            actor_role: None,
+            before: Value::Null,
+            after: Value::Null,
+        }
+    }
+
+    pub fn actor(mut self, user: &AuthUser) -> Self {
+        self.actor_id = Some(user.id.clone());
+        self.actor_role = Some(user.role.clone());
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn subtitle(mut self, subtitle: impl Into<String>) -> Self {
+        self.subtitle = Some(subtitle.into());
+        self
+    }
+
+    /// Snapshots of the subject before and after the mutation. Either side
+    /// may be `()` / `Value::Null` for creations and deletions.
+    pub fn change(mut self, before: &impl Serialize, after: &impl Serialize) -> Self {
+        self.before = serde_json::to_value(before).unwrap_or(Value::Null);
+        self.after = serde_json::to_value(after).unwrap_or(Value::Null);
+        self
+    }
+}
+
+/// Field-level diff of two object snapshots. When both sides exist only
+/// fields present in both are compared, since snapshots often come from
+/// different column lists; a missing side reports every field.
+pub fn diff(before: &Value, after: &Value) -> Map<String, Value> {
+    let empty = Map::new();
+    let b = before.as_object().unwrap_or(&empty);
+    let a = after.as_object().unwrap_or(&empty);
+    let both = !b.is_empty() && !a.is_empty();
+
+    let mut keys: Vec<&String> = b.keys().chain(a.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut changes = Map::new();
+    for key in keys {
+        if IGNORED_FIELDS.contains(&key.as_str()) {
+            continue;
+        }
+        let (old, new) = (b.get(key), a.get(key));
+        if both && (old.is_none() || new.is_none()) {
+            continue;
+        }
+        if old != new {
+            changes.insert(
+                key.clone(),
+                json!({
+                    "before": old.cloned().unwrap_or(Value::Null),
+                    "after": new.cloned().unwrap_or(Value::Null),
+                }),
+            );
+        }
+    }
+    changes
+}
+
+async fn insert(state: &AppState, event: &AuditEvent) -> Result<(), RepoError> {
+    let row = json!({
+        "agency_id": event.agency_id,
+        "type": event.event_type,
+        "subject_table": event.subject_table,
+        "subject_id": event.subject_id,
+        "title": event.title,
+        "subtitle": event.subtitle,
+        "actor_id": event.actor_id,
+        "actor_role": event.actor_role,
+        "changes": diff(&event.before, &event.after),
+        "request_id": current_request_id(),
+    });
+    let _: Vec<Value> = fetch(state.pg.from(TABLE).insert(row.to_string())).await?;
+    Ok(())
+}
+
+/// Writes `event`. Called after the mutation succeeded, so a failure here is
+/// logged rather than turned into an error for a change that already happened.
+pub async fn record(state: &AppState, event: AuditEvent) {
+    if let Err(e) = insert(state, &event).await {
+        warn!(
+            agency_id = %event.agency_id,
+            event_type = %event.event_type,
+            subject_id = %event.subject_id,
+            error = %e,
+            "audit_event_insert_failed"
+        );
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogParams {
+    #[serde(rename = "type")]
+    pub event_type: Option<String>,
+    pub subject_table: Option<String>,
+    pub subject_id: Option<String>,
+    pub actor_id: Option<String>,
+    pub request_id: Option<String>,
+    /// Inclusive RFC3339 lower bound on `created_at`.
+    pub since: Option<DateTime<Utc>>,
+    /// Exclusive RFC3339 upper bound on `created_at`.
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// GET /api/agency/audit-events: the agency's audit trail, newest first.
+/// `?subject_table=talent_commissions&subject_id=<talent>` answers who
+/// changed a talent's commission.
+pub async fn list_events(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<AuditLogParams>,
+) -> AppResult<Json<Vec<ActivityEvent>>> {
+    RoleGuard::new(vec!["agency"]).check(&user.role)?;
+    let mut query = state
+        .pg
+        .from(TABLE)
+        .select(COLUMNS)
+        .eq("agency_id", &user.id)
+        .order("created_at.desc");
+    for (column, value) in [
+        ("type", &params.event_type),
+        ("subject_table", &params.subject_table),
+        ("subject_id", &params.subject_id),
+        ("actor_id", &params.actor_id),
+        ("request_id", &params.request_id),
+    ] {
+        if let Some(v) = value.as_deref().filter(|v| !v.is_empty()) {
+            query = query.eq(column, v);
+        }
+    }
+    if let Some(since) = params.since {
+        query = query.gte("created_at", crate::jobs::runner::ts(since));
+    }
+    if let Some(until) = params.until {
+        query = query.lt("created_at", crate::jobs::runner::ts(until));
+    }
+    let limit = params.limit.unwrap_or(50).clamp(1, 200);
+    let offset = params.offset.unwrap_or(0);
+    let rows: Vec<ActivityEvent> = fetch(query.range(offset, offset + limit - 1)).await?;
+    Ok(Json(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_reports_changed_shared_fields_only() {
+        let before =
+            json!({ "status": "draft", "total_cents": 100, "notes": null, "updated_at": "a" });
+        let after = json!({ "status": "sent", "total_cents": 100, "notes": "hi", "updated_at": "b", "extra": 1 });
+        let changes = diff(&before, &after);
+        assert_eq!(
+            Value::Object(changes),
+            json!({
+                "notes": { "before": null, "after": "hi" },
+                "status": { "before": "draft", "after": "sent" },
+            })
+        );
+    }
+
+    #[test]
+    fn diff_of_a_creation_lists_every_field() {
+        let changes = diff(
+            &Value::Null,
+            &json!({ "amount_cents": 500, "status": "pending" }),
+        );
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes["amount_cents"]["before"], Value::Null);
+        assert_eq!(changes["status"]["after"], "pending");
+    }
+}
@@ -160,7 +160,7 @@ pub async fn create_agency_subscription_checkout(
         .unwrap_or("")
         .to_string();
 
-    let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+    let client = state.stripe_client();
 
     // Create Stripe customer if missing.
     let customer_id = if !existing_customer.trim().is_empty() {
@@ -1,4 +1,7 @@
-use crate::{auth::AuthUser, config::AppState, errors::sanitize_db_error};
+use crate::{
+    auth::AuthUser, config::AppState, errors::sanitize_db_error, repositories::Booking,
+    storage::Bucket,
+};
 use axum::extract::Multipart;
 use axum::{
     extract::{Path, Query, State},
@@ -190,20 +193,10 @@ pub async fn create_with_files(
         .to_string();
 
     // Upload files and insert booking_files rows
-    let http = reqwest::Client::new();
-    let bucket = state.supabase_bucket_private.clone();
+    let bucket = state.bucket(Bucket::Private);
     let mut uploaded: Vec<serde_json::Value> = Vec::new();
     for (fname, data) in files.into_iter() {
-        let sanitized = fname
-            .chars()
-            .map(|c| {
-                if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
-                    c
-                } else {
-                    '_'
-                }
-            })
-            .collect::<String>();
+        let sanitized = crate::storage::sanitize_file_name(&fname);
         let path = format!(
             "agencies/{}/bookings/{}/files/{}_{}",
             user.id,
@@ -211,28 +204,11 @@ pub async fn create_with_files(
             chrono::Utc::now().timestamp_millis(),
             sanitized
         );
-        let storage_url = format!(
-            "{}/storage/v1/object/{}/{}",
-            state.supabase_url, bucket, path
-        );
-        let up = http
-            .post(&storage_url)
-            .header(
-                "Authorization",
-                format!("Bearer {}", state.supabase_service_key),
-            )
-            .header("apikey", state.supabase_service_key.clone())
-            .body(data)
-            .send()
-            .await
-            .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-        if !up.status().is_success() {
-            let msg = up.text().await.unwrap_or_default();
-            return Err((
-                StatusCode::BAD_GATEWAY,
-                format!("storage upload failed: {}", msg),
-            ));
-        }
+        let content_type = crate::storage::sniff_content_type(&data, None, Some(&fname));
+        state
+            .storage
+            .put(&bucket, &path, data.into(), &content_type, false)
+            .await?;
 
         let rec_body = json!({
             "booking_id": booking_id,
@@ -263,6 +239,15 @@ pub async fn create_with_files(
     for (k, v) in booking.iter() {
         out.insert(k.clone(), v.clone());
     }
+    audit_booking(
+        &state,
+        &user,
+        ("booking.created", "Booking created"),
+        &booking_id,
+        &(),
+        &out,
+    )
+    .await;
     out.insert("files".to_string(), serde_json::Value::Array(uploaded));
     Ok(Json(serde_json::Value::Object(out)))
 }
@@ -366,6 +351,21 @@ pub async fn create(
     }
     let v: serde_json::Value = serde_json::from_str(&text)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Some(created) = v.as_array().and_then(|rows| rows.first()) {
+        let id = created
+            .get("id")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        audit_booking(
+            &state,
+            &user,
+            ("booking.created", "Booking created"),
+            id,
+            &(),
+            created,
+        )
+        .await;
+    }
     Ok(Json(v))
 }
 
@@ -408,19 +408,10 @@ pub async fn upload_booking_file(
         return Err((StatusCode::BAD_REQUEST, "missing file".into()));
     }
     let fname = file_name.unwrap_or_else(|| "upload.bin".to_string());
-    let sanitized = fname
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>();
+    let sanitized = crate::storage::sanitize_file_name(&fname);
 
     // Storage target (private bucket)
-    let bucket = state.supabase_bucket_private.clone();
+    let bucket = state.bucket(Bucket::Private);
     let path = format!(
         "agencies/{}/bookings/{}/files/{}_{}",
         user.id,
@@ -429,30 +420,11 @@ pub async fn upload_booking_file(
         sanitized
     );
 
-    // Upload to Supabase Storage using service key
-    let storage_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::new();
-    let up = http
-        .post(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .body(bytes)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    if !up.status().is_success() {
-        let msg = up.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("storage upload failed: {msg}"),
-        ));
-    }
+    let content_type = crate::storage::sniff_content_type(&bytes, None, Some(&fname));
+    state
+        .storage
+        .put(&bucket, &path, bytes.into(), &content_type, false)
+        .await?;
 
     // No public URL for private bucket
     let public_url = None;
@@ -575,6 +547,15 @@ pub async fn update(
     Path(id): Path<String>,
     Json(payload): Json<UpdateBookingPayload>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let before: Vec<serde_json::Value> = crate::repositories::fetch(
+        state
+            .pg
+            .from("bookings")
+            .select("*")
+            .eq("id", &id)
+            .eq("agency_user_id", &user.id),
+    )
+    .await?;
     // Only update fields that are Some
     let mut v =
         serde_json::to_value(&payload).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
@@ -619,28 +600,54 @@ pub async fn update(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     let v: serde_json::Value = serde_json::from_str(&text)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Some(after) = v.as_array().and_then(|rows| rows.first()) {
+        audit_booking(
+            &state,
+            &user,
+            ("booking.updated", "Booking updated"),
+            &id,
+            &before.first(),
+            after,
+        )
+        .await;
+    }
     Ok(Json(v))
 }
 
+/// Records a booking change made by `user`; `before` is `()` for creations.
+async fn audit_booking(
+    state: &AppState,
+    user: &AuthUser,
+    (event_type, title): (&str, &str),
+    id: &str,
+    before: &impl serde::Serialize,
+    after: &impl serde::Serialize,
+) {
+    crate::audit::record(
+        state,
+        crate::audit::AuditEvent::new(&user.id, event_type, "bookings", id)
+            .actor(user)
+            .title(title)
+            .change(before, after),
+    )
+    .await;
+}
+
 pub async fn cancel(
     State(state): State<AppState>,
     user: AuthUser,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let resp = state
-        .pg
-        .from("bookings")
-        .eq("id", &id)
-        .eq("agency_user_id", &user.id)
-        .update(json!({"status": "cancelled", "campaign_id": null}).to_string())
-        .execute()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let text = resp
-        .text()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    Ok(Json(v))
+) -> Result<Json<Vec<Booking>>, (StatusCode, String)> {
+    let before = state.repos.bookings.get_for_agency(&user.id, &id).await?;
+    let booking = state.repos.bookings.cancel(&user.id, &id).await?;
+    audit_booking(
+        &state,
+        &user,
+        ("booking.cancelled", "Booking cancelled"),
+        &id,
+        &before,
+        &booking,
+    )
+    .await;
+    Ok(Json(vec![booking]))
 }
@@ -459,7 +459,11 @@ pub async fn create_catalog(
             catalog_url
         );
 
-        match crate::email::send_plain_email(&state, &client_email, &subject, &body) {
+        let outgoing = crate::email::OutgoingEmail::new(&client_email, &subject, &body)
+            .category("catalog")
+            .agency(&user.id)
+            .reference("catalog", &catalog_id);
+        match crate::email::enqueue(&state, outgoing).await {
             Ok(_) => {
                 email_sent = true;
                 // Mark sent_at
@@ -964,27 +968,5 @@ async fn generate_signed_url(
     bucket: &str,
     path: &str,
 ) -> Option<String> {
-    let url = format!(
-        "{}/storage/v1/object/sign/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let body = serde_json::json!({ "expiresIn": 86400 }); // 24h
-    let http = reqwest::Client::new();
-    let resp = http
-        .post(&url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .json(&body)
-        .send()
-        .await
-        .ok()?;
-    if !resp.status().is_success() {
-        return None;
-    }
-    let j: serde_json::Value = resp.json().await.ok()?;
-    let signed_path = j.get("signedURL").and_then(|v| v.as_str())?;
-    Some(format!("{}/storage/v1{}", state.supabase_url, signed_path))
+    state.storage.signed_url(bucket, path, 86400).await.ok()
 }
@@ -38,6 +38,17 @@ pub struct ServerConfig {
     #[envconfig(from = "PORT", default = "8787")]
     pub port: u16,
 
+    // "supabase" or "local"
+    #[envconfig(from = "STORAGE_BACKEND", default = "supabase")]
+    pub storage_backend: String,
+
+    #[envconfig(from = "STORAGE_LOCAL_DIR", default = "./.storage")]
+    pub storage_local_dir: String,
+
+    // Base URL the local storage backend uses for the links it hands out.
+    #[envconfig(from = "STORAGE_PUBLIC_BASE_URL", default = "http://localhost:8787")]
+    pub storage_public_base_url: String,
+
     #[envconfig(from = "VERIFF_BASE_URL")]
     pub veriff_base_url: String,
 
@@ -63,6 +74,9 @@ pub struct ServerConfig {
     #[envconfig(from = "ELEVENLABS_API_KEY", default = "")]
     pub elevenlabs_api_key: String,
 
+    #[envconfig(from = "ELEVENLABS_BASE_URL", default = "https://api.elevenlabs.io")]
+    pub elevenlabs_base_url: String,
+
     #[envconfig(from = "SMTP_HOST", default = "")]
     pub smtp_host: String,
 
@@ -75,6 +89,10 @@ pub struct ServerConfig {
     #[envconfig(from = "SMTP_PASSWORD", default = "")]
     pub smtp_password: String,
 
+    // Set to false only for local SMTP sinks that do not speak TLS
+    #[envconfig(from = "SMTP_TLS", default = "true")]
+    pub smtp_tls: bool,
+
     #[envconfig(from = "EMAIL_FROM", default = "noreply@likelee.ai")]
     pub email_from: String,
 
@@ -103,6 +121,9 @@ pub struct ServerConfig {
     #[envconfig(from = "STRIPE_SECRET_KEY", default = "")]
     pub stripe_secret_key: String,
 
+    #[envconfig(from = "STRIPE_API_BASE", default = "https://api.stripe.com/")]
+    pub stripe_api_base: String,
+
     #[envconfig(from = "STRIPE_CLIENT_ID", default = "")]
     pub stripe_client_id: String,
 
@@ -177,6 +198,36 @@ pub struct ServerConfig {
     #[envconfig(from = "AGENCY_PAYOUT_SCHEDULER_INTERVAL_SECS", default = "3600")]
     pub agency_payout_scheduler_interval_secs: u64,
 
+    /// Run the background job poller in this process.
+    #[envconfig(from = "JOBS_ENABLED", default = "true")]
+    pub jobs_enabled: bool,
+
+    #[envconfig(from = "JOBS_POLL_INTERVAL_SECS", default = "30")]
+    pub jobs_poll_interval_secs: u64,
+
+    /// Throttle the public token endpoints (shared packages, catalogs, invites).
+    #[envconfig(from = "RATE_LIMIT_ENABLED", default = "true")]
+    pub rate_limit_enabled: bool,
+
+    // "memory" (per process) or "database" (shared across replicas)
+    #[envconfig(from = "RATE_LIMIT_STORE", default = "memory")]
+    pub rate_limit_store: String,
+
+    /// Key clients by X-Real-IP / X-Forwarded-For. Enable only behind nginx.
+    #[envconfig(from = "RATE_LIMIT_TRUST_PROXY", default = "false")]
+    pub rate_limit_trust_proxy: bool,
+
+    // Per-group overrides such as
+    // "ip=60/60,token=120/60,lockout=5/900,token_lockout=20/3600".
+    #[envconfig(from = "RATE_LIMIT_PUBLIC_PACKAGES", default = "")]
+    pub rate_limit_public_packages: String,
+
+    #[envconfig(from = "RATE_LIMIT_PUBLIC_CATALOGS", default = "")]
+    pub rate_limit_public_catalogs: String,
+
+    #[envconfig(from = "RATE_LIMIT_TALENT_INVITES", default = "")]
+    pub rate_limit_talent_invites: String,
+
     // DocuSeal API configuration
     #[envconfig(from = "DOCUSEAL_API_KEY", default = "")]
     pub docuseal_api_key: String,
@@ -190,6 +241,11 @@ pub struct ServerConfig {
     #[envconfig(from = "DOCUSEAL_WEBHOOK_URL", default = "")]
     pub docuseal_webhook_url: String,
 
+    /// Secret configured on the DocuSeal webhook; deliveries without it are
+    /// rejected.
+    #[envconfig(from = "DOCUSEAL_WEBHOOK_SECRET", default = "")]
+    pub docuseal_webhook_secret: String,
+
     #[envconfig(from = "DOCUSEAL_USER_EMAIL", default = "")]
     pub docuseal_user_email: String,
 
@@ -209,6 +265,7 @@ pub struct ServerConfig {
 #[derive(Clone)]
 pub struct AppState {
     pub pg: Postgrest,
+    pub repos: crate::repositories::Repositories,
     pub veriff: VeriffConfig,
     pub duix: DuixConfig,
     pub rekog: Option<RekogClient>,
@@ -217,9 +274,14 @@ pub struct AppState {
     pub supabase_jwt_secret: String,
     pub supabase_bucket_public: String,
     pub supabase_bucket_private: String,
+    pub supabase_bucket_temp: String,
+    pub storage: std::sync::Arc<dyn crate::storage::StorageBackend>,
+    pub rate_limiter: crate::rate_limit::RateLimiter,
     pub elevenlabs_api_key: String,
+    pub elevenlabs_base_url: String,
 
     pub stripe_secret_key: String,
+    pub stripe_api_base: String,
     pub stripe_client_id: String,
     pub stripe_return_url: String,
     pub stripe_refresh_url: String,
@@ -250,10 +312,14 @@ pub struct AppState {
     pub agency_payout_scheduler_enabled: bool,
     pub agency_payout_scheduler_interval_secs: u64,
 
+    pub jobs_enabled: bool,
+    pub jobs_poll_interval_secs: u64,
+
     pub smtp_host: String,
     pub smtp_port: u16,
     pub smtp_user: String,
     pub smtp_password: String,
+    pub smtp_tls: bool,
     pub email_from: String,
     pub email_contact_to: String,
 
@@ -270,6 +336,7 @@ pub struct AppState {
     pub docuseal_api_url: String,
     pub docuseal_app_url: String,
     pub docuseal_webhook_url: String,
+    pub docuseal_webhook_secret: String,
     pub docuseal_user_email: String,
     pub docuseal_master_template_id: String,
     pub docuseal_master_template_name: String,
@@ -277,3 +344,22 @@ pub struct AppState {
     pub kyc_bypass_veriff_limit: bool,
     pub frontend_url: String,
 }
+
+impl AppState {
+    /// Stripe client bound to the configured API base (overridable for tests).
+    pub fn stripe_client(&self) -> stripe_sdk::Client {
+        stripe_sdk::Client::from_url(
+            self.stripe_api_base.as_str(),
+            self.stripe_secret_key.clone(),
+        )
+    }
+
+    /// Concrete bucket name for a logical bucket.
+    pub fn bucket(&self, bucket: crate::storage::Bucket) -> String {
+        match bucket {
+            crate::storage::Bucket::Public => self.supabase_bucket_public.clone(),
+            crate::storage::Bucket::Private => self.supabase_bucket_private.clone(),
+            crate::storage::Bucket::Temp => self.supabase_bucket_temp.clone(),
+        }
+    }
+}
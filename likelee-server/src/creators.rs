@@ -218,54 +218,32 @@ pub async fn upload_profile_photo(
         ));
     }
 
-    let ct = headers
-        .get("content-type")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("image/jpeg")
-        .to_string();
-
+    let ct = crate::storage::sniff_content_type(
+        &body,
+        headers.get("content-type").and_then(|v| v.to_str().ok()),
+        None,
+    );
     let ext = match ct.as_str() {
         "image/png" => "png",
         "image/webp" => "webp",
         _ => "jpg",
     };
 
-    let file_name = format!("profile_{}_{}.{}", user_id, uuid::Uuid::new_v4(), ext);
-    let path = format!("{user_id}/profile-photos/{file_name}");
-
-    let bucket = state.supabase_bucket_public.clone();
-    let storage_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
+    let file_name = format!(
+        "profile_{}_{}.{}",
+        crate::storage::sanitize_segment(&user_id),
+        uuid::Uuid::new_v4(),
+        ext
     );
-
-    let http = reqwest::Client::new();
-    let res = http
-        .post(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .header("content-type", ct)
-        .body(body)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    if !res.status().is_success() {
-        let err_body = res.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to upload to storage: {err_body}"),
-        ));
-    }
-
-    let public_url = format!(
-        "{}/storage/v1/object/public/{}/{}",
-        state.supabase_url, bucket, path
+    let path = format!(
+        "{}/profile-photos/{file_name}",
+        crate::storage::sanitize_segment(&user_id)
     );
 
+    let bucket = state.bucket(crate::storage::Bucket::Public);
+    state.storage.put(&bucket, &path, body, &ct, false).await?;
+    let public_url = state.storage.public_url(&bucket, &path);
+
     let update_body = serde_json::json!({
         "profile_photo_url": public_url,
         "updated_date": chrono::Utc::now().to_rfc3339(),
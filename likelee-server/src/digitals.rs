@@ -611,11 +611,15 @@ pub async fn send_digitals_reminders(
                     )
                 });
 
-            match email::send_plain_email(&state, &email_addr, &subject, &body) {
-                Ok(()) => {
+            let outgoing = email::OutgoingEmail::new(&email_addr, &subject, &body)
+                .category("digitals_reminder")
+                .agency(&user.id)
+                .talent(r.get("id").and_then(|v| v.as_str()));
+            match email::enqueue(&state, outgoing).await {
+                Ok(_) => {
                     sent += 1;
                 }
-                Err((_code, _body)) => {
+                Err(_) => {
                     failed += 1;
                 }
             }
@@ -0,0 +1,281 @@
+use axum::{extract::State, http::StatusCode, Json};
+use base64::{engine::general_purpose, Engine as _};
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment as LettreAttachment, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{message::Mailbox, Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::config::AppState;
+
+pub mod outbox;
+
+pub use outbox::{enqueue, OutboxMessage, OutgoingEmail};
+
+#[derive(Deserialize)]
+pub struct SendEmailRequest {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    pub attachments: Option<Vec<EmailAttachment>>,
+    pub is_html: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content_base64: String,
+}
+
+/// Builds a message from the default sender, optionally under `from_name`.
+pub(crate) fn build_message(
+    state: &AppState,
+    to: &str,
+    subject: &str,
+    body: &str,
+    is_html: bool,
+    attachments: &[EmailAttachment],
+    from_name: Option<&str>,
+) -> Result<Message, (StatusCode, String)> {
+    if to.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "missing_destination".to_string()));
+    }
+
+    let parsed_from: Mailbox = state.email_from.parse::<Mailbox>().map_err(|_e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "invalid_from_address".to_string(),
+        )
+    })?;
+    let from_addr = match from_name {
+        Some(name) if !name.trim().is_empty() => Mailbox {
+            name: Some(name.trim().to_string()),
+            email: parsed_from.email.clone(),
+        },
+        _ => parsed_from,
+    };
+
+    let to_addr: Mailbox = to
+        .parse::<Mailbox>()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid_to_address".to_string()))?;
+
+    let part = if is_html {
+        SinglePart::html(body.to_string())
+    } else {
+        SinglePart::plain(body.to_string())
+    };
+
+    let builder = Message::builder()
+        .from(from_addr)
+        .to(to_addr)
+        .subject(subject.to_string());
+    let email = if attachments.is_empty() {
+        builder.singlepart(part)
+    } else {
+        let mut multipart = MultiPart::mixed().singlepart(part);
+        for att in attachments {
+            let (bytes, ct) = decode_attachment(att)?;
+            multipart =
+                multipart.singlepart(LettreAttachment::new(att.filename.clone()).body(bytes, ct));
+        }
+        builder.multipart(multipart)
+    };
+    email.map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "build_message_failed".to_string(),
+        )
+    })
+}
+
+fn decode_attachment(
+    att: &EmailAttachment,
+) -> Result<(Vec<u8>, ContentType), (StatusCode, String)> {
+    let bytes = general_purpose::STANDARD
+        .decode(att.content_base64.trim())
+        .map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                "invalid_attachment_base64".to_string(),
+            )
+        })?;
+    let ct = ContentType::parse(&att.content_type).map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            "invalid_attachment_content_type".to_string(),
+        )
+    })?;
+    Ok((bytes, ct))
+}
+
+/// Why a single SMTP delivery failed.
+#[derive(Debug)]
+pub(crate) enum TransmitError {
+    /// Relay missing or unreachable, or a 4xx reply: worth retrying.
+    Transient(String),
+    /// 5xx reply: the relay will not accept this message.
+    Permanent(String),
+}
+
+/// Hands `email` to the configured relay and returns its reply, e.g.
+/// `250 2.0.0 Ok: queued as 12345`. Blocking; call from `spawn_blocking`.
+pub(crate) fn transmit(state: &AppState, email: &Message) -> Result<String, TransmitError> {
+    if state.smtp_host.is_empty() || state.smtp_user.is_empty() {
+        return Err(TransmitError::Transient("smtp_not_configured".to_string()));
+    }
+    let creds = Credentials::new(state.smtp_user.clone(), state.smtp_password.clone());
+    let mailer = smtp_transport(&state.smtp_host, state.smtp_port, creds, state.smtp_tls)
+        .map_err(|e| TransmitError::Transient(format!("smtp_relay_init_failed: {e}")))?;
+    match mailer.send(email) {
+        Ok(resp) => Ok(format!(
+            "{} {}",
+            resp.code(),
+            resp.message().collect::<Vec<_>>().join(" ")
+        )),
+        Err(e) if e.is_permanent() => Err(TransmitError::Permanent(e.to_string())),
+        Err(e) => Err(TransmitError::Transient(e.to_string())),
+    }
+}
+
+/// Port 465 uses implicit TLS, anything else STARTTLS (falling back to a plain
+/// relay). With `tls` disabled the connection is unencrypted, which is only
+/// meant for local SMTP sinks.
+fn smtp_transport(
+    host: &str,
+    port: u16,
+    creds: Credentials,
+    tls: bool,
+) -> Result<SmtpTransport, lettre::transport::smtp::Error> {
+    if !tls {
+        return Ok(SmtpTransport::builder_dangerous(host)
+            .port(port)
+            .credentials(creds)
+            .build());
+    }
+    if port == 465 {
+        let params = TlsParameters::new(host.to_string())?;
+        return Ok(SmtpTransport::relay(host)?
+            .port(465)
+            .tls(Tls::Wrapper(params))
+            .credentials(creds)
+            .build());
+    }
+    let relay = match SmtpTransport::starttls_relay(host) {
+        Ok(r) => r,
+        Err(_) => SmtpTransport::relay(host)?,
+    };
+    Ok(relay.port(port).credentials(creds).build())
+}
+
+fn send_sales_plain_text_email(
+    state: &AppState,
+    to: &str,
+    subject: &str,
+    body: &str,
+) -> Result<(), (StatusCode, String)> {
+    send_email_smtp_internal_sales(state, to, subject, body)
+}
+
+fn send_email_smtp_internal_sales(
+    state: &AppState,
+    to: &str,
+    subject: &str,
+    body: &str,
+) -> Result<(), (StatusCode, String)> {
+    if state.smtp_sales_host.is_empty() || state.smtp_sales_user.is_empty() {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "smtp_sales_not_configured".to_string(),
+        ));
+    }
+
+    let from_addr = state.email_from_sales.parse().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "invalid_from_address".to_string(),
+        )
+    })?;
+
+    let to_addr = to
+        .parse()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid_to_address".to_string()))?;
+
+    let email = Message::builder()
+        .from(from_addr)
+        .to(to_addr)
+        .subject(subject.to_string())
+        .singlepart(SinglePart::plain(body.to_string()))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let creds = Credentials::new(
+        state.smtp_sales_user.clone(),
+        state.smtp_sales_password.clone(),
+    );
+
+    let mailer = smtp_transport(
+        &state.smtp_sales_host,
+        state.smtp_sales_port,
+        creds,
+        state.smtp_tls,
+    )
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    mailer
+        .send(&email)
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+
+    Ok(())
+}
+
+pub async fn send_email(
+    State(state): State<AppState>,
+    Json(payload): Json<SendEmailRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    // Determine destination:
+    // - Use payload.to when provided (normal behavior for app emails like invoices)
+    // - Fall back to configured EMAIL_CONTACT_TO only when payload.to is empty
+    let to = if !payload.to.trim().is_empty() {
+        payload.to.trim().to_string()
+    } else {
+        state.email_contact_to.trim().to_string()
+    };
+
+    let is_sales = !state.email_sales_to.trim().is_empty()
+        && to.trim().eq_ignore_ascii_case(state.email_sales_to.trim());
+
+    if is_sales && (payload.attachments.is_some() || payload.is_html.unwrap_or(false)) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "status":"error",
+                "error":"sales_smtp_plain_text_only"
+            })),
+        );
+    }
+
+    if is_sales {
+        match send_sales_plain_text_email(&state, &to, &payload.subject, &payload.body) {
+            Ok(_) => return (StatusCode::OK, Json(json!({"status":"ok"}))),
+            // Without a dedicated sales relay the message goes through the outbox.
+            Err((_code, msg)) if msg == "smtp_sales_not_configured" => {}
+            Err((code, msg)) => return (code, Json(json!({"status":"error", "error": msg}))),
+        }
+    }
+
+    let mut email = OutgoingEmail::new(&to, &payload.subject, &payload.body)
+        .html(payload.is_html.unwrap_or(false))
+        .category("contact");
+    for att in payload.attachments.unwrap_or_default() {
+        email = email.attach(att);
+    }
+    match outbox::enqueue(&state, email).await {
+        Ok(msg) => (
+            StatusCode::OK,
+            Json(json!({"status":"ok", "email_id": msg.id})),
+        ),
+        Err(e) => e.into_parts(),
+    }
+}
@@ -0,0 +1,381 @@
+//! Persistent email outbox.
+//!
+//! Handlers never talk to SMTP directly: they [`enqueue`] an [`OutgoingEmail`],
+//! which is stored in `email_outbox` and delivered straight away on a
+//! background task. Anything that fails transiently is retried by the
+//! `email_outbox` job with exponential backoff; each row keeps its status
+//! (`queued`, `sending`, `sent`, `failed`), attempt count and the relay's
+//! reply so agencies can look up what happened to a message.
+
+use super::{build_message, transmit, EmailAttachment, TransmitError};
+use crate::auth::{AuthUser, RoleGuard};
+use crate::config::AppState;
+use crate::errors::{AppError, AppResult};
+use crate::jobs::runner::{backoff, ts};
+use crate::repositories::{fetch, RepoError};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+pub const OUTBOX_TABLE: &str = "email_outbox";
+
+/// Delivery attempts before a message is marked `failed`.
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// How long a worker may hold a message in `sending` before another one may
+/// pick it up again.
+const SEND_LOCK_SECS: i64 = 300;
+
+/// Columns exposed in the delivery log; bodies and attachments stay server-side.
+const SUMMARY_COLUMNS: &str = "id,agency_id,client_id,talent_id,category,reference_type,reference_id,to_email,subject,status,attempts,max_attempts,next_attempt_at,last_error,smtp_response,sent_at,created_at,updated_at";
+
+/// A message to enqueue, plus who it concerns so it shows up in the right
+/// delivery log.
+#[derive(Debug, Clone, Default)]
+pub struct OutgoingEmail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    pub is_html: bool,
+    pub from_name: Option<String>,
+    pub attachments: Vec<EmailAttachment>,
+    pub category: Option<String>,
+    pub agency_id: Option<String>,
+    pub client_id: Option<String>,
+    pub talent_id: Option<String>,
+    pub reference_type: Option<String>,
+    pub reference_id: Option<String>,
+}
+
+impl OutgoingEmail {
+    pub fn new(to: &str, subject: &str, body: &str) -> Self {
+        Self {
+            to: to.trim().to_string(),
+            subject: subject.to_string(),
+            body: body.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn html(mut self, is_html: bool) -> Self {
+        self.is_html = is_html;
+        self
+    }
+
+    /// Display name for the sender; the address is always `EMAIL_FROM`.
+    pub fn from_name(mut self, name: Option<&str>) -> Self {
+        self.from_name = name
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .map(String::from);
+        self
+    }
+
+    pub fn attach(mut self, attachment: EmailAttachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    /// Short machine name such as `invoice` or `digitals_reminder`.
+    pub fn category(mut self, category: &str) -> Self {
+        self.category = Some(category.to_string());
+        self
+    }
+
+    pub fn agency(mut self, agency_id: &str) -> Self {
+        self.agency_id = Some(agency_id.to_string());
+        self
+    }
+
+    pub fn client(mut self, client_id: Option<&str>) -> Self {
+        self.client_id = client_id.filter(|c| !c.is_empty()).map(String::from);
+        self
+    }
+
+    pub fn talent(mut self, talent_id: Option<&str>) -> Self {
+        self.talent_id = talent_id.filter(|t| !t.is_empty()).map(String::from);
+        self
+    }
+
+    /// The record the message is about, e.g. `("invoice", invoice_id)`.
+    pub fn reference(mut self, kind: &str, id: &str) -> Self {
+        self.reference_type = Some(kind.to_string());
+        self.reference_id = Some(id.to_string());
+        self
+    }
+}
+
+/// An outbox row as shown in the delivery log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxMessage {
+    pub id: String,
+    pub agency_id: Option<String>,
+    pub client_id: Option<String>,
+    pub talent_id: Option<String>,
+    pub category: Option<String>,
+    pub reference_type: Option<String>,
+    pub reference_id: Option<String>,
+    pub to_email: String,
+    pub subject: String,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub next_attempt_at: Option<String>,
+    pub last_error: Option<String>,
+    pub smtp_response: Option<String>,
+    pub sent_at: Option<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// Everything needed to (re)build the message at delivery time.
+#[derive(Debug, Deserialize)]
+struct PendingEmail {
+    id: String,
+    to_email: String,
+    subject: String,
+    body: String,
+    is_html: bool,
+    from_name: Option<String>,
+    attachments: Option<Vec<EmailAttachment>>,
+    attempts: i32,
+    max_attempts: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Sent,
+    /// Failed transiently; queued again for a later attempt.
+    Retrying,
+    Failed,
+}
+
+/// Validates and stores `email`, then tries to deliver it in the background.
+/// Malformed addresses or attachments are rejected here rather than failing
+/// later in the worker.
+pub async fn enqueue(state: &AppState, email: OutgoingEmail) -> AppResult<OutboxMessage> {
+    build_message(
+        state,
+        &email.to,
+        &email.subject,
+        &email.body,
+        email.is_html,
+        &email.attachments,
+        email.from_name.as_deref(),
+    )
+    .map_err(AppError::from)?;
+
+    let row = json!({
+        "agency_id": email.agency_id,
+        "client_id": email.client_id,
+        "talent_id": email.talent_id,
+        "category": email.category,
+        "reference_type": email.reference_type,
+        "reference_id": email.reference_id,
+        "to_email": email.to,
+        "from_name": email.from_name,
+        "subject": email.subject,
+        "body": email.body,
+        "is_html": email.is_html,
+        "attachments": email.attachments,
+        "status": "queued",
+        "attempts": 0,
+        "max_attempts": MAX_ATTEMPTS,
+        "next_attempt_at": ts(Utc::now()),
+    });
+    let rows: Vec<OutboxMessage> = fetch(
+        state
+            .pg
+            .from(OUTBOX_TABLE)
+            .select(SUMMARY_COLUMNS)
+            .insert(row.to_string()),
+    )
+    .await?;
+    let message = rows
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::internal("email.outbox", "insert returned no row"))?;
+
+    let (state, id) = (state.clone(), message.id.clone());
+    tokio::spawn(async move {
+        if let Err(e) = deliver(&state, &id).await {
+            warn!(email_id = %id, error = %e, "immediate email delivery failed; left for the worker");
+        }
+    });
+    Ok(message)
+}
+
+/// Delivers up to `limit` due messages. Used by the `email_outbox` job.
+pub async fn deliver_due(state: &AppState, limit: usize) -> Result<Value, RepoError> {
+    let now = ts(Utc::now());
+    let due: Vec<Value> = fetch(
+        state
+            .pg
+            .from(OUTBOX_TABLE)
+            .select("id")
+            .in_("status", ["queued", "sending"])
+            .lte("next_attempt_at", &now)
+            .or(format!("locked_until.is.null,locked_until.lt.{now}"))
+            .order("next_attempt_at.asc")
+            .limit(limit),
+    )
+    .await?;
+
+    let (mut sent, mut retrying, mut failed) = (0, 0, 0);
+    for id in due
+        .iter()
+        .filter_map(|r| r.get("id").and_then(|v| v.as_str()))
+    {
+        match deliver(state, id).await {
+            Ok(Some(Delivery::Sent)) => sent += 1,
+            Ok(Some(Delivery::Retrying)) => retrying += 1,
+            Ok(Some(Delivery::Failed)) => failed += 1,
+            Ok(None) => {}
+            Err(e) => warn!(email_id = %id, error = %e, "email delivery bookkeeping failed"),
+        }
+    }
+    Ok(json!({ "due": due.len(), "sent": sent, "retrying": retrying, "failed": failed }))
+}
+
+/// Claims message `id` if it is due and unclaimed, makes one SMTP attempt and
+/// records the outcome. Returns `None` when another worker has it or it is no
+/// longer pending.
+pub async fn deliver(state: &AppState, id: &str) -> Result<Option<Delivery>, RepoError> {
+    let now = Utc::now();
+    let claim = json!({
+        "status": "sending",
+        "locked_until": ts(now + Duration::seconds(SEND_LOCK_SECS)),
+    });
+    let claimed: Vec<PendingEmail> = fetch(
+        state
+            .pg
+            .from(OUTBOX_TABLE)
+            .select("id,to_email,subject,body,is_html,from_name,attachments,attempts,max_attempts")
+            .eq("id", id)
+            .in_("status", ["queued", "sending"])
+            .lte("next_attempt_at", ts(now))
+            .or(format!("locked_until.is.null,locked_until.lt.{}", ts(now)))
+            .update(claim.to_string()),
+    )
+    .await?;
+    let Some(msg) = claimed.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let attempt = msg.attempts + 1;
+    let result = {
+        let state = state.clone();
+        let msg_id = msg.id.clone();
+        tokio::task::spawn_blocking(move || {
+            let email = build_message(
+                &state,
+                &msg.to_email,
+                &msg.subject,
+                &msg.body,
+                msg.is_html,
+                msg.attachments.as_deref().unwrap_or_default(),
+                msg.from_name.as_deref(),
+            )
+            .map_err(|(_, e)| TransmitError::Permanent(e))?;
+            transmit(&state, &email)
+        })
+        .await
+        .unwrap_or_else(|e| {
+            Err(TransmitError::Transient(format!(
+                "delivery task for {msg_id} aborted: {e}"
+            )))
+        })
+    };
+
+    let finished = Utc::now();
+    let (outcome, patch) = match result {
+        Ok(reply) => (
+            Delivery::Sent,
+            json!({
+                "status": "sent",
+                "sent_at": ts(finished),
+                "smtp_response": reply,
+                "last_error": null,
+            }),
+        ),
+        Err(TransmitError::Transient(e)) if attempt < msg.max_attempts => (
+            Delivery::Retrying,
+            json!({
+                "status": "queued",
+                "next_attempt_at": ts(finished + backoff(attempt)),
+                "last_error": e,
+            }),
+        ),
+        Err(TransmitError::Transient(e) | TransmitError::Permanent(e)) => (
+            Delivery::Failed,
+            json!({ "status": "failed", "last_error": e }),
+        ),
+    };
+    let mut patch = patch;
+    patch["attempts"] = json!(attempt);
+    patch["locked_until"] = Value::Null;
+    let _: Vec<Value> = fetch(
+        state
+            .pg
+            .from(OUTBOX_TABLE)
+            .select("id")
+            .eq("id", id)
+            .update(patch.to_string()),
+    )
+    .await?;
+
+    match outcome {
+        Delivery::Sent => info!(email_id = %id, attempt, "email delivered"),
+        Delivery::Retrying => warn!(email_id = %id, attempt, "email delivery failed; will retry"),
+        Delivery::Failed => warn!(email_id = %id, attempt, "email delivery failed permanently"),
+    }
+    Ok(Some(outcome))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryLogParams {
+    pub client_id: Option<String>,
+    pub talent_id: Option<String>,
+    pub reference_type: Option<String>,
+    pub reference_id: Option<String>,
+    pub category: Option<String>,
+    pub status: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// GET /api/agency/email-deliveries: the agency's outgoing mail, newest first.
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<DeliveryLogParams>,
+) -> AppResult<Json<Vec<OutboxMessage>>> {
+    RoleGuard::new(vec!["agency"]).check(&user.role)?;
+    let mut query = state
+        .pg
+        .from(OUTBOX_TABLE)
+        .select(SUMMARY_COLUMNS)
+        .eq("agency_id", &user.id)
+        .order("created_at.desc");
+    for (column, value) in [
+        ("client_id", &params.client_id),
+        ("talent_id", &params.talent_id),
+        ("reference_type", &params.reference_type),
+        ("reference_id", &params.reference_id),
+        ("category", &params.category),
+        ("status", &params.status),
+    ] {
+        if let Some(v) = value.as_deref().filter(|v| !v.is_empty()) {
+            query = query.eq(column, v);
+        }
+    }
+    let limit = params.limit.unwrap_or(50).clamp(1, 200);
+    let offset = params.offset.unwrap_or(0);
+    let rows: Vec<OutboxMessage> = fetch(query.range(offset, offset + limit - 1)).await?;
+    Ok(Json(rows))
+}
@@ -1,131 +1,640 @@
-use axum::http::StatusCode;
-use serde_json::json;
+//! Error handling shared by every endpoint.
+//!
+//! Every error response has the same JSON shape:
+//!
+//! ```json
+//! { "status": "error", "code": "not_found", "error": "Resource not found.", "request_id": "…" }
+//! ```
+//!
+//! `code` is a stable, machine-readable identifier the frontend can switch on;
+//! `error` is a human-readable message that never contains database or
+//! upstream internals (those are logged together with the request id).
+//!
+//! New handlers return [`AppResult`]. Handlers still returning
+//! `(StatusCode, String)` are rewritten into the same shape by the
+//! [`request_context`] middleware.
 
-pub fn sanitize_db_error(status_code: u16, text: String) -> (StatusCode, String) {
-    let axum_status =
-        StatusCode::from_u16(status_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-
-    // Log the original error for debugging
-    tracing::error!(
-        status = %axum_status,
-        error = %text,
-        "Database error occurred"
-    );
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const GENERIC_INTERNAL: &str = "An internal error occurred. Our team has been notified.";
+const GENERIC_UPSTREAM: &str = "A dependent service is unavailable. Please try again later.";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Request id of the request currently being handled, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Request id attached to request extensions by [`request_context`].
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+pub type AppResult<T> = Result<T, AppError>;
+
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    /// Any other status with an optional explicit code.
+    Status {
+        status: StatusCode,
+        code: Option<String>,
+        message: String,
+    },
+    /// Non-2xx response from PostgREST; `body` is the raw PostgREST error.
+    Db {
+        status: u16,
+        body: String,
+    },
+    /// A third-party service failed or could not be reached.
+    Upstream {
+        service: &'static str,
+        detail: String,
+    },
+    /// Stripe rejected the request (card declined, invalid parameters, ...).
+    Payment {
+        status: u16,
+        code: Option<String>,
+        message: String,
+    },
+    Internal {
+        context: String,
+        detail: String,
+    },
+}
+
+impl AppError {
+    pub fn internal(context: &str, err: impl std::fmt::Display) -> Self {
+        AppError::Internal {
+            context: context.to_string(),
+            detail: err.to_string(),
+        }
+    }
+
+    pub fn upstream(service: &'static str, err: impl std::fmt::Display) -> Self {
+        AppError::Upstream {
+            service,
+            detail: err.to_string(),
+        }
+    }
+
+    pub fn db(status: u16, body: impl Into<String>) -> Self {
+        AppError::Db {
+            status,
+            body: body.into(),
+        }
+    }
 
-    // If it's a 4xx error that looks like a PostgREST error, try to extract a user-friendly message
-    // but default to a generic one if it contains schema info.
-    if axum_status.is_client_error() {
-        if let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) {
-            if let (Some(code), Some(msg)) = (
-                v.get("code").and_then(|c| c.as_str()),
-                v.get("message").and_then(|m| m.as_str()),
-            ) {
-                // Check if message contains schema-leaking keywords
-                let sensitive_keywords = [
-                    "column",
-                    "table",
-                    "relation",
-                    "schema",
-                    "cache",
-                    "null constraint",
-                    "violates",
-                    "foreign key",
-                    "fk_",
-                    "pk_",
-                    "idx_",
-                ];
-                let contains_sensitive = sensitive_keywords
-                    .iter()
-                    .any(|&k| msg.to_lowercase().contains(k));
-
-                // Map PostgREST/PostgreSQL error codes to user-friendly messages
-                // Reference: https://www.postgresql.org/docs/current/errcodes-appendix.html
-                match code {
-                    "23505" => {
-                        return (
-                            StatusCode::CONFLICT,
-                            json!({
-                                "error": "This record already exists.",
-                                "code": code
-                            })
-                            .to_string(),
-                        );
-                    }
-                    "23503" => {
-                        return (
-                            axum_status,
-                            json!({
-                                "error": "The referenced record was not found.",
-                                "code": code
-                            })
-                            .to_string(),
-                        );
-                    }
-                    "23502" => {
-                        return (
-                            axum_status,
-                            json!({
-                                "error": "Missing required information.",
-                                "code": code
-                            })
-                            .to_string(),
-                        );
-                    }
-                    "42P01" | "42703" => {
-                        // Undefined table or column - definitely sensitive
-                        return (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            json!({
-                                "error": "A server configuration error occurred.",
-                                "code": code
-                            })
-                            .to_string(),
-                        );
-                    }
-                    _ if contains_sensitive => {
-                        return (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            json!({
-                                "error": "Invalid data provided. Please check your input.",
-                                "details": "A validation error occurred on the server.",
-                                "code": code
-                            })
-                            .to_string(),
-                        );
-                    }
-                    _ => {
-                        return (
-                            axum_status,
-                            json!({
-                                "error": msg,
-                                "code": code
-                            })
-                            .to_string(),
-                        );
-                    }
-                }
+    /// Status, stable code and client-safe message.
+    fn resolve(&self) -> (StatusCode, String, String) {
+        match self {
+            AppError::BadRequest(m) => simple(StatusCode::BAD_REQUEST, m),
+            AppError::Unauthorized(m) => simple(StatusCode::UNAUTHORIZED, m),
+            AppError::Forbidden(m) => simple(StatusCode::FORBIDDEN, m),
+            AppError::NotFound(m) => simple(StatusCode::NOT_FOUND, m),
+            AppError::Conflict(m) => simple(StatusCode::CONFLICT, m),
+            AppError::Status {
+                status,
+                code,
+                message,
+            } => {
+                let (status, default_code, message) = simple(*status, message);
+                (status, code.clone().unwrap_or(default_code), message)
             }
+            AppError::Db { status, body } => resolve_db(*status, body),
+            AppError::Upstream { .. } => (
+                StatusCode::BAD_GATEWAY,
+                "upstream_error".to_string(),
+                GENERIC_UPSTREAM.to_string(),
+            ),
+            AppError::Payment {
+                status,
+                code,
+                message,
+            } => {
+                let status = StatusCode::from_u16(*status)
+                    .ok()
+                    .filter(|s| s.is_client_error())
+                    .unwrap_or(StatusCode::PAYMENT_REQUIRED);
+                (
+                    status,
+                    code.clone().unwrap_or_else(|| "payment_failed".to_string()),
+                    if message.is_empty() {
+                        "The payment provider rejected the request.".to_string()
+                    } else {
+                        message.clone()
+                    },
+                )
+            }
+            AppError::Internal { .. } => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error".to_string(),
+                GENERIC_INTERNAL.to_string(),
+            ),
         }
     }
 
-    // For 5xx or unknown 4xx, return generic error
-    (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        json!({
-            "error": "An internal error occurred. Our team has been notified.",
-            "details": "Please try again later or contact support if the issue persists."
-        })
-        .to_string(),
-    )
+    fn log(&self, status: StatusCode, code: &str, request_id: &str) {
+        let detail = match self {
+            AppError::Db { body, .. } => body.clone(),
+            AppError::Upstream { service, detail } => format!("{service}: {detail}"),
+            AppError::Internal { context, detail } => format!("{context}: {detail}"),
+            other => other.to_string(),
+        };
+        if status.is_server_error() {
+            tracing::error!(%request_id, %status, code, error = %detail, "request failed");
+        } else {
+            tracing::warn!(%request_id, %status, code, error = %detail, "request rejected");
+        }
+    }
+
+    /// Logs the error and renders the standard error body.
+    pub fn into_parts(self) -> (StatusCode, Json<Value>) {
+        let request_id = current_request_id().unwrap_or_default();
+        let (status, code, message) = self.resolve();
+        self.log(status, &code, &request_id);
+        (status, Json(error_body(&code, &message, &request_id)))
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::BadRequest(m)
+            | AppError::Unauthorized(m)
+            | AppError::Forbidden(m)
+            | AppError::NotFound(m)
+            | AppError::Conflict(m) => write!(f, "{m}"),
+            AppError::Status {
+                status, message, ..
+            } => write!(f, "{status}: {message}"),
+            AppError::Db { status, body } => write!(f, "database error ({status}): {body}"),
+            AppError::Upstream { service, detail } => write!(f, "{service} error: {detail}"),
+            AppError::Payment { message, .. } => write!(f, "payment error: {message}"),
+            AppError::Internal { context, detail } => write!(f, "{context}: {detail}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// Marks responses already rendered by `AppError` so the middleware leaves them alone.
+#[derive(Clone, Copy)]
+struct Rendered;
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let mut resp = self.into_parts().into_response();
+        resp.extensions_mut().insert(Rendered);
+        resp
+    }
+}
+
+impl From<(StatusCode, String)> for AppError {
+    fn from((status, body): (StatusCode, String)) -> Self {
+        // Bodies produced by `sanitize_db_error` are already in the standard shape.
+        if let Ok(Value::Object(obj)) = serde_json::from_str::<Value>(&body) {
+            let (code, message) = code_and_message(&obj);
+            return AppError::Status {
+                status,
+                code,
+                message: message.unwrap_or_default(),
+            };
+        }
+        match status {
+            StatusCode::BAD_REQUEST => AppError::BadRequest(body),
+            StatusCode::UNAUTHORIZED => AppError::Unauthorized(body),
+            StatusCode::FORBIDDEN => AppError::Forbidden(body),
+            StatusCode::NOT_FOUND => AppError::NotFound(body),
+            StatusCode::CONFLICT => AppError::Conflict(body),
+            s if s.is_server_error() && !is_code(&body) => AppError::Internal {
+                context: "handler".to_string(),
+                detail: body,
+            },
+            status => AppError::Status {
+                status,
+                code: None,
+                message: body,
+            },
+        }
+    }
+}
+
+/// Lets helpers that still return `(StatusCode, String)` forward an `AppError`.
+impl From<AppError> for (StatusCode, String) {
+    fn from(err: AppError) -> Self {
+        let (status, Json(body)) = err.into_parts();
+        (status, body.to_string())
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(err: reqwest::Error) -> Self {
+        AppError::upstream("http", err)
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        AppError::internal("json", err)
+    }
+}
+
+impl From<stripe_sdk::StripeError> for AppError {
+    fn from(err: stripe_sdk::StripeError) -> Self {
+        match err {
+            stripe_sdk::StripeError::Stripe(req) => AppError::Payment {
+                status: if req.error_type == stripe_sdk::ErrorType::Card {
+                    402
+                } else {
+                    req.http_status
+                },
+                code: req
+                    .code
+                    .and_then(|c| serde_json::to_value(c).ok())
+                    .and_then(|v| v.as_str().map(String::from)),
+                message: req.message.unwrap_or_default(),
+            },
+            other => AppError::upstream("stripe", other),
+        }
+    }
+}
+
+/// Kept for handlers that build `(StatusCode, String)` errors from PostgREST responses.
+pub fn sanitize_db_error(status_code: u16, text: String) -> (StatusCode, String) {
+    AppError::db(status_code, text).into()
 }
 
 /// Helper to sanitize any error and log it
 pub fn handle_error<E: std::fmt::Display>(err: E, context: &str) -> (StatusCode, String) {
-    let err_str = err.to_string();
-    tracing::error!(
-        context = context,
-        error = %err_str,
-        "Error occurred"
+    AppError::internal(context, err).into()
+}
+
+/// Assigns a request id (honouring an incoming `x-request-id`), makes it
+/// available to `AppError` via a task-local, echoes it on the response, and
+/// rewrites legacy error bodies into the standard shape.
+pub async fn request_context(mut req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128 && v.bytes().all(|b| b.is_ascii_graphic()))
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let resp = REQUEST_ID.scope(request_id.clone(), next.run(req)).await;
+
+    let mut resp = if resp.status().as_u16() >= 400 && resp.extensions().get::<Rendered>().is_none()
+    {
+        normalize_legacy(resp, &request_id).await
+    } else {
+        resp
+    };
+    if let Ok(v) = HeaderValue::from_str(&request_id) {
+        resp.headers_mut().insert(REQUEST_ID_HEADER, v);
+    }
+    resp
+}
+
+async fn normalize_legacy(resp: Response, request_id: &str) -> Response {
+    let (mut parts, body) = resp.into_parts();
+    let status = parts.status;
+    let bytes = axum::body::to_bytes(body, 1_000_000)
+        .await
+        .unwrap_or_default();
+    let text = String::from_utf8_lossy(&bytes).trim().to_string();
+
+    let parsed = match serde_json::from_str::<Value>(&text) {
+        Ok(Value::Object(obj)) => Some(obj),
+        _ => None,
+    };
+    // Bodies rendered by `AppError::into_parts` (directly or through
+    // `sanitize_db_error`) carry this request's id and were already logged.
+    if parsed
+        .as_ref()
+        .is_some_and(|obj| already_rendered(obj, request_id))
+    {
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+    let (code, message) = match parsed {
+        Some(obj) => code_and_message(&obj),
+        None => (None, Some(text.clone()).filter(|t| !t.is_empty())),
+    };
+    let message = message.unwrap_or_default();
+    let (code, message) = if is_code(&message) {
+        (
+            code.unwrap_or_else(|| message.clone()),
+            default_message(status),
+        )
+    } else {
+        (
+            code.unwrap_or_else(|| default_code(status)),
+            if message.is_empty() {
+                default_message(status)
+            } else {
+                message
+            },
+        )
+    };
+    // 5xx messages from legacy handlers are often raw upstream errors.
+    let message = if status.is_server_error()
+        && message != GENERIC_INTERNAL
+        && message != GENERIC_UPSTREAM
+    {
+        tracing::error!(%request_id, %status, code = %code, error = %text, "request failed");
+        GENERIC_INTERNAL.to_string()
+    } else {
+        if status.is_client_error() {
+            tracing::warn!(%request_id, %status, code = %code, error = %text, "request rejected");
+        }
+        message
+    };
+
+    let body = error_body(&code, &message, request_id).to_string();
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
     );
-    sanitize_db_error(StatusCode::INTERNAL_SERVER_ERROR.as_u16(), err_str)
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn error_body(code: &str, message: &str, request_id: &str) -> Value {
+    json!({
+        "status": "error",
+        "code": code,
+        "error": message,
+        "request_id": request_id,
+    })
+}
+
+fn already_rendered(obj: &serde_json::Map<String, Value>, request_id: &str) -> bool {
+    obj.get("status").and_then(|s| s.as_str()) == Some("error")
+        && obj.get("code").is_some_and(|c| c.is_string())
+        && obj.get("request_id").and_then(|r| r.as_str()) == Some(request_id)
+}
+
+/// Extracts `(code, message)` from the legacy JSON error shapes in use:
+/// `{"error": "..", "code": ".."}`, `{"status":"error","error": {..}}`, `{"message": ".."}`.
+fn code_and_message(obj: &serde_json::Map<String, Value>) -> (Option<String>, Option<String>) {
+    let nested = obj.get("error").and_then(|e| e.as_object());
+    let code = obj
+        .get("code")
+        .and_then(|c| c.as_str())
+        .or_else(|| nested.and_then(|n| n.get("code")).and_then(|c| c.as_str()))
+        .map(String::from);
+    let message = obj
+        .get("error")
+        .and_then(|e| e.as_str())
+        .or_else(|| {
+            nested.and_then(|n| {
+                n.get("error")
+                    .or_else(|| n.get("message"))
+                    .and_then(|m| m.as_str())
+            })
+        })
+        .or_else(|| obj.get("message").and_then(|m| m.as_str()))
+        .map(String::from);
+    (code, message)
+}
+
+/// `agency_only`, `missing_destination`, ... are already machine-readable codes.
+fn is_code(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= 64
+        && s.bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+        && s.bytes().next().is_some_and(|b| b.is_ascii_lowercase())
+}
+
+fn simple(status: StatusCode, message: &str) -> (StatusCode, String, String) {
+    if is_code(message) {
+        (status, message.to_string(), default_message(status))
+    } else if message.trim().is_empty() {
+        (status, default_code(status), default_message(status))
+    } else {
+        (status, default_code(status), message.to_string())
+    }
+}
+
+fn default_code(status: StatusCode) -> String {
+    match status {
+        StatusCode::BAD_REQUEST => "bad_request",
+        StatusCode::UNAUTHORIZED => "unauthorized",
+        StatusCode::PAYMENT_REQUIRED => "payment_required",
+        StatusCode::FORBIDDEN => "forbidden",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+        StatusCode::CONFLICT => "conflict",
+        StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+        StatusCode::UNPROCESSABLE_ENTITY => "invalid_request",
+        StatusCode::TOO_MANY_REQUESTS => "rate_limited",
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => {
+            "upstream_error"
+        }
+        s if s.is_server_error() => "internal_error",
+        _ => "request_failed",
+    }
+    .to_string()
+}
+
+fn default_message(status: StatusCode) -> String {
+    match status {
+        StatusCode::BAD_REQUEST => "The request is invalid.",
+        StatusCode::UNAUTHORIZED => "Authentication is required.",
+        StatusCode::FORBIDDEN => "You do not have permission to perform this action.",
+        StatusCode::NOT_FOUND => "Resource not found.",
+        StatusCode::CONFLICT => "The request conflicts with the current state of the resource.",
+        StatusCode::TOO_MANY_REQUESTS => "Too many requests. Please try again later.",
+        s if s.is_server_error() => GENERIC_INTERNAL,
+        _ => "The request could not be completed.",
+    }
+    .to_string()
+}
+
+/// Maps a PostgREST error body onto a client-safe status, code and message.
+fn resolve_db(status_code: u16, text: &str) -> (StatusCode, String, String) {
+    let status = StatusCode::from_u16(status_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let internal = || {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error".to_string(),
+            GENERIC_INTERNAL.to_string(),
+        )
+    };
+    if !status.is_client_error() {
+        return internal();
+    }
+    let Ok(v) = serde_json::from_str::<Value>(text) else {
+        return internal();
+    };
+    let (Some(code), Some(msg)) = (
+        v.get("code").and_then(|c| c.as_str()),
+        v.get("message").and_then(|m| m.as_str()),
+    ) else {
+        return internal();
+    };
+
+    // Messages mentioning schema objects must not reach the client.
+    let sensitive_keywords = [
+        "column",
+        "table",
+        "relation",
+        "schema",
+        "cache",
+        "null constraint",
+        "violates",
+        "foreign key",
+        "fk_",
+        "pk_",
+        "idx_",
+    ];
+    let contains_sensitive = sensitive_keywords
+        .iter()
+        .any(|&k| msg.to_lowercase().contains(k));
+
+    // Reference: https://www.postgresql.org/docs/current/errcodes-appendix.html
+    let (status, code, message) = match code {
+        "23505" => (
+            StatusCode::CONFLICT,
+            "duplicate_record",
+            "This record already exists.",
+        ),
+        "23503" => (
+            status,
+            "reference_not_found",
+            "The referenced record was not found.",
+        ),
+        "23502" => (status, "missing_field", "Missing required information."),
+        "PGRST116" => (StatusCode::NOT_FOUND, "not_found", "Resource not found."),
+        "42P01" | "42703" => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "server_misconfigured",
+            "A server configuration error occurred.",
+        ),
+        _ if contains_sensitive => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "invalid_data",
+            "Invalid data provided. Please check your input.",
+        ),
+        _ => (status, "invalid_request", msg),
+    };
+    (status, code.to_string(), message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(err: AppError) -> (u16, String, String) {
+        let (status, code, message) = err.resolve();
+        (status.as_u16(), code, message)
+    }
+
+    #[test]
+    fn postgrest_errors_map_to_stable_codes() {
+        let dup = r#"{"code":"23505","message":"duplicate key value violates unique constraint \"pk_x\""}"#;
+        assert_eq!(
+            resolved(AppError::db(409, dup)),
+            (
+                409,
+                "duplicate_record".into(),
+                "This record already exists.".into()
+            )
+        );
+
+        let leak = r#"{"code":"PGRST204","message":"Could not find the 'foo' column of 'bar' in the schema cache"}"#;
+        let (status, code, message) = resolved(AppError::db(400, leak));
+        assert_eq!((status, code.as_str()), (500, "invalid_data"));
+        assert!(!message.contains("schema"));
+
+        assert_eq!(
+            resolved(AppError::db(503, "upstream connect error")).1,
+            "internal_error"
+        );
+    }
+
+    #[test]
+    fn legacy_tuples_keep_code_like_messages_as_codes() {
+        let (status, code, message) =
+            resolved((StatusCode::FORBIDDEN, "agency_only".to_string()).into());
+        assert_eq!((status, code.as_str()), (403, "agency_only"));
+        assert!(!message.is_empty());
+
+        let (_, code, message) = resolved(
+            (
+                StatusCode::CONFLICT,
+                "Only draft invoices can be updated".to_string(),
+            )
+                .into(),
+        );
+        assert_eq!(code, "conflict");
+        assert_eq!(message, "Only draft invoices can be updated");
+    }
+
+    #[test]
+    fn server_error_details_are_not_exposed() {
+        let (status, code, message) = resolved(
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "error sending request for url (http://10.0.0.3/rest/v1/x)".to_string(),
+            )
+                .into(),
+        );
+        assert_eq!((status, code.as_str()), (500, "internal_error"));
+        assert!(!message.contains("10.0.0.3"));
+    }
+
+    #[tokio::test]
+    async fn legacy_plain_text_bodies_are_normalized() {
+        let resp = (StatusCode::FORBIDDEN, "agency_only").into_response();
+        let resp = normalize_legacy(resp, "req-1").await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let bytes = axum::body::to_bytes(resp.into_body(), 10_000)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(v["code"], "agency_only");
+        assert_eq!(v["status"], "error");
+        assert_eq!(v["request_id"], "req-1");
+    }
+
+    #[tokio::test]
+    async fn rendered_bodies_pass_through_unchanged() {
+        let body = error_body("duplicate_record", "This record already exists.", "req-2");
+        let resp = (StatusCode::CONFLICT, body.to_string()).into_response();
+        let resp = normalize_legacy(resp, "req-2").await;
+        assert_eq!(
+            resp.headers()[header::CONTENT_TYPE],
+            HeaderValue::from_static("application/json")
+        );
+        let bytes = axum::body::to_bytes(resp.into_body(), 10_000)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(v, body);
+    }
 }
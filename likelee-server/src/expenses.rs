@@ -1,37 +1,279 @@
-use crate::{auth::AuthUser, config::AppState, errors::sanitize_db_error};
+//! Agency expenses: receipts, approval and rebilling to clients.
+//!
+//! An expense moves `submitted → approved | rejected`, a rejected one can be
+//! submitted again, and an approved one is `reimbursed` once the submitter
+//! has been paid back. Expenses the agency records itself are approved on
+//! creation. Approved and reimbursed expenses are the agency's costs in the
+//! accounting export.
+//!
+//! An expense can be tagged to a talent, a booking and a client; tagging a
+//! booking fills in its talent and client. Billable expenses are rebilled to
+//! their client as expense lines on a new draft invoice. The expenses are
+//! claimed (`rebilled_at`) before the draft is created, so two rebills cannot
+//! put the same expense on two invoices, and voiding the invoice releases
+//! them to be rebilled again.
+
+use crate::{
+    audit::{self, AuditEvent},
+    auth::AuthUser,
+    config::AppState,
+    errors::{AppError, AppResult},
+    invoices::{create_draft, CreateInvoiceExpenseInput, CreateInvoicePayload},
+    jobs::runner::ts,
+    repositories::{fetch, RepoError},
+    storage::{sanitize_file_name, sanitize_segment, sniff_content_type, Bucket},
+};
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
+    extract::{Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
-use serde::Deserialize;
-use serde_json::json;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::warn;
 
-#[derive(Debug, Deserialize)]
+pub const TABLE: &str = "agency_expenses";
+pub const RECEIPTS_TABLE: &str = "agency_expense_receipts";
+
+/// Largest receipt accepted, per file.
+const MAX_RECEIPT_BYTES: usize = 10 * 1024 * 1024;
+const RECEIPT_CONTENT_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "application/pdf",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpenseStatus {
+    /// `pending` is what older clients send for an expense awaiting review.
+    #[serde(alias = "pending")]
+    Submitted,
+    Approved,
+    Rejected,
+    Reimbursed,
+}
+
+impl ExpenseStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "submitted" | "pending" => Some(ExpenseStatus::Submitted),
+            "approved" => Some(ExpenseStatus::Approved),
+            "rejected" => Some(ExpenseStatus::Rejected),
+            "reimbursed" => Some(ExpenseStatus::Reimbursed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExpenseStatus::Submitted => "submitted",
+            ExpenseStatus::Approved => "approved",
+            ExpenseStatus::Rejected => "rejected",
+            ExpenseStatus::Reimbursed => "reimbursed",
+        }
+    }
+
+    pub fn can_become(self, next: ExpenseStatus) -> bool {
+        use ExpenseStatus::*;
+        matches!(
+            (self, next),
+            (Submitted, Approved)
+                | (Submitted, Rejected)
+                | (Rejected, Submitted)
+                | (Approved, Reimbursed)
+        )
+    }
+
+    /// Counted as an agency cost (and billable to a client).
+    pub fn is_approved(self) -> bool {
+        matches!(self, ExpenseStatus::Approved | ExpenseStatus::Reimbursed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Expense {
+    pub id: String,
+    pub agency_id: String,
+    pub name: String,
+    pub category: String,
+    pub expense_date: NaiveDate,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub status: ExpenseStatus,
+    pub submitter: Option<String>,
+    pub notes: Option<String>,
+    pub talent_id: Option<String>,
+    pub booking_id: Option<String>,
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub billable: bool,
+    pub review_note: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub reimbursed_at: Option<DateTime<Utc>>,
+    pub reimbursement_reference: Option<String>,
+    pub rebilled_invoice_id: Option<String>,
+    pub rebilled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Expense {
+    fn is_rebilled(&self) -> bool {
+        self.rebilled_at.is_some()
+    }
+
+    /// Reimbursed and rebilled expenses are settled and no longer edited.
+    fn ensure_editable(&self) -> AppResult<()> {
+        if self.status == ExpenseStatus::Reimbursed {
+            return Err(AppError::Conflict(
+                "Reimbursed expenses cannot be changed".to_string(),
+            ));
+        }
+        if self.is_rebilled() {
+            return Err(AppError::Conflict(
+                "Expense has been rebilled to a client".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn rebill_line(&self) -> CreateInvoiceExpenseInput {
+        CreateInvoiceExpenseInput {
+            description: format!("{} ({})", self.name, self.expense_date),
+            amount_cents: Some(self.amount_cents as i32),
+            taxable: None,
+            tax_rate_bps: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpenseReceipt {
+    pub id: String,
+    pub agency_id: String,
+    pub expense_id: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub storage_bucket: String,
+    pub storage_path: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExpenseDetail {
+    #[serde(flatten)]
+    pub expense: Expense,
+    pub receipts: Vec<ExpenseReceipt>,
+}
+
+async fn get_expense(state: &AppState, agency_id: &str, id: &str) -> AppResult<Expense> {
+    let rows: Vec<Expense> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .select("*")
+            .eq("id", id)
+            .eq("agency_id", agency_id),
+    )
+    .await?;
+    rows.into_iter()
+        .next()
+        .ok_or_else(|| AppError::NotFound("Expense not found".to_string()))
+}
+
+async fn update_expense(state: &AppState, expense: &Expense, patch: Value) -> AppResult<Expense> {
+    let mut patch = patch;
+    patch["updated_at"] = json!(ts(Utc::now()));
+    let rows: Vec<Expense> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .update(patch.to_string())
+            .eq("id", &expense.id)
+            .eq("agency_id", &expense.agency_id),
+    )
+    .await?;
+    Ok(rows.into_iter().next().ok_or(RepoError::NotFound)?)
+}
+
+async fn receipts(state: &AppState, expense: &Expense) -> Result<Vec<ExpenseReceipt>, RepoError> {
+    fetch(
+        state
+            .pg
+            .from(RECEIPTS_TABLE)
+            .select("*")
+            .eq("expense_id", &expense.id)
+            .eq("agency_id", &expense.agency_id)
+            .order("created_at.asc"),
+    )
+    .await
+}
+
+async fn audit_change(
+    state: &AppState,
+    user: &AuthUser,
+    event_type: &str,
+    title: String,
+    before: Option<&Expense>,
+    after: &Expense,
+) {
+    audit::record(
+        state,
+        AuditEvent::new(&after.agency_id, event_type, TABLE, &after.id)
+            .actor(user)
+            .title(title)
+            .change(&before, after),
+    )
+    .await;
+}
+
+#[derive(Debug, Default, Deserialize)]
 pub struct ExpenseListParams {
     pub date_start: Option<String>,
     pub date_end: Option<String>,
     pub category: Option<String>,
     pub status: Option<String>,
+    pub talent_id: Option<String>,
+    pub booking_id: Option<String>,
+    pub client_id: Option<String>,
+    pub billable: Option<bool>,
+    pub rebilled: Option<bool>,
 }
 
+/// GET /api/expenses
 pub async fn list(
     State(state): State<AppState>,
     user: AuthUser,
     Query(params): Query<ExpenseListParams>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> AppResult<Json<Vec<Expense>>> {
     let mut req = state
         .pg
-        .from("agency_expenses")
+        .from(TABLE)
         .select("*")
         .eq("agency_id", &user.id)
         .order("expense_date.desc");
 
-    if let Some(s) = params.category.as_ref().filter(|s| !s.is_empty()) {
-        req = req.eq("category", s);
+    let filters = [
+        ("category", &params.category),
+        ("talent_id", &params.talent_id),
+        ("booking_id", &params.booking_id),
+        ("client_id", &params.client_id),
+    ];
+    for (column, value) in filters {
+        if let Some(v) = value.as_ref().filter(|s| !s.is_empty()) {
+            req = req.eq(column, v);
+        }
     }
     if let Some(s) = params.status.as_ref().filter(|s| !s.is_empty()) {
-        req = req.eq("status", s);
+        let status = ExpenseStatus::parse(s)
+            .ok_or_else(|| AppError::BadRequest("invalid_status".to_string()))?;
+        req = req.eq("status", status.as_str());
     }
     if let Some(d) = params.date_start.as_ref().filter(|s| !s.is_empty()) {
         req = req.gte("expense_date", d);
@@ -39,25 +281,181 @@ pub async fn list(
     if let Some(d) = params.date_end.as_ref().filter(|s| !s.is_empty()) {
         req = req.lte("expense_date", d);
     }
+    if let Some(billable) = params.billable {
+        req = req.eq("billable", billable.to_string());
+    }
+    if let Some(rebilled) = params.rebilled {
+        req = if rebilled {
+            req.not("is", "rebilled_at", "null")
+        } else {
+            req.is("rebilled_at", "null")
+        };
+    }
 
-    let resp = req
-        .execute()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(fetch(req).await?))
+}
 
-    let status = resp.status();
-    let text = resp
-        .text()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+/// GET /api/expenses/:id, with its receipts.
+pub async fn get(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<ExpenseDetail>> {
+    let expense = get_expense(&state, &user.id, &id).await?;
+    let receipts = receipts(&state, &expense).await?;
+    Ok(Json(ExpenseDetail { expense, receipts }))
+}
+
+/// Talent, booking and client an expense is tagged to. `None` leaves a tag
+/// as it is; an empty string clears it.
+#[derive(Debug, Default, Deserialize)]
+pub struct ExpenseTags {
+    pub talent_id: Option<String>,
+    pub booking_id: Option<String>,
+    pub client_id: Option<String>,
+    pub billable: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct BookingRef {
+    talent_id: Option<String>,
+    client_id: Option<String>,
+}
+
+async fn ensure_exists(
+    state: &AppState,
+    table: &str,
+    owner_column: &str,
+    agency_id: &str,
+    id: &str,
+    code: &str,
+) -> AppResult<()> {
+    let rows: Vec<Value> = fetch(
+        state
+            .pg
+            .from(table)
+            .select("id")
+            .eq("id", id)
+            .eq(owner_column, agency_id)
+            .limit(1),
+    )
+    .await?;
+    if rows.is_empty() {
+        return Err(AppError::BadRequest(code.to_string()));
+    }
+    Ok(())
+}
+
+/// Checks the tags belong to the agency and returns the columns to write.
+/// A newly tagged booking fills in its talent and client where those are
+/// not given.
+async fn resolve_tags(
+    state: &AppState,
+    agency_id: &str,
+    tags: &ExpenseTags,
+    current: Option<&Expense>,
+) -> AppResult<serde_json::Map<String, Value>> {
+    fn given(value: &Option<String>) -> Option<Option<&str>> {
+        value
+            .as_deref()
+            .map(|s| Some(s.trim()).filter(|s| !s.is_empty()))
+    }
+
+    let mut talent_id = given(&tags.talent_id);
+    let booking_id = given(&tags.booking_id);
+    let mut client_id = given(&tags.client_id);
+    let mut booking = BookingRef::default();
+
+    if let Some(Some(id)) = booking_id {
+        let rows: Vec<BookingRef> = fetch(
+            state
+                .pg
+                .from("bookings")
+                .select("talent_id,client_id")
+                .eq("id", id)
+                .eq("agency_user_id", agency_id)
+                .limit(1),
+        )
+        .await?;
+        booking = rows
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::BadRequest("invalid_booking_id".to_string()))?;
+        if talent_id.is_none() {
+            talent_id = booking.talent_id.as_deref().map(Some);
+        }
+        if client_id.is_none() {
+            client_id = booking.client_id.as_deref().map(Some);
+        }
+    }
+    if let Some(Some(id)) = talent_id.filter(|id| *id != booking.talent_id.as_deref()) {
+        ensure_exists(
+            state,
+            "agency_users",
+            "agency_id",
+            agency_id,
+            id,
+            "invalid_talent_id",
+        )
+        .await?;
+    }
+    if let Some(Some(id)) = client_id.filter(|id| *id != booking.client_id.as_deref()) {
+        ensure_exists(
+            state,
+            "agency_clients",
+            "agency_id",
+            agency_id,
+            id,
+            "invalid_client_id",
+        )
+        .await?;
+    }
+
+    let billable = tags
+        .billable
+        .unwrap_or_else(|| current.is_some_and(|e| e.billable));
+    let has_client = match client_id {
+        Some(id) => id.is_some(),
+        None => current.is_some_and(|e| e.client_id.is_some()),
+    };
+    if billable && !has_client {
+        return Err(AppError::BadRequest("billable_requires_client".to_string()));
+    }
+
+    let mut columns = serde_json::Map::new();
+    for (column, value) in [
+        ("talent_id", talent_id),
+        ("booking_id", booking_id),
+        ("client_id", client_id),
+    ] {
+        if let Some(value) = value {
+            columns.insert(column.to_string(), json!(value));
+        }
+    }
+    if tags.billable.is_some() || current.is_none() {
+        columns.insert("billable".to_string(), json!(billable));
+    }
+    Ok(columns)
+}
+
+fn parse_date(value: &str) -> AppResult<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest("invalid_expense_date".to_string()))
+}
 
-    if !status.is_success() {
-        return Err(sanitize_db_error(status.as_u16(), text));
+fn validate_amount(amount_cents: i64) -> AppResult<i64> {
+    if !(0..=i32::MAX as i64).contains(&amount_cents) {
+        return Err(AppError::BadRequest("invalid_amount".to_string()));
     }
+    Ok(amount_cents)
+}
 
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    Ok(Json(v))
+fn required(value: &str, code: &str) -> AppResult<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(AppError::BadRequest(code.to_string()));
+    }
+    Ok(value.to_string())
 }
 
 #[derive(Debug, Deserialize)]
@@ -65,47 +463,688 @@ pub struct CreateExpensePayload {
     pub name: String,
     pub category: String,
     pub expense_date: String,
-    pub amount_cents: Option<i32>,
+    pub amount_cents: Option<i64>,
     pub currency: Option<String>,
+    /// `submitted` to put the expense up for approval; defaults to `approved`.
     pub status: Option<String>,
     pub submitter: Option<String>,
+    pub notes: Option<String>,
+    #[serde(flatten)]
+    pub tags: ExpenseTags,
 }
 
+/// POST /api/expenses
 pub async fn create(
     State(state): State<AppState>,
     user: AuthUser,
     Json(payload): Json<CreateExpensePayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let row = json!({
+) -> AppResult<Json<Expense>> {
+    let status = match payload.status.as_deref().filter(|s| !s.trim().is_empty()) {
+        None => ExpenseStatus::Approved,
+        Some(s) => match ExpenseStatus::parse(s) {
+            Some(status @ (ExpenseStatus::Submitted | ExpenseStatus::Approved)) => status,
+            _ => return Err(AppError::BadRequest("invalid_status".to_string())),
+        },
+    };
+    let mut row = json!({
         "agency_id": user.id,
-        "name": payload.name,
-        "category": payload.category,
-        "expense_date": payload.expense_date,
-        "amount_cents": payload.amount_cents.unwrap_or(0),
-        "currency": payload.currency.unwrap_or_else(|| "USD".to_string()),
-        "status": payload.status.unwrap_or_else(|| "approved".to_string()),
+        "name": required(&payload.name, "missing_name")?,
+        "category": required(&payload.category, "missing_category")?,
+        "expense_date": parse_date(&payload.expense_date)?.to_string(),
+        "amount_cents": validate_amount(payload.amount_cents.unwrap_or(0))?,
+        "currency": payload
+            .currency
+            .as_deref()
+            .map(|c| c.trim().to_ascii_uppercase())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| "USD".to_string()),
+        "status": status.as_str(),
         "submitter": payload.submitter,
+        "notes": payload.notes,
+        "talent_id": null,
+        "booking_id": null,
+        "client_id": null,
+        "rebilled_at": null,
+        "rebilled_invoice_id": null,
     });
+    if status == ExpenseStatus::Approved {
+        row["reviewed_at"] = json!(ts(Utc::now()));
+    }
+    let tags = resolve_tags(&state, &user.id, &payload.tags, None).await?;
+    row.as_object_mut()
+        .expect("expense row is an object")
+        .extend(tags);
 
-    let resp = state
-        .pg
-        .from("agency_expenses")
-        .insert(row.to_string())
-        .execute()
+    let rows: Vec<Expense> = fetch(state.pg.from(TABLE).insert(row.to_string())).await?;
+    let created = rows
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::internal("expenses.create", "insert returned no rows"))?;
+    audit_change(
+        &state,
+        &user,
+        "expense.created",
+        format!("Expense {} created", created.name),
+        None,
+        &created,
+    )
+    .await;
+    Ok(Json(created))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateExpensePayload {
+    pub name: Option<String>,
+    pub category: Option<String>,
+    pub expense_date: Option<String>,
+    pub amount_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub submitter: Option<String>,
+    pub notes: Option<String>,
+    #[serde(flatten)]
+    pub tags: ExpenseTags,
+}
+
+/// PATCH /api/expenses/:id: details and tags. Status changes go through the
+/// workflow actions.
+pub async fn update(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateExpensePayload>,
+) -> AppResult<Json<Expense>> {
+    let current = get_expense(&state, &user.id, &id).await?;
+    current.ensure_editable()?;
+
+    let mut patch = serde_json::Map::new();
+    if let Some(name) = payload.name.as_deref() {
+        patch.insert("name".into(), json!(required(name, "missing_name")?));
+    }
+    if let Some(category) = payload.category.as_deref() {
+        patch.insert(
+            "category".into(),
+            json!(required(category, "missing_category")?),
+        );
+    }
+    if let Some(date) = payload.expense_date.as_deref() {
+        patch.insert("expense_date".into(), json!(parse_date(date)?.to_string()));
+    }
+    if let Some(amount) = payload.amount_cents {
+        patch.insert("amount_cents".into(), json!(validate_amount(amount)?));
+    }
+    if let Some(currency) = payload.currency.as_deref() {
+        patch.insert(
+            "currency".into(),
+            json!(required(currency, "invalid_currency")?.to_ascii_uppercase()),
+        );
+    }
+    if let Some(submitter) = payload.submitter {
+        patch.insert("submitter".into(), json!(submitter));
+    }
+    if let Some(notes) = payload.notes {
+        patch.insert("notes".into(), json!(notes));
+    }
+    patch.extend(resolve_tags(&state, &user.id, &payload.tags, Some(&current)).await?);
+
+    let updated = update_expense(&state, &current, Value::Object(patch)).await?;
+    audit_change(
+        &state,
+        &user,
+        "expense.updated",
+        format!("Expense {} updated", updated.name),
+        Some(&current),
+        &updated,
+    )
+    .await;
+    Ok(Json(updated))
+}
+
+/// DELETE /api/expenses/:id, with its receipts.
+pub async fn delete(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<StatusCode> {
+    let current = get_expense(&state, &user.id, &id).await?;
+    current.ensure_editable()?;
+
+    for receipt in receipts(&state, &current).await? {
+        remove_receipt_object(&state, &receipt).await;
+    }
+    let _: Vec<Value> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .delete()
+            .eq("id", &current.id)
+            .eq("agency_id", &current.agency_id),
+    )
+    .await?;
+    audit::record(
+        &state,
+        AuditEvent::new(&current.agency_id, "expense.deleted", TABLE, &current.id)
+            .actor(&user)
+            .title(format!("Expense {} deleted", current.name))
+            .change(&current, &None::<Expense>),
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReviewPayload {
+    /// Reason for a rejection, or a note on an approval.
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReimbursePayload {
+    /// Check number, transfer id or similar.
+    pub reference: Option<String>,
+}
+
+async fn transition(
+    state: &AppState,
+    user: &AuthUser,
+    id: &str,
+    next: ExpenseStatus,
+    mut patch: Value,
+) -> AppResult<Expense> {
+    let current = get_expense(state, &user.id, id).await?;
+    if !current.status.can_become(next) {
+        return Err(AppError::Conflict(format!(
+            "A {} expense cannot be {}",
+            current.status.as_str(),
+            next.as_str()
+        )));
+    }
+    patch["status"] = json!(next.as_str());
+    let updated = update_expense(state, &current, patch).await?;
+    audit_change(
+        state,
+        user,
+        &format!("expense.{}", next.as_str()),
+        format!("Expense {} {}", updated.name, next.as_str()),
+        Some(&current),
+        &updated,
+    )
+    .await;
+    Ok(updated)
+}
+
+/// POST /api/expenses/:id/submit: puts a rejected expense up for review again.
+pub async fn submit(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<Expense>> {
+    let patch = json!({ "review_note": null, "reviewed_at": null });
+    Ok(Json(
+        transition(&state, &user, &id, ExpenseStatus::Submitted, patch).await?,
+    ))
+}
+
+/// POST /api/expenses/:id/approve
+pub async fn approve(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    payload: Option<Json<ReviewPayload>>,
+) -> AppResult<Json<Expense>> {
+    let note = payload.and_then(|Json(p)| p.note);
+    let patch = json!({ "review_note": note, "reviewed_at": ts(Utc::now()) });
+    Ok(Json(
+        transition(&state, &user, &id, ExpenseStatus::Approved, patch).await?,
+    ))
+}
+
+/// POST /api/expenses/:id/reject
+pub async fn reject(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    payload: Option<Json<ReviewPayload>>,
+) -> AppResult<Json<Expense>> {
+    let note = payload.and_then(|Json(p)| p.note);
+    let patch = json!({ "review_note": note, "reviewed_at": ts(Utc::now()) });
+    Ok(Json(
+        transition(&state, &user, &id, ExpenseStatus::Rejected, patch).await?,
+    ))
+}
+
+/// POST /api/expenses/:id/reimburse
+pub async fn reimburse(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    payload: Option<Json<ReimbursePayload>>,
+) -> AppResult<Json<Expense>> {
+    let reference = payload.and_then(|Json(p)| p.reference);
+    let patch = json!({
+        "reimbursed_at": ts(Utc::now()),
+        "reimbursement_reference": reference,
+    });
+    Ok(Json(
+        transition(&state, &user, &id, ExpenseStatus::Reimbursed, patch).await?,
+    ))
+}
+
+/// POST /api/expenses/:id/receipts (multipart `file`): an image or PDF.
+pub async fn upload_receipt(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    mut multipart: Multipart,
+) -> AppResult<Json<ExpenseReceipt>> {
+    let expense = get_expense(&state, &user.id, &id).await?;
+
+    let mut upload: Option<(String, Vec<u8>)> = None;
+    while let Some(field) = multipart
+        .next_field()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::BadRequest(e.to_string()))?
+    {
+        if field.name() == Some("file") {
+            let file_name = field.file_name().unwrap_or("receipt").to_string();
+            let data = field
+                .bytes()
+                .await
+                .map_err(|e| AppError::BadRequest(e.to_string()))?;
+            upload = Some((file_name, data.to_vec()));
+            break;
+        }
+    }
+    let (file_name, bytes) = upload
+        .filter(|(_, bytes)| !bytes.is_empty())
+        .ok_or_else(|| AppError::BadRequest("missing_file".to_string()))?;
+    if bytes.len() > MAX_RECEIPT_BYTES {
+        return Err(AppError::BadRequest("receipt_too_large".to_string()));
+    }
+    // Neither the declared type nor the extension is trusted: receipts are
+    // identified by their contents.
+    let content_type = sniff_content_type(&bytes, None, None);
+    if !RECEIPT_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Err(AppError::BadRequest("unsupported_receipt_type".to_string()));
+    }
+
+    let bucket = state.bucket(Bucket::Private);
+    let path = format!(
+        "agencies/{}/expenses/{}/receipts/{}_{}",
+        sanitize_segment(&expense.agency_id),
+        sanitize_segment(&expense.id),
+        Utc::now().timestamp_millis(),
+        sanitize_file_name(&file_name)
+    );
+    let size_bytes = bytes.len();
+    state
+        .storage
+        .put(&bucket, &path, bytes.into(), &content_type, false)
+        .await?;
 
-    let status = resp.status();
-    let text = resp
-        .text()
+    let row = json!({
+        "agency_id": expense.agency_id,
+        "expense_id": expense.id,
+        "file_name": file_name,
+        "content_type": content_type,
+        "size_bytes": size_bytes,
+        "storage_bucket": bucket,
+        "storage_path": path,
+    });
+    let rows: Vec<ExpenseReceipt> =
+        fetch(state.pg.from(RECEIPTS_TABLE).insert(row.to_string())).await?;
+    let receipt = rows
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::internal("expenses.receipt", "insert returned no rows"))?;
+    audit::record(
+        &state,
+        AuditEvent::new(
+            &expense.agency_id,
+            "expense.receipt_added",
+            TABLE,
+            &expense.id,
+        )
+        .actor(&user)
+        .title(format!(
+            "Receipt {} added to expense {}",
+            receipt.file_name, expense.name
+        )),
+    )
+    .await;
+    Ok(Json(receipt))
+}
+
+async fn get_receipt(
+    state: &AppState,
+    agency_id: &str,
+    expense_id: &str,
+    receipt_id: &str,
+) -> AppResult<ExpenseReceipt> {
+    let rows: Vec<ExpenseReceipt> = fetch(
+        state
+            .pg
+            .from(RECEIPTS_TABLE)
+            .select("*")
+            .eq("id", receipt_id)
+            .eq("expense_id", expense_id)
+            .eq("agency_id", agency_id),
+    )
+    .await?;
+    rows.into_iter()
+        .next()
+        .ok_or_else(|| AppError::NotFound("Receipt not found".to_string()))
+}
+
+/// GET /api/expenses/:id/receipts/:receipt_id: the file itself.
+pub async fn receipt_file(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path((id, receipt_id)): Path<(String, String)>,
+) -> AppResult<Response> {
+    let receipt = get_receipt(&state, &user.id, &id, &receipt_id).await?;
+    let bytes = state
+        .storage
+        .get(&receipt.storage_bucket, &receipt.storage_path)
+        .await?
+        .to_vec();
+    Ok((
+        [
+            (header::CONTENT_TYPE, receipt.content_type.clone()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "inline; filename=\"{}\"",
+                    sanitize_file_name(&receipt.file_name)
+                ),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+async fn remove_receipt_object(state: &AppState, receipt: &ExpenseReceipt) {
+    if let Err(e) = state
+        .storage
+        .delete(&receipt.storage_bucket, &receipt.storage_path)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    {
+        warn!(receipt_id = %receipt.id, error = %e, "failed to delete expense receipt object");
+    }
+}
+
+/// DELETE /api/expenses/:id/receipts/:receipt_id
+pub async fn delete_receipt(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path((id, receipt_id)): Path<(String, String)>,
+) -> AppResult<StatusCode> {
+    let expense = get_expense(&state, &user.id, &id).await?;
+    expense.ensure_editable()?;
+    let receipt = get_receipt(&state, &user.id, &id, &receipt_id).await?;
+    remove_receipt_object(&state, &receipt).await;
+    let _: Vec<Value> = fetch(
+        state
+            .pg
+            .from(RECEIPTS_TABLE)
+            .delete()
+            .eq("id", &receipt.id)
+            .eq("agency_id", &receipt.agency_id),
+    )
+    .await?;
+    audit::record(
+        &state,
+        AuditEvent::new(
+            &expense.agency_id,
+            "expense.receipt_removed",
+            TABLE,
+            &expense.id,
+        )
+        .actor(&user)
+        .title(format!(
+            "Receipt {} removed from expense {}",
+            receipt.file_name, expense.name
+        )),
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RebillPayload {
+    pub client_id: String,
+    /// Limits the rebill to these expenses; by default every approved,
+    /// billable expense of the client that has not been rebilled.
+    pub expense_ids: Option<Vec<String>>,
+    pub invoice_date: Option<String>,
+    pub due_date: Option<String>,
+    pub payment_terms: Option<String>,
+    pub notes_internal: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RebillResult {
+    pub invoice: Value,
+    pub expenses: Vec<Expense>,
+}
+
+/// The expenses to rebill, all in one currency.
+fn rebillable(
+    candidates: Vec<Expense>,
+    client_id: &str,
+    ids: Option<&[String]>,
+) -> AppResult<Vec<Expense>> {
+    let eligible = |e: &Expense| {
+        e.status.is_approved()
+            && e.billable
+            && !e.is_rebilled()
+            && e.client_id.as_deref() == Some(client_id)
+    };
+    let selected: Vec<Expense> = match ids {
+        Some(ids) => {
+            let mut selected = Vec::with_capacity(ids.len());
+            for id in ids {
+                let expense = candidates
+                    .iter()
+                    .find(|e| &e.id == id)
+                    .ok_or_else(|| AppError::NotFound(format!("Expense {id} not found")))?;
+                if !eligible(expense) {
+                    return Err(AppError::Conflict(format!(
+                        "Expense {} is not an approved, billable expense of this client that is still to be rebilled",
+                        expense.name
+                    )));
+                }
+                selected.push(expense.clone());
+            }
+            selected
+        }
+        None => candidates.into_iter().filter(eligible).collect(),
+    };
+    if selected.is_empty() {
+        return Err(AppError::BadRequest("no_rebillable_expenses".to_string()));
+    }
+    if selected.iter().any(|e| e.currency != selected[0].currency) {
+        return Err(AppError::BadRequest("mixed_currencies".to_string()));
+    }
+    Ok(selected)
+}
+
+/// Claimed expenses are put back when the draft could not be created.
+async fn release_claim(state: &AppState, agency_id: &str, ids: &[String]) {
+    let patch = json!({ "rebilled_at": null, "updated_at": ts(Utc::now()) });
+    let result: Result<Vec<Value>, RepoError> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .update(patch.to_string())
+            .eq("agency_id", agency_id)
+            .in_("id", ids)
+            .is("rebilled_invoice_id", "null"),
+    )
+    .await;
+    if let Err(e) = result {
+        warn!(agency_id, error = %e, "failed to release rebill claim on expenses");
+    }
+}
+
+/// POST /api/expenses/rebill: bills a client's approved, billable expenses
+/// as expense lines on a new draft invoice.
+pub async fn rebill(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<RebillPayload>,
+) -> AppResult<Json<RebillResult>> {
+    let client_id = required(&payload.client_id, "missing_client_id")?;
+    let candidates: Vec<Expense> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .select("*")
+            .eq("agency_id", &user.id)
+            .eq("client_id", &client_id)
+            .order("expense_date.asc"),
+    )
+    .await?;
+    let selected = rebillable(candidates, &client_id, payload.expense_ids.as_deref())?;
+    let ids: Vec<String> = selected.iter().map(|e| e.id.clone()).collect();
+
+    let claimed_at = ts(Utc::now());
+    let claimed: Vec<Expense> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .update(json!({ "rebilled_at": claimed_at, "updated_at": claimed_at }).to_string())
+            .eq("agency_id", &user.id)
+            .in_("id", &ids)
+            .is("rebilled_at", "null"),
+    )
+    .await?;
+    if claimed.len() != ids.len() {
+        let mine: Vec<String> = claimed.into_iter().map(|e| e.id).collect();
+        release_claim(&state, &user.id, &mine).await;
+        return Err(AppError::Conflict(
+            "Some of these expenses are being rebilled already".to_string(),
+        ));
+    }
+
+    let draft = CreateInvoicePayload {
+        client_id,
+        invoice_date: payload.invoice_date,
+        due_date: payload.due_date,
+        payment_terms: payload.payment_terms,
+        currency: Some(selected[0].currency.clone()),
+        notes_internal: payload.notes_internal,
+        expenses: Some(selected.iter().map(Expense::rebill_line).collect()),
+        ..Default::default()
+    };
+    let invoice = match create_draft(&state, &user.id, draft).await {
+        Ok(invoice) => invoice,
+        Err(e) => {
+            release_claim(&state, &user.id, &ids).await;
+            return Err(e);
+        }
+    };
+    let invoice_id = invoice
+        .get("id")
+        .and_then(Value::as_str)
+        .ok_or_else(|| AppError::internal("expenses.rebill", "draft has no id"))?;
 
-    if !status.is_success() {
-        return Err(sanitize_db_error(status.as_u16(), text));
+    let patch = json!({ "rebilled_invoice_id": invoice_id, "updated_at": ts(Utc::now()) });
+    let mut expenses: Vec<Expense> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .update(patch.to_string())
+            .eq("agency_id", &user.id)
+            .in_("id", &ids),
+    )
+    .await?;
+    expenses.sort_by(|a, b| (a.expense_date, &a.id).cmp(&(b.expense_date, &b.id)));
+    for after in &expenses {
+        audit_change(
+            &state,
+            &user,
+            "expense.rebilled",
+            format!("Expense {} rebilled on a draft invoice", after.name),
+            selected.iter().find(|e| e.id == after.id),
+            after,
+        )
+        .await;
     }
+    Ok(Json(RebillResult { invoice, expenses }))
+}
+
+/// Makes the expenses rebilled on a voided invoice billable again.
+pub(crate) async fn release_rebilled(
+    state: &AppState,
+    agency_id: &str,
+    invoice_id: &str,
+) -> Result<Vec<Expense>, RepoError> {
+    let patch = json!({
+        "rebilled_invoice_id": null,
+        "rebilled_at": null,
+        "updated_at": ts(Utc::now()),
+    });
+    fetch(
+        state
+            .pg
+            .from(TABLE)
+            .update(patch.to_string())
+            .eq("agency_id", agency_id)
+            .eq("rebilled_invoice_id", invoice_id),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    Ok(Json(v))
+    #[test]
+    fn workflow_only_allows_review_then_reimbursement() {
+        use ExpenseStatus::*;
+        assert!(Submitted.can_become(Approved));
+        assert!(Submitted.can_become(Rejected));
+        assert!(Rejected.can_become(Submitted));
+        assert!(Approved.can_become(Reimbursed));
+        assert!(!Rejected.can_become(Reimbursed));
+        assert!(!Submitted.can_become(Reimbursed));
+        assert!(!Reimbursed.can_become(Approved));
+        assert_eq!(ExpenseStatus::parse("Pending"), Some(Submitted));
+    }
+
+    fn expense(id: &str, status: ExpenseStatus, currency: &str) -> Expense {
+        serde_json::from_value(json!({
+            "id": id,
+            "agency_id": "a",
+            "name": id,
+            "category": "travel",
+            "expense_date": "2026-09-01",
+            "amount_cents": 1000,
+            "currency": currency,
+            "status": status,
+            "client_id": "c",
+            "billable": true,
+            "created_at": "2026-09-01T00:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn rebill_takes_approved_billable_expenses_in_one_currency() {
+        use ExpenseStatus::*;
+        let candidates = vec![
+            expense("a", Approved, "USD"),
+            expense("b", Submitted, "USD"),
+            expense("c", Reimbursed, "USD"),
+        ];
+        let picked = rebillable(candidates.clone(), "c", None).unwrap();
+        let ids: Vec<&str> = picked.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "c"]);
+        assert!(rebillable(candidates.clone(), "c", Some(&["b".to_string()])).is_err());
+        assert!(rebillable(candidates, "other", None).is_err());
+
+        let mixed = vec![expense("a", Approved, "USD"), expense("b", Approved, "EUR")];
+        assert!(rebillable(mixed, "c", None).is_err());
+    }
 }
@@ -0,0 +1,418 @@
+//! Automated invoice dunning.
+//!
+//! Once a day sent invoices past their due date move to `overdue`, and
+//! clients get up to three reminders following the agency's
+//! `agency_invoice_reminder_settings`: three days before the due date, on it,
+//! and seven days after. Every reminder is claimed in
+//! `agency_invoice_reminder_events` before it is queued, and the table's
+//! unique `(invoice_id, reminder_type, scheduled_for)` key keeps a reminder
+//! from going out twice. Reminders that failed to queue are retried on the
+//! next run.
+
+use crate::config::AppState;
+use crate::invoices::{payment_reminder_email, PaymentReminderCopy};
+use crate::jobs::runner::ts;
+use crate::repositories::{fetch, Invoice, InvoiceStatus, RepoError};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::warn;
+
+pub const SETTINGS_TABLE: &str = "agency_invoice_reminder_settings";
+pub const EVENTS_TABLE: &str = "agency_invoice_reminder_events";
+const INVOICES_TABLE: &str = "agency_invoices";
+
+/// How long a reminder stays sendable after its date, so a missed run still
+/// sends it without mailing clients about long-settled stages.
+const STAGE_WINDOW_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderType {
+    Before3Days,
+    OnDueDate,
+    After7Days,
+}
+
+impl ReminderType {
+    pub const ALL: [ReminderType; 3] = [
+        ReminderType::Before3Days,
+        ReminderType::OnDueDate,
+        ReminderType::After7Days,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReminderType::Before3Days => "before_3_days",
+            ReminderType::OnDueDate => "on_due_date",
+            ReminderType::After7Days => "after_7_days",
+        }
+    }
+
+    /// Days from the due date to this reminder.
+    fn offset_days(&self) -> i64 {
+        match self {
+            ReminderType::Before3Days => -3,
+            ReminderType::OnDueDate => 0,
+            ReminderType::After7Days => 7,
+        }
+    }
+
+    pub fn scheduled_for(&self, due_date: NaiveDate) -> NaiveDate {
+        due_date + Duration::days(self.offset_days())
+    }
+
+    /// Agency template tried before the generic `payment_reminder` one.
+    fn template_key(&self) -> &'static str {
+        match self {
+            ReminderType::Before3Days => "payment_reminder_before_due",
+            ReminderType::OnDueDate => "payment_reminder_due_today",
+            ReminderType::After7Days => "payment_reminder_overdue",
+        }
+    }
+
+    fn fallback(&self) -> PaymentReminderCopy {
+        match self {
+            ReminderType::Before3Days => PaymentReminderCopy {
+                subject: "Payment Reminder - Invoice {invoice_number} due {due_date}",
+                body: "Dear {client_name},\n\nThis is a friendly reminder that invoice {invoice_number} for {invoice_total} is due on {due_date}.\n\nIf you have already made the payment, please disregard this message.\n\nThank you,\n{agency_name}",
+            },
+            ReminderType::OnDueDate => PaymentReminderCopy {
+                subject: "Invoice {invoice_number} is due today",
+                body: "Dear {client_name},\n\nInvoice {invoice_number} for {invoice_total} is due today ({due_date}).\n\nIf you have already made the payment, please disregard this message.\n\nThank you,\n{agency_name}",
+            },
+            ReminderType::After7Days => PaymentReminderCopy {
+                subject: "Overdue: Invoice {invoice_number}",
+                body: "Dear {client_name},\n\nInvoice {invoice_number} for {invoice_total} was due on {due_date} and is now {days_overdue} days overdue.\n\nPlease arrange payment at your earliest convenience. If you have already paid, please disregard this message.\n\nThank you,\n{agency_name}",
+            },
+        }
+    }
+
+    /// The reminder whose sending window contains `today`, if any. Windows
+    /// end at the next reminder's date, or a week after the last one.
+    pub fn due_on(due_date: NaiveDate, today: NaiveDate) -> Option<ReminderType> {
+        let mut stages = Self::ALL.iter().peekable();
+        while let Some(stage) = stages.next() {
+            let start = stage.scheduled_for(due_date);
+            let end = stages
+                .peek()
+                .map(|next| next.scheduled_for(due_date))
+                .unwrap_or(start + Duration::days(STAGE_WINDOW_DAYS));
+            if start <= today && today < end {
+                return Some(*stage);
+            }
+        }
+        None
+    }
+}
+
+/// An agency's reminder toggles; agencies without a row get every reminder.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReminderSettings {
+    pub agency_id: String,
+    pub enabled_3_days_before: bool,
+    pub enabled_on_due_date: bool,
+    pub enabled_7_days_after: bool,
+}
+
+impl ReminderSettings {
+    pub fn enabled(&self, reminder: ReminderType) -> bool {
+        match reminder {
+            ReminderType::Before3Days => self.enabled_3_days_before,
+            ReminderType::OnDueDate => self.enabled_on_due_date,
+            ReminderType::After7Days => self.enabled_7_days_after,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ReminderEvent {
+    id: String,
+    invoice_id: String,
+    reminder_type: String,
+    scheduled_for: NaiveDate,
+    status: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub date: NaiveDate,
+    pub marked_overdue: usize,
+    pub sent: usize,
+    pub already_sent: usize,
+    pub disabled: usize,
+    pub failed: usize,
+}
+
+/// Moves unpaid invoices due before `today` to `overdue`.
+async fn mark_overdue(state: &AppState, today: NaiveDate) -> Result<usize, RepoError> {
+    let due: Vec<Invoice> = fetch(
+        state
+            .pg
+            .from(INVOICES_TABLE)
+            .select("*")
+            .in_(
+                "status",
+                vec![
+                    InvoiceStatus::Sent.as_str(),
+                    InvoiceStatus::PartiallyPaid.as_str(),
+                ],
+            )
+            .lt("due_date", today.to_string()),
+    )
+    .await?;
+    for invoice in &due {
+        let updated = state
+            .repos
+            .invoices
+            .set_status(
+                &invoice.agency_id,
+                &invoice.id,
+                InvoiceStatus::Overdue,
+                Utc::now(),
+            )
+            .await?;
+        crate::audit::record(
+            state,
+            crate::audit::AuditEvent::new(
+                &invoice.agency_id,
+                "invoice.status_changed",
+                INVOICES_TABLE,
+                &invoice.id,
+            )
+            .title(format!("Invoice {} marked overdue", invoice.invoice_number))
+            .change(invoice, &updated),
+        )
+        .await;
+    }
+    Ok(due.len())
+}
+
+async fn load_settings(
+    state: &AppState,
+    agency_ids: &[&str],
+) -> Result<HashMap<String, ReminderSettings>, RepoError> {
+    if agency_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let rows: Vec<ReminderSettings> = fetch(
+        state
+            .pg
+            .from(SETTINGS_TABLE)
+            .select("agency_id,enabled_3_days_before,enabled_on_due_date,enabled_7_days_after")
+            .in_("agency_id", agency_ids.to_vec()),
+    )
+    .await?;
+    Ok(rows.into_iter().map(|s| (s.agency_id.clone(), s)).collect())
+}
+
+async fn load_events(
+    state: &AppState,
+    invoice_ids: &[&str],
+) -> Result<Vec<ReminderEvent>, RepoError> {
+    if invoice_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    fetch(
+        state
+            .pg
+            .from(EVENTS_TABLE)
+            .select("id,invoice_id,reminder_type,scheduled_for,status")
+            .in_("invoice_id", invoice_ids.to_vec()),
+    )
+    .await
+}
+
+/// Claims the reminder for this run. A failed earlier attempt is taken over;
+/// anything else already recorded means the reminder is not ours to send.
+async fn claim(
+    state: &AppState,
+    invoice: &Invoice,
+    reminder: ReminderType,
+    scheduled_for: NaiveDate,
+    to_email: &str,
+    previous: Option<&ReminderEvent>,
+) -> Result<Option<String>, RepoError> {
+    let row = json!({
+        "agency_id": invoice.agency_id,
+        "invoice_id": invoice.id,
+        "reminder_type": reminder.as_str(),
+        "scheduled_for": scheduled_for.to_string(),
+        "sent_at": ts(Utc::now()),
+        "to_email": to_email,
+        "status": "sent",
+        "error": null,
+    });
+    let claimed: Result<Vec<ReminderEvent>, RepoError> = match previous {
+        Some(event) => {
+            fetch(
+                state
+                    .pg
+                    .from(EVENTS_TABLE)
+                    .update(row.to_string())
+                    .eq("id", &event.id)
+                    .eq("status", "failed"),
+            )
+            .await
+        }
+        None => fetch(state.pg.from(EVENTS_TABLE).insert(row.to_string())).await,
+    };
+    match claimed {
+        Ok(rows) => Ok(rows.into_iter().next().map(|e| e.id)),
+        // Another runner recorded this reminder first.
+        Err(RepoError::Db { status: 409, .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn record_failure(state: &AppState, event_id: &str, error: &str) -> Result<(), RepoError> {
+    let _: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from(EVENTS_TABLE)
+            .update(json!({ "status": "failed", "error": error }).to_string())
+            .eq("id", event_id),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Marks overdue invoices and sends the reminders due on `today`.
+pub async fn run(state: &AppState, today: NaiveDate) -> Result<Summary, RepoError> {
+    let mut summary = Summary {
+        date: today,
+        marked_overdue: mark_overdue(state, today).await?,
+        ..Default::default()
+    };
+
+    // Every reminder window lies between three days before and two weeks
+    // after the due date.
+    let earliest =
+        today - Duration::days(ReminderType::After7Days.offset_days() + STAGE_WINDOW_DAYS);
+    let latest = today - Duration::days(ReminderType::Before3Days.offset_days());
+    let invoices: Vec<Invoice> = fetch(
+        state
+            .pg
+            .from(INVOICES_TABLE)
+            .select("*")
+            .in_(
+                "status",
+                vec![
+                    InvoiceStatus::Sent.as_str(),
+                    InvoiceStatus::PartiallyPaid.as_str(),
+                    InvoiceStatus::Overdue.as_str(),
+                ],
+            )
+            .gt("due_date", earliest.to_string())
+            .lte("due_date", latest.to_string())
+            .order("due_date.asc"),
+    )
+    .await?;
+
+    let mut agency_ids: Vec<&str> = invoices.iter().map(|i| i.agency_id.as_str()).collect();
+    agency_ids.sort();
+    agency_ids.dedup();
+    let settings = load_settings(state, &agency_ids).await?;
+    let invoice_ids: Vec<&str> = invoices.iter().map(|i| i.id.as_str()).collect();
+    let events = load_events(state, &invoice_ids).await?;
+
+    for invoice in &invoices {
+        let Some(reminder) = ReminderType::due_on(invoice.due_date, today) else {
+            continue;
+        };
+        if settings
+            .get(&invoice.agency_id)
+            .is_some_and(|s| !s.enabled(reminder))
+        {
+            summary.disabled += 1;
+            continue;
+        }
+        let Some(dest) = invoice
+            .bill_to_email
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        else {
+            continue;
+        };
+        let scheduled_for = reminder.scheduled_for(invoice.due_date);
+        let previous = events.iter().find(|e| {
+            e.invoice_id == invoice.id
+                && e.reminder_type == reminder.as_str()
+                && e.scheduled_for == scheduled_for
+        });
+        if previous.is_some_and(|e| e.status != "failed") {
+            summary.already_sent += 1;
+            continue;
+        }
+        let Some(event_id) = claim(state, invoice, reminder, scheduled_for, dest, previous).await?
+        else {
+            summary.already_sent += 1;
+            continue;
+        };
+
+        let queued = match payment_reminder_email(
+            state,
+            invoice,
+            dest,
+            &[reminder.template_key(), "payment_reminder"],
+            &reminder.fallback(),
+        )
+        .await
+        {
+            Ok(message) => crate::email::enqueue(state, message).await,
+            Err(e) => Err(e),
+        };
+        match queued {
+            Ok(_) => summary.sent += 1,
+            Err(e) => {
+                warn!(invoice_id = %invoice.id, reminder = reminder.as_str(), error = %e, "invoice reminder failed");
+                summary.failed += 1;
+                record_failure(state, &event_id, &e.to_string()).await?;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Runs the dunning pass for the current UTC date.
+pub async fn run_today(state: &AppState) -> Result<Summary, RepoError> {
+    run(state, Utc::now().date_naive()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn each_day_falls_in_at_most_one_reminder_window() {
+        let due = date("2026-10-20");
+        let at = |d: &str| ReminderType::due_on(due, date(d));
+        assert_eq!(at("2026-10-16"), None);
+        assert_eq!(at("2026-10-17"), Some(ReminderType::Before3Days));
+        assert_eq!(at("2026-10-19"), Some(ReminderType::Before3Days));
+        assert_eq!(at("2026-10-20"), Some(ReminderType::OnDueDate));
+        assert_eq!(at("2026-10-26"), Some(ReminderType::OnDueDate));
+        assert_eq!(at("2026-10-27"), Some(ReminderType::After7Days));
+        assert_eq!(at("2026-11-02"), Some(ReminderType::After7Days));
+        assert_eq!(at("2026-11-03"), None);
+    }
+
+    #[test]
+    fn settings_gate_each_reminder() {
+        let settings = ReminderSettings {
+            agency_id: "agency-1".into(),
+            enabled_3_days_before: false,
+            enabled_on_due_date: true,
+            enabled_7_days_after: false,
+        };
+        assert!(!settings.enabled(ReminderType::Before3Days));
+        assert!(settings.enabled(ReminderType::OnDueDate));
+        assert!(!settings.enabled(ReminderType::After7Days));
+    }
+}
@@ -0,0 +1,529 @@
+//! Payments and credit notes against agency invoices.
+//!
+//! An invoice's balance is its total less the payments recorded against it
+//! and the credit notes issued on it. Every change to either list goes
+//! through [`settle`], which stores the sums on the invoice and moves it
+//! between `sent`, `partially_paid`, `overdue` and `paid`.
+
+use crate::{
+    audit::{self, AuditEvent},
+    auth::AuthUser,
+    config::AppState,
+    errors::{AppError, AppResult},
+    repositories::{
+        Invoice, InvoiceCreditNote, InvoicePayment, InvoiceSettlement, InvoiceStatus,
+        NewInvoiceCreditNote, NewInvoicePayment, RepoError,
+    },
+};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const PAYMENT_METHODS: [&str; 6] =
+    ["bank_transfer", "card", "check", "cash", "stripe", "other"];
+
+/// Derives the invoice's payment state from its payments and credit notes.
+/// Draft and void invoices keep their status. An invoice is paid once its
+/// payments cover what the credit notes leave; credit notes covering all of
+/// it with nothing paid make it credited, which never sets `paid_at`.
+pub fn settlement(
+    invoice: &Invoice,
+    payments: &[InvoicePayment],
+    credit_notes: &[InvoiceCreditNote],
+    today: NaiveDate,
+) -> InvoiceSettlement {
+    let paid: i64 = payments.iter().map(|p| p.amount_cents as i64).sum();
+    let credited: i64 = credit_notes.iter().map(|c| c.amount_cents as i64).sum();
+    let balance = invoice.total_cents as i64 - paid - credited;
+    let last_payment_at = payments.iter().map(|p| p.paid_on).max().map(day_start);
+
+    let status = match invoice.status {
+        InvoiceStatus::Draft | InvoiceStatus::Void => invoice.status,
+        _ if balance <= 0 && paid == 0 && credited > 0 => InvoiceStatus::Credited,
+        _ if balance <= 0 => InvoiceStatus::Paid,
+        _ if invoice.due_date < today => InvoiceStatus::Overdue,
+        _ if paid > 0 => InvoiceStatus::PartiallyPaid,
+        _ => InvoiceStatus::Sent,
+    };
+    let paid_at = match status {
+        InvoiceStatus::Paid => Some(invoice.paid_at.or(last_payment_at).unwrap_or_else(Utc::now)),
+        _ => None,
+    };
+    InvoiceSettlement {
+        status,
+        amount_paid_cents: paid as i32,
+        amount_credited_cents: credited as i32,
+        last_payment_at,
+        paid_at,
+    }
+}
+
+/// Recomputes and stores the invoice's balance after its payments or credit
+/// notes changed.
+pub async fn settle(state: &AppState, invoice: &Invoice) -> Result<Invoice, RepoError> {
+    let payments = state.repos.invoices.payments(&invoice.id).await?;
+    let credit_notes = state.repos.invoices.credit_notes(&invoice.id).await?;
+    let settled = settlement(invoice, &payments, &credit_notes, Utc::now().date_naive());
+    state
+        .repos
+        .invoices
+        .set_settlement(&invoice.agency_id, &invoice.id, &settled)
+        .await
+}
+
+fn parse_day(value: Option<&str>, field: &str) -> AppResult<NaiveDate> {
+    match value.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .map_err(|_| AppError::BadRequest(format!("invalid_{field}"))),
+        None => Ok(Utc::now().date_naive()),
+    }
+}
+
+fn audit_event(
+    user: &AuthUser,
+    event_type: &str,
+    title: String,
+    before: &Invoice,
+    after: &Invoice,
+) -> AuditEvent {
+    AuditEvent::new(&user.id, event_type, "agency_invoices", &after.id)
+        .actor(user)
+        .title(title)
+        .change(before, after)
+}
+
+async fn outstanding_invoice(state: &AppState, user: &AuthUser, id: &str) -> AppResult<Invoice> {
+    let invoice = state.repos.invoices.get_for_agency(&user.id, id).await?;
+    if !invoice.status.is_outstanding() {
+        return Err(AppError::Conflict(
+            "Only sent, unpaid invoices accept payments and credit notes".to_string(),
+        ));
+    }
+    Ok(invoice)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecordPaymentPayload {
+    pub amount_cents: i64,
+    pub paid_on: Option<String>,
+    pub method: Option<String>,
+    pub reference: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentRecorded {
+    pub invoice: Invoice,
+    pub payment: InvoicePayment,
+}
+
+/// Records `payment` against `invoice` and settles it.
+pub(crate) async fn record(
+    state: &AppState,
+    user: &AuthUser,
+    invoice: &Invoice,
+    payment: NewInvoicePayment,
+) -> AppResult<PaymentRecorded> {
+    let payment = state.repos.invoices.insert_payment(&payment).await?;
+    let updated = settle(state, invoice).await?;
+    audit::record(
+        state,
+        audit_event(
+            user,
+            "invoice.payment_recorded",
+            format!(
+                "Payment of {} recorded on invoice {}",
+                crate::invoice_pdf::money(payment.amount_cents as i64, &invoice.currency),
+                invoice.invoice_number
+            ),
+            invoice,
+            &updated,
+        ),
+    )
+    .await;
+    Ok(PaymentRecorded {
+        invoice: updated,
+        payment,
+    })
+}
+
+/// GET /api/invoices/:id/payments
+pub async fn list_payments(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<Vec<InvoicePayment>>> {
+    let invoice = state.repos.invoices.get_for_agency(&user.id, &id).await?;
+    Ok(Json(state.repos.invoices.payments(&invoice.id).await?))
+}
+
+/// POST /api/invoices/:id/payments: records a full or partial payment.
+pub async fn record_payment(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    Json(payload): Json<RecordPaymentPayload>,
+) -> AppResult<Json<PaymentRecorded>> {
+    let invoice = outstanding_invoice(&state, &user, &id).await?;
+    if payload.amount_cents <= 0 {
+        return Err(AppError::BadRequest("invalid_amount".to_string()));
+    }
+    if payload.amount_cents > invoice.balance_due_cents() {
+        return Err(AppError::Conflict(
+            "Payment exceeds the outstanding balance".to_string(),
+        ));
+    }
+    let method = payload
+        .method
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .unwrap_or("bank_transfer");
+    if !PAYMENT_METHODS.contains(&method) {
+        return Err(AppError::BadRequest("invalid_payment_method".to_string()));
+    }
+    let payment = NewInvoicePayment {
+        agency_id: user.id.clone(),
+        invoice_id: invoice.id.clone(),
+        amount_cents: payload.amount_cents as i32,
+        paid_on: parse_day(payload.paid_on.as_deref(), "paid_on")?,
+        method: method.to_string(),
+        reference: payload.reference.filter(|s| !s.trim().is_empty()),
+        notes: payload.notes.filter(|s| !s.trim().is_empty()),
+        recorded_by: Some(user.id.clone()),
+    };
+    Ok(Json(record(&state, &user, &invoice, payment).await?))
+}
+
+/// DELETE /api/invoices/:id/payments/:payment_id: removes a payment recorded
+/// in error, reopening the balance it covered.
+pub async fn delete_payment(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path((id, payment_id)): Path<(String, String)>,
+) -> AppResult<Json<Invoice>> {
+    let invoice = state.repos.invoices.get_for_agency(&user.id, &id).await?;
+    if matches!(invoice.status, InvoiceStatus::Draft | InvoiceStatus::Void) {
+        return Err(AppError::Conflict(
+            "Payments cannot be changed on draft or void invoices".to_string(),
+        ));
+    }
+    match state
+        .repos
+        .invoices
+        .delete_payment(&invoice.id, &payment_id)
+        .await
+    {
+        Err(RepoError::NotFound) => {
+            return Err(AppError::NotFound("payment_not_found".to_string()))
+        }
+        other => other?,
+    }
+    let updated = settle(&state, &invoice).await?;
+    audit::record(
+        &state,
+        audit_event(
+            &user,
+            "invoice.payment_deleted",
+            format!("Payment removed from invoice {}", invoice.invoice_number),
+            &invoice,
+            &updated,
+        ),
+    )
+    .await;
+    Ok(Json(updated))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueCreditNotePayload {
+    pub amount_cents: i64,
+    pub reason: String,
+    pub invoice_item_id: Option<String>,
+    pub issued_on: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreditNoteIssued {
+    pub invoice: Invoice,
+    pub credit_note: InvoiceCreditNote,
+}
+
+/// GET /api/invoices/:id/credit-notes
+pub async fn list_credit_notes(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<Vec<InvoiceCreditNote>>> {
+    let invoice = state.repos.invoices.get_for_agency(&user.id, &id).await?;
+    Ok(Json(state.repos.invoices.credit_notes(&invoice.id).await?))
+}
+
+/// POST /api/invoices/:id/credit-notes: credits part of the invoice, or of a
+/// single disputed line, reducing the balance and the talent statements.
+pub async fn issue_credit_note(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    Json(payload): Json<IssueCreditNotePayload>,
+) -> AppResult<Json<CreditNoteIssued>> {
+    let invoice = outstanding_invoice(&state, &user, &id).await?;
+    if payload.amount_cents <= 0 {
+        return Err(AppError::BadRequest("invalid_amount".to_string()));
+    }
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err(AppError::BadRequest("missing_reason".to_string()));
+    }
+    if payload.amount_cents > invoice.balance_due_cents() {
+        return Err(AppError::Conflict(
+            "Credit exceeds the outstanding balance".to_string(),
+        ));
+    }
+    let existing = state.repos.invoices.credit_notes(&invoice.id).await?;
+    let item_id = payload
+        .invoice_item_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    if let Some(item_id) = item_id {
+        let items = state.repos.invoices.items(&invoice.id).await?;
+        let item = items
+            .iter()
+            .find(|it| it.id == item_id)
+            .ok_or_else(|| AppError::BadRequest("invalid_invoice_item".to_string()))?;
+        let already: i64 = existing
+            .iter()
+            .filter(|c| c.invoice_item_id.as_deref() == Some(item_id))
+            .map(|c| c.amount_cents as i64)
+            .sum();
+        if already + payload.amount_cents > item.line_total_cents as i64 {
+            return Err(AppError::Conflict(
+                "Credit exceeds the line item total".to_string(),
+            ));
+        }
+    }
+
+    let note = NewInvoiceCreditNote {
+        agency_id: user.id.clone(),
+        invoice_id: invoice.id.clone(),
+        invoice_item_id: item_id.map(str::to_string),
+        credit_note_number: format!("{}-CN{}", invoice.invoice_number, existing.len() + 1),
+        issued_on: parse_day(payload.issued_on.as_deref(), "issued_on")?,
+        amount_cents: payload.amount_cents as i32,
+        reason: reason.to_string(),
+        issued_by: Some(user.id.clone()),
+    };
+    let credit_note = match state.repos.invoices.insert_credit_note(&note).await {
+        Err(RepoError::Db { status: 409, .. }) => {
+            return Err(AppError::Conflict(
+                "Another credit note was issued at the same time; retry".to_string(),
+            ))
+        }
+        other => other?,
+    };
+    let updated = settle(&state, &invoice).await?;
+    audit::record(
+        &state,
+        audit_event(
+            &user,
+            "invoice.credit_note_issued",
+            format!(
+                "Credit note {} issued on invoice {}",
+                credit_note.credit_note_number, invoice.invoice_number
+            ),
+            &invoice,
+            &updated,
+        ),
+    )
+    .await;
+    Ok(Json(CreditNoteIssued {
+        invoice: updated,
+        credit_note,
+    }))
+}
+
+/// Share of a line's gross that credit notes took back: credits against the
+/// line itself, plus invoice-wide credits spread in proportion to the line's
+/// share of the invoice total. Never more than the line.
+pub fn line_credit_cents(
+    item_id: &str,
+    line_total_cents: i64,
+    invoice_total_cents: i64,
+    credit_notes: &[(Option<String>, i64)],
+) -> i64 {
+    let mut credit = 0i64;
+    for (target, amount) in credit_notes {
+        match target.as_deref() {
+            Some(id) if id == item_id => credit += amount,
+            Some(_) => {}
+            None if invoice_total_cents > 0 => {
+                credit += amount * line_total_cents / invoice_total_cents;
+            }
+            None => {}
+        }
+    }
+    credit.clamp(0, line_total_cents.max(0))
+}
+
+/// Fraction of the amount still owed after credits that has been paid.
+pub fn paid_fraction(total_cents: i64, paid_cents: i64, credited_cents: i64) -> f64 {
+    let owed = total_cents - credited_cents;
+    if owed <= 0 {
+        return 1.0;
+    }
+    (paid_cents as f64 / owed as f64).clamp(0.0, 1.0)
+}
+
+/// When a payment date is stored as a day, the instant used for it.
+pub fn day_start(day: NaiveDate) -> DateTime<Utc> {
+    day.and_time(NaiveTime::MIN).and_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice(status: InvoiceStatus, due: &str) -> Invoice {
+        Invoice {
+            status,
+            due_date: NaiveDate::parse_from_str(due, "%Y-%m-%d").unwrap(),
+            ..Invoice::fixture()
+        }
+    }
+
+    fn payment(amount: i32, day: &str) -> InvoicePayment {
+        InvoicePayment {
+            id: format!("pay-{day}"),
+            agency_id: "agency-1".into(),
+            invoice_id: "inv-1".into(),
+            amount_cents: amount,
+            paid_on: NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap(),
+            method: "bank_transfer".into(),
+            reference: None,
+            notes: None,
+            recorded_by: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn credit(amount: i32) -> InvoiceCreditNote {
+        InvoiceCreditNote {
+            id: "cn-1".into(),
+            agency_id: "agency-1".into(),
+            invoice_id: "inv-1".into(),
+            invoice_item_id: None,
+            credit_note_number: "INV-1-CN1".into(),
+            issued_on: NaiveDate::from_ymd_opt(2026, 9, 10).unwrap(),
+            amount_cents: amount,
+            reason: "Disputed".into(),
+            issued_by: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn settlement_follows_payments_credits_and_due_date() {
+        let sent = invoice(InvoiceStatus::Sent, "2026-10-01");
+        let s = settlement(&sent, &[], &[], day("2026-09-15"));
+        assert_eq!(s.status, InvoiceStatus::Sent);
+
+        let s = settlement(
+            &sent,
+            &[payment(4000, "2026-09-12")],
+            &[],
+            day("2026-09-15"),
+        );
+        assert_eq!(s.status, InvoiceStatus::PartiallyPaid);
+        assert_eq!(s.amount_paid_cents, 4000);
+        assert_eq!(s.paid_at, None);
+        assert_eq!(s.last_payment_at, Some(day_start(day("2026-09-12"))));
+
+        let s = settlement(
+            &sent,
+            &[payment(4000, "2026-09-12")],
+            &[],
+            day("2026-10-02"),
+        );
+        assert_eq!(s.status, InvoiceStatus::Overdue);
+
+        let s = settlement(
+            &sent,
+            &[payment(4000, "2026-09-12"), payment(5000, "2026-09-20")],
+            &[credit(1000)],
+            day("2026-10-02"),
+        );
+        assert_eq!(s.status, InvoiceStatus::Paid);
+        assert_eq!(s.amount_credited_cents, 1000);
+        assert_eq!(s.paid_at, Some(day_start(day("2026-09-20"))));
+
+        // Removing a payment from a paid invoice reopens it.
+        let paid = invoice(InvoiceStatus::Paid, "2026-10-01");
+        let s = settlement(
+            &paid,
+            &[payment(4000, "2026-09-12")],
+            &[],
+            day("2026-09-15"),
+        );
+        assert_eq!(s.status, InvoiceStatus::PartiallyPaid);
+
+        let void = invoice(InvoiceStatus::Void, "2026-10-01");
+        let s = settlement(
+            &void,
+            &[payment(10000, "2026-09-12")],
+            &[],
+            day("2026-09-15"),
+        );
+        assert_eq!(s.status, InvoiceStatus::Void);
+    }
+
+    #[test]
+    fn credit_notes_alone_credit_the_invoice_without_paying_it() {
+        let sent = invoice(InvoiceStatus::Sent, "2026-10-01");
+        let s = settlement(&sent, &[], &[credit(10000)], day("2026-09-15"));
+        assert_eq!(s.status, InvoiceStatus::Credited);
+        assert_eq!(s.amount_credited_cents, 10000);
+        assert_eq!(s.paid_at, None);
+        assert_eq!(s.last_payment_at, None);
+
+        let s = settlement(
+            &sent,
+            &[payment(1000, "2026-09-12")],
+            &[credit(9000)],
+            day("2026-09-15"),
+        );
+        assert_eq!(s.status, InvoiceStatus::Paid);
+        assert_eq!(s.paid_at, Some(day_start(day("2026-09-12"))));
+
+        // A credited invoice is no longer owed, and reopens without its notes.
+        let credited = invoice(InvoiceStatus::Credited, "2026-10-01");
+        assert!(!credited.status.is_outstanding());
+        let s = settlement(&credited, &[], &[], day("2026-09-15"));
+        assert_eq!(s.status, InvoiceStatus::Sent);
+    }
+
+    #[test]
+    fn credits_are_allocated_to_lines() {
+        let notes = vec![(Some("item-1".to_string()), 3000), (None, 1000)];
+        // 3000 against the line plus a quarter of the invoice-wide 1000.
+        assert_eq!(line_credit_cents("item-1", 5000, 20000, &notes), 3250);
+        assert_eq!(line_credit_cents("item-2", 15000, 20000, &notes), 750);
+        assert_eq!(
+            line_credit_cents("item-1", 2000, 20000, &[(Some("item-1".into()), 9000)]),
+            2000
+        );
+    }
+
+    #[test]
+    fn paid_fraction_excludes_credited_amounts() {
+        assert_eq!(paid_fraction(10000, 4500, 1000), 0.5);
+        assert_eq!(paid_fraction(10000, 0, 10000), 1.0);
+        assert_eq!(paid_fraction(10000, 20000, 0), 1.0);
+    }
+}
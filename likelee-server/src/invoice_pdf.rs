@@ -0,0 +1,735 @@
+//! Invoice PDF rendering.
+//!
+//! Lays out an A4 invoice with the built-in Helvetica fonts: agency logo and
+//! name, invoice metadata, the bill-to snapshot, item and expense tables,
+//! totals with discount and one tax line per rate, the tax note, payment
+//! instructions and the footer.
+//! Long tables continue on new pages under a repeated header. Rendering is
+//! pure; callers load the data and decide where the bytes go. The page
+//! cursor and table helpers are shared with the talent tax documents.
+
+use crate::invoice_tax::{self, TaxGroup, TaxTreatment};
+use crate::repositories::{Invoice, InvoiceExpense, InvoiceItem};
+use image::DynamicImage;
+use printpdf::{
+    BuiltinFont, Color, ColorBits, ColorSpace, Image, ImageTransform, ImageXObject,
+    IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point, Px,
+    Rect, Rgb,
+};
+
+pub(crate) const PAGE_W: f32 = 210.0;
+pub(crate) const PAGE_H: f32 = 297.0;
+pub(crate) const MARGIN: f32 = 18.0;
+/// Content stops here so the footer and page number have room.
+const BOTTOM: f32 = 30.0;
+pub(crate) const ROW_H: f32 = 6.0;
+const LOGO_MAX_W: f32 = 50.0;
+const LOGO_MAX_H: f32 = 20.0;
+/// Logos are downscaled to this many pixels on their longer side.
+const LOGO_MAX_PX: u32 = 600;
+
+/// Agency details printed in the header.
+#[derive(Debug, Clone, Default)]
+pub struct Branding {
+    pub agency_name: String,
+    pub agency_email: Option<String>,
+    pub logo: Option<DynamicImage>,
+}
+
+/// Amounts printed in the totals block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub subtotal_cents: i64,
+    pub expenses_cents: i64,
+    pub discount_cents: i64,
+    pub tax_cents: i64,
+    pub total_cents: i64,
+    /// Tax per rate, printed one line each.
+    pub tax_lines: Vec<TaxGroup>,
+}
+
+/// Helvetica advance widths for ASCII 32..=126, in 1/1000 em.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Approximate rendered width of `text` in millimetres. Bold text runs about
+/// five percent wider than regular.
+pub(crate) fn text_width(text: &str, size: f32, bold: bool) -> f32 {
+    let units: u32 = text
+        .chars()
+        .map(|c| match c as u32 {
+            32..=126 => HELVETICA_WIDTHS[(c as u32 - 32) as usize] as u32,
+            _ => 556,
+        })
+        .sum();
+    let factor = if bold { 1.05 } else { 1.0 };
+    units as f32 / 1000.0 * size * 0.352_778 * factor
+}
+
+/// Splits `text` into lines no wider than `max_w` millimetres, breaking on
+/// whitespace and, for single overlong words, mid-word.
+pub fn wrap(text: &str, size: f32, max_w: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{line} {word}")
+            };
+            if text_width(&candidate, size, false) <= max_w {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            let mut rest = word.to_string();
+            while text_width(&rest, size, false) > max_w && rest.chars().count() > 1 {
+                let mut cut = rest.chars().count() - 1;
+                while cut > 1
+                    && text_width(&rest.chars().take(cut).collect::<String>(), size, false) > max_w
+                {
+                    cut -= 1;
+                }
+                lines.push(rest.chars().take(cut).collect());
+                rest = rest.chars().skip(cut).collect();
+            }
+            line = rest;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+fn currency_prefix(currency: &str) -> String {
+    match currency.to_ascii_uppercase().as_str() {
+        "USD" => "$".to_string(),
+        "EUR" => "\u{20ac}".to_string(),
+        "GBP" => "\u{a3}".to_string(),
+        other => format!("{other} "),
+    }
+}
+
+/// `-$1,234.50` style amount for `cents` in `currency`.
+pub fn money(cents: i64, currency: &str) -> String {
+    let abs = cents.unsigned_abs();
+    let whole = (abs / 100).to_string();
+    let mut grouped = String::new();
+    for (i, c) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    format!(
+        "{}{}{grouped}.{:02}",
+        if cents < 0 { "-" } else { "" },
+        currency_prefix(currency),
+        abs % 100
+    )
+}
+
+fn quantity(q: f64) -> String {
+    if q.fract() == 0.0 {
+        format!("{q:.0}")
+    } else {
+        format!("{q:.2}")
+    }
+}
+
+pub(crate) struct Fonts {
+    pub(crate) regular: IndirectFontRef,
+    pub(crate) bold: IndirectFontRef,
+}
+
+/// Writes onto the current page and opens new ones as the cursor runs out of
+/// room.
+pub(crate) struct Cursor {
+    doc: PdfDocumentReference,
+    pub(crate) fonts: Fonts,
+    pub(crate) layers: Vec<PdfLayerReference>,
+    pub(crate) y: f32,
+}
+
+impl Cursor {
+    /// Starts an A4 document with the cursor at the top of its first page.
+    pub(crate) fn new(title: &str) -> Result<Self, String> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_W), Mm(PAGE_H), "Page 1");
+        let fonts = Fonts {
+            regular: doc
+                .add_builtin_font(BuiltinFont::Helvetica)
+                .map_err(|e| e.to_string())?,
+            bold: doc
+                .add_builtin_font(BuiltinFont::HelveticaBold)
+                .map_err(|e| e.to_string())?,
+        };
+        let first = doc.get_page(page).get_layer(layer);
+        Ok(Cursor {
+            doc,
+            fonts,
+            layers: vec![first],
+            y: PAGE_H - MARGIN,
+        })
+    }
+
+    /// Prints up to three `footer` lines and "Page X of N" on every page,
+    /// then returns the PDF bytes and the page count.
+    pub(crate) fn finish(self, footer: &[String]) -> Result<(Vec<u8>, usize), String> {
+        let pages = self.layers.len();
+        for (i, layer) in self.layers.iter().enumerate() {
+            for (j, line) in footer.iter().take(3).enumerate() {
+                layer.use_text(
+                    line.as_str(),
+                    8.0,
+                    Mm(MARGIN),
+                    Mm(20.0 - j as f32 * 3.8),
+                    &self.fonts.regular,
+                );
+            }
+            let label = format!("Page {} of {pages}", i + 1);
+            layer.use_text(
+                label.as_str(),
+                8.0,
+                Mm(PAGE_W - MARGIN - text_width(&label, 8.0, false)),
+                Mm(12.0),
+                &self.fonts.regular,
+            );
+        }
+
+        // The layers hold the document weakly; saving needs sole ownership.
+        let Cursor {
+            doc, fonts, layers, ..
+        } = self;
+        drop((fonts, layers));
+        let bytes = doc.save_to_bytes().map_err(|e| e.to_string())?;
+        Ok((bytes, pages))
+    }
+
+    pub(crate) fn layer(&self) -> &PdfLayerReference {
+        self.layers.last().expect("a page is always open")
+    }
+
+    pub(crate) fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(
+            Mm(PAGE_W),
+            Mm(PAGE_H),
+            format!("Page {}", self.layers.len() + 1),
+        );
+        self.layers.push(self.doc.get_page(page).get_layer(layer));
+        self.y = PAGE_H - MARGIN;
+    }
+
+    /// Opens a new page unless `height` more millimetres fit on this one.
+    /// Returns whether a page was opened.
+    pub(crate) fn ensure(&mut self, height: f32) -> bool {
+        if self.y - height < BOTTOM {
+            self.new_page();
+            return true;
+        }
+        false
+    }
+
+    pub(crate) fn text(&self, text: &str, size: f32, x: f32, y: f32, bold: bool) {
+        let font = if bold {
+            &self.fonts.bold
+        } else {
+            &self.fonts.regular
+        };
+        self.layer().use_text(text, size, Mm(x), Mm(y), font);
+    }
+
+    pub(crate) fn text_right(&self, text: &str, size: f32, right: f32, y: f32, bold: bool) {
+        self.text(text, size, right - text_width(text, size, bold), y, bold);
+    }
+
+    pub(crate) fn rule(&self, y: f32, gray: f32) {
+        let layer = self.layer();
+        layer.set_outline_color(Color::Rgb(Rgb::new(gray, gray, gray, None)));
+        layer.set_outline_thickness(0.5);
+        layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(y)), false),
+                (Point::new(Mm(PAGE_W - MARGIN), Mm(y)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    pub(crate) fn band(&self, top: f32, height: f32) {
+        let layer = self.layer();
+        layer.set_fill_color(Color::Rgb(Rgb::new(0.94, 0.94, 0.95, None)));
+        layer.add_rect(Rect::new(
+            Mm(MARGIN),
+            Mm(top - height),
+            Mm(PAGE_W - MARGIN),
+            Mm(top),
+        ));
+        layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+    }
+}
+
+/// A table column: header, left edge and whether it is right aligned (in
+/// which case `x` is the right edge).
+pub(crate) struct Column {
+    pub(crate) title: &'static str,
+    pub(crate) x: f32,
+    pub(crate) right: bool,
+}
+
+fn table_header(cursor: &mut Cursor, columns: &[Column]) {
+    cursor.band(cursor.y + 1.5, ROW_H + 1.0);
+    for col in columns {
+        if col.right {
+            cursor.text_right(col.title, 8.5, col.x, cursor.y - 3.0, true);
+        } else {
+            cursor.text(col.title, 8.5, col.x, cursor.y - 3.0, true);
+        }
+    }
+    cursor.y -= ROW_H + 1.5;
+}
+
+/// Prints rows under `columns`; the first cell of each row wraps within
+/// `first_width` millimetres.
+pub(crate) fn table(
+    cursor: &mut Cursor,
+    columns: &[Column],
+    first_width: f32,
+    rows: &[Vec<String>],
+) {
+    cursor.ensure(ROW_H * 3.0);
+    table_header(cursor, columns);
+    for row in rows {
+        let lines = wrap(&row[0], 9.0, first_width);
+        let height = ROW_H.max(lines.len() as f32 * 4.2 + 1.8);
+        if cursor.ensure(height) {
+            table_header(cursor, columns);
+        }
+        let baseline = cursor.y - 3.5;
+        for (i, line) in lines.iter().enumerate() {
+            cursor.text(line, 9.0, columns[0].x, baseline - i as f32 * 4.2, false);
+        }
+        for (col, cell) in columns.iter().zip(row.iter()).skip(1) {
+            if col.right {
+                cursor.text_right(cell, 9.0, col.x, baseline, false);
+            } else {
+                cursor.text(cell, 9.0, col.x, baseline, false);
+            }
+        }
+        cursor.y -= height;
+        cursor.rule(cursor.y + 0.8, 0.85);
+    }
+    cursor.y -= 4.0;
+}
+
+/// Converts the logo to an RGB image object, returning it with the dpi at
+/// which it fits the header box and its printed height in millimetres.
+fn logo_xobject(logo: &DynamicImage) -> (ImageXObject, f32, f32) {
+    let logo = if logo.width().max(logo.height()) > LOGO_MAX_PX {
+        logo.thumbnail(LOGO_MAX_PX, LOGO_MAX_PX)
+    } else {
+        logo.clone()
+    };
+    // Transparent areas are flattened onto the white page.
+    let rgba = logo.to_rgba8();
+    let mut data = Vec::with_capacity((rgba.width() * rgba.height() * 3) as usize);
+    for px in rgba.pixels() {
+        let a = px[3] as u32;
+        for c in &px.0[..3] {
+            data.push(((*c as u32 * a + 255 * (255 - a)) / 255) as u8);
+        }
+    }
+    let (w, h) = (rgba.width() as f32, rgba.height() as f32);
+    let dpi = (w * 25.4 / LOGO_MAX_W).max(h * 25.4 / LOGO_MAX_H).max(1.0);
+    let xobject = ImageXObject {
+        width: Px(rgba.width() as usize),
+        height: Px(rgba.height() as usize),
+        color_space: ColorSpace::Rgb,
+        bits_per_component: ColorBits::Bit8,
+        interpolate: true,
+        image_data: data,
+        image_filter: None,
+        smask: None,
+        clipping_bbox: None,
+    };
+    (xobject, dpi, h * 25.4 / dpi)
+}
+
+/// Renders `invoice` with its items and expenses into PDF bytes.
+pub fn render(
+    invoice: &Invoice,
+    items: &[InvoiceItem],
+    expenses: &[InvoiceExpense],
+    totals: &Totals,
+    branding: &Branding,
+) -> Result<Vec<u8>, String> {
+    layout(invoice, items, expenses, totals, branding).map(|(bytes, _)| bytes)
+}
+
+/// Does the work of [`render`], also returning the page count.
+fn layout(
+    invoice: &Invoice,
+    items: &[InvoiceItem],
+    expenses: &[InvoiceExpense],
+    totals: &Totals,
+    branding: &Branding,
+) -> Result<(Vec<u8>, usize), String> {
+    let mut cursor = Cursor::new(&format!("Invoice {}", invoice.invoice_number))?;
+    let right = PAGE_W - MARGIN;
+    let currency = invoice.currency.as_str();
+
+    // Header: logo or agency name on the left, invoice details on the right.
+    let top = cursor.y;
+    let mut left_y = top;
+    if let Some(logo) = &branding.logo {
+        let (xobject, dpi, height) = logo_xobject(logo);
+        Image::from(xobject).add_to_layer(
+            cursor.layer().clone(),
+            ImageTransform {
+                translate_x: Some(Mm(MARGIN)),
+                translate_y: Some(Mm(top - height)),
+                dpi: Some(dpi),
+                ..Default::default()
+            },
+        );
+        left_y -= height + 6.0;
+    } else {
+        left_y -= 6.0;
+    }
+    cursor.text(&branding.agency_name, 14.0, MARGIN, left_y, true);
+    if let Some(email) = branding.agency_email.as_deref().filter(|e| !e.is_empty()) {
+        left_y -= 5.0;
+        cursor.text(email, 9.0, MARGIN, left_y, false);
+    }
+
+    cursor.text_right("INVOICE", 22.0, right, top - 8.0, true);
+    let mut meta = vec![
+        ("Invoice #", invoice.invoice_number.clone()),
+        (
+            "Invoice date",
+            invoice.invoice_date.format("%b %-d, %Y").to_string(),
+        ),
+        (
+            "Due date",
+            invoice.due_date.format("%b %-d, %Y").to_string(),
+        ),
+        ("Terms", invoice.payment_terms.replace('_', " ")),
+    ];
+    if let Some(po) = invoice.po_number.as_deref().filter(|s| !s.is_empty()) {
+        meta.push(("PO number", po.to_string()));
+    }
+    if let Some(project) = invoice
+        .project_reference
+        .as_deref()
+        .filter(|s| !s.is_empty())
+    {
+        meta.push(("Project", project.to_string()));
+    }
+    let mut right_y = top - 15.0;
+    for (label, value) in &meta {
+        cursor.text_right(label, 9.0, right - 45.0, right_y, true);
+        cursor.text_right(value, 9.0, right, right_y, false);
+        right_y -= 5.0;
+    }
+
+    cursor.y = left_y.min(right_y) - 8.0;
+    cursor.rule(cursor.y + 3.0, 0.7);
+
+    // Bill-to snapshot.
+    cursor.y -= 3.0;
+    cursor.text("BILL TO", 8.5, MARGIN, cursor.y, true);
+    cursor.y -= 5.5;
+    cursor.text(&invoice.bill_to_company, 11.0, MARGIN, cursor.y, true);
+    for line in [
+        invoice.bill_to_contact_name.as_deref(),
+        invoice.bill_to_email.as_deref(),
+        invoice.bill_to_phone.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|s| !s.trim().is_empty())
+    {
+        cursor.y -= 4.8;
+        cursor.text(line, 9.5, MARGIN, cursor.y, false);
+    }
+    cursor.y -= 10.0;
+
+    // Line items.
+    let item_columns = [
+        Column {
+            title: "Description",
+            x: MARGIN + 2.0,
+            right: false,
+        },
+        Column {
+            title: "Talent",
+            x: 86.0,
+            right: false,
+        },
+        Column {
+            title: "Date",
+            x: 118.0,
+            right: false,
+        },
+        Column {
+            title: "Qty",
+            x: 146.0,
+            right: true,
+        },
+        Column {
+            title: "Rate",
+            x: 169.0,
+            right: true,
+        },
+        Column {
+            title: "Amount",
+            x: right - 2.0,
+            right: true,
+        },
+    ];
+    let item_rows: Vec<Vec<String>> = items
+        .iter()
+        .map(|it| {
+            vec![
+                it.description.clone(),
+                it.talent_name.clone().unwrap_or_default(),
+                it.date_of_service
+                    .map(|d| d.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+                quantity(it.quantity),
+                money(it.unit_price_cents as i64, currency),
+                money(it.line_total_cents as i64, currency),
+            ]
+        })
+        .collect();
+    table(&mut cursor, &item_columns, 64.0, &item_rows);
+
+    if !expenses.is_empty() {
+        let expense_columns = [
+            Column {
+                title: "Expense",
+                x: MARGIN + 2.0,
+                right: false,
+            },
+            Column {
+                title: "Taxable",
+                x: 146.0,
+                right: true,
+            },
+            Column {
+                title: "Amount",
+                x: right - 2.0,
+                right: true,
+            },
+        ];
+        let expense_rows: Vec<Vec<String>> = expenses
+            .iter()
+            .map(|ex| {
+                vec![
+                    ex.description.clone(),
+                    if ex.taxable { "Yes" } else { "No" }.to_string(),
+                    money(ex.amount_cents as i64, currency),
+                ]
+            })
+            .collect();
+        table(&mut cursor, &expense_columns, 110.0, &expense_rows);
+    }
+
+    // Totals.
+    let mut lines = vec![(
+        "Subtotal".to_string(),
+        money(totals.subtotal_cents, currency),
+    )];
+    if totals.expenses_cents != 0 {
+        lines.push((
+            "Expenses".to_string(),
+            money(totals.expenses_cents, currency),
+        ));
+    }
+    if totals.discount_cents != 0 {
+        lines.push((
+            "Discount".to_string(),
+            money(-totals.discount_cents, currency),
+        ));
+    }
+    let tax_label = invoice.tax_label.as_deref().unwrap_or("Tax");
+    let exempt = invoice.tax_exempt || invoice.tax_treatment == TaxTreatment::Exempt;
+    match invoice.tax_treatment {
+        _ if exempt => {
+            lines.push((format!("{tax_label} (exempt)"), money(0, currency)));
+        }
+        TaxTreatment::ReverseCharge => {
+            lines.push((format!("{tax_label} (reverse charge)"), money(0, currency)));
+        }
+        _ => {
+            for group in totals
+                .tax_lines
+                .iter()
+                .filter(|g| g.rate_bps != 0 || g.tax_cents != 0)
+            {
+                let included = if invoice.prices_include_tax {
+                    " incl."
+                } else {
+                    ""
+                };
+                lines.push((
+                    format!(
+                        "{} ({}%{included})",
+                        group.label,
+                        invoice_tax::percent(group.rate_bps)
+                    ),
+                    money(group.tax_cents, currency),
+                ));
+            }
+        }
+    }
+    cursor.ensure(lines.len() as f32 * 5.5 + 14.0);
+    for (label, value) in &lines {
+        cursor.text_right(label, 9.5, right - 40.0, cursor.y, false);
+        cursor.text_right(value, 9.5, right - 2.0, cursor.y, false);
+        cursor.y -= 5.5;
+    }
+    cursor.band(cursor.y + 4.0, 8.0);
+    cursor.text_right("Total due", 11.0, right - 40.0, cursor.y - 1.5, true);
+    cursor.text_right(
+        &money(totals.total_cents, currency),
+        11.0,
+        right - 2.0,
+        cursor.y - 1.5,
+        true,
+    );
+    cursor.y -= 14.0;
+
+    // Tax note, e.g. the reverse-charge wording.
+    if let Some(note) = invoice.tax_note.as_deref().filter(|s| !s.trim().is_empty()) {
+        let lines = wrap(note, 8.5, PAGE_W - 2.0 * MARGIN);
+        cursor.ensure(lines.len().min(4) as f32 * 4.2 + 4.0);
+        for line in lines {
+            cursor.ensure(4.2);
+            cursor.text(&line, 8.5, MARGIN, cursor.y, false);
+            cursor.y -= 4.2;
+        }
+        cursor.y -= 4.0;
+    }
+
+    // Payment instructions.
+    if let Some(instructions) = invoice
+        .payment_instructions
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+    {
+        let lines = wrap(instructions, 9.0, PAGE_W - 2.0 * MARGIN);
+        cursor.ensure(10.0 + lines.len().min(4) as f32 * 4.5);
+        cursor.text("PAYMENT INSTRUCTIONS", 8.5, MARGIN, cursor.y, true);
+        cursor.y -= 5.0;
+        for line in lines {
+            cursor.ensure(4.5);
+            cursor.text(&line, 9.0, MARGIN, cursor.y, false);
+            cursor.y -= 4.5;
+        }
+    }
+
+    // Footer and page numbers on every page.
+    let footer = invoice
+        .footer_text
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| wrap(s, 8.0, PAGE_W - 2.0 * MARGIN - 25.0))
+        .unwrap_or_default();
+    cursor.finish(&footer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, Utc};
+
+    fn invoice() -> Invoice {
+        Invoice {
+            invoice_number: "INV-0001".into(),
+            bill_to_contact_name: Some("Jane Doe".into()),
+            bill_to_email: Some("billing@acme.test".into()),
+            po_number: Some("PO-7".into()),
+            tax_rate_bps: 825,
+            discount_cents: 1000,
+            payment_instructions: Some("Wire to account 123.".into()),
+            footer_text: Some("Thank you for your business.".into()),
+            ..Invoice::fixture()
+        }
+    }
+
+    fn item(n: i32) -> InvoiceItem {
+        InvoiceItem {
+            id: format!("item-{n}"),
+            invoice_id: "inv-1".into(),
+            sort_order: n,
+            description: format!("Shoot day {n} with an unusually long description that wraps"),
+            talent_id: None,
+            talent_name: Some("Ava".into()),
+            date_of_service: NaiveDate::from_ymd_opt(2026, 9, 30),
+            rate_type: None,
+            quantity: 1.5,
+            unit_price_cents: 10000,
+            line_total_cents: 15000,
+            tax_rate_bps: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn money_groups_thousands_and_signs() {
+        assert_eq!(money(123456789, "usd"), "$1,234,567.89");
+        assert_eq!(money(-500, "USD"), "-$5.00");
+        assert_eq!(money(5, "EUR"), "\u{20ac}0.05");
+        assert_eq!(money(100000, "CAD"), "CAD 1,000.00");
+    }
+
+    #[test]
+    fn wrap_breaks_on_words_and_overlong_words() {
+        let lines = wrap("one two three four five six", 9.0, 20.0);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| text_width(l, 9.0, false) <= 20.0));
+        assert_eq!(lines.join(" "), "one two three four five six");
+        let long = wrap(&"x".repeat(200), 9.0, 30.0);
+        assert!(long.len() > 1);
+        assert_eq!(long.concat(), "x".repeat(200));
+    }
+
+    #[test]
+    fn long_invoices_continue_on_new_pages() {
+        let totals = Totals {
+            subtotal_cents: 15000,
+            expenses_cents: 0,
+            discount_cents: 1000,
+            tax_cents: 1155,
+            total_cents: 15155,
+            tax_lines: vec![TaxGroup {
+                rate_bps: 825,
+                label: "Tax".into(),
+                taxable_cents: 14000,
+                tax_cents: 1155,
+            }],
+        };
+        let branding = Branding {
+            agency_name: "North Studio".into(),
+            agency_email: Some("hello@north.test".into()),
+            logo: Some(DynamicImage::new_rgba8(1200, 300)),
+        };
+        let (short, pages) = layout(&invoice(), &[item(1)], &[], &totals, &branding).unwrap();
+        assert!(short.starts_with(b"%PDF-"));
+        assert_eq!(pages, 1);
+
+        let items: Vec<InvoiceItem> = (0..80).map(item).collect();
+        let (_, pages) = layout(&invoice(), &items, &[], &totals, &branding).unwrap();
+        assert!(pages > 2);
+    }
+}
@@ -0,0 +1,536 @@
+//! Invoice tax calculation.
+//!
+//! Every item and expense is taxed at its own `tax_rate_bps`, falling back to
+//! the invoice's rate; expenses marked not taxable are zero-rated. Prices are
+//! either tax-exclusive (tax is added on top) or tax-inclusive (tax is backed
+//! out of the amounts entered). Exempt and reverse-charge invoices carry no
+//! tax; reverse-charge ones print a legal note saying the customer accounts
+//! for the VAT instead.
+//!
+//! When an invoice does not set these itself, the defaults come from the
+//! agency's `agency_tax_currency_settings`, with per-jurisdiction overrides
+//! in its `tax_jurisdictions` map keyed by the client's jurisdiction code.
+
+use crate::{
+    auth::AuthUser,
+    config::AppState,
+    errors::{AppError, AppResult},
+    invoices::stored_totals,
+    repositories::{fetch, InvoiceFilter, InvoiceStatus, RepoError},
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+pub const SETTINGS_TABLE: &str = "agency_tax_currency_settings";
+
+/// Printed on reverse-charge invoices unless the jurisdiction sets its own.
+pub const REVERSE_CHARGE_NOTE: &str = "Reverse charge: VAT to be accounted for by the recipient under Article 196 of Council Directive 2006/112/EC.";
+
+const DEFAULT_LABEL: &str = "Tax";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxTreatment {
+    #[default]
+    Standard,
+    Exempt,
+    ReverseCharge,
+}
+
+impl TaxTreatment {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "standard" => Some(TaxTreatment::Standard),
+            "exempt" => Some(TaxTreatment::Exempt),
+            "reverse_charge" => Some(TaxTreatment::ReverseCharge),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaxTreatment::Standard => "standard",
+            TaxTreatment::Exempt => "exempt",
+            TaxTreatment::ReverseCharge => "reverse_charge",
+        }
+    }
+}
+
+/// How an invoice is taxed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxSettings {
+    /// Rate for lines that do not set their own.
+    pub default_rate_bps: i64,
+    pub prices_include_tax: bool,
+    pub treatment: TaxTreatment,
+    /// Name the tax is printed under, e.g. "VAT" or "Sales Tax".
+    pub label: String,
+}
+
+impl Default for TaxSettings {
+    fn default() -> Self {
+        Self {
+            default_rate_bps: 0,
+            prices_include_tax: false,
+            treatment: TaxTreatment::Standard,
+            label: DEFAULT_LABEL.to_string(),
+        }
+    }
+}
+
+/// One item or expense as entered on the invoice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TaxLine {
+    pub amount_cents: i64,
+    /// The line's own rate; `None` uses the invoice default.
+    pub rate_bps: Option<i64>,
+    /// Zero-rated regardless of rate (expenses marked not taxable).
+    pub zero_rated: bool,
+    /// Items count toward the commission base; expenses do not.
+    pub is_item: bool,
+}
+
+/// Lines taxed at one rate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaxGroup {
+    pub rate_bps: i64,
+    pub label: String,
+    /// Amount the tax is charged on, after discount and net of tax.
+    pub taxable_cents: i64,
+    pub tax_cents: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaxBreakdown {
+    pub tax_cents: i64,
+    /// Tax inside the item amounts when prices include tax, so commission is
+    /// charged on the net price.
+    pub item_tax_included_cents: i64,
+    pub groups: Vec<TaxGroup>,
+}
+
+fn effective_rate(line: &TaxLine, settings: &TaxSettings) -> i64 {
+    if line.zero_rated || settings.treatment != TaxTreatment::Standard {
+        0
+    } else {
+        line.rate_bps.unwrap_or(settings.default_rate_bps).max(0)
+    }
+}
+
+/// Net amount of a tax-inclusive `gross` at `rate_bps`.
+fn net_of(gross: i64, rate_bps: i64) -> i64 {
+    if rate_bps == 0 {
+        return gross;
+    }
+    ((gross as f64) * 10_000.0 / (10_000 + rate_bps) as f64).round() as i64
+}
+
+/// Tax on `base` at `rate_bps`, exclusive or backed out of an inclusive
+/// amount.
+fn tax_on(base: i64, rate_bps: i64, inclusive: bool) -> i64 {
+    if inclusive {
+        base - net_of(base, rate_bps)
+    } else {
+        (base * rate_bps) / 10_000
+    }
+}
+
+fn group_label(rate_bps: i64, settings: &TaxSettings) -> String {
+    match settings.treatment {
+        TaxTreatment::Exempt => format!("{} exempt", settings.label),
+        TaxTreatment::ReverseCharge => format!("{} reverse charge", settings.label),
+        TaxTreatment::Standard => format!("{} {}%", settings.label, percent(rate_bps)),
+    }
+}
+
+/// `rate_bps` as a percentage without trailing zeros: 2000 is "20", 825 is
+/// "8.25".
+pub fn percent(rate_bps: i64) -> String {
+    let rate = format!("{:.2}", rate_bps as f64 / 100.0);
+    rate.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Taxes `lines` under `settings`. The discount comes off the taxed lines in
+/// proportion to their amounts, as it did when every line shared one rate.
+pub fn compute(lines: &[TaxLine], discount_cents: i64, settings: &TaxSettings) -> TaxBreakdown {
+    let inclusive = settings.prices_include_tax && settings.treatment == TaxTreatment::Standard;
+
+    let mut by_rate: BTreeMap<i64, i64> = BTreeMap::new();
+    let mut item_tax_included_cents = 0;
+    for line in lines {
+        let rate = effective_rate(line, settings);
+        *by_rate.entry(rate).or_default() += line.amount_cents;
+        if inclusive && line.is_item {
+            item_tax_included_cents += tax_on(line.amount_cents, rate, true);
+        }
+    }
+
+    // Spread the discount over the taxed groups, or over everything when
+    // nothing is taxed.
+    let taxed: i64 = by_rate
+        .iter()
+        .filter(|(rate, _)| **rate > 0)
+        .map(|(_, amount)| *amount)
+        .sum();
+    let discount_pool = if taxed > 0 {
+        taxed
+    } else {
+        by_rate.values().sum()
+    };
+    let mut remaining_discount = discount_cents.max(0);
+    let shares: Vec<(i64, i64)> = by_rate.iter().map(|(r, a)| (*r, *a)).collect();
+    let eligible: Vec<usize> = shares
+        .iter()
+        .enumerate()
+        .filter(|(_, (rate, _))| taxed == 0 || *rate > 0)
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut groups = Vec::with_capacity(shares.len());
+    let mut tax_cents = 0;
+    for (i, (rate, amount)) in shares.iter().enumerate() {
+        let discount = if !eligible.contains(&i) || discount_pool <= 0 {
+            0
+        } else if Some(&i) == eligible.last() {
+            remaining_discount
+        } else {
+            let share = discount_cents.max(0) * amount / discount_pool;
+            remaining_discount -= share;
+            share
+        };
+        let base = (amount - discount).max(0);
+        let tax = tax_on(base, *rate, inclusive);
+        tax_cents += tax;
+        groups.push(TaxGroup {
+            rate_bps: *rate,
+            label: group_label(*rate, settings),
+            taxable_cents: if inclusive { base - tax } else { base },
+            tax_cents: tax,
+        });
+    }
+
+    TaxBreakdown {
+        tax_cents,
+        item_tax_included_cents,
+        groups,
+    }
+}
+
+/// Adds `groups` into `into`, merging groups at the same rate and label.
+pub fn merge_groups(into: &mut Vec<TaxGroup>, groups: &[TaxGroup]) {
+    for group in groups {
+        match into
+            .iter_mut()
+            .find(|g| g.rate_bps == group.rate_bps && g.label == group.label)
+        {
+            Some(existing) => {
+                existing.taxable_cents += group.taxable_cents;
+                existing.tax_cents += group.tax_cents;
+            }
+            None => into.push(group.clone()),
+        }
+    }
+}
+
+/// Overrides for clients in one jurisdiction.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct JurisdictionTax {
+    pub rate_bps: Option<i32>,
+    pub label: Option<String>,
+    pub prices_include_tax: Option<bool>,
+    pub treatment: Option<TaxTreatment>,
+    pub note: Option<String>,
+}
+
+/// The agency's row in `agency_tax_currency_settings`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AgencyTaxSettings {
+    /// Percent, e.g. 20 for 20%.
+    #[serde(default)]
+    pub default_tax_rate: f64,
+    #[serde(default)]
+    pub tax_display_name: Option<String>,
+    #[serde(default)]
+    pub include_tax_in_displayed_prices: bool,
+    #[serde(default)]
+    pub tax_jurisdictions: HashMap<String, JurisdictionTax>,
+}
+
+pub async fn load_agency_settings(
+    state: &AppState,
+    agency_id: &str,
+) -> Result<Option<AgencyTaxSettings>, RepoError> {
+    let rows: Vec<AgencyTaxSettings> = fetch(
+        state
+            .pg
+            .from(SETTINGS_TABLE)
+            .select("default_tax_rate,tax_display_name,include_tax_in_displayed_prices,tax_jurisdictions")
+            .eq("agency_id", agency_id),
+    )
+    .await?;
+    Ok(rows.into_iter().next())
+}
+
+/// What a new invoice gets when it does not set its own tax fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxDefaults {
+    pub settings: TaxSettings,
+    pub note: Option<String>,
+}
+
+/// Defaults for a client in `jurisdiction`. Agencies without settings keep
+/// the untaxed, tax-exclusive behaviour invoices had before.
+pub fn defaults(agency: Option<&AgencyTaxSettings>, jurisdiction: Option<&str>) -> TaxDefaults {
+    let Some(agency) = agency else {
+        return TaxDefaults {
+            settings: TaxSettings::default(),
+            note: None,
+        };
+    };
+    let code = jurisdiction.map(|j| j.trim().to_ascii_uppercase());
+    let overrides = code
+        .as_deref()
+        .and_then(|c| agency.tax_jurisdictions.get(c))
+        .cloned()
+        .unwrap_or_default();
+    let treatment = overrides.treatment.unwrap_or_default();
+    let note = overrides.note.or_else(|| {
+        (treatment == TaxTreatment::ReverseCharge).then(|| REVERSE_CHARGE_NOTE.to_string())
+    });
+    TaxDefaults {
+        settings: TaxSettings {
+            default_rate_bps: overrides
+                .rate_bps
+                .map(i64::from)
+                .unwrap_or((agency.default_tax_rate * 100.0).round() as i64),
+            prices_include_tax: overrides
+                .prices_include_tax
+                .unwrap_or(agency.include_tax_in_displayed_prices),
+            treatment,
+            label: overrides
+                .label
+                .or_else(|| agency.tax_display_name.clone())
+                .filter(|s| !s.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_LABEL.to_string()),
+        },
+        note,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TaxReportQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// Tax charged in one currency, by rate.
+#[derive(Debug, Serialize)]
+pub struct CurrencyTaxSummary {
+    pub currency: String,
+    pub invoice_count: usize,
+    pub taxable_cents: i64,
+    pub tax_cents: i64,
+    pub groups: Vec<TaxGroup>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaxReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub invoice_count: usize,
+    pub currencies: Vec<CurrencyTaxSummary>,
+}
+
+/// GET /api/invoices/tax-summary?from=&to=: tax on the invoices issued in the
+/// period, grouped by currency and rate. Drafts and void invoices are left
+/// out. Defaults to the current month to date.
+pub async fn report(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(q): Query<TaxReportQuery>,
+) -> AppResult<Json<TaxReport>> {
+    let parse = |value: Option<&str>| {
+        value
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| {
+                NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d")
+                    .map_err(|_| AppError::BadRequest("invalid_date_range".to_string()))
+            })
+            .transpose()
+    };
+    let today = Utc::now().date_naive();
+    let to = parse(q.to.as_deref())?.unwrap_or(today);
+    let from = parse(q.from.as_deref())?.unwrap_or_else(|| to.with_day(1).unwrap_or(to));
+    if from > to {
+        return Err(AppError::BadRequest("invalid_date_range".to_string()));
+    }
+
+    let filter = InvoiceFilter {
+        status: None,
+        date_start: Some(from.to_string()),
+        date_end: Some(to.to_string()),
+    };
+    let invoices = state
+        .repos
+        .invoices
+        .list_for_agency(&user.id, &filter)
+        .await?;
+    let mut invoice_count = 0;
+    let mut currencies: BTreeMap<String, CurrencyTaxSummary> = BTreeMap::new();
+    for invoice in invoices
+        .iter()
+        .filter(|i| !matches!(i.status, InvoiceStatus::Draft | InvoiceStatus::Void))
+    {
+        let items = state.repos.invoices.items(&invoice.id).await?;
+        let expenses = state.repos.invoices.expenses(&invoice.id).await?;
+        let totals = stored_totals(invoice, &items, &expenses)?;
+        let currency = invoice.currency.to_ascii_uppercase();
+        let summary = currencies
+            .entry(currency.clone())
+            .or_insert_with(|| CurrencyTaxSummary {
+                currency,
+                invoice_count: 0,
+                taxable_cents: 0,
+                tax_cents: 0,
+                groups: Vec::new(),
+            });
+        summary.invoice_count += 1;
+        summary.taxable_cents += totals
+            .tax_summary
+            .iter()
+            .map(|g| g.taxable_cents)
+            .sum::<i64>();
+        summary.tax_cents += totals.tax_cents;
+        merge_groups(&mut summary.groups, &totals.tax_summary);
+        invoice_count += 1;
+    }
+    let currencies = currencies
+        .into_values()
+        .map(|mut summary| {
+            summary
+                .groups
+                .sort_by(|a, b| (a.rate_bps, &a.label).cmp(&(b.rate_bps, &b.label)));
+            summary
+        })
+        .collect();
+
+    Ok(Json(TaxReport {
+        from,
+        to,
+        invoice_count,
+        currencies,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(amount: i64, rate: Option<i64>) -> TaxLine {
+        TaxLine {
+            amount_cents: amount,
+            rate_bps: rate,
+            zero_rated: false,
+            is_item: true,
+        }
+    }
+
+    fn vat(rate: i64) -> TaxSettings {
+        TaxSettings {
+            default_rate_bps: rate,
+            label: "VAT".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn single_rate_matches_the_flat_calculation() {
+        let expense = TaxLine {
+            amount_cents: 5000,
+            rate_bps: None,
+            zero_rated: true,
+            is_item: false,
+        };
+        let tax = compute(&[item(110000, None), expense], 0, &vat(1000));
+        assert_eq!(tax.tax_cents, 11000);
+        assert_eq!(tax.groups.len(), 2);
+        assert_eq!(tax.groups[1].label, "VAT 10%");
+        assert_eq!(tax.groups[1].taxable_cents, 110000);
+
+        // The whole discount comes off the taxed lines.
+        let tax = compute(&[item(15000, None)], 1000, &vat(825));
+        assert_eq!(tax.tax_cents, 1155);
+    }
+
+    #[test]
+    fn lines_are_grouped_by_rate_and_share_the_discount() {
+        let lines = [
+            item(30000, Some(2000)),
+            item(10000, Some(500)),
+            item(10000, None),
+        ];
+        let tax = compute(&lines, 4000, &vat(2000));
+        let summary: Vec<_> = tax
+            .groups
+            .iter()
+            .map(|g| (g.rate_bps, g.taxable_cents, g.tax_cents))
+            .collect();
+        assert_eq!(summary, vec![(500, 9200, 460), (2000, 36800, 7360)]);
+        assert_eq!(tax.tax_cents, 7820);
+    }
+
+    #[test]
+    fn inclusive_prices_back_out_the_tax() {
+        let settings = TaxSettings {
+            prices_include_tax: true,
+            ..vat(2000)
+        };
+        let tax = compute(&[item(12000, None)], 0, &settings);
+        assert_eq!(tax.tax_cents, 2000);
+        assert_eq!(tax.item_tax_included_cents, 2000);
+        assert_eq!(tax.groups[0].taxable_cents, 10000);
+    }
+
+    #[test]
+    fn reverse_charge_and_exempt_invoices_carry_no_tax() {
+        let settings = TaxSettings {
+            treatment: TaxTreatment::ReverseCharge,
+            prices_include_tax: true,
+            ..vat(2000)
+        };
+        let tax = compute(&[item(12000, Some(2000))], 0, &settings);
+        assert_eq!(tax.tax_cents, 0);
+        assert_eq!(tax.item_tax_included_cents, 0);
+        assert_eq!(tax.groups[0].label, "VAT reverse charge");
+        assert_eq!(tax.groups[0].taxable_cents, 12000);
+    }
+
+    #[test]
+    fn jurisdiction_defaults_override_the_agency_defaults() {
+        let agency: AgencyTaxSettings = serde_json::from_value(serde_json::json!({
+            "default_tax_rate": 20,
+            "tax_display_name": "VAT",
+            "include_tax_in_displayed_prices": false,
+            "tax_jurisdictions": {
+                "DE": { "treatment": "reverse_charge" },
+                "IE": { "rate_bps": 2300, "prices_include_tax": true }
+            }
+        }))
+        .unwrap();
+        let gb = defaults(Some(&agency), Some("gb"));
+        assert_eq!(gb.settings.default_rate_bps, 2000);
+        assert_eq!(gb.note, None);
+        let de = defaults(Some(&agency), Some("de"));
+        assert_eq!(de.settings.treatment, TaxTreatment::ReverseCharge);
+        assert_eq!(de.note.as_deref(), Some(REVERSE_CHARGE_NOTE));
+        let ie = defaults(Some(&agency), Some("IE"));
+        assert_eq!(ie.settings.default_rate_bps, 2300);
+        assert!(ie.settings.prices_include_tax);
+        assert_eq!(defaults(None, Some("GB")).settings, TaxSettings::default());
+    }
+}
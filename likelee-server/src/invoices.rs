@@ -3,17 +3,31 @@ use crate::{
     config::AppState,
     email,
     email_templates::{load_active_email_template, render_placeholders},
+    errors::{AppError, AppResult},
+    invoice_pdf,
+    invoice_tax::{self, TaxGroup, TaxLine, TaxSettings, TaxTreatment},
+    repositories::{
+        fetch, Invoice, InvoiceCreditNote, InvoiceExpense, InvoiceFilter, InvoiceItem,
+        InvoicePayment, InvoiceStatus, NewInvoicePayment,
+    },
+    storage::{sanitize_file_name, sanitize_segment, Bucket},
 };
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use chrono::{NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
 use uuid::Uuid;
 
+const PDF_CONTENT_TYPE: &str = "application/pdf";
+
 #[derive(Debug, Deserialize)]
 pub struct InvoiceListParams {
     pub status: Option<String>,
@@ -25,103 +39,91 @@ pub async fn list(
     State(state): State<AppState>,
     user: AuthUser,
     Query(params): Query<InvoiceListParams>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let mut req = state
-        .pg
-        .from("agency_invoices")
-        .select("*")
-        .eq("agency_id", &user.id)
-        .order("created_at.desc");
-
-    if let Some(s) = params.status.as_ref().filter(|s| !s.is_empty()) {
-        req = req.eq("status", s);
-    }
-    if let Some(d) = params.date_start.as_ref().filter(|s| !s.is_empty()) {
-        req = req.gte("invoice_date", d);
-    }
-    if let Some(d) = params.date_end.as_ref().filter(|s| !s.is_empty()) {
-        req = req.lte("invoice_date", d);
-    }
-
-    let resp = req
-        .execute()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let text = resp
-        .text()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    Ok(Json(v))
+) -> AppResult<Json<Vec<Invoice>>> {
+    let filter = InvoiceFilter {
+        status: params.status,
+        date_start: params.date_start,
+        date_end: params.date_end,
+    };
+    let invoices = state
+        .repos
+        .invoices
+        .list_for_agency(&user.id, &filter)
+        .await?;
+    Ok(Json(invoices))
 }
 
 pub async fn send_payment_reminder(
     State(state): State<AppState>,
     user: AuthUser,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> AppResult<Json<serde_json::Value>> {
     let current = ensure_invoice_owned(&state, &user, &id).await?;
 
-    let status = current
-        .get("status")
-        .and_then(|v| v.as_str())
-        .unwrap_or("draft");
-    if status != "sent" {
-        return Err((
-            StatusCode::CONFLICT,
+    if !current.status.is_outstanding() {
+        return Err(AppError::Conflict(
             "Only sent invoices can be reminded".to_string(),
         ));
     }
 
     let dest = current
-        .get("bill_to_email")
-        .and_then(|x| x.as_str())
+        .bill_to_email
+        .as_deref()
         .unwrap_or("")
         .trim()
         .to_string();
     if dest.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "missing_destination".to_string()));
+        return Err(AppError::BadRequest("missing_destination".to_string()));
     }
 
-    let invoice_number = current
-        .get("invoice_number")
-        .and_then(|x| x.as_str())
-        .unwrap_or("")
-        .to_string();
-    let invoice_total_cents = current
-        .get("total_cents")
-        .and_then(|x| x.as_i64())
-        .unwrap_or(0);
-    let invoice_total = format!("${:.2}", (invoice_total_cents as f64) / 100.0);
-    let due_date = current
-        .get("due_date")
-        .and_then(|x| x.as_str())
-        .unwrap_or("")
-        .to_string();
-    let client_name = current
-        .get("bill_to_company")
-        .and_then(|x| x.as_str())
-        .unwrap_or("")
-        .to_string();
+    let message = payment_reminder_email(
+        &state,
+        &current,
+        &dest,
+        &["payment_reminder"],
+        &PaymentReminderCopy {
+            subject: "Payment Reminder - Invoice {invoice_number}",
+            body: "Dear {client_name},\n\nThis is a friendly reminder that invoice {invoice_number} for {invoice_total} is due on {due_date}.\n\nIf you have already made the payment, please disregard this message.\n\nThank you,\n{agency_name}",
+        },
+    )
+    .await?;
+    let queued = email::enqueue(&state, message).await?;
 
+    Ok(Json(json!({"status":"ok", "email_id": queued.id})))
+}
+
+/// Built-in reminder wording, used when the agency has no active template.
+pub(crate) struct PaymentReminderCopy {
+    pub subject: &'static str,
+    pub body: &'static str,
+}
+
+/// Builds a payment reminder for `invoice` addressed to `dest`, using the
+/// first active agency template among `template_keys`. The PDF the client was
+/// sent is attached when it can be loaded.
+pub(crate) async fn payment_reminder_email(
+    state: &AppState,
+    invoice: &Invoice,
+    dest: &str,
+    template_keys: &[&str],
+    fallback: &PaymentReminderCopy,
+) -> AppResult<email::OutgoingEmail> {
     let mut agency_email: Option<String> = None;
     let mut agency_name: Option<String> = None;
     let resp = state
         .pg
         .from("agencies")
         .select("email,agency_name")
-        .eq("id", &user.id)
+        .eq("id", &invoice.agency_id)
         .single()
         .execute()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
     let status = resp.status();
     let txt = resp
         .text()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
     if status.is_success() {
         if let Ok(v) = serde_json::from_str::<serde_json::Value>(&txt) {
             agency_email = v
@@ -135,43 +137,61 @@ pub async fn send_payment_reminder(
         }
     }
 
-    let fallback_subject = "Payment Reminder - Invoice {invoice_number}".to_string();
-    let fallback_body =
-        "Dear {client_name},\n\nThis is a friendly reminder that invoice {invoice_number} for {invoice_total} is due on {due_date}.\n\nIf you have already made the payment, please disregard this message.\n\nThank you,\n{agency_name}".to_string();
-
+    let days_overdue = (Utc::now().date_naive() - invoice.due_date)
+        .num_days()
+        .max(0);
     let vars: Vec<(&str, String)> = vec![
-        ("client_name", client_name),
-        ("invoice_number", invoice_number),
-        ("invoice_total", invoice_total),
-        ("due_date", due_date),
+        ("client_name", invoice.bill_to_company.clone()),
+        ("invoice_number", invoice.invoice_number.clone()),
+        (
+            "invoice_total",
+            format!("${:.2}", (invoice.total_cents as f64) / 100.0),
+        ),
+        (
+            "balance_due",
+            format!("${:.2}", (invoice.balance_due_cents() as f64) / 100.0),
+        ),
+        ("due_date", invoice.due_date.format("%Y-%m-%d").to_string()),
+        ("days_overdue", days_overdue.to_string()),
         ("agency_name", agency_name.clone().unwrap_or_default()),
     ];
 
-    let (subject, body) =
-        match load_active_email_template(&state, &user.id, "payment_reminder").await {
-            Ok(Some(tpl)) => (
-                render_placeholders(&tpl.subject, &vars),
-                render_placeholders(&tpl.body, &vars),
-            ),
-            _ => (
-                render_placeholders(&fallback_subject, &vars),
-                render_placeholders(&fallback_body, &vars),
-            ),
-        };
-
-    email::send_plain_text_email(&state, &dest, &subject, &body, agency_email.as_deref()).map_err(
-        |(code, msg)| {
-            (
-                StatusCode::BAD_GATEWAY,
-                format!("email_send_failed upstream_status={} message={}", code, msg),
-            )
-        },
-    )?;
+    let mut template = None;
+    for key in template_keys {
+        if let Ok(Some(tpl)) = load_active_email_template(state, &invoice.agency_id, key).await {
+            template = Some(tpl);
+            break;
+        }
+    }
+    let (subject, body) = match template {
+        Some(tpl) => (
+            render_placeholders(&tpl.subject, &vars),
+            render_placeholders(&tpl.body, &vars),
+        ),
+        None => (
+            render_placeholders(fallback.subject, &vars),
+            render_placeholders(fallback.body, &vars),
+        ),
+    };
 
-    Ok(Json(json!({"status":"ok"})))
+    let mut message = email::OutgoingEmail::new(dest, &subject, &body)
+        .from_name(agency_email.as_deref())
+        .category("invoice_reminder")
+        .agency(&invoice.agency_id)
+        .client(Some(&invoice.client_id))
+        .reference("invoice", &invoice.id);
+    match sent_pdf(state, invoice).await {
+        Ok(bytes) => message = message.attach(pdf_attachment(invoice, &bytes)),
+        Err(e) => tracing::warn!(
+            "invoice_pdf_unavailable invoice_id={} error={}",
+            invoice.id,
+            e
+        ),
+    }
+    Ok(message)
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateInvoiceItemInput {
     pub description: String,
     pub talent_id: Option<String>,
@@ -180,16 +200,19 @@ pub struct CreateInvoiceItemInput {
     pub rate_type: Option<String>,
     pub quantity: Option<f64>,
     pub unit_price_cents: Option<i32>,
+    /// Overrides the invoice's tax rate for this line.
+    pub tax_rate_bps: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateInvoiceExpenseInput {
     pub description: String,
     pub amount_cents: Option<i32>,
     pub taxable: Option<bool>,
+    pub tax_rate_bps: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct CreateInvoicePayload {
     pub client_id: String,
     pub source_booking_id: Option<String>,
@@ -207,6 +230,8 @@ pub struct CreateInvoicePayload {
     pub tax_rate_bps: Option<i32>,
     pub tax_exempt: Option<bool>,
     pub discount_cents: Option<i32>,
+    #[serde(flatten)]
+    pub tax: TaxFieldsInput,
 
     pub notes_internal: Option<String>,
     pub payment_instructions: Option<String>,
@@ -216,71 +241,274 @@ pub struct CreateInvoicePayload {
     pub expenses: Option<Vec<CreateInvoiceExpenseInput>>,
 }
 
+/// Tax fields accepted on create and update; unset ones fall back to the
+/// invoice's current values or the agency's tax settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TaxFieldsInput {
+    pub prices_include_tax: Option<bool>,
+    pub tax_treatment: Option<String>,
+    pub tax_jurisdiction: Option<String>,
+    pub customer_tax_id: Option<String>,
+    pub tax_note: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct InvoiceDetail {
-    pub invoice: serde_json::Value,
-    pub items: serde_json::Value,
-    pub expenses: serde_json::Value,
+    pub invoice: Invoice,
+    pub items: Vec<InvoiceItem>,
+    pub expenses: Vec<InvoiceExpense>,
+    pub payments: Vec<InvoicePayment>,
+    pub credit_notes: Vec<InvoiceCreditNote>,
+    pub balance_due_cents: i64,
+    pub tax_summary: Vec<TaxGroup>,
+}
+
+/// Amounts stored on an invoice.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct InvoiceTotals {
+    pub subtotal_cents: i64,
+    pub expenses_cents: i64,
+    pub tax_cents: i64,
+    pub total_cents: i64,
+    pub agency_fee_cents: i64,
+    pub talent_net_cents: i64,
+    pub tax_summary: Vec<TaxGroup>,
 }
 
-fn compute_totals(
+pub(crate) fn compute_totals(
     items: &[serde_json::Value],
     expenses: &[serde_json::Value],
     agency_commission_bps: i64,
-    tax_rate_bps: i64,
-    tax_exempt: bool,
+    tax: &TaxSettings,
     discount_cents: i64,
-) -> (i64, i64, i64, i64, i64, i64) {
-    let subtotal_cents: i64 = items
-        .iter()
-        .map(|it| {
-            it.get("line_total_cents")
-                .and_then(|v| v.as_i64())
-                .unwrap_or(0)
-        })
-        .sum();
+) -> InvoiceTotals {
+    let rate = |v: &serde_json::Value| v.get("tax_rate_bps").and_then(|r| r.as_i64());
+    let item_lines = items.iter().map(|it| TaxLine {
+        amount_cents: it
+            .get("line_total_cents")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0),
+        rate_bps: rate(it),
+        zero_rated: false,
+        is_item: true,
+    });
+    let expense_lines = expenses.iter().map(|ex| TaxLine {
+        amount_cents: ex.get("amount_cents").and_then(|v| v.as_i64()).unwrap_or(0),
+        rate_bps: rate(ex),
+        zero_rated: !ex.get("taxable").and_then(|v| v.as_bool()).unwrap_or(false),
+        is_item: false,
+    });
+    let lines: Vec<TaxLine> = item_lines.chain(expense_lines).collect();
 
-    let expenses_cents: i64 = expenses
+    let subtotal_cents: i64 = lines
         .iter()
-        .map(|ex| ex.get("amount_cents").and_then(|v| v.as_i64()).unwrap_or(0))
+        .filter(|l| l.is_item)
+        .map(|l| l.amount_cents)
         .sum();
-
-    let taxable_expenses_cents: i64 = expenses
+    let expenses_cents: i64 = lines
         .iter()
-        .filter(|ex| ex.get("taxable").and_then(|v| v.as_bool()).unwrap_or(false))
-        .map(|ex| ex.get("amount_cents").and_then(|v| v.as_i64()).unwrap_or(0))
+        .filter(|l| !l.is_item)
+        .map(|l| l.amount_cents)
         .sum();
 
-    let mut taxable_base_cents = subtotal_cents + taxable_expenses_cents - discount_cents;
-    if taxable_base_cents < 0 {
-        taxable_base_cents = 0;
-    }
+    let breakdown = invoice_tax::compute(&lines, discount_cents, tax);
 
-    let tax_cents = if tax_exempt {
+    // Tax-inclusive prices already contain their tax.
+    let added_tax_cents = if tax.prices_include_tax {
         0
     } else {
-        (taxable_base_cents * tax_rate_bps) / 10_000
+        breakdown.tax_cents
     };
+    let total_cents = (subtotal_cents + expenses_cents - discount_cents + added_tax_cents).max(0);
 
-    let mut total_cents = (subtotal_cents + expenses_cents - discount_cents) + tax_cents;
-    if total_cents < 0 {
-        total_cents = 0;
-    }
-
-    let agency_fee_cents = (subtotal_cents * agency_commission_bps) / 10_000;
-    let talent_net_cents = subtotal_cents - agency_fee_cents;
+    // Commission is charged on the items net of any tax they include.
+    let commission_base_cents = subtotal_cents - breakdown.item_tax_included_cents;
+    let agency_fee_cents = (commission_base_cents * agency_commission_bps) / 10_000;
+    let talent_net_cents = commission_base_cents - agency_fee_cents;
 
-    (
+    InvoiceTotals {
         subtotal_cents,
         expenses_cents,
-        tax_cents,
+        tax_cents: breakdown.tax_cents,
         total_cents,
         agency_fee_cents,
         talent_net_cents,
-    )
+        tax_summary: breakdown.groups,
+    }
+}
+
+/// The tax settings an invoice was issued under.
+pub(crate) fn invoice_tax_settings(invoice: &Invoice) -> TaxSettings {
+    TaxSettings {
+        default_rate_bps: invoice.tax_rate_bps as i64,
+        prices_include_tax: invoice.prices_include_tax,
+        treatment: if invoice.tax_exempt {
+            TaxTreatment::Exempt
+        } else {
+            invoice.tax_treatment
+        },
+        label: invoice
+            .tax_label
+            .clone()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| "Tax".to_string()),
+    }
 }
 
-fn normalize_items(inputs: &[CreateInvoiceItemInput]) -> Vec<serde_json::Value> {
+/// Totals of a stored invoice, recomputed from its items and expenses.
+pub(crate) fn stored_totals(
+    invoice: &Invoice,
+    items: &[InvoiceItem],
+    expenses: &[InvoiceExpense],
+) -> AppResult<InvoiceTotals> {
+    Ok(compute_totals(
+        &items
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?,
+        &expenses
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?,
+        invoice.agency_commission_bps as i64,
+        &invoice_tax_settings(invoice),
+        invoice.discount_cents as i64,
+    ))
+}
+
+impl InvoiceTotals {
+    fn columns(&self) -> serde_json::Value {
+        json!({
+            "subtotal_cents": self.subtotal_cents as i32,
+            "expenses_cents": self.expenses_cents as i32,
+            "tax_cents": self.tax_cents as i32,
+            "total_cents": self.total_cents as i32,
+            "agency_fee_cents": self.agency_fee_cents as i32,
+            "talent_net_cents": self.talent_net_cents as i32,
+        })
+    }
+}
+
+fn extend(row: &mut serde_json::Value, columns: serde_json::Value) {
+    if let (Some(row), serde_json::Value::Object(columns)) = (row.as_object_mut(), columns) {
+        row.extend(columns);
+    }
+}
+
+/// Resolved tax fields of an invoice being created or updated.
+pub(crate) struct InvoiceTax {
+    pub(crate) settings: TaxSettings,
+    jurisdiction: Option<String>,
+    customer_tax_id: Option<String>,
+    note: Option<String>,
+}
+
+impl InvoiceTax {
+    /// Applies the explicit fields of a create or update request on top of
+    /// these, and checks the result is a valid invoice.
+    fn resolve(
+        mut self,
+        input: &TaxFieldsInput,
+        tax_rate_bps: Option<i32>,
+        tax_exempt: Option<bool>,
+    ) -> AppResult<Self> {
+        if let Some(rate) = tax_rate_bps {
+            if rate < 0 {
+                return Err(AppError::BadRequest("invalid_tax_rate".to_string()));
+            }
+            self.settings.default_rate_bps = rate as i64;
+        }
+        if let Some(inclusive) = input.prices_include_tax {
+            self.settings.prices_include_tax = inclusive;
+        }
+        if let Some(treatment) = input.tax_treatment.as_deref() {
+            self.settings.treatment = TaxTreatment::parse(treatment)
+                .ok_or_else(|| AppError::BadRequest("invalid_tax_treatment".to_string()))?;
+        } else if let Some(exempt) = tax_exempt {
+            // Older clients only send the exempt flag.
+            self.settings.treatment = if exempt {
+                TaxTreatment::Exempt
+            } else if self.settings.treatment == TaxTreatment::Exempt {
+                TaxTreatment::Standard
+            } else {
+                self.settings.treatment
+            };
+        }
+        let non_empty = |v: &Option<String>| {
+            v.as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        if let Some(id) = non_empty(&input.customer_tax_id) {
+            self.customer_tax_id = Some(id);
+        }
+        if let Some(note) = non_empty(&input.tax_note) {
+            self.note = Some(note);
+        }
+        if self.settings.treatment == TaxTreatment::ReverseCharge {
+            // The customer's VAT number is part of the legal requirement.
+            if self.customer_tax_id.is_none() {
+                return Err(AppError::BadRequest("missing_customer_tax_id".to_string()));
+            }
+            if self.note.is_none() {
+                self.note = Some(invoice_tax::REVERSE_CHARGE_NOTE.to_string());
+            }
+        }
+        Ok(self)
+    }
+
+    fn columns(&self) -> serde_json::Value {
+        json!({
+            "tax_rate_bps": self.settings.default_rate_bps as i32,
+            "tax_exempt": self.settings.treatment == TaxTreatment::Exempt,
+            "tax_treatment": self.settings.treatment.as_str(),
+            "prices_include_tax": self.settings.prices_include_tax,
+            "tax_label": self.settings.label,
+            "tax_jurisdiction": self.jurisdiction,
+            "customer_tax_id": self.customer_tax_id,
+            "tax_note": self.note,
+        })
+    }
+}
+
+/// Tax for a new invoice to `client`: the agency defaults for the payload's
+/// jurisdiction, or else the client's, overridden by the payload's fields.
+pub(crate) async fn draft_tax(
+    state: &AppState,
+    agency_id: &str,
+    client: &serde_json::Value,
+    payload: &CreateInvoicePayload,
+) -> AppResult<InvoiceTax> {
+    let client_str = |key: &str| {
+        client
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+    let jurisdiction = payload
+        .tax
+        .tax_jurisdiction
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .or_else(|| client_str("tax_jurisdiction"))
+        .map(|j| j.to_ascii_uppercase());
+    let agency_tax = invoice_tax::load_agency_settings(state, agency_id).await?;
+    let defaults = invoice_tax::defaults(agency_tax.as_ref(), jurisdiction.as_deref());
+    InvoiceTax {
+        settings: defaults.settings,
+        jurisdiction,
+        customer_tax_id: client_str("tax_id"),
+        note: defaults.note,
+    }
+    .resolve(&payload.tax, payload.tax_rate_bps, payload.tax_exempt)
+}
+
+pub(crate) fn normalize_items(inputs: &[CreateInvoiceItemInput]) -> Vec<serde_json::Value> {
     inputs
         .iter()
         .enumerate()
@@ -298,12 +526,13 @@ fn normalize_items(inputs: &[CreateInvoiceItemInput]) -> Vec<serde_json::Value>
                 "quantity": qty,
                 "unit_price_cents": it.unit_price_cents.unwrap_or(0),
                 "line_total_cents": line_total_cents,
+                "tax_rate_bps": it.tax_rate_bps,
             })
         })
         .collect()
 }
 
-fn normalize_expenses(inputs: &[CreateInvoiceExpenseInput]) -> Vec<serde_json::Value> {
+pub(crate) fn normalize_expenses(inputs: &[CreateInvoiceExpenseInput]) -> Vec<serde_json::Value> {
     inputs
         .iter()
         .enumerate()
@@ -313,68 +542,67 @@ fn normalize_expenses(inputs: &[CreateInvoiceExpenseInput]) -> Vec<serde_json::V
                 "description": ex.description,
                 "amount_cents": ex.amount_cents.unwrap_or(0),
                 "taxable": ex.taxable.unwrap_or(false),
+                "tax_rate_bps": ex.tax_rate_bps,
             })
         })
         .collect()
 }
 
-async fn get_client_snapshot(
+pub(crate) async fn get_client_snapshot(
     state: &AppState,
-    user: &AuthUser,
+    agency_id: &str,
     client_id: &str,
-) -> Result<serde_json::Value, (StatusCode, String)> {
+) -> AppResult<serde_json::Value> {
     let resp = state
         .pg
         .from("agency_clients")
-        .select("id,agency_id,company,contact_name,email,phone")
+        .select("id,agency_id,company,contact_name,email,phone,tax_jurisdiction,tax_id")
         .eq("id", client_id)
-        .eq("agency_id", &user.id)
+        .eq("agency_id", agency_id)
         .single()
         .execute()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
 
     let status = resp.status();
     let text = resp
         .text()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
     if !status.is_success() {
-        return Err(crate::errors::sanitize_db_error(status.as_u16(), text));
+        return Err(AppError::db(status.as_u16(), text));
     }
 
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let v: serde_json::Value = serde_json::from_str(&text)?;
     Ok(v)
 }
 
 async fn get_booking(
     state: &AppState,
-    user: &AuthUser,
+    agency_id: &str,
     booking_id: &str,
-) -> Result<serde_json::Value, (StatusCode, String)> {
+) -> AppResult<serde_json::Value> {
     let resp = state
         .pg
         .from("bookings")
         .select("id,agency_user_id,talent_id,talent_name,client_id,client_name,date,rate_cents,currency,rate_type,status")
         .eq("id", booking_id)
-        .eq("agency_user_id", &user.id)
+        .eq("agency_user_id", agency_id)
         .single()
         .execute()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
 
     let status = resp.status();
     let text = resp
         .text()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
     if !status.is_success() {
-        return Err(crate::errors::sanitize_db_error(status.as_u16(), text));
+        return Err(AppError::db(status.as_u16(), text));
     }
 
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let v: serde_json::Value = serde_json::from_str(&text)?;
     Ok(v)
 }
 
@@ -382,52 +610,60 @@ async fn ensure_invoice_owned(
     state: &AppState,
     user: &AuthUser,
     invoice_id: &str,
-) -> Result<serde_json::Value, (StatusCode, String)> {
-    let resp = state
-        .pg
-        .from("agency_invoices")
-        .select("*")
-        .eq("id", invoice_id)
-        .eq("agency_id", &user.id)
-        .single()
-        .execute()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    let status = resp.status();
-    let text = resp
-        .text()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    if !status.is_success() {
-        return Err(crate::errors::sanitize_db_error(status.as_u16(), text));
-    }
-
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    Ok(v)
+) -> AppResult<Invoice> {
+    Ok(state
+        .repos
+        .invoices
+        .get_for_agency(&user.id, invoice_id)
+        .await?)
 }
 
 pub async fn create(
     State(state): State<AppState>,
     user: AuthUser,
     Json(payload): Json<CreateInvoicePayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> AppResult<Json<serde_json::Value>> {
+    let invoice = create_draft(&state, &user.id, payload).await?;
+    let id = invoice
+        .get("id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let number = invoice
+        .get("invoice_number")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    crate::audit::record(
+        &state,
+        crate::audit::AuditEvent::new(&user.id, "invoice.created", "agency_invoices", id)
+            .actor(&user)
+            .title(format!("Invoice {number} created"))
+            .change(&(), &invoice),
+    )
+    .await;
+    Ok(Json(invoice))
+}
+
+/// Creates a draft invoice with its items and expenses, numbered by the
+/// agency's `next_invoice_number` sequence.
+pub(crate) async fn create_draft(
+    state: &AppState,
+    agency_id: &str,
+    payload: CreateInvoicePayload,
+) -> AppResult<serde_json::Value> {
     if payload
         .invoice_number
         .as_ref()
         .is_some_and(|s| !s.trim().is_empty())
     {
-        return Err((
-            StatusCode::BAD_REQUEST,
+        return Err(AppError::BadRequest(
             "invoice_number is system-generated and cannot be provided".to_string(),
         ));
     }
 
-    let client = get_client_snapshot(&state, &user, &payload.client_id).await?;
+    let client = get_client_snapshot(state, agency_id, &payload.client_id).await?;
 
     let booking = if let Some(bid) = payload.source_booking_id.as_ref() {
-        Some(get_booking(&state, &user, bid).await?)
+        Some(get_booking(state, agency_id, bid).await?)
     } else {
         None
     };
@@ -446,8 +682,7 @@ pub async fn create(
         .unwrap_or_else(|| invoice_date + chrono::Duration::days(30));
 
     let agency_commission_bps = payload.agency_commission_bps.unwrap_or(2000) as i64;
-    let tax_rate_bps = payload.tax_rate_bps.unwrap_or(0) as i64;
-    let tax_exempt = payload.tax_exempt.unwrap_or(false);
+    let tax = draft_tax(state, agency_id, &client, &payload).await?;
     let discount_cents = payload.discount_cents.unwrap_or(0) as i64;
 
     let mut items_in: Vec<CreateInvoiceItemInput> = payload.items.unwrap_or_default();
@@ -479,6 +714,7 @@ pub async fn create(
                 rate_type,
                 quantity: Some(1.0),
                 unit_price_cents: Some(rate_cents),
+                tax_rate_bps: None,
             });
         }
     }
@@ -486,43 +722,34 @@ pub async fn create(
     let items_norm = normalize_items(&items_in);
     let expenses_norm = normalize_expenses(&payload.expenses.unwrap_or_default());
 
-    let (
-        subtotal_cents,
-        expenses_cents,
-        tax_cents,
-        total_cents,
-        agency_fee_cents,
-        talent_net_cents,
-    ) = compute_totals(
+    let totals = compute_totals(
         &items_norm,
         &expenses_norm,
         agency_commission_bps,
-        tax_rate_bps,
-        tax_exempt,
+        &tax.settings,
         discount_cents,
     );
 
     // Invoice number is always system-generated
-    let body = json!({ "p_agency_id": user.id });
+    let body = json!({ "p_agency_id": agency_id });
     let resp = state
         .pg
         .rpc("next_invoice_number", body.to_string())
         .execute()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
     let status = resp.status();
     let text = resp
         .text()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
     if !status.is_success() {
         let code =
             StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-        return Err(crate::errors::sanitize_db_error(code.as_u16(), text));
+        return Err(AppError::db(code.as_u16(), text));
     }
     // Postgrest returns JSON; accept either {"next_invoice_number":"..."} or string
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let v: serde_json::Value = serde_json::from_str(&text)?;
     let invoice_number = v
         .as_str()
         .map(|s| s.to_string())
@@ -542,8 +769,8 @@ pub async fn create(
             format!("INVC{}{:07}", letter, digits)
         });
 
-    let inv_row = json!({
-        "agency_id": user.id,
+    let mut inv_row = json!({
+        "agency_id": agency_id,
         "client_id": payload.client_id,
         "booking_id": payload.source_booking_id,
 
@@ -565,21 +792,14 @@ pub async fn create(
         "payment_terms": payload.payment_terms.unwrap_or_else(|| "net_30".to_string()),
 
         "agency_commission_bps": agency_commission_bps as i32,
-        "tax_rate_bps": tax_rate_bps as i32,
-        "tax_exempt": tax_exempt,
         "discount_cents": discount_cents as i32,
 
         "notes_internal": payload.notes_internal,
         "payment_instructions": payload.payment_instructions,
         "footer_text": payload.footer_text,
-
-        "subtotal_cents": subtotal_cents as i32,
-        "expenses_cents": expenses_cents as i32,
-        "tax_cents": tax_cents as i32,
-        "total_cents": total_cents as i32,
-        "agency_fee_cents": agency_fee_cents as i32,
-        "talent_net_cents": talent_net_cents as i32,
     });
+    extend(&mut inv_row, tax.columns());
+    extend(&mut inv_row, totals.columns());
 
     let resp = state
         .pg
@@ -587,33 +807,29 @@ pub async fn create(
         .insert(inv_row.to_string())
         .execute()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
 
     let status = resp.status();
     let text = resp
         .text()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
     if !status.is_success() {
         let code =
             StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-        return Err(crate::errors::sanitize_db_error(code.as_u16(), text));
+        return Err(AppError::db(code.as_u16(), text));
     }
 
-    let created: Vec<serde_json::Value> = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let invoice = created.first().cloned().ok_or((
-        StatusCode::INTERNAL_SERVER_ERROR,
-        "create returned empty".to_string(),
-    ))?;
+    let created: Vec<serde_json::Value> = serde_json::from_str(&text)?;
+    let invoice = created
+        .first()
+        .cloned()
+        .ok_or_else(|| AppError::internal("invoices.create", "create returned empty"))?;
 
     let invoice_id = invoice
         .get("id")
         .and_then(|v| v.as_str())
-        .ok_or((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "missing invoice id".to_string(),
-        ))?
+        .ok_or_else(|| AppError::internal("invoices.create", "missing invoice id"))?
         .to_string();
 
     // Insert items
@@ -633,7 +849,7 @@ pub async fn create(
             .insert(serde_json::to_string(&rows).unwrap_or_else(|_| "[]".to_string()))
             .execute()
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .map_err(|e| AppError::internal("invoices.db", e))?;
     }
 
     // Insert expenses
@@ -653,55 +869,32 @@ pub async fn create(
             .insert(serde_json::to_string(&rows).unwrap_or_else(|_| "[]".to_string()))
             .execute()
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .map_err(|e| AppError::internal("invoices.db", e))?;
     }
 
-    Ok(Json(invoice))
+    Ok(invoice)
 }
 
 pub async fn get(
     State(state): State<AppState>,
     user: AuthUser,
     Path(id): Path<String>,
-) -> Result<Json<InvoiceDetail>, (StatusCode, String)> {
+) -> AppResult<Json<InvoiceDetail>> {
     let invoice = ensure_invoice_owned(&state, &user, &id).await?;
-
-    let items_resp = state
-        .pg
-        .from("agency_invoice_items")
-        .select("*")
-        .eq("invoice_id", &id)
-        .order("sort_order.asc")
-        .execute()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let items_text = items_resp
-        .text()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let items: serde_json::Value = serde_json::from_str(&items_text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    let exp_resp = state
-        .pg
-        .from("agency_invoice_expenses")
-        .select("*")
-        .eq("invoice_id", &id)
-        .order("sort_order.asc")
-        .execute()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let exp_text = exp_resp
-        .text()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let expenses: serde_json::Value = serde_json::from_str(&exp_text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let items = state.repos.invoices.items(&id).await?;
+    let expenses = state.repos.invoices.expenses(&id).await?;
+    let payments = state.repos.invoices.payments(&id).await?;
+    let credit_notes = state.repos.invoices.credit_notes(&id).await?;
+    let tax_summary = stored_totals(&invoice, &items, &expenses)?.tax_summary;
 
     Ok(Json(InvoiceDetail {
+        balance_due_cents: invoice.balance_due_cents(),
+        tax_summary,
         invoice,
         items,
         expenses,
+        payments,
+        credit_notes,
     }))
 }
 
@@ -720,6 +913,8 @@ pub struct UpdateInvoicePayload {
     pub tax_rate_bps: Option<i32>,
     pub tax_exempt: Option<bool>,
     pub discount_cents: Option<i32>,
+    #[serde(flatten)]
+    pub tax: TaxFieldsInput,
 
     pub notes_internal: Option<String>,
     pub payment_instructions: Option<String>,
@@ -734,65 +929,59 @@ pub async fn update(
     user: AuthUser,
     Path(id): Path<String>,
     Json(payload): Json<UpdateInvoicePayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> AppResult<Json<serde_json::Value>> {
     if payload
         .invoice_number
         .as_ref()
         .is_some_and(|s| !s.trim().is_empty())
     {
-        return Err((
-            StatusCode::BAD_REQUEST,
+        return Err(AppError::BadRequest(
             "invoice_number is system-generated and cannot be provided".to_string(),
         ));
     }
 
     let current = ensure_invoice_owned(&state, &user, &id).await?;
 
-    let status = current
-        .get("status")
-        .and_then(|v| v.as_str())
-        .unwrap_or("draft");
-    if status != "draft" {
-        return Err((
-            StatusCode::CONFLICT,
+    if current.status != InvoiceStatus::Draft {
+        return Err(AppError::Conflict(
             "Only draft invoices can be updated".to_string(),
         ));
     }
 
     let agency_commission_bps = payload
         .agency_commission_bps
-        .or_else(|| {
-            current
-                .get("agency_commission_bps")
-                .and_then(|v| v.as_i64())
-                .map(|v| v as i32)
-        })
-        .unwrap_or(2000) as i64;
-
-    let tax_rate_bps = payload
-        .tax_rate_bps
-        .or_else(|| {
-            current
-                .get("tax_rate_bps")
-                .and_then(|v| v.as_i64())
-                .map(|v| v as i32)
-        })
-        .unwrap_or(0) as i64;
-
-    let tax_exempt = payload
-        .tax_exempt
-        .or_else(|| current.get("tax_exempt").and_then(|v| v.as_bool()))
-        .unwrap_or(false);
-
-    let discount_cents = payload
-        .discount_cents
-        .or_else(|| {
-            current
-                .get("discount_cents")
-                .and_then(|v| v.as_i64())
-                .map(|v| v as i32)
-        })
-        .unwrap_or(0) as i64;
+        .unwrap_or(current.agency_commission_bps) as i64;
+    let discount_cents = payload.discount_cents.unwrap_or(current.discount_cents) as i64;
+
+    // Moving the invoice to another jurisdiction starts again from that
+    // jurisdiction's defaults.
+    let new_jurisdiction = payload
+        .tax
+        .tax_jurisdiction
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_ascii_uppercase)
+        .filter(|j| current.tax_jurisdiction.as_deref() != Some(j.as_str()));
+    let tax = match new_jurisdiction {
+        Some(jurisdiction) => {
+            let agency_tax = invoice_tax::load_agency_settings(&state, &user.id).await?;
+            let defaults = invoice_tax::defaults(agency_tax.as_ref(), Some(&jurisdiction));
+            InvoiceTax {
+                settings: defaults.settings,
+                jurisdiction: Some(jurisdiction),
+                customer_tax_id: current.customer_tax_id.clone(),
+                note: defaults.note,
+            }
+        }
+        None => InvoiceTax {
+            settings: invoice_tax_settings(&current),
+            jurisdiction: current.tax_jurisdiction.clone(),
+            customer_tax_id: current.customer_tax_id.clone(),
+            note: current.tax_note.clone(),
+        },
+    }
+    .resolve(&payload.tax, payload.tax_rate_bps, payload.tax_exempt)?;
 
     // Replace items/expenses if provided
     let mut items_norm: Vec<serde_json::Value> = vec![];
@@ -807,7 +996,7 @@ pub async fn update(
             .eq("invoice_id", &id)
             .execute()
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .map_err(|e| AppError::internal("invoices.db", e))?;
 
         if !items_norm.is_empty() {
             let rows: Vec<serde_json::Value> = items_norm
@@ -826,7 +1015,7 @@ pub async fn update(
                 .insert(serde_json::to_string(&rows).unwrap_or_else(|_| "[]".to_string()))
                 .execute()
                 .await
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                .map_err(|e| AppError::internal("invoices.db", e))?;
         }
     }
 
@@ -839,7 +1028,7 @@ pub async fn update(
             .eq("invoice_id", &id)
             .execute()
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .map_err(|e| AppError::internal("invoices.db", e))?;
 
         if !expenses_norm.is_empty() {
             let rows: Vec<serde_json::Value> = expenses_norm
@@ -858,7 +1047,7 @@ pub async fn update(
                 .insert(serde_json::to_string(&rows).unwrap_or_else(|_| "[]".to_string()))
                 .execute()
                 .await
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                .map_err(|e| AppError::internal("invoices.db", e))?;
         }
     }
 
@@ -867,50 +1056,42 @@ pub async fn update(
         let resp = state
             .pg
             .from("agency_invoice_items")
-            .select("line_total_cents")
+            .select("line_total_cents,tax_rate_bps")
             .eq("invoice_id", &id)
             .execute()
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .map_err(|e| AppError::internal("invoices.db", e))?;
         let txt = resp
             .text()
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .map_err(|e| AppError::internal("invoices.db", e))?;
         items_norm = serde_json::from_str(&txt).unwrap_or_default();
     }
     if payload.expenses.is_none() {
         let resp = state
             .pg
             .from("agency_invoice_expenses")
-            .select("amount_cents,taxable")
+            .select("amount_cents,taxable,tax_rate_bps")
             .eq("invoice_id", &id)
             .execute()
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .map_err(|e| AppError::internal("invoices.db", e))?;
         let txt = resp
             .text()
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .map_err(|e| AppError::internal("invoices.db", e))?;
         expenses_norm = serde_json::from_str(&txt).unwrap_or_default();
     }
 
-    let (
-        subtotal_cents,
-        expenses_cents,
-        tax_cents,
-        total_cents,
-        agency_fee_cents,
-        talent_net_cents,
-    ) = compute_totals(
+    let totals = compute_totals(
         &items_norm,
         &expenses_norm,
         agency_commission_bps,
-        tax_rate_bps,
-        tax_exempt,
+        &tax.settings,
         discount_cents,
     );
 
-    let body = json!({
+    let mut body = json!({
         "invoice_date": payload.invoice_date,
         "due_date": payload.due_date,
         "payment_terms": payload.payment_terms,
@@ -920,22 +1101,15 @@ pub async fn update(
 
         "currency": payload.currency,
         "agency_commission_bps": agency_commission_bps as i32,
-        "tax_rate_bps": tax_rate_bps as i32,
-        "tax_exempt": tax_exempt,
         "discount_cents": discount_cents as i32,
 
         "notes_internal": payload.notes_internal,
         "payment_instructions": payload.payment_instructions,
         "footer_text": payload.footer_text,
-
-        "subtotal_cents": subtotal_cents as i32,
-        "expenses_cents": expenses_cents as i32,
-        "tax_cents": tax_cents as i32,
-        "total_cents": total_cents as i32,
-        "agency_fee_cents": agency_fee_cents as i32,
-        "talent_net_cents": talent_net_cents as i32,
         "updated_at": Utc::now().to_rfc3339(),
     });
+    extend(&mut body, tax.columns());
+    extend(&mut body, totals.columns());
 
     let resp = state
         .pg
@@ -945,106 +1119,125 @@ pub async fn update(
         .eq("agency_id", &user.id)
         .execute()
         .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "db_error".to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
 
     let status = resp.status();
     let text = resp
         .text()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| AppError::internal("invoices.db", e))?;
     if !status.is_success() {
         let code =
             StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-        return Err(crate::errors::sanitize_db_error(code.as_u16(), text));
+        return Err(AppError::db(code.as_u16(), text));
     }
 
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let v: serde_json::Value = serde_json::from_str(&text)?;
+    let after = v.as_array().and_then(|rows| rows.first()).unwrap_or(&v);
+    crate::audit::record(
+        &state,
+        crate::audit::AuditEvent::new(&user.id, "invoice.updated", "agency_invoices", &id)
+            .actor(&user)
+            .title(format!("Invoice {} edited", current.invoice_number))
+            .change(&current, after),
+    )
+    .await;
     Ok(Json(v))
 }
 
+/// Records an invoice status transition made by `user`.
+async fn audit_status_change(state: &AppState, user: &AuthUser, before: &Invoice, after: &Invoice) {
+    crate::audit::record(
+        state,
+        crate::audit::AuditEvent::new(
+            &user.id,
+            "invoice.status_changed",
+            "agency_invoices",
+            &after.id,
+        )
+        .actor(user)
+        .title(format!(
+            "Invoice {} marked {}",
+            after.invoice_number,
+            after.status.as_str()
+        ))
+        .change(before, after),
+    )
+    .await;
+}
+
 pub async fn mark_sent(
     State(state): State<AppState>,
     user: AuthUser,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> AppResult<Json<Vec<Invoice>>> {
     let current = ensure_invoice_owned(&state, &user, &id).await?;
-    let status = current
-        .get("status")
-        .and_then(|v| v.as_str())
-        .unwrap_or("draft");
-    if status != "draft" {
-        return Err((
-            StatusCode::CONFLICT,
+    Ok(Json(vec![send(&state, Some(&user), &current).await?]))
+}
+
+/// Issues a draft invoice: marks it sent, archives its PDF and emails it to
+/// the client. `actor` is `None` when a background job sends it.
+pub(crate) async fn send(
+    state: &AppState,
+    actor: Option<&AuthUser>,
+    current: &Invoice,
+) -> AppResult<Invoice> {
+    if current.status != InvoiceStatus::Draft {
+        return Err(AppError::Conflict(
             "Only draft invoices can be marked as sent".to_string(),
         ));
     }
-
-    let body = json!({
-        "status": "sent",
-        "sent_at": Utc::now().to_rfc3339(),
-        "updated_at": Utc::now().to_rfc3339(),
-    });
-
-    let resp = state
-        .pg
-        .from("agency_invoices")
-        .update(body.to_string())
-        .eq("id", &id)
-        .eq("agency_id", &user.id)
-        .execute()
-        .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "db_error".to_string()))?;
-
-    let status = resp.status();
-    let text = resp
-        .text()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    if !status.is_success() {
-        let code =
-            StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-        return Err(crate::errors::sanitize_db_error(code.as_u16(), text));
+    let id = &current.id;
+    let agency_id = &current.agency_id;
+
+    let updated = state
+        .repos
+        .invoices
+        .set_status(agency_id, id, InvoiceStatus::Sent, Utc::now())
+        .await?;
+    match actor {
+        Some(user) => audit_status_change(state, user, current, &updated).await,
+        None => {
+            crate::audit::record(
+                state,
+                crate::audit::AuditEvent::new(
+                    agency_id,
+                    "invoice.status_changed",
+                    "agency_invoices",
+                    id,
+                )
+                .title(format!("Invoice {} marked sent", updated.invoice_number))
+                .change(current, &updated),
+            )
+            .await
+        }
     }
 
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // Best-effort: keep the PDF the client receives. Do not fail mark-sent if
+    // rendering or storage fails.
+    let (updated, pdf) = match archive_pdf(state, &updated).await {
+        Ok((invoice, bytes)) => (invoice, Some(bytes)),
+        Err(e) => {
+            tracing::warn!("invoice_pdf_archive_failed invoice_id={} error={}", id, e);
+            (updated, None)
+        }
+    };
 
     // Best-effort: send invoice email to client using agency template (if configured)
     // Do not fail mark-sent if email sending fails.
     {
         let dest = current
-            .get("bill_to_email")
-            .and_then(|x| x.as_str())
+            .bill_to_email
+            .as_deref()
             .unwrap_or("")
             .trim()
             .to_string();
         if !dest.is_empty() {
-            let invoice_number = current
-                .get("invoice_number")
-                .and_then(|x| x.as_str())
-                .unwrap_or("")
-                .to_string();
-            let invoice_total_cents = current
-                .get("total_cents")
-                .and_then(|x| x.as_i64())
-                .unwrap_or(0);
-            let invoice_total = format!("${:.2}", (invoice_total_cents as f64) / 100.0);
-            let payment_terms = current
-                .get("payment_terms")
-                .and_then(|x| x.as_str())
-                .unwrap_or("")
-                .to_string();
-            let due_date = current
-                .get("due_date")
-                .and_then(|x| x.as_str())
-                .unwrap_or("")
-                .to_string();
-            let client_name = current
-                .get("bill_to_company")
-                .and_then(|x| x.as_str())
-                .unwrap_or("")
-                .to_string();
+            let invoice_number = current.invoice_number.clone();
+            let invoice_total = format!("${:.2}", (current.total_cents as f64) / 100.0);
+            let payment_terms = current.payment_terms.clone();
+            let due_date = current.due_date.format("%Y-%m-%d").to_string();
+            let client_name = current.bill_to_company.clone();
 
             let mut agency_email: Option<String> = None;
             let mut agency_name: Option<String> = None;
@@ -1052,7 +1245,7 @@ pub async fn mark_sent(
                 .pg
                 .from("agencies")
                 .select("email,agency_name")
-                .eq("id", &user.id)
+                .eq("id", agency_id)
                 .single()
                 .execute()
                 .await
@@ -1091,7 +1284,7 @@ pub async fn mark_sent(
             ];
 
             let (subject, body) =
-                match load_active_email_template(&state, &user.id, "invoice_email").await {
+                match load_active_email_template(state, agency_id, "invoice_email").await {
                     Ok(Some(tpl)) => (
                         render_placeholders(&tpl.subject, &vars),
                         render_placeholders(&tpl.body, &vars),
@@ -1102,103 +1295,239 @@ pub async fn mark_sent(
                     ),
                 };
 
-            if let Err(code) = email::send_plain_text_email(
-                &state,
-                &dest,
-                &subject,
-                &body,
-                agency_email.as_deref(),
-            ) {
-                tracing::warn!("invoice_email_send_failed error={:?}", code);
+            let mut message = email::OutgoingEmail::new(&dest, &subject, &body)
+                .from_name(agency_email.as_deref())
+                .category("invoice")
+                .agency(agency_id)
+                .client(Some(&current.client_id))
+                .reference("invoice", &current.id);
+            if let Some(bytes) = &pdf {
+                message = message.attach(pdf_attachment(&updated, bytes));
+            }
+            let queued = email::enqueue(state, message).await;
+            if let Err(e) = queued {
+                tracing::warn!("invoice_email_enqueue_failed error={}", e);
             }
         }
     }
-    Ok(Json(v))
+    Ok(updated)
 }
 
 pub async fn mark_paid(
     State(state): State<AppState>,
     user: AuthUser,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> AppResult<Json<Vec<Invoice>>> {
     let current = ensure_invoice_owned(&state, &user, &id).await?;
-    let status = current
-        .get("status")
-        .and_then(|v| v.as_str())
-        .unwrap_or("draft");
-    if status != "sent" {
-        return Err((
-            StatusCode::CONFLICT,
+    if !current.status.is_outstanding() {
+        return Err(AppError::Conflict(
             "Only sent invoices can be marked as paid".to_string(),
         ));
     }
 
-    let body = json!({
-        "status": "paid",
-        "paid_at": Utc::now().to_rfc3339(),
-        "updated_at": Utc::now().to_rfc3339(),
-    });
-
-    let resp = state
-        .pg
-        .from("agency_invoices")
-        .update(body.to_string())
-        .eq("id", &id)
-        .eq("agency_id", &user.id)
-        .execute()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    let status = resp.status();
-    let text = resp
-        .text()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    if !status.is_success() {
-        let code =
-            StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-        return Err(crate::errors::sanitize_db_error(code.as_u16(), text));
-    }
-
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    Ok(Json(v))
+    // Settles the remaining balance with a single payment, so the balance
+    // stays derived from payment records.
+    let payment = NewInvoicePayment {
+        agency_id: user.id.clone(),
+        invoice_id: current.id.clone(),
+        amount_cents: current.balance_due_cents() as i32,
+        paid_on: Utc::now().date_naive(),
+        method: "other".to_string(),
+        reference: None,
+        notes: Some("Marked paid".to_string()),
+        recorded_by: Some(user.id.clone()),
+    };
+    let updated = if payment.amount_cents > 0 {
+        crate::invoice_payments::record(&state, &user, &current, payment)
+            .await?
+            .invoice
+    } else {
+        crate::invoice_payments::settle(&state, &current).await?
+    };
+    audit_status_change(&state, &user, &current, &updated).await;
+    Ok(Json(vec![updated]))
 }
 
 pub async fn void_invoice(
     State(state): State<AppState>,
     user: AuthUser,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let _ = ensure_invoice_owned(&state, &user, &id).await?;
+) -> AppResult<Json<Vec<Invoice>>> {
+    let current = ensure_invoice_owned(&state, &user, &id).await?;
 
-    let body = json!({
-        "status": "void",
-        "updated_at": Utc::now().to_rfc3339(),
-    });
+    let updated = state
+        .repos
+        .invoices
+        .set_status(&user.id, &id, InvoiceStatus::Void, Utc::now())
+        .await?;
+    audit_status_change(&state, &user, &current, &updated).await;
+    // Rebilled expenses can go on another invoice.
+    crate::expenses::release_rebilled(&state, &user.id, &id).await?;
+    Ok(Json(vec![updated]))
+}
 
-    let resp = state
-        .pg
-        .from("agency_invoices")
-        .update(body.to_string())
-        .eq("id", &id)
-        .eq("agency_id", &user.id)
-        .execute()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+/// Largest agency logo fetched for invoice PDFs.
+const MAX_LOGO_BYTES: usize = 5 * 1024 * 1024;
 
-    let status = resp.status();
-    let text = resp
-        .text()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    if !status.is_success() {
-        let code =
-            StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-        return Err(crate::errors::sanitize_db_error(code.as_u16(), text));
+/// Agency name, email and logo for the PDF header. The logo is fetched
+/// best-effort; an invoice without one is still a valid invoice.
+pub(crate) async fn load_branding(
+    state: &AppState,
+    agency_id: &str,
+) -> AppResult<invoice_pdf::Branding> {
+    let rows: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from("agencies")
+            .select("email,agency_name,logo_url")
+            .eq("id", agency_id)
+            .limit(1),
+    )
+    .await?;
+    let row = rows.into_iter().next().unwrap_or_default();
+    let text = |key: &str| {
+        row.get(key)
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    };
+    let logo = match text("logo_url") {
+        Some(url) => fetch_logo(&url).await.unwrap_or_else(|e| {
+            tracing::warn!(
+                "invoice_logo_fetch_failed agency_id={} error={}",
+                agency_id,
+                e
+            );
+            None
+        }),
+        None => None,
+    };
+    Ok(invoice_pdf::Branding {
+        agency_name: text("agency_name").unwrap_or_default(),
+        agency_email: text("email"),
+        logo,
+    })
+}
+
+async fn fetch_logo(url: &str) -> Result<Option<image::DynamicImage>, String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let resp = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("status={}", resp.status()));
+    }
+    if resp
+        .content_length()
+        .is_some_and(|len| len as usize > MAX_LOGO_BYTES)
+    {
+        return Err("logo_too_large".to_string());
+    }
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+    if bytes.len() > MAX_LOGO_BYTES {
+        return Err("logo_too_large".to_string());
     }
+    image::load_from_memory(&bytes)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
 
-    let v: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    Ok(Json(v))
+/// Renders the invoice as it stands, with totals recomputed from its items and
+/// expenses.
+async fn render_pdf(state: &AppState, invoice: &Invoice) -> AppResult<Vec<u8>> {
+    let items = state.repos.invoices.items(&invoice.id).await?;
+    let expenses = state.repos.invoices.expenses(&invoice.id).await?;
+    let computed = stored_totals(invoice, &items, &expenses)?;
+    let totals = invoice_pdf::Totals {
+        subtotal_cents: computed.subtotal_cents,
+        expenses_cents: computed.expenses_cents,
+        discount_cents: invoice.discount_cents as i64,
+        tax_cents: computed.tax_cents,
+        total_cents: computed.total_cents,
+        tax_lines: computed.tax_summary,
+    };
+    let branding = load_branding(state, &invoice.agency_id).await?;
+    invoice_pdf::render(invoice, &items, &expenses, &totals, &branding)
+        .map_err(|e| AppError::internal("invoices.pdf", e))
+}
+
+/// Renders the PDF and stores it in agency storage as the sent copy.
+async fn archive_pdf(state: &AppState, invoice: &Invoice) -> AppResult<(Invoice, Vec<u8>)> {
+    let bytes = render_pdf(state, invoice).await?;
+    let path = format!(
+        "agencies/{}/invoices/{}/{}-{}.pdf",
+        sanitize_segment(&invoice.agency_id),
+        sanitize_segment(&invoice.id),
+        sanitize_segment(&invoice.invoice_number),
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+    );
+    let bucket = state.bucket(Bucket::Private);
+    state
+        .storage
+        .put(
+            &bucket,
+            &path,
+            bytes.clone().into(),
+            PDF_CONTENT_TYPE,
+            false,
+        )
+        .await?;
+    let sha256 = hex::encode(Sha256::digest(&bytes));
+    let invoice = state
+        .repos
+        .invoices
+        .set_sent_pdf(&invoice.agency_id, &invoice.id, &path, &sha256)
+        .await?;
+    Ok((invoice, bytes))
+}
+
+/// The copy the client was sent, archiving one now for invoices sent before
+/// PDFs were kept.
+async fn sent_pdf(state: &AppState, invoice: &Invoice) -> AppResult<Vec<u8>> {
+    match invoice.sent_pdf_path.as_deref() {
+        Some(path) => {
+            let bucket = state.bucket(Bucket::Private);
+            Ok(state.storage.get(&bucket, path).await?.to_vec())
+        }
+        None => archive_pdf(state, invoice).await.map(|(_, bytes)| bytes),
+    }
+}
+
+fn pdf_file_name(invoice: &Invoice) -> String {
+    format!("{}.pdf", sanitize_file_name(&invoice.invoice_number))
+}
+
+fn pdf_attachment(invoice: &Invoice, bytes: &[u8]) -> email::EmailAttachment {
+    email::EmailAttachment {
+        filename: pdf_file_name(invoice),
+        content_type: PDF_CONTENT_TYPE.to_string(),
+        content_base64: BASE64.encode(bytes),
+    }
+}
+
+/// GET /api/invoices/:id/pdf: the invoice rendered as a PDF. Sent invoices
+/// return the stored copy the client received.
+pub async fn pdf(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Response> {
+    let invoice = ensure_invoice_owned(&state, &user, &id).await?;
+    let bytes = match invoice.sent_pdf_path {
+        Some(_) => sent_pdf(&state, &invoice).await?,
+        None => render_pdf(&state, &invoice).await?,
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, PDF_CONTENT_TYPE.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"{}\"", pdf_file_name(&invoice)),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
 }
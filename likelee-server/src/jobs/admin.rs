@@ -0,0 +1,68 @@
+//! Admin endpoints for inspecting and triggering background jobs.
+
+use super::runner::{self, JobRun};
+use crate::auth::{AuthUser, RoleGuard};
+use crate::config::AppState;
+use crate::errors::AppResult;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Debug, Deserialize)]
+pub struct RunListParams {
+    pub job: Option<String>,
+    pub status: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> AppResult<Json<Vec<Value>>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let jobs = runner::list_jobs(&state).await?;
+    Ok(Json(
+        jobs.into_iter()
+            .map(|job| {
+                let running = runner::is_locked(&job);
+                let mut v = json!(job);
+                v["running"] = json!(running);
+                v
+            })
+            .collect(),
+    ))
+}
+
+pub async fn list_runs(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<RunListParams>,
+) -> AppResult<Json<Vec<JobRun>>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let runs = runner::list_runs(
+        &state,
+        params.job.as_deref(),
+        params.status.as_deref(),
+        params.limit.unwrap_or(50).min(200),
+        params.offset.unwrap_or(0),
+    )
+    .await?;
+    Ok(Json(runs))
+}
+
+/// Starts `name` immediately; responds 202 with the new run, or 409
+/// `job_running` while another worker holds the job.
+pub async fn run_job(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(name): Path<String>,
+) -> AppResult<(StatusCode, Json<JobRun>)> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let run = runner::trigger(&state, &name, Some(&user.id)).await?;
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
@@ -0,0 +1,484 @@
+use super::runner::ts;
+use super::Job;
+use crate::config::AppState;
+use crate::payout_fees::{self, PayoutMethod};
+use crate::repositories::{fetch, RepoError};
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+const REQUESTS_TABLE: &str = "agency_payout_requests";
+const RUNS_TABLE: &str = "agency_payout_schedule_runs";
+
+/// Failed attempts allowed per payout period before the scheduler gives up
+/// until the agency or an admin intervenes.
+const MAX_ATTEMPTS_PER_PERIOD: i32 = 3;
+
+/// Executes the payouts agencies have scheduled in `agency_payout_settings`.
+///
+/// Each payout period (the `last_payout_at` it starts from) gets at most one
+/// live `agency_payout_requests` row, keyed by `(agency_id, schedule_period,
+/// schedule_attempt)` under a unique index, so overlapping runs cannot both
+/// create it. A run that finds the period's request still `approved` or
+/// `processing` (a crash mid-payout) executes it again, and
+/// `execute_agency_payout` reuses any payout Stripe already made for that
+/// request instead of creating another.
+pub struct AgencyPayoutScheduler;
+
+#[async_trait]
+impl Job for AgencyPayoutScheduler {
+    fn name(&self) -> &'static str {
+        "agency_payout_scheduler"
+    }
+
+    fn schedule(&self, state: &AppState) -> String {
+        format!(
+            "@every {}s",
+            state.agency_payout_scheduler_interval_secs.max(1)
+        )
+    }
+
+    fn enabled(&self, state: &AppState) -> bool {
+        state.agency_payout_scheduler_enabled
+    }
+
+    async fn run(&self, state: &AppState) -> Result<Value, String> {
+        run_agency_payout_scheduler(state).await
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PayoutSettings {
+    agency_id: String,
+    payout_frequency: Option<String>,
+    min_payout_threshold_cents: Option<i64>,
+    last_payout_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduledRequest {
+    id: String,
+    amount_cents: i64,
+    currency: String,
+    payout_method: String,
+    status: String,
+    schedule_attempt: i32,
+    processed_at: Option<DateTime<Utc>>,
+    failure_reason: Option<String>,
+    #[serde(default)]
+    fee_cents: Option<i64>,
+}
+
+/// What happened to one due agency, as stored in `agency_payout_schedule_runs`.
+#[derive(Debug)]
+enum Outcome {
+    Executed {
+        request_id: String,
+        amount_cents: i64,
+    },
+    PendingApproval {
+        request_id: String,
+        amount_cents: i64,
+    },
+    Skipped {
+        reason: String,
+        amount_cents: i64,
+    },
+    Failed {
+        request_id: Option<String>,
+        reason: String,
+        amount_cents: i64,
+    },
+}
+
+impl Outcome {
+    fn skipped(reason: &str, amount_cents: i64) -> Self {
+        Outcome::Skipped {
+            reason: reason.to_string(),
+            amount_cents,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Executed { .. } => "executed",
+            Outcome::PendingApproval { .. } => "pending_approval",
+            Outcome::Skipped { .. } => "skipped",
+            Outcome::Failed { .. } => "failed",
+        }
+    }
+}
+
+async fn run_agency_payout_scheduler(state: &AppState) -> Result<Value, String> {
+    if !state.payouts_enabled {
+        return Ok(json!({ "skipped": "payouts_disabled" }));
+    }
+
+    let settings: Vec<PayoutSettings> = fetch(
+        state
+            .pg
+            .from("agency_payout_settings")
+            .select("agency_id,payout_frequency,min_payout_threshold_cents,last_payout_at")
+            .limit(2000),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let now = Utc::now();
+    let mut counts = json!({ "executed": 0, "pending_approval": 0, "skipped": 0, "failed": 0 });
+    for s in &settings {
+        let frequency = s.payout_frequency.as_deref().unwrap_or("Monthly");
+        if !is_due(frequency, s.last_payout_at, now) {
+            continue;
+        }
+        let period = s.last_payout_at.map(ts).unwrap_or_else(|| "initial".into());
+        let outcome = match pay_agency(state, s, &period).await {
+            Ok(outcome) => outcome,
+            Err(e) => Outcome::Failed {
+                request_id: None,
+                reason: e.to_string(),
+                amount_cents: 0,
+            },
+        };
+        info!(
+            agency_id = %s.agency_id,
+            period = %period,
+            outcome = ?outcome,
+            "agency_payout_scheduler_outcome"
+        );
+        if let Err(e) = record_run(state, &s.agency_id, &period, &outcome).await {
+            warn!(agency_id = %s.agency_id, error = %e, "agency_payout_run_record_failed");
+        }
+        counts[outcome.label()] = json!(counts[outcome.label()].as_i64().unwrap_or(0) + 1);
+    }
+
+    counts["agencies_checked"] = json!(settings.len());
+    Ok(counts)
+}
+
+/// Never-paid agencies are due straight away; otherwise one period after
+/// the last payout.
+fn is_due(
+    payout_frequency: &str,
+    last_payout_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> bool {
+    let days = match payout_frequency {
+        "Weekly" => 7,
+        "Bi-Weekly" => 14,
+        "Monthly" => 30,
+        _ => 30,
+    };
+    last_payout_at.is_none_or(|last| now >= last + Duration::days(days))
+}
+
+async fn pay_agency(
+    state: &AppState,
+    settings: &PayoutSettings,
+    period: &str,
+) -> Result<Outcome, RepoError> {
+    let agency_id = settings.agency_id.as_str();
+    let previous: Vec<ScheduledRequest> = fetch(
+        state
+            .pg
+            .from(REQUESTS_TABLE)
+            .select("id,amount_cents,currency,payout_method,status,schedule_attempt,processed_at,failure_reason,fee_cents")
+            .eq("agency_id", agency_id)
+            .eq("schedule_period", period)
+            .order("schedule_attempt.desc")
+            .limit(1),
+    )
+    .await?;
+
+    let attempt = match previous.into_iter().next() {
+        None => 1,
+        Some(req) => match req.status.as_str() {
+            // Paid, but the run stopped before moving the schedule on.
+            "paid" => {
+                mark_paid_out(state, agency_id, req.processed_at.unwrap_or_else(Utc::now)).await?;
+                return Ok(Outcome::Executed {
+                    request_id: req.id,
+                    amount_cents: req.amount_cents,
+                });
+            }
+            "approved" | "processing" => {
+                let stripe_account_id = match connected_account(state, agency_id).await? {
+                    Some(acct) => acct,
+                    None => {
+                        return Ok(Outcome::skipped(
+                            "stripe_account_not_connected",
+                            req.amount_cents,
+                        ))
+                    }
+                };
+                return execute(state, agency_id, &stripe_account_id, req).await;
+            }
+            "pending" => return Ok(Outcome::skipped("awaiting_approval", req.amount_cents)),
+            _ if req.schedule_attempt >= MAX_ATTEMPTS_PER_PERIOD => {
+                return Ok(Outcome::skipped("max_attempts_reached", req.amount_cents));
+            }
+            _ => req.schedule_attempt + 1,
+        },
+    };
+
+    let earned = earned_since(state, agency_id, settings.last_payout_at).await?;
+    let available = available_balance(state, agency_id).await?;
+    let amount_cents = earned.min(available);
+    let threshold = settings
+        .min_payout_threshold_cents
+        .unwrap_or(5000)
+        .max(state.min_payout_amount_cents as i64);
+    if amount_cents <= 0 || amount_cents < threshold {
+        return Ok(Outcome::skipped("below_threshold", amount_cents));
+    }
+    if !state.instant_payouts_enabled {
+        return Ok(Outcome::skipped("instant_payouts_disabled", amount_cents));
+    }
+    let Some(stripe_account_id) = connected_account(state, agency_id).await? else {
+        return Ok(Outcome::skipped(
+            "stripe_account_not_connected",
+            amount_cents,
+        ));
+    };
+
+    // Same auto-approval rule as agency-initiated requests.
+    let status = if amount_cents <= state.payout_auto_approve_threshold_cents as i64 {
+        "approved"
+    } else {
+        "pending"
+    };
+    let currency = state.payout_currency.to_uppercase();
+    let quote = payout_fees::quote(
+        state,
+        agency_id,
+        amount_cents,
+        &currency,
+        PayoutMethod::Instant,
+    )
+    .await?;
+    if quote.net_cents <= 0 {
+        return Ok(Outcome::skipped("fee_exceeds_amount", amount_cents));
+    }
+    let mut row = json!({
+        "agency_id": agency_id,
+        "amount_cents": amount_cents,
+        "currency": currency,
+        "payout_method": PayoutMethod::Instant.as_str(),
+        "status": status,
+        "requested_at": ts(Utc::now()),
+        "source": "scheduled",
+        "schedule_period": period,
+        "schedule_attempt": attempt,
+    });
+    if let (Some(row), Value::Object(fee)) = (row.as_object_mut(), quote.columns()) {
+        row.extend(fee);
+    }
+    let inserted: Result<Vec<ScheduledRequest>, RepoError> =
+        fetch(state.pg.from(REQUESTS_TABLE).insert(row.to_string())).await;
+    let req = match inserted {
+        Ok(rows) => rows.into_iter().next().ok_or(RepoError::NotFound)?,
+        // Another runner created this period's request first.
+        Err(RepoError::Db { status: 409, .. }) => {
+            return Ok(Outcome::skipped("already_claimed", amount_cents));
+        }
+        Err(e) => return Err(e),
+    };
+    if status == "pending" {
+        return Ok(Outcome::PendingApproval {
+            request_id: req.id,
+            amount_cents,
+        });
+    }
+    execute(state, agency_id, &stripe_account_id, req).await
+}
+
+async fn execute(
+    state: &AppState,
+    agency_id: &str,
+    stripe_account_id: &str,
+    req: ScheduledRequest,
+) -> Result<Outcome, RepoError> {
+    let fee_cents = crate::payouts::agency_payout_fee_cents(state, req.fee_cents, req.amount_cents);
+    let result = crate::payouts::execute_agency_payout(
+        state,
+        &req.id,
+        agency_id,
+        stripe_account_id,
+        req.amount_cents,
+        fee_cents,
+        &req.currency,
+        &req.payout_method,
+    )
+    .await;
+    if result.is_ok() {
+        mark_paid_out(state, agency_id, Utc::now()).await?;
+        return Ok(Outcome::Executed {
+            request_id: req.id,
+            amount_cents: req.amount_cents,
+        });
+    }
+
+    let reason = fetch::<Vec<ScheduledRequest>>(
+        state
+            .pg
+            .from(REQUESTS_TABLE)
+            .select("id,amount_cents,currency,payout_method,status,schedule_attempt,processed_at,failure_reason,fee_cents")
+            .eq("id", &req.id)
+            .limit(1),
+    )
+    .await
+    .ok()
+    .and_then(|rows| rows.into_iter().next())
+    .and_then(|r| r.failure_reason)
+    .unwrap_or_else(|| "payout_failed".to_string());
+    Ok(Outcome::Failed {
+        request_id: Some(req.id),
+        reason,
+        amount_cents: req.amount_cents,
+    })
+}
+
+async fn mark_paid_out(
+    state: &AppState,
+    agency_id: &str,
+    at: DateTime<Utc>,
+) -> Result<(), RepoError> {
+    let _: Vec<Value> = fetch(
+        state
+            .pg
+            .from("agency_payout_settings")
+            .eq("agency_id", agency_id)
+            .update(json!({ "last_payout_at": ts(at) }).to_string()),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn record_run(
+    state: &AppState,
+    agency_id: &str,
+    period: &str,
+    outcome: &Outcome,
+) -> Result<(), RepoError> {
+    let (request_id, reason, amount_cents) = match outcome {
+        Outcome::Executed {
+            request_id,
+            amount_cents,
+        }
+        | Outcome::PendingApproval {
+            request_id,
+            amount_cents,
+        } => (Some(request_id.as_str()), None, *amount_cents),
+        Outcome::Skipped {
+            reason,
+            amount_cents,
+        } => (None, Some(reason.as_str()), *amount_cents),
+        Outcome::Failed {
+            request_id,
+            reason,
+            amount_cents,
+        } => (request_id.as_deref(), Some(reason.as_str()), *amount_cents),
+    };
+    let row = json!({
+        "agency_id": agency_id,
+        "schedule_period": period,
+        "outcome": outcome.label(),
+        "reason": reason,
+        "amount_cents": amount_cents,
+        "payout_request_id": request_id,
+    });
+    let _: Vec<Value> = fetch(state.pg.from(RUNS_TABLE).insert(row.to_string())).await?;
+    Ok(())
+}
+
+async fn connected_account(state: &AppState, agency_id: &str) -> Result<Option<String>, RepoError> {
+    #[derive(Deserialize)]
+    struct Row {
+        stripe_connect_account_id: Option<String>,
+    }
+    let rows: Vec<Row> = fetch(
+        state
+            .pg
+            .from("agencies")
+            .select("stripe_connect_account_id")
+            .eq("id", agency_id)
+            .limit(1),
+    )
+    .await?;
+    Ok(rows
+        .into_iter()
+        .next()
+        .and_then(|r| r.stripe_connect_account_id)
+        .filter(|s| !s.trim().is_empty()))
+}
+
+/// The agency balance less what is held in payout reserves.
+async fn available_balance(state: &AppState, agency_id: &str) -> Result<i64, RepoError> {
+    #[derive(Deserialize)]
+    struct Row {
+        available_cents: i64,
+        currency: Option<String>,
+    }
+    let rows: Vec<Row> = fetch(
+        state
+            .pg
+            .from("agency_balances")
+            .select("available_cents,currency")
+            .eq("agency_id", agency_id)
+            .limit(1),
+    )
+    .await?;
+    let Some(row) = rows.into_iter().next() else {
+        return Ok(0);
+    };
+    let currency = row.currency.as_deref().unwrap_or(&state.payout_currency);
+    let reserved =
+        crate::payout_reserves::reserved_cents(state, "agency", agency_id, currency).await?;
+    Ok((row.available_cents - reserved).max(0))
+}
+
+/// Licensing payouts credited to the agency since its last payout (or the
+/// last 30 days before the first one).
+async fn earned_since(
+    state: &AppState,
+    agency_id: &str,
+    last_payout_at: Option<DateTime<Utc>>,
+) -> Result<i64, RepoError> {
+    #[derive(Deserialize)]
+    struct Row {
+        amount_cents: Option<i64>,
+    }
+    let since = last_payout_at.unwrap_or_else(|| Utc::now() - Duration::days(30));
+    let rows: Vec<Row> = fetch(
+        state
+            .pg
+            .from("licensing_payouts")
+            .select("amount_cents")
+            .eq("agency_id", agency_id)
+            .gte("paid_at", ts(since))
+            .limit(2000),
+    )
+    .await?;
+    Ok(rows
+        .iter()
+        .filter_map(|r| r.amount_cents)
+        .sum::<i64>()
+        .max(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_payout_is_due_immediately_then_once_per_period() {
+        let now = Utc::now();
+        assert!(is_due("Weekly", None, now));
+        assert!(!is_due("Weekly", Some(now - Duration::days(6)), now));
+        assert!(is_due("Weekly", Some(now - Duration::days(7)), now));
+        assert!(!is_due("Bi-Weekly", Some(now - Duration::days(10)), now));
+        assert!(is_due("unknown", Some(now - Duration::days(30)), now));
+    }
+}
@@ -0,0 +1,29 @@
+use super::Job;
+use crate::config::AppState;
+use axum::async_trait;
+use serde_json::Value;
+
+/// Retries outbox messages whose first delivery attempt failed.
+pub struct EmailOutbox;
+
+#[async_trait]
+impl Job for EmailOutbox {
+    fn name(&self) -> &'static str {
+        "email_outbox"
+    }
+
+    fn schedule(&self, _state: &AppState) -> String {
+        "@every 30s".to_string()
+    }
+
+    // Per-message retries are tracked on the outbox rows themselves.
+    fn max_attempts(&self) -> i32 {
+        1
+    }
+
+    async fn run(&self, state: &AppState) -> Result<Value, String> {
+        crate::email::outbox::deliver_due(state, 100)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
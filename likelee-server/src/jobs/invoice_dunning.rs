@@ -0,0 +1,26 @@
+use super::Job;
+use crate::config::AppState;
+use axum::async_trait;
+use serde_json::Value;
+
+/// Marks past-due invoices overdue and sends the payment reminders each
+/// agency has enabled.
+pub struct InvoiceDunning;
+
+#[async_trait]
+impl Job for InvoiceDunning {
+    fn name(&self) -> &'static str {
+        "invoice_dunning"
+    }
+
+    fn schedule(&self, _state: &AppState) -> String {
+        "0 9 * * *".to_string()
+    }
+
+    async fn run(&self, state: &AppState) -> Result<Value, String> {
+        let summary = crate::invoice_dunning::run_today(state)
+            .await
+            .map_err(|e| e.to_string())?;
+        serde_json::to_value(summary).map_err(|e| e.to_string())
+    }
+}
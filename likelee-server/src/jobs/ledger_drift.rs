@@ -0,0 +1,26 @@
+use super::Job;
+use crate::config::AppState;
+use axum::async_trait;
+use serde_json::Value;
+
+/// Compares ledger-derived connected account balances with Stripe. Drifts
+/// are logged and kept in the run output.
+pub struct LedgerDriftCheck;
+
+#[async_trait]
+impl Job for LedgerDriftCheck {
+    fn name(&self) -> &'static str {
+        "ledger_drift_check"
+    }
+
+    fn schedule(&self, _state: &AppState) -> String {
+        "@every 21600s".to_string()
+    }
+
+    async fn run(&self, state: &AppState) -> Result<Value, String> {
+        let report = crate::ledger::drift::check(state)
+            .await
+            .map_err(|e| e.to_string())?;
+        serde_json::to_value(report).map_err(|e| e.to_string())
+    }
+}
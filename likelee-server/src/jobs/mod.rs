@@ -0,0 +1,103 @@
+//! Background jobs.
+//!
+//! Every job implements [`Job`] and is listed in [`registry`]. Schedules and
+//! run state live in `background_jobs`: each replica polls that table, claims a
+//! due job by taking a time-limited lease, and records every attempt in
+//! `background_job_runs`. A job therefore runs on one replica at a time,
+//! failed runs are retried with exponential backoff, and a replica that dies
+//! mid-run only holds the job until its lease expires.
+
+pub mod admin;
+mod agency_payouts;
+mod email_outbox;
+mod invoice_dunning;
+mod ledger_drift;
+mod payment_reminders;
+mod payout_reserves;
+mod rate_limit_prune;
+mod reconciliation;
+mod recurring_invoices;
+pub mod runner;
+pub mod schedule;
+mod talent_statements;
+
+use crate::config::AppState;
+use axum::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use tracing::{info, warn};
+
+pub use runner::{tick, JobRecord, JobRun};
+pub use schedule::Schedule;
+
+#[async_trait]
+pub trait Job: Send + Sync {
+    /// Stable identifier; the primary key in `background_jobs`.
+    fn name(&self) -> &'static str;
+
+    /// Cron expression or `@every` interval, see [`Schedule::parse`].
+    fn schedule(&self, state: &AppState) -> String;
+
+    /// Configuration gate; disabled jobs are never claimed by the poller but
+    /// can still be triggered manually.
+    fn enabled(&self, _state: &AppState) -> bool {
+        true
+    }
+
+    /// Attempts per scheduled occurrence before giving up until the next one.
+    fn max_attempts(&self) -> i32 {
+        3
+    }
+
+    /// How long a claim is held before another replica may take over.
+    /// Renewed while the job is running.
+    fn lease_secs(&self) -> i64 {
+        300
+    }
+
+    /// Runs one occurrence. The returned value is stored as the run's output.
+    async fn run(&self, state: &AppState) -> Result<Value, String>;
+}
+
+pub fn registry() -> Vec<Arc<dyn Job>> {
+    vec![
+        Arc::new(payment_reminders::PaymentReminders),
+        Arc::new(agency_payouts::AgencyPayoutScheduler),
+        Arc::new(email_outbox::EmailOutbox),
+        Arc::new(rate_limit_prune::RateLimitPrune),
+        Arc::new(ledger_drift::LedgerDriftCheck),
+        Arc::new(reconciliation::StripeReconciliation),
+        Arc::new(invoice_dunning::InvoiceDunning),
+        Arc::new(recurring_invoices::RecurringInvoices),
+        Arc::new(talent_statements::TalentStatements),
+        Arc::new(payout_reserves::PayoutReserves),
+    ]
+}
+
+pub fn find(name: &str) -> Option<Arc<dyn Job>> {
+    registry().into_iter().find(|j| j.name() == name)
+}
+
+/// Registers the jobs and polls for due ones until the process exits.
+/// This should be called once at server startup.
+pub async fn start(state: AppState) {
+    if !state.jobs_enabled {
+        info!("background jobs disabled (JOBS_ENABLED=false)");
+        return;
+    }
+    info!(
+        worker_id = runner::worker_id(),
+        poll_interval_secs = state.jobs_poll_interval_secs,
+        "Starting background job runner"
+    );
+    if let Err(e) = runner::sync_jobs(&state).await {
+        warn!(error = %e, "failed to register background jobs");
+    }
+    loop {
+        if let Err(e) = tick(&state).await {
+            warn!(error = %e, "background job poll failed");
+        }
+        tokio::time::sleep(StdDuration::from_secs(state.jobs_poll_interval_secs.max(1))).await;
+    }
+}
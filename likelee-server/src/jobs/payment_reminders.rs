@@ -0,0 +1,96 @@
+use super::Job;
+use crate::config::AppState;
+use axum::async_trait;
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+/// Emails brands whose licensing payments fall due in five days.
+pub struct PaymentReminders;
+
+#[async_trait]
+impl Job for PaymentReminders {
+    fn name(&self) -> &'static str {
+        "payment_reminders"
+    }
+
+    fn schedule(&self, _state: &AppState) -> String {
+        "0 9 * * *".to_string()
+    }
+
+    async fn run(&self, state: &AppState) -> Result<Value, String> {
+        run_reminders(state).await
+    }
+}
+
+async fn run_reminders(state: &AppState) -> Result<Value, String> {
+    // We send reminders 5 days before the due date
+    let target_date = (Utc::now() + Duration::days(5))
+        .format("%Y-%m-%d")
+        .to_string();
+    info!(target_date = %target_date, "Running payment reminders check");
+
+    // Query payments due in 5 days that are still pending
+    // We join with brands to get the recipient email
+    let resp = state
+        .pg
+        .from("payments")
+        .select("id,gross_cents,due_date,brand_id,brands(email,company_name)")
+        .eq("status", "pending")
+        .eq("due_date", &target_date)
+        .execute()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !resp.status().is_success() {
+        let err = resp.text().await.unwrap_or_default();
+        return Err(format!("Supabase query failed: {err}"));
+    }
+
+    let text = resp.text().await.unwrap_or_default();
+    let rows: Vec<Value> = serde_json::from_str(&text).unwrap_or_default();
+
+    info!(count = rows.len(), "Found payments needing reminders");
+
+    let mut queued = 0;
+    let mut failed = 0;
+    for r in &rows {
+        if let Some(brand) = r.get("brands") {
+            if let Some(email) = brand.get("email").and_then(|v| v.as_str()) {
+                let company_name = brand
+                    .get("company_name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("Brand");
+                let amount =
+                    (r.get("gross_cents").and_then(|v| v.as_i64()).unwrap_or(0) as f64) / 100.0;
+                let subject = "Upcoming Licensing Payment Reminder";
+                let body = format!(
+                    "Hello {company_name},\n\nThis is a friendly reminder from Likelee. Your licensing payment of ${amount:.2} is due in 5 days ({target_date}).\n\nPlease contact the agency or reply to this email to complete the payment.\n\nBest regards,\nLikelee Team"
+                );
+
+                let mut outgoing = crate::email::OutgoingEmail::new(email, subject, &body)
+                    .category("payment_reminder");
+                if let Some(id) = r.get("id").and_then(|v| v.as_str()) {
+                    outgoing = outgoing.reference("payment", id);
+                }
+                match crate::email::enqueue(state, outgoing).await {
+                    Ok(_) => {
+                        queued += 1;
+                        info!(email = %email, "Reminder email queued")
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        warn!(email = %email, error = %e, "Failed to queue reminder email")
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(json!({
+        "target_date": target_date,
+        "payments": rows.len(),
+        "queued": queued,
+        "failed": failed,
+    }))
+}
@@ -0,0 +1,23 @@
+use super::Job;
+use crate::config::AppState;
+use axum::async_trait;
+use serde_json::Value;
+
+/// Transfers payout reserves whose hold has ended.
+pub struct PayoutReserves;
+
+#[async_trait]
+impl Job for PayoutReserves {
+    fn name(&self) -> &'static str {
+        "payout_reserves"
+    }
+
+    fn schedule(&self, _state: &AppState) -> String {
+        "@every 3600s".to_string()
+    }
+
+    async fn run(&self, state: &AppState) -> Result<Value, String> {
+        let summary = crate::payout_reserves::release_due(state).await?;
+        serde_json::to_value(summary).map_err(|e| e.to_string())
+    }
+}
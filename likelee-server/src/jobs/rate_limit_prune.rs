@@ -0,0 +1,23 @@
+use super::Job;
+use crate::config::AppState;
+use axum::async_trait;
+use serde_json::{json, Value};
+
+/// Drops expired rate limit counters and lockouts.
+pub struct RateLimitPrune;
+
+#[async_trait]
+impl Job for RateLimitPrune {
+    fn name(&self) -> &'static str {
+        "rate_limit_prune"
+    }
+
+    fn schedule(&self, _state: &AppState) -> String {
+        "@every 3600s".to_string()
+    }
+
+    async fn run(&self, state: &AppState) -> Result<Value, String> {
+        let removed = state.rate_limiter.prune().await?;
+        Ok(json!({ "removed": removed }))
+    }
+}
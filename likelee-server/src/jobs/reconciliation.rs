@@ -0,0 +1,28 @@
+use super::Job;
+use crate::config::AppState;
+use axum::async_trait;
+use serde_json::Value;
+
+/// Matches the last week of Stripe transfers, payouts and charges with the
+/// local records and stores a discrepancy report.
+pub struct StripeReconciliation;
+
+#[async_trait]
+impl Job for StripeReconciliation {
+    fn name(&self) -> &'static str {
+        "stripe_reconciliation"
+    }
+
+    fn schedule(&self, _state: &AppState) -> String {
+        "@every 86400s".to_string()
+    }
+
+    fn lease_secs(&self) -> i64 {
+        900
+    }
+
+    async fn run(&self, state: &AppState) -> Result<Value, String> {
+        let summary = crate::reconciliation::run_recent(state).await?;
+        serde_json::to_value(summary).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,26 @@
+use super::Job;
+use crate::config::AppState;
+use axum::async_trait;
+use serde_json::Value;
+
+/// Generates the invoices of recurring schedules that are due, sending those
+/// set to auto-send. Runs ahead of the dunning pass.
+pub struct RecurringInvoices;
+
+#[async_trait]
+impl Job for RecurringInvoices {
+    fn name(&self) -> &'static str {
+        "recurring_invoices"
+    }
+
+    fn schedule(&self, _state: &AppState) -> String {
+        "0 6 * * *".to_string()
+    }
+
+    async fn run(&self, state: &AppState) -> Result<Value, String> {
+        let summary = crate::recurring_invoices::run_today(state)
+            .await
+            .map_err(|e| e.to_string())?;
+        serde_json::to_value(summary).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,471 @@
+//! Claiming, running and recording jobs.
+//!
+//! A claim is a single conditional PATCH on `background_jobs` that only
+//! matches while the lease is free (`locked_until` null or in the past), so
+//! concurrent replicas cannot both win it. The lease is renewed while the job
+//! runs and released when its run row is finalised.
+
+use super::{find, registry, Job, Schedule};
+use crate::config::AppState;
+use crate::errors::AppError;
+use crate::repositories::{fetch, RepoError};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::{Arc, OnceLock};
+use tracing::{info, warn};
+
+pub const JOBS_TABLE: &str = "background_jobs";
+pub const RUNS_TABLE: &str = "background_job_runs";
+
+/// Longest delay between retries of a failing run.
+const MAX_BACKOFF_SECS: i64 = 3600;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub name: String,
+    pub schedule: String,
+    pub enabled: bool,
+    pub next_run_at: Option<String>,
+    pub locked_by: Option<String>,
+    pub locked_until: Option<String>,
+    /// Failed attempts of the current occurrence.
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub last_run_at: Option<String>,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRun {
+    pub id: String,
+    pub job_name: String,
+    /// `schedule` or `manual`.
+    pub trigger: String,
+    /// `running`, `succeeded` or `failed`.
+    pub status: String,
+    pub attempt: i32,
+    pub worker_id: Option<String>,
+    pub triggered_by: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub duration_ms: Option<i64>,
+    pub output: Option<Value>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Schedule,
+    Manual,
+}
+
+impl Trigger {
+    fn as_str(self) -> &'static str {
+        match self {
+            Trigger::Schedule => "schedule",
+            Trigger::Manual => "manual",
+        }
+    }
+}
+
+/// Identifies this process in `locked_by` and run rows.
+pub fn worker_id() -> &'static str {
+    static ID: OnceLock<String> = OnceLock::new();
+    ID.get_or_init(|| {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "worker".to_string());
+        let suffix = uuid::Uuid::new_v4().simple().to_string();
+        format!("{host}-{}", &suffix[..8])
+    })
+}
+
+/// Timestamps are written in one fixed-width UTC format so they also compare
+/// correctly as strings.
+pub(crate) fn ts(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Parses a stored timestamp in any RFC 3339 form, including PostgREST's
+/// `+00:00` with microseconds.
+pub(crate) fn parse_ts(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Delay before retry number `attempt` (1-based): 1m, 2m, 4m, ... capped at 1h.
+pub fn backoff(attempt: i32) -> Duration {
+    let exp = attempt.saturating_sub(1).clamp(0, 16) as u32;
+    Duration::seconds((60i64 << exp).min(MAX_BACKOFF_SECS))
+}
+
+fn schedule_for(job: &dyn Job, state: &AppState, record: &JobRecord) -> Option<Schedule> {
+    Schedule::parse(&record.schedule)
+        .or_else(|_| Schedule::parse(&job.schedule(state)))
+        .map_err(|e| warn!(job = job.name(), error = %e, "invalid job schedule"))
+        .ok()
+}
+
+/// Creates rows for newly registered jobs and keeps each row's schedule and
+/// attempt limit in line with the code. `enabled` is left to operators.
+pub async fn sync_jobs(state: &AppState) -> Result<(), RepoError> {
+    let existing: Vec<JobRecord> = fetch(state.pg.from(JOBS_TABLE).select("*")).await?;
+    let now = Utc::now();
+    for job in registry() {
+        let schedule = job.schedule(state);
+        let parsed = match Schedule::parse(&schedule) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!(job = job.name(), error = %e, "skipping job with invalid schedule");
+                continue;
+            }
+        };
+        let next_run_at = ts(parsed.next_after(now));
+        match existing.iter().find(|r| r.name == job.name()) {
+            None => {
+                let row = json!({
+                    "name": job.name(),
+                    "schedule": schedule,
+                    "enabled": true,
+                    "next_run_at": next_run_at,
+                    "attempts": 0,
+                    "max_attempts": job.max_attempts(),
+                });
+                // Another replica may register the same job concurrently.
+                let _: Vec<JobRecord> = fetch(
+                    state
+                        .pg
+                        .from(JOBS_TABLE)
+                        .on_conflict("name")
+                        .upsert(row.to_string()),
+                )
+                .await?;
+                info!(job = job.name(), schedule = %schedule, "registered background job");
+            }
+            Some(r) if r.schedule != schedule || r.max_attempts != job.max_attempts() => {
+                let mut patch = json!({ "max_attempts": job.max_attempts() });
+                if r.schedule != schedule {
+                    patch["schedule"] = json!(schedule);
+                    patch["next_run_at"] = json!(next_run_at);
+                }
+                let _: Vec<JobRecord> = fetch(
+                    state
+                        .pg
+                        .from(JOBS_TABLE)
+                        .eq("name", job.name())
+                        .update(patch.to_string()),
+                )
+                .await?;
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Claims and runs every due job once; returns the runs that were recorded.
+pub async fn tick(state: &AppState) -> Result<Vec<JobRun>, RepoError> {
+    let due: Vec<JobRecord> = fetch(
+        state
+            .pg
+            .from(JOBS_TABLE)
+            .select("*")
+            .eq("enabled", "true")
+            .lte("next_run_at", ts(Utc::now())),
+    )
+    .await?;
+
+    let mut handles = vec![];
+    for record in due {
+        let Some(job) = find(&record.name) else {
+            continue;
+        };
+        if !job.enabled(state) {
+            continue;
+        }
+        let Some(record) = claim(state, job.as_ref(), Trigger::Schedule).await? else {
+            continue;
+        };
+        let state = state.clone();
+        handles.push(tokio::spawn(async move {
+            match begin(&state, job.as_ref(), &record, Trigger::Schedule, None).await {
+                Ok(run) => Ok(finish(&state, job, record, run).await),
+                Err(e) => {
+                    release(&state, job.as_ref(), json!({})).await;
+                    Err(e)
+                }
+            }
+        }));
+    }
+
+    let mut runs = vec![];
+    for handle in handles {
+        match handle.await {
+            Ok(Ok(run)) => runs.push(run),
+            Ok(Err(e)) => warn!(error = %e, "failed to record background job run"),
+            Err(e) => warn!(error = %e, "background job task aborted"),
+        }
+    }
+    Ok(runs)
+}
+
+/// Runs `name` now regardless of its schedule. Returns the `running` run row;
+/// the job itself continues in the background.
+pub async fn trigger(
+    state: &AppState,
+    name: &str,
+    triggered_by: Option<&str>,
+) -> Result<JobRun, AppError> {
+    let job = find(name).ok_or_else(|| AppError::NotFound("job_not_found".to_string()))?;
+    let mut record = claim(state, job.as_ref(), Trigger::Manual).await?;
+    if record.is_none() {
+        // Either the lease is held or the job has not been registered yet.
+        let known: Vec<Value> = fetch(state.pg.from(JOBS_TABLE).select("name").eq("name", name))
+            .await
+            .unwrap_or_default();
+        if !known.is_empty() {
+            return Err(AppError::Conflict("job_running".to_string()));
+        }
+        sync_jobs(state).await?;
+        record = claim(state, job.as_ref(), Trigger::Manual).await?;
+    }
+    let record = record.ok_or_else(|| AppError::Conflict("job_running".to_string()))?;
+
+    let run = match begin(state, job.as_ref(), &record, Trigger::Manual, triggered_by).await {
+        Ok(run) => run,
+        Err(e) => {
+            release(state, job.as_ref(), json!({})).await;
+            return Err(e.into());
+        }
+    };
+    let started = run.clone();
+    let state = state.clone();
+    tokio::spawn(async move {
+        finish(&state, job, record, run).await;
+    });
+    Ok(started)
+}
+
+/// Takes the lease on `job`. Scheduled claims additionally require the job to
+/// be enabled and due. Returns `None` when another worker holds it.
+async fn claim(
+    state: &AppState,
+    job: &dyn Job,
+    trigger: Trigger,
+) -> Result<Option<JobRecord>, RepoError> {
+    let now = Utc::now();
+    let mut query = state
+        .pg
+        .from(JOBS_TABLE)
+        .eq("name", job.name())
+        .or(format!("locked_until.is.null,locked_until.lt.{}", ts(now)));
+    if trigger == Trigger::Schedule {
+        query = query.eq("enabled", "true").lte("next_run_at", ts(now));
+    }
+    let patch = json!({
+        "locked_by": worker_id(),
+        "locked_until": ts(now + Duration::seconds(job.lease_secs())),
+    });
+    let rows: Vec<JobRecord> = fetch(query.update(patch.to_string())).await?;
+    Ok(rows.into_iter().next())
+}
+
+/// Records the start of a run.
+async fn begin(
+    state: &AppState,
+    job: &dyn Job,
+    record: &JobRecord,
+    trigger: Trigger,
+    triggered_by: Option<&str>,
+) -> Result<JobRun, RepoError> {
+    let attempt = match trigger {
+        Trigger::Schedule => record.attempts + 1,
+        Trigger::Manual => 1,
+    };
+    let row = json!({
+        "job_name": job.name(),
+        "trigger": trigger.as_str(),
+        "status": "running",
+        "attempt": attempt,
+        "worker_id": worker_id(),
+        "triggered_by": triggered_by,
+        "started_at": ts(Utc::now()),
+    });
+    let runs: Vec<JobRun> = fetch(state.pg.from(RUNS_TABLE).insert(row.to_string())).await?;
+    runs.into_iter()
+        .next()
+        .ok_or_else(|| RepoError::Decode("insert returned no run".to_string()))
+}
+
+/// Runs the job while renewing its lease, then reschedules the job and
+/// finalises the run row.
+async fn finish(state: &AppState, job: Arc<dyn Job>, record: JobRecord, run: JobRun) -> JobRun {
+    let trigger = if run.trigger == "manual" {
+        Trigger::Manual
+    } else {
+        Trigger::Schedule
+    };
+    let started = Utc::now();
+    let heartbeat = tokio::spawn(heartbeat(state.clone(), job.clone()));
+    // Run on its own task so a panicking job is recorded as a failure.
+    let result = {
+        let (state, job) = (state.clone(), job.clone());
+        tokio::spawn(async move { job.run(&state).await })
+            .await
+            .unwrap_or_else(|e| Err(format!("job panicked: {e}")))
+    };
+    heartbeat.abort();
+
+    let now = Utc::now();
+    let duration_ms = (now - started).num_milliseconds();
+    let (status, output, error) = match &result {
+        Ok(output) => ("succeeded", Some(output.clone()), None),
+        Err(e) => ("failed", None, Some(e.clone())),
+    };
+    match &error {
+        None => info!(job = job.name(), run_id = %run.id, duration_ms, "background job succeeded"),
+        Some(e) => {
+            warn!(job = job.name(), run_id = %run.id, attempt = run.attempt, error = %e, "background job failed")
+        }
+    }
+
+    let mut patch = json!({
+        "last_run_at": ts(now),
+        "last_status": status,
+        "last_error": error,
+    });
+    // Manual runs leave the schedule and retry state alone.
+    if trigger == Trigger::Schedule {
+        let next_occurrence = schedule_for(job.as_ref(), state, &record)
+            .map(|s| s.next_after(now))
+            .unwrap_or_else(|| now + Duration::hours(1));
+        let retry = error.is_some() && run.attempt < record.max_attempts.max(1);
+        patch["attempts"] = json!(if retry { run.attempt } else { 0 });
+        patch["next_run_at"] = json!(ts(if retry {
+            now + backoff(run.attempt)
+        } else {
+            next_occurrence
+        }));
+    }
+    // Release before finalising so the lease is clear once the run reads as done.
+    release(state, job.as_ref(), patch).await;
+
+    let run_patch = json!({
+        "status": status,
+        "finished_at": ts(now),
+        "duration_ms": duration_ms,
+        "output": output,
+        "error": error,
+    });
+    let finished = fetch::<Vec<JobRun>>(
+        state
+            .pg
+            .from(RUNS_TABLE)
+            .eq("id", &run.id)
+            .update(run_patch.to_string()),
+    )
+    .await
+    .map_err(|e| warn!(run_id = %run.id, error = %e, "failed to finalise job run"))
+    .ok()
+    .and_then(|rows| rows.into_iter().next());
+
+    finished.unwrap_or(run)
+}
+
+/// Extends the lease every third of its length while the job runs.
+async fn heartbeat(state: AppState, job: Arc<dyn Job>) {
+    let lease = job.lease_secs().max(3);
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs((lease / 3) as u64)).await;
+        let patch = json!({ "locked_until": ts(Utc::now() + Duration::seconds(lease)) });
+        let renewed = fetch::<Vec<JobRecord>>(
+            state
+                .pg
+                .from(JOBS_TABLE)
+                .eq("name", job.name())
+                .eq("locked_by", worker_id())
+                .update(patch.to_string()),
+        )
+        .await;
+        if let Err(e) = renewed {
+            warn!(job = job.name(), error = %e, "failed to renew job lease");
+        }
+    }
+}
+
+/// Clears our lease, applying `patch` in the same update.
+async fn release(state: &AppState, job: &dyn Job, mut patch: Value) {
+    patch["locked_by"] = Value::Null;
+    patch["locked_until"] = Value::Null;
+    let released = fetch::<Vec<JobRecord>>(
+        state
+            .pg
+            .from(JOBS_TABLE)
+            .eq("name", job.name())
+            .eq("locked_by", worker_id())
+            .update(patch.to_string()),
+    )
+    .await;
+    if let Err(e) = released {
+        warn!(job = job.name(), error = %e, "failed to release job lease");
+    }
+}
+
+/// Recent runs, newest first.
+pub async fn list_runs(
+    state: &AppState,
+    job_name: Option<&str>,
+    status: Option<&str>,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<JobRun>, RepoError> {
+    let mut query = state
+        .pg
+        .from(RUNS_TABLE)
+        .select("*")
+        .order("started_at.desc");
+    if let Some(name) = job_name {
+        query = query.eq("job_name", name);
+    }
+    if let Some(status) = status {
+        query = query.eq("status", status);
+    }
+    fetch(query.range(offset, offset + limit.max(1) - 1)).await
+}
+
+pub async fn list_jobs(state: &AppState) -> Result<Vec<JobRecord>, RepoError> {
+    fetch(state.pg.from(JOBS_TABLE).select("*").order("name.asc")).await
+}
+
+/// Whether `record`'s lease is currently held by some worker.
+pub fn is_locked(record: &JobRecord) -> bool {
+    record
+        .locked_until
+        .as_deref()
+        .and_then(parse_ts)
+        .is_some_and(|t| t > Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_and_caps_at_an_hour() {
+        assert_eq!(backoff(1), Duration::seconds(60));
+        assert_eq!(backoff(2), Duration::seconds(120));
+        assert_eq!(backoff(4), Duration::seconds(480));
+        assert_eq!(backoff(7), Duration::seconds(3600));
+        assert_eq!(backoff(100), Duration::seconds(3600));
+        assert_eq!(backoff(0), Duration::seconds(60));
+    }
+
+    #[test]
+    fn timestamps_sort_as_strings() {
+        let a = ts(parse_ts("2026-10-18T09:00:00Z").unwrap());
+        let b = ts(parse_ts("2026-10-18T10:00:00.5+01:00").unwrap());
+        assert_eq!(a, "2026-10-18T09:00:00.000Z");
+        assert!(a < b);
+    }
+}
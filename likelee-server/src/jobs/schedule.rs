@@ -0,0 +1,255 @@
+//! Job schedules: standard 5-field cron expressions (UTC) plus the shorthands
+//! `@hourly`, `@daily`, `@weekly`, `@monthly` and `@every <n>{s,m,h}`.
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Fixed interval measured from the previous run.
+    Every(Duration),
+    Cron(Cron),
+}
+
+/// Parsed cron fields as bitsets: bit `n` set means value `n` matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u32,
+    days_of_month: u32,
+    months: u16,
+    days_of_week: u8,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl Schedule {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let expr = expr.trim();
+        match expr {
+            "@hourly" => return Self::parse("0 * * * *"),
+            "@daily" | "@midnight" => return Self::parse("0 0 * * *"),
+            "@weekly" => return Self::parse("0 0 * * 0"),
+            "@monthly" => return Self::parse("0 0 1 * *"),
+            _ => {}
+        }
+        if let Some(every) = expr.strip_prefix("@every") {
+            return parse_every(every.trim()).map(Schedule::Every);
+        }
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [min, hour, dom, month, dow] = fields[..] else {
+            return Err(format!("expected 5 cron fields in {expr:?}"));
+        };
+        let days_of_week = parse_field(dow, 0, 7)?;
+        Ok(Schedule::Cron(Cron {
+            minutes: parse_field(min, 0, 59)?,
+            hours: parse_field(hour, 0, 23)? as u32,
+            days_of_month: parse_field(dom, 1, 31)? as u32,
+            months: parse_field(month, 1, 12)? as u16,
+            // Both 0 and 7 mean Sunday.
+            days_of_week: ((days_of_week | (days_of_week >> 7)) & 0x7f) as u8,
+            dom_restricted: dom != "*",
+            dow_restricted: dow != "*",
+        }))
+    }
+
+    /// First time strictly after `after` at which the schedule fires.
+    pub fn next_after(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Schedule::Every(interval) => after + *interval,
+            Schedule::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+impl Cron {
+    fn matches_day(&self, t: DateTime<Utc>) -> bool {
+        let dom = self.days_of_month & (1 << t.day()) != 0;
+        let dow = self.days_of_week & (1 << t.weekday().num_days_from_sunday()) != 0;
+        // Cron semantics: when both day fields are restricted either may match.
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+
+    fn next_after(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        let mut t = after
+            .with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(after)
+            + Duration::minutes(1);
+        // Skip whole months/days/hours where possible; five years covers any
+        // valid expression (e.g. Feb 29).
+        let limit = after + Duration::days(5 * 366);
+        while t < limit {
+            if self.months & (1 << t.month()) == 0 {
+                let (y, m) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = Utc.with_ymd_and_hms(y, m, 1, 0, 0, 0).unwrap();
+                continue;
+            }
+            if !self.matches_day(t) {
+                t = start_of_day(t) + Duration::days(1);
+                continue;
+            }
+            if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0).unwrap_or(t) + Duration::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << t.minute()) == 0 {
+                t += Duration::minutes(1);
+                continue;
+            }
+            return t;
+        }
+        // Unsatisfiable (e.g. "0 0 31 2 *"): push far into the future.
+        limit
+    }
+}
+
+fn start_of_day(t: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(t.year(), t.month(), t.day(), 0, 0, 0)
+        .unwrap()
+}
+
+fn parse_every(spec: &str) -> Result<Duration, String> {
+    let split = spec
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(spec.len());
+    let (num, unit) = spec.split_at(split);
+    let n: i64 = num
+        .parse()
+        .map_err(|_| format!("invalid interval {spec:?}"))?;
+    if n <= 0 {
+        return Err(format!("interval must be positive: {spec:?}"));
+    }
+    match unit {
+        "s" | "" => Ok(Duration::seconds(n)),
+        "m" => Ok(Duration::minutes(n)),
+        "h" => Ok(Duration::hours(n)),
+        _ => Err(format!("invalid interval unit in {spec:?}")),
+    }
+}
+
+/// Parses `*`, `*/n`, `a`, `a-b`, `a-b/n` and comma lists into a bitset.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (
+                r,
+                s.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid step in {field:?}"))?,
+            ),
+            None => (part, 1),
+        };
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, field)?, parse_value(b, field)?)
+        } else {
+            let v = parse_value(range, field)?;
+            (v, if step > 1 { max } else { v })
+        };
+        if lo < min || hi > max || lo > hi {
+            return Err(format!("{field:?} is outside {min}-{max}"));
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_value(v: &str, field: &str) -> Result<u32, String> {
+    v.parse()
+        .map_err(|_| format!("invalid value {v:?} in {field:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(expr: &str, after: &str) -> String {
+        Schedule::parse(expr)
+            .unwrap()
+            .next_after(at(after))
+            .to_rfc3339()
+    }
+
+    #[test]
+    fn daily_and_stepped_expressions() {
+        assert_eq!(
+            next("0 9 * * *", "2026-10-18T08:59:30Z"),
+            "2026-10-18T09:00:00+00:00"
+        );
+        assert_eq!(
+            next("0 9 * * *", "2026-10-18T09:00:00Z"),
+            "2026-10-19T09:00:00+00:00"
+        );
+        assert_eq!(
+            next("*/15 * * * *", "2026-10-18T10:16:00Z"),
+            "2026-10-18T10:30:00+00:00"
+        );
+        assert_eq!(
+            next("@monthly", "2026-12-31T12:00:00Z"),
+            "2027-01-01T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn day_fields_follow_cron_semantics() {
+        // 2026-10-18 is a Sunday; "1-5" restricts to weekdays.
+        assert_eq!(
+            next("30 6 * * 1-5", "2026-10-17T07:00:00Z"),
+            "2026-10-19T06:30:00+00:00"
+        );
+        // 7 is an alias for Sunday.
+        assert_eq!(
+            next("0 0 * * 7", "2026-10-17T00:00:00Z"),
+            "2026-10-18T00:00:00+00:00"
+        );
+        // Either the 1st or a Friday (2026-10-23).
+        assert_eq!(
+            next("0 0 1 * 5", "2026-10-18T00:00:00Z"),
+            "2026-10-23T00:00:00+00:00"
+        );
+        assert_eq!(
+            next("0 0 29 2 *", "2026-03-01T00:00:00Z"),
+            "2028-02-29T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn intervals_and_invalid_expressions() {
+        assert_eq!(
+            next("@every 90s", "2026-10-18T10:00:00Z"),
+            "2026-10-18T10:01:30+00:00"
+        );
+        assert_eq!(
+            next("@every 2h", "2026-10-18T10:00:00Z"),
+            "2026-10-18T12:00:00+00:00"
+        );
+        for bad in [
+            "",
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "@every 0s",
+            "a b c d e",
+        ] {
+            assert!(Schedule::parse(bad).is_err(), "{bad}");
+        }
+    }
+}
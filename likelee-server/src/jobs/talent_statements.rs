@@ -0,0 +1,26 @@
+use super::Job;
+use crate::config::AppState;
+use axum::async_trait;
+use serde_json::Value;
+
+/// Emails last month's talent statements for agencies that turned monthly
+/// delivery on.
+pub struct TalentStatements;
+
+#[async_trait]
+impl Job for TalentStatements {
+    fn name(&self) -> &'static str {
+        "talent_statements"
+    }
+
+    fn schedule(&self, _state: &AppState) -> String {
+        "0 8 1 * *".to_string()
+    }
+
+    async fn run(&self, state: &AppState) -> Result<Value, String> {
+        let summary = crate::talent_statement_exports::run_today(state)
+            .await
+            .map_err(|e| e.to_string())?;
+        serde_json::to_value(summary).map_err(|e| e.to_string())
+    }
+}
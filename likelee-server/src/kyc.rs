@@ -1,4 +1,4 @@
-use crate::{auth::AuthUser, config::AppState};
+use crate::{auth::AuthUser, config::AppState, errors::AppError};
 use axum::{
     extract::{Query, State},
     http::StatusCode,
@@ -156,7 +156,7 @@ struct VeriffCreateSessionBody<'a> {
     verification: VeriffVerification<'a>,
 }
 
-fn compute_hmac_hex(secret: &str, body: &[u8]) -> String {
+pub(crate) fn compute_hmac_hex(secret: &str, body: &[u8]) -> String {
     let mut mac =
         HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
     mac.update(body);
@@ -532,7 +532,7 @@ fn json_get_str<'a>(v: &'a serde_json::Value, path: &[&str]) -> Option<&'a str>
     cur.as_str()
 }
 
-fn constant_time_eq(a: &str, b: &str) -> bool {
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
     if a.len() != b.len() {
         return false;
     }
@@ -565,19 +565,34 @@ pub async fn veriff_webhook(
         ));
     }
 
-    // Veriff webhook payload can have multiple shapes. We accept:
-    // 1) { vendorData, decision: { status }, session: { id } }
-    // 2) { verification: { vendorData, status, id, ... }, status: "success" }
     let v: serde_json::Value = serde_json::from_slice(&body_bytes)
         .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e.to_string()))?;
     debug!(body = %v, "Veriff webhook payload");
 
-    let vendor_data_raw = json_get_str(&v, &["vendorData"])
-        .or_else(|| json_get_str(&v, &["verification", "vendorData"]))
-        .ok_or((
-            axum::http::StatusCode::BAD_REQUEST,
-            "missing vendorData".into(),
-        ))?;
+    // Veriff has no delivery id; a redelivery carries the identical body.
+    let event_id = crate::webhooks::body_digest(&body_bytes);
+    let event_type = json_get_str(&v, &["action"])
+        .or_else(|| json_get_str(&v, &["status"]))
+        .unwrap_or("decision")
+        .to_string();
+    let outcome =
+        crate::webhooks::ingest(&state, crate::webhooks::VERIFF, &event_id, &event_type, v)
+            .await
+            .map_err(AppError::from)?;
+    Ok(outcome.into_result()?)
+}
+
+/// Applies a Veriff decision to the profile named in `vendorData`.
+pub(crate) async fn process_veriff_event(
+    state: &AppState,
+    v: &serde_json::Value,
+) -> Result<(), String> {
+    // Veriff webhook payload can have multiple shapes. We accept:
+    // 1) { vendorData, decision: { status }, session: { id } }
+    // 2) { verification: { vendorData, status, id, ... }, status: "success" }
+    let vendor_data_raw = json_get_str(v, &["vendorData"])
+        .or_else(|| json_get_str(v, &["verification", "vendorData"]))
+        .ok_or_else(|| "missing vendorData".to_string())?;
 
     // vendor_data format: "role:id"
     let parts: Vec<&str> = vendor_data_raw.splitn(2, ':').collect();
@@ -587,9 +602,9 @@ pub async fn veriff_webhook(
         ("creator", parts[0]) // fallback for legacy sessions
     };
 
-    let status = json_get_str(&v, &["decision", "status"])
-        .or_else(|| json_get_str(&v, &["verification", "decision", "status"]))
-        .or_else(|| json_get_str(&v, &["verification", "status"]))
+    let status = json_get_str(v, &["decision", "status"])
+        .or_else(|| json_get_str(v, &["verification", "decision", "status"]))
+        .or_else(|| json_get_str(v, &["verification", "status"]))
         .unwrap_or("pending")
         .to_lowercase();
     let approved = status == "approved";
@@ -602,8 +617,8 @@ pub async fn veriff_webhook(
     };
     info!(%profile_id, %role, %mapped_status, "Received Veriff decision webhook");
 
-    let session_id = json_get_str(&v, &["session", "id"])
-        .or_else(|| json_get_str(&v, &["verification", "id"]))
+    let session_id = json_get_str(v, &["session", "id"])
+        .or_else(|| json_get_str(v, &["verification", "id"]))
         .map(|s| s.to_string());
 
     let payload = ProfileVerification {
@@ -613,8 +628,5 @@ pub async fn veriff_webhook(
         kyc_session_id: session_id,
         verified_at: approved.then(|| Utc::now().to_rfc3339()),
     };
-    update_verification_status(&state, profile_id, role, &payload)
-        .await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e))?;
-    Ok(axum::http::StatusCode::OK)
+    update_verification_status(state, profile_id, role, &payload).await
 }
@@ -0,0 +1,69 @@
+//! Balance explanations for agencies, creators and admins, and the admin
+//! drift report.
+
+use super::{drift, explain, Account, BalanceExplanation};
+use crate::auth::{AuthUser, RoleGuard};
+use crate::config::AppState;
+use crate::errors::{AppError, AppResult};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ExplainParams {
+    pub currency: Option<String>,
+}
+
+/// GET /api/agency/ledger/balance: the agency's ledger balance per currency
+/// with every entry that makes it up.
+pub async fn agency_balance(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<ExplainParams>,
+) -> AppResult<Json<Vec<BalanceExplanation>>> {
+    RoleGuard::new(vec!["agency"]).check(&user.role)?;
+    let account = Account::agency(&user.id);
+    Ok(Json(
+        explain(&state, &account, params.currency.as_deref()).await?,
+    ))
+}
+
+/// GET /api/creator/ledger/balance
+pub async fn creator_balance(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<ExplainParams>,
+) -> AppResult<Json<Vec<BalanceExplanation>>> {
+    RoleGuard::new(vec!["creator"]).check(&user.role)?;
+    let account = Account::creator(&user.id);
+    Ok(Json(
+        explain(&state, &account, params.currency.as_deref()).await?,
+    ))
+}
+
+/// GET /api/admin/ledger/accounts/:account, where `:account` is a code such
+/// as `platform:cash` or `connected:acct_123`.
+pub async fn account_balance(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(code): Path<String>,
+    Query(params): Query<ExplainParams>,
+) -> AppResult<Json<Vec<BalanceExplanation>>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let account = Account::parse(&code)
+        .ok_or_else(|| AppError::BadRequest("invalid_ledger_account".to_string()))?;
+    Ok(Json(
+        explain(&state, &account, params.currency.as_deref()).await?,
+    ))
+}
+
+/// GET /api/admin/ledger/drift: runs the Stripe consistency check now.
+pub async fn drift_report(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> AppResult<Json<drift::DriftReport>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    Ok(Json(drift::check(&state).await?))
+}
@@ -0,0 +1,169 @@
+//! Consistency check between the ledger and Stripe.
+//!
+//! A `connected:<acct>` account holds what the ledger believes sits in that
+//! Stripe connected account: transfers in minus payouts out. The ledger only
+//! knows about money that moved after its cutover (`ledger_settings`), so the
+//! check compares it with the net of the account's Stripe balance
+//! transactions since then, in each currency, and reports every difference.
+//! Without a recorded cutover the whole Stripe balance (available plus
+//! pending) is compared.
+
+use super::{AccountType, BALANCES_VIEW};
+use crate::config::AppState;
+use crate::repositories::{fetch, RepoError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::warn;
+
+const SETTINGS_TABLE: &str = "ledger_settings";
+
+#[derive(Debug, Clone, Deserialize)]
+struct AccountBalance {
+    account: String,
+    owner_id: Option<String>,
+    currency: String,
+    amount_cents: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Drift {
+    pub account: String,
+    pub stripe_account_id: String,
+    pub currency: String,
+    pub ledger_cents: i64,
+    /// Stripe balance movement since the cutover, or the whole balance
+    /// when there is none.
+    pub stripe_cents: i64,
+    /// `stripe_cents - ledger_cents`.
+    pub drift_cents: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DriftReport {
+    pub cutover_at: Option<DateTime<Utc>>,
+    pub accounts_checked: usize,
+    pub drifts: Vec<Drift>,
+    /// Accounts whose Stripe balance could not be read.
+    pub errors: Vec<serde_json::Value>,
+}
+
+async fn cutover_at(state: &AppState) -> Result<Option<DateTime<Utc>>, RepoError> {
+    #[derive(Deserialize)]
+    struct Row {
+        cutover_at: DateTime<Utc>,
+    }
+    let rows: Vec<Row> = fetch(state.pg.from(SETTINGS_TABLE).select("cutover_at").limit(1)).await?;
+    Ok(rows.into_iter().next().map(|r| r.cutover_at))
+}
+
+/// Cents per lowercase currency in the connected account: the net of its
+/// balance transactions since `since`, or its whole balance.
+async fn stripe_amounts(
+    state: &AppState,
+    stripe_account_id: &str,
+    since: Option<DateTime<Utc>>,
+) -> Result<HashMap<String, i64>, String> {
+    let account = stripe_account_id
+        .parse::<stripe_sdk::AccountId>()
+        .map_err(|_| "invalid_account_id".to_string())?;
+    let client = state.stripe_client().with_stripe_account(account);
+    let mut amounts = HashMap::new();
+
+    let Some(since) = since else {
+        let balance = stripe_sdk::Balance::retrieve(&client, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        for a in balance.available.iter().chain(balance.pending.iter()) {
+            *amounts.entry(a.currency.to_string()).or_default() += a.amount;
+        }
+        return Ok(amounts);
+    };
+
+    let mut params = stripe_sdk::ListBalanceTransactions {
+        created: Some(stripe_sdk::RangeQuery::Bounds(stripe_sdk::RangeBounds {
+            gte: Some(since.timestamp()),
+            ..Default::default()
+        })),
+        limit: Some(100),
+        ..Default::default()
+    };
+    loop {
+        let page = stripe_sdk::BalanceTransaction::list(&client, &params)
+            .await
+            .map_err(|e| e.to_string())?;
+        for t in &page.data {
+            *amounts.entry(t.currency.to_string()).or_default() += t.net;
+        }
+        params.starting_after = page.data.last().map(|t| t.id.clone());
+        if !page.has_more || params.starting_after.is_none() {
+            return Ok(amounts);
+        }
+    }
+}
+
+/// Compares every connected account the ledger knows about with Stripe.
+pub async fn check(state: &AppState) -> Result<DriftReport, RepoError> {
+    let balances: Vec<AccountBalance> = fetch(
+        state
+            .pg
+            .from(BALANCES_VIEW)
+            .select("account,owner_id,currency,amount_cents")
+            .eq("account_type", AccountType::Connected.as_str())
+            .order("account.asc"),
+    )
+    .await?;
+
+    let mut accounts: Vec<&str> = balances
+        .iter()
+        .filter_map(|b| b.owner_id.as_deref())
+        .collect();
+    accounts.dedup();
+
+    let cutover_at = cutover_at(state).await?;
+    let mut report = DriftReport {
+        cutover_at,
+        accounts_checked: accounts.len(),
+        ..Default::default()
+    };
+    for stripe_account_id in accounts {
+        let stripe = match stripe_amounts(state, stripe_account_id, cutover_at).await {
+            Ok(b) => b,
+            Err(e) => {
+                warn!(stripe_account_id, error = %e, "ledger_drift_balance_unavailable");
+                report
+                    .errors
+                    .push(json!({ "stripe_account_id": stripe_account_id, "error": e }));
+                continue;
+            }
+        };
+        for ledger in balances
+            .iter()
+            .filter(|b| b.owner_id.as_deref() == Some(stripe_account_id))
+        {
+            let stripe_cents = stripe
+                .get(&ledger.currency.to_lowercase())
+                .copied()
+                .unwrap_or(0);
+            if stripe_cents != ledger.amount_cents {
+                warn!(
+                    account = %ledger.account,
+                    currency = %ledger.currency,
+                    ledger_cents = ledger.amount_cents,
+                    stripe_cents,
+                    "ledger_drift_detected"
+                );
+                report.drifts.push(Drift {
+                    account: ledger.account.clone(),
+                    stripe_account_id: stripe_account_id.to_string(),
+                    currency: ledger.currency.clone(),
+                    ledger_cents: ledger.amount_cents,
+                    stripe_cents,
+                    drift_cents: stripe_cents - ledger.amount_cents,
+                });
+            }
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,672 @@
+//! Double-entry ledger for platform, agency and creator money.
+//!
+//! Every money movement is posted as an immutable [`Entry`] in
+//! `ledger_entries` whose postings in `ledger_postings` sum to zero. Postings
+//! are signed (debits positive, credits negative) and belong to an
+//! [`Account`]; a balance is the sum of an account's postings, so it can
+//! always be explained line by line with [`explain`]. Entries carry an
+//! idempotency key, which makes posting safe to repeat from webhook retries
+//! and job re-runs.
+//!
+//! The legacy balance tables (`creator_balances`, `agency_balances`, ...) are
+//! still maintained by triggers; the ledger records the same movements
+//! alongside them.
+
+pub mod api;
+pub mod drift;
+
+use crate::config::AppState;
+use crate::repositories::{fetch, RepoError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use tracing::warn;
+
+pub const ENTRIES_TABLE: &str = "ledger_entries";
+pub const POSTINGS_TABLE: &str = "ledger_postings";
+/// `ledger_postings` summed per account and currency.
+pub const BALANCES_VIEW: &str = "ledger_account_balances";
+
+const ENTRY_COLUMNS: &str =
+    "id,idempotency_key,kind,currency,memo,reference_type,reference_id,occurred_at,created_at";
+const POSTING_COLUMNS: &str =
+    "id,entry_id,account,account_type,owner_id,currency,amount_cents,created_at";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    ClientPayment,
+    PlatformFee,
+    AgencyCommission,
+    TalentShare,
+    Transfer,
+    Payout,
+    Refund,
+    Adjustment,
+}
+
+impl EntryKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::ClientPayment => "client_payment",
+            EntryKind::PlatformFee => "platform_fee",
+            EntryKind::AgencyCommission => "agency_commission",
+            EntryKind::TalentShare => "talent_share",
+            EntryKind::Transfer => "transfer",
+            EntryKind::Payout => "payout",
+            EntryKind::Refund => "refund",
+            EntryKind::Adjustment => "adjustment",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountType {
+    /// Funds held in the platform's Stripe balance.
+    PlatformCash,
+    /// Fees the platform has earned.
+    PlatformRevenue,
+    /// A client payment waiting to be split, one account per payment.
+    Clearing,
+    /// What the platform owes an agency.
+    Agency,
+    /// What the platform owes a creator.
+    Creator,
+    /// Funds sitting in a Stripe connected account.
+    Connected,
+}
+
+impl AccountType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AccountType::PlatformCash => "platform_cash",
+            AccountType::PlatformRevenue => "platform_revenue",
+            AccountType::Clearing => "clearing",
+            AccountType::Agency => "agency",
+            AccountType::Creator => "creator",
+            AccountType::Connected => "connected",
+        }
+    }
+
+    /// Liabilities and revenue grow with credits; their balances are shown
+    /// with the sign flipped so that money owed reads as a positive amount.
+    pub fn credit_normal(self) -> bool {
+        matches!(
+            self,
+            AccountType::PlatformRevenue
+                | AccountType::Clearing
+                | AccountType::Agency
+                | AccountType::Creator
+        )
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            AccountType::PlatformCash => "platform:cash",
+            AccountType::PlatformRevenue => "platform:revenue",
+            AccountType::Clearing => "clearing",
+            AccountType::Agency => "agency",
+            AccountType::Creator => "creator",
+            AccountType::Connected => "connected",
+        }
+    }
+}
+
+/// A ledger account, written as a code such as `agency:<id>` or `platform:cash`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Account {
+    pub account_type: AccountType,
+    pub owner_id: Option<String>,
+}
+
+impl Account {
+    pub fn platform_cash() -> Self {
+        Self {
+            account_type: AccountType::PlatformCash,
+            owner_id: None,
+        }
+    }
+
+    pub fn platform_revenue() -> Self {
+        Self {
+            account_type: AccountType::PlatformRevenue,
+            owner_id: None,
+        }
+    }
+
+    fn owned(account_type: AccountType, owner_id: &str) -> Self {
+        Self {
+            account_type,
+            owner_id: Some(owner_id.to_string()),
+        }
+    }
+
+    pub fn clearing(payment_id: &str) -> Self {
+        Self::owned(AccountType::Clearing, payment_id)
+    }
+
+    pub fn agency(agency_id: &str) -> Self {
+        Self::owned(AccountType::Agency, agency_id)
+    }
+
+    pub fn creator(creator_id: &str) -> Self {
+        Self::owned(AccountType::Creator, creator_id)
+    }
+
+    pub fn connected(stripe_account_id: &str) -> Self {
+        Self::owned(AccountType::Connected, stripe_account_id)
+    }
+
+    pub fn code(&self) -> String {
+        match &self.owner_id {
+            Some(owner) => format!("{}:{}", self.account_type.prefix(), owner),
+            None => self.account_type.prefix().to_string(),
+        }
+    }
+
+    pub fn parse(code: &str) -> Option<Self> {
+        match code {
+            "platform:cash" => return Some(Self::platform_cash()),
+            "platform:revenue" => return Some(Self::platform_revenue()),
+            _ => {}
+        }
+        let (prefix, owner) = code.split_once(':')?;
+        if owner.is_empty() {
+            return None;
+        }
+        let account_type = match prefix {
+            "clearing" => AccountType::Clearing,
+            "agency" => AccountType::Agency,
+            "creator" => AccountType::Creator,
+            "connected" => AccountType::Connected,
+            _ => return None,
+        };
+        Some(Self::owned(account_type, owner))
+    }
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.code())
+    }
+}
+
+/// One journal entry, e.g.
+/// `Entry::new(EntryKind::Transfer, "transfer:tr_1", "USD").debit(&connected, 500).credit(&cash, 500)`.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    kind: EntryKind,
+    idempotency_key: String,
+    currency: String,
+    memo: Option<String>,
+    reference_type: Option<String>,
+    reference_id: Option<String>,
+    occurred_at: Option<DateTime<Utc>>,
+    postings: Vec<(Account, i64)>,
+}
+
+impl Entry {
+    pub fn new(kind: EntryKind, idempotency_key: impl Into<String>, currency: &str) -> Self {
+        Self {
+            kind,
+            idempotency_key: idempotency_key.into(),
+            currency: currency.to_uppercase(),
+            memo: None,
+            reference_type: None,
+            reference_id: None,
+            occurred_at: None,
+            postings: vec![],
+        }
+    }
+
+    pub fn memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
+
+    /// The business object the movement belongs to, e.g. `("payment_link", id)`.
+    pub fn reference(mut self, reference_type: &str, reference_id: &str) -> Self {
+        self.reference_type = Some(reference_type.to_string());
+        self.reference_id = Some(reference_id.to_string());
+        self
+    }
+
+    pub fn occurred_at(mut self, at: DateTime<Utc>) -> Self {
+        self.occurred_at = Some(at);
+        self
+    }
+
+    /// Zero amounts are dropped so callers need not special-case empty fees.
+    pub fn debit(mut self, account: &Account, amount_cents: i64) -> Self {
+        if amount_cents != 0 {
+            self.postings.push((account.clone(), amount_cents));
+        }
+        self
+    }
+
+    pub fn credit(self, account: &Account, amount_cents: i64) -> Self {
+        self.debit(account, -amount_cents)
+    }
+
+    pub fn kind(&self) -> EntryKind {
+        self.kind
+    }
+
+    pub fn idempotency_key(&self) -> &str {
+        &self.idempotency_key
+    }
+
+    pub fn validate(&self) -> Result<(), LedgerError> {
+        if self.postings.is_empty() {
+            return Err(LedgerError::Empty);
+        }
+        let sum: i64 = self.postings.iter().map(|(_, amount)| amount).sum();
+        if sum != 0 {
+            return Err(LedgerError::Unbalanced(sum));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum LedgerError {
+    /// The entry has no non-zero postings.
+    Empty,
+    /// The postings sum to the given non-zero amount.
+    Unbalanced(i64),
+    Repo(RepoError),
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerError::Empty => f.write_str("ledger entry has no postings"),
+            LedgerError::Unbalanced(sum) => write!(f, "ledger entry is off by {sum} cents"),
+            LedgerError::Repo(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<RepoError> for LedgerError {
+    fn from(e: RepoError) -> Self {
+        LedgerError::Repo(e)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub id: String,
+    pub idempotency_key: String,
+    pub kind: EntryKind,
+    pub currency: String,
+    pub memo: Option<String>,
+    pub reference_type: Option<String>,
+    pub reference_id: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerPosting {
+    pub id: String,
+    pub entry_id: String,
+    pub account: String,
+    pub account_type: AccountType,
+    pub owner_id: Option<String>,
+    pub currency: String,
+    pub amount_cents: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Posted {
+    Created(String),
+    /// An entry with the same idempotency key was already posted.
+    Existing(String),
+}
+
+impl Posted {
+    pub fn entry_id(&self) -> &str {
+        match self {
+            Posted::Created(id) | Posted::Existing(id) => id,
+        }
+    }
+}
+
+async fn find_entry(state: &AppState, key: &str) -> Result<Option<LedgerEntry>, RepoError> {
+    let rows: Vec<LedgerEntry> = fetch(
+        state
+            .pg
+            .from(ENTRIES_TABLE)
+            .select(ENTRY_COLUMNS)
+            .eq("idempotency_key", key)
+            .limit(1),
+    )
+    .await?;
+    Ok(rows.into_iter().next())
+}
+
+async fn has_postings(state: &AppState, entry_id: &str) -> Result<bool, RepoError> {
+    let rows: Vec<Value> = fetch(
+        state
+            .pg
+            .from(POSTINGS_TABLE)
+            .select("id")
+            .eq("entry_id", entry_id)
+            .limit(1),
+    )
+    .await?;
+    Ok(!rows.is_empty())
+}
+
+async fn insert_postings(state: &AppState, entry_id: &str, entry: &Entry) -> Result<(), RepoError> {
+    let rows: Vec<Value> = entry
+        .postings
+        .iter()
+        .map(|(account, amount)| {
+            json!({
+                "entry_id": entry_id,
+                "account": account.code(),
+                "account_type": account.account_type.as_str(),
+                "owner_id": account.owner_id,
+                "currency": entry.currency,
+                "amount_cents": amount,
+            })
+        })
+        .collect();
+    // One statement, so the database's balance check sees the whole entry.
+    let _: Vec<Value> = fetch(
+        state
+            .pg
+            .from(POSTINGS_TABLE)
+            .insert(Value::Array(rows).to_string()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Posts `entry` unless an entry with its idempotency key exists. A header
+/// left without postings by an interrupted earlier attempt is completed.
+pub async fn post(state: &AppState, entry: &Entry) -> Result<Posted, LedgerError> {
+    entry.validate()?;
+
+    if let Some(existing) = find_entry(state, &entry.idempotency_key).await? {
+        if !has_postings(state, &existing.id).await? {
+            insert_postings(state, &existing.id, entry).await?;
+        }
+        return Ok(Posted::Existing(existing.id));
+    }
+
+    let header = json!({
+        "idempotency_key": entry.idempotency_key,
+        "kind": entry.kind.as_str(),
+        "currency": entry.currency,
+        "memo": entry.memo,
+        "reference_type": entry.reference_type,
+        "reference_id": entry.reference_id,
+        "occurred_at": crate::jobs::runner::ts(entry.occurred_at.unwrap_or_else(Utc::now)),
+    });
+    let inserted: Result<Vec<LedgerEntry>, RepoError> =
+        fetch(state.pg.from(ENTRIES_TABLE).insert(header.to_string())).await;
+    let id = match inserted {
+        Ok(rows) => rows
+            .into_iter()
+            .next()
+            .map(|e| e.id)
+            .ok_or_else(|| RepoError::Decode("ledger entry insert returned no row".into()))?,
+        // Lost a race with a concurrent post of the same entry.
+        Err(RepoError::Db { status: 409, .. }) => {
+            let existing = find_entry(state, &entry.idempotency_key)
+                .await?
+                .ok_or(RepoError::NotFound)?;
+            return Ok(Posted::Existing(existing.id));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    insert_postings(state, &id, entry).await?;
+    Ok(Posted::Created(id))
+}
+
+/// Posts `entry` after the money already moved, so a failure is logged
+/// rather than failing the operation that moved it. The idempotency key lets
+/// a later retry of that operation fill the gap.
+pub async fn record(state: &AppState, entry: Entry) {
+    if let Err(e) = post(state, &entry).await {
+        warn!(
+            kind = entry.kind.as_str(),
+            idempotency_key = %entry.idempotency_key,
+            error = %e,
+            "ledger_post_failed"
+        );
+    }
+}
+
+/// A talent's share of a client payment, resolved to the creator it is owed to.
+#[derive(Debug, Clone)]
+pub struct TalentShare {
+    pub creator_id: String,
+    pub amount_cents: i64,
+}
+
+/// The entries for a paid payment link: the client payment lands in a
+/// clearing account for the link and is split from there into the platform
+/// fee, the agency commission and each talent's share. Anything left in the
+/// clearing account afterwards is an unexplained difference.
+pub fn payment_link_entries(
+    payment_link_id: &str,
+    currency: &str,
+    total_cents: i64,
+    platform_fee_cents: i64,
+    agency_id: &str,
+    agency_amount_cents: i64,
+    talent_shares: &[TalentShare],
+) -> Vec<Entry> {
+    let clearing = Account::clearing(payment_link_id);
+    let key = |part: &str| format!("payment_link:{payment_link_id}:{part}");
+    let entry = |kind: EntryKind, part: &str| {
+        Entry::new(kind, key(part), currency).reference("payment_link", payment_link_id)
+    };
+
+    let mut entries = vec![
+        entry(EntryKind::ClientPayment, "client_payment")
+            .memo("Client payment received")
+            .debit(&Account::platform_cash(), total_cents)
+            .credit(&clearing, total_cents),
+        entry(EntryKind::PlatformFee, "platform_fee")
+            .memo("Platform fee")
+            .debit(&clearing, platform_fee_cents)
+            .credit(&Account::platform_revenue(), platform_fee_cents),
+        entry(EntryKind::AgencyCommission, "agency_commission")
+            .memo("Agency commission")
+            .debit(&clearing, agency_amount_cents)
+            .credit(&Account::agency(agency_id), agency_amount_cents),
+    ];
+    for share in talent_shares {
+        entries.push(
+            entry(
+                EntryKind::TalentShare,
+                &format!("talent_share:{}", share.creator_id),
+            )
+            .memo("Talent share")
+            .debit(&clearing, share.amount_cents)
+            .credit(&Account::creator(&share.creator_id), share.amount_cents),
+        );
+    }
+    entries.retain(|e| e.validate().is_ok());
+    entries
+}
+
+/// One posting of an account's history with the balance after it.
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceLine {
+    pub entry_id: String,
+    pub kind: Option<EntryKind>,
+    pub memo: Option<String>,
+    pub reference_type: Option<String>,
+    pub reference_id: Option<String>,
+    pub occurred_at: Option<DateTime<Utc>>,
+    /// Effect on the displayed balance: positive increases it.
+    pub amount_cents: i64,
+    pub balance_cents: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceExplanation {
+    pub account: String,
+    pub currency: String,
+    pub balance_cents: i64,
+    pub lines: Vec<BalanceLine>,
+}
+
+/// An account's balance in each currency it has postings in, with every
+/// posting that makes it up in the order it was written.
+pub async fn explain(
+    state: &AppState,
+    account: &Account,
+    currency: Option<&str>,
+) -> Result<Vec<BalanceExplanation>, RepoError> {
+    let mut query = state
+        .pg
+        .from(POSTINGS_TABLE)
+        .select(POSTING_COLUMNS)
+        .eq("account", account.code())
+        .order("created_at.asc");
+    if let Some(c) = currency {
+        query = query.eq("currency", c.to_uppercase());
+    }
+    let postings: Vec<LedgerPosting> = fetch(query).await?;
+
+    let mut entry_ids: Vec<&str> = postings.iter().map(|p| p.entry_id.as_str()).collect();
+    entry_ids.sort();
+    entry_ids.dedup();
+    let entries: Vec<LedgerEntry> = if entry_ids.is_empty() {
+        vec![]
+    } else {
+        fetch(
+            state
+                .pg
+                .from(ENTRIES_TABLE)
+                .select(ENTRY_COLUMNS)
+                .in_("id", entry_ids),
+        )
+        .await?
+    };
+
+    Ok(build_explanations(account, &postings, &entries))
+}
+
+fn build_explanations(
+    account: &Account,
+    postings: &[LedgerPosting],
+    entries: &[LedgerEntry],
+) -> Vec<BalanceExplanation> {
+    let sign = if account.account_type.credit_normal() {
+        -1
+    } else {
+        1
+    };
+    let mut out: Vec<BalanceExplanation> = vec![];
+    for posting in postings {
+        let idx = match out.iter().position(|e| e.currency == posting.currency) {
+            Some(idx) => idx,
+            None => {
+                out.push(BalanceExplanation {
+                    account: account.code(),
+                    currency: posting.currency.clone(),
+                    balance_cents: 0,
+                    lines: vec![],
+                });
+                out.len() - 1
+            }
+        };
+        let explanation = &mut out[idx];
+        let entry = entries.iter().find(|e| e.id == posting.entry_id);
+        let amount = sign * posting.amount_cents;
+        explanation.balance_cents += amount;
+        explanation.lines.push(BalanceLine {
+            entry_id: posting.entry_id.clone(),
+            kind: entry.map(|e| e.kind),
+            memo: entry.and_then(|e| e.memo.clone()),
+            reference_type: entry.and_then(|e| e.reference_type.clone()),
+            reference_id: entry.and_then(|e| e.reference_id.clone()),
+            occurred_at: entry.map(|e| e.occurred_at),
+            amount_cents: amount,
+            balance_cents: explanation.balance_cents,
+        });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_codes_round_trip() {
+        for account in [
+            Account::platform_cash(),
+            Account::platform_revenue(),
+            Account::agency("a1"),
+            Account::creator("c1"),
+            Account::connected("acct_1"),
+            Account::clearing("pl_1"),
+        ] {
+            assert_eq!(Account::parse(&account.code()), Some(account));
+        }
+        assert_eq!(Account::parse("agency:"), None);
+        assert_eq!(Account::parse("bank:1"), None);
+    }
+
+    #[test]
+    fn unbalanced_entries_are_rejected() {
+        let entry = Entry::new(EntryKind::Adjustment, "k", "usd")
+            .debit(&Account::platform_cash(), 100)
+            .credit(&Account::agency("a1"), 90);
+        assert!(matches!(entry.validate(), Err(LedgerError::Unbalanced(10))));
+        let empty =
+            Entry::new(EntryKind::Adjustment, "k", "usd").debit(&Account::platform_cash(), 0);
+        assert!(matches!(empty.validate(), Err(LedgerError::Empty)));
+    }
+
+    #[test]
+    fn payment_link_split_empties_the_clearing_account() {
+        let entries = payment_link_entries(
+            "pl_1",
+            "usd",
+            10_000,
+            1_000,
+            "a1",
+            2_700,
+            &[
+                TalentShare {
+                    creator_id: "c1".into(),
+                    amount_cents: 6_300,
+                },
+                TalentShare {
+                    creator_id: "c2".into(),
+                    amount_cents: 0,
+                },
+            ],
+        );
+        let kinds: Vec<EntryKind> = entries.iter().map(|e| e.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                EntryKind::ClientPayment,
+                EntryKind::PlatformFee,
+                EntryKind::AgencyCommission,
+                EntryKind::TalentShare,
+            ]
+        );
+        let clearing: i64 = entries
+            .iter()
+            .flat_map(|e| e.postings.iter())
+            .filter(|(a, _)| a.account_type == AccountType::Clearing)
+            .map(|(_, amount)| amount)
+            .sum();
+        assert_eq!(clearing, 0);
+        assert_eq!(entries[0].currency, "USD");
+    }
+}
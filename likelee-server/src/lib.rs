@@ -1,5 +1,6 @@
-#![allow(clippy::uninlined_format_args)]
+#![allow(clippy::uninlined_format_args, clippy::unnecessary_sort_by)]
 
+pub mod accounting_export;
 pub mod active_licenses;
 pub mod agencies;
 pub mod agency_clients;
@@ -7,6 +8,7 @@ pub mod agency_dashboard;
 pub mod agency_roster;
 pub mod agency_talent_invites;
 pub mod analytics;
+pub mod audit;
 pub mod auth;
 pub mod billing;
 pub mod book_outs;
@@ -28,9 +30,14 @@ pub mod errors;
 pub mod expenses;
 pub mod face_profiles;
 pub mod health;
+pub mod invoice_dunning;
+pub mod invoice_payments;
+pub mod invoice_pdf;
+pub mod invoice_tax;
 pub mod invoices;
 pub mod jobs;
 pub mod kyc;
+pub mod ledger;
 pub mod license_submissions;
 pub mod license_templates;
 pub mod licenses;
@@ -39,12 +46,23 @@ pub mod moderation;
 pub mod notifications;
 pub mod packages;
 pub mod payment_links;
+pub mod payout_fees;
+pub mod payout_reserves;
 pub mod payouts;
 pub mod performance_tiers;
+pub mod rate_limit;
+pub mod reconciliation;
+pub mod recurring_invoices;
 pub mod reference_images;
+pub mod refunds;
+pub mod repositories;
 pub mod router;
 pub mod scouting;
 pub mod services;
+pub mod storage;
 pub mod talent;
+pub mod talent_statement_exports;
 pub mod talent_statements;
+pub mod tax_documents;
 pub mod voice;
+pub mod webhooks;
@@ -1,16 +1,17 @@
 use crate::auth::AuthUser;
 use crate::config::AppState;
 use crate::license_templates::LicenseTemplate;
+use crate::repositories::fetch;
 use crate::services::docuseal::DocuSealClient;
+use crate::webhooks::docuseal::{license_transition, DocuSealWebhookEvent, LicenseSigning, Stage};
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
-use postgrest::Postgrest;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tracing::{error, info};
+use tracing::info;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LicenseSubmission {
@@ -104,6 +105,45 @@ fn is_completed_status(s: &str) -> bool {
     v == "completed" || v == "signed"
 }
 
+/// Records a status change on a submission made by `user`.
+async fn audit_status_change(
+    state: &AppState,
+    user: &AuthUser,
+    id: &str,
+    title: &str,
+    before: &impl Serialize,
+    after: &impl Serialize,
+) {
+    crate::audit::record(
+        state,
+        crate::audit::AuditEvent::new(
+            &user.id,
+            "license_submission.status_changed",
+            "license_submissions",
+            id,
+        )
+        .actor(user)
+        .title(title)
+        .change(before, after),
+    )
+    .await;
+}
+
+/// The submission's current status, for auditing handlers that only write.
+async fn current_status(state: &AppState, agency_id: &str, id: &str) -> Option<serde_json::Value> {
+    let rows: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from("license_submissions")
+            .select("status")
+            .eq("id", id)
+            .eq("agency_id", agency_id),
+    )
+    .await
+    .ok()?;
+    rows.into_iter().next()
+}
+
 /// POST /api/license-submissions/draft - Create a draft template for a deal
 pub async fn create_draft(
     State(state): State<AppState>,
@@ -492,7 +532,7 @@ pub async fn finalize(
     Path(id): Path<String>,
     Json(req): Json<FinalizeSubmissionRequest>,
 ) -> Result<Json<LicenseSubmission>, (StatusCode, String)> {
-    let agency_id = auth_user.id;
+    let agency_id = auth_user.id.clone();
 
     // 1. Fetch the draft submission
     let sub_resp = state
@@ -909,6 +949,15 @@ pub async fn finalize(
         StatusCode::INTERNAL_SERVER_ERROR,
         "Failed to update submission (empty response)".to_string(),
     ))?;
+    audit_status_change(
+        &state,
+        &auth_user,
+        &id,
+        "License submission sent",
+        &submission_data,
+        &submission,
+    )
+    .await;
 
     // 6. Create a linked licensing_request — single request for all talents.
     // Resolve the list of talent IDs to use:
@@ -1201,7 +1250,7 @@ pub async fn resend(
     Path(id): Path<String>,
     req_payload: Option<Json<CreateSubmissionRequest>>,
 ) -> Result<Json<LicenseSubmission>, (StatusCode, String)> {
-    let agency_id = auth_user.id;
+    let agency_id = auth_user.id.clone();
 
     // 0. Fetch existing submission to get data (for archiving AND for resending if payload missing)
     let existing_sub_resp = state
@@ -1304,6 +1353,15 @@ pub async fn resend(
         .execute()
         .await
         .ok();
+    audit_status_change(
+        &state,
+        &auth_user,
+        &id,
+        "License submission archived for resend",
+        &existing_data,
+        &update_data,
+    )
+    .await;
 
     // 3. Create new submission record in draft status first to reuse finalize logic if needed,
     // or just perform full submission here.
@@ -1357,7 +1415,8 @@ pub async fn archive(
     auth_user: AuthUser,
     Path(id): Path<String>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    let agency_id = auth_user.id;
+    let agency_id = auth_user.id.clone();
+    let before = current_status(&state, &agency_id, &id).await;
 
     let update_data = json!({ "status": "archived" });
 
@@ -1382,6 +1441,17 @@ pub async fn archive(
     }
 
     tracing::info!(submission_id = %id, "Submission archived");
+    if let Some(before) = before {
+        audit_status_change(
+            &state,
+            &auth_user,
+            &id,
+            "License submission archived",
+            &before,
+            &update_data,
+        )
+        .await;
+    }
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -1392,7 +1462,8 @@ pub async fn recover(
     auth_user: AuthUser,
     Path(id): Path<String>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    let agency_id = auth_user.id;
+    let agency_id = auth_user.id.clone();
+    let before = current_status(&state, &agency_id, &id).await;
 
     // We recover to 'sent' if it was sent, or 'opened' etc.
     // For simplicity, let's look up the previous status or just set to 'sent'
@@ -1418,6 +1489,17 @@ pub async fn recover(
             "Failed to recover".to_string(),
         ));
     }
+    if let Some(before) = before {
+        audit_status_change(
+            &state,
+            &auth_user,
+            &id,
+            "License submission recovered",
+            &before,
+            &update_data,
+        )
+        .await;
+    }
 
     Ok(StatusCode::OK)
 }
@@ -1428,7 +1510,7 @@ pub async fn sync_status(
     auth_user: AuthUser,
     Path(id): Path<String>,
 ) -> Result<Json<LicenseSubmission>, (StatusCode, String)> {
-    let agency_id = auth_user.id;
+    let agency_id = auth_user.id.clone();
 
     let sub_resp = state
         .pg
@@ -1488,7 +1570,10 @@ pub async fn sync_status(
         update.insert("declined_at".to_string(), json!(now.clone()));
     } else if ds_status == "completed" {
         update.insert("status".to_string(), json!("completed"));
-        update.insert("signed_at".to_string(), json!(now.clone()));
+        // A repeated sync keeps the original signing time.
+        if row["signed_at"].is_null() {
+            update.insert("signed_at".to_string(), json!(now.clone()));
+        }
         if let Some(doc) = ds_sub.documents.first() {
             update.insert("signed_document_url".to_string(), json!(doc.url.clone()));
         }
@@ -1534,6 +1619,20 @@ pub async fn sync_status(
         StatusCode::INTERNAL_SERVER_ERROR,
         "Updated submission missing".to_string(),
     ))?;
+    if row.get("status") != Some(&json!(out.status)) {
+        audit_status_change(
+            &state,
+            &auth_user,
+            &id,
+            "License submission status synced",
+            &row,
+            &out,
+        )
+        .await;
+    }
+    if ds_status == "completed" {
+        crate::payout_reserves::contract_signed(&state, &id).await?;
+    }
 
     Ok(Json(out))
 }
@@ -1995,182 +2094,90 @@ pub async fn create_and_send(
     }))
 }
 
-#[derive(Debug, Deserialize)]
-pub struct DocuSealWebhookEvent {
-    pub event_type: String,
-    pub timestamp: String,
-    pub data: serde_json::Value,
-}
+/// Applies a DocuSeal event to the license submission signed under
+/// `submission_id`. Returns `false` when no submission uses it.
+pub(crate) async fn apply_docuseal_event(
+    state: &AppState,
+    event: &DocuSealWebhookEvent,
+    stage: Stage,
+    submission_id: i64,
+) -> Result<bool, String> {
+    let subs: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from("license_submissions")
+            .select("id,requires_agency_signature,agency_submitter_id,agency_signed_at,signed_at")
+            .eq("docuseal_submission_id", submission_id.to_string())
+            .limit(1),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let Some(sub) = subs.first() else {
+        return Ok(false);
+    };
+    let sub_id = sub["id"].as_str().unwrap_or_default();
 
-/// POST /api/webhooks/license-contract
-pub async fn handle_webhook(
-    State(state): State<AppState>,
-    Json(payload): Json<DocuSealWebhookEvent>,
-) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    let signing = LicenseSigning {
+        requires_agency_signature: sub["requires_agency_signature"].as_bool().unwrap_or(false),
+        agency_signed: !sub["agency_signed_at"].is_null(),
+    };
+    let agency_signer = event.is_agency_signer(sub["agency_submitter_id"].as_i64());
+    let transition = license_transition(stage, signing, agency_signer);
     info!(
-        event_type = %payload.event_type,
-        "Received DocuSeal licensing webhook"
+        submission_id,
+        license_submission_id = sub_id,
+        status = transition.status,
+        "Processing license submission update"
     );
 
-    let tracked_event = matches!(
-        payload.event_type.as_str(),
-        "submission.started"
-            | "submission.opened"
-            | "submission.viewed"
-            | "form.started"
-            | "form.viewed"
-            | "form.completed"
-            | "submission.completed"
-            | "submission.declined"
-            | "form.declined"
-    );
-    if !tracked_event {
-        return Ok(axum::http::StatusCode::OK);
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut update = json!({ "status": transition.status });
+    if transition.records_agency_signature {
+        update["agency_signed_at"] = json!(now);
     }
-
-    let submission_id = payload.data["submission_id"]
-        .as_i64()
-        .or_else(|| payload.data["id"].as_i64())
-        .ok_or_else(|| {
-            error!("Missing submission id in licensing webhook payload");
-            (
-                axum::http::StatusCode::BAD_REQUEST,
-                "Missing submission id".to_string(),
-            )
-        })?;
-
-    let pg = Postgrest::new(format!("{}/rest/v1", state.supabase_url))
-        .insert_header("apikey", &state.supabase_service_key)
-        .insert_header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        );
-
-    // Update license_submissions
-    let sub_response = pg
-        .from("license_submissions")
-        .select("id,requires_agency_signature,agency_submitter_id,agency_signed_at")
-        .eq("docuseal_submission_id", submission_id.to_string())
-        .single()
-        .execute()
-        .await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    if sub_response.status().is_success() {
-        let sub_text = sub_response
-            .text()
-            .await
-            .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-        let sub: serde_json::Value = serde_json::from_str(&sub_text)
-            .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-        if let Some(sub_id) = sub["id"].as_str() {
-            let requires_agency_signature =
-                sub["requires_agency_signature"].as_bool().unwrap_or(false);
-            let agency_submitter_id = sub["agency_submitter_id"].as_i64();
-            let agency_already_signed = !sub["agency_signed_at"].is_null();
-
-            let submitter_id = payload.data["submitter_id"].as_i64().or_else(|| {
-                payload
-                    .data
-                    .get("submitter")
-                    .and_then(|s| s.get("id"))
-                    .and_then(|v| v.as_i64())
-            });
-            let submitter_role = payload
-                .data
-                .get("submitter")
-                .and_then(|s| s.get("role"))
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_lowercase())
-                .or_else(|| {
-                    payload
-                        .data
-                        .get("role")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_lowercase())
-                });
-            let is_agency_signer = submitter_role
-                .as_ref()
-                .map(|r| r == "agency" || r == "first party")
-                .unwrap_or(false)
-                || (agency_submitter_id.is_some() && submitter_id == agency_submitter_id);
-
-            let mut update_map = serde_json::Map::new();
-
-            match payload.event_type.as_str() {
-                "form.completed" => {
-                    if requires_agency_signature && (is_agency_signer || !agency_already_signed) {
-                        update_map.insert(
-                            "agency_signed_at".to_string(),
-                            json!(chrono::Utc::now().to_rfc3339()),
-                        );
-                        update_map.insert("status".to_string(), json!("client_pending"));
-                    } else if requires_agency_signature && !agency_already_signed {
-                        update_map.insert("status".to_string(), json!("agency_pending"));
-                    } else {
-                        update_map.insert("status".to_string(), json!("opened"));
-                    }
-                }
-                "submission.completed" => {
-                    update_map.insert("status".to_string(), json!("completed"));
-                    update_map.insert(
-                        "signed_at".to_string(),
-                        json!(chrono::Utc::now().to_rfc3339()),
-                    );
-                    if let Some(url) = payload.data["documents"]
-                        .as_array()
-                        .and_then(|docs| docs.first())
-                        .and_then(|doc| doc["url"].as_str())
-                    {
-                        update_map.insert("signed_document_url".to_string(), json!(url));
-                    }
-                }
-                "submission.declined" | "form.declined" => {
-                    update_map.insert("status".to_string(), json!("declined"));
-                    update_map.insert(
-                        "declined_at".to_string(),
-                        json!(chrono::Utc::now().to_rfc3339()),
-                    );
-                    if let Some(reason) =
-                        payload.data.get("decline_reason").and_then(|v| v.as_str())
-                    {
-                        update_map.insert("decline_reason".to_string(), json!(reason));
-                    }
-                }
-                _ => {
-                    if requires_agency_signature && !agency_already_signed {
-                        update_map.insert("status".to_string(), json!("agency_pending"));
-                    } else if requires_agency_signature {
-                        update_map.insert("status".to_string(), json!("client_pending"));
-                    } else {
-                        update_map.insert("status".to_string(), json!("opened"));
-                    }
-                }
+    match stage {
+        Stage::Completed => {
+            // A replayed event keeps the original signing time.
+            if sub["signed_at"].is_null() {
+                update["signed_at"] = json!(now);
             }
-
-            if !update_map.is_empty() {
-                let _ = pg
-                    .from("license_submissions")
-                    .update(serde_json::Value::Object(update_map).to_string())
-                    .eq("id", sub_id)
-                    .execute()
-                    .await;
+            if let Some(url) = event.document_url() {
+                update["signed_document_url"] = json!(url);
             }
-
-            if matches!(
-                payload.event_type.as_str(),
-                "submission.declined" | "form.declined"
-            ) {
-                let _ = pg
-                    .from("licensing_requests")
-                    .update(json!({ "status": "rejected" }).to_string())
-                    .eq("submission_id", sub_id)
-                    .execute()
-                    .await;
+        }
+        Stage::Declined => {
+            update["declined_at"] = json!(now);
+            if let Some(reason) = event.decline_reason() {
+                update["decline_reason"] = json!(reason);
             }
         }
+        Stage::Opened | Stage::FormCompleted => {}
     }
+    let _: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from("license_submissions")
+            .eq("id", sub_id)
+            .update(update.to_string()),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
 
-    Ok(axum::http::StatusCode::OK)
+    if stage == Stage::Completed {
+        crate::payout_reserves::contract_signed(state, sub_id)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    if stage == Stage::Declined {
+        let _: Vec<serde_json::Value> = fetch(
+            state
+                .pg
+                .from("licensing_requests")
+                .eq("submission_id", sub_id)
+                .update(json!({ "status": "rejected" }).to_string()),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(true)
 }
@@ -1,4 +1,7 @@
-use crate::{auth::AuthUser, config::AppState, errors::sanitize_db_error};
+use crate::{
+    auth::AuthUser, config::AppState, errors::sanitize_db_error,
+    repositories::LicensingRequestStatus,
+};
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
@@ -513,37 +516,53 @@ pub async fn update_status_bulk(
         ));
     }
 
-    let status = payload.status.trim().to_lowercase();
-    if status != "approved"
-        && status != "rejected"
-        && status != "pending"
-        && status != "negotiating"
-        && status != "declined"
-    {
-        return Err((StatusCode::BAD_REQUEST, "Invalid status".to_string()));
-    }
-
-    let decided_at = if status == "pending" {
-        serde_json::Value::Null
-    } else {
-        json!(Utc::now().to_rfc3339())
+    let status = match LicensingRequestStatus::parse(&payload.status) {
+        Some(
+            s @ (LicensingRequestStatus::Approved
+            | LicensingRequestStatus::Rejected
+            | LicensingRequestStatus::Pending
+            | LicensingRequestStatus::Negotiating
+            | LicensingRequestStatus::Declined),
+        ) => s,
+        _ => return Err((StatusCode::BAD_REQUEST, "Invalid status".to_string())),
     };
 
-    let mut v = json!({
-        "status": status,
-        "decided_at": decided_at,
-        "notes": payload.notes,
-        "negotiation_reason": payload.notes, // Using notes as the reason for now
-    });
-
-    if let serde_json::Value::Object(ref mut map) = v {
-        let null_keys: Vec<String> = map
-            .iter()
-            .filter_map(|(k, val)| if val.is_null() { Some(k.clone()) } else { None })
-            .collect();
-        for k in null_keys {
-            map.remove(&k);
-        }
+    let before = state
+        .repos
+        .licensing_requests
+        .list_by_ids(&user.id, &payload.licensing_request_ids)
+        .await?;
+    let updated = state
+        .repos
+        .licensing_requests
+        .update_status(
+            &user.id,
+            &payload.licensing_request_ids,
+            status,
+            payload.notes.as_deref(),
+        )
+        .await?;
+    for after in &updated {
+        let Some(prev) = before.iter().find(|r| r.id == after.id) else {
+            continue;
+        };
+        crate::audit::record(
+            &state,
+            crate::audit::AuditEvent::new(
+                &user.id,
+                "licensing_request.status_changed",
+                "licensing_requests",
+                &after.id,
+            )
+            .actor(&user)
+            .title(format!(
+                "License request {} → {}",
+                prev.status.as_str(),
+                after.status.as_str()
+            ))
+            .change(prev, after),
+        )
+        .await;
     }
 
     let ids: Vec<&str> = payload
@@ -552,23 +571,8 @@ pub async fn update_status_bulk(
         .map(|s| s.as_str())
         .collect();
 
-    let resp = state
-        .pg
-        .from("licensing_requests")
-        .eq("agency_id", &user.id)
-        .in_("id", ids.clone())
-        .update(v.to_string())
-        .execute()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    if !resp.status().is_success() {
-        let err = resp.text().await.unwrap_or_default();
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, err));
-    }
-
     // If counter offer (negotiating), send email to brand
-    if status == "negotiating" {
+    if status == LicensingRequestStatus::Negotiating {
         tracing::info!("Status is negotiating, checking for notes...");
         if let Some(reason) = payload.notes.as_ref() {
             tracing::info!("Notes found: {}, sending counter offer emails", reason);
@@ -639,11 +643,13 @@ pub async fn update_status_bulk(
                                         "Hello {},\n\nThe agency has sent a counter offer for your licensing request.\n\nReason/Notes: {}\n\nPlease reply to this email or contact the agency to review and respond.\n\nBest regards,\nLikelee Team",
                                         target_name, reason
                                     );
-                                    if let Err(e) = crate::email::send_plain_email(
-                                        &state, &email, &subject, &body,
-                                    ) {
+                                    let outgoing =
+                                        crate::email::OutgoingEmail::new(&email, &subject, &body)
+                                            .category("licensing_counter_offer")
+                                            .agency(&user.id);
+                                    if let Err(e) = crate::email::enqueue(&state, outgoing).await {
                                         tracing::error!(
-                                            "Failed to send counter offer email to {}: {:?}",
+                                            "Failed to queue counter offer email to {}: {}",
                                             email,
                                             e
                                         );
@@ -1887,7 +1893,7 @@ pub async fn send_payment_link(
     );
 
     // 9. Create Stripe product + price + payment link
-    let stripe_client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+    let stripe_client = state.stripe_client();
     let currency = "USD";
     let expires_in_hours = 168i64; // 7 days
 
@@ -2058,13 +2064,17 @@ pub async fn send_payment_link(
                 stripe_payment_link_url,
                 expires_at.format("%Y-%m-%d %H:%M UTC"),
             );
-            match crate::email::send_plain_email(&state, email, &subject, &body) {
+            let outgoing = crate::email::OutgoingEmail::new(email, &subject, &body)
+                .category("payment_link")
+                .agency(&user.id)
+                .reference("licensing_request", &id);
+            match crate::email::enqueue(&state, outgoing).await {
                 Ok(_) => {
                     email_sent = true;
-                    info!(licensing_request_id = %id, email = %email, "Payment link email sent");
+                    info!(licensing_request_id = %id, email = %email, "Payment link email queued");
                 }
                 Err(e) => {
-                    warn!(licensing_request_id = %id, email = %email, error = ?e, "Failed to send payment link email");
+                    warn!(licensing_request_id = %id, email = %email, error = %e, "Failed to queue payment link email");
                 }
             }
         }
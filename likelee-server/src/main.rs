@@ -88,7 +88,33 @@ async fn main() {
         None
     };
 
+    let storage: std::sync::Arc<dyn likelee_server::storage::StorageBackend> =
+        if cfg.storage_backend.eq_ignore_ascii_case("local") {
+            info!(dir = %cfg.storage_local_dir, "storage: local filesystem");
+            std::sync::Arc::new(likelee_server::storage::LocalStorage::new(
+                &cfg.storage_local_dir,
+                &cfg.storage_public_base_url,
+                &cfg.supabase_jwt_secret,
+                vec![cfg.supabase_bucket_public.clone()],
+            ))
+        } else {
+            std::sync::Arc::new(likelee_server::storage::SupabaseStorage::new(
+                &cfg.supabase_url,
+                &cfg.supabase_service_key,
+            ))
+        };
+
+    let rate_limiter = likelee_server::rate_limit::RateLimiter::from_config(&cfg, pg.clone())
+        .expect("invalid rate limit configuration");
+    info!(
+        enabled = cfg.rate_limit_enabled,
+        store = %cfg.rate_limit_store,
+        trust_proxy = cfg.rate_limit_trust_proxy,
+        "rate limiting"
+    );
+
     let state = likelee_server::config::AppState {
+        repos: likelee_server::repositories::Repositories::postgrest(pg.clone()),
         pg,
         veriff: likelee_server::config::VeriffConfig {
             base_url: cfg.veriff_base_url,
@@ -105,8 +131,13 @@ async fn main() {
         supabase_jwt_secret: cfg.supabase_jwt_secret.clone(),
         supabase_bucket_public: cfg.supabase_bucket_public.clone(),
         supabase_bucket_private: cfg.supabase_bucket_private.clone(),
+        supabase_bucket_temp: cfg.supabase_bucket_temp.clone(),
+        storage,
+        rate_limiter,
         elevenlabs_api_key: cfg.elevenlabs_api_key.clone(),
+        elevenlabs_base_url: cfg.elevenlabs_base_url.clone(),
         stripe_secret_key: cfg.stripe_secret_key.clone(),
+        stripe_api_base: cfg.stripe_api_base.clone(),
         stripe_client_id: cfg.stripe_client_id.clone(),
         stripe_return_url: cfg.stripe_return_url.clone(),
         stripe_refresh_url: cfg.stripe_refresh_url.clone(),
@@ -137,11 +168,14 @@ async fn main() {
 
         agency_payout_scheduler_enabled: cfg.agency_payout_scheduler_enabled,
         agency_payout_scheduler_interval_secs: cfg.agency_payout_scheduler_interval_secs,
+        jobs_enabled: cfg.jobs_enabled,
+        jobs_poll_interval_secs: cfg.jobs_poll_interval_secs,
 
         smtp_host: cfg.smtp_host.clone(),
         smtp_port: cfg.smtp_port,
         smtp_user: cfg.smtp_user.clone(),
         smtp_password: cfg.smtp_password.clone(),
+        smtp_tls: cfg.smtp_tls,
         email_from: cfg.email_from.clone(),
         email_contact_to: cfg.email_contact_to.clone(),
 
@@ -157,6 +191,7 @@ async fn main() {
         docuseal_api_url: cfg.docuseal_api_url.clone(),
         docuseal_app_url: cfg.docuseal_app_url.clone(),
         docuseal_webhook_url: cfg.docuseal_webhook_url.clone(),
+        docuseal_webhook_secret: cfg.docuseal_webhook_secret.clone(),
         docuseal_user_email: cfg.docuseal_user_email.clone(),
         docuseal_master_template_id: cfg.docuseal_master_template_id.clone(),
         docuseal_master_template_name: cfg.docuseal_master_template_name.clone(),
@@ -167,15 +202,18 @@ async fn main() {
     };
 
     // Start background jobs
-    tokio::spawn(likelee_server::jobs::start_payment_reminders(state.clone()));
-    tokio::spawn(likelee_server::jobs::start_agency_payout_scheduler(
-        state.clone(),
-    ));
+    tokio::spawn(likelee_server::jobs::start(state.clone()));
 
     let app = likelee_server::router::build_router(state);
 
     let listener = tokio::net::TcpListener::bind(("0.0.0.0", port))
         .await
         .expect("bind port");
-    axum::serve(listener, app).await.expect("server run");
+    // Peer addresses feed the rate limiter when no trusted proxy header is used.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .expect("server run");
 }
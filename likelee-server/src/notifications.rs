@@ -385,11 +385,16 @@ pub async fn booking_created_email(
             .await;
     }
 
-    let send_res =
-        email::send_plain_text_email(&state, &dest, &subject, &body, agency_email.as_deref());
+    let outgoing = email::OutgoingEmail::new(&dest, &subject, &body)
+        .from_name(agency_email.as_deref())
+        .category("booking_created")
+        .agency(&user.id)
+        .talent(talent_id_opt)
+        .reference("booking", &payload.booking_id);
+    let send_res = email::enqueue(&state, outgoing).await;
 
-    // Log notification regardless of SMTP result (status success/error)
-    let status_ok = send_res.is_ok();
+    // Log notification regardless of the outcome (status queued/error)
+    let email_id = send_res.as_ref().ok().map(|m| m.id.clone());
     let insert = json!({
         "agency_user_id": user.id,
         "booking_id": payload.booking_id,
@@ -398,7 +403,10 @@ pub async fn booking_created_email(
         "to_email": dest,
         "subject": subject,
         "message": body,
-        "meta_json": json!({"smtp_status": if status_ok {"ok"} else {"error"}}),
+        "meta_json": json!({
+            "smtp_status": if email_id.is_some() {"queued"} else {"error"},
+            "email_id": email_id,
+        }),
     });
     let _ = state
         .pg
@@ -408,10 +416,7 @@ pub async fn booking_created_email(
         .await;
 
     match send_res {
-        Ok(_) => Ok(Json(json!({"status":"ok"}))),
-        Err((code, msg)) => Err((
-            StatusCode::BAD_GATEWAY,
-            format!("email_send_failed upstream_status={} message={}", code, msg),
-        )),
+        Ok(msg) => Ok(Json(json!({"status":"ok", "email_id": msg.id}))),
+        Err(e) => Err(e.into()),
     }
 }
@@ -387,23 +387,16 @@ pub async fn create_package(
                 );
 
                 tracing::info!("Sending package email: To={}, Subject={}, FrontendURL={}, Agency={}, Client={}", client_email, subject, state.frontend_url, agency_name, client_name);
-                match crate::email::send_email_core(
-                    &state,
-                    client_email,
-                    &subject,
-                    &body,
-                    true,
-                    None,
-                )
-                .await
-                {
-                    Ok(_) => tracing::info!("Package email SENT SUCCESSFULLY to {}", client_email),
-                    Err((code, msg)) => tracing::error!(
-                        "FAILED TO SEND package email to {}: [{}] {}",
-                        client_email,
-                        code,
-                        msg
-                    ),
+                let outgoing = crate::email::OutgoingEmail::new(client_email, &subject, &body)
+                    .html(true)
+                    .category("package")
+                    .agency(&user.id)
+                    .reference("package", package_id);
+                match crate::email::enqueue(&state, outgoing).await {
+                    Ok(_) => tracing::info!("Package email queued for {}", client_email),
+                    Err(e) => {
+                        tracing::error!("FAILED TO QUEUE package email to {}: {}", client_email, e)
+                    }
                 }
             } else {
                 tracing::warn!(
@@ -721,13 +714,11 @@ pub async fn create_public_package_full_assets_request(
             payload.client_email.as_deref().unwrap_or(""),
             payload.message.as_deref().unwrap_or("")
         );
-        let _ = crate::email::send_plain_text_email(
-            &state,
-            dest,
-            subject,
-            &body,
-            agency_name.as_deref(),
-        );
+        let outgoing = crate::email::OutgoingEmail::new(dest, subject, &body)
+            .from_name(agency_name.as_deref())
+            .category("package_assets_request")
+            .agency(&agency_id);
+        let _ = crate::email::enqueue(&state, outgoing).await;
     }
 
     Ok(Json(serde_json::json!({ "ok": true })))
@@ -1015,10 +1006,7 @@ pub async fn get_public_package(
                                 asset.get("storage_bucket").and_then(|v| v.as_str()),
                                 asset.get("storage_path").and_then(|v| v.as_str()),
                             ) {
-                                let constructed_url = format!(
-                                    "{}/storage/v1/object/public/{}/{}",
-                                    state.supabase_url, bucket, path
-                                );
+                                let constructed_url = state.storage.public_url(bucket, path);
                                 if let Some(obj) = asset.as_object_mut() {
                                     obj.insert(
                                         "asset_url".to_string(),
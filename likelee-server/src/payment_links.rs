@@ -798,7 +798,7 @@ pub async fn generate_payment_link(
     }
 
     // Create Stripe Payment Link
-    let stripe_client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+    let stripe_client = state.stripe_client();
 
     // Create a product for this payment
     let product_name = format!(
@@ -957,6 +957,19 @@ pub async fn generate_payment_link(
         .and_then(|r| r.get("id").and_then(|v| v.as_str()))
         .unwrap_or("")
         .to_string();
+    crate::audit::record(
+        &state,
+        crate::audit::AuditEvent::new(
+            &user.id,
+            "payment_link.created",
+            "agency_payment_links",
+            &our_payment_link_id,
+        )
+        .actor(&user)
+        .title("Payment link created")
+        .change(&(), &inserted.first()),
+    )
+    .await;
 
     info!(
         agency_id = %user.id,
@@ -1081,9 +1094,13 @@ pub async fn send_payment_link_email(
         agency_name
     );
 
-    // Send email
-    match crate::email::send_plain_email(&state, client_email, &subject, &body) {
-        Ok(_) => {
+    // Queue email
+    let outgoing = crate::email::OutgoingEmail::new(client_email, &subject, &body)
+        .category("payment_link")
+        .agency(&user.id)
+        .reference("payment_link", &payload.payment_link_id);
+    match crate::email::enqueue(&state, outgoing).await {
+        Ok(queued) => {
             // Update email_sent_at and increment count
             let update = json!({
                 "email_sent_at": Utc::now().to_rfc3339(),
@@ -1104,12 +1121,13 @@ pub async fn send_payment_link_email(
             info!(
                 payment_link_id = %payload.payment_link_id,
                 email = %client_email,
-                "Payment link email sent"
+                "Payment link email queued"
             );
 
             Ok(Json(json!({
                 "ok": true,
                 "email_sent": true,
+                "email_id": queued.id,
                 "recipient": client_email
             })))
         }
@@ -1117,13 +1135,10 @@ pub async fn send_payment_link_email(
             error!(
                 payment_link_id = %payload.payment_link_id,
                 email = %client_email,
-                error = ?e,
-                "Failed to send payment link email"
+                error = %e,
+                "Failed to queue payment link email"
             );
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to send email: {:?}", e),
-            ))
+            Err(e.into())
         }
     }
 }
@@ -1195,7 +1210,7 @@ pub async fn cancel_payment_link(
     let pl_resp = state
         .pg
         .from("agency_payment_links")
-        .select("stripe_payment_link_id,status")
+        .select("stripe_payment_link_id,status,total_amount_cents,currency")
         .eq("id", &id)
         .eq("agency_id", &user.id)
         .single()
@@ -1230,7 +1245,7 @@ pub async fn cancel_payment_link(
         .unwrap_or("");
 
     if !stripe_payment_link_id.is_empty() {
-        let stripe_client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+        let stripe_client = state.stripe_client();
 
         // Stripe doesn't have a direct "deactivate" for payment links, but we can update
         // to make it inactive by setting active=false
@@ -1272,6 +1287,21 @@ pub async fn cancel_payment_link(
         agency_id = %user.id,
         "Payment link cancelled"
     );
+    let mut cancelled = pl_row.clone();
+    cancelled["status"] = json!("cancelled");
+    crate::audit::record(
+        &state,
+        crate::audit::AuditEvent::new(
+            &user.id,
+            "payment_link.cancelled",
+            "agency_payment_links",
+            &id,
+        )
+        .actor(&user)
+        .title("Payment link cancelled")
+        .change(&pl_row, &cancelled),
+    )
+    .await;
 
     Ok(Json(json!({"ok": true, "status": "cancelled"})))
 }
@@ -1732,7 +1762,7 @@ async fn execute_creator_stripe_transfer(
     amount_cents: i64,
     currency: &str,
 ) -> Result<String, (StatusCode, String)> {
-    let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+    let client = state.stripe_client();
 
     let currency_enum = match stripe_sdk::Currency::from_str(&currency.to_lowercase()) {
         Ok(c) => c,
@@ -0,0 +1,486 @@
+//! Platform fee schedules for agency payouts.
+//!
+//! An agency's schedule is a list of rules, one per payout method and
+//! optionally per currency: a flat amount plus a percentage of the payout,
+//! kept between a minimum and a maximum. A rule for the payout's currency
+//! wins over the method's rule without a currency; with no matching rule the
+//! platform rate (`payout_fee_bps`) applies.
+//!
+//! Schedules are versioned: ops save a new version rather than editing one,
+//! and every payout request stores the fee it was charged with the schedule
+//! version it came from. Version 0 is the platform rate.
+
+use crate::{
+    audit::{self, AuditEvent},
+    auth::{AuthUser, RoleGuard},
+    config::AppState,
+    errors::{AppError, AppResult},
+    repositories::{fetch, RepoError},
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+pub const TABLE: &str = "agency_payout_fee_schedules";
+
+/// Version reported for the platform rate, which has no stored schedule.
+pub const PLATFORM_VERSION: i32 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutMethod {
+    Standard,
+    Instant,
+}
+
+impl PayoutMethod {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "standard" => Some(PayoutMethod::Standard),
+            "instant" => Some(PayoutMethod::Instant),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PayoutMethod::Standard => "standard",
+            PayoutMethod::Instant => "instant",
+        }
+    }
+}
+
+/// How the fee on one payout is worked out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeRate {
+    #[serde(default)]
+    pub flat_cents: i64,
+    #[serde(default)]
+    pub percent_bps: i64,
+    pub min_fee_cents: Option<i64>,
+    pub max_fee_cents: Option<i64>,
+}
+
+impl FeeRate {
+    pub fn percent(bps: i64) -> Self {
+        FeeRate {
+            percent_bps: bps,
+            ..Default::default()
+        }
+    }
+
+    /// The flat amount plus the percentage, rounded up to the next cent, then
+    /// held within the minimum and maximum.
+    pub fn fee_cents(&self, amount_cents: i64) -> i64 {
+        let percent = (amount_cents * self.percent_bps + 9_999) / 10_000;
+        let mut fee = self.flat_cents + percent;
+        if let Some(min) = self.min_fee_cents {
+            fee = fee.max(min);
+        }
+        if let Some(max) = self.max_fee_cents {
+            fee = fee.min(max);
+        }
+        fee.max(0)
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        let negative = [
+            Some(self.flat_cents),
+            self.min_fee_cents,
+            self.max_fee_cents,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|c| c < 0);
+        if negative || !(0..=10_000).contains(&self.percent_bps) {
+            return Err(AppError::BadRequest("invalid_fee_rate".to_string()));
+        }
+        if let (Some(min), Some(max)) = (self.min_fee_cents, self.max_fee_cents) {
+            if min > max {
+                return Err(AppError::BadRequest("invalid_fee_caps".to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeRule {
+    pub payout_method: PayoutMethod,
+    /// Applies to every currency when unset.
+    pub currency: Option<String>,
+    #[serde(flatten)]
+    pub rate: FeeRate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub id: Option<String>,
+    pub agency_id: String,
+    pub version: i32,
+    pub rules: Vec<FeeRule>,
+    pub note: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl FeeSchedule {
+    /// The platform rate, for agencies without a schedule of their own.
+    pub fn platform(state: &AppState, agency_id: &str) -> Self {
+        let rate = FeeRate::percent(state.payout_fee_bps as i64);
+        FeeSchedule {
+            id: None,
+            agency_id: agency_id.to_string(),
+            version: PLATFORM_VERSION,
+            rules: vec![FeeRule {
+                payout_method: PayoutMethod::Instant,
+                currency: None,
+                rate,
+            }],
+            note: None,
+            created_by: None,
+            created_at: None,
+        }
+    }
+
+    /// The rule for `method` in `currency`, preferring one for that currency.
+    pub fn rate_for(&self, method: PayoutMethod, currency: &str) -> Option<&FeeRate> {
+        let for_method = || self.rules.iter().filter(|r| r.payout_method == method);
+        for_method()
+            .find(|r| {
+                r.currency
+                    .as_deref()
+                    .is_some_and(|c| c.eq_ignore_ascii_case(currency))
+            })
+            .or_else(|| for_method().find(|r| r.currency.is_none()))
+            .map(|r| &r.rate)
+    }
+}
+
+/// Gross, fee and net of a payout before it is requested.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeQuote {
+    pub gross_cents: i64,
+    pub fee_cents: i64,
+    pub net_cents: i64,
+    pub currency: String,
+    pub payout_method: PayoutMethod,
+    pub rate: FeeRate,
+    pub fee_schedule_id: Option<String>,
+    pub fee_schedule_version: i32,
+}
+
+impl FeeQuote {
+    /// Columns stored on the payout request.
+    pub fn columns(&self) -> serde_json::Value {
+        json!({
+            "fee_cents": self.fee_cents,
+            "net_cents": self.net_cents,
+            "fee_schedule_id": self.fee_schedule_id,
+            "fee_schedule_version": self.fee_schedule_version,
+        })
+    }
+}
+
+pub fn quote_with(
+    schedule: &FeeSchedule,
+    platform_bps: u32,
+    amount_cents: i64,
+    currency: &str,
+    method: PayoutMethod,
+) -> FeeQuote {
+    let (rate, id, version) = match schedule.rate_for(method, currency) {
+        Some(rate) => (rate.clone(), schedule.id.clone(), schedule.version),
+        None => (
+            FeeRate::percent(platform_bps as i64),
+            None,
+            PLATFORM_VERSION,
+        ),
+    };
+    let fee_cents = rate.fee_cents(amount_cents);
+    FeeQuote {
+        gross_cents: amount_cents,
+        fee_cents,
+        net_cents: amount_cents - fee_cents,
+        currency: currency.to_string(),
+        payout_method: method,
+        rate,
+        fee_schedule_id: id,
+        fee_schedule_version: version,
+    }
+}
+
+/// The agency's latest schedule version, if it has one.
+pub async fn latest(state: &AppState, agency_id: &str) -> Result<Option<FeeSchedule>, RepoError> {
+    let rows: Vec<FeeSchedule> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .select("*")
+            .eq("agency_id", agency_id)
+            .order("version.desc")
+            .limit(1),
+    )
+    .await?;
+    Ok(rows.into_iter().next())
+}
+
+/// The schedule that applies to the agency's payouts now.
+pub async fn effective(state: &AppState, agency_id: &str) -> Result<FeeSchedule, RepoError> {
+    Ok(latest(state, agency_id)
+        .await?
+        .unwrap_or_else(|| FeeSchedule::platform(state, agency_id)))
+}
+
+pub async fn quote(
+    state: &AppState,
+    agency_id: &str,
+    amount_cents: i64,
+    currency: &str,
+    method: PayoutMethod,
+) -> Result<FeeQuote, RepoError> {
+    let schedule = effective(state, agency_id).await?;
+    Ok(quote_with(
+        &schedule,
+        state.payout_fee_bps,
+        amount_cents,
+        currency,
+        method,
+    ))
+}
+
+/// The payout method for an agency payout request, or the error code to
+/// reject it with. Payouts are instant-only for now.
+pub fn requested_method(
+    state: &AppState,
+    requested: Option<&str>,
+) -> Result<PayoutMethod, &'static str> {
+    if !state.instant_payouts_enabled {
+        return Err("instant_payouts_disabled");
+    }
+    match requested.map(PayoutMethod::parse) {
+        None | Some(Some(PayoutMethod::Instant)) => Ok(PayoutMethod::Instant),
+        Some(Some(PayoutMethod::Standard)) => Err("standard_payouts_disabled"),
+        Some(None) => Err("invalid_payout_method"),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewParams {
+    pub amount_cents: i64,
+    pub currency: Option<String>,
+    pub payout_method: Option<String>,
+}
+
+/// GET /api/agency/payouts/preview: what a payout of `amount_cents` would
+/// cost, checked the same way as the request itself.
+pub async fn preview(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<PreviewParams>,
+) -> AppResult<Json<FeeQuote>> {
+    RoleGuard::new(vec!["agency"]).check(&user.role)?;
+    if !state.payouts_enabled {
+        return Err(AppError::BadRequest("payouts_disabled".to_string()));
+    }
+    if params.amount_cents <= 0 {
+        return Err(AppError::BadRequest("invalid_amount".to_string()));
+    }
+    let currency = params
+        .currency
+        .unwrap_or_else(|| state.payout_currency.clone())
+        .to_uppercase();
+    if !state.payout_allowed_currencies.contains(&currency) {
+        return Err(AppError::BadRequest("unsupported_currency".to_string()));
+    }
+    let method = requested_method(&state, params.payout_method.as_deref())
+        .map_err(|code| AppError::BadRequest(code.to_string()))?;
+    Ok(Json(
+        quote(&state, &user.id, params.amount_cents, &currency, method).await?,
+    ))
+}
+
+/// GET /api/agency/payouts/fee-schedule: the schedule the agency's payouts
+/// are charged with.
+pub async fn get_own(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> AppResult<Json<FeeSchedule>> {
+    RoleGuard::new(vec!["agency"]).check(&user.role)?;
+    Ok(Json(effective(&state, &user.id).await?))
+}
+
+/// GET /api/admin/payout-fee-schedules/:agency_id: every version, newest
+/// first.
+pub async fn admin_history(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(agency_id): Path<String>,
+) -> AppResult<Json<Vec<FeeSchedule>>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let rows: Vec<FeeSchedule> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .select("*")
+            .eq("agency_id", &agency_id)
+            .order("version.desc"),
+    )
+    .await?;
+    Ok(Json(rows))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SchedulePayload {
+    pub rules: Vec<FeeRule>,
+    pub note: Option<String>,
+}
+
+/// Checks and tidies the rules of a new schedule. Only instant rules are
+/// accepted while payouts are instant-only (see [`requested_method`]).
+fn normalize_rules(state: &AppState, rules: Vec<FeeRule>) -> AppResult<Vec<FeeRule>> {
+    if rules.is_empty() {
+        return Err(AppError::BadRequest("missing_fee_rules".to_string()));
+    }
+    let mut out: Vec<FeeRule> = Vec::with_capacity(rules.len());
+    for mut rule in rules {
+        if rule.payout_method == PayoutMethod::Standard {
+            return Err(AppError::BadRequest(
+                "standard_payouts_disabled".to_string(),
+            ));
+        }
+        rule.rate.validate()?;
+        rule.currency = rule
+            .currency
+            .map(|c| c.trim().to_uppercase())
+            .filter(|c| !c.is_empty());
+        if let Some(c) = rule.currency.as_ref() {
+            if !state.payout_allowed_currencies.contains(c) {
+                return Err(AppError::BadRequest("unsupported_currency".to_string()));
+            }
+        }
+        if out
+            .iter()
+            .any(|r| r.payout_method == rule.payout_method && r.currency == rule.currency)
+        {
+            return Err(AppError::BadRequest("duplicate_fee_rule".to_string()));
+        }
+        out.push(rule);
+    }
+    Ok(out)
+}
+
+/// PUT /api/admin/payout-fee-schedules/:agency_id: saves the rules as the
+/// agency's next schedule version.
+pub async fn admin_update(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(agency_id): Path<String>,
+    Json(payload): Json<SchedulePayload>,
+) -> AppResult<Json<FeeSchedule>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let rules = normalize_rules(&state, payload.rules)?;
+    let current = latest(&state, &agency_id).await?;
+    let row = json!({
+        "agency_id": agency_id,
+        "version": current.as_ref().map_or(1, |s| s.version + 1),
+        "rules": rules,
+        "note": payload.note,
+        "created_by": user.id,
+    });
+    let inserted: Result<Vec<FeeSchedule>, RepoError> =
+        fetch(state.pg.from(TABLE).insert(row.to_string())).await;
+    let saved = match inserted {
+        Ok(rows) => rows.into_iter().next().ok_or(RepoError::NotFound)?,
+        // The unique (agency_id, version) key: someone saved a version first.
+        Err(RepoError::Db { status: 409, .. }) => {
+            return Err(AppError::Conflict(
+                "The fee schedule was changed meanwhile; reload and try again".to_string(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let before = current.unwrap_or_else(|| FeeSchedule::platform(&state, &agency_id));
+    audit::record(
+        &state,
+        AuditEvent::new(
+            &agency_id,
+            "payout_fee_schedule.updated",
+            TABLE,
+            saved.id.as_deref().unwrap_or_default(),
+        )
+        .actor(&user)
+        .title(format!(
+            "Payout fee schedule version {} saved",
+            saved.version
+        ))
+        .change(&before, &saved),
+    )
+    .await;
+    Ok(Json(saved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(method: PayoutMethod, currency: Option<&str>, rate: FeeRate) -> FeeRule {
+        FeeRule {
+            payout_method: method,
+            currency: currency.map(str::to_string),
+            rate,
+        }
+    }
+
+    #[test]
+    fn fee_is_flat_plus_percent_within_caps() {
+        let rate = FeeRate {
+            flat_cents: 25,
+            percent_bps: 150,
+            min_fee_cents: Some(100),
+            max_fee_cents: Some(1_500),
+        };
+        assert_eq!(rate.fee_cents(1_000), 100);
+        assert_eq!(rate.fee_cents(10_001), 25 + 151);
+        assert_eq!(rate.fee_cents(1_000_000), 1_500);
+        assert_eq!(FeeRate::percent(100).fee_cents(12_000), 120);
+    }
+
+    #[test]
+    fn currency_rule_wins_and_missing_rules_use_the_platform_rate() {
+        let schedule = FeeSchedule {
+            id: Some("s".to_string()),
+            agency_id: "a".to_string(),
+            version: 3,
+            rules: vec![
+                rule(PayoutMethod::Instant, None, FeeRate::percent(200)),
+                rule(
+                    PayoutMethod::Instant,
+                    Some("EUR"),
+                    FeeRate {
+                        flat_cents: 50,
+                        ..Default::default()
+                    },
+                ),
+            ],
+            note: None,
+            created_by: None,
+            created_at: None,
+        };
+        let usd = quote_with(&schedule, 100, 10_000, "USD", PayoutMethod::Instant);
+        assert_eq!((usd.fee_cents, usd.net_cents), (200, 9_800));
+        assert_eq!(usd.fee_schedule_version, 3);
+        let eur = quote_with(&schedule, 100, 10_000, "eur", PayoutMethod::Instant);
+        assert_eq!(eur.fee_cents, 50);
+        let standard = quote_with(&schedule, 100, 10_000, "USD", PayoutMethod::Standard);
+        assert_eq!(standard.fee_cents, 100);
+        assert_eq!(
+            (standard.fee_schedule_id, standard.fee_schedule_version),
+            (None, PLATFORM_VERSION)
+        );
+    }
+}
@@ -0,0 +1,1159 @@
+//! Rolling reserves and payout holds on payment link earnings.
+//!
+//! A paid payment link is normally transferred straight on to the agency and
+//! its talent. If the brand later disputes the charge, the platform has to
+//! claw that money back from connected accounts that may already be empty.
+//! A reserve policy withholds part of each share instead: `reserve_bps` of it
+//! for `hold_days`, or, with `hold_until_contract_signed`, all of it until the
+//! license contract is signed. Policies are set per agency and per brand risk
+//! level, with platform-wide rows (no agency) as the default; the most
+//! specific one wins, and without one nothing is withheld.
+//!
+//! Every withheld amount is a `payout_reserves` row. It stays in the
+//! recipient's internal balance but is reported as reserved rather than
+//! available, and refunds and disputes take their clawback out of it before
+//! reversing any transfer. The `payout_reserves` job transfers reserves that
+//! are due, except while the payment is disputed or ops hold them; ops can
+//! also release a reserve by hand.
+
+use crate::{
+    audit::{self, AuditEvent},
+    auth::{AuthUser, RoleGuard},
+    config::AppState,
+    errors::{AppError, AppResult},
+    jobs::runner::ts,
+    repositories::{fetch, RepoError},
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use tracing::{error, info, warn};
+
+pub const TABLE: &str = "payout_reserves";
+pub const POLICIES_TABLE: &str = "payout_reserve_policies";
+
+/// Longest hold a policy may set.
+const MAX_HOLD_DAYS: i64 = 180;
+
+/// Reserves released per job run.
+const RELEASE_BATCH: usize = 200;
+
+/// A release still `releasing` after this long stopped before saving its
+/// outcome; the next job run takes it over.
+const RELEASE_STALE_MINUTES: i64 = 15;
+
+/// The transfer was made but `record_stripe_transfer` failed, so the balance
+/// still holds it.
+const TRANSFER_UNRECORDED: &str = "transfer_record_failed";
+
+/// How much chargeback risk a brand carries; set by ops on the brand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskLevel {
+    Low,
+    Standard,
+    High,
+}
+
+impl RiskLevel {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "low" => Some(RiskLevel::Low),
+            "standard" => Some(RiskLevel::Standard),
+            "high" => Some(RiskLevel::High),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RiskLevel::Low => "low",
+            RiskLevel::Standard => "standard",
+            RiskLevel::High => "high",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReservePolicy {
+    pub id: Option<String>,
+    /// The platform default when unset.
+    pub agency_id: Option<String>,
+    /// Applies to brands of every risk level when unset.
+    pub brand_risk_level: Option<RiskLevel>,
+    #[serde(default)]
+    pub reserve_bps: i64,
+    #[serde(default)]
+    pub hold_days: i64,
+    #[serde(default)]
+    pub hold_until_contract_signed: bool,
+    pub note: Option<String>,
+    pub updated_by: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl ReservePolicy {
+    /// The policy for a payment to `agency_id` from a brand at `risk`: the
+    /// agency's own policies beat the platform's, and one for the risk level
+    /// beats one for every level.
+    pub fn pick<'a>(
+        policies: &'a [ReservePolicy],
+        agency_id: &str,
+        risk: RiskLevel,
+    ) -> Option<&'a ReservePolicy> {
+        policies
+            .iter()
+            .filter(|p| {
+                p.agency_id.as_deref().is_none_or(|a| a == agency_id)
+                    && p.brand_risk_level.is_none_or(|r| r == risk)
+            })
+            .max_by_key(|p| (p.agency_id.is_some(), p.brand_risk_level.is_some()))
+    }
+
+    /// What to withhold from a share of `amount_cents`: all of it while the
+    /// contract is unsigned, otherwise `reserve_bps` of it, rounded up to the
+    /// next cent, for `hold_days`.
+    pub fn hold(
+        &self,
+        amount_cents: i64,
+        contract_unsigned: bool,
+        now: DateTime<Utc>,
+    ) -> Option<Hold> {
+        if amount_cents <= 0 {
+            return None;
+        }
+        if self.hold_until_contract_signed && contract_unsigned {
+            return Some(Hold {
+                amount_cents,
+                reason: HoldReason::Contract,
+                release_at: None,
+            });
+        }
+        if self.reserve_bps <= 0 || self.hold_days <= 0 {
+            return None;
+        }
+        let held = ((amount_cents * self.reserve_bps + 9_999) / 10_000).min(amount_cents);
+        Some(Hold {
+            amount_cents: held,
+            reason: HoldReason::Rolling,
+            release_at: Some(now + Duration::days(self.hold_days)),
+        })
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        if !(0..=10_000).contains(&self.reserve_bps)
+            || !(0..=MAX_HOLD_DAYS).contains(&self.hold_days)
+        {
+            return Err(AppError::BadRequest("invalid_reserve_policy".to_string()));
+        }
+        // A percentage needs a period to be held for, and the other way round.
+        if (self.reserve_bps > 0) != (self.hold_days > 0) {
+            return Err(AppError::BadRequest("invalid_reserve_window".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldReason {
+    /// A percentage held for a fixed number of days.
+    Rolling,
+    /// The whole share, held until the license contract is signed.
+    Contract,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hold {
+    pub amount_cents: i64,
+    pub reason: HoldReason,
+    /// `None` until the contract is signed.
+    pub release_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReserveStatus {
+    Held,
+    /// Claimed by a release that is transferring it.
+    Releasing,
+    Released,
+    /// Refunds or disputes took all of it.
+    Forfeited,
+}
+
+impl ReserveStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReserveStatus::Held => "held",
+            ReserveStatus::Releasing => "releasing",
+            ReserveStatus::Released => "released",
+            ReserveStatus::Forfeited => "forfeited",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reserve {
+    pub id: String,
+    pub payment_link_id: String,
+    pub agency_id: String,
+    /// `agency` or `creator`.
+    pub recipient_type: String,
+    /// The agency or creator id.
+    pub recipient_id: String,
+    pub talent_id: Option<String>,
+    pub stripe_connect_account_id: Option<String>,
+    pub currency: String,
+    pub amount_cents: i64,
+    /// Part of the reserve taken by refunds and disputes.
+    #[serde(default)]
+    pub clawback_cents: i64,
+    pub reason: HoldReason,
+    pub release_at: Option<DateTime<Utc>>,
+    pub license_submission_id: Option<String>,
+    pub policy_id: Option<String>,
+    pub status: ReserveStatus,
+    #[serde(default)]
+    pub manual_hold: bool,
+    pub hold_note: Option<String>,
+    pub released_at: Option<DateTime<Utc>>,
+    pub stripe_transfer_id: Option<String>,
+    pub failure_reason: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Reserve {
+    /// What is left to pay out after refunds and disputes took their part.
+    pub fn payable_cents(&self) -> i64 {
+        (self.amount_cents - self.clawback_cents).max(0)
+    }
+}
+
+/// The policy that applies to one payment link's shares.
+#[derive(Debug, Clone, Default)]
+pub struct ReservePlan {
+    policy: Option<ReservePolicy>,
+    license_submission_id: Option<String>,
+    contract_unsigned: bool,
+}
+
+impl ReservePlan {
+    pub fn hold(&self, amount_cents: i64) -> Option<Hold> {
+        self.policy
+            .as_ref()?
+            .hold(amount_cents, self.contract_unsigned, Utc::now())
+    }
+}
+
+async fn brand_risk_level(state: &AppState, brand_id: &str) -> Result<RiskLevel, RepoError> {
+    #[derive(Deserialize)]
+    struct Brand {
+        payout_risk_level: Option<String>,
+    }
+    let rows: Vec<Brand> = fetch(
+        state
+            .pg
+            .from("brands")
+            .select("payout_risk_level")
+            .eq("id", brand_id)
+            .limit(1),
+    )
+    .await?;
+    Ok(rows
+        .first()
+        .and_then(|b| b.payout_risk_level.as_deref())
+        .and_then(RiskLevel::parse)
+        .unwrap_or(RiskLevel::Standard))
+}
+
+/// Works out the reserve policy for a payment to `agency_id` for the given
+/// licensing request, from its brand's risk level and contract.
+pub async fn plan(
+    state: &AppState,
+    agency_id: &str,
+    licensing_request_id: &str,
+) -> Result<ReservePlan, RepoError> {
+    #[derive(Deserialize)]
+    struct Request {
+        brand_id: Option<String>,
+        submission_id: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct Submission {
+        signed_at: Option<String>,
+    }
+
+    let requests: Vec<Request> = fetch(
+        state
+            .pg
+            .from("licensing_requests")
+            .select("brand_id,submission_id")
+            .eq("id", licensing_request_id)
+            .limit(1),
+    )
+    .await?;
+    let request = requests.into_iter().next();
+    let risk = match request.as_ref().and_then(|r| r.brand_id.as_deref()) {
+        Some(brand_id) => brand_risk_level(state, brand_id).await?,
+        None => RiskLevel::Standard,
+    };
+    let policies: Vec<ReservePolicy> = fetch(
+        state
+            .pg
+            .from(POLICIES_TABLE)
+            .select("*")
+            .or(format!("agency_id.eq.{agency_id},agency_id.is.null")),
+    )
+    .await?;
+    let Some(policy) = ReservePolicy::pick(&policies, agency_id, risk).cloned() else {
+        return Ok(ReservePlan::default());
+    };
+
+    let license_submission_id = request.and_then(|r| r.submission_id);
+    let contract_unsigned = match license_submission_id.as_deref() {
+        Some(id) if policy.hold_until_contract_signed => {
+            let rows: Vec<Submission> = fetch(
+                state
+                    .pg
+                    .from("license_submissions")
+                    .select("signed_at")
+                    .eq("id", id)
+                    .limit(1),
+            )
+            .await?;
+            rows.first().is_some_and(|s| s.signed_at.is_none())
+        }
+        _ => false,
+    };
+    Ok(ReservePlan {
+        policy: Some(policy),
+        license_submission_id,
+        contract_unsigned,
+    })
+}
+
+/// One recipient's share of a paid payment link.
+#[derive(Debug, Clone, Copy)]
+pub struct Share<'a> {
+    pub payment_link_id: &'a str,
+    pub agency_id: &'a str,
+    pub currency: &'a str,
+    pub recipient_type: &'a str,
+    pub recipient_id: &'a str,
+    pub talent_id: Option<&'a str>,
+    pub stripe_connect_account_id: Option<&'a str>,
+    pub amount_cents: i64,
+}
+
+/// Records the reserve `plan` takes from `share` and returns the amount
+/// withheld. Nothing is withheld when the reserve cannot be recorded, since
+/// an unrecorded reserve would never be released.
+pub async fn withhold(state: &AppState, plan: &ReservePlan, share: Share<'_>) -> i64 {
+    let Some(hold) = plan.hold(share.amount_cents) else {
+        return 0;
+    };
+    let row = json!({
+        "payment_link_id": share.payment_link_id,
+        "agency_id": share.agency_id,
+        "recipient_type": share.recipient_type,
+        "recipient_id": share.recipient_id,
+        "talent_id": share.talent_id,
+        "stripe_connect_account_id": share.stripe_connect_account_id,
+        "currency": share.currency.to_uppercase(),
+        "amount_cents": hold.amount_cents,
+        "clawback_cents": 0,
+        "reason": hold.reason,
+        "release_at": hold.release_at.map(ts),
+        "license_submission_id": match hold.reason {
+            HoldReason::Contract => plan.license_submission_id.as_deref(),
+            HoldReason::Rolling => None,
+        },
+        "policy_id": plan.policy.as_ref().and_then(|p| p.id.as_deref()),
+        "status": ReserveStatus::Held,
+        "manual_hold": false,
+    });
+    match fetch::<Value>(state.pg.from(TABLE).insert(row.to_string())).await {
+        Ok(_) => {
+            info!(
+                payment_link_id = %share.payment_link_id,
+                recipient_type = %share.recipient_type,
+                recipient_id = %share.recipient_id,
+                amount_cents = hold.amount_cents,
+                reason = ?hold.reason,
+                "payout_reserve_held"
+            );
+            hold.amount_cents
+        }
+        // The unique (payment_link_id, recipient): an earlier delivery of the
+        // same checkout already withheld it.
+        Err(RepoError::Db { status: 409, .. }) => hold.amount_cents,
+        Err(e) => {
+            error!(
+                payment_link_id = %share.payment_link_id,
+                recipient_id = %share.recipient_id,
+                error = %e,
+                "payout_reserve_record_failed"
+            );
+            0
+        }
+    }
+}
+
+/// Every reserve on a payment link.
+pub(crate) async fn for_link(
+    state: &AppState,
+    payment_link_id: &str,
+) -> Result<Vec<Reserve>, RepoError> {
+    fetch(
+        state
+            .pg
+            .from(TABLE)
+            .select("*")
+            .eq("payment_link_id", payment_link_id),
+    )
+    .await
+}
+
+/// Records how much of a held reserve refunds and disputes have taken.
+pub(crate) async fn set_clawback(
+    state: &AppState,
+    reserve_id: &str,
+    clawback_cents: i64,
+) -> Result<(), RepoError> {
+    let _: Vec<Value> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .eq("id", reserve_id)
+            .eq("status", ReserveStatus::Held.as_str())
+            .update(
+                json!({ "clawback_cents": clawback_cents, "updated_at": ts(Utc::now()) })
+                    .to_string(),
+            ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Reserved amounts of an agency (`agency`) or creator, by currency.
+pub async fn reserved_by_currency(
+    state: &AppState,
+    recipient_type: &str,
+    recipient_id: &str,
+) -> Result<HashMap<String, i64>, RepoError> {
+    let rows: Vec<Reserve> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .select("*")
+            .eq("recipient_type", recipient_type)
+            .eq("recipient_id", recipient_id)
+            .in_(
+                "status",
+                [ReserveStatus::Held, ReserveStatus::Releasing].map(ReserveStatus::as_str),
+            ),
+    )
+    .await?;
+    let mut reserved = HashMap::new();
+    for row in rows {
+        *reserved.entry(row.currency.to_uppercase()).or_insert(0) += row.payable_cents();
+    }
+    Ok(reserved)
+}
+
+pub async fn reserved_cents(
+    state: &AppState,
+    recipient_type: &str,
+    recipient_id: &str,
+    currency: &str,
+) -> Result<i64, RepoError> {
+    Ok(reserved_by_currency(state, recipient_type, recipient_id)
+        .await?
+        .get(&currency.to_uppercase())
+        .copied()
+        .unwrap_or(0))
+}
+
+/// The license contract was signed: reserves waiting on it become due.
+pub(crate) async fn contract_signed(
+    state: &AppState,
+    license_submission_id: &str,
+) -> Result<(), RepoError> {
+    let now = ts(Utc::now());
+    let due: Vec<Value> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .eq("license_submission_id", license_submission_id)
+            .eq("status", ReserveStatus::Held.as_str())
+            .is("release_at", "null")
+            .update(json!({ "release_at": now, "updated_at": now }).to_string()),
+    )
+    .await?;
+    if !due.is_empty() {
+        info!(
+            license_submission_id,
+            reserves = due.len(),
+            "payout_reserves_due_on_contract_signature"
+        );
+    }
+    Ok(())
+}
+
+async fn update(state: &AppState, id: &str, mut patch: Value) -> Result<Reserve, RepoError> {
+    patch["updated_at"] = json!(ts(Utc::now()));
+    let rows: Vec<Reserve> =
+        fetch(state.pg.from(TABLE).eq("id", id).update(patch.to_string())).await?;
+    rows.into_iter().next().ok_or(RepoError::NotFound)
+}
+
+/// The transfer an earlier release of the reserve already recorded.
+async fn recorded_transfer(
+    state: &AppState,
+    reserve_id: &str,
+) -> Result<Option<String>, RepoError> {
+    #[derive(Deserialize)]
+    struct Row {
+        stripe_transfer_id: Option<String>,
+    }
+    let rows: Vec<Row> = fetch(
+        state
+            .pg
+            .from("agency_payment_link_transfers")
+            .select("stripe_transfer_id")
+            .eq("reserve_id", reserve_id)
+            .eq("status", "created")
+            .limit(1),
+    )
+    .await?;
+    Ok(rows.into_iter().next().and_then(|r| r.stripe_transfer_id))
+}
+
+/// Transfers `amount_cents` of the reserve to its recipient's connected
+/// account and records it like the checkout transfers. A transfer made by an
+/// earlier attempt that could not record it is recorded instead of made
+/// again.
+async fn transfer(
+    state: &AppState,
+    reserve: &Reserve,
+    amount_cents: i64,
+) -> Result<String, String> {
+    let account_id = match reserve.stripe_connect_account_id.clone() {
+        Some(id) => id,
+        None if reserve.recipient_type == "agency" => {
+            crate::payouts::get_agency_stripe_account(state, &reserve.recipient_id).await?
+        }
+        None => crate::payouts::get_creator_stripe_account(state, &reserve.recipient_id).await?,
+    };
+    let transfer_id = match reserve.stripe_transfer_id.clone() {
+        Some(id) => id,
+        None => create_transfer(state, reserve, &account_id, amount_cents).await?,
+    };
+
+    let recorded = state
+        .pg
+        .rpc(
+            "record_stripe_transfer",
+            json!({
+                "p_payment_link_id": reserve.payment_link_id,
+                "p_recipient_type": reserve.recipient_type,
+                "p_recipient_id": reserve.recipient_id,
+                "p_stripe_connect_account_id": account_id,
+                "p_amount_cents": amount_cents,
+                "p_currency": reserve.currency,
+                "p_stripe_transfer_id": transfer_id,
+                "p_status": "created",
+                "p_reserve_id": reserve.id,
+            })
+            .to_string(),
+        )
+        .execute()
+        .await;
+    if !recorded.as_ref().is_ok_and(|r| r.status().is_success()) {
+        // Kept `releasing` with the transfer on it, so refunds leave the
+        // amount alone and the retry records this transfer rather than
+        // relying on the Stripe idempotency key, which expires.
+        let patch =
+            json!({ "stripe_transfer_id": transfer_id, "failure_reason": TRANSFER_UNRECORDED });
+        if let Err(e) = update(state, &reserve.id, patch).await {
+            error!(
+                reserve_id = %reserve.id,
+                transfer_id = %transfer_id,
+                error = %e,
+                "payout_reserve_transfer_id_unsaved"
+            );
+        }
+        return Err(TRANSFER_UNRECORDED.to_string());
+    }
+    crate::ledger::record(
+        state,
+        crate::payouts::transfer_entry(
+            &transfer_id,
+            &reserve.currency,
+            &account_id,
+            amount_cents,
+            &reserve.payment_link_id,
+        )
+        .memo("Reserve released"),
+    )
+    .await;
+    Ok(transfer_id)
+}
+
+async fn create_transfer(
+    state: &AppState,
+    reserve: &Reserve,
+    account_id: &str,
+    amount_cents: i64,
+) -> Result<String, String> {
+    let currency = stripe_sdk::Currency::from_str(&reserve.currency.to_lowercase())
+        .map_err(|_| "invalid_currency".to_string())?;
+    let client = state
+        .stripe_client()
+        .with_strategy(stripe_sdk::RequestStrategy::Idempotent(format!(
+            "reserve-release-{}",
+            reserve.id
+        )));
+    let mut params = stripe_sdk::CreateTransfer::new(currency, account_id.to_string());
+    params.amount = Some(amount_cents);
+    params.metadata = Some(HashMap::from([
+        (
+            "payment_link_id".to_string(),
+            reserve.payment_link_id.clone(),
+        ),
+        ("reserve_id".to_string(), reserve.id.clone()),
+        ("type".to_string(), "reserve_release".to_string()),
+    ]));
+    let transfer = stripe_sdk::Transfer::create(&client, params)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(transfer.id.to_string())
+}
+
+/// Pays a held reserve out: claims it, transfers what refunds and disputes
+/// left of it and marks it released, or forfeited when they took it all. A
+/// failed transfer puts it back on hold with the reason, and the Stripe
+/// idempotency key keeps a retry from paying twice. A release that could not
+/// record its transfer, or stopped before saving its outcome, leaves the
+/// reserve `releasing`; once that is [`RELEASE_STALE_MINUTES`] old the job
+/// claims it again and finishes it with the same transfer.
+pub(crate) async fn release(state: &AppState, reserve_id: &str) -> Result<Reserve, String> {
+    let stale_before = Utc::now() - Duration::minutes(RELEASE_STALE_MINUTES);
+    let claimed: Vec<Reserve> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .eq("id", reserve_id)
+            .or(format!(
+                "status.eq.held,and(status.eq.releasing,updated_at.lt.{})",
+                ts(stale_before)
+            ))
+            .update(
+                json!({ "status": ReserveStatus::Releasing, "updated_at": ts(Utc::now()) })
+                    .to_string(),
+            ),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let Some(reserve) = claimed.into_iter().next() else {
+        return Err("reserve_not_held".to_string());
+    };
+
+    let payable = reserve.payable_cents();
+    let released_at = ts(Utc::now());
+    let patch = if payable == 0 {
+        json!({ "status": ReserveStatus::Forfeited, "released_at": released_at })
+    } else {
+        let transferred = match recorded_transfer(state, &reserve.id).await {
+            Ok(Some(transfer_id)) => Ok(transfer_id),
+            Ok(None) => transfer(state, &reserve, payable).await,
+            Err(e) => Err(e.to_string()),
+        };
+        match transferred {
+            Ok(transfer_id) => json!({
+                "status": ReserveStatus::Released,
+                "released_at": released_at,
+                "stripe_transfer_id": transfer_id,
+                "failure_reason": null,
+            }),
+            Err(e) if e == TRANSFER_UNRECORDED => return Err(e),
+            Err(e) => {
+                let _ = update(
+                    state,
+                    &reserve.id,
+                    json!({ "status": ReserveStatus::Held, "failure_reason": e }),
+                )
+                .await;
+                return Err(e);
+            }
+        }
+    };
+    update(state, &reserve.id, patch).await.map_err(|e| {
+        // Left `releasing`: the job finishes it once it is stale.
+        error!(reserve_id = %reserve.id, error = %e, "payout_reserve_release_unsaved");
+        e.to_string()
+    })
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReleaseSummary {
+    pub released: usize,
+    pub forfeited: usize,
+    /// Due but kept because the payment is disputed.
+    pub disputed: usize,
+    pub failed: usize,
+}
+
+/// Releases every reserve that is due and not held by ops, skipping
+/// payments with an open dispute, and finishes releases that went stale.
+pub async fn release_due(state: &AppState) -> Result<ReleaseSummary, String> {
+    let now = Utc::now();
+    let due: Vec<Reserve> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .select("*")
+            .is("manual_hold", "false")
+            .or(format!(
+                "and(status.eq.held,release_at.lte.{}),and(status.eq.releasing,updated_at.lt.{})",
+                ts(now),
+                ts(now - Duration::minutes(RELEASE_STALE_MINUTES))
+            ))
+            .order("release_at.asc")
+            .limit(RELEASE_BATCH),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut summary = ReleaseSummary::default();
+    if due.is_empty() {
+        return Ok(summary);
+    }
+
+    #[derive(Deserialize)]
+    struct Link {
+        id: String,
+    }
+    let link_ids: HashSet<&str> = due.iter().map(|r| r.payment_link_id.as_str()).collect();
+    let disputed: Vec<Link> = fetch(
+        state
+            .pg
+            .from("agency_payment_links")
+            .select("id")
+            .in_("id", link_ids)
+            .eq("status", "disputed"),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let disputed: HashSet<&str> = disputed.iter().map(|l| l.id.as_str()).collect();
+
+    for reserve in &due {
+        if disputed.contains(reserve.payment_link_id.as_str()) {
+            summary.disputed += 1;
+            continue;
+        }
+        match release(state, &reserve.id).await {
+            Ok(r) if r.status == ReserveStatus::Forfeited => summary.forfeited += 1,
+            Ok(_) => summary.released += 1,
+            Err(e) => {
+                warn!(reserve_id = %reserve.id, error = %e, "payout_reserve_release_failed");
+                summary.failed += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+async fn get_reserve(state: &AppState, id: &str) -> AppResult<Reserve> {
+    let rows: Vec<Reserve> = fetch(state.pg.from(TABLE).select("*").eq("id", id).limit(1)).await?;
+    rows.into_iter()
+        .next()
+        .ok_or_else(|| AppError::NotFound("Reserve not found".to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListParams {
+    pub agency_id: Option<String>,
+    pub payment_link_id: Option<String>,
+    pub status: Option<String>,
+}
+
+/// GET /api/admin/payout-reserves: newest first.
+pub async fn admin_list(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<ListParams>,
+) -> AppResult<Json<Vec<Reserve>>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let mut query = state.pg.from(TABLE).select("*");
+    if let Some(agency_id) = params.agency_id.as_deref() {
+        query = query.eq("agency_id", agency_id);
+    }
+    if let Some(link_id) = params.payment_link_id.as_deref() {
+        query = query.eq("payment_link_id", link_id);
+    }
+    if let Some(status) = params.status.as_deref() {
+        query = query.eq("status", status);
+    }
+    Ok(Json(fetch(query.order("created_at.desc")).await?))
+}
+
+async fn audit_reserve(
+    state: &AppState,
+    user: &AuthUser,
+    event_type: &str,
+    title: String,
+    before: &Reserve,
+    after: &Reserve,
+) {
+    audit::record(
+        state,
+        AuditEvent::new(&after.agency_id, event_type, TABLE, &after.id)
+            .actor(user)
+            .title(title)
+            .change(before, after),
+    )
+    .await;
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct HoldPayload {
+    pub note: Option<String>,
+}
+
+/// POST /api/admin/payout-reserves/:id/hold: keeps a held reserve from being
+/// released until ops release it.
+pub async fn admin_hold(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    payload: Option<Json<HoldPayload>>,
+) -> AppResult<Json<Reserve>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let before = get_reserve(&state, &id).await?;
+    if before.status != ReserveStatus::Held {
+        return Err(AppError::Conflict(format!(
+            "A {} reserve cannot be held",
+            before.status.as_str()
+        )));
+    }
+    let note = payload.and_then(|Json(p)| p.note);
+    let after = update(
+        &state,
+        &id,
+        json!({ "manual_hold": true, "hold_note": note }),
+    )
+    .await?;
+    audit_reserve(
+        &state,
+        &user,
+        "payout_reserve.held",
+        "Payout reserve put on hold".to_string(),
+        &before,
+        &after,
+    )
+    .await;
+    Ok(Json(after))
+}
+
+/// POST /api/admin/payout-reserves/:id/release: pays a held reserve out now,
+/// whether or not it is due or on hold.
+pub async fn admin_release(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<Reserve>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let before = get_reserve(&state, &id).await?;
+    if before.status != ReserveStatus::Held {
+        return Err(AppError::Conflict(format!(
+            "A {} reserve cannot be released",
+            before.status.as_str()
+        )));
+    }
+    if before.manual_hold {
+        update(&state, &id, json!({ "manual_hold": false })).await?;
+    }
+    let after = match release(&state, &id).await {
+        Ok(after) => after,
+        Err(e) if e == "reserve_not_held" => {
+            return Err(AppError::Conflict(
+                "The reserve was released meanwhile".to_string(),
+            ))
+        }
+        Err(e) => {
+            return Err(AppError::Upstream {
+                service: "stripe",
+                detail: e,
+            })
+        }
+    };
+    audit_reserve(
+        &state,
+        &user,
+        "payout_reserve.released",
+        format!("Payout reserve {}", after.status.as_str()),
+        &before,
+        &after,
+    )
+    .await;
+    Ok(Json(after))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PolicyParams {
+    pub agency_id: Option<String>,
+}
+
+/// GET /api/admin/payout-reserves/policies: the platform defaults and every
+/// agency's policies, or one agency's with `agency_id`.
+pub async fn admin_policies(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<PolicyParams>,
+) -> AppResult<Json<Vec<ReservePolicy>>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let mut query = state.pg.from(POLICIES_TABLE).select("*");
+    if let Some(agency_id) = params.agency_id.as_deref() {
+        query = query.eq("agency_id", agency_id);
+    }
+    Ok(Json(fetch(query.order("agency_id.asc.nullsfirst")).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PolicyPayload {
+    pub agency_id: Option<String>,
+    pub brand_risk_level: Option<RiskLevel>,
+    #[serde(default)]
+    pub reserve_bps: i64,
+    #[serde(default)]
+    pub hold_days: i64,
+    #[serde(default)]
+    pub hold_until_contract_signed: bool,
+    pub note: Option<String>,
+}
+
+/// PUT /api/admin/payout-reserves/policies: saves the policy for an agency
+/// (or the platform) and brand risk level, replacing the one there was.
+pub async fn admin_save_policy(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<PolicyPayload>,
+) -> AppResult<Json<ReservePolicy>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let agency_id = payload
+        .agency_id
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty());
+    let policy = ReservePolicy {
+        id: None,
+        agency_id: agency_id.clone(),
+        brand_risk_level: payload.brand_risk_level,
+        reserve_bps: payload.reserve_bps,
+        hold_days: payload.hold_days,
+        hold_until_contract_signed: payload.hold_until_contract_signed,
+        note: payload.note,
+        updated_by: Some(user.id.clone()),
+        updated_at: None,
+    };
+    policy.validate()?;
+
+    let mut query = state.pg.from(POLICIES_TABLE).select("*");
+    query = match agency_id.as_deref() {
+        Some(a) => query.eq("agency_id", a),
+        None => query.is("agency_id", "null"),
+    };
+    query = match policy.brand_risk_level {
+        Some(r) => query.eq("brand_risk_level", r.as_str()),
+        None => query.is("brand_risk_level", "null"),
+    };
+    let rows: Vec<ReservePolicy> = fetch(query.limit(1)).await?;
+    let current = rows.into_iter().next();
+
+    let row = json!({
+        "agency_id": policy.agency_id,
+        "brand_risk_level": policy.brand_risk_level,
+        "reserve_bps": policy.reserve_bps,
+        "hold_days": policy.hold_days,
+        "hold_until_contract_signed": policy.hold_until_contract_signed,
+        "note": policy.note,
+        "updated_by": policy.updated_by,
+        "updated_at": ts(Utc::now()),
+    });
+    let saved: Result<Vec<ReservePolicy>, RepoError> = match current.as_ref() {
+        Some(c) => {
+            fetch(
+                state
+                    .pg
+                    .from(POLICIES_TABLE)
+                    .eq("id", c.id.as_deref().unwrap_or_default())
+                    .update(row.to_string()),
+            )
+            .await
+        }
+        None => fetch(state.pg.from(POLICIES_TABLE).insert(row.to_string())).await,
+    };
+    let saved = match saved {
+        Ok(rows) => rows.into_iter().next().ok_or(RepoError::NotFound)?,
+        // The unique (agency_id, brand_risk_level): saved by someone else first.
+        Err(RepoError::Db { status: 409, .. }) => {
+            return Err(AppError::Conflict(
+                "The reserve policy was changed meanwhile; reload and try again".to_string(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    match saved.agency_id.as_deref() {
+        Some(agency_id) => {
+            audit::record(
+                &state,
+                AuditEvent::new(
+                    agency_id,
+                    "payout_reserve_policy.updated",
+                    POLICIES_TABLE,
+                    saved.id.as_deref().unwrap_or_default(),
+                )
+                .actor(&user)
+                .title("Payout reserve policy saved")
+                .change(&current, &saved),
+            )
+            .await
+        }
+        None => info!(
+            policy_id = ?saved.id,
+            brand_risk_level = ?saved.brand_risk_level,
+            actor_id = %user.id,
+            "platform_payout_reserve_policy_saved"
+        ),
+    }
+    Ok(Json(saved))
+}
+
+/// DELETE /api/admin/payout-reserves/policies/:id
+pub async fn admin_delete_policy(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<StatusCode> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let rows: Vec<ReservePolicy> =
+        fetch(state.pg.from(POLICIES_TABLE).eq("id", &id).delete()).await?;
+    let deleted = rows
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::NotFound("Reserve policy not found".to_string()))?;
+    if let Some(agency_id) = deleted.agency_id.as_deref() {
+        audit::record(
+            &state,
+            AuditEvent::new(
+                agency_id,
+                "payout_reserve_policy.deleted",
+                POLICIES_TABLE,
+                &id,
+            )
+            .actor(&user)
+            .title("Payout reserve policy removed")
+            .change(&deleted, &None::<ReservePolicy>),
+        )
+        .await;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BrandRiskPayload {
+    pub risk_level: RiskLevel,
+}
+
+/// PUT /api/admin/payout-reserves/brands/:brand_id: sets the brand's risk
+/// level, which picks the reserve policy for its payments.
+pub async fn admin_set_brand_risk(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(brand_id): Path<String>,
+    Json(payload): Json<BrandRiskPayload>,
+) -> AppResult<Json<Value>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let rows: Vec<Value> = fetch(
+        state
+            .pg
+            .from("brands")
+            .eq("id", &brand_id)
+            .select("id,payout_risk_level")
+            .update(json!({ "payout_risk_level": payload.risk_level }).to_string()),
+    )
+    .await?;
+    let brand = rows
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::NotFound("Brand not found".to_string()))?;
+    info!(
+        brand_id = %brand_id,
+        risk_level = payload.risk_level.as_str(),
+        actor_id = %user.id,
+        "brand_payout_risk_level_set"
+    );
+    Ok(Json(brand))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(agency: Option<&str>, risk: Option<RiskLevel>, bps: i64) -> ReservePolicy {
+        ReservePolicy {
+            agency_id: agency.map(str::to_string),
+            brand_risk_level: risk,
+            reserve_bps: bps,
+            hold_days: 30,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn most_specific_policy_wins() {
+        let policies = vec![
+            policy(None, None, 100),
+            policy(None, Some(RiskLevel::High), 2_000),
+            policy(Some("a"), None, 500),
+        ];
+        let pick =
+            |agency, risk| ReservePolicy::pick(&policies, agency, risk).map(|p| p.reserve_bps);
+        assert_eq!(pick("a", RiskLevel::High), Some(500));
+        assert_eq!(pick("b", RiskLevel::High), Some(2_000));
+        assert_eq!(pick("b", RiskLevel::Low), Some(100));
+        assert_eq!(
+            ReservePolicy::pick(&policies[1..2], "b", RiskLevel::Low).map(|p| p.reserve_bps),
+            None
+        );
+    }
+
+    #[test]
+    fn holds_a_percentage_or_everything_until_signed() {
+        let now = Utc::now();
+        let rolling = policy(None, None, 1_000);
+        let hold = rolling.hold(7_201, true, now).unwrap();
+        assert_eq!((hold.amount_cents, hold.reason), (721, HoldReason::Rolling));
+        assert_eq!(hold.release_at, Some(now + Duration::days(30)));
+
+        let contract = ReservePolicy {
+            hold_until_contract_signed: true,
+            ..rolling
+        };
+        let hold = contract.hold(7_200, true, now).unwrap();
+        assert_eq!(
+            (hold.amount_cents, hold.reason, hold.release_at),
+            (7_200, HoldReason::Contract, None)
+        );
+        assert_eq!(contract.hold(7_200, false, now).unwrap().amount_cents, 720);
+        assert_eq!(ReservePolicy::default().hold(7_200, true, now), None);
+    }
+}
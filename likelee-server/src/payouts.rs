@@ -1,4 +1,4 @@
-use crate::errors::sanitize_db_error;
+use crate::errors::{sanitize_db_error, AppError};
 use axum::{
     extract::{Query, State},
     http::StatusCode,
@@ -11,34 +11,10 @@ use tracing::{error, info, warn};
 use crate::auth::AuthUser;
 use crate::auth::RoleGuard;
 use crate::config::AppState;
+use crate::repositories::RepoError;
 use std::str::FromStr;
 // use stripe_sdk; // Implicitly available
 
-fn sanitized_error_response(
-    status_code: u16,
-    text: String,
-) -> (StatusCode, Json<serde_json::Value>) {
-    let (code, body) = sanitize_db_error(status_code, text);
-    let v: serde_json::Value = serde_json::from_str(&body).unwrap_or_else(
-        |_| json!({"error":"An internal error occurred. Our team has been notified."}),
-    );
-    (code, Json(json!({"status":"error","error": v})))
-}
-
-fn internal_error_response<E: std::fmt::Display>(
-    context: &str,
-    err: E,
-) -> (StatusCode, Json<serde_json::Value>) {
-    error!(context = context, error = %err, "Internal error");
-    (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Json(json!({
-            "status":"error",
-            "error":"internal_error"
-        })),
-    )
-}
-
 async fn resolve_talent_creator_id(
     state: &AppState,
     user: &AuthUser,
@@ -164,7 +140,7 @@ pub async fn create_onboarding_link(
     {
         Ok(r) => r,
         Err(e) => {
-            return internal_error_response("create_onboarding_link.fetch_profile", e);
+            return AppError::internal("create_onboarding_link.fetch_profile", e).into_parts();
         }
     };
     let text = prof_resp.text().await.unwrap_or("[]".into());
@@ -209,7 +185,7 @@ pub async fn create_onboarding_link(
         .unwrap_or("")
         .to_string();
 
-    let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+    let client = state.stripe_client();
 
     // Create account if missing
     if account_id.is_empty() {
@@ -249,7 +225,8 @@ pub async fn create_onboarding_link(
                     .await;
             }
             Err(e) => {
-                return internal_error_response("create_onboarding_link.stripe_account_create", e);
+                return AppError::internal("create_onboarding_link.stripe_account_create", e)
+                    .into_parts();
             }
         }
     }
@@ -273,8 +250,8 @@ pub async fn create_onboarding_link(
     match stripe_sdk::AccountLink::create(&client, link_params).await {
         Ok(link) => (StatusCode::OK, Json(json!({"url": link.url}))),
         Err(e) => {
-            let resp =
-                internal_error_response("create_onboarding_link.stripe_account_link_create", e);
+            let resp = AppError::internal("create_onboarding_link.stripe_account_link_create", e)
+                .into_parts();
             (resp.0, resp.1)
         }
     }
@@ -315,13 +292,13 @@ pub async fn get_account_status(
     {
         Ok(r) => r,
         Err(e) => {
-            return internal_error_response("get_account_status.fetch_profile", e);
+            return AppError::internal("get_account_status.fetch_profile", e).into_parts();
         }
     };
     let text = match resp.text().await {
         Ok(t) => t,
         Err(e) => {
-            return internal_error_response("get_account_status.read_body", e);
+            return AppError::internal("get_account_status.read_body", e).into_parts();
         }
     };
     let rows: Vec<serde_json::Value> = serde_json::from_str(&text).unwrap_or_default();
@@ -365,7 +342,7 @@ pub async fn get_account_status(
             .and_then(|v| v.as_str())
             .map(|s| s.to_string())
         {
-            let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+            let client = state.stripe_client();
             if let Ok(parsed) = acct_id.parse::<stripe_sdk::AccountId>() {
                 match stripe_sdk::Account::retrieve(&client, &parsed, &["external_accounts"]).await
                 {
@@ -428,7 +405,8 @@ pub async fn create_agency_onboarding_link(
     {
         Ok(r) => r,
         Err(e) => {
-            return internal_error_response("create_agency_onboarding_link.fetch_agency", e);
+            return AppError::internal("create_agency_onboarding_link.fetch_agency", e)
+                .into_parts();
         }
     };
     let status = agency_resp.status();
@@ -449,7 +427,7 @@ pub async fn create_agency_onboarding_link(
                 })),
             );
         }
-        return sanitized_error_response(status.as_u16(), text);
+        return AppError::db(status.as_u16(), text).into_parts();
     }
     let mut rows: Vec<serde_json::Value> = serde_json::from_str(&text).unwrap_or_default();
     if rows.is_empty() {
@@ -484,7 +462,8 @@ pub async fn create_agency_onboarding_link(
             .execute()
             .await;
         if let Err(e) = insert_resp {
-            return internal_error_response("create_agency_onboarding_link.create_agency_row", e);
+            return AppError::internal("create_agency_onboarding_link.create_agency_row", e)
+                .into_parts();
         }
 
         // Re-fetch after insertion
@@ -499,7 +478,8 @@ pub async fn create_agency_onboarding_link(
         {
             Ok(r) => r,
             Err(e) => {
-                return internal_error_response("create_agency_onboarding_link.refetch_agency", e);
+                return AppError::internal("create_agency_onboarding_link.refetch_agency", e)
+                    .into_parts();
             }
         };
         let status = agency_resp.status();
@@ -519,7 +499,7 @@ pub async fn create_agency_onboarding_link(
                     })),
                 );
             }
-            return sanitized_error_response(status.as_u16(), text);
+            return AppError::db(status.as_u16(), text).into_parts();
         }
         rows = serde_json::from_str(&text).unwrap_or_default();
         if rows.is_empty() {
@@ -540,7 +520,7 @@ pub async fn create_agency_onboarding_link(
         .unwrap_or("")
         .to_string();
 
-    let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+    let client = state.stripe_client();
 
     if account_id.is_empty() {
         let mut params = stripe_sdk::CreateAccount::new();
@@ -577,10 +557,11 @@ pub async fn create_agency_onboarding_link(
                     .await;
             }
             Err(e) => {
-                return internal_error_response(
+                return AppError::internal(
                     "create_agency_onboarding_link.stripe_account_create",
                     e,
-                );
+                )
+                .into_parts();
             }
         }
     }
@@ -602,10 +583,11 @@ pub async fn create_agency_onboarding_link(
     link_params.refresh_url = Some(state.stripe_refresh_url.as_str());
     match stripe_sdk::AccountLink::create(&client, link_params).await {
         Ok(link) => (StatusCode::OK, Json(json!({"url": link.url}))),
-        Err(e) => internal_error_response(
+        Err(e) => AppError::internal(
             "create_agency_onboarding_link.stripe_account_link_create",
             e,
-        ),
+        )
+        .into_parts(),
     }
 }
 
@@ -624,14 +606,14 @@ pub async fn get_agency_account_status(
     {
         Ok(r) => r,
         Err(e) => {
-            return internal_error_response("get_agency_account_status.fetch_agency", e);
+            return AppError::internal("get_agency_account_status.fetch_agency", e).into_parts();
         }
     };
     let status = resp.status();
     let text = match resp.text().await {
         Ok(t) => t,
         Err(e) => {
-            return internal_error_response("get_agency_account_status.read_body", e);
+            return AppError::internal("get_agency_account_status.read_body", e).into_parts();
         }
     };
     if !status.is_success() {
@@ -649,7 +631,7 @@ pub async fn get_agency_account_status(
                 })),
             );
         }
-        return sanitized_error_response(status.as_u16(), text);
+        return AppError::db(status.as_u16(), text).into_parts();
     }
     let mut rows: Vec<serde_json::Value> = serde_json::from_str(&text).unwrap_or_default();
     if rows.is_empty() {
@@ -716,7 +698,7 @@ pub async fn get_agency_account_status(
             .and_then(|v| v.as_str())
             .map(|s| s.to_string())
         {
-            let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+            let client = state.stripe_client();
             if let Ok(parsed) = acct_id.parse::<stripe_sdk::AccountId>() {
                 match stripe_sdk::Account::retrieve(&client, &parsed, &["external_accounts"]).await
                 {
@@ -777,39 +759,38 @@ pub async fn get_balance(
             Json(json!({"status":"error","error":"missing_profile_id"})),
         );
     }
-    let resp = match state
-        .pg
-        .from("creator_balances")
-        .select("creator_id,currency,available_cents,earned_cents")
-        .eq("creator_id", &q.profile_id)
-        .execute()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            let msg = e.to_string();
-            if msg.contains("relation") && msg.contains("does not exist") {
-                warn!(%msg, "creator_balances view missing; defaulting zero");
-                return (
-                    StatusCode::OK,
-                    Json(
-                        json!({"balances": [], "allowed_currencies": state.payout_allowed_currencies}),
-                    ),
-                );
-            }
-            return sanitized_error_response(StatusCode::INTERNAL_SERVER_ERROR.as_u16(), msg);
-        }
-    };
-    let text = match resp.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            return sanitized_error_response(
-                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                e.to_string(),
+    let mut rows = match state.repos.balances.creator_balances(&q.profile_id).await {
+        Ok(rows) => rows,
+        Err(RepoError::Db { body, .. })
+            if body.contains("relation") && body.contains("does not exist") =>
+        {
+            warn!(msg = %body, "creator_balances view missing; defaulting zero");
+            return (
+                StatusCode::OK,
+                Json(
+                    json!({"balances": [], "allowed_currencies": state.payout_allowed_currencies}),
+                ),
             );
         }
+        Err(e) => return AppError::from(e).into_parts(),
+    };
+    let reserved = match crate::payout_reserves::reserved_by_currency(
+        &state,
+        "creator",
+        &q.profile_id,
+    )
+    .await
+    {
+        Ok(reserved) => reserved,
+        Err(e) => return AppError::from(e).into_parts(),
     };
-    let mut rows: Vec<BalanceRow> = serde_json::from_str(&text).unwrap_or_default();
+    for row in &mut rows {
+        row.reserved_cents = reserved
+            .get(&row.currency.to_uppercase())
+            .copied()
+            .unwrap_or(0);
+        row.available_cents = (row.available_cents - row.reserved_cents).max(0);
+    }
     // filter to allowed currencies
     rows.retain(|r| {
         state
@@ -935,10 +916,8 @@ pub async fn request_payout(
     {
         Ok(r) => r,
         Err(e) => {
-            return sanitized_error_response(
-                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                e.to_string(),
-            );
+            return AppError::db(StatusCode::INTERNAL_SERVER_ERROR.as_u16(), e.to_string())
+                .into_parts();
         }
     };
     let text = bal_resp.text().await.unwrap_or("[]".to_string());
@@ -947,6 +926,18 @@ pub async fn request_payout(
         .first()
         .and_then(|r| r.get("available_cents").and_then(|x| x.as_i64()))
         .unwrap_or(0);
+    // Reserved funds are not available to pay out.
+    let available = match crate::payout_reserves::reserved_cents(
+        &state,
+        "creator",
+        &payload.profile_id,
+        &currency,
+    )
+    .await
+    {
+        Ok(reserved) => available - reserved,
+        Err(e) => return AppError::from(e).into_parts(),
+    };
 
     info!(
         profile_id = %payload.profile_id,
@@ -991,16 +982,14 @@ pub async fn request_payout(
     {
         Ok(r) => r,
         Err(e) => {
-            return sanitized_error_response(
-                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                e.to_string(),
-            );
+            return AppError::db(StatusCode::INTERNAL_SERVER_ERROR.as_u16(), e.to_string())
+                .into_parts();
         }
     };
     let st = ins.status();
     let text = ins.text().await.unwrap_or_else(|_| "".into());
     if !st.is_success() {
-        return sanitized_error_response(st.as_u16(), text);
+        return AppError::db(st.as_u16(), text).into_parts();
     }
     let rows: Vec<serde_json::Value> = serde_json::from_str(&text).unwrap_or_default();
     let created = rows.first().cloned().unwrap_or(json!({"status":"ok"}));
@@ -1103,7 +1092,7 @@ async fn execute_payout(
         return Err(());
     }
 
-    let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+    let client = state.stripe_client();
     // Creator payouts have no platform fee; Stripe fees apply at transfer/payout time.
     let net_cents = amount_cents;
 
@@ -1204,6 +1193,21 @@ async fn execute_payout(
                 .execute()
                 .await;
 
+            crate::ledger::record(
+                state,
+                payout_entry(
+                    format!("creator_payout:{payout_request_id}"),
+                    "creator_payout_request",
+                    payout_request_id,
+                    &crate::ledger::Account::creator(profile_id),
+                    &account_id,
+                    amount_cents,
+                    amount_cents - net_cents,
+                    currency,
+                ),
+            )
+            .await;
+
             // For instant payouts, mark paid immediately (webhooks will confirm).
             if method == "instant" {
                 let _ = state
@@ -1256,23 +1260,15 @@ pub async fn get_history(
     }
     let limit_usize: usize = q.limit.unwrap_or(5).clamp(1, 100).try_into().unwrap_or(5);
 
-    let resp = match state
-        .pg
-        .from("creator_payout_requests")
-        .select("id,creator_id,amount_cents,payout_method,currency,status,created_at,requested_at,processed_at,stripe_transfer_id,stripe_payout_id,failure_reason")
-        .eq("creator_id", &q.profile_id)
-        .order("created_at.desc")
-        .limit(limit_usize)
-        .execute()
+    let rows = match state
+        .repos
+        .payouts
+        .creator_history(&q.profile_id, limit_usize)
         .await
     {
-        Ok(r) => r,
-        Err(e) => {
-            return internal_error_response("get_history.fetch", e);
-        }
+        Ok(rows) => rows,
+        Err(e) => return AppError::from(e).into_parts(),
     };
-    let text = resp.text().await.unwrap_or("[]".into());
-    let rows: Vec<serde_json::Value> = serde_json::from_str(&text).unwrap_or_default();
     info!(
         profile_id = %q.profile_id,
         limit = q.limit.unwrap_or(5),
@@ -1313,27 +1309,46 @@ pub async fn stripe_webhook(
         );
     }
 
-    // Best-effort: also parse into typed Event; currently unused but kept for potential
-    // future event handlers without reintroducing signature parsing changes.
-    let typed_event: Option<stripe_sdk::Event> = serde_json::from_str(&payload).ok();
     let etype = payload_json
         .get("type")
         .and_then(|v| v.as_str())
         .unwrap_or("")
         .to_string();
+    let event_id = payload_json
+        .get("id")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| crate::webhooks::body_digest(&body));
+
+    match crate::webhooks::ingest(
+        &state,
+        crate::webhooks::STRIPE,
+        &event_id,
+        &etype,
+        payload_json,
+    )
+    .await
+    .map_err(AppError::from)
+    .and_then(|outcome| outcome.into_result())
+    {
+        Ok(status) => (status, Json(json!({"status":"ok"}))),
+        Err(e) => e.into_parts(),
+    }
+}
 
-    // Store raw event
-    let body = json!({
-        "provider": "stripe",
-        "event_type": etype,
-        "payload": serde_json::from_str::<serde_json::Value>(&payload).unwrap_or(json!({}))
-    });
-    let _ = state
-        .pg
-        .from("webhook_events")
-        .insert(body.to_string())
-        .execute()
-        .await;
+/// Applies a verified Stripe event. Called for new deliveries and for
+/// replays from `webhook_events`.
+pub(crate) async fn process_stripe_event(
+    state: &AppState,
+    payload_json: &serde_json::Value,
+) -> Result<(), String> {
+    // Best-effort: also parse into typed Event for handlers that use the SDK types.
+    let typed_event: Option<stripe_sdk::Event> = serde_json::from_value(payload_json.clone()).ok();
+    let etype = payload_json
+        .get("type")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
 
     // Minimal handlers
     match etype.as_str() {
@@ -1364,12 +1379,11 @@ pub async fn stripe_webhook(
                     .map(|s| !s.trim().is_empty())
                     .unwrap_or(false);
                 if has_request_ids {
-                    let _ =
-                        handle_licensing_requests_checkout_session_completed(&state, &obj).await;
+                    handle_licensing_requests_checkout_session_completed(state, &obj).await?;
                 } else {
-                    let _ = handle_licensing_checkout_session_completed(&state, &obj).await;
+                    handle_licensing_checkout_session_completed(state, &obj).await?;
                 }
-                return (StatusCode::OK, Json(json!({"status":"ok"})));
+                return Ok(());
             }
 
             // Check if this is a payment link checkout
@@ -1398,8 +1412,8 @@ pub async fn stripe_webhook(
                     stripe_payment_link_id = %stripe_payment_link_id,
                     "checkout.session.completed detected as payment-link checkout"
                 );
-                let _ = handle_payment_link_checkout_completed(&state, &obj).await;
-                return (StatusCode::OK, Json(json!({"status":"ok"})));
+                handle_payment_link_checkout_completed(state, &obj).await?;
+                return Ok(());
             }
 
             tracing::info!(
@@ -1429,8 +1443,8 @@ pub async fn stripe_webhook(
                 .to_string();
 
             if !agency_id.is_empty() && !subscription_id.is_empty() {
-                let _ = sync_agency_subscription_from_stripe(
-                    &state,
+                sync_agency_subscription_from_stripe(
+                    state,
                     &agency_id,
                     &subscription_id,
                     if customer_id.is_empty() {
@@ -1439,7 +1453,7 @@ pub async fn stripe_webhook(
                         Some(customer_id.as_str())
                     },
                 )
-                .await;
+                .await?;
             }
         }
         "customer.subscription.created"
@@ -1459,8 +1473,8 @@ pub async fn stripe_webhook(
                 .to_string();
 
             if billing_domain == "licensing" {
-                let _ = sync_licensing_access_grant_from_stripe_subscription(&state, &obj).await;
-                return (StatusCode::OK, Json(json!({"status":"ok"})));
+                sync_licensing_access_grant_from_stripe_subscription(state, &obj).await?;
+                return Ok(());
             }
 
             let subscription_id = obj.get("id").and_then(|v| v.as_str()).unwrap_or("");
@@ -1477,8 +1491,8 @@ pub async fn stripe_webhook(
                 .to_string();
 
             if !agency_id.is_empty() && !subscription_id.trim().is_empty() {
-                let _ = sync_agency_subscription_from_stripe(
-                    &state,
+                sync_agency_subscription_from_stripe(
+                    state,
                     &agency_id,
                     subscription_id,
                     if customer_id.is_empty() {
@@ -1487,7 +1501,7 @@ pub async fn stripe_webhook(
                         Some(customer_id.as_str())
                     },
                 )
-                .await;
+                .await?;
             }
         }
         "invoice.paid" => {
@@ -1510,8 +1524,8 @@ pub async fn stripe_webhook(
 
             if !subscription_id.is_empty() {
                 // We may not have agency_id on invoice; fetch subscription and read metadata.
-                let _ = sync_agency_subscription_by_subscription_id(
-                    &state,
+                sync_agency_subscription_by_subscription_id(
+                    state,
                     &subscription_id,
                     if customer_id.is_empty() {
                         None
@@ -1519,7 +1533,30 @@ pub async fn stripe_webhook(
                         Some(customer_id.as_str())
                     },
                 )
-                .await;
+                .await?;
+            }
+        }
+        // ====================================================================
+        // Refunds and disputes on payment link charges
+        // ====================================================================
+        "charge.refunded"
+        | "charge.dispute.created"
+        | "charge.dispute.funds_withdrawn"
+        | "charge.dispute.closed" => {
+            let obj = payload_json
+                .get("data")
+                .and_then(|d| d.get("object"))
+                .cloned()
+                .unwrap_or(json!({}));
+            match etype.as_str() {
+                "charge.refunded" => crate::refunds::handle_charge_refunded(state, &obj).await?,
+                "charge.dispute.created" => {
+                    crate::refunds::handle_dispute_created(state, &obj).await?
+                }
+                "charge.dispute.funds_withdrawn" => {
+                    crate::refunds::handle_dispute_funds_withdrawn(state, &obj).await?
+                }
+                _ => crate::refunds::handle_dispute_closed(state, &obj).await?,
             }
         }
         // Connected Account status updates
@@ -1585,7 +1622,7 @@ pub async fn stripe_webhook(
                     }
                     update.insert("stripe_payout_id".into(), json!(pid));
                     if let (Some(btx), Some(acct_id)) = (p.balance_transaction, maybe_account) {
-                        let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+                        let client = state.stripe_client();
                         let connected_client = match acct_id.parse::<stripe_sdk::AccountId>() {
                             Ok(id) => client.with_stripe_account(id),
                             Err(_) => client, // fallback: use platform client, retrieval may fail but won't panic
@@ -1619,7 +1656,7 @@ pub async fn stripe_webhook(
         _ => {}
     }
 
-    (StatusCode::OK, Json(json!({"status":"ok"})))
+    Ok(())
 }
 
 async fn handle_licensing_checkout_session_completed(
@@ -2379,6 +2416,37 @@ async fn handle_payment_link_checkout_completed(
         .insert(payout_record.to_string())
         .execute()
         .await;
+
+    let mut talent_shares = vec![];
+    for split in talent_splits.as_array().into_iter().flatten() {
+        let amount_cents = split
+            .get("amount_cents")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0);
+        match split_creator_id(state, split).await {
+            Ok(creator_id) => talent_shares.push(crate::ledger::TalentShare {
+                creator_id,
+                amount_cents,
+            }),
+            Err(e) => warn!(
+                payment_link_id = %payment_link_id,
+                error = %e,
+                "ledger_talent_share_unresolved"
+            ),
+        }
+    }
+    for entry in crate::ledger::payment_link_entries(
+        &payment_link_id,
+        &currency,
+        if pl_total > 0 { pl_total } else { amount_total },
+        platform_fee_cents,
+        &agency_id,
+        agency_amount_cents,
+        &talent_shares,
+    ) {
+        crate::ledger::record(state, entry).await;
+    }
+
     // Update payments table status
     let payment_update = json!({
         "status": "paid",
@@ -2510,15 +2578,42 @@ async fn create_payment_link_transfers(
     talent_splits: &serde_json::Value,
     currency: &str,
     payment_link_id: &str,
-    _lr_ids: &[&str],
+    lr_ids: &[&str],
 ) -> Result<TransferResults, String> {
-    let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+    let client = state.stripe_client();
     let currency_enum = stripe_sdk::Currency::from_str(&currency.to_lowercase())
         .map_err(|_| "invalid_currency".to_string())?;
 
     let mut results = TransferResults::default();
 
+    // Reserves are withheld from each share and transferred when released.
+    let reserve_plan = match crate::payout_reserves::plan(
+        state,
+        agency_id,
+        lr_ids.first().copied().unwrap_or(""),
+    )
+    .await
+    {
+        Ok(plan) => plan,
+        Err(e) => {
+            error!(payment_link_id = %payment_link_id, error = %e, "Failed to load payout reserve policy; nothing withheld");
+            Default::default()
+        }
+    };
+    let agency_share = crate::payout_reserves::Share {
+        payment_link_id,
+        agency_id,
+        currency,
+        recipient_type: "agency",
+        recipient_id: agency_id,
+        talent_id: None,
+        stripe_connect_account_id: None,
+        amount_cents: agency_amount_cents,
+    };
+
     // 1. Transfer to agency connected account
+    let agency_amount_cents = agency_amount_cents
+        - crate::payout_reserves::withhold(state, &reserve_plan, agency_share).await;
     if agency_amount_cents > 0 {
         match get_agency_stripe_account(state, agency_id).await {
             Ok(agency_account_id) => {
@@ -2555,6 +2650,19 @@ async fn create_payment_link_transfers(
                             .execute()
                             .await;
 
+                        crate::ledger::record(
+                            state,
+                            transfer_entry(
+                                transfer.id.as_str(),
+                                currency,
+                                &agency_account_id,
+                                agency_amount_cents,
+                                payment_link_id,
+                            )
+                            .memo("Agency commission transferred"),
+                        )
+                        .await;
+
                         info!(
                             agency_id = %agency_id,
                             transfer_id = %transfer.id,
@@ -2611,6 +2719,35 @@ async fn create_payment_link_transfers(
                 continue;
             }
 
+            let held = match split_creator_id(state, split).await {
+                Ok(reserve_creator_id) => {
+                    crate::payout_reserves::withhold(
+                        state,
+                        &reserve_plan,
+                        crate::payout_reserves::Share {
+                            talent_id: Some(talent_id).filter(|t| !t.is_empty()),
+                            stripe_connect_account_id: split
+                                .get("stripe_connect_account_id")
+                                .and_then(|v| v.as_str())
+                                .filter(|s| !s.is_empty()),
+                            recipient_type: "creator",
+                            recipient_id: &reserve_creator_id,
+                            amount_cents,
+                            ..agency_share
+                        },
+                    )
+                    .await
+                }
+                Err(e) => {
+                    warn!(talent_id = %talent_id, error = %e, "Talent share has no creator; nothing withheld");
+                    0
+                }
+            };
+            let amount_cents = amount_cents - held;
+            if amount_cents <= 0 {
+                continue;
+            }
+
             let talent_account_id_result = {
                 let stored = split
                     .get("stripe_connect_account_id")
@@ -2670,6 +2807,19 @@ async fn create_payment_link_transfers(
                                 .execute()
                                 .await;
 
+                            crate::ledger::record(
+                                state,
+                                transfer_entry(
+                                    transfer.id.as_str(),
+                                    currency,
+                                    &talent_account_id,
+                                    amount_cents,
+                                    payment_link_id,
+                                )
+                                .memo("Talent share transferred"),
+                            )
+                            .await;
+
                             info!(
                                 talent_id = %talent_id,
                                 creator_id = %creator_id,
@@ -2711,7 +2861,30 @@ async fn create_payment_link_transfers(
     Ok(results)
 }
 
-async fn get_agency_stripe_account(state: &AppState, agency_id: &str) -> Result<String, String> {
+/// Ledger entry moving a transfer's amount from the platform balance into
+/// the recipient's connected account.
+pub(crate) fn transfer_entry(
+    transfer_id: &str,
+    currency: &str,
+    connected_account_id: &str,
+    amount_cents: i64,
+    payment_link_id: &str,
+) -> crate::ledger::Entry {
+    use crate::ledger::{Account, Entry, EntryKind};
+    Entry::new(
+        EntryKind::Transfer,
+        format!("transfer:{transfer_id}"),
+        currency,
+    )
+    .reference("payment_link", payment_link_id)
+    .debit(&Account::connected(connected_account_id), amount_cents)
+    .credit(&Account::platform_cash(), amount_cents)
+}
+
+pub(crate) async fn get_agency_stripe_account(
+    state: &AppState,
+    agency_id: &str,
+) -> Result<String, String> {
     let resp = state
         .pg
         .from("agencies")
@@ -2732,6 +2905,28 @@ async fn get_agency_stripe_account(state: &AppState, agency_id: &str) -> Result<
         .ok_or_else(|| "Agency has no connected Stripe account".to_string())
 }
 
+/// The creator a `talent_splits` entry pays, from the split itself or its
+/// talent's roster row.
+pub(crate) async fn split_creator_id(
+    state: &AppState,
+    split: &serde_json::Value,
+) -> Result<String, String> {
+    match split
+        .get("creator_id")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+    {
+        Some(id) => Ok(id.to_string()),
+        None => {
+            let talent_id = split
+                .get("talent_id")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            get_creator_id_from_talent_id(state, talent_id).await
+        }
+    }
+}
+
 /// Resolve the `creator_id` for a talent ID (agency_users.id).
 async fn get_creator_id_from_talent_id(
     state: &AppState,
@@ -2759,7 +2954,10 @@ async fn get_creator_id_from_talent_id(
         .ok_or_else(|| format!("Talent {} has no creator profile", talent_id))
 }
 
-async fn get_creator_stripe_account(state: &AppState, creator_id: &str) -> Result<String, String> {
+pub(crate) async fn get_creator_stripe_account(
+    state: &AppState,
+    creator_id: &str,
+) -> Result<String, String> {
     let resp = state
         .pg
         .from("creators")
@@ -2996,7 +3194,7 @@ async fn fetch_subscription(
     state: &AppState,
     subscription_id: &str,
 ) -> Result<stripe_sdk::Subscription, String> {
-    let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+    let client = state.stripe_client();
     let parsed = subscription_id
         .parse::<stripe_sdk::SubscriptionId>()
         .map_err(|_| "invalid_subscription_id".to_string())?;
@@ -3139,49 +3337,31 @@ pub async fn get_agency_balance(
     State(state): State<AppState>,
     user: AuthUser,
 ) -> (StatusCode, Json<serde_json::Value>) {
-    // Get available balance from agency_balances table
-    let balance_resp = match state
-        .pg
-        .from("agency_balances")
-        .select("available_cents,currency,updated_at")
-        .eq("agency_id", &user.id)
-        .limit(1)
-        .execute()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            return internal_error_response("get_agency_balance.fetch", e);
-        }
-    };
-
-    let balance_text = match balance_resp.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            return internal_error_response("get_agency_balance.read_body", e);
-        }
+    let balance = match state.repos.balances.agency_balance(&user.id).await {
+        Ok(b) => b,
+        Err(e) => return AppError::from(e).into_parts(),
     };
 
-    let balance_rows: Vec<serde_json::Value> =
-        serde_json::from_str(&balance_text).unwrap_or_default();
-    let balance_row = balance_rows.first().cloned().unwrap_or(json!({
-        "available_cents": 0,
-        "currency": "USD"
-    }));
-
-    let currency = balance_row
-        .get("currency")
-        .and_then(|v| v.as_str())
-        .unwrap_or("USD")
-        .to_string();
+    let (available_cents, earned_cents, currency) = balance
+        .map(|b| (b.available_cents, b.earned_cents, b.currency))
+        .unwrap_or((0, 0, "USD".to_string()));
+    let reserved_cents =
+        match crate::payout_reserves::reserved_cents(&state, "agency", &user.id, &currency).await {
+            Ok(reserved) => reserved,
+            Err(e) => return AppError::from(e).into_parts(),
+        };
 
     (
         StatusCode::OK,
         Json(json!({
             "available_balance": {
-                "amount_cents": balance_row.get("available_cents").and_then(|v| v.as_i64()).unwrap_or(0),
-                "earned_cents": balance_row.get("earned_cents").and_then(|v| v.as_i64()).unwrap_or(0),
-                "currency": currency.clone()
+                "amount_cents": (available_cents - reserved_cents).max(0),
+                "earned_cents": earned_cents,
+                "currency": currency
+            },
+            "reserved_balance": {
+                "amount_cents": reserved_cents,
+                "currency": currency
             }
         })),
     )
@@ -3240,32 +3420,27 @@ pub async fn request_agency_payout(
     }
 
     // Likelee payouts are instant-only.
-    if !state.instant_payouts_enabled {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"status":"error","error":"instant_payouts_disabled"})),
-        );
-    }
-    if let Some(m) = payload.payout_method.as_deref() {
-        let m = m.to_lowercase();
-        if m == "standard" {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(json!({"status":"error","error":"standard_payouts_disabled"})),
-            );
-        }
-        if m != "instant" {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "status":"error",
-                    "error":"invalid_payout_method",
-                    "allowed":["instant"]
-                })),
-            );
-        }
-    }
-    let method = "instant".to_string();
+    let payout_method =
+        match crate::payout_fees::requested_method(&state, payload.payout_method.as_deref()) {
+            Ok(m) => m,
+            Err("invalid_payout_method") => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({
+                        "status":"error",
+                        "error":"invalid_payout_method",
+                        "allowed":["instant"]
+                    })),
+                );
+            }
+            Err(code) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"status":"error","error": code})),
+                );
+            }
+        };
+    let method = payout_method.as_str().to_string();
 
     // Get agency's available balance
     let balance_resp = match state
@@ -3279,7 +3454,7 @@ pub async fn request_agency_payout(
     {
         Ok(r) => r,
         Err(e) => {
-            return internal_error_response("request_agency_payout.fetch_balance", e);
+            return AppError::internal("request_agency_payout.fetch_balance", e).into_parts();
         }
     };
 
@@ -3290,6 +3465,12 @@ pub async fn request_agency_payout(
         .first()
         .and_then(|r| r.get("available_cents").and_then(|x| x.as_i64()))
         .unwrap_or(0);
+    // Reserved funds are not available to pay out.
+    let available =
+        match crate::payout_reserves::reserved_cents(&state, "agency", &user.id, &currency).await {
+            Ok(reserved) => available - reserved,
+            Err(e) => return AppError::from(e).into_parts(),
+        };
 
     info!(
         agency_id = %user.id,
@@ -3329,7 +3510,8 @@ pub async fn request_agency_payout(
     {
         Ok(r) => r,
         Err(e) => {
-            return internal_error_response("request_agency_payout.fetch_agency_account", e);
+            return AppError::internal("request_agency_payout.fetch_agency_account", e)
+                .into_parts();
         }
     };
 
@@ -3353,8 +3535,30 @@ pub async fn request_agency_payout(
         );
     }
 
-    // Compute fee
-    let fee_cents = (payload.amount_cents * (state.payout_fee_bps as i64) + 9999) / 10000;
+    // Fee from the agency's schedule; the request keeps it and the version.
+    let quote = match crate::payout_fees::quote(
+        &state,
+        &user.id,
+        payload.amount_cents,
+        &currency,
+        payout_method,
+    )
+    .await
+    {
+        Ok(q) => q,
+        Err(e) => return AppError::from(e).into_parts(),
+    };
+    if quote.net_cents <= 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "status":"error",
+                "error":"fee_exceeds_amount",
+                "fee_cents": quote.fee_cents
+            })),
+        );
+    }
+    let fee_cents = quote.fee_cents;
 
     // Auto-approve based on threshold
     let status = if (payload.amount_cents as u32) <= state.payout_auto_approve_threshold_cents {
@@ -3364,7 +3568,7 @@ pub async fn request_agency_payout(
     };
 
     // Create payout request
-    let body = json!({
+    let mut body = json!({
         "agency_id": user.id,
         "amount_cents": payload.amount_cents,
         "currency": currency,
@@ -3372,6 +3576,9 @@ pub async fn request_agency_payout(
         "status": status,
         "requested_at": chrono::Utc::now().to_rfc3339(),
     });
+    if let (Some(row), serde_json::Value::Object(fee)) = (body.as_object_mut(), quote.columns()) {
+        row.extend(fee);
+    }
 
     let ins = match state
         .pg
@@ -3382,7 +3589,7 @@ pub async fn request_agency_payout(
     {
         Ok(r) => r,
         Err(e) => {
-            return internal_error_response("request_agency_payout.insert_request", e);
+            return AppError::internal("request_agency_payout.insert_request", e).into_parts();
         }
     };
 
@@ -3445,7 +3652,7 @@ pub async fn request_agency_payout(
         match state
             .pg
             .from("agency_payout_requests")
-            .select("id,agency_id,amount_cents,currency,payout_method,status,requested_at,processed_at,stripe_transfer_id,stripe_payout_id,failure_reason")
+            .select("id,agency_id,amount_cents,currency,payout_method,status,requested_at,processed_at,stripe_transfer_id,stripe_payout_id,failure_reason,fee_cents,net_cents,fee_schedule_version")
             .eq("id", req_id)
             .limit(1)
             .execute()
@@ -3467,24 +3674,60 @@ pub async fn request_agency_payout(
         created.clone()
     };
 
+    if let Some(req_id) = created_id.as_deref() {
+        crate::audit::record(
+            &state,
+            crate::audit::AuditEvent::new(
+                &user.id,
+                "payout_request.created",
+                "agency_payout_requests",
+                req_id,
+            )
+            .actor(&user)
+            .title(format!(
+                "Payout of {:.2} {} requested",
+                payload.amount_cents as f64 / 100.0,
+                currency
+            ))
+            .change(&(), &payout_request),
+        )
+        .await;
+    }
+
     (
         StatusCode::OK,
         Json(json!({"status":"ok","payout_request": payout_request})),
     )
 }
 
+/// Platform fee on an agency payout request: the fee stored on it, or for
+/// requests made before fees were stored, the platform rate rounded up to
+/// the next cent.
+pub(crate) fn agency_payout_fee_cents(
+    state: &AppState,
+    stored_fee_cents: Option<i64>,
+    amount_cents: i64,
+) -> i64 {
+    stored_fee_cents.unwrap_or((amount_cents * (state.payout_fee_bps as i64) + 9999) / 10000)
+}
+
+/// Sends the payout for `payout_request_id` to the connected account.
+/// Executing the same request again (e.g. after a restart) reuses the payout
+/// already made for it: the one saved on the request, or, for a request left
+/// `processing`, the one Stripe lists with its id in the metadata. The
+/// idempotency key on the Stripe call only covers retries within 24 hours.
 #[allow(clippy::too_many_arguments)]
 pub async fn execute_agency_payout(
     state: &AppState,
     payout_request_id: &str,
-    _agency_id: &str,
+    agency_id: &str,
     stripe_account_id: &str,
     amount_cents: i64,
     fee_cents: i64,
     currency: &str,
     method: &str,
 ) -> Result<(), ()> {
-    let client = stripe_sdk::Client::new(state.stripe_secret_key.clone());
+    let client = state.stripe_client();
     let net_cents = amount_cents - fee_cents;
 
     info!(
@@ -3509,6 +3752,34 @@ pub async fn execute_agency_payout(
         return Err(());
     }
 
+    let previous: Vec<serde_json::Value> = match crate::repositories::fetch(
+        state
+            .pg
+            .from("agency_payout_requests")
+            .select("status,stripe_payout_id,requested_at")
+            .eq("id", payout_request_id)
+            .limit(1),
+    )
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            warn!(agency_payout_request_id = %payout_request_id, error = %e, "agency_payout_request_lookup_failed");
+            return Err(());
+        }
+    };
+    let previous = previous.into_iter().next().unwrap_or_default();
+    let stored_payout_id = previous
+        .get("stripe_payout_id")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(str::to_string);
+    let was_processing = previous.get("status").and_then(|v| v.as_str()) == Some("processing");
+    let requested_at = previous
+        .get("requested_at")
+        .and_then(|v| v.as_str())
+        .and_then(crate::jobs::runner::parse_ts);
+
     // Mark as processing
     let _ = state
         .pg
@@ -3544,7 +3815,9 @@ pub async fn execute_agency_payout(
     };
 
     let connected_client = match stripe_account_id.parse::<stripe_sdk::AccountId>() {
-        Ok(id) => client.clone().with_stripe_account(id),
+        Ok(id) => client.clone().with_stripe_account(id).with_strategy(
+            stripe_sdk::RequestStrategy::Idempotent(format!("agency-payout-{payout_request_id}")),
+        ),
         Err(_) => {
             let _ = state
                 .pg
@@ -3559,15 +3832,61 @@ pub async fn execute_agency_payout(
         }
     };
 
-    let mut payout_params = stripe_sdk::CreatePayout::new(net_cents, payout_currency);
-    payout_params.method = Some(stripe_sdk::PayoutMethod::Instant);
+    let existing_payout_id = match stored_payout_id {
+        Some(id) => Some(id),
+        // A previous attempt may have reached Stripe before it could save the id.
+        None if was_processing => {
+            match find_agency_payout(&connected_client, payout_request_id, requested_at).await {
+                Ok(found) => found,
+                Err(e) => {
+                    // Left `processing` so the next run checks again rather
+                    // than paying out under a new request.
+                    warn!(
+                        agency_payout_request_id = %payout_request_id,
+                        connected_account_id = %stripe_account_id,
+                        stripe_error = %e,
+                        "agency_payout_existing_lookup_failed"
+                    );
+                    return Err(());
+                }
+            }
+        }
+        None => None,
+    };
 
-    match stripe_sdk::Payout::create(&connected_client, payout_params).await {
-        Ok(p) => {
+    let created = match existing_payout_id {
+        Some(id) => {
             info!(
                 agency_payout_request_id = %payout_request_id,
                 connected_account_id = %stripe_account_id,
-                stripe_payout_id = %p.id.to_string(),
+                stripe_payout_id = %id,
+                "agency_payout_stripe_payout_reused"
+            );
+            Ok(id)
+        }
+        None => {
+            let mut payout_params = stripe_sdk::CreatePayout::new(net_cents, payout_currency);
+            payout_params.method = Some(stripe_sdk::PayoutMethod::Instant);
+            payout_params.metadata = Some(
+                [(
+                    "agency_payout_request_id".to_string(),
+                    payout_request_id.to_string(),
+                )]
+                .into_iter()
+                .collect(),
+            );
+            stripe_sdk::Payout::create(&connected_client, payout_params)
+                .await
+                .map(|p| p.id.to_string())
+        }
+    };
+
+    match created {
+        Ok(payout_id) => {
+            info!(
+                agency_payout_request_id = %payout_request_id,
+                connected_account_id = %stripe_account_id,
+                stripe_payout_id = %payout_id,
                 net_cents,
                 currency = %currency,
                 payout_method = %method,
@@ -3577,10 +3896,25 @@ pub async fn execute_agency_payout(
                 .pg
                 .from("agency_payout_requests")
                 .eq("id", payout_request_id)
-                .update(json!({"stripe_payout_id": p.id.to_string()}).to_string())
+                .update(json!({"stripe_payout_id": payout_id}).to_string())
                 .execute()
                 .await;
 
+            crate::ledger::record(
+                state,
+                payout_entry(
+                    format!("agency_payout:{payout_request_id}"),
+                    "agency_payout_request",
+                    payout_request_id,
+                    &crate::ledger::Account::agency(agency_id),
+                    stripe_account_id,
+                    amount_cents,
+                    fee_cents,
+                    currency,
+                ),
+            )
+            .await;
+
             if method == "instant" {
                 let _ = state
                     .pg
@@ -3621,6 +3955,69 @@ pub async fn execute_agency_payout(
     }
 }
 
+/// The payout Stripe made for `payout_request_id`, found by the metadata
+/// [`execute_agency_payout`] sets on it, among payouts created since the
+/// request was made.
+async fn find_agency_payout(
+    client: &stripe_sdk::Client,
+    payout_request_id: &str,
+    requested_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<Option<String>, String> {
+    let since = requested_at.unwrap_or_else(chrono::Utc::now) - chrono::Duration::hours(1);
+    let mut params = stripe_sdk::ListPayouts {
+        created: Some(stripe_sdk::RangeQuery::Bounds(stripe_sdk::RangeBounds {
+            gte: Some(since.timestamp()),
+            ..Default::default()
+        })),
+        limit: Some(100),
+        ..Default::default()
+    };
+    loop {
+        let page = stripe_sdk::Payout::list(client, &params)
+            .await
+            .map_err(|e| e.to_string())?;
+        let found = page.data.iter().find(|p| {
+            p.metadata
+                .as_ref()
+                .and_then(|m| m.get("agency_payout_request_id"))
+                .is_some_and(|id| id == payout_request_id)
+        });
+        if let Some(p) = found {
+            return Ok(Some(p.id.to_string()));
+        }
+        params.starting_after = page.data.last().map(|p| p.id.clone());
+        if !page.has_more || params.starting_after.is_none() {
+            return Ok(None);
+        }
+    }
+}
+
+/// Ledger entry for a payout from a connected account: the owner's balance
+/// goes down by the full amount, the connected account by what was paid out,
+/// and any payout fee becomes platform revenue.
+#[allow(clippy::too_many_arguments)]
+fn payout_entry(
+    idempotency_key: String,
+    reference_type: &str,
+    payout_request_id: &str,
+    owner: &crate::ledger::Account,
+    connected_account_id: &str,
+    amount_cents: i64,
+    fee_cents: i64,
+    currency: &str,
+) -> crate::ledger::Entry {
+    use crate::ledger::{Account, Entry, EntryKind};
+    Entry::new(EntryKind::Payout, idempotency_key, currency)
+        .memo("Payout to bank account")
+        .reference(reference_type, payout_request_id)
+        .debit(owner, amount_cents)
+        .credit(
+            &Account::connected(connected_account_id),
+            amount_cents - fee_cents,
+        )
+        .credit(&Account::platform_revenue(), fee_cents)
+}
+
 async fn fetch_connected_available_cents(
     client: &stripe_sdk::Client,
     connected_account_id: &str,
@@ -3643,34 +4040,8 @@ pub async fn get_agency_payout_history(
     State(state): State<AppState>,
     user: AuthUser,
 ) -> (StatusCode, Json<serde_json::Value>) {
-    let resp = match state
-        .pg
-        .from("agency_payout_requests")
-        .select("id,amount_cents,currency,payout_method,status,requested_at,processed_at,stripe_transfer_id,stripe_payout_id,failure_reason")
-        .eq("agency_id", &user.id)
-        .order("requested_at.desc")
-        .execute()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            return internal_error_response("get_agency_payout_history.fetch", e);
-        }
-    };
-
-    let status = resp.status();
-
-    let text = match resp.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            return internal_error_response("get_agency_payout_history.read_body", e);
-        }
-    };
-
-    if !status.is_success() {
-        return sanitized_error_response(status.as_u16(), text);
+    match state.repos.payouts.agency_history(&user.id).await {
+        Ok(rows) => (StatusCode::OK, Json(json!({"items": rows}))),
+        Err(e) => AppError::from(e).into_parts(),
     }
-
-    let rows: Vec<serde_json::Value> = serde_json::from_str(&text).unwrap_or_default();
-    (StatusCode::OK, Json(json!({"items": rows})))
 }
@@ -503,7 +503,7 @@ pub async fn update_talent_commission(
 
     let text_user = resp_user.text().await.unwrap_or_else(|_| "[]".to_string());
     let user_data: Vec<serde_json::Value> = serde_json::from_str(&text_user).unwrap_or_default();
-    let _old_custom_rate = user_data
+    let old_custom_rate = user_data
         .first()
         .and_then(|v| v.get("commission_rate"))
         .and_then(|v| v.as_f64());
@@ -597,6 +597,23 @@ pub async fn update_talent_commission(
     let _ = state.pg.from("talent_commissions").upsert(json!({"talent_id": payload.talent_id, "agency_id": agency_id, "commission_rate": new_rate_to_log, "updated_at": chrono::Utc::now().to_rfc3339()}).to_string()).on_conflict("talent_id, agency_id").execute().await;
     let _ = state.pg.from("talent_commission_history").insert(json!({"talent_id": payload.talent_id, "commission_rate": new_rate_to_log, "agency_id": agency_id}).to_string()).execute().await;
 
+    crate::audit::record(
+        &state,
+        crate::audit::AuditEvent::new(
+            agency_id,
+            "talent_commission.updated",
+            "talent_commissions",
+            &payload.talent_id,
+        )
+        .actor(&auth_user)
+        .title("Commission rate changed")
+        .change(
+            &json!({ "commission_rate": old_custom_rate }),
+            &json!({ "commission_rate": new_rate_to_log }),
+        ),
+    )
+    .await;
+
     Ok(StatusCode::OK)
 }
 
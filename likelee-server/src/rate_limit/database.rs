@@ -0,0 +1,90 @@
+use super::{Lockout, RateLimitStore, Window};
+use crate::repositories::fetch;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use postgrest::Postgrest;
+use serde::Deserialize;
+use serde_json::json;
+
+const TABLE: &str = "rate_limit_counters";
+
+/// Counters in `rate_limit_counters`, shared by every replica. Increments go
+/// through the `rate_limit_*` RPCs so they are atomic under concurrency.
+#[derive(Clone)]
+pub struct DatabaseStore {
+    pg: Postgrest,
+}
+
+impl DatabaseStore {
+    pub fn new(pg: Postgrest) -> Self {
+        Self { pg }
+    }
+}
+
+#[derive(Deserialize)]
+struct HitRow {
+    count: u32,
+    resets_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct LockRow {
+    locked_until: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+impl RateLimitStore for DatabaseStore {
+    async fn hit(&self, key: &str, window_secs: u64) -> Result<Window, String> {
+        let args = json!({ "p_key": key, "p_window_secs": window_secs });
+        let row: HitRow = fetch(self.pg.rpc("rate_limit_hit", args.to_string()))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Window {
+            count: row.count,
+            resets_at: row.resets_at,
+        })
+    }
+
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, String> {
+        let rows: Vec<LockRow> = fetch(
+            self.pg
+                .from(TABLE)
+                .select("locked_until")
+                .eq("key", key)
+                .limit(1),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next().and_then(|r| r.locked_until))
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        lockout: Lockout,
+    ) -> Result<Option<DateTime<Utc>>, String> {
+        let args = json!({
+            "p_key": key,
+            "p_max_failures": lockout.max_failures,
+            "p_lockout_secs": lockout.lockout_secs,
+        });
+        fetch(self.pg.rpc("rate_limit_record_failure", args.to_string()))
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn clear_failures(&self, key: &str) -> Result<(), String> {
+        let patch = json!({ "failures": 0, "failure_window_ends_at": null, "locked_until": null });
+        let _: Vec<serde_json::Value> =
+            fetch(self.pg.from(TABLE).eq("key", key).update(patch.to_string()))
+                .await
+                .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn prune(&self) -> Result<u64, String> {
+        fetch(self.pg.rpc("rate_limit_prune", "{}"))
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,141 @@
+use super::{Lockout, RateLimitStore, Window};
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Default)]
+struct Entry {
+    hits: u32,
+    window_ends_at: Option<DateTime<Utc>>,
+    failures: u32,
+    failure_window_ends_at: Option<DateTime<Utc>>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+impl Entry {
+    fn expired(&self, now: DateTime<Utc>) -> bool {
+        [
+            self.window_ends_at,
+            self.failure_window_ends_at,
+            self.locked_until,
+        ]
+        .iter()
+        .flatten()
+        .all(|t| *t <= now)
+    }
+}
+
+/// Per-process counters. Each replica limits on its own.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl MemoryStore {
+    fn with_entry<T>(&self, key: &str, f: impl FnOnce(&mut Entry) -> T) -> T {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        f(entries.entry(key.to_string()).or_default())
+    }
+}
+
+fn secs(secs: u64) -> Duration {
+    Duration::seconds(i64::try_from(secs).unwrap_or(i64::MAX / 1000))
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn hit(&self, key: &str, window_secs: u64) -> Result<Window, String> {
+        let now = Utc::now();
+        Ok(self.with_entry(key, |e| {
+            match e.window_ends_at {
+                Some(ends) if ends > now => e.hits += 1,
+                _ => {
+                    e.hits = 1;
+                    e.window_ends_at = Some(now + secs(window_secs));
+                }
+            }
+            Window {
+                count: e.hits,
+                resets_at: e.window_ends_at.unwrap_or(now),
+            }
+        }))
+    }
+
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, String> {
+        let now = Utc::now();
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(entries
+            .get(key)
+            .and_then(|e| e.locked_until)
+            .filter(|t| *t > now))
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        lockout: Lockout,
+    ) -> Result<Option<DateTime<Utc>>, String> {
+        let now = Utc::now();
+        Ok(self.with_entry(key, |e| {
+            match e.failure_window_ends_at {
+                Some(ends) if ends > now => e.failures += 1,
+                _ => {
+                    e.failures = 1;
+                    e.failure_window_ends_at = Some(now + secs(lockout.lockout_secs));
+                }
+            }
+            if e.failures < lockout.max_failures {
+                return None;
+            }
+            let until = now + secs(lockout.lockout_secs);
+            e.failures = 0;
+            e.failure_window_ends_at = None;
+            e.locked_until = Some(until);
+            Some(until)
+        }))
+    }
+
+    async fn clear_failures(&self, key: &str) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(key);
+        Ok(())
+    }
+
+    async fn prune(&self) -> Result<u64, String> {
+        let now = Utc::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let before = entries.len();
+        entries.retain(|_, e| !e.expired(now));
+        Ok((before - entries.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn counts_hits_and_locks_after_max_failures() {
+        let store = MemoryStore::default();
+        for expected in 1..=3 {
+            assert_eq!(store.hit("ip:1", 60).await.unwrap().count, expected);
+        }
+        assert_eq!(store.hit("ip:2", 60).await.unwrap().count, 1);
+
+        let lockout = Lockout {
+            max_failures: 3,
+            lockout_secs: 60,
+        };
+        assert_eq!(store.record_failure("fail", lockout).await.unwrap(), None);
+        store.clear_failures("fail").await.unwrap();
+        for _ in 0..2 {
+            assert_eq!(store.record_failure("fail", lockout).await.unwrap(), None);
+        }
+        assert!(store.locked_until("fail").await.unwrap().is_none());
+        let until = store.record_failure("fail", lockout).await.unwrap();
+        assert!(until.is_some());
+        assert_eq!(store.locked_until("fail").await.unwrap(), until);
+        assert_eq!(store.prune().await.unwrap(), 0);
+    }
+}
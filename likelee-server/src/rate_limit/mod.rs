@@ -0,0 +1,469 @@
+//! Rate limiting for unauthenticated token endpoints.
+//!
+//! Route groups are wrapped with [`enforce`] through `route_layer`. Every
+//! request counts against a per-IP and a per-token fixed window, and groups
+//! with a credential header (package passwords) lock a token out for one IP
+//! after repeated rejected credentials, and for everyone after many more
+//! from any number of IPs. Counters live in a
+//! [`RateLimitStore`]: process memory by default, or Postgres
+//! (`RATE_LIMIT_STORE=database`) so that replicas share them.
+
+pub mod database;
+pub mod memory;
+
+use crate::config::ServerConfig;
+use crate::errors::AppError;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, RawPathParams, Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use postgrest::Postgrest;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::warn;
+
+pub use database::DatabaseStore;
+pub use memory::MemoryStore;
+
+/// At most `limit` requests per fixed window of `window_secs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub limit: u32,
+    pub window_secs: u64,
+}
+
+/// After `max_failures` rejected credentials within `lockout_secs`, further
+/// requests are refused for `lockout_secs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lockout {
+    pub max_failures: u32,
+    pub lockout_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupConfig {
+    pub per_ip: Option<Quota>,
+    pub per_token: Option<Quota>,
+    /// Rejected credentials for one token from one IP.
+    pub lockout: Option<Lockout>,
+    /// Rejected credentials for one token from any IP, so rotating addresses
+    /// does not reset the count. Locks out legitimate visitors too, hence a
+    /// much higher threshold than `lockout`.
+    pub token_lockout: Option<Lockout>,
+    /// Only 401 responses to requests carrying this header count as failed
+    /// attempts, so opening a protected link without a password does not.
+    pub credential_header: Option<&'static str>,
+}
+
+impl GroupConfig {
+    /// Applies overrides written as
+    /// `ip=30/60,token=60/60,lockout=5/900,token_lockout=20/3600`, i.e.
+    /// `limit/window_secs` for quotas and `failures/secs` for lockouts.
+    /// `off` disables a rule, e.g. `token=off`.
+    pub fn with_spec(mut self, spec: &str) -> Result<Self, String> {
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (rule, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected rule=value, got `{part}`"))?;
+            let pair = if value == "off" {
+                None
+            } else {
+                let (a, b) = value
+                    .split_once('/')
+                    .ok_or_else(|| format!("expected n/secs in `{part}`"))?;
+                let a: u32 = a
+                    .parse()
+                    .map_err(|_| format!("invalid count in `{part}`"))?;
+                let b: u64 = b
+                    .parse()
+                    .map_err(|_| format!("invalid seconds in `{part}`"))?;
+                if a == 0 || b == 0 {
+                    return Err(format!("`{part}` must be positive"));
+                }
+                Some((a, b))
+            };
+            match rule.trim() {
+                "ip" => self.per_ip = pair.map(|(limit, window_secs)| Quota { limit, window_secs }),
+                "token" => {
+                    self.per_token = pair.map(|(limit, window_secs)| Quota { limit, window_secs })
+                }
+                "lockout" => {
+                    self.lockout = pair.map(|(max_failures, lockout_secs)| Lockout {
+                        max_failures,
+                        lockout_secs,
+                    })
+                }
+                "token_lockout" => {
+                    self.token_lockout = pair.map(|(max_failures, lockout_secs)| Lockout {
+                        max_failures,
+                        lockout_secs,
+                    })
+                }
+                other => return Err(format!("unknown rate limit rule `{other}`")),
+            }
+        }
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    /// `/api/public/packages/:token` and its sub-routes; password protected.
+    PublicPackages,
+    /// `/api/public/catalogs/:token`.
+    PublicCatalogs,
+    /// `/api/invites/agency-talent/:token/magic-link`.
+    TalentInvites,
+}
+
+impl RouteGroup {
+    pub const ALL: [RouteGroup; 3] = [
+        RouteGroup::PublicPackages,
+        RouteGroup::PublicCatalogs,
+        RouteGroup::TalentInvites,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RouteGroup::PublicPackages => "public_packages",
+            RouteGroup::PublicCatalogs => "public_catalogs",
+            RouteGroup::TalentInvites => "talent_invites",
+        }
+    }
+
+    pub fn defaults(self) -> GroupConfig {
+        let quota = |limit, window_secs| Some(Quota { limit, window_secs });
+        match self {
+            RouteGroup::PublicPackages => GroupConfig {
+                per_ip: quota(60, 60),
+                per_token: quota(120, 60),
+                lockout: Some(Lockout {
+                    max_failures: 5,
+                    lockout_secs: 900,
+                }),
+                token_lockout: Some(Lockout {
+                    max_failures: 20,
+                    lockout_secs: 3600,
+                }),
+                credential_header: Some("x-package-password"),
+            },
+            RouteGroup::PublicCatalogs => GroupConfig {
+                per_ip: quota(60, 60),
+                per_token: quota(120, 60),
+                lockout: None,
+                token_lockout: None,
+                credential_header: None,
+            },
+            RouteGroup::TalentInvites => GroupConfig {
+                per_ip: quota(20, 60),
+                per_token: quota(10, 60),
+                lockout: None,
+                token_lockout: None,
+                credential_header: None,
+            },
+        }
+    }
+}
+
+/// State of a fixed window after counting a request.
+#[derive(Debug, Clone, Copy)]
+pub struct Window {
+    pub count: u32,
+    pub resets_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Counts one request against `key` in a fixed window of `window_secs`.
+    async fn hit(&self, key: &str, window_secs: u64) -> Result<Window, String>;
+
+    /// Lock expiry for `key`, if it is currently locked out.
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, String>;
+
+    /// Records a rejected credential for `key`. Returns the lock expiry when
+    /// this failure reaches `lockout.max_failures`.
+    async fn record_failure(
+        &self,
+        key: &str,
+        lockout: Lockout,
+    ) -> Result<Option<DateTime<Utc>>, String>;
+
+    /// Forgets failed attempts for `key` after a successful one.
+    async fn clear_failures(&self, key: &str) -> Result<(), String>;
+
+    /// Drops expired counters; returns how many were removed.
+    async fn prune(&self) -> Result<u64, String>;
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    groups: Arc<HashMap<RouteGroup, GroupConfig>>,
+    enabled: bool,
+    trust_proxy: bool,
+}
+
+impl RateLimiter {
+    /// All groups at their defaults, keyed by the peer address.
+    pub fn new(store: Arc<dyn RateLimitStore>) -> Self {
+        Self {
+            store,
+            groups: Arc::new(RouteGroup::ALL.map(|g| (g, g.defaults())).into()),
+            enabled: true,
+            trust_proxy: false,
+        }
+    }
+
+    /// Builds the limiter described by the `RATE_LIMIT_*` settings.
+    pub fn from_config(cfg: &ServerConfig, pg: Postgrest) -> Result<Self, String> {
+        let store: Arc<dyn RateLimitStore> = match cfg.rate_limit_store.as_str() {
+            "memory" => Arc::new(MemoryStore::default()),
+            "database" => Arc::new(DatabaseStore::new(pg)),
+            other => return Err(format!("unknown RATE_LIMIT_STORE `{other}`")),
+        };
+        let mut limiter = Self::new(store)
+            .enabled(cfg.rate_limit_enabled)
+            .trust_proxy(cfg.rate_limit_trust_proxy);
+        for (group, spec) in [
+            (RouteGroup::PublicPackages, &cfg.rate_limit_public_packages),
+            (RouteGroup::PublicCatalogs, &cfg.rate_limit_public_catalogs),
+            (RouteGroup::TalentInvites, &cfg.rate_limit_talent_invites),
+        ] {
+            let config = group
+                .defaults()
+                .with_spec(spec)
+                .map_err(|e| format!("{}: {e}", group.name()))?;
+            limiter = limiter.with_group(group, config);
+        }
+        Ok(limiter)
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Take the client address from `X-Real-IP` / `X-Forwarded-For`, as set
+    /// by the nginx in front of the server. Only safe behind such a proxy.
+    pub fn trust_proxy(mut self, trust: bool) -> Self {
+        self.trust_proxy = trust;
+        self
+    }
+
+    pub fn with_group(mut self, group: RouteGroup, config: GroupConfig) -> Self {
+        Arc::make_mut(&mut self.groups).insert(group, config);
+        self
+    }
+
+    /// Middleware state for [`enforce`] on the routes of `group`.
+    pub fn group(&self, group: RouteGroup) -> GroupLimiter {
+        GroupLimiter {
+            limiter: self.clone(),
+            group,
+        }
+    }
+
+    pub async fn prune(&self) -> Result<u64, String> {
+        self.store.prune().await
+    }
+
+    fn config(&self, group: RouteGroup) -> GroupConfig {
+        self.groups
+            .get(&group)
+            .cloned()
+            .unwrap_or_else(|| group.defaults())
+    }
+
+    fn client_ip(&self, req: &Request) -> String {
+        if self.trust_proxy {
+            let header = |name| {
+                req.headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+            };
+            // nginx appends the address it saw, so the last hop is the trusted one.
+            let forwarded = header("x-forwarded-for")
+                .and_then(|v| v.rsplit(',').next())
+                .map(str::trim);
+            if let Some(ip) = header("x-real-ip").or(forwarded) {
+                return ip.to_string();
+            }
+        }
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+}
+
+#[derive(Clone)]
+pub struct GroupLimiter {
+    limiter: RateLimiter,
+    group: RouteGroup,
+}
+
+/// Tokens are secrets; counters only ever see a digest of them.
+fn token_key(token: &str) -> String {
+    hex::encode(&Sha256::digest(token.as_bytes())[..12])
+}
+
+fn too_many(code: &str, message: &str, until: DateTime<Utc>) -> Response {
+    let retry_after = (until - Utc::now()).num_seconds().max(1);
+    let mut resp = AppError::Status {
+        status: StatusCode::TOO_MANY_REQUESTS,
+        code: Some(code.to_string()),
+        message: message.to_string(),
+    }
+    .into_response();
+    if let Ok(v) = HeaderValue::from_str(&retry_after.to_string()) {
+        resp.headers_mut().insert("retry-after", v);
+    }
+    resp
+}
+
+/// Middleware applied with `from_fn_with_state(limiter.group(..), enforce)`.
+/// Store failures are logged and let the request through.
+pub async fn enforce(
+    State(GroupLimiter { limiter, group }): State<GroupLimiter>,
+    params: RawPathParams,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !limiter.enabled {
+        return next.run(req).await;
+    }
+    let config = limiter.config(group);
+    let store = &limiter.store;
+    let ip = limiter.client_ip(&req);
+    let token = params
+        .iter()
+        .find(|(name, _)| *name == "token")
+        .map(|(_, value)| token_key(value));
+    let g = group.name();
+
+    // The per-IP lockout, then the one shared by every IP (`shared`).
+    let lockouts = [
+        config
+            .lockout
+            .zip(token.as_ref())
+            .map(|(l, t)| (l, format!("{g}:fail:{t}:{ip}"), false)),
+        config
+            .token_lockout
+            .zip(token.as_ref())
+            .map(|(l, t)| (l, format!("{g}:fail:{t}"), true)),
+    ];
+    for (_, key, _) in lockouts.iter().flatten() {
+        match store.locked_until(key).await {
+            Ok(Some(until)) if until > Utc::now() => {
+                return too_many(
+                    "too_many_attempts",
+                    "Too many failed attempts. Try again later.",
+                    until,
+                );
+            }
+            Ok(_) => {}
+            Err(e) => warn!(group = g, error = %e, "rate limit lookup failed"),
+        }
+    }
+
+    let quotas = [
+        config.per_ip.map(|q| (q, format!("{g}:ip:{ip}"))),
+        config
+            .per_token
+            .zip(token.as_ref())
+            .map(|(q, t)| (q, format!("{g}:token:{t}"))),
+    ];
+    for (quota, key) in quotas.into_iter().flatten() {
+        match store.hit(&key, quota.window_secs).await {
+            Ok(window) if window.count > quota.limit => {
+                warn!(group = g, %ip, key = %key, "rate limit exceeded");
+                return too_many(
+                    "rate_limited",
+                    "Too many requests. Try again later.",
+                    window.resets_at,
+                );
+            }
+            Ok(_) => {}
+            Err(e) => warn!(group = g, error = %e, "rate limit update failed"),
+        }
+    }
+
+    let sent_credential = config
+        .credential_header
+        .is_some_and(|h| req.headers().contains_key(h));
+    let resp = next.run(req).await;
+
+    if !sent_credential {
+        return resp;
+    }
+    let status = resp.status();
+    for (lockout, key, shared) in lockouts.iter().flatten() {
+        let result = if status == StatusCode::UNAUTHORIZED {
+            store.record_failure(key, *lockout).await.map(|locked| {
+                if let Some(until) = locked {
+                    warn!(group = g, %ip, %until, key = %key, "locked out after repeated failed attempts");
+                }
+            })
+        } else if status.is_success() && !shared {
+            // A success only vouches for this IP; the shared count runs out
+            // on its own so that one correct guess cannot reset it.
+            store.clear_failures(key).await
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            warn!(group = g, error = %e, "rate limit failure tracking failed");
+        }
+    }
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_overrides_defaults() {
+        let config = RouteGroup::PublicPackages
+            .defaults()
+            .with_spec("ip=5/10, token=off,lockout=3/60,token_lockout=off")
+            .unwrap();
+        assert_eq!(
+            config.per_ip,
+            Some(Quota {
+                limit: 5,
+                window_secs: 10
+            })
+        );
+        assert_eq!(config.per_token, None);
+        assert_eq!(
+            config.lockout,
+            Some(Lockout {
+                max_failures: 3,
+                lockout_secs: 60
+            })
+        );
+        assert_eq!(config.token_lockout, None);
+        assert_eq!(
+            RouteGroup::PublicCatalogs.defaults().with_spec("").unwrap(),
+            RouteGroup::PublicCatalogs.defaults()
+        );
+    }
+
+    #[test]
+    fn spec_rejects_malformed_rules() {
+        let defaults = RouteGroup::TalentInvites.defaults();
+        assert!(defaults.clone().with_spec("ip=5").is_err());
+        assert!(defaults.clone().with_spec("ip=0/60").is_err());
+        assert!(defaults.clone().with_spec("burst=5/60").is_err());
+        assert!(defaults.with_spec("token=x/60").is_err());
+    }
+}
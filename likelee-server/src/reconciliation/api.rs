@@ -0,0 +1,134 @@
+//! Discrepancy reports for agencies and ops. Runs are started by the
+//! `stripe_reconciliation` job, or on demand through
+//! `POST /api/admin/jobs/stripe_reconciliation/run`.
+
+use super::{
+    discrepancies, find_run, list_runs, Discrepancy, DiscrepancyFilter, DiscrepancyKind, Run,
+};
+use crate::auth::{AuthUser, RoleGuard};
+use crate::config::AppState;
+use crate::errors::{AppError, AppResult};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct ReportParams {
+    pub run_id: Option<String>,
+    pub kind: Option<String>,
+    pub object: Option<String>,
+    pub agency_id: Option<String>,
+}
+
+/// The window an agency's report covers. Run totals and errors span every
+/// agency and stay with ops.
+#[derive(Debug, Serialize)]
+pub struct ReportWindow {
+    pub run_id: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgencyReport {
+    /// `None` until the first run completes.
+    pub run: Option<ReportWindow>,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminReport {
+    pub run: Option<Run>,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+fn parse_kind(kind: Option<&str>) -> AppResult<Option<DiscrepancyKind>> {
+    kind.map(|k| {
+        DiscrepancyKind::parse(k)
+            .ok_or_else(|| AppError::BadRequest("invalid_discrepancy_kind".to_string()))
+    })
+    .transpose()
+}
+
+/// GET /api/agency/reconciliation: the agency's discrepancies from the latest
+/// completed run, optionally narrowed by `kind` and `object`.
+pub async fn agency_report(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<ReportParams>,
+) -> AppResult<Json<AgencyReport>> {
+    RoleGuard::new(vec!["agency"]).check(&user.role)?;
+    let filter = DiscrepancyFilter {
+        agency_id: Some(&user.id),
+        kind: parse_kind(params.kind.as_deref())?,
+        object: params.object.as_deref(),
+    };
+    let Some(run) = find_run(&state, None).await? else {
+        return Ok(Json(AgencyReport {
+            run: None,
+            discrepancies: Vec::new(),
+        }));
+    };
+    let found = discrepancies(&state, &run.id, &filter).await?;
+    Ok(Json(AgencyReport {
+        run: Some(ReportWindow {
+            run_id: run.id,
+            window_start: run.window_start,
+            window_end: run.window_end,
+            finished_at: run.finished_at,
+        }),
+        discrepancies: found,
+    }))
+}
+
+/// GET /api/admin/reconciliation: a run (the latest completed one unless
+/// `run_id` is given) with its discrepancies.
+pub async fn admin_report(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<ReportParams>,
+) -> AppResult<Json<AdminReport>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let filter = DiscrepancyFilter {
+        agency_id: params.agency_id.as_deref(),
+        kind: parse_kind(params.kind.as_deref())?,
+        object: params.object.as_deref(),
+    };
+    let run = match params.run_id.as_deref() {
+        Some(id) => Some(
+            find_run(&state, Some(id))
+                .await?
+                .ok_or_else(|| AppError::NotFound("reconciliation_run_not_found".to_string()))?,
+        ),
+        None => find_run(&state, None).await?,
+    };
+    let found = match &run {
+        Some(run) => discrepancies(&state, &run.id, &filter).await?,
+        None => Vec::new(),
+    };
+    Ok(Json(AdminReport {
+        run,
+        discrepancies: found,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunListParams {
+    pub limit: Option<usize>,
+}
+
+/// GET /api/admin/reconciliation/runs: recent runs, newest first.
+pub async fn runs(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<RunListParams>,
+) -> AppResult<Json<Vec<Run>>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    Ok(Json(
+        list_runs(&state, params.limit.unwrap_or(20).min(100)).await?,
+    ))
+}
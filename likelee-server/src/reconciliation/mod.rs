@@ -0,0 +1,903 @@
+//! Reconciliation of local payment records against Stripe.
+//!
+//! A run pulls the Stripe activity of a time window and matches it with what
+//! we recorded for it:
+//!
+//! - platform transfers against `agency_payment_link_transfers`, by transfer
+//!   id, comparing the amount left after reversals;
+//! - payouts of the connected accounts of agencies and creators that
+//!   requested one in the window against `agency_payout_requests` and
+//!   `creator_payout_requests`, by payout id, comparing the net paid out;
+//! - platform charge balance transactions against `licensing_payouts`, by
+//!   payment intent, comparing the gross charged.
+//!
+//! Every difference is stored as a [`Discrepancy`] of the run: a local record
+//! Stripe does not know (`missing`), a Stripe object nothing local accounts
+//! for (`extra`), or a pair whose amounts disagree (`amount_mismatch`).
+//! Nothing is corrected automatically.
+
+pub mod api;
+
+use crate::config::AppState;
+use crate::jobs::runner::ts;
+use crate::repositories::{fetch, RepoError};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use tracing::{info, warn};
+
+pub const RUNS_TABLE: &str = "reconciliation_runs";
+pub const DISCREPANCIES_TABLE: &str = "reconciliation_discrepancies";
+
+/// How far back a scheduled run looks. Runs overlap so activity that settles
+/// late (pending payouts, delayed webhooks) is seen again.
+pub const DEFAULT_LOOKBACK_DAYS: i64 = 7;
+
+const RUN_COLUMNS: &str = "id,status,window_start,window_end,missing_count,extra_count,amount_mismatch_count,errors,error,started_at,finished_at";
+const DISCREPANCY_COLUMNS: &str = "id,run_id,kind,object,local_table,local_id,stripe_id,stripe_account_id,agency_id,creator_id,currency,expected_cents,actual_cents,created_at";
+const PAGE_SIZE: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscrepancyKind {
+    /// Recorded locally but not found in Stripe.
+    Missing,
+    /// Found in Stripe with no local record.
+    Extra,
+    AmountMismatch,
+}
+
+impl DiscrepancyKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiscrepancyKind::Missing => "missing",
+            DiscrepancyKind::Extra => "extra",
+            DiscrepancyKind::AmountMismatch => "amount_mismatch",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "missing" => Some(DiscrepancyKind::Missing),
+            "extra" => Some(DiscrepancyKind::Extra),
+            "amount_mismatch" => Some(DiscrepancyKind::AmountMismatch),
+            _ => None,
+        }
+    }
+}
+
+/// A local record of money that should have moved in Stripe.
+#[derive(Debug, Clone, Default)]
+pub struct LocalRecord {
+    pub table: &'static str,
+    pub id: String,
+    /// The Stripe object it should match, if one was recorded.
+    pub stripe_id: Option<String>,
+    pub stripe_account_id: Option<String>,
+    pub agency_id: Option<String>,
+    pub creator_id: Option<String>,
+    pub currency: String,
+    pub amount_cents: i64,
+}
+
+/// A Stripe object as far as reconciliation is concerned.
+#[derive(Debug, Clone, Default)]
+pub struct StripeRecord {
+    pub id: String,
+    pub stripe_account_id: Option<String>,
+    pub agency_id: Option<String>,
+    pub creator_id: Option<String>,
+    pub currency: String,
+    pub amount_cents: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Discrepancy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    pub kind: DiscrepancyKind,
+    /// `charge`, `transfer` or `payout`.
+    pub object: String,
+    pub local_table: Option<String>,
+    pub local_id: Option<String>,
+    pub stripe_id: Option<String>,
+    pub stripe_account_id: Option<String>,
+    pub agency_id: Option<String>,
+    pub creator_id: Option<String>,
+    pub currency: Option<String>,
+    /// What the local record says.
+    pub expected_cents: Option<i64>,
+    /// What Stripe says.
+    pub actual_cents: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub id: String,
+    pub status: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub missing_count: i64,
+    pub extra_count: i64,
+    pub amount_mismatch_count: i64,
+    pub errors: Value,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+fn same_currency(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+/// Matches local records with Stripe objects by Stripe id. Local records
+/// without a Stripe id count as missing; Stripe objects matched by no local
+/// record count as extra.
+pub fn compare(object: &str, local: &[LocalRecord], stripe: &[StripeRecord]) -> Vec<Discrepancy> {
+    let by_id: HashMap<&str, &StripeRecord> = stripe.iter().map(|s| (s.id.as_str(), s)).collect();
+    let mut matched = BTreeSet::new();
+    let mut out = Vec::new();
+
+    for record in local {
+        let found = record
+            .stripe_id
+            .as_deref()
+            .and_then(|id| by_id.get(id).copied());
+        let discrepancy = |kind, actual_cents| Discrepancy {
+            id: None,
+            run_id: None,
+            kind,
+            object: object.to_string(),
+            local_table: Some(record.table.to_string()),
+            local_id: Some(record.id.clone()),
+            stripe_id: record.stripe_id.clone(),
+            stripe_account_id: record.stripe_account_id.clone(),
+            agency_id: record.agency_id.clone(),
+            creator_id: record.creator_id.clone(),
+            currency: Some(record.currency.clone()),
+            expected_cents: Some(record.amount_cents),
+            actual_cents,
+            created_at: None,
+        };
+        match found {
+            None => out.push(discrepancy(DiscrepancyKind::Missing, None)),
+            Some(s) => {
+                matched.insert(s.id.as_str());
+                if s.amount_cents != record.amount_cents
+                    || !same_currency(&s.currency, &record.currency)
+                {
+                    out.push(discrepancy(
+                        DiscrepancyKind::AmountMismatch,
+                        Some(s.amount_cents),
+                    ));
+                }
+            }
+        }
+    }
+
+    for s in stripe.iter().filter(|s| !matched.contains(s.id.as_str())) {
+        out.push(Discrepancy {
+            id: None,
+            run_id: None,
+            kind: DiscrepancyKind::Extra,
+            object: object.to_string(),
+            local_table: None,
+            local_id: None,
+            stripe_id: Some(s.id.clone()),
+            stripe_account_id: s.stripe_account_id.clone(),
+            agency_id: s.agency_id.clone(),
+            creator_id: s.creator_id.clone(),
+            currency: Some(s.currency.clone()),
+            expected_cents: None,
+            actual_cents: Some(s.amount_cents),
+            created_at: None,
+        });
+    }
+    out
+}
+
+/// Stripe transfers as reconciled: transfers made for a payment link, net of
+/// reversals, with transfers that restore a reversed one after a won dispute
+/// folded into the transfer they restore.
+pub fn net_transfers(transfers: &[stripe_sdk::Transfer]) -> Vec<StripeRecord> {
+    let metadata = |t: &stripe_sdk::Transfer, key: &str| {
+        t.metadata.get(key).filter(|v| !v.is_empty()).cloned()
+    };
+    let mut restored: HashMap<String, i64> = HashMap::new();
+    for t in transfers {
+        if let Some(original) = metadata(t, "restores_transfer_id") {
+            *restored.entry(original).or_default() += t.amount - t.amount_reversed;
+        }
+    }
+    transfers
+        .iter()
+        .filter(|t| metadata(t, "payment_link_id").is_some())
+        .filter(|t| metadata(t, "restores_transfer_id").is_none())
+        .map(|t| StripeRecord {
+            id: t.id.to_string(),
+            stripe_account_id: t.destination.as_ref().map(|d| d.id().to_string()),
+            agency_id: metadata(t, "agency_id"),
+            creator_id: None,
+            currency: t.currency.to_string(),
+            amount_cents: t.amount - t.amount_reversed
+                + restored.get(t.id.as_str()).copied().unwrap_or(0),
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub run_id: String,
+    pub missing: usize,
+    pub extra: usize,
+    pub amount_mismatch: usize,
+    pub errors: Vec<Value>,
+}
+
+fn stripe_range(start: DateTime<Utc>, end: DateTime<Utc>) -> stripe_sdk::RangeQuery<i64> {
+    stripe_sdk::RangeQuery::Bounds(stripe_sdk::RangeBounds {
+        gte: Some(start.timestamp()),
+        lt: Some(end.timestamp()),
+        ..Default::default()
+    })
+}
+
+async fn list_transfers(
+    client: &stripe_sdk::Client,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<stripe_sdk::Transfer>, String> {
+    let mut params = stripe_sdk::ListTransfers {
+        created: Some(stripe_range(start, end)),
+        limit: Some(PAGE_SIZE),
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    loop {
+        let page = stripe_sdk::Transfer::list(client, &params)
+            .await
+            .map_err(|e| e.to_string())?;
+        params.starting_after = page.data.last().map(|t| t.id.clone());
+        out.extend(page.data);
+        if !page.has_more || params.starting_after.is_none() {
+            return Ok(out);
+        }
+    }
+}
+
+async fn list_payouts(
+    client: &stripe_sdk::Client,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<stripe_sdk::Payout>, String> {
+    let mut params = stripe_sdk::ListPayouts {
+        created: Some(stripe_range(start, end)),
+        limit: Some(PAGE_SIZE),
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    loop {
+        let page = stripe_sdk::Payout::list(client, &params)
+            .await
+            .map_err(|e| e.to_string())?;
+        params.starting_after = page.data.last().map(|p| p.id.clone());
+        out.extend(page.data);
+        if !page.has_more || params.starting_after.is_none() {
+            return Ok(out);
+        }
+    }
+}
+
+/// Charge balance transactions with their charge expanded, for the payment
+/// intent it belongs to.
+async fn list_charges(
+    client: &stripe_sdk::Client,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<stripe_sdk::BalanceTransaction>, String> {
+    let mut params = stripe_sdk::ListBalanceTransactions {
+        created: Some(stripe_range(start, end)),
+        type_: Some("charge"),
+        expand: &["data.source"],
+        limit: Some(PAGE_SIZE),
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    loop {
+        let page = stripe_sdk::BalanceTransaction::list(client, &params)
+            .await
+            .map_err(|e| e.to_string())?;
+        params.starting_after = page.data.last().map(|b| b.id.clone());
+        out.extend(page.data);
+        if !page.has_more || params.starting_after.is_none() {
+            return Ok(out);
+        }
+    }
+}
+
+fn charge_payment_intent(bt: &stripe_sdk::BalanceTransaction) -> Option<String> {
+    match bt.source.as_ref()?.as_object()? {
+        stripe_sdk::BalanceTransactionSourceUnion::Charge(charge) => {
+            charge.payment_intent.as_ref().map(|pi| pi.id().to_string())
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TransferRow {
+    id: String,
+    payment_link_id: String,
+    recipient_type: String,
+    recipient_id: String,
+    stripe_connect_account_id: Option<String>,
+    stripe_transfer_id: Option<String>,
+    amount_cents: i64,
+    #[serde(default)]
+    reversed_cents: i64,
+    currency: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LinkRow {
+    id: String,
+    agency_id: String,
+    stripe_payment_intent_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PayoutRow {
+    id: String,
+    #[serde(default)]
+    agency_id: Option<String>,
+    #[serde(default)]
+    creator_id: Option<String>,
+    amount_cents: i64,
+    currency: String,
+    stripe_payout_id: Option<String>,
+    #[serde(default)]
+    fee_cents: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LicensingPayoutRow {
+    agency_id: String,
+    stripe_payment_intent_id: String,
+    amount_cents: i64,
+    #[serde(default)]
+    talent_earnings_cents: Option<i64>,
+    #[serde(default)]
+    platform_fee_cents: Option<i64>,
+    currency: String,
+}
+
+async fn reconcile_transfers(
+    state: &AppState,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<Discrepancy>, String> {
+    let rows: Vec<TransferRow> = fetch(
+        state
+            .pg
+            .from("agency_payment_link_transfers")
+            .select("id,payment_link_id,recipient_type,recipient_id,stripe_connect_account_id,stripe_transfer_id,amount_cents,reversed_cents,currency")
+            .not("is", "stripe_transfer_id", "null")
+            .gte("created_at", ts(start))
+            .lt("created_at", ts(end)),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let transfers = list_transfers(&state.stripe_client(), start, end).await?;
+
+    let link_ids: BTreeSet<&str> = rows.iter().map(|r| r.payment_link_id.as_str()).collect();
+    let links: Vec<LinkRow> = if link_ids.is_empty() {
+        Vec::new()
+    } else {
+        fetch(
+            state
+                .pg
+                .from("agency_payment_links")
+                .select("id,agency_id,stripe_payment_intent_id")
+                .in_("id", link_ids),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+    };
+    let link_agency: HashMap<&str, &str> = links
+        .iter()
+        .map(|l| (l.id.as_str(), l.agency_id.as_str()))
+        .collect();
+
+    let local: Vec<LocalRecord> = rows
+        .iter()
+        .map(|r| LocalRecord {
+            table: "agency_payment_link_transfers",
+            id: r.id.clone(),
+            stripe_id: r.stripe_transfer_id.clone(),
+            stripe_account_id: r.stripe_connect_account_id.clone(),
+            agency_id: if r.recipient_type == "agency" {
+                Some(r.recipient_id.clone())
+            } else {
+                link_agency
+                    .get(r.payment_link_id.as_str())
+                    .map(|a| a.to_string())
+            },
+            creator_id: None,
+            currency: r.currency.clone(),
+            amount_cents: r.amount_cents - r.reversed_cents,
+        })
+        .collect();
+    Ok(compare("transfer", &local, &net_transfers(&transfers)))
+}
+
+async fn reconcile_payouts(
+    state: &AppState,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    errors: &mut Vec<Value>,
+) -> Result<Vec<Discrepancy>, String> {
+    let statuses = ["processing", "paid"];
+    let agency_rows: Vec<PayoutRow> = fetch(
+        state
+            .pg
+            .from("agency_payout_requests")
+            .select("id,agency_id,amount_cents,currency,stripe_payout_id,fee_cents")
+            .in_("status", statuses)
+            .gte("requested_at", ts(start))
+            .lt("requested_at", ts(end)),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let creator_rows: Vec<PayoutRow> = fetch(
+        state
+            .pg
+            .from("creator_payout_requests")
+            .select("id,creator_id,amount_cents,currency,stripe_payout_id")
+            .in_("status", statuses)
+            .gte("requested_at", ts(start))
+            .lt("requested_at", ts(end)),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    // Each owner's connected account, resolved the way payouts resolve it.
+    let mut owners: Vec<(&str, &str)> = Vec::new();
+    for r in &agency_rows {
+        owners.extend(r.agency_id.as_deref().map(|id| ("agency", id)));
+    }
+    for r in &creator_rows {
+        owners.extend(r.creator_id.as_deref().map(|id| ("creator", id)));
+    }
+    owners.sort();
+    owners.dedup();
+
+    let mut out = Vec::new();
+    for (owner_type, owner_id) in owners {
+        let account = match owner_type {
+            "agency" => crate::payouts::get_agency_stripe_account(state, owner_id).await,
+            _ => crate::payouts::get_creator_stripe_account(state, owner_id).await,
+        };
+        let (table, rows) = match owner_type {
+            "agency" => ("agency_payout_requests", &agency_rows),
+            _ => ("creator_payout_requests", &creator_rows),
+        };
+        let local: Vec<LocalRecord> = rows
+            .iter()
+            .filter(|r| r.agency_id.as_deref().or(r.creator_id.as_deref()) == Some(owner_id))
+            .map(|r| LocalRecord {
+                table,
+                id: r.id.clone(),
+                stripe_id: r.stripe_payout_id.clone(),
+                stripe_account_id: account.as_ref().ok().cloned(),
+                agency_id: r.agency_id.clone(),
+                creator_id: r.creator_id.clone(),
+                currency: r.currency.clone(),
+                amount_cents: match owner_type {
+                    "agency" => {
+                        r.amount_cents
+                            - crate::payouts::agency_payout_fee_cents(
+                                state,
+                                r.fee_cents,
+                                r.amount_cents,
+                            )
+                    }
+                    _ => r.amount_cents,
+                },
+            })
+            .collect();
+
+        let account = match account {
+            Ok(a) => a,
+            Err(e) => {
+                // Nothing to compare with; every request of the owner is missing.
+                errors.push(json!({ "owner_type": owner_type, "owner_id": owner_id, "error": e }));
+                out.extend(compare("payout", &local, &[]));
+                continue;
+            }
+        };
+        let payouts = match account.parse::<stripe_sdk::AccountId>() {
+            Ok(id) => {
+                let client = state.stripe_client().with_stripe_account(id);
+                list_payouts(&client, start, end).await
+            }
+            Err(_) => Err("invalid_account_id".to_string()),
+        };
+        let payouts = match payouts {
+            Ok(p) => p,
+            Err(e) => {
+                warn!(stripe_account_id = %account, error = %e, "reconciliation_payouts_unavailable");
+                errors.push(json!({ "stripe_account_id": account, "error": e }));
+                continue;
+            }
+        };
+        // Automatic payouts come from the account's own payout schedule.
+        let stripe: Vec<StripeRecord> = payouts
+            .iter()
+            .filter(|p| !p.automatic)
+            .map(|p| StripeRecord {
+                id: p.id.to_string(),
+                stripe_account_id: Some(account.clone()),
+                agency_id: (owner_type == "agency").then(|| owner_id.to_string()),
+                creator_id: (owner_type == "creator").then(|| owner_id.to_string()),
+                currency: p.currency.to_string(),
+                amount_cents: p.amount,
+            })
+            .collect();
+        out.extend(compare("payout", &local, &stripe));
+    }
+    Ok(out)
+}
+
+async fn reconcile_charges(
+    state: &AppState,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<Discrepancy>, String> {
+    let rows: Vec<LicensingPayoutRow> = fetch(
+        state
+            .pg
+            .from("licensing_payouts")
+            .select("agency_id,stripe_payment_intent_id,amount_cents,talent_earnings_cents,platform_fee_cents,currency")
+            .not("is", "stripe_payment_intent_id", "null")
+            .gte("paid_at", ts(start))
+            .lt("paid_at", ts(end)),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let charges = list_charges(&state.stripe_client(), start, end).await?;
+
+    // Only charges made through a payment link are expected to have a
+    // licensing payout; subscriptions and other charges are left out.
+    let intents: BTreeSet<String> = charges.iter().filter_map(charge_payment_intent).collect();
+    let links: Vec<LinkRow> = if intents.is_empty() {
+        Vec::new()
+    } else {
+        fetch(
+            state
+                .pg
+                .from("agency_payment_links")
+                .select("id,agency_id,stripe_payment_intent_id")
+                .in_("stripe_payment_intent_id", &intents),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+    };
+    let link_agency: HashMap<&str, &str> = links
+        .iter()
+        .filter_map(|l| {
+            l.stripe_payment_intent_id
+                .as_deref()
+                .map(|pi| (pi, l.agency_id.as_str()))
+        })
+        .collect();
+    let stripe: Vec<StripeRecord> = charges
+        .iter()
+        .filter_map(|bt| {
+            let pi = charge_payment_intent(bt)?;
+            let agency_id = link_agency.get(pi.as_str())?.to_string();
+            Some(StripeRecord {
+                id: pi,
+                stripe_account_id: None,
+                agency_id: Some(agency_id),
+                creator_id: None,
+                currency: bt.currency.to_string(),
+                amount_cents: bt.amount,
+            })
+        })
+        .collect();
+
+    // A payment intent pays for one link, recorded as one licensing payout
+    // holding the agency, talent and platform shares of the gross.
+    let mut local: Vec<LocalRecord> = Vec::new();
+    for r in &rows {
+        let gross = r.amount_cents
+            + r.talent_earnings_cents.unwrap_or(0)
+            + r.platform_fee_cents.unwrap_or(0);
+        match local
+            .iter_mut()
+            .find(|l| l.id == r.stripe_payment_intent_id)
+        {
+            Some(existing) => existing.amount_cents += gross,
+            None => local.push(LocalRecord {
+                table: "licensing_payouts",
+                id: r.stripe_payment_intent_id.clone(),
+                stripe_id: Some(r.stripe_payment_intent_id.clone()),
+                stripe_account_id: None,
+                agency_id: Some(r.agency_id.clone()),
+                creator_id: None,
+                currency: r.currency.clone(),
+                amount_cents: gross,
+            }),
+        }
+    }
+    Ok(compare("charge", &local, &stripe))
+}
+
+async fn finish(state: &AppState, run_id: &str, update: Value) -> Result<(), RepoError> {
+    let _: Vec<Value> = fetch(
+        state
+            .pg
+            .from(RUNS_TABLE)
+            .eq("id", run_id)
+            .update(update.to_string()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Reconciles Stripe activity created in `[start, end)` and stores the
+/// discrepancies found under a new run. A Stripe list or database read that
+/// fails marks the run failed; a connected account that cannot be read is
+/// recorded in the run's errors and skipped.
+pub async fn run(
+    state: &AppState,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Summary, String> {
+    let created: Vec<Run> = fetch(
+        state
+            .pg
+            .from(RUNS_TABLE)
+            .insert(
+                json!({
+                    "status": "running",
+                    "window_start": ts(start),
+                    "window_end": ts(end),
+                    "missing_count": 0,
+                    "extra_count": 0,
+                    "amount_mismatch_count": 0,
+                    "errors": [],
+                    "started_at": ts(Utc::now()),
+                })
+                .to_string(),
+            )
+            .select(RUN_COLUMNS),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let run_id = created
+        .into_iter()
+        .next()
+        .map(|r| r.id)
+        .ok_or_else(|| "reconciliation run insert returned no row".to_string())?;
+
+    let mut errors = Vec::new();
+    let result = async {
+        let mut found = reconcile_transfers(state, start, end).await?;
+        found.extend(reconcile_payouts(state, start, end, &mut errors).await?);
+        found.extend(reconcile_charges(state, start, end).await?);
+        Ok::<_, String>(found)
+    }
+    .await;
+
+    let found = match result {
+        Ok(found) => found,
+        Err(e) => {
+            warn!(run_id = %run_id, error = %e, "reconciliation_run_failed");
+            finish(
+                state,
+                &run_id,
+                json!({
+                    "status": "failed",
+                    "error": e,
+                    "errors": errors,
+                    "finished_at": ts(Utc::now()),
+                }),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            return Err(e);
+        }
+    };
+
+    if !found.is_empty() {
+        let rows: Vec<Value> = found
+            .iter()
+            .map(|d| {
+                let mut row = serde_json::to_value(d).unwrap_or_default();
+                row["run_id"] = json!(run_id);
+                row
+            })
+            .collect();
+        let _: Vec<Value> = fetch(
+            state
+                .pg
+                .from(DISCREPANCIES_TABLE)
+                .insert(Value::Array(rows).to_string()),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    let count = |kind| found.iter().filter(|d| d.kind == kind).count();
+    let summary = Summary {
+        run_id: run_id.clone(),
+        missing: count(DiscrepancyKind::Missing),
+        extra: count(DiscrepancyKind::Extra),
+        amount_mismatch: count(DiscrepancyKind::AmountMismatch),
+        errors,
+    };
+    finish(
+        state,
+        &run_id,
+        json!({
+            "status": "completed",
+            "missing_count": summary.missing,
+            "extra_count": summary.extra,
+            "amount_mismatch_count": summary.amount_mismatch,
+            "errors": summary.errors,
+            "finished_at": ts(Utc::now()),
+        }),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    info!(
+        run_id = %run_id,
+        missing = summary.missing,
+        extra = summary.extra,
+        amount_mismatch = summary.amount_mismatch,
+        "reconciliation_run_completed"
+    );
+    Ok(summary)
+}
+
+/// Runs over the default lookback window ending now.
+pub async fn run_recent(state: &AppState) -> Result<Summary, String> {
+    let end = Utc::now();
+    run(state, end - Duration::days(DEFAULT_LOOKBACK_DAYS), end).await
+}
+
+/// The most recent completed run, or a specific one.
+pub async fn find_run(state: &AppState, run_id: Option<&str>) -> Result<Option<Run>, RepoError> {
+    let query = state.pg.from(RUNS_TABLE).select(RUN_COLUMNS);
+    let query = match run_id {
+        Some(id) => query.eq("id", id),
+        None => query
+            .eq("status", "completed")
+            .order("started_at.desc")
+            .limit(1),
+    };
+    let runs: Vec<Run> = fetch(query).await?;
+    Ok(runs.into_iter().next())
+}
+
+pub async fn list_runs(state: &AppState, limit: usize) -> Result<Vec<Run>, RepoError> {
+    fetch(
+        state
+            .pg
+            .from(RUNS_TABLE)
+            .select(RUN_COLUMNS)
+            .order("started_at.desc")
+            .limit(limit),
+    )
+    .await
+}
+
+#[derive(Debug, Default)]
+pub struct DiscrepancyFilter<'a> {
+    pub agency_id: Option<&'a str>,
+    pub kind: Option<DiscrepancyKind>,
+    pub object: Option<&'a str>,
+}
+
+pub async fn discrepancies(
+    state: &AppState,
+    run_id: &str,
+    filter: &DiscrepancyFilter<'_>,
+) -> Result<Vec<Discrepancy>, RepoError> {
+    let mut query = state
+        .pg
+        .from(DISCREPANCIES_TABLE)
+        .select(DISCREPANCY_COLUMNS)
+        .eq("run_id", run_id);
+    if let Some(agency_id) = filter.agency_id {
+        query = query.eq("agency_id", agency_id);
+    }
+    if let Some(kind) = filter.kind {
+        query = query.eq("kind", kind.as_str());
+    }
+    if let Some(object) = filter.object {
+        query = query.eq("object", object);
+    }
+    fetch(query.order("created_at.asc")).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(id: &str, stripe_id: Option<&str>, amount_cents: i64) -> LocalRecord {
+        LocalRecord {
+            table: "agency_payment_link_transfers",
+            id: id.to_string(),
+            stripe_id: stripe_id.map(str::to_string),
+            currency: "USD".to_string(),
+            amount_cents,
+            ..Default::default()
+        }
+    }
+
+    fn stripe(id: &str, amount_cents: i64) -> StripeRecord {
+        StripeRecord {
+            id: id.to_string(),
+            currency: "usd".to_string(),
+            amount_cents,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compare_reports_missing_extra_and_mismatched_records() {
+        let found = compare(
+            "transfer",
+            &[
+                local("a", Some("tr_a"), 100),
+                local("b", Some("tr_b"), 200),
+                local("c", Some("tr_gone"), 300),
+                local("d", None, 400),
+            ],
+            &[stripe("tr_a", 100), stripe("tr_b", 150), stripe("tr_x", 50)],
+        );
+        let summary: Vec<(DiscrepancyKind, Option<&str>, Option<&str>)> = found
+            .iter()
+            .map(|d| (d.kind, d.local_id.as_deref(), d.stripe_id.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (DiscrepancyKind::AmountMismatch, Some("b"), Some("tr_b")),
+                (DiscrepancyKind::Missing, Some("c"), Some("tr_gone")),
+                (DiscrepancyKind::Missing, Some("d"), None),
+                (DiscrepancyKind::Extra, None, Some("tr_x")),
+            ]
+        );
+        assert_eq!(found[0].expected_cents, Some(200));
+        assert_eq!(found[0].actual_cents, Some(150));
+    }
+
+    #[test]
+    fn compare_treats_a_currency_difference_as_a_mismatch() {
+        let mut eur = stripe("tr_a", 100);
+        eur.currency = "eur".to_string();
+        let found = compare("transfer", &[local("a", Some("tr_a"), 100)], &[eur]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, DiscrepancyKind::AmountMismatch);
+    }
+
+    #[test]
+    fn discrepancy_kinds_round_trip() {
+        for kind in [
+            DiscrepancyKind::Missing,
+            DiscrepancyKind::Extra,
+            DiscrepancyKind::AmountMismatch,
+        ] {
+            assert_eq!(DiscrepancyKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(DiscrepancyKind::parse("status"), None);
+    }
+}
@@ -0,0 +1,841 @@
+//! Recurring invoice schedules for retainer billing.
+//!
+//! A schedule holds an invoice template (items, expenses and financial
+//! settings) for one agency client. The `recurring_invoices` job turns every
+//! due occurrence into a draft through [`crate::invoices::create_draft`], so
+//! generated invoices share the agency's numbering sequence and totals logic,
+//! and sends it when the schedule has `auto_send` set. Each occurrence is
+//! claimed in `agency_recurring_invoice_runs` before its invoice is created;
+//! the table's unique `(schedule_id, occurrence_date)` key keeps it from
+//! being billed twice.
+
+use crate::{
+    audit::{self, AuditEvent},
+    auth::AuthUser,
+    config::AppState,
+    errors::{AppError, AppResult},
+    invoice_tax::TaxSettings,
+    invoices::{
+        compute_totals, create_draft, draft_tax, get_client_snapshot, normalize_expenses,
+        normalize_items, CreateInvoiceExpenseInput, CreateInvoiceItemInput, CreateInvoicePayload,
+        TaxFieldsInput,
+    },
+    jobs::runner::ts,
+    repositories::{fetch, RepoError},
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Days, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::warn;
+
+pub const SCHEDULES_TABLE: &str = "agency_recurring_invoice_schedules";
+pub const RUNS_TABLE: &str = "agency_recurring_invoice_runs";
+
+const DEFAULT_PREVIEW_COUNT: usize = 6;
+const MAX_PREVIEW_COUNT: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frequency {
+    Weekly,
+    Biweekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Frequency {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "weekly" => Some(Frequency::Weekly),
+            "biweekly" => Some(Frequency::Biweekly),
+            "monthly" => Some(Frequency::Monthly),
+            "quarterly" => Some(Frequency::Quarterly),
+            "yearly" => Some(Frequency::Yearly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Frequency::Weekly => "weekly",
+            Frequency::Biweekly => "biweekly",
+            Frequency::Monthly => "monthly",
+            Frequency::Quarterly => "quarterly",
+            Frequency::Yearly => "yearly",
+        }
+    }
+
+    /// The `n`th occurrence counting from `start` (the 0th). Monthly steps
+    /// are taken from `start` rather than the previous occurrence, so a
+    /// schedule starting on the 31st falls on the last day of short months
+    /// and returns to the 31st afterwards.
+    pub fn occurrence(&self, start: NaiveDate, n: u32) -> Option<NaiveDate> {
+        match self {
+            Frequency::Weekly => start.checked_add_days(Days::new(7 * n as u64)),
+            Frequency::Biweekly => start.checked_add_days(Days::new(14 * n as u64)),
+            Frequency::Monthly => start.checked_add_months(Months::new(n)),
+            Frequency::Quarterly => start.checked_add_months(Months::new(3 * n)),
+            Frequency::Yearly => start.checked_add_months(Months::new(12 * n)),
+        }
+    }
+
+    /// The first occurrence on or after `date`.
+    pub fn next_on_or_after(&self, start: NaiveDate, date: NaiveDate) -> Option<NaiveDate> {
+        if date <= start {
+            return Some(start);
+        }
+        // Start from a lower bound on the occurrence index and step forward.
+        let days = (date - start).num_days();
+        let mut n = match self {
+            Frequency::Weekly => days / 7,
+            Frequency::Biweekly => days / 14,
+            Frequency::Monthly => days / 31,
+            Frequency::Quarterly => days / 92,
+            Frequency::Yearly => days / 366,
+        } as u32;
+        loop {
+            let next = self.occurrence(start, n)?;
+            if next >= date {
+                return Some(next);
+            }
+            n += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleStatus {
+    Active,
+    Paused,
+    Ended,
+}
+
+impl ScheduleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleStatus::Active => "active",
+            ScheduleStatus::Paused => "paused",
+            ScheduleStatus::Ended => "ended",
+        }
+    }
+}
+
+/// The part of an invoice copied onto every generated one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InvoiceTemplate {
+    #[serde(default)]
+    pub items: Vec<CreateInvoiceItemInput>,
+    #[serde(default)]
+    pub expenses: Vec<CreateInvoiceExpenseInput>,
+    pub currency: Option<String>,
+    pub payment_terms: Option<String>,
+    pub po_number: Option<String>,
+    pub project_reference: Option<String>,
+    pub agency_commission_bps: Option<i32>,
+    pub tax_rate_bps: Option<i32>,
+    pub tax_exempt: Option<bool>,
+    pub discount_cents: Option<i32>,
+    pub notes_internal: Option<String>,
+    pub payment_instructions: Option<String>,
+    pub footer_text: Option<String>,
+    #[serde(flatten)]
+    pub tax: TaxFieldsInput,
+}
+
+impl InvoiceTemplate {
+    fn payload(
+        &self,
+        client_id: &str,
+        invoice_date: NaiveDate,
+        due_date: NaiveDate,
+    ) -> CreateInvoicePayload {
+        CreateInvoicePayload {
+            client_id: client_id.to_string(),
+            invoice_date: Some(invoice_date.to_string()),
+            due_date: Some(due_date.to_string()),
+            payment_terms: self.payment_terms.clone(),
+            po_number: self.po_number.clone(),
+            project_reference: self.project_reference.clone(),
+            currency: self.currency.clone(),
+            agency_commission_bps: self.agency_commission_bps,
+            tax_rate_bps: self.tax_rate_bps,
+            tax_exempt: self.tax_exempt,
+            discount_cents: self.discount_cents,
+            notes_internal: self.notes_internal.clone(),
+            payment_instructions: self.payment_instructions.clone(),
+            footer_text: self.footer_text.clone(),
+            items: Some(self.items.clone()),
+            expenses: Some(self.expenses.clone()),
+            tax: self.tax.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringSchedule {
+    pub id: String,
+    pub agency_id: String,
+    pub client_id: String,
+    pub name: String,
+    pub frequency: Frequency,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub next_run_on: Option<NaiveDate>,
+    pub due_days: i32,
+    pub auto_send: bool,
+    pub status: ScheduleStatus,
+    pub template: InvoiceTemplate,
+    pub generated_count: i32,
+    pub last_generated_at: Option<DateTime<Utc>>,
+    pub last_invoice_id: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RecurringSchedule {
+    /// The first occurrence on or after `date` that is within the schedule.
+    fn next_occurrence(&self, date: NaiveDate) -> Option<NaiveDate> {
+        self.frequency
+            .next_on_or_after(self.start_date, date)
+            .filter(|d| self.end_date.is_none_or(|end| *d <= end))
+    }
+
+    fn due_date(&self, invoice_date: NaiveDate) -> NaiveDate {
+        invoice_date + chrono::Duration::days(self.due_days.max(0) as i64)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecurringRun {
+    pub id: String,
+    pub schedule_id: String,
+    pub occurrence_date: NaiveDate,
+    pub invoice_id: Option<String>,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+async fn get_schedule(state: &AppState, agency_id: &str, id: &str) -> AppResult<RecurringSchedule> {
+    let rows: Vec<RecurringSchedule> = fetch(
+        state
+            .pg
+            .from(SCHEDULES_TABLE)
+            .select("*")
+            .eq("id", id)
+            .eq("agency_id", agency_id),
+    )
+    .await?;
+    rows.into_iter()
+        .next()
+        .ok_or_else(|| AppError::NotFound("Recurring invoice schedule not found".to_string()))
+}
+
+async fn update_schedule(
+    state: &AppState,
+    schedule: &RecurringSchedule,
+    patch: serde_json::Value,
+) -> Result<RecurringSchedule, RepoError> {
+    let mut patch = patch;
+    patch["updated_at"] = json!(ts(Utc::now()));
+    let rows: Vec<RecurringSchedule> = fetch(
+        state
+            .pg
+            .from(SCHEDULES_TABLE)
+            .update(patch.to_string())
+            .eq("id", &schedule.id)
+            .eq("agency_id", &schedule.agency_id),
+    )
+    .await?;
+    rows.into_iter().next().ok_or(RepoError::NotFound)
+}
+
+async fn audit_change(
+    state: &AppState,
+    user: &AuthUser,
+    event_type: &str,
+    title: String,
+    before: Option<&RecurringSchedule>,
+    after: &RecurringSchedule,
+) {
+    audit::record(
+        state,
+        AuditEvent::new(&after.agency_id, event_type, SCHEDULES_TABLE, &after.id)
+            .actor(user)
+            .title(title)
+            .change(&before, after),
+    )
+    .await;
+}
+
+/// GET /api/recurring-invoices
+pub async fn list(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> AppResult<Json<Vec<RecurringSchedule>>> {
+    let rows: Vec<RecurringSchedule> = fetch(
+        state
+            .pg
+            .from(SCHEDULES_TABLE)
+            .select("*")
+            .eq("agency_id", &user.id)
+            .order("created_at.desc"),
+    )
+    .await?;
+    Ok(Json(rows))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSchedulePayload {
+    pub client_id: String,
+    pub name: String,
+    pub frequency: String,
+    pub start_date: String,
+    pub end_date: Option<String>,
+    pub due_days: Option<i32>,
+    pub auto_send: Option<bool>,
+    #[serde(flatten)]
+    pub template: InvoiceTemplate,
+}
+
+fn parse_day(value: &str, field: &str) -> AppResult<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest(format!("invalid_{field}")))
+}
+
+/// POST /api/recurring-invoices: starts a schedule. Billing begins with the
+/// first occurrence on or after today; earlier ones are not back-billed.
+pub async fn create(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<CreateSchedulePayload>,
+) -> AppResult<Json<RecurringSchedule>> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("missing_name".to_string()));
+    }
+    let frequency = Frequency::parse(&payload.frequency)
+        .ok_or_else(|| AppError::BadRequest("invalid_frequency".to_string()))?;
+    let start_date = parse_day(&payload.start_date, "start_date")?;
+    let end_date = payload
+        .end_date
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_day(s, "end_date"))
+        .transpose()?;
+    let due_days = payload.due_days.unwrap_or(30);
+    if due_days < 0 {
+        return Err(AppError::BadRequest("invalid_due_days".to_string()));
+    }
+    if payload.template.items.is_empty() {
+        return Err(AppError::BadRequest("missing_items".to_string()));
+    }
+    let next_run_on = frequency
+        .next_on_or_after(start_date, Utc::now().date_naive())
+        .filter(|d| end_date.is_none_or(|end| *d <= end))
+        .ok_or_else(|| AppError::BadRequest("invalid_end_date".to_string()))?;
+    // Tax settings that would fail generation are rejected up front.
+    let client = get_client_snapshot(&state, &user.id, &payload.client_id).await?;
+    let invoice = payload.template.payload(
+        &payload.client_id,
+        next_run_on,
+        next_run_on + Days::new(due_days as u64),
+    );
+    draft_tax(&state, &user.id, &client, &invoice).await?;
+
+    let row = json!({
+        "agency_id": user.id,
+        "client_id": payload.client_id,
+        "name": name,
+        "frequency": frequency.as_str(),
+        "start_date": start_date.to_string(),
+        "end_date": end_date.map(|d| d.to_string()),
+        "next_run_on": next_run_on.to_string(),
+        "due_days": due_days,
+        "auto_send": payload.auto_send.unwrap_or(false),
+        "status": ScheduleStatus::Active.as_str(),
+        "template": payload.template,
+        "generated_count": 0,
+        "last_generated_at": null,
+        "last_invoice_id": null,
+        "created_by": user.id,
+    });
+    let rows: Vec<RecurringSchedule> =
+        fetch(state.pg.from(SCHEDULES_TABLE).insert(row.to_string())).await?;
+    let schedule = rows
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::internal("recurring_invoices.create", "insert returned no row"))?;
+    audit_change(
+        &state,
+        &user,
+        "recurring_invoice.created",
+        format!("Recurring invoice schedule {} created", schedule.name),
+        None,
+        &schedule,
+    )
+    .await;
+    Ok(Json(schedule))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleDetail {
+    pub schedule: RecurringSchedule,
+    pub runs: Vec<RecurringRun>,
+}
+
+/// GET /api/recurring-invoices/:id: the schedule and the invoices it has
+/// generated so far.
+pub async fn get(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<ScheduleDetail>> {
+    let schedule = get_schedule(&state, &user.id, &id).await?;
+    let runs: Vec<RecurringRun> = fetch(
+        state
+            .pg
+            .from(RUNS_TABLE)
+            .select("id,schedule_id,occurrence_date,invoice_id,status,error,created_at")
+            .eq("schedule_id", &schedule.id)
+            .order("occurrence_date.desc"),
+    )
+    .await?;
+    Ok(Json(ScheduleDetail { schedule, runs }))
+}
+
+/// POST /api/recurring-invoices/:id/pause
+pub async fn pause(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<RecurringSchedule>> {
+    let current = get_schedule(&state, &user.id, &id).await?;
+    if current.status != ScheduleStatus::Active {
+        return Err(AppError::Conflict(
+            "Only active schedules can be paused".to_string(),
+        ));
+    }
+    let updated = update_schedule(
+        &state,
+        &current,
+        json!({ "status": ScheduleStatus::Paused.as_str() }),
+    )
+    .await?;
+    audit_change(
+        &state,
+        &user,
+        "recurring_invoice.paused",
+        format!("Recurring invoice schedule {} paused", updated.name),
+        Some(&current),
+        &updated,
+    )
+    .await;
+    Ok(Json(updated))
+}
+
+/// POST /api/recurring-invoices/:id/resume: occurrences that fell while the
+/// schedule was paused are skipped rather than billed on resume.
+pub async fn resume(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<RecurringSchedule>> {
+    let current = get_schedule(&state, &user.id, &id).await?;
+    if current.status != ScheduleStatus::Paused {
+        return Err(AppError::Conflict(
+            "Only paused schedules can be resumed".to_string(),
+        ));
+    }
+    let today = Utc::now().date_naive();
+    let from = current.next_run_on.map_or(today, |d| d.max(today));
+    let patch = match current.next_occurrence(from) {
+        Some(next) => json!({
+            "status": ScheduleStatus::Active.as_str(),
+            "next_run_on": next.to_string(),
+        }),
+        None => json!({ "status": ScheduleStatus::Ended.as_str(), "next_run_on": null }),
+    };
+    let updated = update_schedule(&state, &current, patch).await?;
+    audit_change(
+        &state,
+        &user,
+        "recurring_invoice.resumed",
+        format!("Recurring invoice schedule {} resumed", updated.name),
+        Some(&current),
+        &updated,
+    )
+    .await;
+    Ok(Json(updated))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewQuery {
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PreviewInvoice {
+    pub invoice_date: NaiveDate,
+    pub due_date: NaiveDate,
+    pub currency: String,
+    pub subtotal_cents: i64,
+    pub expenses_cents: i64,
+    pub tax_cents: i64,
+    pub total_cents: i64,
+    pub agency_fee_cents: i64,
+    pub talent_net_cents: i64,
+}
+
+/// The next `count` invoices the schedule would generate, from `from` on.
+pub fn preview_invoices(
+    schedule: &RecurringSchedule,
+    tax: &TaxSettings,
+    from: NaiveDate,
+    count: usize,
+) -> Vec<PreviewInvoice> {
+    let template = &schedule.template;
+    let totals = compute_totals(
+        &normalize_items(&template.items),
+        &normalize_expenses(&template.expenses),
+        template.agency_commission_bps.unwrap_or(2000) as i64,
+        tax,
+        template.discount_cents.unwrap_or(0) as i64,
+    );
+    let mut out = Vec::with_capacity(count);
+    let mut next = schedule.next_occurrence(from);
+    while let Some(invoice_date) = next {
+        if out.len() == count {
+            break;
+        }
+        out.push(PreviewInvoice {
+            invoice_date,
+            due_date: schedule.due_date(invoice_date),
+            currency: template
+                .currency
+                .clone()
+                .unwrap_or_else(|| "USD".to_string()),
+            subtotal_cents: totals.subtotal_cents,
+            expenses_cents: totals.expenses_cents,
+            tax_cents: totals.tax_cents,
+            total_cents: totals.total_cents,
+            agency_fee_cents: totals.agency_fee_cents,
+            talent_net_cents: totals.talent_net_cents,
+        });
+        next = invoice_date
+            .succ_opt()
+            .and_then(|d| schedule.next_occurrence(d));
+    }
+    out
+}
+
+/// GET /api/recurring-invoices/:id/preview?count=: upcoming generations with
+/// their totals. Paused schedules preview what resuming today would bill.
+pub async fn preview(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    Query(q): Query<PreviewQuery>,
+) -> AppResult<Json<Vec<PreviewInvoice>>> {
+    let schedule = get_schedule(&state, &user.id, &id).await?;
+    let count = q
+        .count
+        .unwrap_or(DEFAULT_PREVIEW_COUNT)
+        .clamp(1, MAX_PREVIEW_COUNT);
+    let today = Utc::now().date_naive();
+    let from = match (schedule.status, schedule.next_run_on) {
+        (ScheduleStatus::Ended, _) | (_, None) => return Ok(Json(Vec::new())),
+        (ScheduleStatus::Active, Some(next)) => next,
+        (ScheduleStatus::Paused, Some(next)) => next.max(today),
+    };
+    // Tax comes from the current agency and client settings, as it will when
+    // the invoices are generated.
+    let client = get_client_snapshot(&state, &user.id, &schedule.client_id).await?;
+    let payload = schedule
+        .template
+        .payload(&schedule.client_id, from, schedule.due_date(from));
+    let tax = draft_tax(&state, &user.id, &client, &payload).await?;
+    let upcoming = preview_invoices(&schedule, &tax.settings, from, count);
+    Ok(Json(upcoming))
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub date: NaiveDate,
+    pub generated: usize,
+    pub sent: usize,
+    pub already_generated: usize,
+    pub failed: usize,
+    pub ended: usize,
+}
+
+/// Claims the occurrence for this run. A failed earlier attempt is taken
+/// over; anything else already recorded means it was generated elsewhere.
+async fn claim(
+    state: &AppState,
+    schedule: &RecurringSchedule,
+    occurrence: NaiveDate,
+) -> Result<Option<String>, RepoError> {
+    let previous: Vec<RecurringRun> = fetch(
+        state
+            .pg
+            .from(RUNS_TABLE)
+            .select("id,schedule_id,occurrence_date,invoice_id,status,error,created_at")
+            .eq("schedule_id", &schedule.id)
+            .eq("occurrence_date", occurrence.to_string()),
+    )
+    .await?;
+    let claimed: Result<Vec<RecurringRun>, RepoError> =
+        match previous.first() {
+            Some(run) if run.status != "failed" => return Ok(None),
+            Some(run) => fetch(
+                state
+                    .pg
+                    .from(RUNS_TABLE)
+                    .update(
+                        json!({ "status": "pending", "error": null, "updated_at": ts(Utc::now()) })
+                            .to_string(),
+                    )
+                    .eq("id", &run.id)
+                    .eq("status", "failed"),
+            )
+            .await,
+            None => {
+                let row = json!({
+                    "agency_id": schedule.agency_id,
+                    "schedule_id": schedule.id,
+                    "occurrence_date": occurrence.to_string(),
+                    "invoice_id": null,
+                    "status": "pending",
+                    "error": null,
+                });
+                fetch(state.pg.from(RUNS_TABLE).insert(row.to_string())).await
+            }
+        };
+    match claimed {
+        Ok(rows) => Ok(rows.into_iter().next().map(|r| r.id)),
+        // Another runner claimed the occurrence first.
+        Err(RepoError::Db { status: 409, .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn finish_run(
+    state: &AppState,
+    run_id: &str,
+    patch: serde_json::Value,
+) -> Result<(), RepoError> {
+    let mut patch = patch;
+    patch["updated_at"] = json!(ts(Utc::now()));
+    let _: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from(RUNS_TABLE)
+            .update(patch.to_string())
+            .eq("id", run_id),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Creates the occurrence's invoice and sends it if the schedule says so.
+/// Returns the invoice id and whether it went out.
+async fn generate(
+    state: &AppState,
+    schedule: &RecurringSchedule,
+    occurrence: NaiveDate,
+) -> AppResult<(String, bool)> {
+    let payload = schedule.template.payload(
+        &schedule.client_id,
+        occurrence,
+        schedule.due_date(occurrence),
+    );
+    let created = create_draft(state, &schedule.agency_id, payload).await?;
+    let invoice_id = created
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| AppError::internal("recurring_invoices.generate", "missing invoice id"))?
+        .to_string();
+    if !schedule.auto_send {
+        return Ok((invoice_id, false));
+    }
+    // The draft exists either way; a failed send leaves it for the agency
+    // to send by hand rather than generating it again.
+    let sent = match state
+        .repos
+        .invoices
+        .get_for_agency(&schedule.agency_id, &invoice_id)
+        .await
+    {
+        Ok(invoice) => crate::invoices::send(state, None, &invoice)
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e.into()),
+    };
+    if let Err(e) = &sent {
+        warn!(schedule_id = %schedule.id, invoice_id = %invoice_id, error = %e, "recurring invoice send failed");
+    }
+    Ok((invoice_id, sent.is_ok()))
+}
+
+/// Generates every occurrence due on or before `today` for one schedule.
+async fn run_schedule(
+    state: &AppState,
+    schedule: &RecurringSchedule,
+    today: NaiveDate,
+    summary: &mut Summary,
+) -> Result<(), RepoError> {
+    let mut current = schedule.clone();
+    while let Some(occurrence) = current.next_run_on.filter(|d| *d <= today) {
+        let mut patch = json!({});
+        match claim(state, &current, occurrence).await? {
+            None => summary.already_generated += 1,
+            Some(run_id) => match generate(state, &current, occurrence).await {
+                Ok((invoice_id, sent)) => {
+                    summary.generated += 1;
+                    if sent {
+                        summary.sent += 1;
+                    }
+                    let status = if sent { "sent" } else { "generated" };
+                    finish_run(
+                        state,
+                        &run_id,
+                        json!({ "status": status, "invoice_id": invoice_id }),
+                    )
+                    .await?;
+                    patch = json!({
+                        "generated_count": current.generated_count + 1,
+                        "last_generated_at": ts(Utc::now()),
+                        "last_invoice_id": invoice_id,
+                    });
+                }
+                Err(e) => {
+                    // Leave the schedule on this occurrence so the next run
+                    // retries it.
+                    warn!(schedule_id = %current.id, occurrence = %occurrence, error = %e, "recurring invoice generation failed");
+                    summary.failed += 1;
+                    finish_run(
+                        state,
+                        &run_id,
+                        json!({ "status": "failed", "error": e.to_string() }),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            },
+        }
+        match occurrence
+            .succ_opt()
+            .and_then(|d| current.next_occurrence(d))
+        {
+            Some(next) => patch["next_run_on"] = json!(next.to_string()),
+            None => {
+                patch["next_run_on"] = json!(null);
+                patch["status"] = json!(ScheduleStatus::Ended.as_str());
+                summary.ended += 1;
+            }
+        }
+        current = update_schedule(state, &current, patch).await?;
+    }
+    Ok(())
+}
+
+/// Generates the invoices of every active schedule due on or before `today`,
+/// catching up on occurrences missed while the job was not running.
+pub async fn run(state: &AppState, today: NaiveDate) -> Result<Summary, RepoError> {
+    let mut summary = Summary {
+        date: today,
+        ..Default::default()
+    };
+    let due: Vec<RecurringSchedule> = fetch(
+        state
+            .pg
+            .from(SCHEDULES_TABLE)
+            .select("*")
+            .eq("status", ScheduleStatus::Active.as_str())
+            .lte("next_run_on", today.to_string())
+            .order("next_run_on.asc"),
+    )
+    .await?;
+    for schedule in &due {
+        run_schedule(state, schedule, today, &mut summary).await?;
+    }
+    Ok(summary)
+}
+
+/// Runs the generation pass for the current UTC date.
+pub async fn run_today(state: &AppState) -> Result<Summary, RepoError> {
+    run(state, Utc::now().date_naive()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn monthly_occurrences_are_counted_from_the_start_date() {
+        let start = date("2026-01-31");
+        let monthly = Frequency::Monthly;
+        assert_eq!(monthly.occurrence(start, 1), Some(date("2026-02-28")));
+        assert_eq!(monthly.occurrence(start, 2), Some(date("2026-03-31")));
+        assert_eq!(
+            monthly.next_on_or_after(start, date("2026-03-01")),
+            Some(date("2026-03-31"))
+        );
+        assert_eq!(
+            monthly.next_on_or_after(start, date("2026-01-01")),
+            Some(start)
+        );
+        assert_eq!(
+            Frequency::Biweekly.next_on_or_after(date("2026-10-01"), date("2026-10-16")),
+            Some(date("2026-10-29"))
+        );
+        assert_eq!(
+            Frequency::Quarterly.next_on_or_after(date("2026-01-15"), date("2026-04-15")),
+            Some(date("2026-04-15"))
+        );
+    }
+
+    #[test]
+    fn preview_stops_at_the_end_date() {
+        let schedule: RecurringSchedule = serde_json::from_value(json!({
+            "id": "sched-1", "agency_id": "agency-1", "client_id": "client-1",
+            "name": "Retainer", "frequency": "monthly",
+            "start_date": "2026-01-15", "end_date": "2026-04-30", "next_run_on": "2026-02-15",
+            "due_days": 14, "auto_send": false, "status": "active",
+            "template": {
+                "items": [{ "description": "Monthly retainer", "unit_price_cents": 250000 }],
+                "agency_commission_bps": 2000, "tax_rate_bps": 1000
+            },
+            "generated_count": 1, "last_generated_at": null, "last_invoice_id": null,
+            "created_by": null,
+            "created_at": "2026-01-01T00:00:00Z", "updated_at": "2026-01-01T00:00:00Z"
+        }))
+        .unwrap();
+        let tax = TaxSettings {
+            default_rate_bps: 1000,
+            ..Default::default()
+        };
+        let preview = preview_invoices(&schedule, &tax, date("2026-02-15"), 6);
+        let dates: Vec<_> = preview.iter().map(|p| p.invoice_date).collect();
+        assert_eq!(
+            dates,
+            vec![date("2026-02-15"), date("2026-03-15"), date("2026-04-15")]
+        );
+        assert_eq!(preview[0].due_date, date("2026-03-01"));
+        assert_eq!(preview[0].total_cents, 275000);
+        assert_eq!(preview[0].talent_net_cents, 200000);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::auth::AuthUser;
 use crate::config::AppState;
+use crate::storage::Bucket;
 use axum::{
     body::Bytes,
     extract::{Path, Query, State},
@@ -84,7 +85,6 @@ pub async fn delete_reference_image(
 
     // 2) Delete storage objects (STRICT)
     // If any storage deletion fails, do not delete DB rows.
-    let http = reqwest::Client::new();
     for r in rows.iter() {
         let bucket = r
             .get("storage_bucket")
@@ -97,21 +97,8 @@ pub async fn delete_reference_image(
                 "missing storage metadata for reference image".into(),
             ));
         }
-        let del_url = format!("{}/storage/v1/object/{}/{path}", state.supabase_url, bucket);
-        let del = http
-            .delete(&del_url)
-            .header(
-                "Authorization",
-                format!("Bearer {}", state.supabase_service_key),
-            )
-            .header("apikey", state.supabase_service_key.clone())
-            .send()
-            .await
-            .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-        let del_status = del.status();
-        if !del_status.is_success() {
-            let txt = del.text().await.unwrap_or_default();
-            error!(status=?del_status, body=%txt, "reference image storage delete failed");
+        if let Err(e) = state.storage.delete(bucket, path).await {
+            error!(error = %e, "reference image storage delete failed");
             return Err((
                 StatusCode::BAD_GATEWAY,
                 "failed to delete reference image from storage".into(),
@@ -168,11 +155,16 @@ pub async fn upload_reference_image(
     if body.is_empty() {
         return Err((StatusCode::BAD_REQUEST, "empty body".into()));
     }
-    let ct = headers
-        .get("content-type")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("image/jpeg")
-        .to_string();
+    let ct = crate::storage::sniff_content_type(
+        &body,
+        headers.get("content-type").and_then(|v| v.to_str().ok()),
+        None,
+    );
+    let ct = if ct == "application/octet-stream" {
+        "image/jpeg".to_string()
+    } else {
+        ct
+    };
 
     // 1) Moderation pre-scan (best-effort: if Rekog configured)
     if let Some(client) = state.rekog.as_ref() {
@@ -384,12 +376,9 @@ pub async fn upload_reference_image(
         ));
     }
 
-    // 2) Upload to Supabase Storage (public bucket) using service key
-    let bucket = state.supabase_bucket_public.clone();
-    let owner = user.id.replace(
-        |c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '-',
-        "_",
-    );
+    // 2) Upload to storage (public bucket)
+    let bucket = state.bucket(Bucket::Public);
+    let owner = crate::storage::sanitize_segment(&user.id);
     let ext = match ct.as_str() {
         "image/png" => "png",
         "image/webp" => "webp",
@@ -403,45 +392,9 @@ pub async fn upload_reference_image(
         ext
     );
 
-    let storage_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::builder()
-        .http1_only()
-        .tcp_keepalive(std::time::Duration::from_secs(30))
-        .pool_idle_timeout(std::time::Duration::from_secs(30))
-        .timeout(std::time::Duration::from_secs(60))
-        .build()
-        .unwrap();
-    let up = http
-        .post(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .header("content-type", ct)
-        .body(body)
-        .send()
-        .await
-        .map_err(|e| {
-            let m = e.to_string();
-            error!(error=%m, "storage upload error");
-            (StatusCode::BAD_GATEWAY, m)
-        })?;
-    if !up.status().is_success() {
-        let msg = up.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("storage upload failed: {msg}"),
-        ));
-    }
+    state.storage.put(&bucket, &path, body, &ct, false).await?;
 
-    let public_url = format!(
-        "{}/storage/v1/object/public/{}/{}",
-        state.supabase_url, bucket, path
-    );
+    let public_url = state.storage.public_url(&bucket, &path);
 
     // 3) Persist to reference_images via Postgrest
     let payload = serde_json::json!({
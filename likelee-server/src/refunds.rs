@@ -0,0 +1,982 @@
+//! Refunds and disputes on payment link charges.
+//!
+//! A payment link tracks how much of its charge has been refunded and how
+//! much is held by a dispute. Their sum is the amount to claw back, split
+//! across the agency commission, each talent's share and the platform fee in
+//! proportion to the original payment. Every Stripe event recomputes that
+//! target and moves each share from what was taken back so far
+//! (`clawback_splits`) to its new target, so partial refunds, redeliveries and
+//! won disputes (which hand money back) all go through the same path. The
+//! new state and the balance moves are saved together, and only if no other
+//! event changed the link since it was read.
+//!
+//! A share's held payout reserve (see `payout_reserves`) covers its clawback
+//! first. The rest is reversed on Stripe from the share's transfers; a won
+//! dispute transfers the reversed amount again as a new transfer, recorded
+//! as its own row (`restores_transfer_id`) since Stripe keeps the original
+//! reversed, and later clawbacks reverse that row. Each Stripe call carries
+//! an idempotency key derived from the event that caused it, and the transfer
+//! rows record how much has been reversed, so a failed reversal makes the
+//! event fail and its redelivery only repeats what is still missing.
+
+use crate::audit::{self, AuditEvent};
+use crate::config::AppState;
+use crate::ledger::{self, Account, Entry, EntryKind};
+use crate::payout_reserves::{self, Reserve, ReserveStatus};
+use crate::repositories::{fetch, RepoError};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::{error, info, warn};
+
+const LINKS_TABLE: &str = "agency_payment_links";
+const TRANSFERS_TABLE: &str = "agency_payment_link_transfers";
+
+const LINK_COLUMNS: &str = "id,agency_id,licensing_request_id,status,currency,total_amount_cents,platform_fee_cents,agency_amount_cents,talent_splits,refunded_cents,disputed_cents,clawback_cents,clawback_splits,stripe_dispute_id,dispute_status";
+
+/// Key of the platform fee in `clawback_splits`.
+const PLATFORM_FEE: &str = "platform_fee";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PaymentLink {
+    id: String,
+    agency_id: String,
+    licensing_request_id: Option<String>,
+    status: String,
+    currency: Option<String>,
+    total_amount_cents: Option<i64>,
+    platform_fee_cents: Option<i64>,
+    agency_amount_cents: Option<i64>,
+    talent_splits: Option<Value>,
+    refunded_cents: Option<i64>,
+    disputed_cents: Option<i64>,
+    clawback_cents: Option<i64>,
+    clawback_splits: Option<Map<String, Value>>,
+    stripe_dispute_id: Option<String>,
+    dispute_status: Option<String>,
+}
+
+impl PaymentLink {
+    fn total(&self) -> i64 {
+        self.total_amount_cents.unwrap_or(0)
+    }
+
+    fn currency(&self) -> &str {
+        self.currency.as_deref().unwrap_or("USD")
+    }
+
+    /// Amount to claw back: refunds plus whatever a dispute holds.
+    fn clawback_target(&self) -> i64 {
+        (self.refunded_cents.unwrap_or(0) + self.disputed_cents.unwrap_or(0)).clamp(0, self.total())
+    }
+
+    fn clawed_back(&self, key: &str) -> i64 {
+        self.clawback_splits
+            .as_ref()
+            .and_then(|m| m.get(key))
+            .and_then(Value::as_i64)
+            .unwrap_or(0)
+    }
+
+    /// A dispute that is open keeps the link `disputed`; otherwise the status
+    /// follows how much of the payment has been taken back.
+    fn derived_status(&self) -> &'static str {
+        let open_dispute = self.disputed_cents.unwrap_or(0) > 0
+            && !matches!(self.dispute_status.as_deref(), Some("lost"));
+        let target = self.clawback_target();
+        if open_dispute {
+            "disputed"
+        } else if target == 0 {
+            "paid"
+        } else if target >= self.total() {
+            "refunded"
+        } else {
+            "partially_refunded"
+        }
+    }
+
+    fn snapshot(&self) -> Value {
+        json!({
+            "status": self.status,
+            "refunded_cents": self.refunded_cents.unwrap_or(0),
+            "disputed_cents": self.disputed_cents.unwrap_or(0),
+            "clawback_cents": self.clawback_cents.unwrap_or(0),
+            "dispute_status": self.dispute_status,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TransferRow {
+    id: String,
+    recipient_type: String,
+    recipient_id: String,
+    stripe_connect_account_id: String,
+    amount_cents: i64,
+    stripe_transfer_id: Option<String>,
+    reversed_cents: Option<i64>,
+    /// Set on a transfer that paid back reversed money after a won dispute.
+    restores_transfer_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recipient {
+    Agency,
+    Creator,
+    Platform,
+}
+
+/// One slice of the original payment.
+#[derive(Debug, Clone)]
+struct Share {
+    /// Key in `clawback_splits`: `agency:<id>`, `creator:<id>` or `platform_fee`.
+    key: String,
+    recipient: Recipient,
+    /// Agency or creator id; empty for the platform fee.
+    recipient_id: String,
+    /// Roster id used by older transfer rows for talent shares.
+    talent_id: Option<String>,
+    amount_cents: i64,
+}
+
+impl Share {
+    fn account(&self) -> Account {
+        match self.recipient {
+            Recipient::Agency => Account::agency(&self.recipient_id),
+            Recipient::Creator => Account::creator(&self.recipient_id),
+            Recipient::Platform => Account::platform_revenue(),
+        }
+    }
+
+    fn recipient_type(&self) -> Option<&'static str> {
+        match self.recipient {
+            Recipient::Agency => Some("agency"),
+            Recipient::Creator => Some("creator"),
+            Recipient::Platform => None,
+        }
+    }
+
+    /// The share's transfers in the order they were made: the checkout
+    /// transfer, any released reserve, then restores after won disputes.
+    fn transfers<'a>(&self, transfers: &'a [TransferRow]) -> Vec<&'a TransferRow> {
+        let Some(recipient_type) = self.recipient_type() else {
+            return vec![];
+        };
+        transfers
+            .iter()
+            .filter(|t| {
+                t.recipient_type == recipient_type
+                    && t.stripe_transfer_id.is_some()
+                    && (t.recipient_id == self.recipient_id
+                        || Some(&t.recipient_id) == self.talent_id.as_ref())
+            })
+            .collect()
+    }
+
+    fn reserve<'a>(&self, reserves: &'a [Reserve]) -> Option<&'a Reserve> {
+        let recipient_type = self.recipient_type()?;
+        reserves
+            .iter()
+            .find(|r| r.recipient_type == recipient_type && r.recipient_id == self.recipient_id)
+    }
+}
+
+/// Part of a share's clawback taken out of its reserve. A held reserve covers
+/// as much as it can, since that money never left the platform; one already
+/// released or forfeited keeps what it covered then.
+fn reserve_part(reserve: Option<&Reserve>, share_target: i64) -> i64 {
+    match reserve {
+        Some(r) if r.status == ReserveStatus::Held => share_target.clamp(0, r.amount_cents),
+        Some(r) => r.clawback_cents,
+        None => 0,
+    }
+}
+
+/// Part of `target` that falls on no share: `amounts` should add up to
+/// `total`, and whatever is missing (a talent share that could not be
+/// resolved) would otherwise silently land on the platform fee.
+fn unassigned(total: i64, amounts: &[i64], target: i64) -> i64 {
+    let missing = total - amounts.iter().sum::<i64>();
+    if total <= 0 || missing <= 0 {
+        return 0;
+    }
+    ((missing as i128 * target as i128) / total as i128) as i64
+}
+
+/// Splits `target` across `amounts` in proportion, rounding each recipient
+/// down; the last amount (the platform fee) takes the remainder so the parts
+/// always add up to `target`.
+fn allocate(total: i64, amounts: &[i64], target: i64) -> Vec<i64> {
+    if total <= 0 || amounts.is_empty() {
+        return vec![0; amounts.len()];
+    }
+    let mut parts: Vec<i64> = amounts[..amounts.len() - 1]
+        .iter()
+        .map(|a| ((*a as i128 * target as i128) / total as i128) as i64)
+        .collect();
+    let rest = target - parts.iter().sum::<i64>();
+    parts.push(rest);
+    parts
+}
+
+async fn shares(state: &AppState, link: &PaymentLink) -> Vec<Share> {
+    let mut shares = vec![Share {
+        key: format!("agency:{}", link.agency_id),
+        recipient: Recipient::Agency,
+        recipient_id: link.agency_id.clone(),
+        talent_id: None,
+        amount_cents: link.agency_amount_cents.unwrap_or(0),
+    }];
+    let splits = link.talent_splits.clone().unwrap_or(json!([]));
+    for split in splits.as_array().into_iter().flatten() {
+        let amount_cents = split
+            .get("amount_cents")
+            .and_then(Value::as_i64)
+            .unwrap_or(0);
+        if amount_cents <= 0 {
+            continue;
+        }
+        match crate::payouts::split_creator_id(state, split).await {
+            Ok(creator_id) => shares.push(Share {
+                key: format!("creator:{creator_id}"),
+                recipient: Recipient::Creator,
+                recipient_id: creator_id,
+                talent_id: split
+                    .get("talent_id")
+                    .and_then(Value::as_str)
+                    .map(String::from),
+                amount_cents,
+            }),
+            Err(e) => warn!(
+                payment_link_id = %link.id,
+                error = %e,
+                "refund_talent_share_unresolved"
+            ),
+        }
+    }
+    shares.push(Share {
+        key: PLATFORM_FEE.to_string(),
+        recipient: Recipient::Platform,
+        recipient_id: String::new(),
+        talent_id: None,
+        amount_cents: link.platform_fee_cents.unwrap_or(0),
+    });
+    shares
+}
+
+async fn find_link(
+    state: &AppState,
+    payment_intent_id: &str,
+) -> Result<Option<PaymentLink>, String> {
+    if payment_intent_id.is_empty() {
+        return Ok(None);
+    }
+    let rows: Vec<PaymentLink> = fetch(
+        state
+            .pg
+            .from(LINKS_TABLE)
+            .select(LINK_COLUMNS)
+            .eq("stripe_payment_intent_id", payment_intent_id)
+            .limit(1),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(rows.into_iter().next())
+}
+
+async fn reload_link(state: &AppState, id: &str) -> Result<PaymentLink, String> {
+    let rows: Vec<PaymentLink> = fetch(
+        state
+            .pg
+            .from(LINKS_TABLE)
+            .select(LINK_COLUMNS)
+            .eq("id", id)
+            .limit(1),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    rows.into_iter()
+        .next()
+        .ok_or_else(|| format!("payment link {id} disappeared"))
+}
+
+fn str_field<'a>(obj: &'a Value, key: &str) -> &'a str {
+    obj.get(key).and_then(Value::as_str).unwrap_or("")
+}
+
+/// `charge.refunded`: `amount_refunded` is cumulative, so partial refunds and
+/// redeliveries converge on the same state.
+pub(crate) async fn handle_charge_refunded(state: &AppState, obj: &Value) -> Result<(), String> {
+    let charge_id = str_field(obj, "id");
+    let Some(before) = find_link(state, str_field(obj, "payment_intent")).await? else {
+        info!(
+            charge_id,
+            "charge.refunded for a charge without a payment link; ignoring"
+        );
+        return Ok(());
+    };
+    let refunded = obj
+        .get("amount_refunded")
+        .and_then(Value::as_i64)
+        .unwrap_or(0);
+    settle(
+        state,
+        before,
+        &format!("{charge_id}:refunded:{refunded}"),
+        json!({ "stripe_charge_id": charge_id }),
+        |link| link.refunded_cents = Some(refunded),
+    )
+    .await
+}
+
+/// `charge.dispute.created`: the disputed amount is withdrawn from the
+/// platform, so it is clawed back like a refund while the dispute is open.
+/// An inquiry (`warning_*` status) withdraws nothing and only marks the link;
+/// if it escalates, Stripe withdraws the funds then and sends
+/// `charge.dispute.funds_withdrawn`.
+pub(crate) async fn handle_dispute_created(state: &AppState, obj: &Value) -> Result<(), String> {
+    open_dispute(state, obj, "created").await
+}
+
+/// `charge.dispute.funds_withdrawn`: claws back an inquiry that became a
+/// chargeback; a no-op for disputes already clawed back when created.
+pub(crate) async fn handle_dispute_funds_withdrawn(
+    state: &AppState,
+    obj: &Value,
+) -> Result<(), String> {
+    open_dispute(state, obj, "funds_withdrawn").await
+}
+
+async fn open_dispute(state: &AppState, obj: &Value, event: &str) -> Result<(), String> {
+    let dispute_id = str_field(obj, "id");
+    let Some(before) = find_link(state, str_field(obj, "payment_intent")).await? else {
+        info!(
+            dispute_id,
+            "dispute for a charge without a payment link; ignoring"
+        );
+        return Ok(());
+    };
+    let amount = obj.get("amount").and_then(Value::as_i64).unwrap_or(0);
+    let status = str_field(obj, "status");
+    let inquiry = status.starts_with("warning_");
+    settle(
+        state,
+        before,
+        &format!("{dispute_id}:{event}"),
+        json!({}),
+        |link| {
+            if !inquiry {
+                link.disputed_cents = Some(amount);
+            }
+            link.stripe_dispute_id = Some(dispute_id.to_string());
+            link.dispute_status = Some(status.to_string());
+        },
+    )
+    .await
+}
+
+/// `charge.dispute.closed`: a won dispute returns the funds, so the clawed
+/// back shares are paid out again; a lost one keeps them taken back.
+pub(crate) async fn handle_dispute_closed(state: &AppState, obj: &Value) -> Result<(), String> {
+    let dispute_id = str_field(obj, "id");
+    let Some(before) = find_link(state, str_field(obj, "payment_intent")).await? else {
+        info!(
+            dispute_id,
+            "dispute for a charge without a payment link; ignoring"
+        );
+        return Ok(());
+    };
+    let status = str_field(obj, "status");
+    let disputed = if status == "lost" {
+        obj.get("amount").and_then(Value::as_i64).unwrap_or(0)
+    } else {
+        0
+    };
+    settle(
+        state,
+        before,
+        &format!("{dispute_id}:closed:{status}"),
+        json!({}),
+        |link| {
+            link.stripe_dispute_id = Some(dispute_id.to_string());
+            link.dispute_status = Some(status.to_string());
+            link.disputed_cents = Some(disputed);
+        },
+    )
+    .await
+}
+
+/// Every share of a payment link with its clawback target under one state.
+struct Clawback {
+    shares: Vec<Share>,
+    targets: Vec<i64>,
+    transfers: Vec<TransferRow>,
+    reserves: Vec<Reserve>,
+    /// Part of each share's target covered by its reserve.
+    reserve_parts: Vec<i64>,
+}
+
+impl Clawback {
+    async fn load(state: &AppState, link: &PaymentLink) -> Result<Self, String> {
+        let shares = shares(state, link).await;
+        let target = link.clawback_target();
+        let amounts: Vec<i64> = shares.iter().map(|s| s.amount_cents).collect();
+        let unassigned = unassigned(link.total(), &amounts, target);
+        if unassigned > 0 {
+            error!(
+                payment_link_id = %link.id,
+                unassigned_cents = unassigned,
+                "payment_clawback_share_missing; charged to the platform fee"
+            );
+        }
+        let targets = allocate(link.total(), &amounts, target);
+
+        let transfers: Vec<TransferRow> = fetch(
+            state
+                .pg
+                .from(TRANSFERS_TABLE)
+                .select("id,recipient_type,recipient_id,stripe_connect_account_id,amount_cents,stripe_transfer_id,reversed_cents,restores_transfer_id")
+                .eq("payment_link_id", &link.id)
+                .order("created_at.asc"),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let reserves = payout_reserves::for_link(state, &link.id)
+            .await
+            .map_err(|e| e.to_string())?;
+        let reserve_parts = shares
+            .iter()
+            .zip(&targets)
+            .map(|(share, target)| reserve_part(share.reserve(&reserves), *target))
+            .collect();
+        Ok(Clawback {
+            shares,
+            targets,
+            transfers,
+            reserves,
+            reserve_parts,
+        })
+    }
+
+    /// New `clawback_splits`, the balance moves from `before` to them and
+    /// the ledger entry recording those moves.
+    fn moves(
+        &self,
+        before: &PaymentLink,
+        after: &PaymentLink,
+        cause: &str,
+    ) -> (Map<String, Value>, Vec<Value>, Entry) {
+        let delta_total = after.clawback_target() - before.clawback_cents.unwrap_or(0);
+        let mut entry = Entry::new(
+            if delta_total >= 0 {
+                EntryKind::Refund
+            } else {
+                EntryKind::Adjustment
+            },
+            format!("payment_link:{}:clawback:{cause}", after.id),
+            after.currency(),
+        )
+        .reference("payment_link", &after.id)
+        .memo(if delta_total >= 0 {
+            "Payment refunded or disputed"
+        } else {
+            "Disputed payment returned"
+        })
+        .credit(&Account::platform_cash(), delta_total);
+
+        let mut splits = Map::new();
+        let mut moves = vec![];
+        for ((share, share_target), covered) in self
+            .shares
+            .iter()
+            .zip(&self.targets)
+            .zip(&self.reserve_parts)
+        {
+            splits.insert(share.key.clone(), json!(share_target));
+            let clawed_back = before.clawed_back(&share.key);
+            let delta = share_target - clawed_back;
+            if delta == 0 {
+                continue;
+            }
+            entry = entry.debit(&share.account(), delta);
+            let Some(recipient_type) = share.recipient_type() else {
+                continue;
+            };
+            // The reserve's part was never transferred out of the balance.
+            let reserve = share.reserve(&self.reserves);
+            let reserve_delta = covered - reserve_part(reserve, clawed_back);
+            let transferred = !share.transfers(&self.transfers).is_empty();
+            for (delta, transferred) in
+                [(delta - reserve_delta, transferred), (reserve_delta, false)]
+            {
+                if delta != 0 {
+                    moves.push(json!({
+                        "recipient_type": recipient_type,
+                        "recipient_id": share.recipient_id,
+                        "delta_cents": delta,
+                        "transferred": transferred,
+                    }));
+                }
+            }
+        }
+        (splits, moves, entry)
+    }
+}
+
+/// Attempts at saving a clawback while other events keep changing the link.
+const SETTLE_ATTEMPTS: u32 = 3;
+
+/// Moves every share of `before` to its clawback target under the state
+/// `change` produces, saves that state together with the balance moves and
+/// reconciles the Stripe transfers. `cause` identifies the event and scopes
+/// the ledger and Stripe idempotency keys.
+///
+/// The link and balances are saved in one `settle_payment_clawback` call that
+/// only applies while the link's `clawback_cents` is still the one read, so a
+/// concurrent refund and dispute cannot overwrite each other; the loser
+/// reloads the link and recomputes.
+async fn settle(
+    state: &AppState,
+    mut before: PaymentLink,
+    cause: &str,
+    extra: Value,
+    change: impl Fn(&mut PaymentLink),
+) -> Result<(), String> {
+    let mut attempt = 1;
+    let (after, clawback, entry) = loop {
+        let mut after = before.clone();
+        change(&mut after);
+        let clawback = Clawback::load(state, &after).await?;
+        let (splits, moves, entry) = clawback.moves(&before, &after, cause);
+
+        let target = after.clawback_target();
+        after.clawback_cents = Some(target);
+        after.clawback_splits = Some(splits.clone());
+        after.status = after.derived_status().to_string();
+        let mut update = json!({
+            "status": after.status,
+            "refunded_cents": after.refunded_cents.unwrap_or(0),
+            "disputed_cents": after.disputed_cents.unwrap_or(0),
+            "clawback_cents": target,
+            "clawback_splits": splits,
+            "stripe_dispute_id": after.stripe_dispute_id,
+            "dispute_status": after.dispute_status,
+        });
+        if let (Some(update), Some(extra)) = (update.as_object_mut(), extra.as_object()) {
+            update.extend(extra.clone());
+        }
+        if after.status == "refunded" && before.status != "refunded" {
+            update["refunded_at"] = json!(crate::jobs::runner::ts(chrono::Utc::now()));
+        }
+
+        let saved: bool = fetch(
+            state.pg.rpc(
+                "settle_payment_clawback",
+                json!({
+                    "p_payment_link_id": after.id,
+                    "p_expected_clawback_cents": before.clawback_cents.unwrap_or(0),
+                    "p_link": update,
+                    "p_moves": moves,
+                })
+                .to_string(),
+            ),
+        )
+        .await
+        .map_err(|e| format!("payment_clawback_balance_update_failed: {e}"))?;
+        if saved {
+            break (after, clawback, entry);
+        }
+        if attempt == SETTLE_ATTEMPTS {
+            return Err("payment_link_changed_concurrently".to_string());
+        }
+        warn!(payment_link_id = %before.id, attempt, "payment_clawback_link_changed; retrying");
+        attempt += 1;
+        before = reload_link(state, &before.id).await?;
+    };
+    if entry.validate().is_ok() {
+        ledger::record(state, entry).await;
+    }
+
+    // Reserves follow the saved link, so a redelivery finishes this if it fails.
+    for (share, part) in clawback.shares.iter().zip(&clawback.reserve_parts) {
+        if let Some(reserve) = share
+            .reserve(&clawback.reserves)
+            .filter(|r| r.status == ReserveStatus::Held && r.clawback_cents != *part)
+        {
+            payout_reserves::set_clawback(state, &reserve.id, *part)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    if let Some(lr_id) = after
+        .licensing_request_id
+        .as_deref()
+        .filter(|s| !s.is_empty())
+    {
+        let _ = state
+            .pg
+            .from("licensing_requests")
+            .eq("id", lr_id)
+            .update(json!({ "payment_status": after.status }).to_string())
+            .execute()
+            .await;
+    }
+
+    let mut failures = vec![];
+    for ((share, share_target), reserve_part) in clawback
+        .shares
+        .iter()
+        .zip(&clawback.targets)
+        .zip(&clawback.reserve_parts)
+    {
+        // What the reserve does not cover is taken from the transfers.
+        let transfers = share.transfers(&clawback.transfers);
+        if let Err(e) = reconcile_transfers(
+            state,
+            &after,
+            &transfers,
+            share_target - reserve_part,
+            cause,
+        )
+        .await
+        {
+            error!(
+                payment_link_id = %after.id,
+                share = %share.key,
+                error = %e,
+                "payment_clawback_transfer_failed"
+            );
+            failures.push(format!("{}: {e}", share.key));
+        }
+    }
+
+    if before.status != after.status || before.clawback_cents != after.clawback_cents {
+        notify_agency(state, &before, &after).await;
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("transfer_clawback_failed: {}", failures.join("; ")))
+    }
+}
+
+/// Brings a share's reversed transfers to `share_target` of what it was
+/// originally paid. Reversals are final on Stripe, so money handed back after
+/// a won dispute is a new transfer that later clawbacks reverse in turn: the
+/// amount reversed across the transfers must equal everything restored plus
+/// the target.
+async fn reconcile_transfers(
+    state: &AppState,
+    link: &PaymentLink,
+    transfers: &[&TransferRow],
+    share_target: i64,
+    cause: &str,
+) -> Result<(), String> {
+    let (restores, originals): (Vec<&TransferRow>, Vec<&TransferRow>) = transfers
+        .iter()
+        .partition(|t| t.restores_transfer_id.is_some());
+    let paid: i64 = originals.iter().map(|t| t.amount_cents).sum();
+    let restored: i64 = restores.iter().map(|t| t.amount_cents).sum();
+    let reversed: i64 = transfers
+        .iter()
+        .map(|t| t.reversed_cents.unwrap_or(0))
+        .sum();
+    let desired = restored + share_target.clamp(0, paid);
+
+    if desired > reversed {
+        let mut remaining = desired - reversed;
+        for transfer in transfers {
+            let part = remaining.min(transfer.amount_cents - transfer.reversed_cents.unwrap_or(0));
+            if part <= 0 {
+                continue;
+            }
+            reverse_transfer(state, link, transfer, part, cause).await?;
+            remaining -= part;
+        }
+    } else if desired < reversed {
+        if let Some(source) = transfers
+            .iter()
+            .rev()
+            .find(|t| t.reversed_cents.unwrap_or(0) > 0)
+        {
+            restore_transfer(state, link, source, reversed - desired, cause).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Reverses `amount` more of `transfer`.
+async fn reverse_transfer(
+    state: &AppState,
+    link: &PaymentLink,
+    transfer: &TransferRow,
+    amount: i64,
+    cause: &str,
+) -> Result<(), String> {
+    let Some(transfer_id) = transfer.stripe_transfer_id.as_deref() else {
+        return Ok(());
+    };
+    let client = state
+        .stripe_client()
+        .with_strategy(stripe_sdk::RequestStrategy::Idempotent(format!(
+            "reversal-{cause}-{transfer_id}"
+        )));
+    let id = transfer_id
+        .parse::<stripe_sdk::TransferId>()
+        .map_err(|_| "invalid_transfer_id".to_string())?;
+    let params = stripe_sdk::CreateTransferReversal {
+        amount: Some(amount as u64),
+        metadata: Some(HashMap::from([
+            ("payment_link_id".to_string(), link.id.clone()),
+            ("cause".to_string(), cause.to_string()),
+        ])),
+        ..Default::default()
+    };
+    let reversal = stripe_sdk::TransferReversal::create(&client, &id, params)
+        .await
+        .map_err(|e| e.to_string())?;
+    ledger::record(
+        state,
+        Entry::new(
+            EntryKind::Transfer,
+            format!("reversal:{}", reversal.id),
+            link.currency(),
+        )
+        .memo("Transfer reversed")
+        .reference("payment_link", &link.id)
+        .debit(&Account::platform_cash(), amount)
+        .credit(
+            &Account::connected(&transfer.stripe_connect_account_id),
+            amount,
+        ),
+    )
+    .await;
+
+    let reversed = transfer.reversed_cents.unwrap_or(0) + amount;
+    let status = if reversed >= transfer.amount_cents {
+        "reversed"
+    } else {
+        "created"
+    };
+    let _: Vec<Value> = fetch(
+        state
+            .pg
+            .from(TRANSFERS_TABLE)
+            .eq("id", &transfer.id)
+            .update(json!({ "reversed_cents": reversed, "status": status }).to_string()),
+    )
+    .await
+    .map_err(|e: RepoError| e.to_string())?;
+    Ok(())
+}
+
+/// Transfers `amount` of reversed money back to `source`'s connected account
+/// and records it as a transfer restoring `source`. The transfer group names
+/// the event and source, so a redelivery after the idempotency key expired
+/// finds the transfer an earlier attempt made but could not record.
+async fn restore_transfer(
+    state: &AppState,
+    link: &PaymentLink,
+    source: &TransferRow,
+    amount: i64,
+    cause: &str,
+) -> Result<(), String> {
+    let Some(source_transfer_id) = source.stripe_transfer_id.as_deref() else {
+        return Ok(());
+    };
+    let group = format!("restore-{cause}-{source_transfer_id}");
+    let client = state
+        .stripe_client()
+        .with_strategy(stripe_sdk::RequestStrategy::Idempotent(group.clone()));
+    let existing = stripe_sdk::Transfer::list(
+        &state.stripe_client(),
+        &stripe_sdk::ListTransfers {
+            transfer_group: Some(&group),
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let restored_id = match existing.data.into_iter().next() {
+        Some(t) => t.id.to_string(),
+        None => {
+            let currency = stripe_sdk::Currency::from_str(&link.currency().to_lowercase())
+                .map_err(|_| "invalid_currency".to_string())?;
+            let mut params =
+                stripe_sdk::CreateTransfer::new(currency, source.stripe_connect_account_id.clone());
+            params.amount = Some(amount);
+            params.transfer_group = Some(&group);
+            params.metadata = Some(HashMap::from([
+                ("payment_link_id".to_string(), link.id.clone()),
+                (
+                    "restores_transfer_id".to_string(),
+                    source_transfer_id.to_string(),
+                ),
+                ("type".to_string(), "dispute_won".to_string()),
+            ]));
+            stripe_sdk::Transfer::create(&client, params)
+                .await
+                .map_err(|e| e.to_string())?
+                .id
+                .to_string()
+        }
+    };
+
+    let recorded = state
+        .pg
+        .rpc(
+            "record_stripe_transfer",
+            json!({
+                "p_payment_link_id": link.id,
+                "p_recipient_type": source.recipient_type,
+                "p_recipient_id": source.recipient_id,
+                "p_stripe_connect_account_id": source.stripe_connect_account_id,
+                "p_amount_cents": amount,
+                "p_currency": link.currency(),
+                "p_stripe_transfer_id": restored_id,
+                "p_status": "created",
+                "p_restores_transfer_id": source.id,
+            })
+            .to_string(),
+        )
+        .execute()
+        .await;
+    if !recorded.as_ref().is_ok_and(|r| r.status().is_success()) {
+        return Err(format!("restore_transfer_unrecorded: {restored_id}"));
+    }
+    ledger::record(
+        state,
+        Entry::new(
+            EntryKind::Transfer,
+            format!("transfer:{restored_id}"),
+            link.currency(),
+        )
+        .memo("Transfer restored after dispute")
+        .reference("payment_link", &link.id)
+        .debit(
+            &Account::connected(&source.stripe_connect_account_id),
+            amount,
+        )
+        .credit(&Account::platform_cash(), amount),
+    )
+    .await;
+    Ok(())
+}
+
+fn money(cents: i64, currency: &str) -> String {
+    format!("{:.2} {}", cents as f64 / 100.0, currency.to_uppercase())
+}
+
+/// Emails the agency and adds the change to its activity feed.
+async fn notify_agency(state: &AppState, before: &PaymentLink, after: &PaymentLink) {
+    let (event_type, title) = match after.status.as_str() {
+        "disputed" => ("payment_link.disputed", "Payment disputed"),
+        "paid" => (
+            "payment_link.dispute_won",
+            "Dispute resolved in your favour",
+        ),
+        _ if after.stripe_dispute_id.is_some()
+            && after.dispute_status.as_deref() == Some("lost") =>
+        {
+            ("payment_link.dispute_lost", "Dispute lost")
+        }
+        _ => ("payment_link.refunded", "Payment refunded"),
+    };
+    let currency = after.currency();
+    let clawback = after.clawback_cents.unwrap_or(0);
+    let summary = format!(
+        "{} of {} is currently taken back from this payment (refunded {}, disputed {}).",
+        money(clawback, currency),
+        money(after.total(), currency),
+        money(after.refunded_cents.unwrap_or(0), currency),
+        money(after.disputed_cents.unwrap_or(0), currency),
+    );
+
+    audit::record(
+        state,
+        AuditEvent::new(&after.agency_id, event_type, LINKS_TABLE, &after.id)
+            .title(title)
+            .subtitle(summary.clone())
+            .change(&before.snapshot(), &after.snapshot()),
+    )
+    .await;
+
+    let agency: Result<Vec<Value>, RepoError> = fetch(
+        state
+            .pg
+            .from("agencies")
+            .select("email,agency_name")
+            .eq("id", &after.agency_id)
+            .limit(1),
+    )
+    .await;
+    let Some(email) = agency
+        .ok()
+        .and_then(|rows| rows.into_iter().next())
+        .and_then(|row| row.get("email").and_then(Value::as_str).map(String::from))
+        .filter(|e| !e.trim().is_empty())
+    else {
+        warn!(agency_id = %after.agency_id, "payment_clawback_notice_no_agency_email");
+        return;
+    };
+    let body = format!(
+        "Hello,\n\n{title} for a licensing payment.\n\n{summary}\n\nCommission and talent shares have been adjusted in proportion, and any transfers already made were reversed or restored accordingly.\n\nBest regards,\nLikelee Team"
+    );
+    let outgoing = crate::email::OutgoingEmail::new(&email, title, &body)
+        .category("payment_refund")
+        .agency(&after.agency_id)
+        .reference("payment_link", &after.id);
+    if let Err(e) = crate::email::enqueue(state, outgoing).await {
+        warn!(agency_id = %after.agency_id, error = %e, "payment_clawback_notice_failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocation_is_proportional_and_exact() {
+        // agency 1800, talent 7200, platform fee 1000 of a 10000 payment.
+        assert_eq!(
+            allocate(10_000, &[1_800, 7_200, 1_000], 5_000),
+            vec![900, 3_600, 500]
+        );
+        let parts = allocate(10_000, &[1_800, 7_200, 1_000], 3_333);
+        assert_eq!(parts, vec![599, 2_399, 335]);
+        assert_eq!(parts.iter().sum::<i64>(), 3_333);
+        assert_eq!(allocate(0, &[1, 2], 5), vec![0, 0]);
+    }
+
+    #[test]
+    fn missing_shares_are_reported_apart_from_rounding() {
+        assert_eq!(unassigned(10_000, &[1_800, 7_200, 1_000], 3_333), 0);
+        // The 7200 talent share could not be resolved.
+        assert_eq!(unassigned(10_000, &[1_800, 1_000], 5_000), 3_600);
+    }
+
+    #[test]
+    fn status_follows_refunds_and_disputes() {
+        let mut link: PaymentLink = serde_json::from_value(json!({
+            "id": "pl", "agency_id": "a", "licensing_request_id": null, "status": "paid",
+            "currency": "USD", "total_amount_cents": 100, "platform_fee_cents": 10,
+            "agency_amount_cents": 20, "talent_splits": [], "refunded_cents": 0,
+            "disputed_cents": 0, "clawback_cents": 0, "clawback_splits": {},
+            "stripe_dispute_id": null, "dispute_status": null,
+        }))
+        .unwrap();
+        assert_eq!(link.derived_status(), "paid");
+        link.refunded_cents = Some(40);
+        assert_eq!(link.derived_status(), "partially_refunded");
+        link.disputed_cents = Some(60);
+        link.dispute_status = Some("needs_response".into());
+        assert_eq!(link.derived_status(), "disputed");
+        link.dispute_status = Some("lost".into());
+        assert_eq!(link.derived_status(), "refunded");
+        assert_eq!(link.clawback_target(), 100);
+    }
+}
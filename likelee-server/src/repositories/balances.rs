@@ -0,0 +1,110 @@
+use super::{fetch, RepoError};
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use postgrest::Postgrest;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatorBalance {
+    pub creator_id: String,
+    pub currency: String,
+    pub available_cents: i64,
+    pub earned_cents: i64,
+    /// Held in payout reserves; filled in by the balance endpoints, which
+    /// leave it out of `available_cents`.
+    #[serde(default)]
+    pub reserved_cents: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgencyBalance {
+    pub agency_id: String,
+    pub currency: String,
+    pub available_cents: i64,
+    pub earned_cents: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait BalanceRepository: Send + Sync {
+    async fn creator_balances(&self, creator_id: &str) -> Result<Vec<CreatorBalance>, RepoError>;
+
+    /// `None` when the agency has never received a payout.
+    async fn agency_balance(&self, agency_id: &str) -> Result<Option<AgencyBalance>, RepoError>;
+}
+
+pub struct PostgrestBalanceRepository {
+    pg: Postgrest,
+}
+
+impl PostgrestBalanceRepository {
+    pub fn new(pg: Postgrest) -> Self {
+        Self { pg }
+    }
+}
+
+#[async_trait]
+impl BalanceRepository for PostgrestBalanceRepository {
+    async fn creator_balances(&self, creator_id: &str) -> Result<Vec<CreatorBalance>, RepoError> {
+        fetch(
+            self.pg
+                .from("creator_balances")
+                .select("creator_id,currency,available_cents,earned_cents")
+                .eq("creator_id", creator_id),
+        )
+        .await
+    }
+
+    async fn agency_balance(&self, agency_id: &str) -> Result<Option<AgencyBalance>, RepoError> {
+        let rows: Vec<AgencyBalance> = fetch(
+            self.pg
+                .from("agency_balances")
+                .select("agency_id,currency,available_cents,earned_cents,updated_at")
+                .eq("agency_id", agency_id)
+                .limit(1),
+        )
+        .await?;
+        Ok(rows.into_iter().next())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryBalanceRepository {
+    creators: Mutex<Vec<CreatorBalance>>,
+    agencies: Mutex<Vec<AgencyBalance>>,
+}
+
+impl InMemoryBalanceRepository {
+    pub fn insert_creator(&self, balance: CreatorBalance) {
+        self.creators.lock().unwrap().push(balance);
+    }
+
+    pub fn insert_agency(&self, balance: AgencyBalance) {
+        self.agencies.lock().unwrap().push(balance);
+    }
+}
+
+#[async_trait]
+impl BalanceRepository for InMemoryBalanceRepository {
+    async fn creator_balances(&self, creator_id: &str) -> Result<Vec<CreatorBalance>, RepoError> {
+        Ok(self
+            .creators
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|b| b.creator_id == creator_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn agency_balance(&self, agency_id: &str) -> Result<Option<AgencyBalance>, RepoError> {
+        Ok(self
+            .agencies
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|b| b.agency_id == agency_id)
+            .cloned())
+    }
+}
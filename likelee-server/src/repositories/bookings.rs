@@ -0,0 +1,194 @@
+use super::{fetch, RepoError};
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use postgrest::Postgrest;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Mirrors the `public.booking_status` enum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BookingStatus {
+    Pending,
+    Confirmed,
+    Completed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Booking {
+    pub id: String,
+    pub agency_user_id: String,
+    pub talent_id: Option<String>,
+    pub talent_name: Option<String>,
+    pub client_id: Option<String>,
+    pub client_name: Option<String>,
+    pub campaign_id: Option<String>,
+    #[serde(rename = "type")]
+    pub booking_type: String,
+    pub status: BookingStatus,
+    pub date: NaiveDate,
+    pub rate_cents: Option<i32>,
+    pub currency: String,
+    pub rate_type: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+const BOOKING_COLUMNS: &str = "id,agency_user_id,talent_id,talent_name,client_id,client_name,campaign_id,type,status,date,rate_cents,currency,rate_type,created_at,updated_at";
+
+#[derive(Debug, Clone, Default)]
+pub struct BookingFilter {
+    pub date_start: Option<String>,
+    pub date_end: Option<String>,
+    pub client_id: Option<String>,
+}
+
+#[async_trait]
+pub trait BookingRepository: Send + Sync {
+    async fn list_for_agency(
+        &self,
+        agency_id: &str,
+        filter: &BookingFilter,
+    ) -> Result<Vec<Booking>, RepoError>;
+
+    async fn get_for_agency(&self, agency_id: &str, id: &str) -> Result<Booking, RepoError>;
+
+    /// Cancels the booking and detaches it from its campaign.
+    async fn cancel(&self, agency_id: &str, id: &str) -> Result<Booking, RepoError>;
+}
+
+pub struct PostgrestBookingRepository {
+    pg: Postgrest,
+}
+
+impl PostgrestBookingRepository {
+    pub fn new(pg: Postgrest) -> Self {
+        Self { pg }
+    }
+}
+
+#[async_trait]
+impl BookingRepository for PostgrestBookingRepository {
+    async fn list_for_agency(
+        &self,
+        agency_id: &str,
+        filter: &BookingFilter,
+    ) -> Result<Vec<Booking>, RepoError> {
+        let mut req = self
+            .pg
+            .from("bookings")
+            .select(BOOKING_COLUMNS)
+            .eq("agency_user_id", agency_id);
+        if let Some(ds) = filter.date_start.as_ref() {
+            req = req.gte("date", ds);
+        }
+        if let Some(de) = filter.date_end.as_ref() {
+            req = req.lte("date", de);
+        }
+        if let Some(cid) = filter.client_id.as_ref() {
+            req = req.eq("client_id", cid);
+        }
+        fetch(req.order("date.desc")).await
+    }
+
+    async fn get_for_agency(&self, agency_id: &str, id: &str) -> Result<Booking, RepoError> {
+        let rows: Vec<Booking> = fetch(
+            self.pg
+                .from("bookings")
+                .select(BOOKING_COLUMNS)
+                .eq("id", id)
+                .eq("agency_user_id", agency_id)
+                .limit(1),
+        )
+        .await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+
+    async fn cancel(&self, agency_id: &str, id: &str) -> Result<Booking, RepoError> {
+        let rows: Vec<Booking> = fetch(
+            self.pg
+                .from("bookings")
+                .eq("id", id)
+                .eq("agency_user_id", agency_id)
+                .update(json!({"status": "cancelled", "campaign_id": null}).to_string())
+                .select(BOOKING_COLUMNS),
+        )
+        .await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryBookingRepository {
+    bookings: Mutex<HashMap<String, Booking>>,
+}
+
+impl InMemoryBookingRepository {
+    pub fn insert(&self, booking: Booking) {
+        self.bookings
+            .lock()
+            .unwrap()
+            .insert(booking.id.clone(), booking);
+    }
+}
+
+#[async_trait]
+impl BookingRepository for InMemoryBookingRepository {
+    async fn list_for_agency(
+        &self,
+        agency_id: &str,
+        filter: &BookingFilter,
+    ) -> Result<Vec<Booking>, RepoError> {
+        let start = filter
+            .date_start
+            .as_deref()
+            .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
+        let end = filter
+            .date_end
+            .as_deref()
+            .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
+        let mut out: Vec<Booking> = self
+            .bookings
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|b| b.agency_user_id == agency_id)
+            .filter(|b| start.is_none_or(|d| b.date >= d))
+            .filter(|b| end.is_none_or(|d| b.date <= d))
+            .filter(|b| {
+                filter
+                    .client_id
+                    .as_deref()
+                    .is_none_or(|c| b.client_id.as_deref() == Some(c))
+            })
+            .cloned()
+            .collect();
+        out.sort_by_key(|b| std::cmp::Reverse(b.date));
+        Ok(out)
+    }
+
+    async fn get_for_agency(&self, agency_id: &str, id: &str) -> Result<Booking, RepoError> {
+        self.bookings
+            .lock()
+            .unwrap()
+            .get(id)
+            .filter(|b| b.agency_user_id == agency_id)
+            .cloned()
+            .ok_or(RepoError::NotFound)
+    }
+
+    async fn cancel(&self, agency_id: &str, id: &str) -> Result<Booking, RepoError> {
+        let mut bookings = self.bookings.lock().unwrap();
+        let booking = bookings
+            .get_mut(id)
+            .filter(|b| b.agency_user_id == agency_id)
+            .ok_or(RepoError::NotFound)?;
+        booking.status = BookingStatus::Cancelled;
+        booking.campaign_id = None;
+        booking.updated_at = Utc::now();
+        Ok(booking.clone())
+    }
+}
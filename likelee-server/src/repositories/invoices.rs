@@ -0,0 +1,839 @@
+use super::{fetch, RepoError};
+use crate::invoice_tax::TaxTreatment;
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use postgrest::Postgrest;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvoiceStatus {
+    Draft,
+    Sent,
+    /// Sent with some, but not all, of the balance paid.
+    #[serde(rename = "partially_paid")]
+    PartiallyPaid,
+    /// Sent and past its due date; set by the `invoice_dunning` job.
+    Overdue,
+    Paid,
+    /// Sent and settled by credit notes alone, with nothing paid.
+    Credited,
+    Void,
+}
+
+impl InvoiceStatus {
+    /// Sent and not yet settled, so still owed by the client.
+    pub fn is_outstanding(&self) -> bool {
+        matches!(
+            self,
+            InvoiceStatus::Sent | InvoiceStatus::PartiallyPaid | InvoiceStatus::Overdue
+        )
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvoiceStatus::Draft => "draft",
+            InvoiceStatus::Sent => "sent",
+            InvoiceStatus::PartiallyPaid => "partially_paid",
+            InvoiceStatus::Overdue => "overdue",
+            InvoiceStatus::Paid => "paid",
+            InvoiceStatus::Credited => "credited",
+            InvoiceStatus::Void => "void",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invoice {
+    pub id: String,
+    pub agency_id: String,
+    pub client_id: String,
+    pub booking_id: Option<String>,
+
+    pub invoice_number: String,
+    pub status: InvoiceStatus,
+
+    pub invoice_date: NaiveDate,
+    pub due_date: NaiveDate,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub paid_at: Option<DateTime<Utc>>,
+
+    pub bill_to_company: String,
+    pub bill_to_contact_name: Option<String>,
+    pub bill_to_email: Option<String>,
+    pub bill_to_phone: Option<String>,
+
+    pub po_number: Option<String>,
+    pub project_reference: Option<String>,
+
+    pub currency: String,
+    pub payment_terms: String,
+
+    pub agency_commission_bps: i32,
+    pub tax_rate_bps: i32,
+    pub tax_exempt: bool,
+    pub discount_cents: i32,
+
+    /// How tax applies; see `invoice_tax`. Line-level rates live on the items
+    /// and expenses, `tax_rate_bps` is the default for lines without one.
+    pub tax_treatment: TaxTreatment,
+    pub prices_include_tax: bool,
+    pub tax_label: Option<String>,
+    pub tax_jurisdiction: Option<String>,
+    pub customer_tax_id: Option<String>,
+    pub tax_note: Option<String>,
+
+    pub notes_internal: Option<String>,
+    pub payment_instructions: Option<String>,
+    pub footer_text: Option<String>,
+
+    pub subtotal_cents: i32,
+    pub expenses_cents: i32,
+    pub tax_cents: i32,
+    pub total_cents: i32,
+    pub agency_fee_cents: i32,
+    pub talent_net_cents: i32,
+
+    /// Sums of the invoice's payment records and credit notes, kept in step
+    /// by `invoices::settle`.
+    pub amount_paid_cents: i32,
+    pub amount_credited_cents: i32,
+    pub last_payment_at: Option<DateTime<Utc>>,
+
+    /// Storage path of the PDF the client was sent, once sent.
+    pub sent_pdf_path: Option<String>,
+    pub sent_pdf_sha256: Option<String>,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceItem {
+    pub id: String,
+    pub invoice_id: String,
+    pub sort_order: i32,
+    pub description: String,
+    pub talent_id: Option<String>,
+    pub talent_name: Option<String>,
+    pub date_of_service: Option<NaiveDate>,
+    pub rate_type: Option<String>,
+    pub quantity: f64,
+    pub unit_price_cents: i32,
+    pub line_total_cents: i32,
+    pub tax_rate_bps: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceExpense {
+    pub id: String,
+    pub invoice_id: String,
+    pub sort_order: i32,
+    pub description: String,
+    pub amount_cents: i32,
+    pub taxable: bool,
+    pub tax_rate_bps: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Invoice {
+    /// What the client still owes after payments and credit notes.
+    pub fn balance_due_cents(&self) -> i64 {
+        (self.total_cents as i64
+            - self.amount_paid_cents as i64
+            - self.amount_credited_cents as i64)
+            .max(0)
+    }
+}
+
+#[cfg(test)]
+impl Invoice {
+    /// A $100.00 draft for "Acme Corp" with a 20% commission and no tax,
+    /// dated 2026-09-01 and due 2026-09-30. Tests override what they need
+    /// with struct update syntax.
+    pub(crate) fn fixture() -> Self {
+        let created_at = NaiveDate::from_ymd_opt(2026, 9, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc();
+        Invoice {
+            id: "inv-1".into(),
+            agency_id: "agency-1".into(),
+            client_id: "client-1".into(),
+            booking_id: None,
+            invoice_number: "INV-1".into(),
+            status: InvoiceStatus::Draft,
+            invoice_date: NaiveDate::from_ymd_opt(2026, 9, 1).unwrap(),
+            due_date: NaiveDate::from_ymd_opt(2026, 9, 30).unwrap(),
+            sent_at: None,
+            paid_at: None,
+            bill_to_company: "Acme Corp".into(),
+            bill_to_contact_name: None,
+            bill_to_email: None,
+            bill_to_phone: None,
+            po_number: None,
+            project_reference: None,
+            currency: "USD".into(),
+            payment_terms: "net_30".into(),
+            agency_commission_bps: 2000,
+            tax_rate_bps: 0,
+            tax_exempt: false,
+            discount_cents: 0,
+            tax_treatment: TaxTreatment::Standard,
+            prices_include_tax: false,
+            tax_label: None,
+            tax_jurisdiction: None,
+            customer_tax_id: None,
+            tax_note: None,
+            notes_internal: None,
+            payment_instructions: None,
+            footer_text: None,
+            subtotal_cents: 10000,
+            expenses_cents: 0,
+            tax_cents: 0,
+            total_cents: 10000,
+            agency_fee_cents: 2000,
+            talent_net_cents: 8000,
+            amount_paid_cents: 0,
+            amount_credited_cents: 0,
+            last_payment_at: None,
+            sent_pdf_path: None,
+            sent_pdf_sha256: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoicePayment {
+    pub id: String,
+    pub agency_id: String,
+    pub invoice_id: String,
+    pub amount_cents: i32,
+    pub paid_on: NaiveDate,
+    pub method: String,
+    pub reference: Option<String>,
+    pub notes: Option<String>,
+    pub recorded_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A payment to record; `id` and `created_at` come from the database.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewInvoicePayment {
+    pub agency_id: String,
+    pub invoice_id: String,
+    pub amount_cents: i32,
+    pub paid_on: NaiveDate,
+    pub method: String,
+    pub reference: Option<String>,
+    pub notes: Option<String>,
+    pub recorded_by: Option<String>,
+}
+
+/// Reduces what the client owes on an invoice, optionally against a single
+/// line item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceCreditNote {
+    pub id: String,
+    pub agency_id: String,
+    pub invoice_id: String,
+    pub invoice_item_id: Option<String>,
+    pub credit_note_number: String,
+    pub issued_on: NaiveDate,
+    pub amount_cents: i32,
+    pub reason: String,
+    pub issued_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewInvoiceCreditNote {
+    pub agency_id: String,
+    pub invoice_id: String,
+    pub invoice_item_id: Option<String>,
+    pub credit_note_number: String,
+    pub issued_on: NaiveDate,
+    pub amount_cents: i32,
+    pub reason: String,
+    pub issued_by: Option<String>,
+}
+
+/// Payment state derived from an invoice's payments and credit notes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvoiceSettlement {
+    pub status: InvoiceStatus,
+    pub amount_paid_cents: i32,
+    pub amount_credited_cents: i32,
+    pub last_payment_at: Option<DateTime<Utc>>,
+    pub paid_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InvoiceFilter {
+    pub status: Option<String>,
+    pub date_start: Option<String>,
+    pub date_end: Option<String>,
+}
+
+#[async_trait]
+pub trait InvoiceRepository: Send + Sync {
+    async fn list_for_agency(
+        &self,
+        agency_id: &str,
+        filter: &InvoiceFilter,
+    ) -> Result<Vec<Invoice>, RepoError>;
+
+    /// Returns `RepoError::NotFound` when the invoice does not belong to `agency_id`.
+    async fn get_for_agency(&self, agency_id: &str, id: &str) -> Result<Invoice, RepoError>;
+
+    async fn items(&self, invoice_id: &str) -> Result<Vec<InvoiceItem>, RepoError>;
+
+    async fn expenses(&self, invoice_id: &str) -> Result<Vec<InvoiceExpense>, RepoError>;
+
+    /// Moves the invoice to `status`, stamping `sent_at`/`paid_at` where relevant.
+    async fn set_status(
+        &self,
+        agency_id: &str,
+        id: &str,
+        status: InvoiceStatus,
+        at: DateTime<Utc>,
+    ) -> Result<Invoice, RepoError>;
+
+    async fn payments(&self, invoice_id: &str) -> Result<Vec<InvoicePayment>, RepoError>;
+
+    async fn insert_payment(
+        &self,
+        payment: &NewInvoicePayment,
+    ) -> Result<InvoicePayment, RepoError>;
+
+    /// Returns `RepoError::NotFound` when the payment is not on the invoice.
+    async fn delete_payment(&self, invoice_id: &str, payment_id: &str) -> Result<(), RepoError>;
+
+    async fn credit_notes(&self, invoice_id: &str) -> Result<Vec<InvoiceCreditNote>, RepoError>;
+
+    async fn insert_credit_note(
+        &self,
+        note: &NewInvoiceCreditNote,
+    ) -> Result<InvoiceCreditNote, RepoError>;
+
+    /// Stores the status and sums derived from payments and credit notes.
+    async fn set_settlement(
+        &self,
+        agency_id: &str,
+        id: &str,
+        settlement: &InvoiceSettlement,
+    ) -> Result<Invoice, RepoError>;
+
+    /// Points the invoice at the stored copy of the PDF that was sent.
+    async fn set_sent_pdf(
+        &self,
+        agency_id: &str,
+        id: &str,
+        path: &str,
+        sha256: &str,
+    ) -> Result<Invoice, RepoError>;
+}
+
+pub struct PostgrestInvoiceRepository {
+    pg: Postgrest,
+}
+
+impl PostgrestInvoiceRepository {
+    pub fn new(pg: Postgrest) -> Self {
+        Self { pg }
+    }
+}
+
+#[async_trait]
+impl InvoiceRepository for PostgrestInvoiceRepository {
+    async fn list_for_agency(
+        &self,
+        agency_id: &str,
+        filter: &InvoiceFilter,
+    ) -> Result<Vec<Invoice>, RepoError> {
+        let mut req = self
+            .pg
+            .from("agency_invoices")
+            .select("*")
+            .eq("agency_id", agency_id)
+            .order("created_at.desc");
+        if let Some(s) = filter.status.as_ref().filter(|s| !s.is_empty()) {
+            req = req.eq("status", s);
+        }
+        if let Some(d) = filter.date_start.as_ref().filter(|s| !s.is_empty()) {
+            req = req.gte("invoice_date", d);
+        }
+        if let Some(d) = filter.date_end.as_ref().filter(|s| !s.is_empty()) {
+            req = req.lte("invoice_date", d);
+        }
+        fetch(req).await
+    }
+
+    async fn get_for_agency(&self, agency_id: &str, id: &str) -> Result<Invoice, RepoError> {
+        let rows: Vec<Invoice> = fetch(
+            self.pg
+                .from("agency_invoices")
+                .select("*")
+                .eq("id", id)
+                .eq("agency_id", agency_id)
+                .limit(1),
+        )
+        .await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+
+    async fn items(&self, invoice_id: &str) -> Result<Vec<InvoiceItem>, RepoError> {
+        fetch(
+            self.pg
+                .from("agency_invoice_items")
+                .select("*")
+                .eq("invoice_id", invoice_id)
+                .order("sort_order.asc"),
+        )
+        .await
+    }
+
+    async fn expenses(&self, invoice_id: &str) -> Result<Vec<InvoiceExpense>, RepoError> {
+        fetch(
+            self.pg
+                .from("agency_invoice_expenses")
+                .select("*")
+                .eq("invoice_id", invoice_id)
+                .order("sort_order.asc"),
+        )
+        .await
+    }
+
+    async fn set_status(
+        &self,
+        agency_id: &str,
+        id: &str,
+        status: InvoiceStatus,
+        at: DateTime<Utc>,
+    ) -> Result<Invoice, RepoError> {
+        let mut body = json!({
+            "status": status.as_str(),
+            "updated_at": at.to_rfc3339(),
+        });
+        match status {
+            InvoiceStatus::Sent => body["sent_at"] = json!(at.to_rfc3339()),
+            InvoiceStatus::Paid => body["paid_at"] = json!(at.to_rfc3339()),
+            _ => {}
+        }
+        let rows: Vec<Invoice> = fetch(
+            self.pg
+                .from("agency_invoices")
+                .update(body.to_string())
+                .eq("id", id)
+                .eq("agency_id", agency_id),
+        )
+        .await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+
+    async fn payments(&self, invoice_id: &str) -> Result<Vec<InvoicePayment>, RepoError> {
+        fetch(
+            self.pg
+                .from("agency_invoice_payments")
+                .select("*")
+                .eq("invoice_id", invoice_id)
+                .order("paid_on.asc,created_at.asc"),
+        )
+        .await
+    }
+
+    async fn insert_payment(
+        &self,
+        payment: &NewInvoicePayment,
+    ) -> Result<InvoicePayment, RepoError> {
+        let body = serde_json::to_string(payment).map_err(|e| RepoError::Decode(e.to_string()))?;
+        let rows: Vec<InvoicePayment> =
+            fetch(self.pg.from("agency_invoice_payments").insert(body)).await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+
+    async fn delete_payment(&self, invoice_id: &str, payment_id: &str) -> Result<(), RepoError> {
+        let rows: Vec<InvoicePayment> = fetch(
+            self.pg
+                .from("agency_invoice_payments")
+                .delete()
+                .eq("id", payment_id)
+                .eq("invoice_id", invoice_id),
+        )
+        .await?;
+        if rows.is_empty() {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn credit_notes(&self, invoice_id: &str) -> Result<Vec<InvoiceCreditNote>, RepoError> {
+        fetch(
+            self.pg
+                .from("agency_invoice_credit_notes")
+                .select("*")
+                .eq("invoice_id", invoice_id)
+                .order("created_at.asc"),
+        )
+        .await
+    }
+
+    async fn insert_credit_note(
+        &self,
+        note: &NewInvoiceCreditNote,
+    ) -> Result<InvoiceCreditNote, RepoError> {
+        let body = serde_json::to_string(note).map_err(|e| RepoError::Decode(e.to_string()))?;
+        let rows: Vec<InvoiceCreditNote> =
+            fetch(self.pg.from("agency_invoice_credit_notes").insert(body)).await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+
+    async fn set_settlement(
+        &self,
+        agency_id: &str,
+        id: &str,
+        settlement: &InvoiceSettlement,
+    ) -> Result<Invoice, RepoError> {
+        let body = json!({
+            "status": settlement.status.as_str(),
+            "amount_paid_cents": settlement.amount_paid_cents,
+            "amount_credited_cents": settlement.amount_credited_cents,
+            "last_payment_at": settlement.last_payment_at.map(|t| t.to_rfc3339()),
+            "paid_at": settlement.paid_at.map(|t| t.to_rfc3339()),
+            "updated_at": Utc::now().to_rfc3339(),
+        });
+        let rows: Vec<Invoice> = fetch(
+            self.pg
+                .from("agency_invoices")
+                .update(body.to_string())
+                .eq("id", id)
+                .eq("agency_id", agency_id),
+        )
+        .await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+
+    async fn set_sent_pdf(
+        &self,
+        agency_id: &str,
+        id: &str,
+        path: &str,
+        sha256: &str,
+    ) -> Result<Invoice, RepoError> {
+        let body = json!({
+            "sent_pdf_path": path,
+            "sent_pdf_sha256": sha256,
+            "sent_pdf_generated_at": Utc::now().to_rfc3339(),
+        });
+        let rows: Vec<Invoice> = fetch(
+            self.pg
+                .from("agency_invoices")
+                .update(body.to_string())
+                .eq("id", id)
+                .eq("agency_id", agency_id),
+        )
+        .await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryInvoiceRepository {
+    invoices: Mutex<HashMap<String, Invoice>>,
+    items: Mutex<Vec<InvoiceItem>>,
+    expenses: Mutex<Vec<InvoiceExpense>>,
+    payments: Mutex<Vec<InvoicePayment>>,
+    credit_notes: Mutex<Vec<InvoiceCreditNote>>,
+}
+
+impl InMemoryInvoiceRepository {
+    pub fn insert(&self, invoice: Invoice) {
+        self.invoices
+            .lock()
+            .unwrap()
+            .insert(invoice.id.clone(), invoice);
+    }
+
+    pub fn insert_item(&self, item: InvoiceItem) {
+        self.items.lock().unwrap().push(item);
+    }
+
+    pub fn insert_expense(&self, expense: InvoiceExpense) {
+        self.expenses.lock().unwrap().push(expense);
+    }
+}
+
+#[async_trait]
+impl InvoiceRepository for InMemoryInvoiceRepository {
+    async fn list_for_agency(
+        &self,
+        agency_id: &str,
+        filter: &InvoiceFilter,
+    ) -> Result<Vec<Invoice>, RepoError> {
+        let start = filter
+            .date_start
+            .as_deref()
+            .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
+        let end = filter
+            .date_end
+            .as_deref()
+            .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
+        let mut out: Vec<Invoice> = self
+            .invoices
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|i| i.agency_id == agency_id)
+            .filter(|i| {
+                filter
+                    .status
+                    .as_deref()
+                    .filter(|s| !s.is_empty())
+                    .is_none_or(|s| i.status.as_str() == s)
+            })
+            .filter(|i| start.is_none_or(|d| i.invoice_date >= d))
+            .filter(|i| end.is_none_or(|d| i.invoice_date <= d))
+            .cloned()
+            .collect();
+        out.sort_by_key(|i| std::cmp::Reverse(i.created_at));
+        Ok(out)
+    }
+
+    async fn get_for_agency(&self, agency_id: &str, id: &str) -> Result<Invoice, RepoError> {
+        self.invoices
+            .lock()
+            .unwrap()
+            .get(id)
+            .filter(|i| i.agency_id == agency_id)
+            .cloned()
+            .ok_or(RepoError::NotFound)
+    }
+
+    async fn items(&self, invoice_id: &str) -> Result<Vec<InvoiceItem>, RepoError> {
+        let mut out: Vec<InvoiceItem> = self
+            .items
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|it| it.invoice_id == invoice_id)
+            .cloned()
+            .collect();
+        out.sort_by_key(|it| it.sort_order);
+        Ok(out)
+    }
+
+    async fn expenses(&self, invoice_id: &str) -> Result<Vec<InvoiceExpense>, RepoError> {
+        let mut out: Vec<InvoiceExpense> = self
+            .expenses
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|ex| ex.invoice_id == invoice_id)
+            .cloned()
+            .collect();
+        out.sort_by_key(|ex| ex.sort_order);
+        Ok(out)
+    }
+
+    async fn set_status(
+        &self,
+        agency_id: &str,
+        id: &str,
+        status: InvoiceStatus,
+        at: DateTime<Utc>,
+    ) -> Result<Invoice, RepoError> {
+        let mut invoices = self.invoices.lock().unwrap();
+        let invoice = invoices
+            .get_mut(id)
+            .filter(|i| i.agency_id == agency_id)
+            .ok_or(RepoError::NotFound)?;
+        invoice.status = status;
+        invoice.updated_at = at;
+        match status {
+            InvoiceStatus::Sent => invoice.sent_at = Some(at),
+            InvoiceStatus::Paid => invoice.paid_at = Some(at),
+            _ => {}
+        }
+        Ok(invoice.clone())
+    }
+
+    async fn payments(&self, invoice_id: &str) -> Result<Vec<InvoicePayment>, RepoError> {
+        let mut out: Vec<InvoicePayment> = self
+            .payments
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.invoice_id == invoice_id)
+            .cloned()
+            .collect();
+        out.sort_by_key(|p| (p.paid_on, p.created_at));
+        Ok(out)
+    }
+
+    async fn insert_payment(
+        &self,
+        payment: &NewInvoicePayment,
+    ) -> Result<InvoicePayment, RepoError> {
+        let row = InvoicePayment {
+            id: uuid::Uuid::new_v4().to_string(),
+            agency_id: payment.agency_id.clone(),
+            invoice_id: payment.invoice_id.clone(),
+            amount_cents: payment.amount_cents,
+            paid_on: payment.paid_on,
+            method: payment.method.clone(),
+            reference: payment.reference.clone(),
+            notes: payment.notes.clone(),
+            recorded_by: payment.recorded_by.clone(),
+            created_at: Utc::now(),
+        };
+        self.payments.lock().unwrap().push(row.clone());
+        Ok(row)
+    }
+
+    async fn delete_payment(&self, invoice_id: &str, payment_id: &str) -> Result<(), RepoError> {
+        let mut payments = self.payments.lock().unwrap();
+        let before = payments.len();
+        payments.retain(|p| !(p.id == payment_id && p.invoice_id == invoice_id));
+        if payments.len() == before {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn credit_notes(&self, invoice_id: &str) -> Result<Vec<InvoiceCreditNote>, RepoError> {
+        Ok(self
+            .credit_notes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|c| c.invoice_id == invoice_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn insert_credit_note(
+        &self,
+        note: &NewInvoiceCreditNote,
+    ) -> Result<InvoiceCreditNote, RepoError> {
+        let mut notes = self.credit_notes.lock().unwrap();
+        if notes.iter().any(|c| {
+            c.agency_id == note.agency_id && c.credit_note_number == note.credit_note_number
+        }) {
+            return Err(RepoError::Db {
+                status: 409,
+                body: "duplicate credit_note_number".to_string(),
+            });
+        }
+        let row = InvoiceCreditNote {
+            id: uuid::Uuid::new_v4().to_string(),
+            agency_id: note.agency_id.clone(),
+            invoice_id: note.invoice_id.clone(),
+            invoice_item_id: note.invoice_item_id.clone(),
+            credit_note_number: note.credit_note_number.clone(),
+            issued_on: note.issued_on,
+            amount_cents: note.amount_cents,
+            reason: note.reason.clone(),
+            issued_by: note.issued_by.clone(),
+            created_at: Utc::now(),
+        };
+        notes.push(row.clone());
+        Ok(row)
+    }
+
+    async fn set_settlement(
+        &self,
+        agency_id: &str,
+        id: &str,
+        settlement: &InvoiceSettlement,
+    ) -> Result<Invoice, RepoError> {
+        let mut invoices = self.invoices.lock().unwrap();
+        let invoice = invoices
+            .get_mut(id)
+            .filter(|i| i.agency_id == agency_id)
+            .ok_or(RepoError::NotFound)?;
+        invoice.status = settlement.status;
+        invoice.amount_paid_cents = settlement.amount_paid_cents;
+        invoice.amount_credited_cents = settlement.amount_credited_cents;
+        invoice.last_payment_at = settlement.last_payment_at;
+        invoice.paid_at = settlement.paid_at;
+        invoice.updated_at = Utc::now();
+        Ok(invoice.clone())
+    }
+
+    async fn set_sent_pdf(
+        &self,
+        agency_id: &str,
+        id: &str,
+        path: &str,
+        sha256: &str,
+    ) -> Result<Invoice, RepoError> {
+        let mut invoices = self.invoices.lock().unwrap();
+        let invoice = invoices
+            .get_mut(id)
+            .filter(|i| i.agency_id == agency_id)
+            .ok_or(RepoError::NotFound)?;
+        invoice.sent_pdf_path = Some(path.to_string());
+        invoice.sent_pdf_sha256 = Some(sha256.to_string());
+        Ok(invoice.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::decode;
+
+    /// The fixture as PostgREST returns it, timestamps included.
+    fn invoice_row() -> serde_json::Value {
+        let mut row = serde_json::to_value(Invoice::fixture()).unwrap();
+        row["created_at"] = json!("2026-09-01T10:00:00.123456+00:00");
+        row["updated_at"] = json!("2026-09-01T10:00:00+00:00");
+        row
+    }
+
+    #[test]
+    fn decodes_postgrest_invoice_row() {
+        let rows: Vec<Invoice> = decode(&json!([invoice_row()]).to_string()).unwrap();
+        assert_eq!(rows[0].status, InvoiceStatus::Draft);
+        assert_eq!(rows[0].total_cents, 10000);
+    }
+
+    #[test]
+    fn schema_drift_fails_decoding() {
+        let mut row = invoice_row();
+        row.as_object_mut().unwrap().remove("total_cents");
+        let res: Result<Vec<Invoice>, RepoError> = decode(&json!([row]).to_string());
+        assert!(matches!(res, Err(RepoError::Decode(_))));
+
+        let mut row = invoice_row();
+        row["status"] = json!("archived");
+        let res: Result<Vec<Invoice>, RepoError> = decode(&json!([row]).to_string());
+        assert!(matches!(res, Err(RepoError::Decode(_))));
+    }
+
+    #[tokio::test]
+    async fn in_memory_set_status_is_scoped_to_agency() {
+        let repo = InMemoryInvoiceRepository::default();
+        repo.insert(Invoice::fixture());
+
+        let err = repo
+            .set_status("agency-2", "inv-1", InvoiceStatus::Sent, Utc::now())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RepoError::NotFound));
+
+        let updated = repo
+            .set_status("agency-1", "inv-1", InvoiceStatus::Sent, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(updated.status, InvoiceStatus::Sent);
+        assert!(updated.sent_at.is_some());
+    }
+}
@@ -0,0 +1,222 @@
+use super::{fetch, RepoError};
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use postgrest::Postgrest;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LicensingRequestStatus {
+    Pending,
+    Negotiating,
+    Approved,
+    Rejected,
+    Declined,
+    Archived,
+}
+
+impl LicensingRequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LicensingRequestStatus::Pending => "pending",
+            LicensingRequestStatus::Negotiating => "negotiating",
+            LicensingRequestStatus::Approved => "approved",
+            LicensingRequestStatus::Rejected => "rejected",
+            LicensingRequestStatus::Declined => "declined",
+            LicensingRequestStatus::Archived => "archived",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        serde_json::from_value(json!(s.trim().to_lowercase())).ok()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LicensingRequest {
+    pub id: String,
+    pub agency_id: String,
+    pub brand_id: Option<String>,
+    pub talent_id: Option<String>,
+    pub talent_name: Option<String>,
+    pub client_name: Option<String>,
+    pub campaign_title: Option<String>,
+    pub submission_id: Option<String>,
+    pub status: LicensingRequestStatus,
+    pub notes: Option<String>,
+    pub negotiation_reason: Option<String>,
+    pub license_start_date: Option<NaiveDate>,
+    pub license_end_date: Option<NaiveDate>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+const LICENSING_REQUEST_COLUMNS: &str = "id,agency_id,brand_id,talent_id,talent_name,client_name,campaign_title,submission_id,status,notes,negotiation_reason,license_start_date,license_end_date,decided_at,archived_at,created_at";
+
+#[async_trait]
+pub trait LicensingRequestRepository: Send + Sync {
+    async fn get(&self, id: &str) -> Result<LicensingRequest, RepoError>;
+
+    async fn list_by_ids(
+        &self,
+        agency_id: &str,
+        ids: &[String],
+    ) -> Result<Vec<LicensingRequest>, RepoError>;
+
+    /// Updates every listed request owned by `agency_id`. `decided_at` is cleared
+    /// when moving back to `pending`.
+    async fn update_status(
+        &self,
+        agency_id: &str,
+        ids: &[String],
+        status: LicensingRequestStatus,
+        notes: Option<&str>,
+    ) -> Result<Vec<LicensingRequest>, RepoError>;
+}
+
+pub struct PostgrestLicensingRequestRepository {
+    pg: Postgrest,
+}
+
+impl PostgrestLicensingRequestRepository {
+    pub fn new(pg: Postgrest) -> Self {
+        Self { pg }
+    }
+}
+
+#[async_trait]
+impl LicensingRequestRepository for PostgrestLicensingRequestRepository {
+    async fn get(&self, id: &str) -> Result<LicensingRequest, RepoError> {
+        let rows: Vec<LicensingRequest> = fetch(
+            self.pg
+                .from("licensing_requests")
+                .select(LICENSING_REQUEST_COLUMNS)
+                .eq("id", id)
+                .limit(1),
+        )
+        .await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+
+    async fn list_by_ids(
+        &self,
+        agency_id: &str,
+        ids: &[String],
+    ) -> Result<Vec<LicensingRequest>, RepoError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        fetch(
+            self.pg
+                .from("licensing_requests")
+                .select(LICENSING_REQUEST_COLUMNS)
+                .eq("agency_id", agency_id)
+                .in_("id", ids.iter().map(|s| s.as_str())),
+        )
+        .await
+    }
+
+    async fn update_status(
+        &self,
+        agency_id: &str,
+        ids: &[String],
+        status: LicensingRequestStatus,
+        notes: Option<&str>,
+    ) -> Result<Vec<LicensingRequest>, RepoError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut body = json!({
+            "status": status.as_str(),
+            "decided_at": if status == LicensingRequestStatus::Pending {
+                serde_json::Value::Null
+            } else {
+                json!(Utc::now().to_rfc3339())
+            },
+        });
+        if let Some(n) = notes {
+            body["notes"] = json!(n);
+            body["negotiation_reason"] = json!(n);
+        }
+        fetch(
+            self.pg
+                .from("licensing_requests")
+                .eq("agency_id", agency_id)
+                .in_("id", ids.iter().map(|s| s.as_str()))
+                .update(body.to_string())
+                .select(LICENSING_REQUEST_COLUMNS),
+        )
+        .await
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryLicensingRequestRepository {
+    requests: Mutex<HashMap<String, LicensingRequest>>,
+}
+
+impl InMemoryLicensingRequestRepository {
+    pub fn insert(&self, request: LicensingRequest) {
+        self.requests
+            .lock()
+            .unwrap()
+            .insert(request.id.clone(), request);
+    }
+}
+
+#[async_trait]
+impl LicensingRequestRepository for InMemoryLicensingRequestRepository {
+    async fn get(&self, id: &str) -> Result<LicensingRequest, RepoError> {
+        self.requests
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or(RepoError::NotFound)
+    }
+
+    async fn list_by_ids(
+        &self,
+        agency_id: &str,
+        ids: &[String],
+    ) -> Result<Vec<LicensingRequest>, RepoError> {
+        let requests = self.requests.lock().unwrap();
+        Ok(ids
+            .iter()
+            .filter_map(|id| requests.get(id))
+            .filter(|r| r.agency_id == agency_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn update_status(
+        &self,
+        agency_id: &str,
+        ids: &[String],
+        status: LicensingRequestStatus,
+        notes: Option<&str>,
+    ) -> Result<Vec<LicensingRequest>, RepoError> {
+        let mut requests = self.requests.lock().unwrap();
+        let mut out = vec![];
+        for id in ids {
+            if let Some(r) = requests.get_mut(id).filter(|r| r.agency_id == agency_id) {
+                r.status = status;
+                r.decided_at = if status == LicensingRequestStatus::Pending {
+                    None
+                } else {
+                    Some(Utc::now())
+                };
+                if let Some(n) = notes {
+                    r.notes = Some(n.to_string());
+                    r.negotiation_reason = Some(n.to_string());
+                }
+                out.push(r.clone());
+            }
+        }
+        Ok(out)
+    }
+}
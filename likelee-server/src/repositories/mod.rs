@@ -0,0 +1,131 @@
+//! Typed data access over PostgREST.
+//!
+//! Each aggregate exposes a repository trait that returns domain structs, with
+//! one implementation backed by `postgrest::Postgrest` and one kept in memory
+//! for tests. Rows are decoded strictly: a missing or mistyped column surfaces
+//! as `RepoError::Decode` instead of silently defaulting to zero.
+
+pub mod balances;
+pub mod bookings;
+pub mod invoices;
+pub mod licensing_requests;
+pub mod payouts;
+
+use crate::errors::AppError;
+use axum::http::StatusCode;
+use postgrest::Postgrest;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+
+pub use balances::{AgencyBalance, BalanceRepository, CreatorBalance};
+pub use bookings::{Booking, BookingFilter, BookingRepository, BookingStatus};
+pub use invoices::{
+    Invoice, InvoiceCreditNote, InvoiceExpense, InvoiceFilter, InvoiceItem, InvoicePayment,
+    InvoiceRepository, InvoiceSettlement, InvoiceStatus, NewInvoiceCreditNote, NewInvoicePayment,
+};
+pub use licensing_requests::{
+    LicensingRequest, LicensingRequestRepository, LicensingRequestStatus,
+};
+pub use payouts::{AgencyPayoutRequest, CreatorPayoutRequest, PayoutRepository, PayoutStatus};
+
+#[derive(Debug)]
+pub enum RepoError {
+    /// The row does not exist or is not owned by the caller.
+    NotFound,
+    /// PostgREST answered with a non-success status.
+    Db { status: u16, body: String },
+    /// The request never reached PostgREST or the body could not be read.
+    Transport(String),
+    /// The response did not match the expected row shape.
+    Decode(String),
+}
+
+impl std::fmt::Display for RepoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepoError::NotFound => write!(f, "not_found"),
+            RepoError::Db { status, body } => write!(f, "db_error status={status} body={body}"),
+            RepoError::Transport(e) => write!(f, "transport_error: {e}"),
+            RepoError::Decode(e) => write!(f, "decode_error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RepoError {}
+
+impl From<RepoError> for AppError {
+    fn from(err: RepoError) -> Self {
+        match err {
+            RepoError::NotFound => AppError::NotFound("not_found".to_string()),
+            RepoError::Db { status, body } => AppError::db(status, body),
+            RepoError::Transport(e) => AppError::internal("repository.transport", e),
+            RepoError::Decode(e) => AppError::internal("repository.decode", e),
+        }
+    }
+}
+
+impl From<RepoError> for (StatusCode, String) {
+    fn from(err: RepoError) -> Self {
+        AppError::from(err).into()
+    }
+}
+
+/// Executes a PostgREST request and decodes the JSON body into `T`.
+pub(crate) async fn fetch<T: DeserializeOwned>(req: postgrest::Builder) -> Result<T, RepoError> {
+    let resp = req
+        .execute()
+        .await
+        .map_err(|e| RepoError::Transport(e.to_string()))?;
+    let status = resp.status();
+    let text = resp
+        .text()
+        .await
+        .map_err(|e| RepoError::Transport(e.to_string()))?;
+    if !status.is_success() {
+        return Err(RepoError::Db {
+            status: status.as_u16(),
+            body: text,
+        });
+    }
+    decode(&text)
+}
+
+pub(crate) fn decode<T: DeserializeOwned>(text: &str) -> Result<T, RepoError> {
+    serde_json::from_str(text).map_err(|e| RepoError::Decode(e.to_string()))
+}
+
+/// Repository handles shared through `AppState`.
+#[derive(Clone)]
+pub struct Repositories {
+    pub invoices: Arc<dyn InvoiceRepository>,
+    pub bookings: Arc<dyn BookingRepository>,
+    pub licensing_requests: Arc<dyn LicensingRequestRepository>,
+    pub balances: Arc<dyn BalanceRepository>,
+    pub payouts: Arc<dyn PayoutRepository>,
+}
+
+impl Repositories {
+    pub fn postgrest(pg: Postgrest) -> Self {
+        Self {
+            invoices: Arc::new(invoices::PostgrestInvoiceRepository::new(pg.clone())),
+            bookings: Arc::new(bookings::PostgrestBookingRepository::new(pg.clone())),
+            licensing_requests: Arc::new(
+                licensing_requests::PostgrestLicensingRequestRepository::new(pg.clone()),
+            ),
+            balances: Arc::new(balances::PostgrestBalanceRepository::new(pg.clone())),
+            payouts: Arc::new(payouts::PostgrestPayoutRepository::new(pg)),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            invoices: Arc::new(invoices::InMemoryInvoiceRepository::default()),
+            bookings: Arc::new(bookings::InMemoryBookingRepository::default()),
+            licensing_requests: Arc::new(
+                licensing_requests::InMemoryLicensingRequestRepository::default(),
+            ),
+            balances: Arc::new(balances::InMemoryBalanceRepository::default()),
+            payouts: Arc::new(payouts::InMemoryPayoutRepository::default()),
+        }
+    }
+}
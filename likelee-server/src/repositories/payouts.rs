@@ -0,0 +1,251 @@
+use super::{fetch, RepoError};
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use postgrest::Postgrest;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayoutStatus {
+    Pending,
+    Approved,
+    Processing,
+    Paid,
+    Failed,
+    Cancelled,
+}
+
+impl PayoutStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayoutStatus::Pending => "pending",
+            PayoutStatus::Approved => "approved",
+            PayoutStatus::Processing => "processing",
+            PayoutStatus::Paid => "paid",
+            PayoutStatus::Failed => "failed",
+            PayoutStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgencyPayoutRequest {
+    pub id: String,
+    pub agency_id: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub payout_method: String,
+    pub status: PayoutStatus,
+    pub requested_at: DateTime<Utc>,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub stripe_transfer_id: Option<String>,
+    pub stripe_payout_id: Option<String>,
+    pub failure_reason: Option<String>,
+    /// Platform fee charged and the fee schedule version it came from; unset
+    /// on requests made before fees were stored.
+    #[serde(default)]
+    pub fee_cents: Option<i64>,
+    #[serde(default)]
+    pub net_cents: Option<i64>,
+    #[serde(default)]
+    pub fee_schedule_version: Option<i32>,
+}
+
+const AGENCY_PAYOUT_COLUMNS: &str = "id,agency_id,amount_cents,currency,payout_method,status,requested_at,processed_at,stripe_transfer_id,stripe_payout_id,failure_reason,fee_cents,net_cents,fee_schedule_version";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatorPayoutRequest {
+    pub id: String,
+    pub creator_id: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub payout_method: String,
+    pub status: PayoutStatus,
+    pub created_at: DateTime<Utc>,
+    pub requested_at: DateTime<Utc>,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub stripe_transfer_id: Option<String>,
+    pub stripe_payout_id: Option<String>,
+    pub failure_reason: Option<String>,
+}
+
+const CREATOR_PAYOUT_COLUMNS: &str = "id,creator_id,amount_cents,payout_method,currency,status,created_at,requested_at,processed_at,stripe_transfer_id,stripe_payout_id,failure_reason";
+
+#[async_trait]
+pub trait PayoutRepository: Send + Sync {
+    async fn agency_history(&self, agency_id: &str) -> Result<Vec<AgencyPayoutRequest>, RepoError>;
+
+    async fn creator_history(
+        &self,
+        creator_id: &str,
+        limit: usize,
+    ) -> Result<Vec<CreatorPayoutRequest>, RepoError>;
+
+    async fn get_agency_request(&self, id: &str) -> Result<AgencyPayoutRequest, RepoError>;
+
+    async fn set_agency_request_status(
+        &self,
+        id: &str,
+        status: PayoutStatus,
+        failure_reason: Option<&str>,
+    ) -> Result<AgencyPayoutRequest, RepoError>;
+}
+
+pub struct PostgrestPayoutRepository {
+    pg: Postgrest,
+}
+
+impl PostgrestPayoutRepository {
+    pub fn new(pg: Postgrest) -> Self {
+        Self { pg }
+    }
+}
+
+#[async_trait]
+impl PayoutRepository for PostgrestPayoutRepository {
+    async fn agency_history(&self, agency_id: &str) -> Result<Vec<AgencyPayoutRequest>, RepoError> {
+        fetch(
+            self.pg
+                .from("agency_payout_requests")
+                .select(AGENCY_PAYOUT_COLUMNS)
+                .eq("agency_id", agency_id)
+                .order("requested_at.desc"),
+        )
+        .await
+    }
+
+    async fn creator_history(
+        &self,
+        creator_id: &str,
+        limit: usize,
+    ) -> Result<Vec<CreatorPayoutRequest>, RepoError> {
+        fetch(
+            self.pg
+                .from("creator_payout_requests")
+                .select(CREATOR_PAYOUT_COLUMNS)
+                .eq("creator_id", creator_id)
+                .order("created_at.desc")
+                .limit(limit),
+        )
+        .await
+    }
+
+    async fn get_agency_request(&self, id: &str) -> Result<AgencyPayoutRequest, RepoError> {
+        let rows: Vec<AgencyPayoutRequest> = fetch(
+            self.pg
+                .from("agency_payout_requests")
+                .select(AGENCY_PAYOUT_COLUMNS)
+                .eq("id", id)
+                .limit(1),
+        )
+        .await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+
+    async fn set_agency_request_status(
+        &self,
+        id: &str,
+        status: PayoutStatus,
+        failure_reason: Option<&str>,
+    ) -> Result<AgencyPayoutRequest, RepoError> {
+        let mut body = json!({ "status": status.as_str() });
+        if let Some(reason) = failure_reason {
+            body["failure_reason"] = json!(reason);
+        }
+        if matches!(status, PayoutStatus::Paid | PayoutStatus::Failed) {
+            body["processed_at"] = json!(Utc::now().to_rfc3339());
+        }
+        let rows: Vec<AgencyPayoutRequest> = fetch(
+            self.pg
+                .from("agency_payout_requests")
+                .eq("id", id)
+                .update(body.to_string())
+                .select(AGENCY_PAYOUT_COLUMNS),
+        )
+        .await?;
+        rows.into_iter().next().ok_or(RepoError::NotFound)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryPayoutRepository {
+    agency: Mutex<Vec<AgencyPayoutRequest>>,
+    creator: Mutex<Vec<CreatorPayoutRequest>>,
+}
+
+impl InMemoryPayoutRepository {
+    pub fn insert_agency_request(&self, request: AgencyPayoutRequest) {
+        self.agency.lock().unwrap().push(request);
+    }
+
+    pub fn insert_creator_request(&self, request: CreatorPayoutRequest) {
+        self.creator.lock().unwrap().push(request);
+    }
+}
+
+#[async_trait]
+impl PayoutRepository for InMemoryPayoutRepository {
+    async fn agency_history(&self, agency_id: &str) -> Result<Vec<AgencyPayoutRequest>, RepoError> {
+        let mut out: Vec<AgencyPayoutRequest> = self
+            .agency
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.agency_id == agency_id)
+            .cloned()
+            .collect();
+        out.sort_by_key(|r| std::cmp::Reverse(r.requested_at));
+        Ok(out)
+    }
+
+    async fn creator_history(
+        &self,
+        creator_id: &str,
+        limit: usize,
+    ) -> Result<Vec<CreatorPayoutRequest>, RepoError> {
+        let mut out: Vec<CreatorPayoutRequest> = self
+            .creator
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.creator_id == creator_id)
+            .cloned()
+            .collect();
+        out.sort_by_key(|r| std::cmp::Reverse(r.created_at));
+        out.truncate(limit);
+        Ok(out)
+    }
+
+    async fn get_agency_request(&self, id: &str) -> Result<AgencyPayoutRequest, RepoError> {
+        self.agency
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|r| r.id == id)
+            .cloned()
+            .ok_or(RepoError::NotFound)
+    }
+
+    async fn set_agency_request_status(
+        &self,
+        id: &str,
+        status: PayoutStatus,
+        failure_reason: Option<&str>,
+    ) -> Result<AgencyPayoutRequest, RepoError> {
+        let mut rows = self.agency.lock().unwrap();
+        let row = rows
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or(RepoError::NotFound)?;
+        row.status = status;
+        if let Some(reason) = failure_reason {
+            row.failure_reason = Some(reason.to_string());
+        }
+        if matches!(status, PayoutStatus::Paid | PayoutStatus::Failed) {
+            row.processed_at = Some(Utc::now());
+        }
+        Ok(row.clone())
+    }
+}
@@ -1,7 +1,8 @@
 use crate::config::AppState;
+use crate::rate_limit::RouteGroup;
 use axum::{
     extract::DefaultBodyLimit,
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 use tower_http::cors::{Any, CorsLayer};
@@ -175,6 +176,14 @@ pub fn build_router(state: AppState) -> Router {
             "/api/agency/payouts/history",
             get(crate::payouts::get_agency_payout_history),
         )
+        .route(
+            "/api/agency/payouts/preview",
+            get(crate::payout_fees::preview),
+        )
+        .route(
+            "/api/agency/payouts/fee-schedule",
+            get(crate::payout_fees::get_own),
+        )
         // Agency Payout Settings & Schedule
         .route(
             "/api/agency/payout-settings",
@@ -388,6 +397,7 @@ pub fn build_router(state: AppState) -> Router {
             "/api/invoices",
             get(crate::invoices::list).post(crate::invoices::create),
         )
+        .route("/api/invoices/tax-summary", get(crate::invoice_tax::report))
         .route(
             "/api/invoices/:id",
             get(crate::invoices::get).post(crate::invoices::update),
@@ -396,6 +406,21 @@ pub fn build_router(state: AppState) -> Router {
             "/api/invoices/:id/mark-sent",
             post(crate::invoices::mark_sent),
         )
+        .route("/api/invoices/:id/pdf", get(crate::invoices::pdf))
+        .route(
+            "/api/invoices/:id/payments",
+            get(crate::invoice_payments::list_payments)
+                .post(crate::invoice_payments::record_payment),
+        )
+        .route(
+            "/api/invoices/:id/payments/:payment_id",
+            delete(crate::invoice_payments::delete_payment),
+        )
+        .route(
+            "/api/invoices/:id/credit-notes",
+            get(crate::invoice_payments::list_credit_notes)
+                .post(crate::invoice_payments::issue_credit_note),
+        )
         .route(
             "/api/invoices/:id/send-payment-reminder",
             post(crate::invoices::send_payment_reminder),
@@ -408,14 +433,91 @@ pub fn build_router(state: AppState) -> Router {
             "/api/invoices/:id/void",
             post(crate::invoices::void_invoice),
         )
+        .route(
+            "/api/recurring-invoices",
+            get(crate::recurring_invoices::list).post(crate::recurring_invoices::create),
+        )
+        .route(
+            "/api/recurring-invoices/:id",
+            get(crate::recurring_invoices::get),
+        )
+        .route(
+            "/api/recurring-invoices/:id/pause",
+            post(crate::recurring_invoices::pause),
+        )
+        .route(
+            "/api/recurring-invoices/:id/resume",
+            post(crate::recurring_invoices::resume),
+        )
+        .route(
+            "/api/recurring-invoices/:id/preview",
+            get(crate::recurring_invoices::preview),
+        )
         .route(
             "/api/talent-statements",
             get(crate::talent_statements::list),
         )
+        .route(
+            "/api/talent-statements/export",
+            get(crate::talent_statement_exports::export),
+        )
+        .route(
+            "/api/talent-statements/send",
+            post(crate::talent_statement_exports::send_statements),
+        )
+        .route(
+            "/api/talent-statements/deliveries",
+            get(crate::talent_statement_exports::list_deliveries),
+        )
+        .route(
+            "/api/talent-statements/deliveries/:id/pdf",
+            get(crate::talent_statement_exports::delivery_pdf),
+        )
+        .route(
+            "/api/talent-statements/settings",
+            get(crate::talent_statement_exports::get_settings)
+                .put(crate::talent_statement_exports::update_settings),
+        )
         .route(
             "/api/expenses",
             get(crate::expenses::list).post(crate::expenses::create),
         )
+        .route("/api/expenses/rebill", post(crate::expenses::rebill))
+        .route(
+            "/api/expenses/:id",
+            get(crate::expenses::get)
+                .patch(crate::expenses::update)
+                .delete(crate::expenses::delete),
+        )
+        .route("/api/expenses/:id/submit", post(crate::expenses::submit))
+        .route("/api/expenses/:id/approve", post(crate::expenses::approve))
+        .route("/api/expenses/:id/reject", post(crate::expenses::reject))
+        .route(
+            "/api/expenses/:id/reimburse",
+            post(crate::expenses::reimburse),
+        )
+        .route(
+            "/api/expenses/:id/receipts",
+            post(crate::expenses::upload_receipt),
+        )
+        .route(
+            "/api/expenses/:id/receipts/:receipt_id",
+            get(crate::expenses::receipt_file).delete(crate::expenses::delete_receipt),
+        )
+        .route(
+            "/api/accounting/accounts",
+            get(crate::accounting_export::get_accounts)
+                .put(crate::accounting_export::update_accounts),
+        )
+        .route(
+            "/api/accounting/export",
+            get(crate::accounting_export::export),
+        )
+        .route("/api/tax-documents", get(crate::tax_documents::list))
+        .route(
+            "/api/tax-documents/generate",
+            post(crate::tax_documents::generate),
+        )
         // Digitals & Share
         .route(
             "/api/agency/digitals",
@@ -579,10 +681,6 @@ pub fn build_router(state: AppState) -> Router {
                 .delete(crate::packages::delete_package)
                 .put(crate::packages::update_package),
         )
-        .route(
-            "/api/public/packages/:token",
-            get(crate::packages::get_public_package),
-        )
         // Catalogs
         .route(
             "/api/agency/catalogs",
@@ -596,18 +694,6 @@ pub fn build_router(state: AppState) -> Router {
             "/api/agency/catalogs/:id",
             delete(crate::catalogs::delete_catalog),
         )
-        .route(
-            "/api/public/catalogs/:token",
-            get(crate::catalogs::get_public_catalog),
-        )
-        .route(
-            "/api/public/packages/:token/full-assets-request",
-            post(crate::packages::create_public_package_full_assets_request),
-        )
-        .route(
-            "/api/public/packages/:token/interactions",
-            post(crate::packages::create_interaction).delete(crate::packages::delete_interaction),
-        )
         // --- Scouting ---
         .route(
             "/api/scouting/templates",
@@ -798,17 +884,78 @@ pub fn build_router(state: AppState) -> Router {
             "/api/invites/agency-talent/:token/decline",
             post(crate::agency_talent_invites::decline_by_token),
         )
+        // --- Admin ---
+        .route("/api/admin/jobs", get(crate::jobs::admin::list_jobs))
+        .route("/api/admin/jobs/runs", get(crate::jobs::admin::list_runs))
         .route(
-            "/api/invites/agency-talent/:token/magic-link",
-            get(crate::agency_talent_invites::get_magic_link_by_token),
+            "/api/admin/jobs/:name/run",
+            post(crate::jobs::admin::run_job),
+        )
+        .route(
+            "/api/admin/webhooks",
+            get(crate::webhooks::admin::list_events),
+        )
+        .route(
+            "/api/admin/webhooks/:id/replay",
+            post(crate::webhooks::admin::replay_event),
+        )
+        .route(
+            "/api/admin/ledger/accounts/:account",
+            get(crate::ledger::api::account_balance),
+        )
+        .route(
+            "/api/admin/ledger/drift",
+            get(crate::ledger::api::drift_report),
+        )
+        .route(
+            "/api/admin/payout-fee-schedules/:agency_id",
+            get(crate::payout_fees::admin_history).put(crate::payout_fees::admin_update),
+        )
+        .route(
+            "/api/admin/payout-reserves",
+            get(crate::payout_reserves::admin_list),
+        )
+        .route(
+            "/api/admin/payout-reserves/policies",
+            get(crate::payout_reserves::admin_policies)
+                .put(crate::payout_reserves::admin_save_policy),
+        )
+        .route(
+            "/api/admin/payout-reserves/policies/:id",
+            delete(crate::payout_reserves::admin_delete_policy),
+        )
+        .route(
+            "/api/admin/payout-reserves/brands/:brand_id",
+            put(crate::payout_reserves::admin_set_brand_risk),
+        )
+        .route(
+            "/api/admin/payout-reserves/:id/hold",
+            post(crate::payout_reserves::admin_hold),
+        )
+        .route(
+            "/api/admin/payout-reserves/:id/release",
+            post(crate::payout_reserves::admin_release),
+        )
+        .route(
+            "/api/admin/reconciliation",
+            get(crate::reconciliation::api::admin_report),
+        )
+        .route(
+            "/api/admin/reconciliation/runs",
+            get(crate::reconciliation::api::runs),
         )
         // --- Webhooks ---
         .route("/webhooks/stripe", post(crate::payouts::stripe_webhook))
         .route("/webhooks/kyc/veriff", post(crate::kyc::veriff_webhook))
-        .route("/webhooks/docuseal", post(crate::scouting::handle_webhook))
+        // Scouting and licensing contracts share one DocuSeal dispatcher; both
+        // URLs stay registered for existing DocuSeal webhook configurations.
+        .route(
+            "/webhooks/docuseal",
+            post(crate::webhooks::docuseal::handle_webhook),
+        )
         .route(
             "/api/webhooks/licenseContract",
-            post(crate::license_submissions::handle_webhook),
+            post(crate::webhooks::docuseal::handle_webhook),
         )
         // --- Integrations & Misc ---
         .route(
@@ -827,11 +974,73 @@ pub fn build_router(state: AppState) -> Router {
             "/api/notifications/booking-created-email",
             post(crate::notifications::booking_created_email),
         )
+        .route(
+            "/api/agency/email-deliveries",
+            get(crate::email::outbox::list_deliveries),
+        )
+        .route("/api/agency/audit-events", get(crate::audit::list_events))
+        .route(
+            "/api/agency/ledger/balance",
+            get(crate::ledger::api::agency_balance),
+        )
+        .route(
+            "/api/creator/ledger/balance",
+            get(crate::ledger::api::creator_balance),
+        )
+        .route(
+            "/api/agency/reconciliation",
+            get(crate::reconciliation::api::agency_report),
+        )
         .route(
             "/api/notifications/booking-notifications",
             get(crate::notifications::list_booking_notifications),
         )
+        .merge(public_token_routes(&state))
+        .merge(state.storage.routes().unwrap_or_default())
         .with_state(state)
+        .layer(axum::middleware::from_fn(crate::errors::request_context))
         .layer(DefaultBodyLimit::max(20_000_000)) // 20MB limit
         .layer(cors)
 }
+
+/// Unauthenticated routes addressed by a shared token, each group behind its
+/// own rate limit (see `crate::rate_limit`).
+fn public_token_routes(state: &AppState) -> Router<AppState> {
+    let limit = |group| {
+        axum::middleware::from_fn_with_state(
+            state.rate_limiter.group(group),
+            crate::rate_limit::enforce,
+        )
+    };
+
+    let packages = Router::new()
+        .route(
+            "/api/public/packages/:token",
+            get(crate::packages::get_public_package),
+        )
+        .route(
+            "/api/public/packages/:token/full-assets-request",
+            post(crate::packages::create_public_package_full_assets_request),
+        )
+        .route(
+            "/api/public/packages/:token/interactions",
+            post(crate::packages::create_interaction).delete(crate::packages::delete_interaction),
+        )
+        .route_layer(limit(RouteGroup::PublicPackages));
+
+    let catalogs = Router::new()
+        .route(
+            "/api/public/catalogs/:token",
+            get(crate::catalogs::get_public_catalog),
+        )
+        .route_layer(limit(RouteGroup::PublicCatalogs));
+
+    let invites = Router::new()
+        .route(
+            "/api/invites/agency-talent/:token/magic-link",
+            get(crate::agency_talent_invites::get_magic_link_by_token),
+        )
+        .route_layer(limit(RouteGroup::TalentInvites));
+
+    packages.merge(catalogs).merge(invites)
+}
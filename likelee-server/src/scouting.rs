@@ -13,7 +13,9 @@ use tracing::{error, info, warn};
 use crate::auth::AuthUser;
 use crate::config::AppState;
 use crate::entitlements::{docuseal_template_limit, get_agency_plan_tier};
+use crate::repositories::fetch;
 use crate::services::docuseal::DocuSealClient;
+use crate::webhooks::docuseal::{offer_status, DocuSealWebhookEvent, Stage};
 
 async fn get_template_count(
     state: &AppState,
@@ -1425,131 +1427,71 @@ pub async fn get_offer_details(
 }
 
 // ============================================================================
-// Webhook Handler
+// DocuSeal Events
 // ============================================================================
 
-#[derive(Debug, Deserialize)]
-pub struct DocuSealWebhookEvent {
-    pub event_type: String,
-    pub timestamp: String,
-    pub data: serde_json::Value,
-}
+/// Applies a DocuSeal event to the scouting offer signed under
+/// `submission_id`. Returns `false` when no offer uses that submission.
+pub(crate) async fn apply_docuseal_event(
+    state: &AppState,
+    event: &DocuSealWebhookEvent,
+    stage: Stage,
+    submission_id: i64,
+) -> Result<bool, String> {
+    let offers: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from("scouting_offers")
+            .select("id,prospect_id")
+            .eq("docuseal_submission_id", submission_id.to_string())
+            .limit(1),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let Some(offer) = offers.first() else {
+        return Ok(false);
+    };
+    let offer_id = offer["id"].as_str().unwrap_or_default();
+    let prospect_id = offer["prospect_id"].as_str().unwrap_or_default();
 
-/// POST /webhooks/docuseal
-pub async fn handle_webhook(
-    State(state): State<AppState>,
-    Json(payload): Json<DocuSealWebhookEvent>,
-) -> Result<StatusCode, (StatusCode, String)> {
+    let new_status = offer_status(stage);
     info!(
-        event_type = %payload.event_type,
-        payload = ?payload,
-        "Received DocuSeal webhook"
+        submission_id,
+        offer_id, new_status, "Processing scouting offer update"
     );
 
-    // Map DocuSeal events to our statuses
-    // submission.started -> opened
-    // submission.completed -> completed/signed
-    // submission.declined -> declined
-
-    let status_update = match payload.event_type.as_str() {
-        "submission.started" | "submission.opened" | "submission.viewed" | "form.started"
-        | "form.viewed" => Some("opened"),
-        "submission.completed" | "form.completed" => Some("completed"),
-        "submission.declined" | "form.declined" => Some("declined"),
+    let mut update_json = json!({ "status": new_status });
+    if new_status == "completed" {
+        if let Some(url) = event.document_url() {
+            update_json["signed_document_url"] = json!(url);
+            update_json["signed_at"] = json!(chrono::Utc::now().to_rfc3339());
+        }
+    }
+    let _: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from("scouting_offers")
+            .eq("id", offer_id)
+            .update(update_json.to_string()),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let prospect_status = match new_status {
+        "completed" => Some("signed"),
+        "declined" => Some("declined"),
         _ => None,
     };
-
-    if status_update.is_none() {
-        // Ignore other events
-        return Ok(StatusCode::OK);
-    }
-
-    let new_status = status_update.unwrap();
-
-    let submission_id = payload.data["submission_id"]
-        .as_i64()
-        .or_else(|| payload.data["id"].as_i64())
-        .ok_or_else(|| {
-            error!("Missing submission id in webhook payload");
-            (StatusCode::BAD_REQUEST, "Missing submission id".to_string())
-        })?;
-
-    info!(submission_id, new_status, "Processing submission update");
-
-    let pg = Postgrest::new(format!("{}/rest/v1", state.supabase_url))
-        .insert_header("apikey", &state.supabase_service_key)
-        .insert_header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        );
-
-    // 1. Update scouting_offers status
-    // We need to find the offer associated with this submission_id
-    let offer_response = pg
-        .from("scouting_offers")
-        .select("id, prospect_id")
-        .eq("docuseal_submission_id", submission_id.to_string())
-        .single()
-        .execute()
-        .await;
-
-    if let Ok(resp) = offer_response {
-        if resp.status().is_success() {
-            let offer_body = resp.text().await.unwrap_or_default();
-            if let Ok(offer) = serde_json::from_str::<serde_json::Value>(&offer_body) {
-                if let Some(offer_id) = offer["id"].as_str() {
-                    let prospect_id = offer["prospect_id"].as_str().unwrap_or("");
-
-                    // Extract signed document URL
-                    let signed_document_url = if new_status == "completed" {
-                        payload.data["documents"]
-                            .as_array()
-                            .and_then(|docs| docs.first())
-                            .and_then(|doc| doc["url"].as_str())
-                            .map(|s| s.to_string())
-                    } else {
-                        None
-                    };
-
-                    let mut update_json = json!({ "status": new_status });
-                    if let Some(url) = signed_document_url {
-                        update_json["signed_document_url"] = json!(url);
-                        if new_status == "completed" {
-                            update_json["signed_at"] = json!(chrono::Utc::now().to_rfc3339());
-                        }
-                    }
-
-                    let _ = pg
-                        .from("scouting_offers")
-                        .update(update_json.to_string())
-                        .eq("id", offer_id)
-                        .execute()
-                        .await;
-
-                    // Update prospect status
-                    if !prospect_id.is_empty() {
-                        let p_status = if new_status == "completed" {
-                            "signed"
-                        } else if new_status == "declined" {
-                            "declined"
-                        } else {
-                            ""
-                        };
-                        if !p_status.is_empty() {
-                            let _ = pg
-                                .from("scouting_prospects")
-                                .update(json!({ "status": p_status }).to_string())
-                                .eq("id", prospect_id)
-                                .execute()
-                                .await;
-                        }
-                    }
-                    return Ok(StatusCode::OK);
-                }
-            }
-        }
+    if let Some(p_status) = prospect_status.filter(|_| !prospect_id.is_empty()) {
+        let _: Vec<serde_json::Value> = fetch(
+            state
+                .pg
+                .from("scouting_prospects")
+                .eq("id", prospect_id)
+                .update(json!({ "status": p_status }).to_string()),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
     }
-
-    info!("No offer found for submission_id: {}", submission_id);
-    Ok(StatusCode::OK)
+    Ok(true)
 }
@@ -0,0 +1,347 @@
+use super::{normalize_path, sniff_content_type, StorageBackend, StorageError, StorageObject};
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{Path, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const ROUTE_PREFIX: &str = "/storage/local";
+
+/// Filesystem-backed storage for development and tests.
+///
+/// Objects live at `{root}/{bucket}/{path}` and are served by this server under
+/// `/storage/local/...`: public buckets without a token, everything else only
+/// through URLs signed with the configured secret.
+#[derive(Clone)]
+pub struct LocalStorage {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    root: PathBuf,
+    base_url: String,
+    secret: Vec<u8>,
+    public_buckets: Vec<String>,
+}
+
+impl LocalStorage {
+    pub fn new(
+        root: impl Into<PathBuf>,
+        base_url: &str,
+        secret: &str,
+        public_buckets: Vec<String>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                root: root.into(),
+                base_url: base_url.trim_end_matches('/').to_string(),
+                secret: secret.as_bytes().to_vec(),
+                public_buckets,
+            }),
+        }
+    }
+
+    fn bucket_dir(&self, bucket: &str) -> Result<PathBuf, StorageError> {
+        let bucket = normalize_path(bucket)?;
+        if bucket.contains('/') {
+            return Err(StorageError::InvalidPath(bucket));
+        }
+        Ok(self.inner.root.join(bucket))
+    }
+
+    fn file_path(&self, bucket: &str, path: &str) -> Result<PathBuf, StorageError> {
+        Ok(self.bucket_dir(bucket)?.join(normalize_path(path)?))
+    }
+
+    fn mac(&self, bucket: &str, path: &str, expires: i64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.inner.secret).expect("hmac accepts any key");
+        mac.update(format!("{bucket}/{path}:{expires}").as_bytes());
+        mac
+    }
+
+    fn signature(&self, bucket: &str, path: &str, expires: i64) -> String {
+        hex::encode(self.mac(bucket, path, expires).finalize().into_bytes())
+    }
+
+    fn verify(&self, bucket: &str, path: &str, q: &SignedQuery) -> bool {
+        if self.inner.public_buckets.iter().any(|b| b == bucket) {
+            return true;
+        }
+        let (Some(expires), Some(token)) = (q.expires, q.token.as_deref()) else {
+            return false;
+        };
+        if expires < chrono::Utc::now().timestamp() {
+            return false;
+        }
+        hex::decode(token)
+            .map(|sig| self.mac(bucket, path, expires).verify_slice(&sig).is_ok())
+            .unwrap_or(false)
+    }
+
+    fn url(&self, bucket: &str, path: &str) -> String {
+        format!("{}{ROUTE_PREFIX}/{bucket}/{path}", self.inner.base_url)
+    }
+}
+
+fn io_error(err: std::io::Error) -> StorageError {
+    if err.kind() == std::io::ErrorKind::NotFound {
+        StorageError::NotFound
+    } else {
+        StorageError::Io(err.to_string())
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    async fn put(
+        &self,
+        bucket: &str,
+        path: &str,
+        bytes: Bytes,
+        _content_type: &str,
+        upsert: bool,
+    ) -> Result<(), StorageError> {
+        let file = self.file_path(bucket, path)?;
+        if !upsert && tokio::fs::try_exists(&file).await.unwrap_or(false) {
+            return Err(StorageError::Backend {
+                status: 409,
+                message: "Duplicate".to_string(),
+            });
+        }
+        if let Some(dir) = file.parent() {
+            tokio::fs::create_dir_all(dir).await.map_err(io_error)?;
+        }
+        tokio::fs::write(&file, &bytes).await.map_err(io_error)
+    }
+
+    async fn get(&self, bucket: &str, path: &str) -> Result<Bytes, StorageError> {
+        let file = self.file_path(bucket, path)?;
+        tokio::fs::read(&file)
+            .await
+            .map(Bytes::from)
+            .map_err(io_error)
+    }
+
+    async fn delete(&self, bucket: &str, path: &str) -> Result<(), StorageError> {
+        let file = self.file_path(bucket, path)?;
+        tokio::fs::remove_file(&file).await.map_err(io_error)
+    }
+
+    async fn signed_url(
+        &self,
+        bucket: &str,
+        path: &str,
+        expires_in_secs: u64,
+    ) -> Result<String, StorageError> {
+        let file = self.file_path(bucket, path)?;
+        if !tokio::fs::try_exists(&file).await.unwrap_or(false) {
+            return Err(StorageError::NotFound);
+        }
+        let path = normalize_path(path)?;
+        let expires = chrono::Utc::now().timestamp() + expires_in_secs as i64;
+        Ok(format!(
+            "{}?expires={expires}&token={}",
+            self.url(bucket, &path),
+            self.signature(bucket, &path, expires)
+        ))
+    }
+
+    async fn list(&self, bucket: &str, prefix: &str) -> Result<Vec<StorageObject>, StorageError> {
+        let dir = if prefix.trim_matches('/').is_empty() {
+            self.bucket_dir(bucket)?
+        } else {
+            self.file_path(bucket, prefix)?
+        };
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(e) => e,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(io_error(e)),
+        };
+        let mut out = vec![];
+        while let Some(entry) = entries.next_entry().await.map_err(io_error)? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let meta = entry.metadata().await.map_err(io_error)?;
+            out.push(StorageObject {
+                content_type: meta
+                    .is_file()
+                    .then(|| sniff_content_type(&[], None, Some(&name))),
+                size: meta.is_file().then_some(meta.len() as i64),
+                name,
+            });
+        }
+        out.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(out)
+    }
+
+    fn public_url(&self, bucket: &str, path: &str) -> String {
+        self.url(bucket, path)
+    }
+
+    fn routes(&self) -> Option<Router<crate::config::AppState>> {
+        let this = self.clone();
+        Some(Router::new().route(
+            &format!("{ROUTE_PREFIX}/:bucket/*path"),
+            get(move |params, query| serve(this.clone(), params, query)),
+        ))
+    }
+}
+
+#[derive(Deserialize)]
+struct SignedQuery {
+    expires: Option<i64>,
+    token: Option<String>,
+}
+
+async fn serve(
+    storage: LocalStorage,
+    Path((bucket, path)): Path<(String, String)>,
+    Query(q): Query<SignedQuery>,
+) -> Response {
+    let Ok(path) = normalize_path(&path) else {
+        return (StatusCode::BAD_REQUEST, "invalid_path").into_response();
+    };
+    if !storage.verify(&bucket, &path, &q) {
+        return (StatusCode::FORBIDDEN, "invalid_signature").into_response();
+    }
+    match storage.get(&bucket, &path).await {
+        Ok(bytes) => {
+            let ct = sniff_content_type(&bytes, None, Some(&path));
+            ([(header::CONTENT_TYPE, ct)], bytes).into_response()
+        }
+        Err(StorageError::NotFound) => (StatusCode::NOT_FOUND, "not_found").into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "local storage read failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage() -> (LocalStorage, PathBuf) {
+        let root = std::env::temp_dir().join(format!("likelee-storage-{}", uuid::Uuid::new_v4()));
+        (
+            LocalStorage::new(
+                &root,
+                "http://localhost:8787",
+                "secret",
+                vec!["pub".to_string()],
+            ),
+            root,
+        )
+    }
+
+    #[tokio::test]
+    async fn round_trips_objects_and_lists_one_level() {
+        let (s, root) = storage();
+        s.put(
+            "priv",
+            "a/b/one.pdf",
+            Bytes::from_static(b"%PDF-1"),
+            "application/pdf",
+            false,
+        )
+        .await
+        .unwrap();
+        s.put(
+            "priv",
+            "a/two.txt",
+            Bytes::from_static(b"hi"),
+            "text/plain",
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(s.get("priv", "a/b/one.pdf").await.unwrap(), "%PDF-1");
+
+        let dup = s
+            .put(
+                "priv",
+                "a/two.txt",
+                Bytes::from_static(b"x"),
+                "text/plain",
+                false,
+            )
+            .await;
+        assert!(matches!(
+            dup,
+            Err(StorageError::Backend { status: 409, .. })
+        ));
+
+        let names: Vec<String> = s
+            .list("priv", "a")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|o| o.name)
+            .collect();
+        assert_eq!(names, vec!["b", "two.txt"]);
+
+        s.delete("priv", "a/two.txt").await.unwrap();
+        assert!(matches!(
+            s.get("priv", "a/two.txt").await,
+            Err(StorageError::NotFound)
+        ));
+        assert!(matches!(
+            s.get("priv", "../outside").await,
+            Err(StorageError::InvalidPath(_))
+        ));
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn signed_urls_verify_until_expiry() {
+        let (s, root) = storage();
+        s.put(
+            "priv",
+            "doc.pdf",
+            Bytes::from_static(b"x"),
+            "application/pdf",
+            true,
+        )
+        .await
+        .unwrap();
+        let url = s.signed_url("priv", "doc.pdf", 60).await.unwrap();
+        let query: Vec<(String, String)> = url
+            .split_once('?')
+            .unwrap()
+            .1
+            .split('&')
+            .filter_map(|kv| kv.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let get = |k: &str| query.iter().find(|(qk, _)| qk == k).map(|(_, v)| v.clone());
+        let q = SignedQuery {
+            expires: get("expires").and_then(|e| e.parse().ok()),
+            token: get("token"),
+        };
+        assert!(s.verify("priv", "doc.pdf", &q));
+        assert!(!s.verify("priv", "other.pdf", &q));
+        let expired = SignedQuery {
+            expires: Some(1),
+            token: Some(s.signature("priv", "doc.pdf", 1)),
+        };
+        assert!(!s.verify("priv", "doc.pdf", &expired));
+        assert!(s.verify(
+            "pub",
+            "x.png",
+            &SignedQuery {
+                expires: None,
+                token: None
+            }
+        ));
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,314 @@
+//! Object storage behind a single trait.
+//!
+//! Handlers never build Storage URLs themselves: they pick a [`Bucket`],
+//! build a path from sanitised segments and call the [`StorageBackend`] held
+//! in `AppState::storage`. Production uses Supabase Storage; development and
+//! tests can use the local filesystem (`STORAGE_BACKEND=local`).
+
+pub mod local;
+pub mod supabase;
+
+use crate::errors::AppError;
+use axum::async_trait;
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use serde::Serialize;
+
+pub use local::LocalStorage;
+pub use supabase::SupabaseStorage;
+
+/// Logical bucket; resolved to a concrete name with `AppState::bucket`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bucket {
+    /// Objects served without authentication (profile photos, portfolio).
+    Public,
+    /// Objects only reachable through signed URLs (contracts, recordings).
+    Private,
+    /// Short-lived scratch objects.
+    Temp,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageObject {
+    /// Name relative to the listed prefix.
+    pub name: String,
+    pub size: Option<i64>,
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    NotFound,
+    /// The path contained traversal, empty or control characters.
+    InvalidPath(String),
+    /// The backend answered with a non-success status.
+    Backend {
+        status: u16,
+        message: String,
+    },
+    /// The backend could not be reached or the local filesystem failed.
+    Io(String),
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::NotFound => write!(f, "not_found"),
+            StorageError::InvalidPath(p) => write!(f, "invalid_path: {p}"),
+            StorageError::Backend { status, message } => {
+                write!(f, "storage_error status={status} message={message}")
+            }
+            StorageError::Io(e) => write!(f, "storage_io_error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<StorageError> for AppError {
+    fn from(err: StorageError) -> Self {
+        match err {
+            StorageError::NotFound => AppError::NotFound("file not found".to_string()),
+            StorageError::InvalidPath(_) => AppError::BadRequest("invalid_path".to_string()),
+            StorageError::Backend { status: 409, .. } => {
+                AppError::Conflict("file already exists".to_string())
+            }
+            other => AppError::upstream("storage", other),
+        }
+    }
+}
+
+impl From<StorageError> for (StatusCode, String) {
+    fn from(err: StorageError) -> Self {
+        AppError::from(err).into()
+    }
+}
+
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Stores `bytes` at `bucket/path`. Fails with a 409 backend error when the
+    /// object exists and `upsert` is false.
+    async fn put(
+        &self,
+        bucket: &str,
+        path: &str,
+        bytes: Bytes,
+        content_type: &str,
+        upsert: bool,
+    ) -> Result<(), StorageError>;
+
+    async fn get(&self, bucket: &str, path: &str) -> Result<Bytes, StorageError>;
+
+    async fn delete(&self, bucket: &str, path: &str) -> Result<(), StorageError>;
+
+    /// Absolute URL granting read access for `expires_in_secs`.
+    async fn signed_url(
+        &self,
+        bucket: &str,
+        path: &str,
+        expires_in_secs: u64,
+    ) -> Result<String, StorageError>;
+
+    /// Objects directly under `prefix` (one level, like Supabase's list API).
+    async fn list(&self, bucket: &str, prefix: &str) -> Result<Vec<StorageObject>, StorageError>;
+
+    /// Unauthenticated URL for an object in a public bucket.
+    fn public_url(&self, bucket: &str, path: &str) -> String;
+
+    /// Extra routes the backend needs mounted (the local backend serves its own files).
+    fn routes(&self) -> Option<axum::Router<crate::config::AppState>> {
+        None
+    }
+}
+
+/// Validates an object path: relative, no `.`/`..` or empty segments, no
+/// backslashes or control characters. Returns it with duplicate slashes removed.
+pub fn normalize_path(path: &str) -> Result<String, StorageError> {
+    let invalid = || StorageError::InvalidPath(path.to_string());
+    if path.contains('\\') || path.chars().any(|c| c.is_control()) {
+        return Err(invalid());
+    }
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if segments.is_empty() || segments.iter().any(|s| *s == "." || *s == "..") {
+        return Err(invalid());
+    }
+    Ok(segments.join("/"))
+}
+
+/// Makes a single path segment (user id, folder id) safe to embed in a path.
+pub fn sanitize_segment(segment: &str) -> String {
+    segment.replace(
+        |c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '-',
+        "_",
+    )
+}
+
+/// Makes an uploaded file name safe to embed in a path, keeping its extension.
+pub fn sanitize_file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let cleaned: String = base
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let cleaned = cleaned.trim_start_matches('.');
+    if cleaned.is_empty() {
+        return "upload.bin".to_string();
+    }
+    // Keep the tail so the extension survives truncation.
+    let skip = cleaned.len().saturating_sub(128);
+    cleaned[skip..].to_string()
+}
+
+/// Content type for an upload: the declared type when it is specific,
+/// otherwise sniffed from the leading bytes, then guessed from the file name.
+pub fn sniff_content_type(bytes: &[u8], declared: Option<&str>, file_name: Option<&str>) -> String {
+    if let Some(ct) = declared
+        .map(|c| c.trim().to_ascii_lowercase())
+        .filter(|c| !c.is_empty() && c != "application/octet-stream")
+    {
+        return ct;
+    }
+    magic_content_type(bytes)
+        .or_else(|| file_name.and_then(content_type_for_name))
+        .unwrap_or("application/octet-stream")
+        .to_string()
+}
+
+fn magic_content_type(b: &[u8]) -> Option<&'static str> {
+    let starts = |sig: &[u8]| b.starts_with(sig);
+    if starts(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if starts(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if starts(b"GIF87a") || starts(b"GIF89a") {
+        Some("image/gif")
+    } else if starts(b"RIFF") && b.get(8..12) == Some(b"WEBP") {
+        Some("image/webp")
+    } else if starts(b"RIFF") && b.get(8..12) == Some(b"WAVE") {
+        Some("audio/wav")
+    } else if starts(b"%PDF-") {
+        Some("application/pdf")
+    } else if starts(b"OggS") {
+        Some("audio/ogg")
+    } else if starts(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        Some("audio/webm")
+    } else if starts(b"ID3") || starts(&[0xFF, 0xFB]) || starts(&[0xFF, 0xF3]) {
+        Some("audio/mpeg")
+    } else if b.get(4..8) == Some(b"ftyp") {
+        match b.get(8..11) {
+            Some(b"M4A") => Some("audio/mp4"),
+            Some(b"qt ") => Some("video/quicktime"),
+            _ => Some("video/mp4"),
+        }
+    } else if starts(b"PK\x03\x04") {
+        Some("application/zip")
+    } else {
+        None
+    }
+}
+
+fn content_type_for_name(name: &str) -> Option<&'static str> {
+    let ext = name.rsplit_once('.')?.1.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "csv" => "text/csv",
+        "txt" => "text/plain",
+        "json" => "application/json",
+        "zip" => "application/zip",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "m4a" => "audio/mp4",
+        "webm" => "audio/webm",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        _ => return None,
+    })
+}
+
+/// File extension to use when storing an object of `content_type`.
+pub fn extension_for(content_type: &str) -> &'static str {
+    let ct = content_type.to_ascii_lowercase();
+    let ct = ct.split(';').next().unwrap_or("").trim();
+    match ct {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "application/pdf" => "pdf",
+        "audio/wav" | "audio/x-wav" | "audio/wave" => "wav",
+        "audio/ogg" => "ogg",
+        "audio/mp4" | "audio/m4a" | "audio/x-m4a" | "video/mp4" => "mp4",
+        "audio/webm" | "video/webm" => "webm",
+        "audio/mpeg" => "mp3",
+        "text/csv" => "csv",
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_with_traversal_are_rejected() {
+        assert!(normalize_path("../etc/passwd").is_err());
+        assert!(normalize_path("agencies/a/../../b").is_err());
+        assert!(normalize_path("a\\b").is_err());
+        assert!(normalize_path("a/\nb").is_err());
+        assert!(normalize_path("//").is_err());
+        assert_eq!(normalize_path("/a//b/c.png").unwrap(), "a/b/c.png");
+    }
+
+    #[test]
+    fn file_names_are_made_safe() {
+        assert_eq!(
+            sanitize_file_name("my contract (v2).pdf"),
+            "my_contract__v2_.pdf"
+        );
+        assert_eq!(sanitize_file_name("../../secret.txt"), "secret.txt");
+        assert_eq!(sanitize_file_name(".."), "upload.bin");
+        assert!(sanitize_file_name(&format!("{}.pdf", "a".repeat(300))).ends_with(".pdf"));
+        assert_eq!(sanitize_segment("user@id/1"), "user_id_1");
+    }
+
+    #[test]
+    fn content_type_prefers_declared_then_magic_then_name() {
+        let png = b"\x89PNG\r\n\x1a\n....";
+        assert_eq!(
+            sniff_content_type(png, Some("image/webp"), None),
+            "image/webp"
+        );
+        assert_eq!(
+            sniff_content_type(png, Some("application/octet-stream"), None),
+            "image/png"
+        );
+        assert_eq!(
+            sniff_content_type(b"%PDF-1.7", None, None),
+            "application/pdf"
+        );
+        assert_eq!(
+            sniff_content_type(b"a,b\n1,2", None, Some("export.CSV")),
+            "text/csv"
+        );
+        assert_eq!(
+            sniff_content_type(b"??", None, None),
+            "application/octet-stream"
+        );
+        assert_eq!(extension_for("audio/webm;codecs=opus"), "webm");
+    }
+}
@@ -0,0 +1,207 @@
+use super::{normalize_path, StorageBackend, StorageError, StorageObject};
+use axum::async_trait;
+use axum::body::Bytes;
+use serde_json::json;
+use std::time::Duration;
+
+/// Supabase Storage accessed with the service-role key.
+pub struct SupabaseStorage {
+    http: reqwest::Client,
+    supabase_url: String,
+    service_key: String,
+}
+
+impl SupabaseStorage {
+    pub fn new(supabase_url: &str, service_key: &str) -> Self {
+        let http = reqwest::Client::builder()
+            .http1_only()
+            .tcp_keepalive(Duration::from_secs(30))
+            .pool_idle_timeout(Duration::from_secs(30))
+            .timeout(Duration::from_secs(60))
+            .build()
+            .unwrap_or_default();
+        Self {
+            http,
+            supabase_url: supabase_url.trim_end_matches('/').to_string(),
+            service_key: service_key.to_string(),
+        }
+    }
+
+    fn object_url(&self, kind: &str, bucket: &str, path: &str) -> Result<String, StorageError> {
+        let path = encode_path(&normalize_path(path)?);
+        let bucket = urlencoding::encode(bucket);
+        Ok(format!(
+            "{}/storage/v1/object/{kind}{bucket}/{path}",
+            self.supabase_url
+        ))
+    }
+
+    fn authed(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        req.header("Authorization", format!("Bearer {}", self.service_key))
+            .header("apikey", self.service_key.clone())
+    }
+
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<reqwest::Response, StorageError> {
+        let resp = self
+            .authed(req)
+            .send()
+            .await
+            .map_err(|e| StorageError::Io(e.to_string()))?;
+        if resp.status().is_success() {
+            return Ok(resp);
+        }
+        let status = resp.status().as_u16();
+        let message = resp.text().await.unwrap_or_default();
+        if is_not_found(status, &message) {
+            return Err(StorageError::NotFound);
+        }
+        Err(StorageError::Backend { status, message })
+    }
+}
+
+/// Supabase reports missing objects either as a 404 or as a 400 whose body
+/// carries `"statusCode":"404"` / "Object not found".
+fn is_not_found(status: u16, body: &str) -> bool {
+    if status == 404 {
+        return true;
+    }
+    let compact: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.contains("Objectnotfound")
+        || compact.contains("\"error\":\"not_found\"")
+        || compact.contains("\"statusCode\":404")
+        || compact.contains("\"statusCode\":\"404\"")
+}
+
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|s| urlencoding::encode(s).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[async_trait]
+impl StorageBackend for SupabaseStorage {
+    async fn put(
+        &self,
+        bucket: &str,
+        path: &str,
+        bytes: Bytes,
+        content_type: &str,
+        upsert: bool,
+    ) -> Result<(), StorageError> {
+        let url = self.object_url("", bucket, path)?;
+        let req = self
+            .http
+            .post(&url)
+            .header("content-type", content_type)
+            .header("x-upsert", if upsert { "true" } else { "false" })
+            .body(bytes);
+        self.send(req).await.map(|_| ())
+    }
+
+    async fn get(&self, bucket: &str, path: &str) -> Result<Bytes, StorageError> {
+        let url = self.object_url("", bucket, path)?;
+        self.send(self.http.get(&url))
+            .await?
+            .bytes()
+            .await
+            .map_err(|e| StorageError::Io(e.to_string()))
+    }
+
+    async fn delete(&self, bucket: &str, path: &str) -> Result<(), StorageError> {
+        let url = self.object_url("", bucket, path)?;
+        self.send(self.http.delete(&url)).await.map(|_| ())
+    }
+
+    async fn signed_url(
+        &self,
+        bucket: &str,
+        path: &str,
+        expires_in_secs: u64,
+    ) -> Result<String, StorageError> {
+        let url = self.object_url("sign/", bucket, path)?;
+        let resp = self
+            .send(
+                self.http
+                    .post(&url)
+                    .json(&json!({ "expiresIn": expires_in_secs })),
+            )
+            .await?;
+        let body: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| StorageError::Io(e.to_string()))?;
+        let signed_path = body
+            .get("signedURL")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| StorageError::Backend {
+                status: 200,
+                message: "invalid sign response".to_string(),
+            })?;
+        Ok(format!("{}/storage/v1{}", self.supabase_url, signed_path))
+    }
+
+    async fn list(&self, bucket: &str, prefix: &str) -> Result<Vec<StorageObject>, StorageError> {
+        let prefix = if prefix.trim_matches('/').is_empty() {
+            String::new()
+        } else {
+            normalize_path(prefix)?
+        };
+        let url = format!(
+            "{}/storage/v1/object/list/{}",
+            self.supabase_url,
+            urlencoding::encode(bucket)
+        );
+        let resp = self
+            .send(self.http.post(&url).json(&json!({
+                "prefix": prefix,
+                "limit": 1000,
+                "offset": 0,
+                "sortBy": { "column": "name", "order": "asc" },
+            })))
+            .await?;
+        let rows: Vec<serde_json::Value> = resp
+            .json()
+            .await
+            .map_err(|e| StorageError::Io(e.to_string()))?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|r| {
+                let meta = r.get("metadata");
+                Some(StorageObject {
+                    name: r.get("name")?.as_str()?.to_string(),
+                    size: meta.and_then(|m| m.get("size")).and_then(|v| v.as_i64()),
+                    content_type: meta
+                        .and_then(|m| m.get("mimetype"))
+                        .and_then(|v| v.as_str())
+                        .map(String::from),
+                })
+            })
+            .collect())
+    }
+
+    fn public_url(&self, bucket: &str, path: &str) -> String {
+        format!(
+            "{}/storage/v1/object/public/{}/{}",
+            self.supabase_url, bucket, path
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_objects_are_detected_in_both_shapes() {
+        assert!(is_not_found(404, ""));
+        assert!(is_not_found(
+            400,
+            r#"{"statusCode": "404", "error": "not_found", "message": "Object not found"}"#
+        ));
+        assert!(!is_not_found(
+            400,
+            r#"{"statusCode":"400","error":"invalid"}"#
+        ));
+    }
+}
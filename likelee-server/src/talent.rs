@@ -575,19 +575,10 @@ pub async fn upload_portfolio_item(
     };
 
     let fname = file_name.unwrap_or_else(|| "upload.bin".to_string());
-    let sanitized = fname
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>();
+    let sanitized = crate::storage::sanitize_file_name(&fname);
 
     // Use PUBLIC bucket so the UI can render without signed URLs.
-    let bucket = state.supabase_bucket_public.clone();
+    let bucket = state.bucket(crate::storage::Bucket::Public);
     let path = format!(
         "talent/{}/portfolio/{}_{}",
         resolved.talent_id,
@@ -595,38 +586,13 @@ pub async fn upload_portfolio_item(
         sanitized
     );
 
-    let storage_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::new();
-    let mut req = http
-        .post(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone());
-    if let Some(ct) = mime_type.as_ref().filter(|s| !s.is_empty()) {
-        req = req.header("content-type", ct.clone());
-    }
-    let up = req
-        .body(bytes.clone())
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    if !up.status().is_success() {
-        let msg = up.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("storage upload failed: {msg}"),
-        ));
-    }
+    let mime_type = crate::storage::sniff_content_type(&bytes, mime_type.as_deref(), Some(&fname));
+    state
+        .storage
+        .put(&bucket, &path, bytes.clone().into(), &mime_type, false)
+        .await?;
 
-    let public_url = format!(
-        "{}/storage/v1/object/public/{}/{}",
-        state.supabase_url, bucket, path
-    );
+    let public_url = state.storage.public_url(&bucket, &path);
 
     let body = json!({
         "agency_id": resolved.agency_id,
@@ -674,7 +640,7 @@ pub async fn get_latest_tax_document(
     let mut req = state
         .pg
         .from("talent_tax_documents")
-        .select("id,talent_id,doc_type,tax_year,public_url,created_at")
+        .select("id,talent_id,doc_type,tax_year,storage_bucket,storage_path,public_url,created_at")
         .eq("talent_id", &resolved.talent_id);
     if let Some(dt) = q.doc_type {
         if !dt.trim().is_empty() {
@@ -697,7 +663,18 @@ pub async fn get_latest_tax_document(
     }
     let txt = resp.text().await.unwrap_or("[]".into());
     let rows: serde_json::Value = serde_json::from_str(&txt).unwrap_or(json!([]));
-    if let Some(first) = rows.as_array().and_then(|a| a.first()).cloned() {
+    if let Some(mut first) = rows.as_array().and_then(|a| a.first()).cloned() {
+        // Generated documents live in the private bucket; hand out a short-lived link.
+        let stored = (
+            first.get("storage_bucket").and_then(|v| v.as_str()),
+            first.get("storage_path").and_then(|v| v.as_str()),
+        );
+        if first.get("public_url").is_none_or(|v| v.is_null()) {
+            if let (Some(bucket), Some(path)) = stored {
+                let url = state.storage.signed_url(bucket, path, 3600).await?;
+                first["public_url"] = json!(url);
+            }
+        }
         return Ok(Json(first));
     }
     Ok(Json(json!({})))
@@ -2659,13 +2636,12 @@ pub async fn create_book_out(
                 }
                 let body = lines.join("\n");
 
-                let send_res = crate::email::send_plain_text_email(
-                    &state,
-                    agency_email,
-                    &subject,
-                    &body,
-                    Some(&talent_name),
-                );
+                let outgoing = crate::email::OutgoingEmail::new(agency_email, &subject, &body)
+                    .from_name(Some(&talent_name))
+                    .category("book_out")
+                    .agency(&resolved.agency_id)
+                    .talent(Some(&resolved.talent_id));
+                let send_res = crate::email::enqueue(&state, outgoing).await;
 
                 // In-app agency notification log (best-effort)
                 let book_out_id = v
@@ -2675,7 +2651,7 @@ pub async fn create_book_out(
                     .and_then(|x| x.as_str())
                     .map(|s| s.to_string());
                 let meta = json!({
-                    "smtp_status": if send_res.is_ok() {"ok"} else {"error"},
+                    "smtp_status": if send_res.is_ok() {"queued"} else {"error"},
                     "talent_id": resolved.talent_id,
                     "talent_name": talent_name,
                     "start_date": start,
@@ -0,0 +1,958 @@
+//! Talent statement exports and delivery.
+//!
+//! A period statement lists one talent's lines on the agency's issued
+//! invoices dated in the period, with gross, agency fee, net and how much of
+//! the net has been paid. Statements download as PDF (one talent) or CSV
+//! (one or all talent), and can be emailed to each talent with the PDF
+//! attached: on demand, or on the first of every month for the previous
+//! month when the agency has turned that on.
+//!
+//! Every email is recorded in `talent_statement_deliveries` with the hash of
+//! the statement and a version number per talent and period. A statement
+//! whose figures have not changed since it was sent is not sent again; one
+//! that changed goes out as the next version. The PDF of every version is
+//! kept in the private bucket.
+
+use crate::{
+    accounting_export::{amount, csv_row},
+    audit::{self, AuditEvent},
+    auth::AuthUser,
+    config::AppState,
+    email,
+    errors::{AppError, AppResult},
+    invoice_pdf::{self, Branding, Column, Cursor, MARGIN, PAGE_W},
+    invoices::load_branding,
+    jobs::runner::ts,
+    repositories::{fetch, InvoiceStatus, RepoError},
+    storage::{sanitize_file_name, sanitize_segment, Bucket},
+    talent_statements::{self, TalentStatementLine, TalentStatementsQuery},
+    tax_documents::{self, Recipient},
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{Datelike, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use tracing::warn;
+
+pub const DELIVERIES_TABLE: &str = "talent_statement_deliveries";
+pub const SETTINGS_TABLE: &str = "agency_talent_statement_settings";
+const PDF_CONTENT_TYPE: &str = "application/pdf";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Pdf,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "pdf" => Some(ExportFormat::Pdf),
+            "csv" => Some(ExportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// One currency's figures on a statement.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StatementTotals {
+    pub currency: String,
+    pub gross_cents: i64,
+    pub agency_fee_cents: i64,
+    pub net_cents: i64,
+    pub paid_cents: i64,
+    pub outstanding_cents: i64,
+}
+
+/// A talent's statement for a period. Its hash identifies the version sent.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeriodStatement {
+    pub talent_id: String,
+    pub talent_name: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub lines: Vec<TalentStatementLine>,
+    pub totals: Vec<StatementTotals>,
+}
+
+impl PeriodStatement {
+    pub fn sha256(&self) -> String {
+        let bytes = serde_json::to_vec(self).unwrap_or_default();
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    pub fn file_name(&self, extension: &str) -> String {
+        sanitize_file_name(&format!(
+            "statement-{}-{}-{}.{extension}",
+            self.talent_name, self.from, self.to
+        ))
+    }
+}
+
+/// The previous calendar month, the period monthly statements cover.
+pub fn previous_month(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let first = today.with_day(1).unwrap_or(today);
+    let to = first - Days::new(1);
+    (to.with_day(1).unwrap_or(to), to)
+}
+
+/// `from`/`to` query values, defaulting to the previous month.
+fn parse_period(from: Option<&str>, to: Option<&str>) -> AppResult<(NaiveDate, NaiveDate)> {
+    let invalid = || AppError::BadRequest("invalid_date_range".to_string());
+    let parse = |value: Option<&str>| {
+        value
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| invalid()))
+            .transpose()
+    };
+    let (default_from, default_to) = previous_month(Utc::now().date_naive());
+    let from = parse(from)?.unwrap_or(default_from);
+    let to = parse(to)?.unwrap_or(default_to);
+    if to < from {
+        return Err(invalid());
+    }
+    Ok((from, to))
+}
+
+/// Groups the lines of issued invoices dated in the period by talent, sorted
+/// by talent name. Draft and void invoices are left out.
+pub fn period_statements(
+    lines: Vec<TalentStatementLine>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Vec<PeriodStatement> {
+    let mut by_talent: BTreeMap<String, Vec<TalentStatementLine>> = BTreeMap::new();
+    for line in lines {
+        let dated = line
+            .invoice_date
+            .as_deref()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .is_some_and(|d| from <= d && d <= to);
+        let issued = line.status != InvoiceStatus::Draft.as_str()
+            && line.status != InvoiceStatus::Void.as_str();
+        if dated && issued {
+            by_talent
+                .entry(line.talent_id.clone())
+                .or_default()
+                .push(line);
+        }
+    }
+    let mut statements: Vec<PeriodStatement> = by_talent
+        .into_iter()
+        .map(|(talent_id, mut lines)| {
+            lines.sort_by(|a, b| {
+                (&a.invoice_date, &a.invoice_number).cmp(&(&b.invoice_date, &b.invoice_number))
+            });
+            let mut totals: BTreeMap<String, StatementTotals> = BTreeMap::new();
+            for line in &lines {
+                let t = totals
+                    .entry(line.currency.clone())
+                    .or_insert_with(|| StatementTotals {
+                        currency: line.currency.clone(),
+                        ..Default::default()
+                    });
+                t.gross_cents += line.gross_cents;
+                t.agency_fee_cents += line.agency_fee_cents;
+                t.net_cents += line.net_cents;
+                t.paid_cents += line.paid_net_cents;
+                t.outstanding_cents += line.net_cents - line.paid_net_cents;
+            }
+            PeriodStatement {
+                talent_id,
+                talent_name: lines[0].talent_name.clone(),
+                from,
+                to,
+                lines,
+                totals: totals.into_values().collect(),
+            }
+        })
+        .collect();
+    statements.sort_by(|a, b| a.talent_name.cmp(&b.talent_name));
+    statements
+}
+
+/// `partially_paid` -> `Partially paid`.
+fn status_label(status: &str) -> String {
+    let mut label = status.replace('_', " ");
+    if let Some(first) = label.get_mut(0..1) {
+        first.make_ascii_uppercase();
+    }
+    label
+}
+
+/// One row per line: talent, invoice, gross, fee, net, paid and status.
+pub fn render_csv(statements: &[PeriodStatement]) -> String {
+    let mut out = csv_row(&[
+        "talent",
+        "invoice_number",
+        "invoice_date",
+        "client",
+        "description",
+        "currency",
+        "gross",
+        "agency_fee",
+        "net",
+        "paid",
+        "status",
+    ]);
+    for statement in statements {
+        for line in &statement.lines {
+            out.push_str(&csv_row(&[
+                &statement.talent_name,
+                &line.invoice_number,
+                line.invoice_date.as_deref().unwrap_or(""),
+                &line.client_name,
+                &line.description,
+                &line.currency,
+                &amount(line.gross_cents),
+                &amount(line.agency_fee_cents),
+                &amount(line.net_cents),
+                &amount(line.paid_net_cents),
+                &line.status,
+            ]));
+        }
+    }
+    out
+}
+
+/// Renders `statement` into PDF bytes under the agency's name.
+pub fn render_pdf(statement: &PeriodStatement, branding: &Branding) -> Result<Vec<u8>, String> {
+    let mut cursor = Cursor::new(&format!(
+        "Statement {} {}",
+        statement.talent_name, statement.from
+    ))?;
+    let right = PAGE_W - MARGIN;
+
+    let top = cursor.y;
+    cursor.text(&branding.agency_name, 14.0, MARGIN, top - 6.0, true);
+    if let Some(email) = branding.agency_email.as_deref() {
+        cursor.text(email, 9.0, MARGIN, top - 11.0, false);
+    }
+    cursor.text_right("STATEMENT", 22.0, right, top - 8.0, true);
+    cursor.text_right(
+        &format!(
+            "{} to {}",
+            statement.from.format("%b %-d, %Y"),
+            statement.to.format("%b %-d, %Y")
+        ),
+        9.5,
+        right,
+        top - 15.0,
+        false,
+    );
+    cursor.y = top - 24.0;
+    cursor.rule(cursor.y + 3.0, 0.7);
+
+    cursor.y -= 3.0;
+    cursor.text("TALENT", 8.5, MARGIN, cursor.y, true);
+    cursor.y -= 5.5;
+    cursor.text(&statement.talent_name, 11.0, MARGIN, cursor.y, true);
+    cursor.y -= 10.0;
+
+    let columns = [
+        Column {
+            title: "Invoice / description",
+            x: MARGIN + 2.0,
+            right: false,
+        },
+        Column {
+            title: "Date",
+            x: 80.0,
+            right: false,
+        },
+        Column {
+            title: "Gross",
+            x: 116.0,
+            right: true,
+        },
+        Column {
+            title: "Agency fee",
+            x: 138.0,
+            right: true,
+        },
+        Column {
+            title: "Net",
+            x: 158.0,
+            right: true,
+        },
+        Column {
+            title: "Status",
+            x: 162.0,
+            right: false,
+        },
+    ];
+    let rows: Vec<Vec<String>> = statement
+        .lines
+        .iter()
+        .map(|line| {
+            let money = |cents| invoice_pdf::money(cents, &line.currency);
+            vec![
+                format!(
+                    "{} {} - {}",
+                    line.invoice_number, line.client_name, line.description
+                ),
+                line.invoice_date.clone().unwrap_or_default(),
+                money(line.gross_cents),
+                money(line.agency_fee_cents),
+                money(line.net_cents),
+                status_label(&line.status),
+            ]
+        })
+        .collect();
+    invoice_pdf::table(&mut cursor, &columns, 56.0, &rows);
+
+    for totals in &statement.totals {
+        let money = |cents| invoice_pdf::money(cents, &totals.currency);
+        let lines = [
+            ("Gross", money(totals.gross_cents)),
+            ("Agency fee", money(-totals.agency_fee_cents)),
+            ("Net", money(totals.net_cents)),
+            ("Paid", money(totals.paid_cents)),
+        ];
+        cursor.ensure(lines.len() as f32 * 5.5 + 14.0);
+        for (label, value) in &lines {
+            cursor.text_right(label, 9.5, right - 40.0, cursor.y, false);
+            cursor.text_right(value, 9.5, right - 2.0, cursor.y, false);
+            cursor.y -= 5.5;
+        }
+        cursor.band(cursor.y + 4.0, 8.0);
+        cursor.text_right("Outstanding", 11.0, right - 40.0, cursor.y - 1.5, true);
+        cursor.text_right(
+            &money(totals.outstanding_cents),
+            11.0,
+            right - 2.0,
+            cursor.y - 1.5,
+            true,
+        );
+        cursor.y -= 14.0;
+    }
+
+    cursor.finish(&[]).map(|(bytes, _)| bytes)
+}
+
+/// The agency's period statements, for one talent or all of them.
+pub async fn load_statements(
+    state: &AppState,
+    agency_id: &str,
+    talent_id: Option<&str>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> AppResult<Vec<PeriodStatement>> {
+    let built = talent_statements::build(
+        state,
+        agency_id,
+        &TalentStatementsQuery {
+            talent_id: talent_id.map(String::from),
+            year: Some(to.year()),
+        },
+    )
+    .await?;
+    Ok(period_statements(built.lines, from, to))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub talent_id: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub format: Option<String>,
+}
+
+/// GET /api/talent-statements/export: one talent's statement as PDF, or the
+/// lines of one or all talent as CSV.
+pub async fn export(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(q): Query<ExportQuery>,
+) -> AppResult<Response> {
+    let format = ExportFormat::parse(q.format.as_deref().unwrap_or("pdf").trim())
+        .ok_or_else(|| AppError::BadRequest("invalid_format".to_string()))?;
+    let (from, to) = parse_period(q.from.as_deref(), q.to.as_deref())?;
+    let talent_id = q
+        .talent_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+
+    let (content_type, file_name, body) = match format {
+        ExportFormat::Csv => {
+            let statements = load_statements(&state, &user.id, talent_id, from, to).await?;
+            let file_name = match statements.as_slice() {
+                [one] if talent_id.is_some() => one.file_name("csv"),
+                _ => format!("statements-{from}-{to}.csv"),
+            };
+            (
+                "text/csv; charset=utf-8",
+                file_name,
+                render_csv(&statements).into_bytes(),
+            )
+        }
+        ExportFormat::Pdf => {
+            let talent_id =
+                talent_id.ok_or_else(|| AppError::BadRequest("talent_id_required".to_string()))?;
+            let recipient = tax_documents::roster(&state, &user.id)
+                .await?
+                .into_iter()
+                .find(|r| r.id == talent_id)
+                .ok_or_else(|| AppError::NotFound("talent not found".to_string()))?;
+            let statement = load_statements(&state, &user.id, Some(talent_id), from, to)
+                .await?
+                .pop()
+                .unwrap_or_else(|| PeriodStatement {
+                    talent_id: recipient.id.clone(),
+                    talent_name: recipient.name(),
+                    from,
+                    to,
+                    lines: Vec::new(),
+                    totals: Vec::new(),
+                });
+            let branding = load_branding(&state, &user.id).await?;
+            let bytes = render_pdf(&statement, &branding)
+                .map_err(|e| AppError::internal("talent_statements.pdf", e))?;
+            (PDF_CONTENT_TYPE, statement.file_name("pdf"), bytes)
+        }
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// A `talent_statement_deliveries` row: one emailed statement version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementDelivery {
+    pub id: String,
+    pub agency_id: String,
+    pub talent_id: String,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub version: i32,
+    pub sha256: String,
+    #[serde(default)]
+    pub storage_path: Option<String>,
+    #[serde(default)]
+    pub to_email: Option<String>,
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub sent_at: Option<String>,
+    #[serde(default)]
+    pub sent_by: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DeliverySummary {
+    pub period_start: Option<NaiveDate>,
+    pub period_end: Option<NaiveDate>,
+    pub agencies: usize,
+    pub sent: usize,
+    pub already_sent: usize,
+    /// Talent not on the roster or without an email address.
+    pub no_email: usize,
+    pub failed: usize,
+}
+
+impl DeliverySummary {
+    fn add(&mut self, other: DeliverySummary) {
+        self.agencies += other.agencies;
+        self.sent += other.sent;
+        self.already_sent += other.already_sent;
+        self.no_email += other.no_email;
+        self.failed += other.failed;
+    }
+}
+
+/// Claims `version` of the statement for sending. A failed earlier attempt
+/// at the same version is taken over; a row written by someone else means it
+/// is not ours to send.
+async fn claim(
+    state: &AppState,
+    row: serde_json::Value,
+    failed: Option<&StatementDelivery>,
+) -> Result<Option<StatementDelivery>, RepoError> {
+    let claimed: Result<Vec<StatementDelivery>, RepoError> = match failed {
+        Some(previous) => {
+            fetch(
+                state
+                    .pg
+                    .from(DELIVERIES_TABLE)
+                    .update(row.to_string())
+                    .eq("id", &previous.id)
+                    .eq("status", "failed"),
+            )
+            .await
+        }
+        None => fetch(state.pg.from(DELIVERIES_TABLE).insert(row.to_string())).await,
+    };
+    match claimed {
+        Ok(rows) => Ok(rows.into_iter().next()),
+        Err(RepoError::Db { status: 409, .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn record_failure(state: &AppState, delivery_id: &str, error: &str) -> Result<(), RepoError> {
+    let _: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from(DELIVERIES_TABLE)
+            .update(json!({ "status": "failed", "error": error }).to_string())
+            .eq("id", delivery_id),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Stores the PDF and queues the email for a claimed delivery.
+async fn send(
+    state: &AppState,
+    delivery: &StatementDelivery,
+    statement: &PeriodStatement,
+    branding: &Branding,
+    bytes: Vec<u8>,
+) -> AppResult<()> {
+    if let Some(path) = delivery.storage_path.as_deref() {
+        let bucket = state.bucket(Bucket::Private);
+        // A retried version overwrites its own, identical, earlier upload.
+        state
+            .storage
+            .put(&bucket, path, bytes.clone().into(), PDF_CONTENT_TYPE, true)
+            .await?;
+    }
+    let to = delivery.to_email.as_deref().unwrap_or_default();
+    let subject = format!(
+        "Your statement from {} for {} to {}",
+        branding.agency_name, statement.from, statement.to
+    );
+    let mut body = format!(
+        "Hi {},\n\nAttached is your statement for {} to {}.\n",
+        statement.talent_name, statement.from, statement.to
+    );
+    for t in &statement.totals {
+        body.push_str(&format!(
+            "\n{}: net {}, paid {}, outstanding {}",
+            t.currency,
+            invoice_pdf::money(t.net_cents, &t.currency),
+            invoice_pdf::money(t.paid_cents, &t.currency),
+            invoice_pdf::money(t.outstanding_cents, &t.currency),
+        ));
+    }
+    body.push_str(&format!("\n\nThank you,\n{}", branding.agency_name));
+    let outgoing = email::OutgoingEmail::new(to, &subject, &body)
+        .from_name(Some(&branding.agency_name))
+        .category("talent_statement")
+        .agency(&delivery.agency_id)
+        .talent(Some(&delivery.talent_id))
+        .reference("talent_statement", &delivery.id)
+        .attach(email::EmailAttachment {
+            filename: statement.file_name("pdf"),
+            content_type: PDF_CONTENT_TYPE.to_string(),
+            content_base64: BASE64.encode(&bytes),
+        });
+    email::enqueue(state, outgoing).await?;
+    Ok(())
+}
+
+/// Emails the agency's talent their statements for the period, skipping
+/// versions already sent. `only` limits the run to some talent.
+pub async fn deliver(
+    state: &AppState,
+    agency_id: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    only: Option<&[String]>,
+    actor: Option<&AuthUser>,
+) -> AppResult<DeliverySummary> {
+    let mut summary = DeliverySummary {
+        period_start: Some(from),
+        period_end: Some(to),
+        agencies: 1,
+        ..Default::default()
+    };
+    let statements: Vec<PeriodStatement> = load_statements(state, agency_id, None, from, to)
+        .await?
+        .into_iter()
+        .filter(|s| only.is_none_or(|ids| ids.contains(&s.talent_id)))
+        .collect();
+    if statements.is_empty() {
+        return Ok(summary);
+    }
+    let roster: HashMap<String, Recipient> = tax_documents::roster(state, agency_id)
+        .await?
+        .into_iter()
+        .map(|r| (r.id.clone(), r))
+        .collect();
+    let deliveries: Vec<StatementDelivery> = fetch(
+        state
+            .pg
+            .from(DELIVERIES_TABLE)
+            .select("*")
+            .eq("agency_id", agency_id)
+            .eq("period_start", from.to_string())
+            .eq("period_end", to.to_string())
+            .order("version.desc"),
+    )
+    .await?;
+    let branding = load_branding(state, agency_id).await?;
+
+    for statement in &statements {
+        let Some(to_email) = roster
+            .get(&statement.talent_id)
+            .and_then(|r| r.email.as_deref())
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        else {
+            summary.no_email += 1;
+            continue;
+        };
+        let sha256 = statement.sha256();
+        let latest = deliveries
+            .iter()
+            .find(|d| d.talent_id == statement.talent_id);
+        let (version, failed) = match latest {
+            Some(d) if d.sha256 == sha256 && d.status != "failed" => {
+                summary.already_sent += 1;
+                continue;
+            }
+            Some(d) if d.sha256 == sha256 => (d.version, Some(d)),
+            Some(d) => (d.version + 1, None),
+            None => (1, None),
+        };
+
+        let bytes = render_pdf(statement, &branding)
+            .map_err(|e| AppError::internal("talent_statements.pdf", e))?;
+        let path = format!(
+            "agencies/{}/talent-statements/{}/{}_{}-v{}.pdf",
+            sanitize_segment(agency_id),
+            sanitize_segment(&statement.talent_id),
+            from,
+            to,
+            version
+        );
+        let row = json!({
+            "agency_id": agency_id,
+            "talent_id": statement.talent_id,
+            "period_start": from.to_string(),
+            "period_end": to.to_string(),
+            "version": version,
+            "sha256": sha256,
+            "storage_path": path,
+            "to_email": to_email,
+            "status": "sent",
+            "error": null,
+            "sent_at": ts(Utc::now()),
+            "sent_by": actor.map(|u| u.id.clone()),
+        });
+        let Some(delivery) = claim(state, row, failed).await? else {
+            summary.already_sent += 1;
+            continue;
+        };
+        match send(state, &delivery, statement, &branding, bytes).await {
+            Ok(()) => {
+                summary.sent += 1;
+                let mut event = AuditEvent::new(
+                    agency_id,
+                    "talent_statement.sent",
+                    DELIVERIES_TABLE,
+                    &delivery.id,
+                )
+                .title(format!(
+                    "Statement {} to {} v{} sent to {}",
+                    from, to, version, statement.talent_name
+                ));
+                if let Some(user) = actor {
+                    event = event.actor(user);
+                }
+                audit::record(state, event).await;
+            }
+            Err(e) => {
+                warn!(agency_id, talent_id = %statement.talent_id, error = %e, "talent statement delivery failed");
+                summary.failed += 1;
+                record_failure(state, &delivery.id, &e.to_string()).await?;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// An agency's statement delivery toggle; off unless the agency opts in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatementSettings {
+    #[serde(default)]
+    pub monthly_email_enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct OptedIn {
+    agency_id: String,
+}
+
+/// Sends last month's statements for every agency that turned monthly
+/// delivery on.
+pub async fn run_monthly(state: &AppState, today: NaiveDate) -> AppResult<DeliverySummary> {
+    let (from, to) = previous_month(today);
+    let agencies: Vec<OptedIn> = fetch(
+        state
+            .pg
+            .from(SETTINGS_TABLE)
+            .select("agency_id")
+            .eq("monthly_email_enabled", "true"),
+    )
+    .await?;
+    let mut summary = DeliverySummary {
+        period_start: Some(from),
+        period_end: Some(to),
+        ..Default::default()
+    };
+    for agency in &agencies {
+        summary.add(deliver(state, &agency.agency_id, from, to, None, None).await?);
+    }
+    Ok(summary)
+}
+
+/// Runs the monthly delivery for the current UTC date.
+pub async fn run_today(state: &AppState) -> AppResult<DeliverySummary> {
+    run_monthly(state, Utc::now().date_naive()).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SendPayload {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(default)]
+    pub talent_ids: Option<Vec<String>>,
+}
+
+/// POST /api/talent-statements/send: emails the period's statements now.
+pub async fn send_statements(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<SendPayload>,
+) -> AppResult<Json<DeliverySummary>> {
+    let (from, to) = parse_period(payload.from.as_deref(), payload.to.as_deref())?;
+    let summary = deliver(
+        &state,
+        &user.id,
+        from,
+        to,
+        payload.talent_ids.as_deref(),
+        Some(&user),
+    )
+    .await?;
+    Ok(Json(summary))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveriesQuery {
+    pub talent_id: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// GET /api/talent-statements/deliveries: sent statement versions, newest
+/// first.
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(q): Query<DeliveriesQuery>,
+) -> AppResult<Json<Vec<StatementDelivery>>> {
+    let mut req = state
+        .pg
+        .from(DELIVERIES_TABLE)
+        .select("*")
+        .eq("agency_id", &user.id)
+        .order("period_start.desc")
+        .order("version.desc");
+    if let Some(talent_id) = q.talent_id.as_deref().filter(|s| !s.trim().is_empty()) {
+        req = req.eq("talent_id", talent_id);
+    }
+    if q.from.is_some() || q.to.is_some() {
+        let (from, to) = parse_period(q.from.as_deref(), q.to.as_deref())?;
+        req = req
+            .gte("period_start", from.to_string())
+            .lte("period_end", to.to_string());
+    }
+    Ok(Json(fetch(req).await?))
+}
+
+/// GET /api/talent-statements/deliveries/:id/pdf: the PDF as it was sent.
+pub async fn delivery_pdf(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Response> {
+    let rows: Vec<StatementDelivery> = fetch(
+        state
+            .pg
+            .from(DELIVERIES_TABLE)
+            .select("*")
+            .eq("agency_id", &user.id)
+            .eq("id", &id)
+            .limit(1),
+    )
+    .await?;
+    let path = rows
+        .into_iter()
+        .next()
+        .and_then(|d| d.storage_path)
+        .ok_or_else(|| AppError::NotFound("delivery not found".to_string()))?;
+    let bucket = state.bucket(Bucket::Private);
+    let bytes = state.storage.get(&bucket, &path).await?.to_vec();
+    let file_name = sanitize_file_name(path.rsplit('/').next().unwrap_or("statement.pdf"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, PDF_CONTENT_TYPE.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"{file_name}\""),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// GET /api/talent-statements/settings
+pub async fn get_settings(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> AppResult<Json<StatementSettings>> {
+    let rows: Vec<StatementSettings> = fetch(
+        state
+            .pg
+            .from(SETTINGS_TABLE)
+            .select("monthly_email_enabled")
+            .eq("agency_id", &user.id)
+            .limit(1),
+    )
+    .await?;
+    Ok(Json(rows.into_iter().next().unwrap_or_default()))
+}
+
+/// PUT /api/talent-statements/settings
+pub async fn update_settings(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(settings): Json<StatementSettings>,
+) -> AppResult<Json<StatementSettings>> {
+    let before = get_settings(State(state.clone()), user.clone()).await?.0;
+    let body = json!({
+        "agency_id": user.id,
+        "monthly_email_enabled": settings.monthly_email_enabled,
+        "updated_at": Utc::now().to_rfc3339(),
+    });
+    let _: Vec<serde_json::Value> = fetch(
+        state
+            .pg
+            .from(SETTINGS_TABLE)
+            .upsert(body.to_string())
+            .on_conflict("agency_id"),
+    )
+    .await?;
+    audit::record(
+        &state,
+        AuditEvent::new(
+            &user.id,
+            "talent_statement_settings.updated",
+            SETTINGS_TABLE,
+            &user.id,
+        )
+        .actor(&user)
+        .change(&before, &settings),
+    )
+    .await;
+    Ok(Json(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(talent: &str, date: &str, status: &str, net: i64, paid: i64) -> TalentStatementLine {
+        TalentStatementLine {
+            talent_id: talent.into(),
+            talent_name: format!("Talent {talent}"),
+            invoice_id: format!("inv-{date}"),
+            invoice_number: format!("INV-{date}"),
+            invoice_date: Some(date.into()),
+            client_name: "Acme, Inc.".into(),
+            description: "Shoot".into(),
+            gross_cents: net * 5 / 4,
+            credited_cents: 0,
+            agency_fee_cents: net / 4,
+            net_cents: net,
+            paid_net_cents: paid,
+            paid_ytd_cents: paid,
+            currency: "USD".into(),
+            status: status.into(),
+            paid_at: None,
+        }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn statements_cover_issued_lines_in_the_period() {
+        let lines = vec![
+            line("b", "2026-09-03", "paid", 8_000, 8_000),
+            line("a", "2026-09-20", "partially_paid", 4_000, 1_000),
+            line("a", "2026-09-10", "draft", 9_000, 0),
+            line("a", "2026-10-01", "sent", 9_000, 0),
+            line("a", "2026-09-05", "sent", 2_000, 0),
+        ];
+        let statements = period_statements(lines, date("2026-09-01"), date("2026-09-30"));
+        assert_eq!(statements.len(), 2);
+        let a = &statements[0];
+        assert_eq!(a.talent_id, "a");
+        assert_eq!(a.lines.len(), 2);
+        assert_eq!(a.lines[0].invoice_date.as_deref(), Some("2026-09-05"));
+        assert_eq!(
+            (
+                a.totals[0].net_cents,
+                a.totals[0].paid_cents,
+                a.totals[0].outstanding_cents
+            ),
+            (6_000, 1_000, 5_000)
+        );
+
+        let csv = render_csv(&statements);
+        assert_eq!(csv.lines().count(), 4);
+        assert!(
+            csv.contains(",\"Acme, Inc.\",Shoot,USD,50.00,10.00,40.00,10.00,partially_paid\r\n")
+        );
+    }
+
+    #[test]
+    fn monthly_statements_cover_the_previous_month() {
+        assert_eq!(
+            previous_month(date("2026-03-01")),
+            (date("2026-02-01"), date("2026-02-28"))
+        );
+        assert_eq!(
+            previous_month(date("2026-01-15")),
+            (date("2025-12-01"), date("2025-12-31"))
+        );
+        assert_eq!(status_label("partially_paid"), "Partially paid");
+        assert_eq!(status_label("overdue"), "Overdue");
+    }
+}
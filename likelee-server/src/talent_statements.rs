@@ -1,6 +1,12 @@
-use crate::{auth::AuthUser, config::AppState, errors::sanitize_db_error};
+use crate::{
+    auth::AuthUser,
+    config::AppState,
+    errors::sanitize_db_error,
+    invoice_payments::{line_credit_cents, paid_fraction},
+    repositories::{fetch, Invoice, InvoiceFilter, InvoiceItem, InvoiceStatus},
+};
 use axum::{extract::Query, extract::State, http::StatusCode, Json};
-use chrono::{Datelike, NaiveDate, Utc};
+use chrono::{Datelike, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
@@ -31,47 +37,107 @@ pub struct TalentStatementLine {
     pub client_name: String,
     pub description: String,
     pub gross_cents: i64,
+    pub credited_cents: i64,
     pub agency_fee_cents: i64,
     pub net_cents: i64,
+    pub paid_net_cents: i64,
+    /// The part of `paid_net_cents` paid in the statement year.
+    pub paid_ytd_cents: i64,
+    pub currency: String,
     pub status: String,
     pub paid_at: Option<String>,
 }
 
-fn parse_date(v: &str) -> Option<NaiveDate> {
-    NaiveDate::parse_from_str(v, "%Y-%m-%d").ok()
-}
+type CreditNotesByInvoice = HashMap<String, Vec<(Option<String>, i64)>>;
 
-pub async fn list(
-    State(state): State<AppState>,
-    user: AuthUser,
-    Query(params): Query<TalentStatementsQuery>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    // Pull invoice items with their parent invoice + client snapshot.
-    // PostgREST supports embedding via foreign keys: invoice_id -> agency_invoices.
+/// Credit notes issued by the agency, grouped by invoice.
+async fn credit_notes_by_invoice(
+    state: &AppState,
+    agency_id: &str,
+) -> Result<CreditNotesByInvoice, (StatusCode, String)> {
     let resp = state
         .pg
-        .from("agency_invoice_items")
-        .select(
-            "id,invoice_id,description,talent_id,talent_name,date_of_service,quantity,unit_price_cents,line_total_cents,agency_invoices!inner(id,agency_id,client_id,invoice_number,status,invoice_date,paid_at,agency_commission_bps,bill_to_company)",
-        )
-        .eq("agency_invoices.agency_id", &user.id)
+        .from("agency_invoice_credit_notes")
+        .select("invoice_id,invoice_item_id,amount_cents")
+        .eq("agency_id", agency_id)
         .execute()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
     let status = resp.status();
     let text = resp
         .text()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
     if !status.is_success() {
         return Err(sanitize_db_error(status.as_u16(), text));
     }
-
-    let rows: serde_json::Value = serde_json::from_str(&text)
+    let rows: Vec<serde_json::Value> = serde_json::from_str(&text)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let arr = rows.as_array().cloned().unwrap_or_default();
+
+    let mut by_invoice: CreditNotesByInvoice = HashMap::new();
+    for r in rows {
+        let Some(invoice_id) = r.get("invoice_id").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        by_invoice.entry(invoice_id.to_string()).or_default().push((
+            r.get("invoice_item_id")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+            r.get("amount_cents").and_then(|v| v.as_i64()).unwrap_or(0),
+        ));
+    }
+    Ok(by_invoice)
+}
+
+/// Per-talent summaries and invoice lines for one agency.
+#[derive(Debug, Serialize, Clone)]
+pub struct TalentStatements {
+    pub year: i32,
+    pub summaries: Vec<TalentStatementSummary>,
+    pub lines: Vec<TalentStatementLine>,
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<TalentStatementsQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let statements = build(&state, &user.id, &params).await?;
+    Ok(Json(json!(statements)))
+}
+
+/// Builds the statements of `agency_id`'s talent from their invoice lines,
+/// with year-to-date figures for `params.year` (default: this year).
+pub async fn build(
+    state: &AppState,
+    agency_id: &str,
+    params: &TalentStatementsQuery,
+) -> Result<TalentStatements, (StatusCode, String)> {
+    let invoices: HashMap<String, Invoice> = state
+        .repos
+        .invoices
+        .list_for_agency(agency_id, &InvoiceFilter::default())
+        .await?
+        .into_iter()
+        .map(|i| (i.id.clone(), i))
+        .collect();
+    let items: Vec<InvoiceItem> = if invoices.is_empty() {
+        Vec::new()
+    } else {
+        fetch(
+            state
+                .pg
+                .from("agency_invoice_items")
+                .select("*")
+                .in_(
+                    "invoice_id",
+                    invoices.keys().map(String::as_str).collect::<Vec<_>>(),
+                )
+                .order("sort_order.asc"),
+        )
+        .await?
+    };
+    let credit_notes = credit_notes_by_invoice(state, agency_id).await?;
 
     let now = Utc::now();
     let current_year = now.year();
@@ -80,23 +146,16 @@ pub async fn list(
     let mut summary_by_talent: HashMap<String, TalentStatementSummary> = HashMap::new();
     let mut lines: Vec<TalentStatementLine> = vec![];
 
-    for r in arr {
-        let invoice = r.get("agency_invoices");
-        let inv_obj = invoice.and_then(|v| v.as_object());
-        let inv_agency_id = inv_obj
-            .and_then(|o| o.get("agency_id"))
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
-        if inv_agency_id != user.id {
+    for item in items {
+        let Some(invoice) = invoices.get(&item.invoice_id) else {
             continue;
-        }
+        };
 
-        let talent_id = r
-            .get("talent_id")
-            .and_then(|v| v.as_str())
+        let talent_id = item
+            .talent_id
+            .clone()
             .filter(|s| !s.trim().is_empty())
-            .unwrap_or("unknown")
-            .to_string();
+            .unwrap_or_else(|| "unknown".to_string());
 
         if let Some(filter_id) = params.talent_id.as_ref().filter(|s| !s.trim().is_empty()) {
             if &talent_id != filter_id {
@@ -104,69 +163,51 @@ pub async fn list(
             }
         }
 
-        let talent_name = r
-            .get("talent_name")
-            .and_then(|v| v.as_str())
+        let talent_name = item
+            .talent_name
+            .clone()
             .filter(|s| !s.trim().is_empty())
-            .unwrap_or("(unknown)")
-            .to_string();
-
-        let status_str = inv_obj
-            .and_then(|o| o.get("status"))
-            .and_then(|v| v.as_str())
-            .unwrap_or("draft")
-            .to_string();
-
-        let invoice_id = inv_obj
-            .and_then(|o| o.get("id"))
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
-
-        let invoice_number = inv_obj
-            .and_then(|o| o.get("invoice_number"))
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
-
-        let invoice_date = inv_obj
-            .and_then(|o| o.get("invoice_date"))
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
-
-        let paid_at = inv_obj
-            .and_then(|o| o.get("paid_at"))
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
-
-        let client_name = inv_obj
-            .and_then(|o| o.get("bill_to_company"))
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
-
-        let commission_bps = inv_obj
-            .and_then(|o| o.get("agency_commission_bps"))
-            .and_then(|v| v.as_i64())
-            .unwrap_or(2000);
-
-        let gross_cents = r
-            .get("line_total_cents")
-            .and_then(|v| v.as_i64())
+            .unwrap_or_else(|| "(unknown)".to_string());
+
+        let status_str = invoice.status.as_str().to_string();
+        let paid_at = invoice.paid_at.map(|t| t.to_rfc3339());
+
+        let line_total_cents = item.line_total_cents as i64;
+        // Credit notes reduce what the talent earned on the line.
+        let credited_cents = credit_notes
+            .get(&invoice.id)
+            .map(|notes| {
+                line_credit_cents(
+                    &item.id,
+                    line_total_cents,
+                    invoice.total_cents as i64,
+                    notes,
+                )
+            })
             .unwrap_or(0);
+        let gross_cents = line_total_cents - credited_cents;
 
-        let agency_fee_cents = (gross_cents * commission_bps) / 10_000;
+        let agency_fee_cents = (gross_cents * invoice.agency_commission_bps as i64) / 10_000;
         let net_cents = gross_cents - agency_fee_cents;
 
-        let owed = status_str == "sent";
-        let paid = status_str == "paid" || paid_at.is_some();
+        let owed = invoice.status.is_outstanding();
+        let paid = invoice.status == InvoiceStatus::Paid || paid_at.is_some();
 
-        let ytd_paid = if let Some(paid_at_str) = paid_at.as_ref() {
-            let paid_date = paid_at_str.split('T').next().and_then(parse_date);
-            paid && paid_date.map(|d| d.year() == ytd_year).unwrap_or(false)
+        // Partial payments release the same share of every line.
+        let paid_net_cents = if paid {
+            net_cents
+        } else if owed {
+            let fraction = paid_fraction(
+                invoice.total_cents as i64,
+                invoice.amount_paid_cents as i64,
+                invoice.amount_credited_cents as i64,
+            );
+            (net_cents as f64 * fraction).round() as i64
         } else {
-            false
+            0
         };
+        let last_paid_at = invoice.paid_at.or(invoice.last_payment_at);
+        let ytd_paid = last_paid_at.is_some_and(|t| t.year() == ytd_year);
 
         let entry = summary_by_talent
             .entry(talent_id.clone())
@@ -181,19 +222,18 @@ pub async fn list(
 
         entry.total_jobs += 1;
         if owed {
-            entry.total_owed_cents += net_cents;
-        }
-        if ytd_paid {
-            entry.total_paid_ytd_cents += net_cents;
+            entry.total_owed_cents += net_cents - paid_net_cents;
         }
-        if paid {
-            if let Some(paid_at_str) = paid_at.as_ref() {
+        let paid_ytd_cents = if ytd_paid { paid_net_cents } else { 0 };
+        entry.total_paid_ytd_cents += paid_ytd_cents;
+        if paid_net_cents > 0 {
+            if let Some(paid_at_str) = last_paid_at.map(|t| t.to_rfc3339()) {
                 let replace = match entry.last_payment_at.as_ref() {
                     None => true,
-                    Some(prev) => prev < paid_at_str,
+                    Some(prev) => prev < &paid_at_str,
                 };
                 if replace {
-                    entry.last_payment_at = Some(paid_at_str.clone());
+                    entry.last_payment_at = Some(paid_at_str);
                 }
             }
         }
@@ -201,20 +241,20 @@ pub async fn list(
         lines.push(TalentStatementLine {
             talent_id,
             talent_name,
-            invoice_id,
-            invoice_number,
-            invoice_date,
-            client_name,
-            description: r
-                .get("description")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string(),
+            invoice_id: invoice.id.clone(),
+            invoice_number: invoice.invoice_number.clone(),
+            invoice_date: Some(invoice.invoice_date.to_string()),
+            client_name: invoice.bill_to_company.clone(),
+            description: item.description,
             gross_cents,
+            credited_cents,
             agency_fee_cents,
             net_cents,
+            paid_net_cents,
+            paid_ytd_cents,
+            currency: invoice.currency.to_uppercase(),
             status: if owed {
-                "sent".to_string()
+                status_str
             } else if paid {
                 "paid".to_string()
             } else {
@@ -225,11 +265,11 @@ pub async fn list(
     }
 
     let mut summaries: Vec<TalentStatementSummary> = summary_by_talent.into_values().collect();
-    summaries.sort_by(|a, b| b.total_owed_cents.cmp(&a.total_owed_cents));
+    summaries.sort_by_key(|b| std::cmp::Reverse(b.total_owed_cents));
 
-    Ok(Json(json!({
-        "year": ytd_year,
-        "summaries": summaries,
-        "lines": lines,
-    })))
+    Ok(TalentStatements {
+        year: ytd_year,
+        summaries,
+        lines,
+    })
 }
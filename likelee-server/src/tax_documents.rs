@@ -0,0 +1,853 @@
+//! Year-end talent tax documents.
+//!
+//! For a tax year, adds up what an agency paid each of its talent: licensing
+//! payouts, paid IRL payments and the talent's share of paid invoices (from
+//! the talent statements). US talent get a 1099-NEC style summary, everyone
+//! else a plain earnings statement. Each document is rendered to PDF, kept in
+//! the private bucket and recorded in `talent_tax_documents`, and the talent
+//! is notified in-app and by email.
+//!
+//! Generating again only writes a new document when the figures changed; the
+//! new one is marked corrected and points at the one it supersedes.
+
+use crate::{
+    audit::{self, AuditEvent},
+    auth::AuthUser,
+    config::AppState,
+    email,
+    errors::{AppError, AppResult},
+    invoice_pdf::{self, Column, Cursor, MARGIN, PAGE_W, ROW_H},
+    invoices::load_branding,
+    repositories::{fetch, RepoError},
+    storage::{sanitize_segment, Bucket},
+    talent_statements::{self, TalentStatementLine, TalentStatementsQuery},
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+pub const TABLE: &str = "talent_tax_documents";
+const PDF_CONTENT_TYPE: &str = "application/pdf";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DocType {
+    #[serde(rename = "1099")]
+    Form1099Nec,
+    #[serde(rename = "earnings_statement")]
+    EarningsStatement,
+}
+
+impl DocType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DocType::Form1099Nec => "1099",
+            DocType::EarningsStatement => "earnings_statement",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            DocType::Form1099Nec => "1099-NEC Earnings Summary",
+            DocType::EarningsStatement => "Annual Earnings Statement",
+        }
+    }
+
+    /// US talent get the 1099 summary. Talent without a country on file are
+    /// treated as US so nobody who may need a 1099 goes without one.
+    pub fn for_country(country: Option<&str>) -> Self {
+        let country = country.map(str::trim).unwrap_or("");
+        let us = country.is_empty()
+            || ["us", "usa", "united states", "united states of america"]
+                .contains(&country.to_ascii_lowercase().as_str());
+        if us {
+            DocType::Form1099Nec
+        } else {
+            DocType::EarningsStatement
+        }
+    }
+}
+
+/// Non-employee compensation below this is not reportable on a 1099-NEC:
+/// $600 through 2025 and $2,000 from 2026.
+pub fn nec_threshold_cents(tax_year: i32) -> i64 {
+    if tax_year >= 2026 {
+        200_000
+    } else {
+        60_000
+    }
+}
+
+/// One currency's earnings for a talent over the tax year.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EarningsTotals {
+    pub currency: String,
+    pub licensing_cents: i64,
+    pub irl_cents: i64,
+    pub invoice_cents: i64,
+    pub total_cents: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    Licensing,
+    Irl,
+    Invoices,
+}
+
+/// Earnings per talent id, then per currency.
+pub type Earnings = BTreeMap<String, BTreeMap<String, EarningsTotals>>;
+
+fn credit(earnings: &mut Earnings, talent_id: &str, currency: &str, source: Source, cents: i64) {
+    if talent_id.trim().is_empty() || cents == 0 {
+        return;
+    }
+    let currency = match currency.trim() {
+        "" => "USD".to_string(),
+        c => c.to_uppercase(),
+    };
+    let totals = earnings
+        .entry(talent_id.to_string())
+        .or_default()
+        .entry(currency.clone())
+        .or_insert_with(|| EarningsTotals {
+            currency,
+            ..Default::default()
+        });
+    match source {
+        Source::Licensing => totals.licensing_cents += cents,
+        Source::Irl => totals.irl_cents += cents,
+        Source::Invoices => totals.invoice_cents += cents,
+    }
+    totals.total_cents += cents;
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LicensingPayoutRow {
+    #[serde(default)]
+    pub talent_id: Option<String>,
+    #[serde(default)]
+    pub amount_cents: i64,
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// `[{talent_id, amount_cents}]` when one payout covers several talent.
+    #[serde(default)]
+    pub talent_splits: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IrlPaymentRow {
+    pub talent_id: String,
+    pub amount_cents: i64,
+    #[serde(default)]
+    pub currency: Option<String>,
+}
+
+/// Adds up the year's payouts, IRL payments and paid invoice lines per
+/// talent. Licensing payouts split between talent credit each split; the
+/// others credit the payout's talent, as the talent revenue views do.
+pub fn aggregate(
+    licensing: &[LicensingPayoutRow],
+    irl: &[IrlPaymentRow],
+    invoice_lines: &[TalentStatementLine],
+) -> Earnings {
+    let mut earnings = Earnings::new();
+    for payout in licensing {
+        let currency = payout.currency.as_deref().unwrap_or("");
+        let splits = payout
+            .talent_splits
+            .as_ref()
+            .and_then(|v| v.as_array())
+            .filter(|a| !a.is_empty());
+        match splits {
+            Some(splits) => {
+                for split in splits {
+                    credit(
+                        &mut earnings,
+                        split
+                            .get("talent_id")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or(""),
+                        currency,
+                        Source::Licensing,
+                        split
+                            .get("amount_cents")
+                            .and_then(|v| v.as_i64())
+                            .unwrap_or(0),
+                    );
+                }
+            }
+            None => credit(
+                &mut earnings,
+                payout.talent_id.as_deref().unwrap_or(""),
+                currency,
+                Source::Licensing,
+                payout.amount_cents,
+            ),
+        }
+    }
+    for payment in irl {
+        credit(
+            &mut earnings,
+            &payment.talent_id,
+            payment.currency.as_deref().unwrap_or(""),
+            Source::Irl,
+            payment.amount_cents,
+        );
+    }
+    for line in invoice_lines {
+        credit(
+            &mut earnings,
+            &line.talent_id,
+            &line.currency,
+            Source::Invoices,
+            line.paid_ytd_cents,
+        );
+    }
+    earnings
+}
+
+/// A roster entry, as printed on the document.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Recipient {
+    pub id: String,
+    #[serde(default)]
+    pub full_legal_name: Option<String>,
+    #[serde(default)]
+    pub stage_name: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub city: Option<String>,
+    #[serde(default)]
+    pub state_province: Option<String>,
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub creator_id: Option<String>,
+}
+
+impl Recipient {
+    /// Legal name where known; the stage name otherwise.
+    pub fn name(&self) -> String {
+        [&self.full_legal_name, &self.stage_name]
+            .into_iter()
+            .flatten()
+            .map(|s| s.trim())
+            .find(|s| !s.is_empty())
+            .unwrap_or("Talent")
+            .to_string()
+    }
+
+    fn location(&self) -> String {
+        [&self.city, &self.state_province, &self.country]
+            .into_iter()
+            .flatten()
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The talent's login, which in-app notifications are addressed to.
+    fn auth_user_id(&self) -> Option<&str> {
+        [&self.user_id, &self.creator_id]
+            .into_iter()
+            .flatten()
+            .map(|s| s.as_str())
+            .find(|s| !s.trim().is_empty())
+    }
+}
+
+/// The agency's talent roster.
+pub(crate) async fn roster(state: &AppState, agency_id: &str) -> Result<Vec<Recipient>, RepoError> {
+    fetch(
+        state
+            .pg
+            .from("agency_users")
+            .select("id,full_legal_name,stage_name,email,city,state_province,country,user_id,creator_id")
+            .eq("agency_id", agency_id),
+    )
+    .await
+}
+
+/// Everything printed on a document. Its hash decides whether a rerun
+/// produces a new document.
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    pub doc_type: DocType,
+    pub tax_year: i32,
+    pub payer_name: String,
+    pub payer_email: Option<String>,
+    pub recipient_name: String,
+    pub recipient_location: String,
+    pub totals: Vec<EarningsTotals>,
+}
+
+impl Statement {
+    pub fn sha256(&self) -> String {
+        let bytes = serde_json::to_vec(self).unwrap_or_default();
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    /// USD compensation, the figure a 1099-NEC reports.
+    pub fn usd_cents(&self) -> i64 {
+        self.totals
+            .iter()
+            .filter(|t| t.currency == "USD")
+            .map(|t| t.total_cents)
+            .sum()
+    }
+}
+
+/// Renders `statement` into PDF bytes.
+pub fn render(statement: &Statement, corrected: bool) -> Result<Vec<u8>, String> {
+    let mut cursor = Cursor::new(&format!(
+        "{} {}",
+        statement.doc_type.title(),
+        statement.tax_year
+    ))?;
+    let right = PAGE_W - MARGIN;
+
+    // Header: payer on the left, document title and year on the right.
+    let top = cursor.y;
+    cursor.text(&statement.payer_name, 14.0, MARGIN, top - 6.0, true);
+    if let Some(email) = statement.payer_email.as_deref() {
+        cursor.text(email, 9.0, MARGIN, top - 11.0, false);
+    }
+    cursor.text_right(statement.doc_type.title(), 14.0, right, top - 6.0, true);
+    cursor.text_right(
+        &format!("Tax year {}", statement.tax_year),
+        10.0,
+        right,
+        top - 12.0,
+        false,
+    );
+    if corrected {
+        cursor.text_right("CORRECTED", 10.0, right, top - 17.5, true);
+    }
+    cursor.y = top - 26.0;
+    cursor.rule(cursor.y + 3.0, 0.7);
+
+    cursor.y -= 3.0;
+    cursor.text("RECIPIENT", 8.5, MARGIN, cursor.y, true);
+    cursor.y -= 5.5;
+    cursor.text(&statement.recipient_name, 11.0, MARGIN, cursor.y, true);
+    if !statement.recipient_location.is_empty() {
+        cursor.y -= 4.8;
+        cursor.text(&statement.recipient_location, 9.5, MARGIN, cursor.y, false);
+    }
+    cursor.y -= 12.0;
+
+    let columns = [
+        Column {
+            title: "Currency",
+            x: MARGIN + 2.0,
+            right: false,
+        },
+        Column {
+            title: "Licensing",
+            x: 92.0,
+            right: true,
+        },
+        Column {
+            title: "Bookings",
+            x: 124.0,
+            right: true,
+        },
+        Column {
+            title: "Invoiced work",
+            x: 158.0,
+            right: true,
+        },
+        Column {
+            title: "Total",
+            x: right - 2.0,
+            right: true,
+        },
+    ];
+    let rows: Vec<Vec<String>> = statement
+        .totals
+        .iter()
+        .map(|t| {
+            let money = |cents| invoice_pdf::money(cents, &t.currency);
+            vec![
+                t.currency.clone(),
+                money(t.licensing_cents),
+                money(t.irl_cents),
+                money(t.invoice_cents),
+                money(t.total_cents),
+            ]
+        })
+        .collect();
+    invoice_pdf::table(&mut cursor, &columns, 40.0, &rows);
+
+    let note = match statement.doc_type {
+        DocType::Form1099Nec => {
+            let usd = statement.usd_cents();
+            cursor.ensure(ROW_H * 2.0);
+            cursor.band(cursor.y + 4.0, 8.0);
+            cursor.text(
+                "Box 1: Nonemployee compensation",
+                11.0,
+                MARGIN + 2.0,
+                cursor.y - 1.5,
+                true,
+            );
+            cursor.text_right(
+                &invoice_pdf::money(usd, "USD"),
+                11.0,
+                right - 2.0,
+                cursor.y - 1.5,
+                true,
+            );
+            cursor.y -= 14.0;
+            let threshold = nec_threshold_cents(statement.tax_year);
+            if usd < threshold {
+                format!(
+                    "This is a summary of payments made to you in {}. The amount is below the {} 1099-NEC reporting threshold, so no form is filed with the IRS.",
+                    statement.tax_year,
+                    invoice_pdf::money(threshold, "USD")
+                )
+            } else {
+                format!(
+                    "This is a summary of nonemployee compensation paid to you in {}. The official Form 1099-NEC filed with the IRS reports the Box 1 amount.",
+                    statement.tax_year
+                )
+            }
+        }
+        DocType::EarningsStatement => format!(
+            "This statement lists what {} paid you in {}, by currency. It is provided for your own tax reporting; no withholding was applied.",
+            statement.payer_name, statement.tax_year
+        ),
+    };
+    for line in invoice_pdf::wrap(&note, 9.0, PAGE_W - 2.0 * MARGIN) {
+        cursor.ensure(4.5);
+        cursor.text(&line, 9.0, MARGIN, cursor.y, false);
+        cursor.y -= 4.5;
+    }
+
+    cursor.finish(&[]).map(|(bytes, _)| bytes)
+}
+
+/// A `talent_tax_documents` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaxDocument {
+    pub id: String,
+    pub talent_id: String,
+    #[serde(default)]
+    pub agency_id: Option<String>,
+    pub doc_type: String,
+    #[serde(default)]
+    pub tax_year: Option<i32>,
+    #[serde(default)]
+    pub storage_bucket: Option<String>,
+    #[serde(default)]
+    pub storage_path: Option<String>,
+    #[serde(default)]
+    pub totals: Vec<EarningsTotals>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub supersedes_id: Option<String>,
+    #[serde(default)]
+    pub notified_at: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GeneratePayload {
+    pub tax_year: i32,
+    /// Limits the run to these talent; all talent with earnings otherwise.
+    #[serde(default)]
+    pub talent_ids: Option<Vec<String>>,
+    /// Defaults to true.
+    #[serde(default)]
+    pub notify: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GenerateSummary {
+    pub tax_year: i32,
+    pub generated: Vec<TaxDocument>,
+    /// Talent whose latest document already has these figures.
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    pub tax_year: Option<i32>,
+}
+
+fn validate_year(tax_year: i32) -> AppResult<()> {
+    if !(2000..=Utc::now().year()).contains(&tax_year) {
+        return Err(AppError::BadRequest("invalid_tax_year".to_string()));
+    }
+    Ok(())
+}
+
+/// Talent earnings of `agency_id` paid during `tax_year`.
+pub async fn earnings_for_year(
+    state: &AppState,
+    agency_id: &str,
+    tax_year: i32,
+) -> AppResult<Earnings> {
+    let start = format!("{tax_year}-01-01T00:00:00Z");
+    let end = format!("{}-01-01T00:00:00Z", tax_year + 1);
+    let licensing: Vec<LicensingPayoutRow> = fetch(
+        state
+            .pg
+            .from("licensing_payouts")
+            .select("talent_id,amount_cents,currency,talent_splits")
+            .eq("agency_id", agency_id)
+            .gte("paid_at", &start)
+            .lt("paid_at", &end),
+    )
+    .await?;
+    let irl: Vec<IrlPaymentRow> = fetch(
+        state
+            .pg
+            .from("talent_irl_payments")
+            .select("talent_id,amount_cents,currency")
+            .eq("agency_id", agency_id)
+            .eq("status", "paid")
+            .gte("paid_at", &start)
+            .lt("paid_at", &end),
+    )
+    .await?;
+    let statements = talent_statements::build(
+        state,
+        agency_id,
+        &TalentStatementsQuery {
+            talent_id: None,
+            year: Some(tax_year),
+        },
+    )
+    .await?;
+    Ok(aggregate(&licensing, &irl, &statements.lines))
+}
+
+/// Latest document per talent for the year, from newest to oldest rows.
+async fn latest_documents(
+    state: &AppState,
+    agency_id: &str,
+    tax_year: i32,
+) -> Result<HashMap<String, TaxDocument>, RepoError> {
+    let rows: Vec<TaxDocument> = fetch(
+        state
+            .pg
+            .from(TABLE)
+            .select("*")
+            .eq("agency_id", agency_id)
+            .eq("tax_year", tax_year.to_string())
+            .order("created_at.desc"),
+    )
+    .await?;
+    let mut latest = HashMap::new();
+    for row in rows {
+        latest.entry(row.talent_id.clone()).or_insert(row);
+    }
+    Ok(latest)
+}
+
+/// Tells the talent a document is ready, in-app and by email. Both are
+/// best-effort; returns whether either went out.
+async fn notify(
+    state: &AppState,
+    agency_id: &str,
+    payer_name: &str,
+    recipient: &Recipient,
+    document: &TaxDocument,
+    statement: &Statement,
+) -> bool {
+    let subject = format!(
+        "Your {} {} is ready",
+        statement.tax_year,
+        statement.doc_type.title()
+    );
+    let message = format!(
+        "Hi {},\n\n{} has issued your {} for {}. You can download it from your tax documents in Likelee.",
+        recipient.name(),
+        payer_name,
+        statement.doc_type.title(),
+        statement.tax_year
+    );
+    let mut notified = false;
+    if let Some(talent_user_id) = recipient.auth_user_id() {
+        let insert = json!({
+            "talent_user_id": talent_user_id,
+            "agency_id": agency_id,
+            "channel": "in_app",
+            "from_label": payer_name,
+            "subject": subject,
+            "message": message,
+            "meta_json": json!({
+                "tax_document_id": document.id,
+                "doc_type": document.doc_type,
+                "tax_year": statement.tax_year,
+            }),
+        });
+        match state
+            .pg
+            .from("talent_notifications")
+            .insert(insert.to_string())
+            .execute()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() => notified = true,
+            Ok(resp) => tracing::warn!(
+                "tax_document_notification_failed document_id={} status={}",
+                document.id,
+                resp.status()
+            ),
+            Err(e) => tracing::warn!(
+                "tax_document_notification_failed document_id={} error={}",
+                document.id,
+                e
+            ),
+        }
+    }
+    if let Some(to) = recipient.email.as_deref().filter(|e| !e.trim().is_empty()) {
+        let outgoing = email::OutgoingEmail::new(to, &subject, &message)
+            .from_name(Some(payer_name))
+            .category("tax_document")
+            .agency(agency_id)
+            .talent(Some(&recipient.id))
+            .reference("tax_document", &document.id);
+        match email::enqueue(state, outgoing).await {
+            Ok(_) => notified = true,
+            Err(e) => tracing::warn!(
+                "tax_document_email_failed document_id={} error={:?}",
+                document.id,
+                e
+            ),
+        }
+    }
+    notified
+}
+
+/// POST /api/tax-documents/generate: builds the year's documents for the
+/// agency's talent, writing new ones only where the figures changed.
+pub async fn generate(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<GeneratePayload>,
+) -> AppResult<Json<GenerateSummary>> {
+    let tax_year = payload.tax_year;
+    validate_year(tax_year)?;
+    let only: Option<Vec<String>> = payload
+        .talent_ids
+        .map(|ids| ids.into_iter().filter(|id| !id.trim().is_empty()).collect());
+
+    let earnings = earnings_for_year(&state, &user.id, tax_year).await?;
+    let roster = roster(&state, &user.id).await?;
+    let mut latest = latest_documents(&state, &user.id, tax_year).await?;
+    let branding = load_branding(&state, &user.id).await?;
+    let bucket = state.bucket(Bucket::Private);
+
+    let mut summary = GenerateSummary {
+        tax_year,
+        generated: Vec::new(),
+        unchanged: 0,
+    };
+    for recipient in &roster {
+        if only
+            .as_ref()
+            .is_some_and(|ids| !ids.contains(&recipient.id))
+        {
+            continue;
+        }
+        let Some(totals) = earnings.get(&recipient.id) else {
+            continue;
+        };
+        let statement = Statement {
+            doc_type: DocType::for_country(recipient.country.as_deref()),
+            tax_year,
+            payer_name: branding.agency_name.clone(),
+            payer_email: branding.agency_email.clone(),
+            recipient_name: recipient.name(),
+            recipient_location: recipient.location(),
+            totals: totals.values().cloned().collect(),
+        };
+        let sha256 = statement.sha256();
+        let previous = latest.remove(&recipient.id);
+        if previous
+            .as_ref()
+            .is_some_and(|p| p.sha256.as_deref() == Some(sha256.as_str()))
+        {
+            summary.unchanged += 1;
+            continue;
+        }
+
+        let bytes = render(&statement, previous.is_some())
+            .map_err(|e| AppError::internal("tax_documents.pdf", e))?;
+        let path = format!(
+            "agencies/{}/tax-documents/{}/{}-{}-{}.pdf",
+            sanitize_segment(&user.id),
+            tax_year,
+            sanitize_segment(&recipient.id),
+            statement.doc_type.as_str(),
+            Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+        );
+        state
+            .storage
+            .put(&bucket, &path, bytes.into(), PDF_CONTENT_TYPE, false)
+            .await?;
+        let insert = json!({
+            "talent_id": recipient.id,
+            "agency_id": user.id,
+            "doc_type": statement.doc_type.as_str(),
+            "tax_year": tax_year,
+            "storage_bucket": bucket,
+            "storage_path": path,
+            "totals": statement.totals,
+            "sha256": sha256,
+            "supersedes_id": previous.as_ref().map(|p| p.id.clone()),
+        });
+        let rows: Vec<TaxDocument> = fetch(state.pg.from(TABLE).insert(insert.to_string())).await?;
+        let mut document = rows
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::internal("tax_documents.insert", "no row returned"))?;
+
+        if payload.notify.unwrap_or(true)
+            && notify(
+                &state,
+                &user.id,
+                &branding.agency_name,
+                recipient,
+                &document,
+                &statement,
+            )
+            .await
+        {
+            let now = Utc::now().to_rfc3339();
+            let _: Vec<serde_json::Value> = fetch(
+                state
+                    .pg
+                    .from(TABLE)
+                    .eq("id", &document.id)
+                    .update(json!({ "notified_at": now }).to_string()),
+            )
+            .await?;
+            document.notified_at = Some(now);
+        }
+
+        audit::record(
+            &state,
+            AuditEvent::new(&user.id, "tax_document.generated", TABLE, &document.id)
+                .actor(&user)
+                .title(format!(
+                    "{} {} for {}",
+                    tax_year,
+                    statement.doc_type.title(),
+                    statement.recipient_name
+                ))
+                .change(&previous.map(|p| p.totals), &statement.totals),
+        )
+        .await;
+        summary.generated.push(document);
+    }
+    Ok(Json(summary))
+}
+
+/// GET /api/tax-documents?tax_year=: the agency's documents, newest first.
+pub async fn list(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(q): Query<ListQuery>,
+) -> AppResult<Json<Vec<TaxDocument>>> {
+    let mut req = state
+        .pg
+        .from(TABLE)
+        .select("*")
+        .eq("agency_id", &user.id)
+        .order("created_at.desc");
+    if let Some(year) = q.tax_year {
+        req = req.eq("tax_year", year.to_string());
+    }
+    Ok(Json(fetch(req).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(talent_id: &str, currency: &str, paid_ytd_cents: i64) -> TalentStatementLine {
+        TalentStatementLine {
+            talent_id: talent_id.into(),
+            talent_name: String::new(),
+            invoice_id: "inv-1".into(),
+            invoice_number: "INV-1".into(),
+            invoice_date: None,
+            client_name: String::new(),
+            description: String::new(),
+            gross_cents: paid_ytd_cents,
+            credited_cents: 0,
+            agency_fee_cents: 0,
+            net_cents: paid_ytd_cents,
+            paid_net_cents: paid_ytd_cents,
+            paid_ytd_cents,
+            currency: currency.into(),
+            status: "paid".into(),
+            paid_at: None,
+        }
+    }
+
+    #[test]
+    fn earnings_are_split_by_talent_currency_and_source() {
+        let licensing = vec![
+            LicensingPayoutRow {
+                talent_id: Some("t1".into()),
+                amount_cents: 10_000,
+                currency: Some("usd".into()),
+                talent_splits: Some(json!([])),
+            },
+            LicensingPayoutRow {
+                talent_id: None,
+                amount_cents: 99_999,
+                currency: Some("USD".into()),
+                talent_splits: Some(json!([
+                    { "talent_id": "t1", "amount_cents": 2_500 },
+                    { "talent_id": "t2", "amount_cents": 7_500 },
+                ])),
+            },
+        ];
+        let irl = vec![IrlPaymentRow {
+            talent_id: "t2".into(),
+            amount_cents: 5_000,
+            currency: Some("EUR".into()),
+        }];
+        let lines = vec![line("t1", "USD", 40_000), line("t2", "USD", 0)];
+
+        let earnings = aggregate(&licensing, &irl, &lines);
+        let t1 = &earnings["t1"]["USD"];
+        assert_eq!(
+            (t1.licensing_cents, t1.invoice_cents, t1.total_cents),
+            (12_500, 40_000, 52_500)
+        );
+        assert_eq!(earnings["t2"]["USD"].total_cents, 7_500);
+        assert_eq!(earnings["t2"]["EUR"].irl_cents, 5_000);
+        assert_eq!(earnings.len(), 2);
+    }
+
+    #[test]
+    fn us_talent_get_the_1099_summary() {
+        assert_eq!(DocType::for_country(Some("USA")), DocType::Form1099Nec);
+        assert_eq!(DocType::for_country(None), DocType::Form1099Nec);
+        assert_eq!(
+            DocType::for_country(Some("Germany")),
+            DocType::EarningsStatement
+        );
+        assert_eq!(nec_threshold_cents(2025), 60_000);
+        assert_eq!(nec_threshold_cents(2026), 200_000);
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{auth::AuthUser, config::AppState};
+use crate::{
+    auth::AuthUser,
+    config::AppState,
+    storage::{Bucket, StorageError},
+};
 use axum::{
     body::Bytes,
     extract::{Path, Query, State},
@@ -70,60 +74,30 @@ pub async fn upload_voice_recording(
         return Err((StatusCode::BAD_REQUEST, "empty body".into()));
     }
 
-    let ct = headers
-        .get("content-type")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("audio/webm")
-        .to_string();
-
-    // Private bucket
-    let bucket = state.supabase_bucket_private.clone();
-    let owner = user.id.replace(
-        |c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '-',
-        "_",
+    let ct = crate::storage::sniff_content_type(
+        &body,
+        headers.get("content-type").and_then(|v| v.to_str().ok()),
+        None,
     );
-    let ext = if ct.contains("wav") {
-        "wav"
-    } else if ct.contains("ogg") {
-        "ogg"
-    } else if ct.contains("mp4") || ct.contains("m4a") {
-        "mp4"
+    let ct = if ct == "application/octet-stream" {
+        "audio/webm".to_string()
     } else {
-        "webm"
+        ct
     };
 
+    // Private bucket
+    let bucket = state.bucket(Bucket::Private);
     let path = format!(
         "likeness/{}/voice/recordings/{}.{}",
-        owner,
+        crate::storage::sanitize_segment(&user.id),
         chrono::Utc::now().timestamp_millis(),
-        ext
+        match crate::storage::extension_for(&ct) {
+            "bin" => "webm",
+            ext => ext,
+        }
     );
 
-    // Upload to Storage
-    let storage_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::new();
-    let up = http
-        .post(&storage_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .header("content-type", ct.clone())
-        .body(body)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    if !up.status().is_success() {
-        let msg = up.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("storage upload failed: {msg}"),
-        ));
-    }
+    state.storage.put(&bucket, &path, body, &ct, false).await?;
 
     // Persist row
     let payload = serde_json::json!({
@@ -154,7 +128,7 @@ pub async fn upload_voice_recording(
 
     Ok(Json(UploadVoiceResponse {
         id: rec_id,
-        storage_bucket: state.supabase_bucket_private.clone(),
+        storage_bucket: bucket,
         storage_path: path,
     }))
 }
@@ -307,34 +281,13 @@ pub async fn signed_url_for_recording(
         return Err((StatusCode::NOT_FOUND, "recording not found".into()));
     }
 
-    // Request signed URL via Storage API
-    let url = format!(
-        "{}/storage/v1/object/sign/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let body = serde_json::json!({ "expiresIn": q.expires_sec });
-    let http = reqwest::Client::new();
-    let sign = http
-        .post(&url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .json(&body)
-        .send()
+    let full = match state
+        .storage
+        .signed_url(bucket, path, q.expires_sec.max(1) as u64)
         .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    if !sign.status().is_success() {
-        let msg = sign.text().await.unwrap_or_default();
-
-        let is_object_not_found = msg.contains("Object not found")
-            || msg.contains("\"error\":\"not_found\"")
-            || msg.contains("\"error\": \"not_found\"")
-            || msg.contains("\"statusCode\":404")
-            || msg.contains("\"statusCode\": 404");
-
-        if is_object_not_found {
+    {
+        Ok(url) => url,
+        Err(StorageError::NotFound) => {
             let _ = state
                 .pg
                 .from("voice_recordings")
@@ -346,18 +299,8 @@ pub async fn signed_url_for_recording(
 
             return Err((StatusCode::NOT_FOUND, "recording not found".into()));
         }
-
-        return Err((StatusCode::BAD_GATEWAY, format!("sign url failed: {msg}")));
-    }
-    let signed_json: serde_json::Value = sign
-        .json()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    let signed_path = signed_json
-        .get("signedURL")
-        .and_then(|v| v.as_str())
-        .ok_or((StatusCode::BAD_GATEWAY, "invalid sign response".into()))?;
-    let full = format!("{}/storage/v1{}", state.supabase_url, signed_path);
+        Err(e) => return Err(e.into()),
+    };
     Ok(Json(SignedUrlOut { url: full }))
 }
 
@@ -425,33 +368,8 @@ pub async fn create_clone_from_recording(
         .and_then(|v| v.as_str())
         .unwrap_or("audio/webm");
 
-    // 2) Download audio bytes from private storage using service key
-    let get_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::new();
-    let audio_resp = http
-        .get(&get_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
-    if !audio_resp.status().is_success() {
-        let msg = audio_resp.text().await.unwrap_or_default();
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("failed to download recording: {msg}"),
-        ));
-    }
-    let bytes = audio_resp
-        .bytes()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+    // 2) Download audio bytes from private storage
+    let bytes = state.storage.get(bucket, path).await?;
 
     // 3) POST to ElevenLabs voices/add
     let form = reqwest::multipart::Form::new()
@@ -467,7 +385,10 @@ pub async fn create_clone_from_recording(
 
     let el_http = reqwest::Client::new();
     let el_resp = el_http
-        .post("https://api.elevenlabs.io/v1/voices/add")
+        .post(format!(
+            "{}/v1/voices/add",
+            state.elevenlabs_base_url.trim_end_matches('/')
+        ))
         .header("xi-api-key", state.elevenlabs_api_key.clone())
         .multipart(form)
         .send()
@@ -675,20 +596,9 @@ pub async fn delete_voice_recording(
         .ok_or((StatusCode::NOT_FOUND, "recording not found".into()))?;
 
     // Delete storage object (best-effort)
-    let del_url = format!(
-        "{}/storage/v1/object/{}/{}",
-        state.supabase_url, bucket, path
-    );
-    let http = reqwest::Client::new();
-    let _ = http
-        .delete(&del_url)
-        .header(
-            "Authorization",
-            format!("Bearer {}", state.supabase_service_key),
-        )
-        .header("apikey", state.supabase_service_key.clone())
-        .send()
-        .await;
+    if let Err(e) = state.storage.delete(bucket, path).await {
+        tracing::warn!(error = %e, bucket, path, "voice recording storage delete failed");
+    }
 
     // Delete brand asset references (best-effort)
     let _ = state
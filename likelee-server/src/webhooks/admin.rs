@@ -0,0 +1,57 @@
+//! Admin endpoints for inspecting and replaying webhook events.
+
+use super::{Outcome, WebhookEvent};
+use crate::auth::{AuthUser, RoleGuard};
+use crate::config::AppState;
+use crate::errors::AppResult;
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::Deserialize;
+use tracing::info;
+
+#[derive(Debug, Deserialize)]
+pub struct EventListParams {
+    pub provider: Option<String>,
+    pub status: Option<String>,
+    pub event_type: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+pub async fn list_events(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<EventListParams>,
+) -> AppResult<Json<Vec<WebhookEvent>>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    let events = super::list(
+        &state,
+        params.provider.as_deref(),
+        params.status.as_deref(),
+        params.event_type.as_deref(),
+        params.limit.unwrap_or(50).min(200),
+        params.offset.unwrap_or(0),
+    )
+    .await?;
+    Ok(Json(events))
+}
+
+/// Runs a `failed` event through its handler again and returns the updated
+/// event; 409 `webhook_event_not_failed` for any other status.
+pub async fn replay_event(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<WebhookEvent>> {
+    RoleGuard::new(vec!["admin"]).check(&user.role)?;
+    info!(event = %id, replayed_by = %user.id, "replaying webhook event");
+    let event = match super::replay(&state, &id).await? {
+        Outcome::Processed(event)
+        | Outcome::Failed(event, _)
+        | Outcome::Duplicate(event)
+        | Outcome::InFlight(event) => event,
+    };
+    Ok(Json(event))
+}
@@ -0,0 +1,370 @@
+//! DocuSeal contract webhooks.
+//!
+//! Scouting offers and license submissions are both signed through DocuSeal,
+//! which posts every submission event to the same webhook. A delivery must
+//! carry the secret configured on the DocuSeal webhook, either verbatim in
+//! [`SECRET_HEADER`] or as an HMAC-SHA256 of the body in
+//! [`SIGNATURE_HEADER`]. Verified events are routed to whichever record owns
+//! the submission.
+
+use crate::config::AppState;
+use crate::errors::{AppError, AppResult};
+use crate::kyc::{compute_hmac_hex, constant_time_eq};
+use axum::{body::Bytes, extract::State, http::HeaderMap, http::StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{info, warn};
+
+/// Header carrying the shared secret as configured on the DocuSeal webhook.
+pub const SECRET_HEADER: &str = "x-docuseal-secret";
+/// Header carrying the hex HMAC-SHA256 of the raw body, keyed by the secret.
+pub const SIGNATURE_HEADER: &str = "x-docuseal-signature";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DocuSealWebhookEvent {
+    pub event_type: String,
+    pub timestamp: String,
+    pub data: Value,
+}
+
+impl DocuSealWebhookEvent {
+    /// `form.*` events describe a submitter and carry `submission_id`;
+    /// `submission.*` events describe the submission itself.
+    pub fn submission_id(&self) -> Option<i64> {
+        self.data["submission_id"]
+            .as_i64()
+            .or_else(|| self.data["id"].as_i64())
+    }
+
+    /// DocuSeal events have no id of their own; the event type, the submission
+    /// or submitter id and the event timestamp identify one.
+    pub fn event_id(&self) -> String {
+        let subject = self
+            .data
+            .get("id")
+            .or_else(|| self.data.get("submission_id"))
+            .map(|v| match v {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .unwrap_or_default();
+        format!("{}:{subject}:{}", self.event_type, self.timestamp)
+    }
+
+    /// URL of the first signed document, present on completion events.
+    pub fn document_url(&self) -> Option<&str> {
+        self.data["documents"]
+            .as_array()
+            .and_then(|docs| docs.first())
+            .and_then(|doc| doc["url"].as_str())
+    }
+
+    pub fn decline_reason(&self) -> Option<&str> {
+        self.data.get("decline_reason").and_then(|v| v.as_str())
+    }
+
+    /// Whether the submitter behind a `form.*` event is the agency party,
+    /// by role or by the submitter id stored when the submission was created.
+    pub fn is_agency_signer(&self, agency_submitter_id: Option<i64>) -> bool {
+        let submitter_id = self.data["submitter_id"].as_i64().or_else(|| {
+            self.data
+                .get("submitter")
+                .and_then(|s| s.get("id"))
+                .and_then(|v| v.as_i64())
+        });
+        let role = self
+            .data
+            .get("submitter")
+            .and_then(|s| s.get("role"))
+            .or_else(|| self.data.get("role"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_lowercase());
+        matches!(role.as_deref(), Some("agency" | "first party"))
+            || (agency_submitter_id.is_some() && submitter_id == agency_submitter_id)
+    }
+}
+
+/// Where a DocuSeal event leaves the submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Started or viewed by a submitter.
+    Opened,
+    /// One submitter finished; others may still have to sign.
+    FormCompleted,
+    /// Every submitter has signed.
+    Completed,
+    Declined,
+}
+
+impl Stage {
+    /// `None` for events that do not change contract state.
+    pub fn from_event_type(event_type: &str) -> Option<Self> {
+        match event_type {
+            "submission.started" | "submission.opened" | "submission.viewed" | "form.started"
+            | "form.viewed" => Some(Stage::Opened),
+            "form.completed" => Some(Stage::FormCompleted),
+            "submission.completed" => Some(Stage::Completed),
+            "submission.declined" | "form.declined" => Some(Stage::Declined),
+            _ => None,
+        }
+    }
+}
+
+/// Scouting offers have a single signer, so a finished form completes them.
+pub fn offer_status(stage: Stage) -> &'static str {
+    match stage {
+        Stage::Opened => "opened",
+        Stage::FormCompleted | Stage::Completed => "completed",
+        Stage::Declined => "declined",
+    }
+}
+
+/// Signing state of a license submission before the event is applied.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LicenseSigning {
+    pub requires_agency_signature: bool,
+    pub agency_signed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LicenseTransition {
+    pub status: &'static str,
+    /// The event is the agency's signature; stamp `agency_signed_at`.
+    pub records_agency_signature: bool,
+}
+
+/// License submissions that need the agency's countersignature move through
+/// `agency_pending` and `client_pending` before `completed`.
+pub fn license_transition(
+    stage: Stage,
+    signing: LicenseSigning,
+    agency_signer: bool,
+) -> LicenseTransition {
+    let status = |status| LicenseTransition {
+        status,
+        records_agency_signature: false,
+    };
+    let LicenseSigning {
+        requires_agency_signature,
+        agency_signed,
+    } = signing;
+    match stage {
+        Stage::FormCompleted if requires_agency_signature && (agency_signer || !agency_signed) => {
+            LicenseTransition {
+                status: "client_pending",
+                records_agency_signature: true,
+            }
+        }
+        Stage::FormCompleted => status("opened"),
+        Stage::Completed => status("completed"),
+        Stage::Declined => status("declined"),
+        Stage::Opened if requires_agency_signature && !agency_signed => status("agency_pending"),
+        Stage::Opened if requires_agency_signature => status("client_pending"),
+        Stage::Opened => status("opened"),
+    }
+}
+
+/// Checks the delivery against `secret`. An unset secret rejects everything.
+pub fn verify(secret: &str, headers: &HeaderMap, body: &[u8]) -> AppResult<()> {
+    if secret.is_empty() {
+        warn!("DOCUSEAL_WEBHOOK_SECRET is not set; rejecting DocuSeal webhook");
+        return Err(AppError::Unauthorized(
+            "webhook_secret_not_configured".to_string(),
+        ));
+    }
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    if let Some(sig) = header(SIGNATURE_HEADER) {
+        let sig = sig.trim().trim_start_matches("sha256=");
+        if constant_time_eq(&compute_hmac_hex(secret, body), &sig.to_lowercase()) {
+            return Ok(());
+        }
+    } else if let Some(provided) = header(SECRET_HEADER) {
+        if constant_time_eq(secret, provided.trim()) {
+            return Ok(());
+        }
+    }
+    warn!("Invalid DocuSeal webhook signature");
+    Err(AppError::Unauthorized("invalid_signature".to_string()))
+}
+
+/// POST /webhooks/docuseal and /api/webhooks/licenseContract
+pub async fn handle_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<StatusCode> {
+    verify(&state.docuseal_webhook_secret, &headers, &body)?;
+    let payload: Value = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("invalid webhook payload: {e}")))?;
+    let event: DocuSealWebhookEvent = serde_json::from_value(payload.clone())
+        .map_err(|e| AppError::BadRequest(format!("invalid webhook payload: {e}")))?;
+    info!(
+        event_type = %event.event_type,
+        submission_id = ?event.submission_id(),
+        "Received DocuSeal webhook"
+    );
+    super::ingest(
+        &state,
+        super::DOCUSEAL,
+        &event.event_id(),
+        &event.event_type,
+        payload,
+    )
+    .await?
+    .into_result()
+}
+
+/// Routes a verified event to the scouting offer or license submission that
+/// owns its submission.
+pub(crate) async fn process_event(state: &AppState, payload: &Value) -> Result<(), String> {
+    let event: DocuSealWebhookEvent =
+        serde_json::from_value(payload.clone()).map_err(|e| e.to_string())?;
+    let Some(stage) = Stage::from_event_type(&event.event_type) else {
+        return Ok(());
+    };
+    let submission_id = event
+        .submission_id()
+        .ok_or_else(|| "missing submission id".to_string())?;
+
+    if crate::scouting::apply_docuseal_event(state, &event, stage, submission_id).await? {
+        return Ok(());
+    }
+    if crate::license_submissions::apply_docuseal_event(state, &event, stage, submission_id).await?
+    {
+        return Ok(());
+    }
+    info!(
+        submission_id,
+        "DocuSeal event matches no scouting offer or license submission"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(event_type: &str, data: Value) -> DocuSealWebhookEvent {
+        DocuSealWebhookEvent {
+            event_type: event_type.to_string(),
+            timestamp: "2026-10-18T09:00:00Z".to_string(),
+            data,
+        }
+    }
+
+    #[test]
+    fn maps_events_to_stages() {
+        assert_eq!(Stage::from_event_type("form.viewed"), Some(Stage::Opened));
+        assert_eq!(
+            Stage::from_event_type("submission.started"),
+            Some(Stage::Opened)
+        );
+        assert_eq!(
+            Stage::from_event_type("form.completed"),
+            Some(Stage::FormCompleted)
+        );
+        assert_eq!(
+            Stage::from_event_type("submission.completed"),
+            Some(Stage::Completed)
+        );
+        assert_eq!(
+            Stage::from_event_type("form.declined"),
+            Some(Stage::Declined)
+        );
+        assert_eq!(Stage::from_event_type("template.created"), None);
+    }
+
+    #[test]
+    fn maps_stages_to_offer_status() {
+        assert_eq!(offer_status(Stage::Opened), "opened");
+        assert_eq!(offer_status(Stage::FormCompleted), "completed");
+        assert_eq!(offer_status(Stage::Completed), "completed");
+        assert_eq!(offer_status(Stage::Declined), "declined");
+    }
+
+    #[test]
+    fn license_waits_for_agency_then_client() {
+        let unsigned = LicenseSigning {
+            requires_agency_signature: true,
+            agency_signed: false,
+        };
+        let countersigned = LicenseSigning {
+            requires_agency_signature: true,
+            agency_signed: true,
+        };
+        assert_eq!(
+            license_transition(Stage::Opened, unsigned, false).status,
+            "agency_pending"
+        );
+        assert_eq!(
+            license_transition(Stage::FormCompleted, unsigned, true),
+            LicenseTransition {
+                status: "client_pending",
+                records_agency_signature: true,
+            }
+        );
+        assert_eq!(
+            license_transition(Stage::Opened, countersigned, false).status,
+            "client_pending"
+        );
+        assert_eq!(
+            license_transition(Stage::FormCompleted, countersigned, false),
+            LicenseTransition {
+                status: "opened",
+                records_agency_signature: false,
+            }
+        );
+        assert_eq!(
+            license_transition(Stage::Completed, countersigned, false).status,
+            "completed"
+        );
+        let single = LicenseSigning::default();
+        assert_eq!(
+            license_transition(Stage::Opened, single, false).status,
+            "opened"
+        );
+        assert_eq!(
+            license_transition(Stage::Declined, single, false).status,
+            "declined"
+        );
+    }
+
+    #[test]
+    fn identifies_submission_and_agency_signer() {
+        let form = event(
+            "form.completed",
+            json!({ "id": 9, "submission_id": 77, "submitter": { "id": 9, "role": "Client" } }),
+        );
+        assert_eq!(form.submission_id(), Some(77));
+        assert!(!form.is_agency_signer(None));
+        assert!(form.is_agency_signer(Some(9)));
+        let agency = event(
+            "form.completed",
+            json!({ "id": 3, "submission_id": 77, "role": "First Party" }),
+        );
+        assert!(agency.is_agency_signer(None));
+        assert_eq!(
+            event("submission.completed", json!({ "id": 77 })).submission_id(),
+            Some(77)
+        );
+    }
+
+    #[test]
+    fn verifies_secret_header_or_body_hmac() {
+        let body = br#"{"event_type":"form.viewed"}"#;
+        let mut headers = HeaderMap::new();
+        assert!(verify("s3cret", &headers, body).is_err());
+
+        headers.insert(SECRET_HEADER, "s3cret".parse().unwrap());
+        assert!(verify("s3cret", &headers, body).is_ok());
+        assert!(verify("", &headers, body).is_err());
+        assert!(verify("other", &headers, body).is_err());
+
+        let mut headers = HeaderMap::new();
+        let sig = compute_hmac_hex("s3cret", body);
+        headers.insert(SIGNATURE_HEADER, format!("sha256={sig}").parse().unwrap());
+        assert!(verify("s3cret", &headers, body).is_ok());
+        assert!(verify("s3cret", &headers, b"{}").is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! Idempotent webhook ingestion.
+//!
+//! Every verified delivery is recorded in `webhook_events` under the
+//! provider's event id before it is handled, and the row tracks whether
+//! handling succeeded (`received` → `processed` | `failed`). A redelivery of
+//! a processed event is acknowledged without running the handler again; a
+//! redelivery of a failed event retries it. Failed events can also be
+//! replayed by an admin through the same handlers.
+//!
+//! Handler errors are what trigger a retry, so every handler must be safe to
+//! run again after failing part way: they return errors only before their
+//! first write, or their writes are upserts and conditional updates.
+
+pub mod admin;
+pub mod docuseal;
+
+use crate::config::AppState;
+use crate::errors::{AppError, AppResult};
+use crate::jobs::runner::{parse_ts, ts};
+use crate::repositories::{fetch, RepoError};
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+pub const EVENTS_TABLE: &str = "webhook_events";
+
+pub const STRIPE: &str = "stripe";
+pub const VERIFF: &str = "veriff";
+pub const DOCUSEAL: &str = "docuseal";
+
+/// How long a `received` event may stay unfinished before a redelivery is
+/// allowed to take it over, e.g. after the replica handling it died.
+const STALE_SECS: i64 = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    pub id: String,
+    pub provider: String,
+    pub event_id: Option<String>,
+    pub event_type: String,
+    /// `received`, `processed` or `failed`.
+    pub status: String,
+    pub error: Option<String>,
+    /// Times the handler has been started for this event.
+    pub attempts: i32,
+    pub payload: Value,
+    pub last_attempt_at: Option<String>,
+    pub processed_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    /// The handler ran and succeeded.
+    Processed(WebhookEvent),
+    /// The handler ran and returned an error, now stored on the event.
+    Failed(WebhookEvent, String),
+    /// The event had already been processed; nothing was run.
+    Duplicate(WebhookEvent),
+    /// Another delivery of the event is being handled right now.
+    InFlight(WebhookEvent),
+}
+
+impl Outcome {
+    /// Status to answer the provider with. Failures and in-flight events get a
+    /// non-2xx response so the provider redelivers later.
+    pub fn into_result(self) -> AppResult<StatusCode> {
+        match self {
+            Outcome::Processed(_) | Outcome::Duplicate(_) => Ok(StatusCode::OK),
+            Outcome::InFlight(_) => Err(AppError::Conflict("webhook_in_progress".to_string())),
+            Outcome::Failed(event, e) => Err(AppError::internal(
+                &format!("webhook.{}", event.provider),
+                e,
+            )),
+        }
+    }
+}
+
+enum Claim {
+    Claimed(WebhookEvent),
+    Skipped(Outcome),
+}
+
+/// Fallback event id for providers that do not send one: redeliveries carry
+/// the identical body.
+pub fn body_digest(body: &[u8]) -> String {
+    hex::encode(Sha256::digest(body))
+}
+
+/// Records a verified delivery and runs its handler unless the event was
+/// already processed or is being processed elsewhere.
+pub async fn ingest(
+    state: &AppState,
+    provider: &str,
+    event_id: &str,
+    event_type: &str,
+    payload: Value,
+) -> Result<Outcome, RepoError> {
+    match claim(state, provider, event_id, event_type, payload).await? {
+        Claim::Claimed(event) => run(state, event).await,
+        Claim::Skipped(outcome) => {
+            if let Outcome::Duplicate(event) | Outcome::InFlight(event) = &outcome {
+                info!(
+                    provider,
+                    event_id,
+                    status = %event.status,
+                    "skipping duplicate webhook delivery"
+                );
+            }
+            Ok(outcome)
+        }
+    }
+}
+
+/// Re-runs a failed event through its provider's handler.
+pub async fn replay(state: &AppState, id: &str) -> AppResult<Outcome> {
+    let event = get(state, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("webhook_event_not_found".to_string()))?;
+    if event.status != "failed" {
+        return Err(AppError::Conflict("webhook_event_not_failed".to_string()));
+    }
+    match retake(state, event, Utc::now()).await? {
+        Claim::Claimed(event) => Ok(run(state, event).await?),
+        Claim::Skipped(_) => Err(AppError::Conflict("webhook_in_progress".to_string())),
+    }
+}
+
+pub async fn get(state: &AppState, id: &str) -> Result<Option<WebhookEvent>, RepoError> {
+    let rows: Vec<WebhookEvent> =
+        fetch(state.pg.from(EVENTS_TABLE).select("*").eq("id", id)).await?;
+    Ok(rows.into_iter().next())
+}
+
+pub async fn list(
+    state: &AppState,
+    provider: Option<&str>,
+    status: Option<&str>,
+    event_type: Option<&str>,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<WebhookEvent>, RepoError> {
+    let mut query = state
+        .pg
+        .from(EVENTS_TABLE)
+        .select("*")
+        .order("created_at.desc");
+    if let Some(provider) = provider {
+        query = query.eq("provider", provider);
+    }
+    if let Some(status) = status {
+        query = query.eq("status", status);
+    }
+    if let Some(event_type) = event_type {
+        query = query.eq("event_type", event_type);
+    }
+    fetch(query.range(offset, offset + limit.max(1) - 1)).await
+}
+
+async fn find(
+    state: &AppState,
+    provider: &str,
+    event_id: &str,
+) -> Result<Option<WebhookEvent>, RepoError> {
+    let rows: Vec<WebhookEvent> = fetch(
+        state
+            .pg
+            .from(EVENTS_TABLE)
+            .select("*")
+            .eq("provider", provider)
+            .eq("event_id", event_id),
+    )
+    .await?;
+    Ok(rows.into_iter().next())
+}
+
+/// Inserts the event as `received`, or decides what to do with an existing
+/// row for the same `(provider, event_id)`.
+async fn claim(
+    state: &AppState,
+    provider: &str,
+    event_id: &str,
+    event_type: &str,
+    payload: Value,
+) -> Result<Claim, RepoError> {
+    let now = Utc::now();
+    if let Some(existing) = find(state, provider, event_id).await? {
+        return retake(state, existing, now).await;
+    }
+    let row = json!({
+        "provider": provider,
+        "event_id": event_id,
+        "event_type": event_type,
+        "payload": payload,
+        "status": "received",
+        "attempts": 1,
+        "last_attempt_at": ts(now),
+    });
+    let inserted: Result<Vec<WebhookEvent>, RepoError> =
+        fetch(state.pg.from(EVENTS_TABLE).insert(row.to_string())).await;
+    match inserted {
+        Ok(rows) => rows
+            .into_iter()
+            .next()
+            .map(Claim::Claimed)
+            .ok_or_else(|| RepoError::Decode("insert returned no event".to_string())),
+        // A concurrent delivery of the same event won the unique index.
+        Err(RepoError::Db { status: 409, .. }) => match find(state, provider, event_id).await? {
+            Some(existing) => retake(state, existing, now).await,
+            None => Err(RepoError::NotFound),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// Takes over a failed or stale event with a PATCH conditioned on the state
+/// that was read, so only one delivery or replay wins.
+async fn retake(
+    state: &AppState,
+    existing: WebhookEvent,
+    now: DateTime<Utc>,
+) -> Result<Claim, RepoError> {
+    let stale_before = now - Duration::seconds(STALE_SECS);
+    let mut query = state
+        .pg
+        .from(EVENTS_TABLE)
+        .eq("id", &existing.id)
+        .eq("status", &existing.status);
+    let in_flight = existing
+        .last_attempt_at
+        .as_deref()
+        .and_then(parse_ts)
+        .is_some_and(|t| t >= stale_before);
+    match existing.status.as_str() {
+        "processed" => return Ok(Claim::Skipped(Outcome::Duplicate(existing))),
+        "received" if in_flight => return Ok(Claim::Skipped(Outcome::InFlight(existing))),
+        "received" => {
+            query = query.or(format!(
+                "last_attempt_at.is.null,last_attempt_at.lt.{}",
+                ts(stale_before)
+            ))
+        }
+        _ => {}
+    }
+    let patch = json!({
+        "status": "received",
+        "attempts": existing.attempts + 1,
+        "last_attempt_at": ts(now),
+    });
+    let rows: Vec<WebhookEvent> = fetch(query.update(patch.to_string())).await?;
+    Ok(match rows.into_iter().next() {
+        Some(event) => Claim::Claimed(event),
+        None => Claim::Skipped(Outcome::InFlight(existing)),
+    })
+}
+
+/// Runs the provider handler for a claimed event and stores the result.
+async fn run(state: &AppState, event: WebhookEvent) -> Result<Outcome, RepoError> {
+    // Run on its own task so a panicking handler is recorded as a failure.
+    let result = {
+        let (state, provider, payload) =
+            (state.clone(), event.provider.clone(), event.payload.clone());
+        tokio::spawn(async move { dispatch(&state, &provider, &payload).await })
+            .await
+            .unwrap_or_else(|e| Err(format!("handler panicked: {e}")))
+    };
+
+    let patch = match &result {
+        Ok(()) => json!({
+            "status": "processed",
+            "error": null,
+            "processed_at": ts(Utc::now()),
+        }),
+        Err(e) => {
+            warn!(
+                provider = %event.provider,
+                event_id = ?event.event_id,
+                attempt = event.attempts,
+                error = %e,
+                "webhook handler failed"
+            );
+            json!({ "status": "failed", "error": e })
+        }
+    };
+    let rows: Vec<WebhookEvent> = fetch(
+        state
+            .pg
+            .from(EVENTS_TABLE)
+            .eq("id", &event.id)
+            .update(patch.to_string()),
+    )
+    .await?;
+    let event = rows.into_iter().next().unwrap_or(event);
+    Ok(match result {
+        Ok(()) => Outcome::Processed(event),
+        Err(e) => Outcome::Failed(event, e),
+    })
+}
+
+async fn dispatch(state: &AppState, provider: &str, payload: &Value) -> Result<(), String> {
+    match provider {
+        STRIPE => crate::payouts::process_stripe_event(state, payload).await,
+        VERIFF => crate::kyc::process_veriff_event(state, payload).await,
+        DOCUSEAL => docuseal::process_event(state, payload).await,
+        other => Err(format!("no handler for webhook provider {other}")),
+    }
+}
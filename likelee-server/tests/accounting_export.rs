@@ -0,0 +1,188 @@
+mod common;
+
+use axum::http::Method;
+use chrono::{Duration, NaiveDate, Utc};
+use common::{fixtures::seed_agency, TestApp, TestUser};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+fn seed_expense(app: &TestApp, agency: &TestUser, name: &str, date: NaiveDate, status: &str) {
+    app.supabase.seed(
+        "agency_expenses",
+        json!({
+            "agency_id": agency.id,
+            "name": name,
+            "category": "Travel",
+            "expense_date": date.to_string(),
+            "amount_cents": 4500,
+            "currency": "USD",
+            "status": status,
+            "submitter": null,
+        }),
+    );
+}
+
+async fn export(app: &TestApp, agency: &TestUser, query: &str) -> (u16, String) {
+    let resp = app
+        .request(
+            Method::GET,
+            &format!("/api/accounting/export?{query}"),
+            agency,
+        )
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status().as_u16();
+    (status, resp.text().await.unwrap())
+}
+
+async fn put_accounts(app: &TestApp, agency: &TestUser, accounts: Value) -> (u16, Value) {
+    let resp = app
+        .request(Method::PUT, "/api/accounting/accounts", agency)
+        .json(&accounts)
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status().as_u16();
+    (status, resp.json().await.unwrap_or(Value::Null))
+}
+
+/// Debits and credits per entry number of a journal CSV.
+fn entry_totals(journal: &str) -> HashMap<String, (i64, i64)> {
+    let cents = |s: &str| {
+        if s.is_empty() {
+            0
+        } else {
+            (s.parse::<f64>().unwrap() * 100.0).round() as i64
+        }
+    };
+    let mut totals: HashMap<String, (i64, i64)> = HashMap::new();
+    for row in journal.lines().skip(1) {
+        // Memos are quoted when they contain commas; the amounts are last.
+        let fields: Vec<&str> = row.rsplitn(4, ',').collect();
+        let entry = row.split(',').nth(1).unwrap().to_string();
+        let total = totals.entry(entry).or_default();
+        total.0 += cents(fields[2]);
+        total.1 += cents(fields[1]);
+    }
+    totals
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn closed_periods_export_the_same_balanced_journal() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+    let day = today() - Duration::days(10);
+    let (from, to) = (today() - Duration::days(14), today() - Duration::days(1));
+
+    let (status, invoice) = app
+        .post(
+            "/api/invoices",
+            &agency,
+            json!({
+                "client_id": client_id,
+                "invoice_date": day.to_string(),
+                "agency_commission_bps": 2000,
+                "tax_rate_bps": 1000,
+                "discount_cents": 1000,
+                "items": [{ "description": "Shoot day", "unit_price_cents": 100000 }],
+                "expenses": [{ "description": "Travel", "amount_cents": 5000 }],
+            }),
+        )
+        .await;
+    assert_eq!(status, 200, "{invoice}");
+    let id = invoice["id"].as_str().unwrap();
+    app.post(&format!("/api/invoices/{id}/mark-sent"), &agency, json!({}))
+        .await;
+    let (status, body) = app
+        .post(
+            &format!("/api/invoices/{id}/payments"),
+            &agency,
+            json!({ "amount_cents": 40000, "paid_on": day.to_string(), "method": "check" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    seed_expense(&app, &agency, "Flights", day, "approved");
+    seed_expense(&app, &agency, "Taxi", day, "pending");
+    app.supabase.seed(
+        "agency_payout_requests",
+        json!({
+            "agency_id": agency.id,
+            "amount_cents": 100000,
+            "currency": "USD",
+            "payout_method": "standard",
+            "status": "paid",
+            "requested_at": format!("{day}T09:00:00Z"),
+            "processed_at": format!("{day}T12:00:00Z"),
+            "stripe_transfer_id": "tr_1",
+            "stripe_payout_id": "po_1",
+            "failure_reason": null,
+        }),
+    );
+
+    let (status, body) = put_accounts(&app, &agency, json!({ "bank": " " })).await;
+    assert_eq!(
+        (status, body["code"].as_str()),
+        (400, Some("invalid_account"))
+    );
+    let (status, accounts) = put_accounts(
+        &app,
+        &agency,
+        json!({ "bank": "1000 Bank", "expense_categories": { "Travel": "Travel Expense" } }),
+    )
+    .await;
+    assert_eq!(status, 200, "{accounts}");
+    assert_eq!(accounts["commission_income"], "Commission Income");
+
+    let period = format!("from={from}&to={to}");
+    let (status, journal) = export(&app, &agency, &format!("format=journal&{period}")).await;
+    assert_eq!(status, 200, "{journal}");
+    let totals = entry_totals(&journal);
+    // Invoice, payment, expense and payout.
+    assert_eq!(totals.len(), 4, "{journal}");
+    assert!(totals.values().all(|(dr, cr)| dr == cr), "{journal}");
+    assert!(journal.contains(",Travel Expense,"));
+    assert!(journal.contains(",Payout Fees,"));
+    assert!(!journal.contains("Taxi"));
+    assert!(journal.contains(&format!("{day},2,payment,INVCA0000001,1000 Bank,")));
+
+    // Later edits do not change the closed period's file.
+    put_accounts(&app, &agency, json!({ "bank": "2000 Bank" })).await;
+    seed_expense(&app, &agency, "Hotel", day, "approved");
+    let (_, again) = export(&app, &agency, &format!("format=journal&{period}")).await;
+    assert_eq!(again, journal);
+    assert_eq!(app.supabase.rows("agency_accounting_exports").len(), 1);
+
+    let (status, iif) = export(&app, &agency, &format!("format=iif&{period}")).await;
+    assert_eq!(status, 200);
+    assert!(iif.starts_with("!TRNS\t"));
+    assert!(iif.contains("\t1000 Bank\t"));
+    assert_eq!(iif.matches("ENDTRNS\r\n").count(), 5);
+    let (status, xero) = export(&app, &agency, &format!("format=xero&{period}")).await;
+    assert_eq!(status, 200);
+    assert!(xero.starts_with("*Narration,*Date,Description,*AccountCode,*TaxRate,*Amount"));
+
+    // The open period is built live with the current mapping.
+    let (status, live) = export(&app, &agency, &format!("from={from}&to={}", today())).await;
+    assert_eq!(status, 200);
+    assert!(live.contains("2000 Bank"));
+    assert!(live.contains("Hotel"));
+
+    let (status, _) = export(&app, &agency, "format=pdf").await;
+    assert_eq!(status, 400);
+    let (status, _) = export(&app, &agency, &format!("from={to}&to={from}")).await;
+    assert_eq!(status, 400);
+
+    let (_, other) = export(
+        &app,
+        &TestUser::agency(),
+        &format!("format=journal&{period}"),
+    )
+    .await;
+    assert_eq!(other.lines().count(), 1);
+}
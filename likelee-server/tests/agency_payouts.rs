@@ -0,0 +1,382 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use chrono::{Duration, SecondsFormat, Utc};
+use common::{stripe, TestApp, TestUser};
+use likelee_server::jobs;
+use serde_json::{json, Value};
+
+fn ts(offset: Duration) -> String {
+    (Utc::now() + offset).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn payout(id: &str, amount: i64) -> Value {
+    let now = Utc::now().timestamp();
+    json!({
+        "id": id,
+        "object": "payout",
+        "amount": amount,
+        "arrival_date": now,
+        "automatic": false,
+        "created": now,
+        "currency": "usd",
+        "livemode": false,
+        "method": "instant",
+        "reconciliation_status": "not_applicable",
+        "source_type": "card",
+        "status": "pending",
+        "type": "bank_account",
+    })
+}
+
+/// An agency with a weekly schedule, a connected account and `earned` cents
+/// of licensing payouts since its last payout.
+fn seed_agency(app: &TestApp, agency: &TestUser, earned: i64, last_payout_at: Option<String>) {
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "stripe_connect_account_id": "acct_agency" }),
+    );
+    app.supabase.seed(
+        "agency_payout_settings",
+        json!({
+            "agency_id": agency.id,
+            "payout_frequency": "Weekly",
+            "min_payout_threshold_cents": 5000,
+            "last_payout_at": last_payout_at,
+        }),
+    );
+    app.supabase.seed(
+        "agency_balances",
+        json!({ "agency_id": agency.id, "available_cents": 50_000 }),
+    );
+    app.supabase.seed(
+        "licensing_payouts",
+        json!({ "agency_id": agency.id, "amount_cents": earned, "paid_at": ts(Duration::hours(-2)) }),
+    );
+}
+
+async fn run_scheduler(app: &TestApp) -> Value {
+    jobs::find("agency_payout_scheduler")
+        .unwrap()
+        .run(&app.state)
+        .await
+        .unwrap()
+}
+
+fn rows_for(app: &TestApp, table: &str, agency: &TestUser) -> Vec<Value> {
+    app.supabase
+        .rows(table)
+        .into_iter()
+        .filter(|r| r["agency_id"] == agency.id.as_str())
+        .map(Value::Object)
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn due_payout_is_executed_once_and_moves_the_schedule() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let small = TestUser::agency();
+    seed_agency(&app, &agency, 12_000, None);
+    seed_agency(&app, &small, 3_000, None);
+    app.stripe.on(
+        Method::POST,
+        "/v1/payouts",
+        StatusCode::OK,
+        payout("po_1", 11_880),
+    );
+
+    let output = run_scheduler(&app).await;
+    assert_eq!(output["executed"], 1, "{output}");
+    assert_eq!(output["skipped"], 1, "{output}");
+
+    let requests = rows_for(&app, "agency_payout_requests", &agency);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["status"], "paid");
+    assert_eq!(requests[0]["source"], "scheduled");
+    assert_eq!(requests[0]["schedule_period"], "initial");
+    assert_eq!(requests[0]["amount_cents"], 12_000);
+    assert_eq!(requests[0]["stripe_payout_id"], "po_1");
+
+    let calls = app.stripe.calls_to(Method::POST, "/v1/payouts");
+    assert_eq!(calls.len(), 1);
+    let key = format!("agency-payout-{}", requests[0]["id"].as_str().unwrap());
+    assert_eq!(calls[0].headers["idempotency-key"], key.as_str());
+
+    let settings = rows_for(&app, "agency_payout_settings", &agency);
+    assert!(settings[0]["last_payout_at"].is_string());
+    let runs = rows_for(&app, "agency_payout_schedule_runs", &agency);
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0]["outcome"], "executed");
+    assert_eq!(runs[0]["payout_request_id"], requests[0]["id"]);
+
+    let skipped = rows_for(&app, "agency_payout_schedule_runs", &small);
+    assert_eq!(skipped[0]["outcome"], "skipped");
+    assert_eq!(skipped[0]["reason"], "below_threshold");
+    assert!(rows_for(&app, "agency_payout_requests", &small).is_empty());
+
+    // Not due again until next week.
+    run_scheduler(&app).await;
+    assert_eq!(app.stripe.calls_to(Method::POST, "/v1/payouts").len(), 1);
+    assert_eq!(rows_for(&app, "agency_payout_requests", &agency).len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_payouts_are_recorded_and_retried_with_a_new_attempt() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let last = ts(Duration::days(-8));
+    seed_agency(&app, &agency, 9_000, Some(last.clone()));
+
+    // No Stripe mock for /v1/payouts: the payout call fails.
+    let output = run_scheduler(&app).await;
+    assert_eq!(output["failed"], 1, "{output}");
+    let requests = rows_for(&app, "agency_payout_requests", &agency);
+    assert_eq!(requests[0]["status"], "failed");
+    assert_eq!(requests[0]["schedule_period"], last.as_str());
+    let runs = rows_for(&app, "agency_payout_schedule_runs", &agency);
+    assert_eq!(runs[0]["outcome"], "failed");
+    assert!(runs[0]["reason"].as_str().is_some_and(|r| !r.is_empty()));
+    assert_eq!(
+        rows_for(&app, "agency_payout_settings", &agency)[0]["last_payout_at"],
+        last.as_str()
+    );
+
+    app.stripe.on(
+        Method::POST,
+        "/v1/payouts",
+        StatusCode::OK,
+        payout("po_2", 8_910),
+    );
+    let output = run_scheduler(&app).await;
+    assert_eq!(output["executed"], 1, "{output}");
+    let mut requests = rows_for(&app, "agency_payout_requests", &agency);
+    requests.sort_by_key(|r| r["schedule_attempt"].as_i64());
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1]["schedule_attempt"], 2);
+    assert_eq!(requests[1]["status"], "paid");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn interrupted_payout_is_resumed_instead_of_duplicated() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    seed_agency(&app, &agency, 12_000, None);
+    // A previous run created the request but stopped before paying it.
+    app.supabase.seed(
+        "agency_payout_requests",
+        json!({
+            "id": "apr-1",
+            "agency_id": agency.id,
+            "amount_cents": 12_000,
+            "currency": "USD",
+            "payout_method": "instant",
+            "status": "processing",
+            "source": "scheduled",
+            "schedule_period": "initial",
+            "schedule_attempt": 1,
+        }),
+    );
+    app.stripe.on(
+        Method::POST,
+        "/v1/payouts",
+        StatusCode::OK,
+        payout("po_3", 11_880),
+    );
+
+    let output = run_scheduler(&app).await;
+    assert_eq!(output["executed"], 1, "{output}");
+    let requests = rows_for(&app, "agency_payout_requests", &agency);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["status"], "paid");
+    let calls = app.stripe.calls_to(Method::POST, "/v1/payouts");
+    assert_eq!(calls[0].headers["idempotency-key"], "agency-payout-apr-1");
+    assert_eq!(
+        calls[0]
+            .form_value("metadata[agency_payout_request_id]")
+            .as_deref(),
+        Some("apr-1")
+    );
+}
+
+fn interrupted_request(app: &TestApp, agency: &TestUser, stripe_payout_id: Option<&str>) {
+    app.supabase.seed(
+        "agency_payout_requests",
+        json!({
+            "id": "apr-2",
+            "agency_id": agency.id,
+            "amount_cents": 12_000,
+            "currency": "USD",
+            "payout_method": "instant",
+            "status": "processing",
+            "source": "scheduled",
+            "schedule_period": "initial",
+            "schedule_attempt": 1,
+            "requested_at": ts(Duration::days(-3)),
+            "stripe_payout_id": stripe_payout_id,
+        }),
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rerun_reuses_the_payout_saved_on_the_request() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    seed_agency(&app, &agency, 12_000, None);
+    interrupted_request(&app, &agency, Some("po_saved"));
+
+    let output = run_scheduler(&app).await;
+    assert_eq!(output["executed"], 1, "{output}");
+    let requests = rows_for(&app, "agency_payout_requests", &agency);
+    assert_eq!(requests[0]["status"], "paid");
+    assert_eq!(requests[0]["stripe_payout_id"], "po_saved");
+    assert!(app.stripe.calls_to(Method::POST, "/v1/payouts").is_empty());
+    assert!(app.stripe.calls_to(Method::GET, "/v1/payouts").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rerun_finds_an_unsaved_payout_by_its_metadata() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    seed_agency(&app, &agency, 12_000, None);
+    // Stripe made the payout days ago, past the idempotency key's lifetime,
+    // but the run died before saving its id.
+    interrupted_request(&app, &agency, None);
+    let mut other = payout("po_other", 500);
+    other["metadata"] = json!({ "agency_payout_request_id": "apr-9" });
+    let mut made = payout("po_made", 11_880);
+    made["metadata"] = json!({ "agency_payout_request_id": "apr-2" });
+    app.stripe.on(
+        Method::GET,
+        "/v1/payouts",
+        StatusCode::OK,
+        stripe::list("/v1/payouts", vec![other, made]),
+    );
+
+    let output = run_scheduler(&app).await;
+    assert_eq!(output["executed"], 1, "{output}");
+    let requests = rows_for(&app, "agency_payout_requests", &agency);
+    assert_eq!(requests[0]["stripe_payout_id"], "po_made");
+    assert_eq!(requests[0]["status"], "paid");
+    assert!(app.stripe.calls_to(Method::POST, "/v1/payouts").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rerun_waits_when_stripe_cannot_be_checked() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    seed_agency(&app, &agency, 12_000, None);
+    interrupted_request(&app, &agency, None);
+    app.stripe.on(
+        Method::GET,
+        "/v1/payouts",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        json!({ "error": { "type": "api_error", "message": "unavailable" } }),
+    );
+
+    run_scheduler(&app).await;
+    let requests = rows_for(&app, "agency_payout_requests", &agency);
+    for c in app.stripe.calls() {
+        eprintln!("CALL {} {} {}", c.method, c.path, c.query);
+    }
+    eprintln!("REQ {:?}", requests);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["status"], "processing");
+    assert!(app.stripe.calls_to(Method::POST, "/v1/payouts").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn payouts_are_charged_by_the_agency_fee_schedule_version() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let admin = TestUser::new("admin");
+    seed_agency(&app, &agency, 12_000, None);
+    app.stripe.on(
+        Method::POST,
+        "/v1/payouts",
+        StatusCode::OK,
+        payout("po_4", 9_700),
+    );
+
+    // No schedule yet: the platform rate of 1%.
+    let preview = "/api/agency/payouts/preview?amount_cents=10000";
+    let (status, quote) = app.get(preview, &agency).await;
+    assert_eq!(status, 200, "{quote}");
+    assert_eq!(
+        (quote["fee_cents"].clone(), quote["net_cents"].clone()),
+        (json!(100), json!(9_900))
+    );
+    assert_eq!(quote["fee_schedule_version"], 0);
+
+    let path = format!("/api/admin/payout-fee-schedules/{}", agency.id);
+    let schedule = json!({
+        "rules": [
+            { "payout_method": "instant", "flat_cents": 100, "percent_bps": 200, "min_fee_cents": 250, "max_fee_cents": 5_000 },
+            { "payout_method": "instant", "currency": "eur", "percent_bps": 50 },
+        ],
+        "note": "Negotiated rate",
+    });
+    let put =
+        |user: &TestUser, body: &Value| app.request(Method::PUT, &path, user).json(body).send();
+    assert_eq!(put(&agency, &schedule).await.unwrap().status(), 403);
+    let bad = json!({ "rules": [{ "payout_method": "instant", "min_fee_cents": 10, "max_fee_cents": 5 }] });
+    assert_eq!(put(&admin, &bad).await.unwrap().status(), 400);
+    // Payouts are instant-only, so a standard rule would never be charged.
+    let standard = json!({ "rules": [{ "payout_method": "standard", "percent_bps": 50 }] });
+    assert_eq!(put(&admin, &standard).await.unwrap().status(), 400);
+    let saved: Value = put(&admin, &schedule).await.unwrap().json().await.unwrap();
+    assert_eq!(saved["version"], 1, "{saved}");
+
+    let (_, quote) = app.get(preview, &agency).await;
+    assert_eq!(quote["fee_cents"], 300, "{quote}");
+    assert_eq!(quote["fee_schedule_version"], 1);
+    let (_, small) = app
+        .get("/api/agency/payouts/preview?amount_cents=5000", &agency)
+        .await;
+    assert_eq!(small["fee_cents"], 250, "{small}");
+    let (_, eur) = app.get(&format!("{preview}&currency=EUR"), &agency).await;
+    assert_eq!(eur["fee_cents"], 50, "{eur}");
+    let (status, _) = app
+        .get(&format!("{preview}&payout_method=standard"), &agency)
+        .await;
+    assert_eq!(status, 400);
+
+    // The request keeps the fee and version it was charged with, and pays
+    // out the net.
+    let (status, body) = app
+        .post(
+            "/api/agency/payouts/request",
+            &agency,
+            json!({ "amount_cents": 10_000 }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    let request = &body["payout_request"];
+    assert_eq!(request["status"], "paid", "{body}");
+    assert_eq!(request["fee_cents"], 300);
+    assert_eq!(request["net_cents"], 9_700);
+    assert_eq!(request["fee_schedule_version"], 1);
+    let calls = app.stripe.calls_to(Method::POST, "/v1/payouts");
+    let form = String::from_utf8_lossy(&calls[0].body).to_string();
+    assert!(form.contains("amount=9700"), "{form}");
+
+    // A later version does not change what was charged before.
+    put(
+        &admin,
+        &json!({ "rules": [{ "payout_method": "instant", "percent_bps": 0 }] }),
+    )
+    .await
+    .unwrap();
+    let (_, history) = app.get(&path, &admin).await;
+    let versions: Vec<i64> = history
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["version"].as_i64().unwrap())
+        .collect();
+    assert_eq!(versions, vec![2, 1]);
+    let (_, own) = app.get("/api/agency/payouts/fee-schedule", &agency).await;
+    assert_eq!(own["version"], 2);
+    let (_, payouts) = app.get("/api/agency/payouts/history", &agency).await;
+    assert_eq!(payouts["items"][0]["fee_cents"], 300, "{payouts}");
+}
@@ -0,0 +1,254 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{fixtures::seed_agency, TestApp, TestUser};
+use serde_json::{json, Value};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn commission_change_is_audited_with_actor_diff_and_request_id() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    app.supabase.seed(
+        "talent_commissions",
+        json!({ "talent_id": "talent-1", "agency_id": agency.id, "commission_rate": 20.0 }),
+    );
+    app.supabase.on_rpc("get_agency_performance_stats", |_| {
+        (StatusCode::OK, json!([]))
+    });
+
+    let (status, body) = app
+        .post(
+            "/api/agency/dashboard/talent-commissions/update",
+            &agency,
+            json!({ "talent_id": "talent-1", "custom_rate": 35.0 }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+
+    let (status, events) = app
+        .get(
+            "/api/agency/audit-events?subject_table=talent_commissions&subject_id=talent-1",
+            &agency,
+        )
+        .await;
+    assert_eq!(status, 200, "{events}");
+    let events = events.as_array().unwrap();
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event["type"], "talent_commission.updated");
+    assert_eq!(event["actor_id"], agency.id.as_str());
+    assert_eq!(event["actor_role"], "agency");
+    assert_eq!(
+        event["changes"],
+        json!({ "commission_rate": { "before": 20.0, "after": 35.0 } })
+    );
+    assert!(event["request_id"]
+        .as_str()
+        .is_some_and(|id| !id.is_empty()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn audit_log_is_filtered_paginated_and_scoped_to_the_agency() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    for id in ["bk-1", "bk-2"] {
+        app.supabase.seed(
+            "bookings",
+            json!({
+                "id": id,
+                "agency_user_id": agency.id,
+                "type": "job",
+                "status": "confirmed",
+                "date": "2026-11-01",
+                "currency": "USD",
+                "campaign_id": "camp-1",
+            }),
+        );
+        let (status, body) = app
+            .post(&format!("/api/bookings/{id}/cancel"), &agency, json!({}))
+            .await;
+        assert_eq!(status, 200, "{body}");
+    }
+
+    let path = "/api/agency/audit-events?type=booking.cancelled&limit=1";
+    let (status, first) = app.get(path, &agency).await;
+    assert_eq!(status, 200, "{first}");
+    assert_eq!(first.as_array().unwrap().len(), 1);
+    assert_eq!(first[0]["subject_table"], "bookings");
+    assert_eq!(first[0]["changes"]["status"]["before"], "confirmed");
+    assert_eq!(first[0]["changes"]["status"]["after"], "cancelled");
+    assert_eq!(first[0]["changes"]["campaign_id"]["after"], json!(null));
+
+    let (_, second) = app.get(&format!("{path}&offset=1"), &agency).await;
+    assert_eq!(second.as_array().unwrap().len(), 1);
+    assert_ne!(first[0]["subject_id"], second[0]["subject_id"]);
+
+    let (_, none) = app
+        .get("/api/agency/audit-events?type=invoice.updated", &agency)
+        .await;
+    assert!(none.as_array().unwrap().is_empty());
+
+    let other = TestUser::new("agency");
+    let (status, theirs) = app.get("/api/agency/audit-events", &other).await;
+    assert_eq!(status, 200);
+    assert!(theirs.as_array().unwrap().is_empty());
+
+    let (status, _) = app
+        .get("/api/agency/audit-events", &TestUser::creator())
+        .await;
+    assert_eq!(status, 403);
+}
+
+async fn events_for(app: &TestApp, agency: &TestUser, table: &str, id: &str) -> Vec<Value> {
+    let (status, events) = app
+        .get(
+            &format!("/api/agency/audit-events?subject_table={table}&subject_id={id}"),
+            agency,
+        )
+        .await;
+    assert_eq!(status, 200, "{events}");
+    events.as_array().unwrap().clone()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invoice_creation_is_audited() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+    let (status, invoice) = app
+        .post(
+            "/api/invoices",
+            &agency,
+            json!({
+                "client_id": client_id,
+                "invoice_date": "2026-10-01",
+                "due_date": "2026-10-31",
+                "items": [{ "description": "Shoot day", "unit_price_cents": 50000 }],
+            }),
+        )
+        .await;
+    assert_eq!(status, 200, "{invoice}");
+
+    let id = invoice["id"].as_str().unwrap();
+    let events = events_for(&app, &agency, "agency_invoices", id).await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["type"], "invoice.created");
+    assert_eq!(events[0]["actor_id"], agency.id.as_str());
+    assert_eq!(events[0]["changes"]["status"]["after"], "draft");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn booking_creation_and_update_are_audited() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let (status, created) = app
+        .post(
+            "/api/bookings",
+            &agency,
+            json!({ "date": "2026-11-01", "location": "Studio A" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{created}");
+    let id = created[0]["id"].as_str().unwrap().to_string();
+
+    let (status, body) = app
+        .post(
+            &format!("/api/bookings/{id}"),
+            &agency,
+            json!({ "location": "Studio B" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+
+    let events = events_for(&app, &agency, "bookings", &id).await;
+    let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(types, ["booking.updated", "booking.created"]);
+    assert_eq!(
+        events[0]["changes"]["location"],
+        json!({ "before": "Studio A", "after": "Studio B" })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn license_submission_archive_is_audited() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    app.supabase.seed(
+        "license_submissions",
+        json!({ "id": "ls-1", "agency_id": agency.id, "template_id": "tpl-1", "status": "sent" }),
+    );
+    let resp = app
+        .request(Method::DELETE, "/api/license-submissions/ls-1", &agency)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 204);
+
+    let events = events_for(&app, &agency, "license_submissions", "ls-1").await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["type"], "license_submission.status_changed");
+    assert_eq!(
+        events[0]["changes"]["status"],
+        json!({ "before": "sent", "after": "archived" })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn payment_link_cancellation_is_audited() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    app.supabase.seed(
+        "agency_payment_links",
+        json!({
+            "id": "pl-1",
+            "agency_id": agency.id,
+            "stripe_payment_link_id": "",
+            "status": "active",
+            "total_amount_cents": 5000,
+            "currency": "usd",
+        }),
+    );
+    let (status, body) = app
+        .post("/api/agency/payment-links/pl-1", &agency, json!({}))
+        .await;
+    assert_eq!(status, 200, "{body}");
+
+    let events = events_for(&app, &agency, "agency_payment_links", "pl-1").await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["type"], "payment_link.cancelled");
+    assert_eq!(
+        events[0]["changes"]["status"],
+        json!({ "before": "active", "after": "cancelled" })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn roster_edit_is_audited() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    app.supabase.seed("agencies", json!({ "id": agency.id }));
+    app.supabase.seed(
+        "agency_users",
+        json!({ "id": "tal-1", "agency_id": agency.id, "role": "talent", "stage_name": "Ana" }),
+    );
+    app.supabase.seed(
+        "agency_talent_relationships",
+        json!({ "agency_id": agency.id, "talent_id": "tal-1", "status": "active" }),
+    );
+    let (status, body) = app
+        .post(
+            "/api/agency/talent/tal-1",
+            &agency,
+            json!({ "stage_name": "Ana B" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+
+    let events = events_for(&app, &agency, "agency_users", "tal-1").await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["type"], "talent.updated");
+    assert_eq!(
+        events[0]["changes"],
+        json!({ "stage_name": { "before": "Ana", "after": "Ana B" } })
+    );
+}
@@ -0,0 +1,159 @@
+//! Domain data and database functions shared by several test files.
+
+use super::{MockSupabase, TestApp, TestUser};
+use axum::http::StatusCode;
+use chrono::{Duration, NaiveDate, Utc};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Seeds the agency "North Studio" with one client, "Acme Corp", and numbers
+/// its invoices INVCA0000001, INVCA0000002, ... Returns the client id.
+pub fn seed_agency(app: &TestApp, agency: &TestUser) -> String {
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "email": agency.email, "agency_name": "North Studio", "logo_url": null }),
+    );
+    let counter = AtomicUsize::new(0);
+    app.supabase.on_rpc("next_invoice_number", move |_| {
+        let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        (StatusCode::OK, json!(format!("INVCA{n:07}")))
+    });
+    let client = app.supabase.seed(
+        "agency_clients",
+        json!({
+            "agency_id": agency.id,
+            "company": "Acme Corp",
+            "contact_name": "Jane Doe",
+            "email": "billing@acme.test",
+            "phone": null,
+        }),
+    );
+    client["id"].as_str().unwrap().to_string()
+}
+
+/// Seeds a $100.00 invoice to "Acme Corp", dated 30 days before `due` and
+/// marked sent now, with every `agency_invoices` column filled in. Returns
+/// its id.
+pub fn seed_invoice(
+    app: &TestApp,
+    agency: &TestUser,
+    number: &str,
+    status: &str,
+    due: NaiveDate,
+) -> String {
+    let row = app.supabase.seed(
+        "agency_invoices",
+        json!({
+            "agency_id": agency.id,
+            "client_id": "client-1",
+            "booking_id": null,
+            "invoice_number": number,
+            "status": status,
+            "invoice_date": (due - Duration::days(30)).to_string(),
+            "due_date": due.to_string(),
+            "sent_at": Utc::now().to_rfc3339(),
+            "paid_at": null,
+            "bill_to_company": "Acme Corp",
+            "bill_to_contact_name": "Jane Doe",
+            "bill_to_email": "billing@acme.test",
+            "bill_to_phone": null,
+            "po_number": null,
+            "project_reference": null,
+            "currency": "USD",
+            "payment_terms": "net_30",
+            "agency_commission_bps": 2000,
+            "tax_rate_bps": 0,
+            "tax_exempt": false,
+            "discount_cents": 0,
+            "tax_treatment": "standard",
+            "prices_include_tax": false,
+            "tax_label": null,
+            "tax_jurisdiction": null,
+            "customer_tax_id": null,
+            "tax_note": null,
+            "notes_internal": null,
+            "payment_instructions": null,
+            "footer_text": null,
+            "subtotal_cents": 10000,
+            "expenses_cents": 0,
+            "tax_cents": 0,
+            "total_cents": 10000,
+            "agency_fee_cents": 2000,
+            "talent_net_cents": 8000,
+            "amount_paid_cents": 0,
+            "amount_credited_cents": 0,
+            "last_payment_at": null,
+            "sent_pdf_path": null,
+            "sent_pdf_sha256": null,
+        }),
+    );
+    row["id"].as_str().unwrap().to_string()
+}
+
+/// Adds `name` to the agency's roster as an active talent. Returns its id.
+pub fn seed_talent(app: &TestApp, agency: &TestUser, name: &str) -> String {
+    let email = format!(
+        "{}@talent.test",
+        name.split(' ').next().unwrap_or(name).to_lowercase()
+    );
+    let talent = app.supabase.seed(
+        "agency_users",
+        json!({
+            "agency_id": agency.id,
+            "user_id": null,
+            "creator_id": null,
+            "full_legal_name": name,
+            "stage_name": null,
+            "email": email,
+            "country": "US",
+            "status": "active",
+        }),
+    );
+    talent["id"].as_str().unwrap().to_string()
+}
+
+/// Stands in for the `settle_payment_clawback` RPC: saves `p_link` onto the
+/// payment link while its `clawback_cents` is still the expected one and
+/// answers whether it did. The balance moves are only recorded, see
+/// [`clawback_moves`].
+pub fn settle_payment_clawback(supabase: &MockSupabase) {
+    let db = supabase.clone();
+    supabase.on_rpc("settle_payment_clawback", move |args| {
+        let id = args["p_payment_link_id"].as_str().unwrap_or_default();
+        let current = db.find("agency_payment_links", "id", id).map(|link| {
+            link.get("clawback_cents")
+                .and_then(Value::as_i64)
+                .unwrap_or(0)
+        });
+        if current != args["p_expected_clawback_cents"].as_i64() {
+            return (StatusCode::OK, json!(false));
+        }
+        db.update("agency_payment_links", id, args["p_link"].clone());
+        (StatusCode::OK, json!(true))
+    });
+}
+
+/// Balance moves passed to `settle_payment_clawback`, in order.
+pub fn clawback_moves(supabase: &MockSupabase) -> Vec<Value> {
+    supabase
+        .rpc_calls("settle_payment_clawback")
+        .iter()
+        .flat_map(|args| args["p_moves"].as_array().cloned().unwrap_or_default())
+        .collect()
+}
+
+/// Stands in for the `record_stripe_transfer` RPC by inserting the transfer
+/// row it describes. The balance updates it makes are left out.
+pub fn record_stripe_transfer(supabase: &MockSupabase) {
+    let db = supabase.clone();
+    supabase.on_rpc("record_stripe_transfer", move |args| {
+        let row: serde_json::Map<String, Value> = args
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(k, v)| Some((k.strip_prefix("p_")?.to_string(), v.clone())))
+            .collect();
+        db.seed("agency_payment_link_transfers", Value::Object(row));
+        (StatusCode::OK, json!(null))
+    });
+}
@@ -0,0 +1,155 @@
+//! Generic recording HTTP mock used for Stripe, DocuSeal, Veriff and ElevenLabs.
+//!
+//! Routes are matched on method and path (`:name` segments match anything);
+//! the most recently registered route wins so tests can override defaults.
+//! Unmatched requests get a 404 with a Stripe-shaped error body.
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::any,
+    Json, Router,
+};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+type Responder = Arc<dyn Fn(&RecordedCall) -> (StatusCode, Value) + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct RecordedCall {
+    pub method: Method,
+    pub path: String,
+    pub query: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl RecordedCall {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+
+    /// Decoded `application/x-www-form-urlencoded` body (what Stripe receives).
+    pub fn form(&self) -> Vec<(String, String)> {
+        super::supabase::parse_query(&String::from_utf8_lossy(&self.body))
+    }
+
+    pub fn form_value(&self, key: &str) -> Option<String> {
+        self.form()
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+}
+
+struct Route {
+    method: Method,
+    path: String,
+    responder: Responder,
+}
+
+#[derive(Default)]
+struct Inner {
+    routes: Vec<Route>,
+    calls: Vec<RecordedCall>,
+}
+
+#[derive(Clone)]
+pub struct MockHttp {
+    pub url: String,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl MockHttp {
+    pub async fn start() -> Self {
+        let inner: Arc<Mutex<Inner>> = Arc::default();
+        let app = Router::new()
+            .fallback(any(handle))
+            .with_state(inner.clone());
+        let url = super::serve(app).await;
+        Self { url, inner }
+    }
+
+    /// Always answers `method path` with `status` and `body`.
+    pub fn on(&self, method: Method, path: &str, status: StatusCode, body: Value) {
+        self.on_with(method, path, move |_| (status, body.clone()));
+    }
+
+    /// Answers `method path` by calling `f` with the recorded request.
+    pub fn on_with(
+        &self,
+        method: Method,
+        path: &str,
+        f: impl Fn(&RecordedCall) -> (StatusCode, Value) + Send + Sync + 'static,
+    ) {
+        self.inner.lock().unwrap().routes.push(Route {
+            method,
+            path: path.to_string(),
+            responder: Arc::new(f),
+        });
+    }
+
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.inner.lock().unwrap().calls.clone()
+    }
+
+    pub fn calls_to(&self, method: Method, path: &str) -> Vec<RecordedCall> {
+        self.calls()
+            .into_iter()
+            .filter(|c| c.method == method && path_matches(path, &c.path))
+            .collect()
+    }
+}
+
+async fn handle(
+    State(inner): State<Arc<Mutex<Inner>>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let call = RecordedCall {
+        method,
+        path: uri.path().to_string(),
+        query: uri.query().unwrap_or("").to_string(),
+        headers,
+        body,
+    };
+    let responder = {
+        let mut inner = inner.lock().unwrap();
+        inner.calls.push(call.clone());
+        inner
+            .routes
+            .iter()
+            .rev()
+            .find(|r| r.method == call.method && path_matches(&r.path, &call.path))
+            .map(|r| r.responder.clone())
+    };
+    match responder {
+        Some(f) => {
+            let (status, body) = f(&call);
+            (status, Json(body)).into_response()
+        }
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "error": {
+                    "type": "invalid_request_error",
+                    "message": format!("no mock for {} {}", call.method, call.path),
+                }
+            })),
+        )
+            .into_response(),
+    }
+}
+
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let p: Vec<&str> = pattern.trim_matches('/').split('/').collect();
+    let a: Vec<&str> = path.trim_matches('/').split('/').collect();
+    p.len() == a.len()
+        && p.iter()
+            .zip(a.iter())
+            .all(|(p, a)| p.starts_with(':') || p == a)
+}
@@ -0,0 +1,298 @@
+//! Test support for router-level integration tests.
+//!
+//! `TestApp::spawn` boots `router::build_router` on a random local port with an
+//! `AppState` whose every outbound dependency points at an in-process stand-in:
+//! Supabase (PostgREST + Storage), Stripe, DocuSeal, Veriff, ElevenLabs and an
+//! SMTP sink. Tests seed the mocks, drive the API over HTTP and then assert on
+//! both the responses and what the server sent to its dependencies.
+#![allow(dead_code)]
+
+pub mod fixtures;
+pub mod mock_http;
+pub mod smtp;
+pub mod stripe;
+pub mod supabase;
+
+use axum::Router;
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use likelee_server::config::{AppState, DuixConfig, VeriffConfig};
+use likelee_server::rate_limit::{MemoryStore, RateLimiter};
+use likelee_server::repositories::Repositories;
+use likelee_server::storage::SupabaseStorage;
+use postgrest::Postgrest;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::Arc;
+
+pub use mock_http::MockHttp;
+pub use smtp::SmtpSink;
+pub use supabase::MockSupabase;
+
+pub const JWT_SECRET: &str = "test-jwt-secret";
+pub const STRIPE_WEBHOOK_SECRET: &str = "whsec_test";
+pub const DOCUSEAL_API_KEY: &str = "docuseal-test-key";
+pub const DOCUSEAL_WEBHOOK_SECRET: &str = "docuseal-webhook-secret";
+pub const VERIFF_SHARED_SECRET: &str = "veriff-test-secret";
+
+/// Serves `app` on an ephemeral local port and returns its base URL.
+pub async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind mock listener");
+    let addr = listener.local_addr().expect("local addr");
+    tokio::spawn(async move {
+        let app = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
+        axum::serve(listener, app).await.expect("mock server");
+    });
+    format!("http://{addr}")
+}
+
+#[derive(Debug, Clone)]
+pub struct TestUser {
+    pub id: String,
+    pub email: String,
+    pub role: String,
+}
+
+impl TestUser {
+    pub fn new(role: &str) -> Self {
+        let id = uuid::Uuid::new_v4().to_string();
+        Self {
+            email: format!("{role}-{}@example.test", &id[..8]),
+            id,
+            role: role.to_string(),
+        }
+    }
+
+    pub fn agency() -> Self {
+        Self::new("agency")
+    }
+
+    pub fn creator() -> Self {
+        Self::new("creator")
+    }
+}
+
+pub struct TestApp {
+    pub url: String,
+    pub http: reqwest::Client,
+    pub state: AppState,
+    pub supabase: MockSupabase,
+    pub stripe: MockHttp,
+    pub docuseal: MockHttp,
+    pub veriff: MockHttp,
+    pub elevenlabs: MockHttp,
+    pub mail: SmtpSink,
+}
+
+impl TestApp {
+    pub async fn spawn() -> Self {
+        Self::spawn_with(|_| {}).await
+    }
+
+    /// Like `spawn`, but lets the test tweak the state (feature flags, limits)
+    /// before the router is built.
+    pub async fn spawn_with(configure: impl FnOnce(&mut AppState)) -> Self {
+        let supabase = MockSupabase::start().await;
+        let stripe = MockHttp::start().await;
+        stripe::install_defaults(&stripe);
+        let docuseal = MockHttp::start().await;
+        let veriff = MockHttp::start().await;
+        let elevenlabs = MockHttp::start().await;
+        let mail = SmtpSink::start();
+
+        let mut state = test_state(&supabase, &stripe, &docuseal, &veriff, &elevenlabs, &mail);
+        configure(&mut state);
+
+        let url = serve(likelee_server::router::build_router(state.clone())).await;
+        Self {
+            url,
+            http: reqwest::Client::new(),
+            state,
+            supabase,
+            stripe,
+            docuseal,
+            veriff,
+            elevenlabs,
+            mail,
+        }
+    }
+
+    pub fn token(&self, user: &TestUser) -> String {
+        let claims = json!({
+            "sub": user.id,
+            "email": user.email,
+            "aud": "authenticated",
+            "exp": chrono::Utc::now().timestamp() + 3600,
+            "user_metadata": { "role": user.role },
+        });
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+        )
+        .expect("encode jwt")
+    }
+
+    pub fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        user: &TestUser,
+    ) -> reqwest::RequestBuilder {
+        self.http
+            .request(method, format!("{}{}", self.url, path))
+            .bearer_auth(self.token(user))
+    }
+
+    pub async fn get(&self, path: &str, user: &TestUser) -> (u16, Value) {
+        read(self.request(reqwest::Method::GET, path, user)).await
+    }
+
+    pub async fn post(&self, path: &str, user: &TestUser, body: Value) -> (u16, Value) {
+        read(self.request(reqwest::Method::POST, path, user).json(&body)).await
+    }
+
+    /// Posts a DocuSeal `event` to `path` signed with the webhook secret.
+    pub async fn docuseal_webhook(&self, path: &str, event: &Value) -> (u16, Value) {
+        let payload = event.to_string();
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(DOCUSEAL_WEBHOOK_SECRET.as_bytes()).expect("hmac key");
+        mac.update(payload.as_bytes());
+        read(
+            self.http
+                .post(format!("{}{}", self.url, path))
+                .header(
+                    "X-Docuseal-Signature",
+                    hex::encode(mac.finalize().into_bytes()),
+                )
+                .header("Content-Type", "application/json")
+                .body(payload),
+        )
+        .await
+    }
+
+    /// Posts `event` to `/webhooks/stripe` with a valid `Stripe-Signature`.
+    pub async fn stripe_webhook(&self, event: &Value) -> (u16, Value) {
+        let payload = event.to_string();
+        let sig = stripe::sign_payload(&payload, STRIPE_WEBHOOK_SECRET);
+        read(
+            self.http
+                .post(format!("{}/webhooks/stripe", self.url))
+                .header("Stripe-Signature", sig)
+                .header("Content-Type", "application/json")
+                .body(payload),
+        )
+        .await
+    }
+}
+
+async fn read(req: reqwest::RequestBuilder) -> (u16, Value) {
+    let resp = req.send().await.expect("request to test server");
+    let status = resp.status().as_u16();
+    let text = resp.text().await.unwrap_or_default();
+    let body = serde_json::from_str(&text).unwrap_or(Value::String(text));
+    (status, body)
+}
+
+fn test_state(
+    supabase: &MockSupabase,
+    stripe: &MockHttp,
+    docuseal: &MockHttp,
+    veriff: &MockHttp,
+    elevenlabs: &MockHttp,
+    mail: &SmtpSink,
+) -> AppState {
+    let service_key = "service-role-test-key".to_string();
+    let pg = Postgrest::new(format!("{}/rest/v1", supabase.url))
+        .insert_header("apikey", service_key.clone())
+        .insert_header("Authorization", format!("Bearer {service_key}"))
+        .insert_header("Prefer", "return=representation");
+
+    AppState {
+        repos: Repositories::postgrest(pg.clone()),
+        storage: Arc::new(SupabaseStorage::new(&supabase.url, &service_key)),
+        rate_limiter: RateLimiter::new(Arc::new(MemoryStore::default())),
+        pg,
+        veriff: VeriffConfig {
+            base_url: veriff.url.clone(),
+            api_key: "veriff-test-key".to_string(),
+            shared_secret: VERIFF_SHARED_SECRET.to_string(),
+        },
+        duix: DuixConfig {
+            base_url: "http://127.0.0.1:9".to_string(),
+            auth_token: "duix-test-token".to_string(),
+        },
+        rekog: None,
+        supabase_url: supabase.url.clone(),
+        supabase_service_key: service_key,
+        supabase_jwt_secret: JWT_SECRET.to_string(),
+        supabase_bucket_public: "likelee-public".to_string(),
+        supabase_bucket_private: "likelee-private".to_string(),
+        supabase_bucket_temp: "likelee-temp".to_string(),
+        elevenlabs_api_key: "elevenlabs-test-key".to_string(),
+        elevenlabs_base_url: elevenlabs.url.clone(),
+
+        stripe_secret_key: "sk_test_mock".to_string(),
+        stripe_api_base: format!("{}/", stripe.url),
+        stripe_client_id: "ca_test".to_string(),
+        stripe_return_url: "http://localhost:5173/stripe/return".to_string(),
+        stripe_refresh_url: "http://localhost:5173/stripe/refresh".to_string(),
+        stripe_webhook_secret: STRIPE_WEBHOOK_SECRET.to_string(),
+
+        stripe_agency_price_id: "price_agency".to_string(),
+        stripe_scale_price_id: "price_scale".to_string(),
+        stripe_licensing_basic_price_id: "price_lic_basic".to_string(),
+        stripe_licensing_pro_price_id: "price_lic_pro".to_string(),
+        stripe_licensing_enterprise_price_id: "price_lic_enterprise".to_string(),
+
+        stripe_agency_basic_base_price_id: "price_agency_basic".to_string(),
+        stripe_agency_pro_base_price_id: "price_agency_pro".to_string(),
+        stripe_checkout_success_url: "http://localhost:5173/success".to_string(),
+        stripe_checkout_cancel_url: "http://localhost:5173/cancel".to_string(),
+        stripe_licensing_success_url: "http://localhost:5173/licensing/success".to_string(),
+        stripe_licensing_cancel_url: "http://localhost:5173/licensing/cancel".to_string(),
+
+        payouts_enabled: true,
+        payout_auto_approve_threshold_cents: 500_000,
+        min_payout_amount_cents: 1000,
+        instant_payouts_enabled: true,
+        payout_fee_bps: 100,
+        payout_currency: "USD".to_string(),
+        payout_allowed_currencies: vec!["USD".to_string(), "EUR".to_string()],
+
+        agency_payout_scheduler_enabled: false,
+        agency_payout_scheduler_interval_secs: 3600,
+        jobs_enabled: false,
+        jobs_poll_interval_secs: 30,
+
+        smtp_host: "127.0.0.1".to_string(),
+        smtp_port: mail.port,
+        smtp_user: "mailer".to_string(),
+        smtp_password: "mailer".to_string(),
+        smtp_tls: false,
+        email_from: "noreply@likelee.test".to_string(),
+        email_contact_to: "contact@likelee.test".to_string(),
+
+        smtp_sales_host: "127.0.0.1".to_string(),
+        smtp_sales_port: mail.port,
+        smtp_sales_user: "sales".to_string(),
+        smtp_sales_password: "sales".to_string(),
+        email_from_sales: "operations@likelee.test".to_string(),
+        email_sales_to: "operations@likelee.test".to_string(),
+
+        docuseal_api_key: DOCUSEAL_API_KEY.to_string(),
+        docuseal_base_url: docuseal.url.clone(),
+        docuseal_api_url: docuseal.url.clone(),
+        docuseal_app_url: docuseal.url.clone(),
+        docuseal_webhook_url: String::new(),
+        docuseal_webhook_secret: DOCUSEAL_WEBHOOK_SECRET.to_string(),
+        docuseal_user_email: "contracts@likelee.test".to_string(),
+        docuseal_master_template_id: String::new(),
+        docuseal_master_template_name: String::new(),
+
+        kyc_bypass_veriff_limit: false,
+        frontend_url: "http://localhost:5173".to_string(),
+    }
+}
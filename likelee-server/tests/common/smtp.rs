@@ -0,0 +1,144 @@
+//! Minimal plaintext SMTP server that accepts everything and keeps the mail.
+//!
+//! The server sends mail with lettre's blocking transport from inside request
+//! handlers, so the sink runs on plain OS threads rather than the test runtime.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct CapturedMail {
+    pub from: String,
+    pub to: Vec<String>,
+    /// Raw RFC 5322 message as received after `DATA`.
+    pub data: String,
+}
+
+impl CapturedMail {
+    pub fn header(&self, name: &str) -> Option<String> {
+        let prefix = format!("{}:", name.to_lowercase());
+        self.data
+            .lines()
+            .take_while(|l| !l.is_empty())
+            .find(|l| l.to_lowercase().starts_with(&prefix))
+            .map(|l| l[prefix.len()..].trim().to_string())
+    }
+
+    pub fn subject(&self) -> Option<String> {
+        self.header("Subject")
+    }
+}
+
+#[derive(Clone)]
+pub struct SmtpSink {
+    pub port: u16,
+    mails: Arc<Mutex<Vec<CapturedMail>>>,
+}
+
+impl SmtpSink {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind smtp sink");
+        let port = listener.local_addr().expect("smtp addr").port();
+        let mails: Arc<Mutex<Vec<CapturedMail>>> = Arc::default();
+        let sink = mails.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sink = sink.clone();
+                std::thread::spawn(move || {
+                    let _ = session(stream, &sink);
+                });
+            }
+        });
+        Self { port, mails }
+    }
+
+    pub fn messages(&self) -> Vec<CapturedMail> {
+        self.mails.lock().unwrap().clone()
+    }
+
+    /// Waits up to two seconds for at least `n` messages, for mail sent from
+    /// background tasks.
+    pub fn wait_for(&self, n: usize) -> Vec<CapturedMail> {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        loop {
+            let mails = self.messages();
+            if mails.len() >= n || Instant::now() >= deadline {
+                return mails;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+fn session(stream: TcpStream, sink: &Mutex<Vec<CapturedMail>>) -> std::io::Result<()> {
+    let mut out = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    out.write_all(b"220 localhost ESMTP sink\r\n")?;
+
+    let mut from = String::new();
+    let mut to = vec![];
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let cmd = line.trim_end().to_string();
+        let upper = cmd.to_uppercase();
+        if upper.starts_with("EHLO") {
+            out.write_all(b"250-localhost\r\n250-AUTH PLAIN LOGIN\r\n250 8BITMIME\r\n")?;
+        } else if upper.starts_with("HELO") {
+            out.write_all(b"250 localhost\r\n")?;
+        } else if upper == "AUTH LOGIN" {
+            out.write_all(b"334 VXNlcm5hbWU6\r\n")?;
+            reader.read_line(&mut String::new())?;
+            out.write_all(b"334 UGFzc3dvcmQ6\r\n")?;
+            reader.read_line(&mut String::new())?;
+            out.write_all(b"235 2.7.0 Authentication successful\r\n")?;
+        } else if upper.starts_with("AUTH") {
+            out.write_all(b"235 2.7.0 Authentication successful\r\n")?;
+        } else if upper.starts_with("MAIL FROM:") {
+            from = address(&cmd["MAIL FROM:".len()..]);
+            to.clear();
+            out.write_all(b"250 OK\r\n")?;
+        } else if upper.starts_with("RCPT TO:") {
+            to.push(address(&cmd["RCPT TO:".len()..]));
+            out.write_all(b"250 OK\r\n")?;
+        } else if upper == "DATA" {
+            out.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")?;
+            let mut data = String::new();
+            loop {
+                let mut l = String::new();
+                if reader.read_line(&mut l)? == 0 {
+                    return Ok(());
+                }
+                if l == ".\r\n" || l == ".\n" {
+                    break;
+                }
+                let l = l.strip_prefix('.').unwrap_or(&l);
+                data.push_str(&l.replace("\r\n", "\n"));
+            }
+            sink.lock().unwrap().push(CapturedMail {
+                from: std::mem::take(&mut from),
+                to: std::mem::take(&mut to),
+                data,
+            });
+            out.write_all(b"250 OK queued\r\n")?;
+        } else if upper == "QUIT" {
+            out.write_all(b"221 Bye\r\n")?;
+            return Ok(());
+        } else {
+            out.write_all(b"250 OK\r\n")?;
+        }
+    }
+}
+
+fn address(s: &str) -> String {
+    s.split_whitespace()
+        .next()
+        .unwrap_or("")
+        .trim_matches(|c| c == '<' || c == '>')
+        .to_string()
+}
@@ -0,0 +1,199 @@
+//! Stripe fixtures: default API responses for the mock and webhook signing.
+
+use super::MockHttp;
+use axum::http::{Method, StatusCode};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Answers `POST /v1/transfers` with a transfer echoing the requested amount,
+/// currency, destination and metadata, `POST /v1/transfers/:id/reversals`
+/// with a reversal of the requested amount, and the transfer, payout and
+/// balance transaction lists with empty pages.
+pub fn install_defaults(stripe: &MockHttp) {
+    for path in ["/v1/transfers", "/v1/payouts", "/v1/balance_transactions"] {
+        stripe.on(Method::GET, path, StatusCode::OK, list(path, vec![]));
+    }
+    let seq = Arc::new(AtomicUsize::new(0));
+    stripe.on_with(Method::POST, "/v1/transfers", move |call| {
+        let n = seq.fetch_add(1, Ordering::SeqCst) + 1;
+        let metadata: serde_json::Map<String, Value> = call
+            .form()
+            .into_iter()
+            .filter_map(|(k, v)| {
+                k.strip_prefix("metadata[")
+                    .and_then(|k| k.strip_suffix(']'))
+                    .map(|k| (k.to_string(), json!(v)))
+            })
+            .collect();
+        (
+            StatusCode::OK,
+            transfer(
+                &format!("tr_mock_{n}"),
+                call.form_value("amount")
+                    .and_then(|a| a.parse().ok())
+                    .unwrap_or(0),
+                &call.form_value("currency").unwrap_or_else(|| "usd".into()),
+                &call.form_value("destination").unwrap_or_default(),
+                Value::Object(metadata),
+            ),
+        )
+    });
+    let seq = Arc::new(AtomicUsize::new(0));
+    stripe.on_with(Method::POST, "/v1/transfers/:id/reversals", move |call| {
+        let n = seq.fetch_add(1, Ordering::SeqCst) + 1;
+        let transfer_id = call
+            .path
+            .trim_start_matches("/v1/transfers/")
+            .trim_end_matches("/reversals")
+            .to_string();
+        (
+            StatusCode::OK,
+            transfer_reversal(
+                &format!("trr_mock_{n}"),
+                &transfer_id,
+                call.form_value("amount")
+                    .and_then(|a| a.parse().ok())
+                    .unwrap_or(0),
+            ),
+        )
+    });
+}
+
+pub fn transfer_reversal(id: &str, transfer_id: &str, amount: i64) -> Value {
+    json!({
+        "id": id,
+        "object": "transfer_reversal",
+        "amount": amount,
+        "balance_transaction": null,
+        "created": chrono::Utc::now().timestamp(),
+        "currency": "usd",
+        "destination_payment_refund": null,
+        "metadata": {},
+        "source_refund": null,
+        "transfer": transfer_id,
+    })
+}
+
+pub fn transfer(
+    id: &str,
+    amount: i64,
+    currency: &str,
+    destination: &str,
+    metadata: Value,
+) -> Value {
+    json!({
+        "id": id,
+        "object": "transfer",
+        "amount": amount,
+        "amount_reversed": 0,
+        "balance_transaction": null,
+        "created": chrono::Utc::now().timestamp(),
+        "currency": currency,
+        "description": null,
+        "destination": destination,
+        "livemode": false,
+        "metadata": metadata,
+        "reversals": {
+            "object": "list",
+            "data": [],
+            "has_more": false,
+            "url": format!("/v1/transfers/{id}/reversals"),
+        },
+        "reversed": false,
+        "source_transaction": null,
+        "transfer_group": null,
+    })
+}
+
+/// A single, complete page of a Stripe list.
+pub fn list(url: &str, data: Vec<Value>) -> Value {
+    json!({
+        "object": "list",
+        "data": data,
+        "has_more": false,
+        "url": url,
+    })
+}
+
+pub fn payout(id: &str, amount: i64, currency: &str, automatic: bool) -> Value {
+    let now = chrono::Utc::now().timestamp();
+    json!({
+        "id": id,
+        "object": "payout",
+        "amount": amount,
+        "arrival_date": now,
+        "automatic": automatic,
+        "created": now,
+        "currency": currency,
+        "livemode": false,
+        "metadata": {},
+        "method": "instant",
+        "reconciliation_status": "not_applicable",
+        "source_type": "card",
+        "status": "paid",
+        "type": "bank_account",
+    })
+}
+
+/// A `charge` balance transaction with its charge expanded.
+pub fn charge_balance_transaction(id: &str, amount: i64, payment_intent: &str) -> Value {
+    let now = chrono::Utc::now().timestamp();
+    json!({
+        "id": id,
+        "object": "balance_transaction",
+        "amount": amount,
+        "available_on": now,
+        "created": now,
+        "currency": "usd",
+        "fee": 0,
+        "fee_details": [],
+        "net": amount,
+        "reporting_category": "charge",
+        "status": "available",
+        "type": "charge",
+        "source": {
+            "id": format!("ch_{id}"),
+            "object": "charge",
+            "amount": amount,
+            "amount_captured": amount,
+            "amount_refunded": 0,
+            "billing_details": {},
+            "captured": true,
+            "created": now,
+            "currency": "usd",
+            "disputed": false,
+            "livemode": false,
+            "metadata": {},
+            "paid": true,
+            "payment_intent": payment_intent,
+            "refunded": false,
+            "refunds": list(&format!("/v1/charges/ch_{id}/refunds"), vec![]),
+            "status": "succeeded",
+        },
+    })
+}
+
+/// Wraps `object` in a Stripe event envelope.
+pub fn event(event_type: &str, object: Value) -> Value {
+    json!({
+        "id": format!("evt_{}", uuid::Uuid::new_v4().simple()),
+        "object": "event",
+        "api_version": "2023-10-16",
+        "created": chrono::Utc::now().timestamp(),
+        "livemode": false,
+        "pending_webhooks": 1,
+        "type": event_type,
+        "data": { "object": object },
+    })
+}
+
+/// Builds a `Stripe-Signature` header value for `payload`.
+pub fn sign_payload(payload: &str, secret: &str) -> String {
+    let t = chrono::Utc::now().timestamp();
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac key");
+    mac.update(format!("{t}.{payload}").as_bytes());
+    format!("t={t},v1={}", hex::encode(mac.finalize().into_bytes()))
+}
@@ -0,0 +1,870 @@
+//! In-process Supabase stand-in: the subset of PostgREST the server uses
+//! (filters, select projection, ordering, ranges, insert/upsert/update/delete,
+//! RPC) over in-memory tables, plus the Storage object API.
+//!
+//! Embedded resources in `select` (e.g. `brands(email)`) are not joined; they
+//! come back as `null`. Unknown filter operators are ignored.
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::any,
+    Json, Router,
+};
+use serde_json::{json, Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub type Row = Map<String, Value>;
+type RpcHandler = Arc<dyn Fn(&Value) -> (StatusCode, Value) + Send + Sync>;
+type Shared = Arc<Mutex<Inner>>;
+
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: Method,
+    /// Path below `/rest/v1/` or `/storage/v1/`.
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub body: Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Inner {
+    tables: HashMap<String, Vec<Row>>,
+    rpcs: HashMap<String, RpcHandler>,
+    rpc_calls: Vec<(String, Value)>,
+    objects: HashMap<(String, String), StoredObject>,
+    requests: Vec<RecordedRequest>,
+}
+
+#[derive(Clone)]
+pub struct MockSupabase {
+    pub url: String,
+    inner: Shared,
+}
+
+impl MockSupabase {
+    pub async fn start() -> Self {
+        let inner: Shared = Arc::default();
+        let app = Router::new()
+            .route("/rest/v1/*rest", any(rest))
+            .route("/storage/v1/*rest", any(storage))
+            .with_state(inner.clone());
+        let url = super::serve(app).await;
+        Self { url, inner }
+    }
+
+    /// Inserts a row as if it came from the database, filling `id`,
+    /// `created_at`, `updated_at` and the table's column defaults when
+    /// absent. Returns the stored row.
+    pub fn seed(&self, table: &str, row: Value) -> Row {
+        let row = with_defaults(table, row.as_object().cloned().unwrap_or_default());
+        self.inner
+            .lock()
+            .unwrap()
+            .tables
+            .entry(table.to_string())
+            .or_default()
+            .push(row.clone());
+        row
+    }
+
+    /// Merges `patch` into the row of `table` whose `id` matches.
+    pub fn update(&self, table: &str, id: &str, patch: Value) {
+        let mut db = self.inner.lock().unwrap();
+        let row = db
+            .tables
+            .get_mut(table)
+            .and_then(|rows| rows.iter_mut().find(|r| r.get("id") == Some(&json!(id))))
+            .unwrap_or_else(|| panic!("no {table} row with id {id}"));
+        for (k, v) in patch.as_object().cloned().unwrap_or_default() {
+            row.insert(k, v);
+        }
+    }
+
+    pub fn rows(&self, table: &str) -> Vec<Row> {
+        self.inner
+            .lock()
+            .unwrap()
+            .tables
+            .get(table)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn find(&self, table: &str, column: &str, value: &str) -> Option<Row> {
+        self.rows(table)
+            .into_iter()
+            .find(|r| r.get(column).and_then(scalar).as_deref() == Some(value))
+    }
+
+    /// Registers an RPC. Unregistered functions answer 404 like PostgREST does.
+    pub fn on_rpc(
+        &self,
+        name: &str,
+        handler: impl Fn(&Value) -> (StatusCode, Value) + Send + Sync + 'static,
+    ) {
+        self.inner
+            .lock()
+            .unwrap()
+            .rpcs
+            .insert(name.to_string(), Arc::new(handler));
+    }
+
+    /// Arguments of every call made to RPC `name`, in order.
+    pub fn rpc_calls(&self, name: &str) -> Vec<Value> {
+        self.inner
+            .lock()
+            .unwrap()
+            .rpc_calls
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, args)| args.clone())
+            .collect()
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.inner.lock().unwrap().requests.clone()
+    }
+
+    pub fn object(&self, bucket: &str, key: &str) -> Option<StoredObject> {
+        self.inner
+            .lock()
+            .unwrap()
+            .objects
+            .get(&(bucket.to_string(), key.to_string()))
+            .cloned()
+    }
+
+    pub fn objects(&self, bucket: &str) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .inner
+            .lock()
+            .unwrap()
+            .objects
+            .keys()
+            .filter(|(b, _)| b == bucket)
+            .map(|(_, k)| k.clone())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn put_object(&self, bucket: &str, key: &str, content_type: &str, bytes: &[u8]) {
+        self.inner.lock().unwrap().objects.insert(
+            (bucket.to_string(), key.to_string()),
+            StoredObject {
+                content_type: content_type.to_string(),
+                bytes: bytes.to_vec(),
+            },
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PostgREST
+// ---------------------------------------------------------------------------
+
+async fn rest(
+    State(inner): State<Shared>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let path = uri.path().trim_start_matches("/rest/v1/").to_string();
+    let query = parse_query(uri.query().unwrap_or(""));
+    let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+    let mut db = inner.lock().unwrap();
+    db.requests.push(RecordedRequest {
+        method: method.clone(),
+        path: path.clone(),
+        query: query.clone(),
+        body: body.clone(),
+    });
+
+    if let Some(name) = path.strip_prefix("rpc/") {
+        db.rpc_calls.push((name.to_string(), body.clone()));
+        let handler = db.rpcs.get(name).cloned();
+        drop(db);
+        return match handler {
+            Some(h) => {
+                let (status, value) = h(&body);
+                (status, Json(value)).into_response()
+            }
+            None => pg_error(
+                StatusCode::NOT_FOUND,
+                "PGRST202",
+                &format!("Could not find the function public.{name}"),
+            ),
+        };
+    }
+
+    let params = QueryParams::parse(&query, &headers);
+    let prefer = header_str(&headers, "prefer");
+    let table = db.tables.entry(path.clone()).or_default();
+
+    match method {
+        Method::GET | Method::HEAD => {
+            let mut rows: Vec<Row> = table
+                .iter()
+                .filter(|r| params.matches(r))
+                .cloned()
+                .collect();
+            params.sort(&mut rows);
+            let total = rows.len();
+            let page: Vec<Row> = rows
+                .into_iter()
+                .skip(params.offset)
+                .take(params.limit.unwrap_or(usize::MAX))
+                .collect();
+            respond(&headers, &params, page, total, StatusCode::OK)
+        }
+        Method::POST => {
+            let incoming: Vec<Row> = match body {
+                Value::Array(items) => items
+                    .into_iter()
+                    .filter_map(|v| v.as_object().cloned())
+                    .collect(),
+                Value::Object(o) => vec![o],
+                _ => return pg_error(StatusCode::BAD_REQUEST, "PGRST102", "Empty or invalid json"),
+            };
+            let merge = prefer.contains("resolution=merge-duplicates");
+            let ignore = prefer.contains("resolution=ignore-duplicates");
+            let conflict_cols: Vec<String> = params
+                .on_conflict
+                .clone()
+                .unwrap_or_else(|| vec!["id".to_string()]);
+
+            let mut out = vec![];
+            for row in incoming {
+                let existing = (merge || ignore)
+                    .then(|| {
+                        table.iter_mut().find(|r| {
+                            conflict_cols
+                                .iter()
+                                .all(|c| row.get(c).is_some() && r.get(c) == row.get(c))
+                        })
+                    })
+                    .flatten();
+                match existing {
+                    Some(current) if merge => {
+                        for (k, v) in row {
+                            current.insert(k, v);
+                        }
+                        touch(current);
+                        out.push(current.clone());
+                    }
+                    Some(_) => {}
+                    None => {
+                        let row = with_defaults(&path, row);
+                        table.push(row.clone());
+                        out.push(row);
+                    }
+                }
+            }
+            if prefer.contains("return=representation") {
+                let total = out.len();
+                respond(&headers, &params, out, total, StatusCode::CREATED)
+            } else {
+                StatusCode::CREATED.into_response()
+            }
+        }
+        Method::PATCH => {
+            let patch = body.as_object().cloned().unwrap_or_default();
+            let mut out = vec![];
+            for row in table.iter_mut().filter(|r| params.matches(r)) {
+                for (k, v) in &patch {
+                    row.insert(k.clone(), v.clone());
+                }
+                if !patch.contains_key("updated_at") {
+                    touch(row);
+                }
+                out.push(row.clone());
+            }
+            let total = out.len();
+            respond(&headers, &params, out, total, StatusCode::OK)
+        }
+        Method::DELETE => {
+            let (removed, kept): (Vec<Row>, Vec<Row>) =
+                table.drain(..).partition(|r| params.matches(r));
+            *table = kept;
+            let total = removed.len();
+            respond(&headers, &params, removed, total, StatusCode::OK)
+        }
+        _ => pg_error(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "PGRST117",
+            "Unsupported HTTP method",
+        ),
+    }
+}
+
+fn respond(
+    headers: &HeaderMap,
+    params: &QueryParams,
+    rows: Vec<Row>,
+    total: usize,
+    status: StatusCode,
+) -> Response {
+    let projected: Vec<Value> = rows
+        .iter()
+        .map(|r| Value::Object(params.project(r)))
+        .collect();
+
+    if header_str(headers, "accept").contains("vnd.pgrst.object+json") {
+        if projected.len() != 1 {
+            return pg_error(
+                StatusCode::NOT_ACCEPTABLE,
+                "PGRST116",
+                "JSON object requested, multiple (or no) rows returned",
+            );
+        }
+        return (status, Json(projected.into_iter().next().unwrap())).into_response();
+    }
+
+    let range = if projected.is_empty() {
+        format!("*/{total}")
+    } else {
+        format!(
+            "{}-{}/{total}",
+            params.offset,
+            params.offset + projected.len() - 1
+        )
+    };
+    (
+        status,
+        [(header::CONTENT_RANGE, range)],
+        Json(Value::Array(projected)),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Default)]
+struct QueryParams {
+    select: Option<String>,
+    filters: Vec<(String, String)>,
+    order: Vec<(String, bool, Option<bool>)>,
+    limit: Option<usize>,
+    offset: usize,
+    on_conflict: Option<Vec<String>>,
+}
+
+impl QueryParams {
+    fn parse(query: &[(String, String)], headers: &HeaderMap) -> Self {
+        let mut p = QueryParams::default();
+        for (k, v) in query {
+            match k.as_str() {
+                "select" => p.select = Some(v.clone()),
+                "order" => {
+                    for part in v.split(',') {
+                        let mut bits = part.split('.');
+                        let col = bits.next().unwrap_or("").to_string();
+                        let mut desc = false;
+                        let mut nulls_first = None;
+                        for b in bits {
+                            match b {
+                                "desc" => desc = true,
+                                "asc" => desc = false,
+                                "nullsfirst" => nulls_first = Some(true),
+                                "nullslast" => nulls_first = Some(false),
+                                _ => {}
+                            }
+                        }
+                        p.order.push((col, desc, nulls_first));
+                    }
+                }
+                "limit" => p.limit = v.parse().ok(),
+                "offset" => p.offset = v.parse().unwrap_or(0),
+                "on_conflict" => {
+                    p.on_conflict = Some(v.split(',').map(|s| s.trim().to_string()).collect())
+                }
+                "columns" => {}
+                // Foreign-table modifiers such as `cities.limit`.
+                _ if k.contains('.') => {}
+                _ => p.filters.push((k.clone(), v.clone())),
+            }
+        }
+        // `Builder::limit`/`range` send a Range header rather than query params.
+        let range = header_str(headers, "range");
+        if let Some((lo, hi)) = range.split_once('-') {
+            if let (Ok(lo), Ok(hi)) = (lo.trim().parse::<usize>(), hi.trim().parse::<usize>()) {
+                p.offset = lo;
+                p.limit = Some(hi.saturating_sub(lo) + 1);
+            }
+        }
+        p
+    }
+
+    fn matches(&self, row: &Row) -> bool {
+        self.filters.iter().all(|(k, v)| match k.as_str() {
+            "or" => logical(row, v).iter().any(|b| *b),
+            "and" => logical(row, v).iter().all(|b| *b),
+            _ => eval(row, k, v),
+        })
+    }
+
+    fn sort(&self, rows: &mut [Row]) {
+        if self.order.is_empty() {
+            return;
+        }
+        rows.sort_by(|a, b| {
+            for (col, desc, nulls_first) in &self.order {
+                let (x, y) = (
+                    a.get(col).unwrap_or(&Value::Null),
+                    b.get(col).unwrap_or(&Value::Null),
+                );
+                // Postgres defaults: NULLS LAST for ASC, NULLS FIRST for DESC.
+                let nulls_first = nulls_first.unwrap_or(*desc);
+                let ord = match (x.is_null(), y.is_null()) {
+                    (true, true) => Ordering::Equal,
+                    (true, false) if nulls_first => Ordering::Less,
+                    (true, false) => Ordering::Greater,
+                    (false, true) if nulls_first => Ordering::Greater,
+                    (false, true) => Ordering::Less,
+                    (false, false) => {
+                        let o = compare_values(x, y);
+                        if *desc {
+                            o.reverse()
+                        } else {
+                            o
+                        }
+                    }
+                };
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            Ordering::Equal
+        });
+    }
+
+    fn project(&self, row: &Row) -> Row {
+        let Some(select) = self.select.as_deref().filter(|s| s.trim() != "*") else {
+            return row.clone();
+        };
+        let mut out = Row::new();
+        for item in split_top_level(select) {
+            let item = item.trim();
+            if item.is_empty() {
+                continue;
+            }
+            if item == "*" {
+                for (k, v) in row {
+                    out.insert(k.clone(), v.clone());
+                }
+                continue;
+            }
+            let (alias, expr) = match item.split_once(':') {
+                Some((a, e)) if !a.contains('(') && !e.starts_with(':') => {
+                    (Some(a.trim()), e.trim())
+                }
+                _ => (None, item),
+            };
+            if let Some(idx) = expr.find('(') {
+                // Embedded resource: not joined by the mock.
+                let name = expr[..idx].split('!').next().unwrap_or("").trim();
+                out.insert(alias.unwrap_or(name).to_string(), Value::Null);
+                continue;
+            }
+            let col = expr.split("::").next().unwrap_or(expr).trim();
+            let value = row.get(col).cloned().unwrap_or(Value::Null);
+            out.insert(alias.unwrap_or(col).to_string(), value);
+        }
+        out
+    }
+}
+
+/// Evaluates the conditions inside `or=(...)` / `and=(...)`.
+fn logical(row: &Row, expr: &str) -> Vec<bool> {
+    let inner = expr
+        .trim()
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix(')'))
+        .unwrap_or(expr);
+    split_top_level(inner)
+        .into_iter()
+        .map(|cond| {
+            let cond = cond.trim();
+            if cond.starts_with("and(") {
+                return logical(row, &cond[3..]).iter().all(|b| *b);
+            }
+            if cond.starts_with("or(") {
+                return logical(row, &cond[2..]).iter().any(|b| *b);
+            }
+            match cond.split_once('.') {
+                Some((col, rest)) => eval(row, col.trim(), rest),
+                None => true,
+            }
+        })
+        .collect()
+}
+
+fn eval(row: &Row, col: &str, expr: &str) -> bool {
+    let (negate, expr) = match expr.strip_prefix("not.") {
+        Some(rest) => (true, rest),
+        None => (false, expr),
+    };
+    let (op, val) = expr.split_once('.').unwrap_or((expr, ""));
+    let cell = row.get(col).unwrap_or(&Value::Null);
+    let cell_s = scalar(cell);
+    let result = match op {
+        "eq" => cell_s.as_deref() == Some(val),
+        "neq" => cell_s.as_deref().is_some_and(|c| c != val),
+        "gt" => compare_str(cell, val).is_some_and(|o| o == Ordering::Greater),
+        "gte" => compare_str(cell, val).is_some_and(|o| o != Ordering::Less),
+        "lt" => compare_str(cell, val).is_some_and(|o| o == Ordering::Less),
+        "lte" => compare_str(cell, val).is_some_and(|o| o != Ordering::Greater),
+        "in" => {
+            let list = val.trim_start_matches('(').trim_end_matches(')');
+            cell_s.as_deref().is_some_and(|c| {
+                split_top_level(list)
+                    .iter()
+                    .any(|item| item.trim().trim_matches('"') == c)
+            })
+        }
+        "is" => match val {
+            "null" => cell.is_null(),
+            "true" => cell == &Value::Bool(true),
+            "false" => cell == &Value::Bool(false),
+            _ => false,
+        },
+        "like" => cell_s.as_deref().is_some_and(|c| like(c, val)),
+        "ilike" => cell_s
+            .as_deref()
+            .is_some_and(|c| like(&c.to_lowercase(), &val.to_lowercase())),
+        "cs" => match cell {
+            Value::Array(items) => {
+                let wanted = val.trim_start_matches('{').trim_end_matches('}');
+                wanted.split(',').filter(|w| !w.is_empty()).all(|w| {
+                    items
+                        .iter()
+                        .any(|i| scalar(i).as_deref() == Some(w.trim_matches('"')))
+                })
+            }
+            _ => false,
+        },
+        _ => true,
+    };
+    result != negate
+}
+
+fn like(value: &str, pattern: &str) -> bool {
+    let pattern = pattern.replace('%', "*");
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return value == pattern;
+    }
+    let mut rest = value;
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() {
+            continue;
+        }
+        if i == 0 {
+            match rest.strip_prefix(part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else {
+            match rest.find(part) {
+                Some(idx) => rest = &rest[idx + part.len()..],
+                None => return false,
+            }
+        }
+    }
+    true
+}
+
+fn scalar(v: &Value) -> Option<String> {
+    match v {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn compare_str(cell: &Value, val: &str) -> Option<Ordering> {
+    let c = scalar(cell)?;
+    match (c.parse::<f64>(), val.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b),
+        _ => Some(c.as_str().cmp(val)),
+    }
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x
+            .as_f64()
+            .partial_cmp(&y.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        _ => scalar(a).cmp(&scalar(b)),
+    }
+}
+
+/// Splits on commas that are not nested inside parentheses.
+fn split_top_level(s: &str) -> Vec<String> {
+    let mut out = vec![];
+    let mut depth = 0i32;
+    let mut cur = String::new();
+    for ch in s.chars() {
+        match ch {
+            '(' => {
+                depth += 1;
+                cur.push(ch);
+            }
+            ')' => {
+                depth -= 1;
+                cur.push(ch);
+            }
+            ',' if depth == 0 => out.push(std::mem::take(&mut cur)),
+            _ => cur.push(ch),
+        }
+    }
+    if !cur.is_empty() {
+        out.push(cur);
+    }
+    out
+}
+
+/// Column defaults the migrations declare for NOT NULL columns that inserts
+/// are expected to leave out.
+fn column_defaults(table: &str) -> Vec<(&'static str, Value)> {
+    match table {
+        "agency_invoices" => vec![
+            ("tax_treatment", json!("standard")),
+            ("prices_include_tax", json!(false)),
+            ("amount_paid_cents", json!(0)),
+            ("amount_credited_cents", json!(0)),
+        ],
+        _ => vec![],
+    }
+}
+
+fn with_defaults(table: &str, mut row: Row) -> Row {
+    for (column, value) in column_defaults(table) {
+        row.entry(column).or_insert(value);
+    }
+    let now = chrono::Utc::now().to_rfc3339();
+    row.entry("id")
+        .or_insert_with(|| json!(uuid::Uuid::new_v4().to_string()));
+    row.entry("created_at").or_insert_with(|| json!(now));
+    row.entry("updated_at").or_insert_with(|| json!(now));
+    row
+}
+
+fn touch(row: &mut Row) {
+    if row.contains_key("updated_at") {
+        row.insert(
+            "updated_at".to_string(),
+            json!(chrono::Utc::now().to_rfc3339()),
+        );
+    }
+}
+
+fn pg_error(status: StatusCode, code: &str, message: &str) -> Response {
+    (
+        status,
+        Json(json!({ "code": code, "message": message, "details": null, "hint": null })),
+    )
+        .into_response()
+}
+
+// ---------------------------------------------------------------------------
+// Storage
+// ---------------------------------------------------------------------------
+
+async fn storage(
+    State(inner): State<Shared>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let path = urlencoding::decode(uri.path().trim_start_matches("/storage/v1/"))
+        .map(|s| s.into_owned())
+        .unwrap_or_default();
+    let json_body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+    let mut db = inner.lock().unwrap();
+    db.requests.push(RecordedRequest {
+        method: method.clone(),
+        path: path.clone(),
+        query: parse_query(uri.query().unwrap_or("")),
+        body: json_body.clone(),
+    });
+
+    let Some(object_path) = path.strip_prefix("object/") else {
+        // Bucket management and anything else: accept and ignore.
+        return (StatusCode::OK, Json(json!({}))).into_response();
+    };
+
+    if let Some(rest) = object_path.strip_prefix("sign/") {
+        return match split_bucket(rest) {
+            Some(_) => (
+                StatusCode::OK,
+                Json(json!({ "signedURL": format!("/object/sign/{rest}?token=mock-token") })),
+            )
+                .into_response(),
+            None => storage_error(StatusCode::BAD_REQUEST, "invalid_path"),
+        };
+    }
+
+    if let Some(bucket) = object_path.strip_prefix("list/") {
+        let prefix = json_body
+            .get("prefix")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .trim_end_matches('/')
+            .to_string();
+        let mut items: Vec<Value> = db
+            .objects
+            .iter()
+            .filter(|((b, k), _)| b == bucket && (prefix.is_empty() || k.starts_with(&prefix)))
+            .map(|((_, k), obj)| {
+                let name = k
+                    .strip_prefix(&prefix)
+                    .unwrap_or(k)
+                    .trim_start_matches('/')
+                    .to_string();
+                json!({
+                    "name": name,
+                    "id": uuid::Uuid::new_v4().to_string(),
+                    "metadata": { "size": obj.bytes.len(), "mimetype": obj.content_type },
+                })
+            })
+            .collect();
+        items.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+        return (StatusCode::OK, Json(Value::Array(items))).into_response();
+    }
+
+    let read_path = object_path
+        .strip_prefix("public/")
+        .or_else(|| object_path.strip_prefix("authenticated/"))
+        .unwrap_or(object_path);
+
+    match method {
+        Method::GET => match split_bucket(read_path).and_then(|k| db.objects.get(&k)) {
+            Some(obj) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, obj.content_type.clone())],
+                obj.bytes.clone(),
+            )
+                .into_response(),
+            None => storage_error(StatusCode::NOT_FOUND, "not_found"),
+        },
+        Method::POST | Method::PUT => {
+            let Some(key) = split_bucket(object_path) else {
+                return storage_error(StatusCode::BAD_REQUEST, "invalid_path");
+            };
+            let upsert = header_str(&headers, "x-upsert") == "true" || method == Method::PUT;
+            if !upsert && db.objects.contains_key(&key) {
+                return storage_error(StatusCode::CONFLICT, "Duplicate");
+            }
+            let content_type = headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("application/octet-stream")
+                .to_string();
+            let key_str = format!("{}/{}", key.0, key.1);
+            db.objects.insert(
+                key,
+                StoredObject {
+                    content_type,
+                    bytes: body.to_vec(),
+                },
+            );
+            (
+                StatusCode::OK,
+                Json(json!({ "Key": key_str, "Id": uuid::Uuid::new_v4().to_string() })),
+            )
+                .into_response()
+        }
+        Method::DELETE => match split_bucket(object_path) {
+            Some(key) => match db.objects.remove(&key) {
+                Some(_) => (
+                    StatusCode::OK,
+                    Json(json!({ "message": "Successfully deleted" })),
+                )
+                    .into_response(),
+                None => storage_error(StatusCode::NOT_FOUND, "not_found"),
+            },
+            None => {
+                // Bulk delete: DELETE /object/{bucket} with {"prefixes": [...]}.
+                let bucket = object_path.trim_end_matches('/').to_string();
+                let prefixes: Vec<String> = json_body
+                    .get("prefixes")
+                    .and_then(|v| v.as_array())
+                    .map(|a| {
+                        a.iter()
+                            .filter_map(|v| v.as_str().map(String::from))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let mut removed = vec![];
+                for p in prefixes {
+                    if db.objects.remove(&(bucket.clone(), p.clone())).is_some() {
+                        removed.push(json!({ "name": p }));
+                    }
+                }
+                (StatusCode::OK, Json(Value::Array(removed))).into_response()
+            }
+        },
+        _ => storage_error(StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed"),
+    }
+}
+
+fn split_bucket(path: &str) -> Option<(String, String)> {
+    let (bucket, key) = path.split_once('/')?;
+    if bucket.is_empty() || key.is_empty() {
+        return None;
+    }
+    Some((bucket.to_string(), key.to_string()))
+}
+
+fn storage_error(status: StatusCode, error: &str) -> Response {
+    (
+        status,
+        Json(
+            json!({ "statusCode": status.as_u16().to_string(), "error": error, "message": error }),
+        ),
+    )
+        .into_response()
+}
+
+// ---------------------------------------------------------------------------
+
+fn header_str(headers: &HeaderMap, name: &str) -> String {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+pub(crate) fn parse_query(raw: &str) -> Vec<(String, String)> {
+    raw.split('&')
+        .filter(|p| !p.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(k), decode(v))
+        })
+        .collect()
+}
+
+fn decode(s: &str) -> String {
+    let s = s.replace('+', " ");
+    urlencoding::decode(&s).map(|c| c.into_owned()).unwrap_or(s)
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use common::{TestApp, TestUser, DOCUSEAL_WEBHOOK_SECRET};
+use serde_json::json;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unauthenticated_delivery_is_rejected() {
+    let app = TestApp::spawn().await;
+    app.supabase.seed(
+        "scouting_offers",
+        json!({ "id": "offer-1", "docuseal_submission_id": "41", "status": "sent" }),
+    );
+    let payload = json!({
+        "event_type": "submission.completed",
+        "timestamp": "2026-10-18T09:00:00Z",
+        "data": { "id": 41 },
+    });
+
+    let unsigned = app
+        .http
+        .post(format!("{}/webhooks/docuseal", app.url))
+        .json(&payload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unsigned.status(), 401);
+    let wrong_secret = app
+        .http
+        .post(format!("{}/webhooks/docuseal", app.url))
+        .header("X-Docuseal-Secret", "guessed")
+        .json(&payload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(wrong_secret.status(), 401);
+
+    assert_eq!(
+        app.supabase
+            .find("scouting_offers", "id", "offer-1")
+            .unwrap()["status"],
+        "sent"
+    );
+    assert!(app.supabase.rows("webhook_events").is_empty());
+
+    let with_secret = app
+        .http
+        .post(format!("{}/webhooks/docuseal", app.url))
+        .header("X-Docuseal-Secret", DOCUSEAL_WEBHOOK_SECRET)
+        .json(&payload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(with_secret.status(), 200);
+    assert_eq!(
+        app.supabase
+            .find("scouting_offers", "id", "offer-1")
+            .unwrap()["status"],
+        "completed"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn events_are_routed_to_the_owning_record_from_either_url() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    app.supabase.seed(
+        "scouting_offers",
+        json!({ "id": "offer-1", "prospect_id": "prospect-1", "docuseal_submission_id": "41", "status": "sent" }),
+    );
+    app.supabase.seed(
+        "scouting_prospects",
+        json!({ "id": "prospect-1", "agency_id": agency.id, "status": "offer_sent" }),
+    );
+    app.supabase.seed(
+        "license_submissions",
+        json!({
+            "id": "ls-1",
+            "docuseal_submission_id": "42",
+            "requires_agency_signature": true,
+            "agency_submitter_id": 501,
+            "agency_signed_at": null,
+            "status": "sent",
+        }),
+    );
+
+    // A scouting contract event arriving on the licensing URL.
+    let (status, body) = app
+        .docuseal_webhook(
+            "/api/webhooks/licenseContract",
+            &json!({
+                "event_type": "submission.completed",
+                "timestamp": "2026-10-18T09:00:00Z",
+                "data": { "id": 41, "documents": [{ "url": "https://docs.example/offer.pdf" }] },
+            }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    let offer = app
+        .supabase
+        .find("scouting_offers", "id", "offer-1")
+        .unwrap();
+    assert_eq!(offer["status"], "completed");
+    assert_eq!(
+        offer["signed_document_url"],
+        "https://docs.example/offer.pdf"
+    );
+    assert_eq!(
+        app.supabase
+            .find("scouting_prospects", "id", "prospect-1")
+            .unwrap()["status"],
+        "signed"
+    );
+
+    // The agency countersigns the license, then the client completes it.
+    let (status, body) = app
+        .docuseal_webhook(
+            "/webhooks/docuseal",
+            &json!({
+                "event_type": "form.viewed",
+                "timestamp": "2026-10-18T09:01:00Z",
+                "data": { "id": 501, "submission_id": 42 },
+            }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(
+        app.supabase
+            .find("license_submissions", "id", "ls-1")
+            .unwrap()["status"],
+        "agency_pending"
+    );
+
+    let (status, body) = app
+        .docuseal_webhook(
+            "/webhooks/docuseal",
+            &json!({
+                "event_type": "form.completed",
+                "timestamp": "2026-10-18T09:02:00Z",
+                "data": { "id": 501, "submission_id": 42 },
+            }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    let sub = app
+        .supabase
+        .find("license_submissions", "id", "ls-1")
+        .unwrap();
+    assert_eq!(sub["status"], "client_pending");
+    assert!(sub["agency_signed_at"].is_string());
+
+    let (status, body) = app
+        .docuseal_webhook(
+            "/webhooks/docuseal",
+            &json!({
+                "event_type": "submission.completed",
+                "timestamp": "2026-10-18T09:03:00Z",
+                "data": { "id": 42, "documents": [{ "url": "https://docs.example/license.pdf" }] },
+            }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    let sub = app
+        .supabase
+        .find("license_submissions", "id", "ls-1")
+        .unwrap();
+    assert_eq!(sub["status"], "completed");
+    assert_eq!(
+        sub["signed_document_url"],
+        "https://docs.example/license.pdf"
+    );
+    assert_eq!(
+        app.supabase
+            .find("scouting_offers", "id", "offer-1")
+            .unwrap()["status"],
+        "completed"
+    );
+}
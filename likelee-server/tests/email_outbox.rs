@@ -0,0 +1,130 @@
+mod common;
+
+use chrono::{Duration, SecondsFormat, Utc};
+use common::{TestApp, TestUser};
+use likelee_server::email::outbox;
+use serde_json::{json, Value};
+
+/// Waits for the background delivery attempt started by `enqueue`.
+async fn wait_for_attempt(app: &TestApp, id: &str) -> Value {
+    for _ in 0..150 {
+        if let Some(row) = app.supabase.find("email_outbox", "id", id) {
+            if row["attempts"] != 0 && row["status"] != "sending" {
+                return Value::Object(row);
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("email {id} was never attempted");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reminders_are_delivered_and_logged_per_talent() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let talent = app.supabase.seed(
+        "agency_users",
+        json!({
+            "agency_id": agency.id,
+            "role": "talent",
+            "full_legal_name": "Mia Stone",
+            "email": "mia@talent.test",
+        }),
+    );
+    let talent_id = talent["id"].as_str().unwrap();
+
+    let (status, body) = app
+        .post(
+            "/api/agency/digitals/reminders",
+            &agency,
+            json!({ "talent_ids": [talent_id] }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["sent"], 1);
+
+    let mails = app.mail.wait_for(1);
+    assert_eq!(mails.len(), 1);
+    assert_eq!(mails[0].to, vec!["mia@talent.test".to_string()]);
+
+    let queued = app.supabase.rows("email_outbox");
+    assert_eq!(queued.len(), 1);
+    let row = wait_for_attempt(&app, queued[0]["id"].as_str().unwrap()).await;
+    assert_eq!(row["status"], "sent");
+    assert_eq!(row["attempts"], 1);
+    assert!(row["smtp_response"].as_str().unwrap().starts_with("250"));
+
+    let (status, log) = app
+        .get(
+            &format!("/api/agency/email-deliveries?talent_id={talent_id}"),
+            &agency,
+        )
+        .await;
+    assert_eq!(status, 200, "{log}");
+    let log = log.as_array().unwrap();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0]["category"], "digitals_reminder");
+    assert_eq!(log[0]["status"], "sent");
+    assert!(log[0].get("body").is_none());
+
+    let (_, other) = app
+        .get("/api/agency/email-deliveries", &TestUser::agency())
+        .await;
+    assert_eq!(other, json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn transient_failures_are_retried_until_max_attempts() {
+    // Nothing listens on the discard port, so every attempt fails to connect.
+    let app = TestApp::spawn_with(|state| state.smtp_port = 9).await;
+    let agency = TestUser::agency();
+
+    let (status, body) = app
+        .post(
+            "/api/integrations/core/send-email",
+            &agency,
+            json!({ "to": "client@acme.test", "subject": "Hi", "body": "Hello" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    let id = body["email_id"].as_str().unwrap().to_string();
+
+    let row = wait_for_attempt(&app, &id).await;
+    assert_eq!(row["status"], "queued");
+    assert_eq!(row["attempts"], 1);
+    assert!(row["last_error"].as_str().is_some_and(|e| !e.is_empty()));
+    let retry_at = row["next_attempt_at"].as_str().unwrap().to_string();
+    assert!(retry_at > Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+
+    // Not due yet: the worker leaves it alone.
+    let summary = outbox::deliver_due(&app.state, 10).await.unwrap();
+    assert_eq!(summary["due"], 0);
+
+    // Last allowed attempt: the message is given up on.
+    let past = (Utc::now() - Duration::seconds(1)).to_rfc3339_opts(SecondsFormat::Millis, true);
+    app.supabase.update(
+        "email_outbox",
+        &id,
+        json!({ "attempts": 4, "next_attempt_at": past }),
+    );
+    let summary = outbox::deliver_due(&app.state, 10).await.unwrap();
+    assert_eq!(summary["failed"], 1, "{summary}");
+    let row = app.supabase.find("email_outbox", "id", &id).unwrap();
+    assert_eq!(row["status"], "failed");
+    assert_eq!(row["attempts"], 5);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn malformed_addresses_are_rejected_before_queueing() {
+    let app = TestApp::spawn().await;
+    let (status, body) = app
+        .post(
+            "/api/integrations/core/send-email",
+            &TestUser::agency(),
+            json!({ "to": "not an address", "subject": "Hi", "body": "Hello" }),
+        )
+        .await;
+    assert_eq!(status, 400, "{body}");
+    assert_eq!(body["code"], "invalid_to_address");
+    assert!(app.supabase.rows("email_outbox").is_empty());
+}
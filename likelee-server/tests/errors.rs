@@ -0,0 +1,72 @@
+mod common;
+
+use common::{TestApp, TestUser};
+use serde_json::Value;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn legacy_errors_use_the_standard_shape_and_echo_the_request_id() {
+    let app = TestApp::spawn().await;
+    let resp = app
+        .http
+        .get(format!("{}/api/invoices", app.url))
+        .header("x-request-id", "req-abc-123")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    assert_eq!(resp.headers()["x-request-id"], "req-abc-123");
+    assert_eq!(resp.headers()["content-type"], "application/json");
+
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "error");
+    assert_eq!(body["code"], "unauthorized");
+    assert_eq!(body["request_id"], "req-abc-123");
+    assert!(body["error"].as_str().is_some_and(|m| !m.is_empty()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn app_errors_carry_stable_codes() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+
+    let (status, body) = app
+        .get(&format!("/api/invoices/{}", uuid::Uuid::new_v4()), &agency)
+        .await;
+    assert_eq!(status, 404);
+    assert_eq!(body["code"], "not_found");
+    assert!(body["request_id"].as_str().is_some_and(|id| !id.is_empty()));
+
+    let (status, body) = app
+        .post(
+            "/api/invoices",
+            &agency,
+            serde_json::json!({
+                "client_id": "client-1",
+                "invoice_date": "2026-10-01",
+                "due_date": "2026-10-31",
+                "invoice_number": "INV-1",
+                "items": [],
+            }),
+        )
+        .await;
+    assert_eq!(status, 400, "{body}");
+    assert_eq!(body["code"], "bad_request");
+    assert_eq!(
+        body["error"],
+        "invoice_number is system-generated and cannot be provided"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn successful_responses_get_a_generated_request_id() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let resp = app
+        .request(reqwest::Method::GET, "/api/invoices", &agency)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let id = resp.headers()["x-request-id"].to_str().unwrap();
+    assert!(uuid::Uuid::parse_str(id).is_ok());
+}
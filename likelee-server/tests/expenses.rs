@@ -0,0 +1,254 @@
+mod common;
+
+use common::{
+    fixtures::{seed_agency, seed_talent},
+    TestApp, TestUser,
+};
+use reqwest::multipart::{Form, Part};
+use reqwest::Method;
+use serde_json::{json, Value};
+
+const PDF: &[u8] = b"%PDF-1.7 receipt";
+
+fn receipt_form(name: &str, bytes: &'static [u8]) -> Form {
+    Form::new().part("file", Part::bytes(bytes).file_name(name.to_string()))
+}
+
+/// A booking of one talent for the agency's client; returns the client,
+/// talent and booking ids.
+fn seed_booking(app: &TestApp, agency: &TestUser) -> (String, String, String) {
+    let client_id = seed_agency(app, agency);
+    let talent_id = seed_talent(app, agency, "Ava Stone");
+    let booking = app.supabase.seed(
+        "bookings",
+        json!({
+            "agency_user_id": agency.id,
+            "talent_id": talent_id,
+            "client_id": client_id,
+            "date": "2026-09-01",
+        }),
+    );
+    let booking_id = booking["id"].as_str().unwrap().to_string();
+    (client_id, talent_id, booking_id)
+}
+
+async fn create(app: &TestApp, agency: &TestUser, body: Value) -> (u16, Value) {
+    app.post("/api/expenses", agency, body).await
+}
+
+async fn send(app: &TestApp, method: Method, path: &str, agency: &TestUser) -> u16 {
+    let resp = app.request(method, path, agency).send().await.unwrap();
+    resp.status().as_u16()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn expenses_are_reviewed_reimbursed_and_rebilled_once() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let (client_id, talent_id, booking_id) = seed_booking(&app, &agency);
+
+    // Tagging the booking fills in its talent and client.
+    let (status, taxi) = create(
+        &app,
+        &agency,
+        json!({
+            "name": "Taxi to set",
+            "category": "travel",
+            "expense_date": "2026-09-01",
+            "amount_cents": 4_500,
+            "status": "pending",
+            "booking_id": booking_id,
+            "billable": true,
+        }),
+    )
+    .await;
+    assert_eq!(status, 200, "{taxi}");
+    assert_eq!(taxi["status"], "submitted");
+    assert_eq!(taxi["client_id"], client_id.as_str());
+    assert_eq!(taxi["talent_id"], talent_id.as_str());
+    let taxi_id = taxi["id"].as_str().unwrap();
+
+    let (status, body) = create(
+        &app,
+        &agency,
+        json!({ "name": "Props", "category": "supplies", "expense_date": "2026-09-02", "billable": true }),
+    )
+    .await;
+    assert_eq!(
+        (status, body["code"].as_str()),
+        (400, Some("billable_requires_client"))
+    );
+    let (_, props) = create(
+        &app,
+        &agency,
+        json!({
+            "name": "Props",
+            "category": "supplies",
+            "expense_date": "2026-09-02",
+            "amount_cents": 12_000,
+            "client_id": client_id,
+            "billable": true,
+        }),
+    )
+    .await;
+    assert_eq!(props["status"], "approved");
+    let props_id = props["id"].as_str().unwrap();
+
+    // Receipts: images and PDFs only, sniffed from the bytes.
+    let resp = app
+        .request(
+            Method::POST,
+            &format!("/api/expenses/{taxi_id}/receipts"),
+            &agency,
+        )
+        .multipart(receipt_form("taxi.pdf", PDF))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let receipt: Value = resp.json().await.unwrap();
+    let path = receipt["storage_path"].as_str().unwrap();
+    assert!(path.starts_with(&format!(
+        "agencies/{}/expenses/{taxi_id}/receipts/",
+        agency.id
+    )));
+    let object = app.supabase.object("likelee-private", path).unwrap();
+    assert_eq!(object.content_type, "application/pdf");
+    let resp = app
+        .request(
+            Method::POST,
+            &format!("/api/expenses/{taxi_id}/receipts"),
+            &agency,
+        )
+        .multipart(receipt_form("notes.pdf", b"plain text"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let (_, detail) = app.get(&format!("/api/expenses/{taxi_id}"), &agency).await;
+    assert_eq!(detail["receipts"].as_array().unwrap().len(), 1, "{detail}");
+
+    // Only approved expenses are reimbursed or rebilled.
+    let path = |action: &str| format!("/api/expenses/{taxi_id}/{action}");
+    assert_eq!(
+        send(&app, Method::POST, &path("reimburse"), &agency).await,
+        409
+    );
+    assert_eq!(
+        send(&app, Method::POST, &path("reject"), &agency).await,
+        200
+    );
+    assert_eq!(
+        send(&app, Method::POST, &path("approve"), &agency).await,
+        409
+    );
+    assert_eq!(
+        send(&app, Method::POST, &path("submit"), &agency).await,
+        200
+    );
+    let (_, approved) = app.post(&path("approve"), &agency, json!({})).await;
+    assert_eq!(approved["status"], "approved", "{approved}");
+    assert!(approved["reviewed_at"].is_string());
+
+    let (status, rebilled) = app
+        .post(
+            "/api/expenses/rebill",
+            &agency,
+            json!({ "client_id": client_id }),
+        )
+        .await;
+    assert_eq!(status, 200, "{rebilled}");
+    let invoice_id = rebilled["invoice"]["id"].as_str().unwrap().to_string();
+    assert_eq!(rebilled["invoice"]["status"], "draft");
+    assert_eq!(rebilled["expenses"].as_array().unwrap().len(), 2);
+    let lines = app.supabase.rows("agency_invoice_expenses");
+    let mut amounts: Vec<i64> = lines
+        .iter()
+        .filter(|l| l["invoice_id"] == invoice_id.as_str())
+        .map(|l| l["amount_cents"].as_i64().unwrap())
+        .collect();
+    amounts.sort();
+    assert_eq!(amounts, vec![4_500, 12_000]);
+
+    // Rebilled expenses are not billed or changed again.
+    let (status, again) = app
+        .post(
+            "/api/expenses/rebill",
+            &agency,
+            json!({ "client_id": client_id }),
+        )
+        .await;
+    assert_eq!(
+        (status, again["code"].as_str()),
+        (400, Some("no_rebillable_expenses"))
+    );
+    let status = app
+        .request(Method::PATCH, &format!("/api/expenses/{props_id}"), &agency)
+        .json(&json!({ "amount_cents": 1 }))
+        .send()
+        .await
+        .unwrap()
+        .status();
+    assert_eq!(status, 409);
+
+    // Reimbursing is independent of rebilling.
+    let (_, reimbursed) = app
+        .post(
+            &path("reimburse"),
+            &agency,
+            json!({ "reference": "CHK-42" }),
+        )
+        .await;
+    assert_eq!(reimbursed["status"], "reimbursed", "{reimbursed}");
+    assert_eq!(
+        send(
+            &app,
+            Method::DELETE,
+            &format!("/api/expenses/{taxi_id}"),
+            &agency
+        )
+        .await,
+        409
+    );
+
+    // Voiding the draft releases the expenses for another invoice.
+    assert_eq!(
+        send(
+            &app,
+            Method::POST,
+            &format!("/api/invoices/{invoice_id}/void"),
+            &agency
+        )
+        .await,
+        200
+    );
+    let (_, open) = app
+        .get(
+            &format!("/api/expenses?client_id={client_id}&rebilled=false"),
+            &agency,
+        )
+        .await;
+    assert_eq!(open.as_array().unwrap().len(), 2, "{open}");
+    let (status, only_props) = app
+        .post(
+            "/api/expenses/rebill",
+            &agency,
+            json!({ "client_id": client_id, "expense_ids": [props_id] }),
+        )
+        .await;
+    assert_eq!(status, 200, "{only_props}");
+    assert_eq!(only_props["expenses"][0]["id"], props_id);
+
+    let (_, other) = app.get("/api/expenses", &TestUser::agency()).await;
+    assert!(other.as_array().unwrap().is_empty(), "{other}");
+    assert_eq!(
+        send(
+            &app,
+            Method::GET,
+            &format!("/api/expenses/{taxi_id}"),
+            &TestUser::agency()
+        )
+        .await,
+        404
+    );
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use chrono::{Duration, NaiveDate, Utc};
+use common::fixtures::seed_invoice;
+use common::{TestApp, TestUser};
+use likelee_server::jobs;
+use serde_json::{json, Value};
+
+fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+async fn run_dunning(app: &TestApp) -> Value {
+    jobs::find("invoice_dunning")
+        .unwrap()
+        .run(&app.state)
+        .await
+        .unwrap()
+}
+
+fn subjects(app: &TestApp, n: usize) -> Vec<String> {
+    let mut subjects: Vec<String> = app
+        .mail
+        .wait_for(n)
+        .iter()
+        .map(|m| m.subject().unwrap())
+        .collect();
+    subjects.sort();
+    subjects
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dunning_sends_each_reminder_once_and_marks_invoices_overdue() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "email": agency.email, "agency_name": "North Studio" }),
+    );
+    app.supabase.seed(
+        "agency_email_templates",
+        json!({
+            "agency_id": agency.id,
+            "template_key": "payment_reminder_overdue",
+            "subject": "{invoice_number} is {days_overdue} days late",
+            "body": "Please pay {invoice_total}.",
+            "is_active": true,
+        }),
+    );
+    let soon = seed_invoice(&app, &agency, "INV-1", "sent", today() + Duration::days(3));
+    let due_today = seed_invoice(&app, &agency, "INV-2", "sent", today());
+    let late = seed_invoice(&app, &agency, "INV-3", "sent", today() - Duration::days(8));
+    let ancient = seed_invoice(&app, &agency, "INV-4", "sent", today() - Duration::days(60));
+    seed_invoice(&app, &agency, "INV-5", "paid", today() - Duration::days(8));
+    seed_invoice(&app, &agency, "INV-6", "draft", today());
+
+    let output = run_dunning(&app).await;
+    assert_eq!(output["marked_overdue"], 2, "{output}");
+    assert_eq!(output["sent"], 3, "{output}");
+    assert_eq!(
+        subjects(&app, 3),
+        vec![
+            "INV-3 is 8 days late",
+            "Invoice INV-2 is due today",
+            &format!(
+                "Payment Reminder - Invoice INV-1 due {}",
+                today() + Duration::days(3)
+            ),
+        ]
+    );
+
+    for (id, status) in [
+        (&soon, "sent"),
+        (&due_today, "sent"),
+        (&late, "overdue"),
+        (&ancient, "overdue"),
+    ] {
+        assert_eq!(
+            app.supabase.find("agency_invoices", "id", id).unwrap()["status"],
+            status
+        );
+    }
+    let events = app.supabase.rows("agency_invoice_reminder_events");
+    assert_eq!(events.len(), 3);
+    let late_event = events
+        .iter()
+        .find(|e| e["invoice_id"] == late.as_str())
+        .unwrap();
+    assert_eq!(late_event["reminder_type"], "after_7_days");
+    assert_eq!(
+        late_event["scheduled_for"],
+        (today() - Duration::days(1)).to_string()
+    );
+    assert_eq!(late_event["status"], "sent");
+    assert_eq!(late_event["to_email"], "billing@acme.test");
+
+    let output = run_dunning(&app).await;
+    assert_eq!(output["sent"], 0, "{output}");
+    assert_eq!(output["already_sent"], 3, "{output}");
+    assert_eq!(app.supabase.rows("agency_invoice_reminder_events").len(), 3);
+    assert_eq!(app.supabase.rows("email_outbox").len(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dunning_follows_agency_settings() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "email": agency.email, "agency_name": "North Studio" }),
+    );
+    app.supabase.seed(
+        "agency_invoice_reminder_settings",
+        json!({
+            "agency_id": agency.id,
+            "enabled_3_days_before": false,
+            "enabled_on_due_date": true,
+            "enabled_7_days_after": false,
+        }),
+    );
+    seed_invoice(&app, &agency, "INV-1", "sent", today() + Duration::days(2));
+    seed_invoice(&app, &agency, "INV-2", "sent", today());
+    let late = seed_invoice(&app, &agency, "INV-3", "sent", today() - Duration::days(9));
+
+    let output = run_dunning(&app).await;
+    assert_eq!(output["sent"], 1, "{output}");
+    assert_eq!(output["disabled"], 2, "{output}");
+    assert_eq!(subjects(&app, 1), vec!["Invoice INV-2 is due today"]);
+    // Disabled reminders still leave the invoice overdue.
+    assert_eq!(
+        app.supabase.find("agency_invoices", "id", &late).unwrap()["status"],
+        "overdue"
+    );
+
+    // Overdue invoices can still be paid.
+    let (status, body) = app
+        .post(
+            &format!("/api/invoices/{late}/mark-paid"),
+            &agency,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body[0]["status"], "paid");
+}
@@ -0,0 +1,442 @@
+mod common;
+
+use axum::http::Method;
+use common::{fixtures::seed_agency, TestApp, TestUser};
+use serde_json::{json, Value};
+
+async fn create_invoice(app: &TestApp, agency: &TestUser, client_id: &str) -> Value {
+    let (status, body) = app
+        .post(
+            "/api/invoices",
+            agency,
+            json!({
+                "client_id": client_id,
+                "invoice_date": "2026-10-01",
+                "due_date": "2026-10-31",
+                "agency_commission_bps": 2000,
+                "tax_rate_bps": 1000,
+                "items": [
+                    { "description": "Shoot day", "quantity": 2.0, "unit_price_cents": 50000 },
+                    { "description": "Usage", "unit_price_cents": 10000 },
+                ],
+                "expenses": [
+                    { "description": "Travel", "amount_cents": 5000, "taxable": false },
+                ],
+            }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    body
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invoice_lifecycle_create_send_pay() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+
+    let invoice = create_invoice(&app, &agency, &client_id).await;
+    let id = invoice["id"].as_str().unwrap().to_string();
+    assert_eq!(invoice["status"], "draft");
+    assert_eq!(invoice["invoice_number"], "INVCA0000001");
+    assert_eq!(invoice["subtotal_cents"], 110000);
+    assert_eq!(invoice["expenses_cents"], 5000);
+    assert_eq!(invoice["tax_cents"], 11000);
+    assert_eq!(invoice["total_cents"], 126000);
+    assert_eq!(app.supabase.rows("agency_invoice_items").len(), 2);
+
+    let (status, detail) = app.get(&format!("/api/invoices/{id}"), &agency).await;
+    assert_eq!(status, 200, "{detail}");
+    assert_eq!(detail["items"].as_array().unwrap().len(), 2);
+    assert_eq!(detail["expenses"][0]["amount_cents"], 5000);
+
+    let (status, sent) = app
+        .post(&format!("/api/invoices/{id}/mark-sent"), &agency, json!({}))
+        .await;
+    assert_eq!(status, 200, "{sent}");
+    assert_eq!(sent[0]["status"], "sent");
+    assert!(sent[0]["sent_at"].is_string());
+
+    let mails = app.mail.wait_for(1);
+    assert_eq!(mails.len(), 1);
+    assert_eq!(mails[0].to, vec!["billing@acme.test".to_string()]);
+    assert_eq!(
+        mails[0].subject().as_deref(),
+        Some("Invoice INVCA0000001 from North Studio")
+    );
+
+    let (status, paid) = app
+        .post(&format!("/api/invoices/{id}/mark-paid"), &agency, json!({}))
+        .await;
+    assert_eq!(status, 200, "{paid}");
+    assert_eq!(paid[0]["status"], "paid");
+    assert!(paid[0]["paid_at"].is_string());
+
+    let (status, _) = app
+        .post(&format!("/api/invoices/{id}/mark-paid"), &agency, json!({}))
+        .await;
+    assert_eq!(status, 409);
+}
+
+async fn delete(app: &TestApp, user: &TestUser, path: &str) -> (u16, Value) {
+    let resp = app
+        .request(Method::DELETE, path, user)
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status().as_u16();
+    (status, resp.json().await.unwrap_or(Value::Null))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn partial_payments_and_credit_notes_settle_the_balance() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+    let invoice = create_invoice(&app, &agency, &client_id).await;
+    let id = invoice["id"].as_str().unwrap().to_string();
+    let payments = format!("/api/invoices/{id}/payments");
+    let credit_notes = format!("/api/invoices/{id}/credit-notes");
+
+    // Drafts take no payments.
+    let (status, _) = app
+        .post(&payments, &agency, json!({ "amount_cents": 1000 }))
+        .await;
+    assert_eq!(status, 409);
+    app.post(&format!("/api/invoices/{id}/mark-sent"), &agency, json!({}))
+        .await;
+
+    let (status, body) = app
+        .post(&payments, &agency, json!({ "amount_cents": 0 }))
+        .await;
+    assert_eq!(
+        (status, body["code"].as_str()),
+        (400, Some("invalid_amount"))
+    );
+    let (status, body) = app
+        .post(
+            &payments,
+            &agency,
+            json!({ "amount_cents": 1000, "method": "barter" }),
+        )
+        .await;
+    assert_eq!(status, 400, "{body}");
+    let (status, _) = app
+        .post(&payments, &agency, json!({ "amount_cents": 126001 }))
+        .await;
+    assert_eq!(status, 409);
+
+    let (status, body) = app
+        .post(
+            &payments,
+            &agency,
+            json!({
+                "amount_cents": 50000,
+                "paid_on": "2026-10-05",
+                "method": "check",
+                "reference": "CHK-1001",
+            }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["invoice"]["status"], "partially_paid");
+    assert_eq!(body["invoice"]["amount_paid_cents"], 50000);
+    let payment_id = body["payment"]["id"].as_str().unwrap().to_string();
+
+    let (status, body) = app
+        .post(
+            &credit_notes,
+            &agency,
+            json!({ "amount_cents": 6000, "reason": " " }),
+        )
+        .await;
+    assert_eq!(status, 400, "{body}");
+    let (status, body) = app
+        .post(
+            &credit_notes,
+            &agency,
+            json!({ "amount_cents": 6000, "reason": "Usage disputed" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(
+        body["credit_note"]["credit_note_number"],
+        "INVCA0000001-CN1"
+    );
+    assert_eq!(body["invoice"]["amount_credited_cents"], 6000);
+
+    let (status, detail) = app.get(&format!("/api/invoices/{id}"), &agency).await;
+    assert_eq!(status, 200, "{detail}");
+    assert_eq!(detail["balance_due_cents"], 70000);
+    assert_eq!(detail["payments"][0]["reference"], "CHK-1001");
+    assert_eq!(detail["credit_notes"].as_array().unwrap().len(), 1);
+
+    // Marking paid settles the remaining balance with one more payment.
+    let (status, paid) = app
+        .post(&format!("/api/invoices/{id}/mark-paid"), &agency, json!({}))
+        .await;
+    assert_eq!(status, 200, "{paid}");
+    assert_eq!(paid[0]["status"], "paid");
+    assert_eq!(paid[0]["amount_paid_cents"], 120000);
+    assert_eq!(app.supabase.rows("agency_invoice_payments").len(), 2);
+
+    // Removing a payment recorded in error reopens the invoice.
+    let payment = format!("{payments}/{payment_id}");
+    let (status, body) = delete(&app, &agency, &payment).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["status"], "partially_paid");
+    assert_eq!(body["amount_paid_cents"], 70000);
+    assert!(body["paid_at"].is_null());
+
+    let (status, _) = delete(&app, &agency, &payment).await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invoices_are_taxed_per_line_and_per_jurisdiction() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+    app.supabase.seed(
+        "agency_tax_currency_settings",
+        json!({
+            "agency_id": agency.id,
+            "default_tax_rate": 20,
+            "tax_display_name": "VAT",
+            "include_tax_in_displayed_prices": false,
+            "tax_jurisdictions": {
+                "DE": { "treatment": "reverse_charge" },
+                "FR": { "label": "TVA", "prices_include_tax": true },
+            },
+        }),
+    );
+    let invoice = |extra: Value| {
+        let mut body = json!({
+            "client_id": client_id,
+            "invoice_date": "2026-10-01",
+            "agency_commission_bps": 2000,
+            "items": [
+                { "description": "Shoot day", "unit_price_cents": 12000 },
+                { "description": "Prints", "unit_price_cents": 5000, "tax_rate_bps": 500 },
+            ],
+            "expenses": [
+                { "description": "Travel", "amount_cents": 2000, "taxable": false },
+            ],
+        });
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        body
+    };
+
+    // Agency default rate, with the second line at its own rate.
+    let (status, standard) = app.post("/api/invoices", &agency, invoice(json!({}))).await;
+    assert_eq!(status, 200, "{standard}");
+    assert_eq!(standard["tax_cents"], 2650);
+    assert_eq!(standard["total_cents"], 21650);
+    let id = standard["id"].as_str().unwrap().to_string();
+    let (status, detail) = app.get(&format!("/api/invoices/{id}"), &agency).await;
+    assert_eq!(status, 200, "{detail}");
+    let summary: Vec<(i64, &str, i64)> = detail["tax_summary"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|g| {
+            (
+                g["rate_bps"].as_i64().unwrap(),
+                g["label"].as_str().unwrap(),
+                g["tax_cents"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (0, "VAT 0%", 0),
+            (500, "VAT 5%", 250),
+            (2000, "VAT 20%", 2400)
+        ]
+    );
+
+    // Prices entered with tax included.
+    let (status, inclusive) = app
+        .post(
+            "/api/invoices",
+            &agency,
+            invoice(json!({ "tax_jurisdiction": "fr" })),
+        )
+        .await;
+    assert_eq!(status, 200, "{inclusive}");
+    assert_eq!(inclusive["tax_label"], "TVA");
+    assert_eq!(inclusive["prices_include_tax"], true);
+    assert_eq!(inclusive["tax_cents"], 2238);
+    assert_eq!(inclusive["total_cents"], 19000);
+    assert_eq!(inclusive["agency_fee_cents"], 2952);
+
+    // Reverse charge needs the customer's VAT number and adds the note.
+    let (status, body) = app
+        .post(
+            "/api/invoices",
+            &agency,
+            invoice(json!({ "tax_jurisdiction": "DE" })),
+        )
+        .await;
+    assert_eq!(
+        (status, body["code"].as_str()),
+        (400, Some("missing_customer_tax_id"))
+    );
+    let (status, reverse) = app
+        .post(
+            "/api/invoices",
+            &agency,
+            invoice(json!({ "tax_jurisdiction": "DE", "customer_tax_id": "DE123456789" })),
+        )
+        .await;
+    assert_eq!(status, 200, "{reverse}");
+    assert_eq!(reverse["tax_treatment"], "reverse_charge");
+    assert_eq!(reverse["tax_cents"], 0);
+    assert_eq!(reverse["total_cents"], 19000);
+    assert!(reverse["tax_note"]
+        .as_str()
+        .unwrap()
+        .starts_with("Reverse charge"));
+    let reverse_id = reverse["id"].as_str().unwrap();
+    let (status, content_type, pdf) = download_pdf(&app, &agency, reverse_id).await;
+    assert_eq!((status, content_type.as_str()), (200, "application/pdf"));
+    assert!(pdf.starts_with(b"%PDF-"));
+
+    // Only issued invoices count towards the period's tax.
+    app.post(&format!("/api/invoices/{id}/mark-sent"), &agency, json!({}))
+        .await;
+    app.post(
+        &format!("/api/invoices/{reverse_id}/mark-sent"),
+        &agency,
+        json!({}),
+    )
+    .await;
+    let (status, report) = app
+        .get(
+            "/api/invoices/tax-summary?from=2026-10-01&to=2026-10-31",
+            &agency,
+        )
+        .await;
+    assert_eq!(status, 200, "{report}");
+    assert_eq!(report["invoice_count"], 2);
+    assert_eq!(report["currencies"][0]["currency"], "USD");
+    assert_eq!(report["currencies"][0]["tax_cents"], 2650);
+    assert_eq!(
+        report["currencies"][0]["groups"].as_array().unwrap().len(),
+        4
+    );
+    let (status, body) = app
+        .get(
+            "/api/invoices/tax-summary?from=2026-11-01&to=2026-10-01",
+            &agency,
+        )
+        .await;
+    assert_eq!(
+        (status, body["code"].as_str()),
+        (400, Some("invalid_date_range"))
+    );
+}
+
+async fn download_pdf(app: &TestApp, user: &TestUser, id: &str) -> (u16, String, Vec<u8>) {
+    let resp = app
+        .request(Method::GET, &format!("/api/invoices/{id}/pdf"), user)
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status().as_u16();
+    let content_type = resp
+        .headers()
+        .get("content-type")
+        .map(|v| v.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    (status, content_type, resp.bytes().await.unwrap().to_vec())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sent_invoices_carry_the_stored_pdf() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+    let invoice = create_invoice(&app, &agency, &client_id).await;
+    let id = invoice["id"].as_str().unwrap().to_string();
+
+    let (status, content_type, draft) = download_pdf(&app, &agency, &id).await;
+    assert_eq!(status, 200);
+    assert_eq!(content_type, "application/pdf");
+    assert!(draft.starts_with(b"%PDF-"));
+    assert!(app.supabase.objects("likelee-private").is_empty());
+
+    let (status, _, _) = download_pdf(&app, &TestUser::agency(), &id).await;
+    assert_eq!(status, 404);
+
+    let (status, sent) = app
+        .post(&format!("/api/invoices/{id}/mark-sent"), &agency, json!({}))
+        .await;
+    assert_eq!(status, 200, "{sent}");
+    let path = sent[0]["sent_pdf_path"].as_str().unwrap().to_string();
+    assert!(path.starts_with(&format!("agencies/{}/invoices/{id}/", agency.id)));
+    let stored = app.supabase.object("likelee-private", &path).unwrap();
+    assert_eq!(stored.content_type, "application/pdf");
+    assert!(stored.bytes.starts_with(b"%PDF-"));
+
+    let mails = app.mail.wait_for(1);
+    assert!(mails[0].data.contains("application/pdf"));
+    assert!(mails[0].data.contains("INVCA0000001.pdf"));
+
+    // Later downloads and reminders use the copy the client received.
+    let (_, _, downloaded) = download_pdf(&app, &agency, &id).await;
+    assert_eq!(downloaded, stored.bytes);
+
+    let (status, body) = app
+        .post(
+            &format!("/api/invoices/{id}/send-payment-reminder"),
+            &agency,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+    let mails = app.mail.wait_for(2);
+    assert!(mails[1].data.contains("INVCA0000001.pdf"));
+    assert_eq!(app.supabase.objects("likelee-private").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invoices_are_scoped_to_the_owning_agency() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+    let invoice = create_invoice(&app, &agency, &client_id).await;
+    let id = invoice["id"].as_str().unwrap();
+
+    let other = TestUser::agency();
+    let (status, _) = app.get(&format!("/api/invoices/{id}"), &other).await;
+    assert_eq!(status, 404);
+
+    let (status, list) = app.get("/api/invoices", &other).await;
+    assert_eq!(status, 200);
+    assert_eq!(list, json!([]));
+
+    let (status, _) = app
+        .post(&format!("/api/invoices/{id}/void"), &other, json!({}))
+        .await;
+    assert_eq!(status, 404);
+    assert_eq!(
+        app.supabase.find("agency_invoices", "id", id).unwrap()["status"],
+        "draft"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn requests_without_a_token_are_rejected() {
+    let app = TestApp::spawn().await;
+    let resp = app
+        .http
+        .get(format!("{}/api/invoices", app.url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+}
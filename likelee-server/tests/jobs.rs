@@ -0,0 +1,184 @@
+mod common;
+
+use chrono::{Duration, SecondsFormat, Utc};
+use common::{TestApp, TestUser};
+use likelee_server::jobs::{self, runner};
+use serde_json::{json, Value};
+
+fn ts(offset: Duration) -> String {
+    (Utc::now() + offset).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn job_row(name: &str, schedule: &str, next_run_at: String) -> Value {
+    json!({
+        "name": name,
+        "schedule": schedule,
+        "enabled": true,
+        "next_run_at": next_run_at,
+        "locked_by": null,
+        "locked_until": null,
+        "attempts": 0,
+        "max_attempts": 3,
+        "last_run_at": null,
+        "last_status": null,
+        "last_error": null,
+    })
+}
+
+async fn wait_for_run(app: &TestApp, id: &str) -> Value {
+    for _ in 0..100 {
+        if let Some(run) = app.supabase.find("background_job_runs", "id", id) {
+            if run["status"] != "running" {
+                return Value::Object(run);
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("run {id} did not finish");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn registered_jobs_are_listed_for_admins_only() {
+    let app = TestApp::spawn().await;
+    runner::sync_jobs(&app.state).await.unwrap();
+
+    let reminders = app
+        .supabase
+        .find("background_jobs", "name", "payment_reminders")
+        .unwrap();
+    assert_eq!(reminders["schedule"], "0 9 * * *");
+    let payouts = app
+        .supabase
+        .find("background_jobs", "name", "agency_payout_scheduler")
+        .unwrap();
+    assert_eq!(payouts["schedule"], "@every 3600s");
+
+    let (status, body) = app.get("/api/admin/jobs", &TestUser::new("admin")).await;
+    assert_eq!(status, 200, "{body}");
+    let names: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|j| j["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "agency_payout_scheduler",
+            "email_outbox",
+            "invoice_dunning",
+            "ledger_drift_check",
+            "payment_reminders",
+            "payout_reserves",
+            "rate_limit_prune",
+            "recurring_invoices",
+            "stripe_reconciliation",
+            "talent_statements"
+        ]
+    );
+    assert_eq!(body[0]["running"], false);
+
+    let (status, body) = app.get("/api/admin/jobs", &TestUser::agency()).await;
+    assert_eq!(status, 403, "{body}");
+    let (status, _) = app
+        .post(
+            "/api/admin/jobs/payment_reminders/run",
+            &TestUser::agency(),
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 403);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tick_runs_each_due_job_once_and_reschedules_it() {
+    let app = TestApp::spawn().await;
+    app.supabase.seed(
+        "background_jobs",
+        job_row("payment_reminders", "0 9 * * *", ts(-Duration::minutes(1))),
+    );
+    // Due, but switched off by AGENCY_PAYOUT_SCHEDULER_ENABLED=false.
+    app.supabase.seed(
+        "background_jobs",
+        job_row(
+            "agency_payout_scheduler",
+            "@every 3600s",
+            ts(-Duration::minutes(1)),
+        ),
+    );
+
+    let runs = jobs::tick(&app.state).await.unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].job_name, "payment_reminders");
+    assert_eq!(runs[0].status, "succeeded");
+    assert_eq!(runs[0].trigger, "schedule");
+    assert_eq!(runs[0].attempt, 1);
+    assert_eq!(runs[0].output.as_ref().unwrap()["payments"], 0);
+
+    let job = app
+        .supabase
+        .find("background_jobs", "name", "payment_reminders")
+        .unwrap();
+    assert!(job["locked_by"].is_null());
+    assert!(job["locked_until"].is_null());
+    assert_eq!(job["attempts"], 0);
+    assert_eq!(job["last_status"], "succeeded");
+    assert!(job["next_run_at"].as_str().unwrap() > ts(Duration::zero()).as_str());
+    assert!(job["next_run_at"].as_str().unwrap().ends_with(":00.000Z"));
+
+    assert!(jobs::tick(&app.state).await.unwrap().is_empty());
+    assert_eq!(app.supabase.rows("background_job_runs").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admins_can_trigger_a_run_unless_the_lease_is_held() {
+    let app = TestApp::spawn().await;
+    let admin = TestUser::new("admin");
+    let next_run_at = ts(Duration::hours(6));
+    app.supabase.seed(
+        "background_jobs",
+        job_row("payment_reminders", "0 9 * * *", next_run_at.clone()),
+    );
+
+    let (status, run) = app
+        .post("/api/admin/jobs/payment_reminders/run", &admin, json!({}))
+        .await;
+    assert_eq!(status, 202, "{run}");
+    assert_eq!(run["trigger"], "manual");
+    assert_eq!(run["triggered_by"], admin.id.as_str());
+
+    let finished = wait_for_run(&app, run["id"].as_str().unwrap()).await;
+    assert_eq!(finished["status"], "succeeded");
+    let job = app
+        .supabase
+        .find("background_jobs", "name", "payment_reminders")
+        .unwrap();
+    // Manual runs do not move the schedule.
+    assert_eq!(job["next_run_at"], next_run_at.as_str());
+    assert!(job["locked_by"].is_null());
+
+    let (status, runs) = app
+        .get("/api/admin/jobs/runs?job=payment_reminders", &admin)
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(runs.as_array().unwrap().len(), 1);
+
+    let mut held = job_row("agency_payout_scheduler", "@every 3600s", next_run_at);
+    held["locked_by"] = json!("other-replica");
+    held["locked_until"] = json!(ts(Duration::minutes(5)));
+    app.supabase.seed("background_jobs", held);
+    let (status, body) = app
+        .post(
+            "/api/admin/jobs/agency_payout_scheduler/run",
+            &admin,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 409, "{body}");
+    assert_eq!(body["code"], "job_running");
+
+    let (status, _) = app
+        .post("/api/admin/jobs/nope/run", &admin, json!({}))
+        .await;
+    assert_eq!(status, 404);
+}
@@ -0,0 +1,276 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{stripe, TestApp, TestUser};
+use likelee_server::jobs;
+use likelee_server::ledger::{self, Account, Entry, EntryKind, Posted};
+use serde_json::{json, Value};
+
+fn seed_payment_link(app: &TestApp, agency: &TestUser, creator: &TestUser) {
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "stripe_connect_account_id": "acct_agency" }),
+    );
+    app.supabase.seed(
+        "licensing_requests",
+        json!({ "id": "lr-1", "agency_id": agency.id, "status": "approved", "submission_id": null }),
+    );
+    app.supabase.seed(
+        "agency_payment_links",
+        json!({
+            "agency_id": agency.id,
+            "licensing_request_id": "lr-1",
+            "status": "active",
+            "currency": "USD",
+            "total_amount_cents": 10000,
+            "platform_fee_cents": 1000,
+            "net_amount_cents": 9000,
+            "agency_amount_cents": 1800,
+            "talent_amount_cents": 7200,
+            "talent_splits": [{
+                "talent_id": "talent-1",
+                "creator_id": creator.id,
+                "amount_cents": 7200,
+                "stripe_connect_account_id": "acct_talent",
+            }],
+        }),
+    );
+    app.supabase
+        .on_rpc("record_stripe_transfer", |_| (StatusCode::OK, json!(null)));
+}
+
+fn balance_of(body: &Value) -> i64 {
+    body[0]["balance_cents"].as_i64().unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn payment_link_checkout_is_journaled_and_explained() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+    let admin = TestUser::new("admin");
+    seed_payment_link(&app, &agency, &creator);
+
+    let event = stripe::event(
+        "checkout.session.completed",
+        json!({
+            "id": "cs_test_1",
+            "object": "checkout.session",
+            "amount_total": 10000,
+            "payment_intent": "pi_test_1",
+            "metadata": { "agency_id": agency.id, "licensing_request_ids": "lr-1" },
+        }),
+    );
+    let (status, body) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 200, "{body}");
+
+    let mut kinds: Vec<String> = app
+        .supabase
+        .rows("ledger_entries")
+        .into_iter()
+        .map(|e| e["kind"].as_str().unwrap().to_string())
+        .collect();
+    kinds.sort();
+    assert_eq!(
+        kinds,
+        vec![
+            "agency_commission",
+            "client_payment",
+            "platform_fee",
+            "talent_share",
+            "transfer",
+            "transfer"
+        ]
+    );
+    let postings = app.supabase.rows("ledger_postings");
+    assert_eq!(
+        postings
+            .iter()
+            .map(|p| p["amount_cents"].as_i64().unwrap())
+            .sum::<i64>(),
+        0
+    );
+
+    let (status, body) = app.get("/api/agency/ledger/balance", &agency).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["account"], format!("agency:{}", agency.id));
+    assert_eq!(body[0]["currency"], "USD");
+    assert_eq!(balance_of(&body), 1800);
+    assert_eq!(body[0]["lines"][0]["kind"], "agency_commission");
+    assert_eq!(body[0]["lines"][0]["reference_type"], "payment_link");
+    assert_eq!(body[0]["lines"][0]["balance_cents"], 1800);
+
+    let (status, body) = app.get("/api/creator/ledger/balance", &creator).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(balance_of(&body), 7200);
+
+    let (_, body) = app
+        .get("/api/admin/ledger/accounts/platform:revenue", &admin)
+        .await;
+    assert_eq!(balance_of(&body), 1000);
+    let (_, body) = app
+        .get("/api/admin/ledger/accounts/platform:cash", &admin)
+        .await;
+    assert_eq!(balance_of(&body), 1000);
+    let (_, body) = app
+        .get("/api/admin/ledger/accounts/connected:acct_agency", &admin)
+        .await;
+    assert_eq!(balance_of(&body), 1800);
+
+    // Replaying the movements does not post them twice.
+    let entry = Entry::new(EntryKind::Transfer, "transfer:tr_mock_1", "usd")
+        .debit(&Account::connected("acct_agency"), 1800)
+        .credit(&Account::platform_cash(), 1800);
+    let posted = ledger::post(&app.state, &entry).await.unwrap();
+    assert!(matches!(posted, Posted::Existing(_)));
+    assert_eq!(app.supabase.rows("ledger_postings").len(), postings.len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ledger_endpoints_check_roles_and_account_codes() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let admin = TestUser::new("admin");
+
+    let (status, _) = app.get("/api/creator/ledger/balance", &agency).await;
+    assert_eq!(status, 403);
+    let (status, _) = app
+        .get("/api/admin/ledger/accounts/platform:cash", &agency)
+        .await;
+    assert_eq!(status, 403);
+    let (status, body) = app.get("/api/admin/ledger/accounts/bank:1", &admin).await;
+    assert_eq!(status, 400, "{body}");
+
+    let (status, body) = app.get("/api/agency/ledger/balance", &agency).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body, json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn drift_check_flags_connected_accounts_that_disagree_with_stripe() {
+    let app = TestApp::spawn().await;
+    let admin = TestUser::new("admin");
+    app.supabase.seed(
+        "ledger_account_balances",
+        json!({
+            "account": "connected:acct_agency",
+            "account_type": "connected",
+            "owner_id": "acct_agency",
+            "currency": "USD",
+            "amount_cents": 5000,
+        }),
+    );
+    app.stripe.on(
+        Method::GET,
+        "/v1/balance",
+        StatusCode::OK,
+        json!({
+            "object": "balance",
+            "livemode": false,
+            "available": [{ "amount": 3000, "currency": "usd" }],
+            "pending": [{ "amount": 1500, "currency": "usd" }],
+        }),
+    );
+
+    let (status, body) = app.get("/api/admin/ledger/drift", &admin).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["accounts_checked"], 1);
+    assert_eq!(body["drifts"][0]["account"], "connected:acct_agency");
+    assert_eq!(body["drifts"][0]["ledger_cents"], 5000);
+    assert_eq!(body["drifts"][0]["stripe_cents"], 4500);
+    assert_eq!(body["drifts"][0]["drift_cents"], -500);
+
+    let calls = app.stripe.calls_to(Method::GET, "/v1/balance");
+    assert_eq!(
+        calls[0]
+            .headers
+            .get("stripe-account")
+            .and_then(|v| v.to_str().ok()),
+        Some("acct_agency")
+    );
+
+    let output = jobs::find("ledger_drift_check")
+        .unwrap()
+        .run(&app.state)
+        .await
+        .unwrap();
+    assert_eq!(output["drifts"].as_array().unwrap().len(), 1);
+}
+
+fn balance_transaction(id: &str, kind: &str, net: i64) -> Value {
+    let now = chrono::Utc::now().timestamp();
+    json!({
+        "id": id,
+        "object": "balance_transaction",
+        "amount": net,
+        "available_on": now,
+        "created": now,
+        "currency": "usd",
+        "fee": 0,
+        "fee_details": [],
+        "net": net,
+        "reporting_category": kind,
+        "status": "available",
+        "type": kind,
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn drift_check_compares_only_stripe_activity_since_the_cutover() {
+    let app = TestApp::spawn().await;
+    let admin = TestUser::new("admin");
+    let cutover = chrono::Utc::now() - chrono::Duration::days(30);
+    app.supabase.seed(
+        "ledger_settings",
+        json!({ "id": true, "cutover_at": cutover.to_rfc3339() }),
+    );
+    for (acct, ledger_cents) in [("acct_old", 5000), ("acct_off", 5000)] {
+        app.supabase.seed(
+            "ledger_account_balances",
+            json!({
+                "account": format!("connected:{acct}"),
+                "account_type": "connected",
+                "owner_id": acct,
+                "currency": "USD",
+                "amount_cents": ledger_cents,
+            }),
+        );
+    }
+    // Both accounts hold far more than the ledger from before the cutover;
+    // only acct_off moved money since that the ledger did not record.
+    app.stripe
+        .on_with(Method::GET, "/v1/balance_transactions", |call| {
+            let acct = call
+                .headers
+                .get("stripe-account")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default();
+            let mut data = vec![
+                balance_transaction("txn_in", "transfer", 8000),
+                balance_transaction("txn_out", "payout", -3000),
+            ];
+            if acct == "acct_off" {
+                data.push(balance_transaction("txn_extra", "payout", -700));
+            }
+            (
+                StatusCode::OK,
+                stripe::list("/v1/balance_transactions", data),
+            )
+        });
+
+    let (status, body) = app.get("/api/admin/ledger/drift", &admin).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["accounts_checked"], 2);
+    let drifts = body["drifts"].as_array().unwrap();
+    assert_eq!(drifts.len(), 1, "{body}");
+    assert_eq!(drifts[0]["stripe_account_id"], "acct_off");
+    assert_eq!(drifts[0]["stripe_cents"], 4300);
+    assert_eq!(drifts[0]["drift_cents"], -700);
+
+    let calls = app.stripe.calls_to(Method::GET, "/v1/balance_transactions");
+    assert!(calls[0]
+        .query
+        .contains(&format!("created[gte]={}", cutover.timestamp())));
+    assert!(app.stripe.calls_to(Method::GET, "/v1/balance").is_empty());
+}
@@ -0,0 +1,542 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{fixtures, stripe, TestApp, TestUser, DOCUSEAL_WEBHOOK_SECRET};
+use likelee_server::jobs;
+use serde_json::{json, Value};
+
+/// An active payment link for 100.00: 18.00 to the agency and 72.00 to one
+/// talent, for a licensing request of `brand` whose contract (DocuSeal
+/// submission 77) is not signed yet. Balances are credited as the checkout
+/// trigger would.
+fn seed_link(app: &TestApp, agency: &TestUser, creator: &TestUser, brand: &TestUser) {
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "email": "agency@example.com", "agency_name": "Acme", "stripe_connect_account_id": "acct_agency" }),
+    );
+    app.supabase.seed(
+        "brands",
+        json!({ "id": brand.id, "company_name": "Brand", "payout_risk_level": "standard" }),
+    );
+    app.supabase.seed(
+        "license_submissions",
+        json!({ "id": "sub-1", "agency_id": agency.id, "template_id": "tpl-1", "docuseal_submission_id": 77, "status": "sent", "signed_at": null }),
+    );
+    app.supabase.seed(
+        "licensing_requests",
+        json!({ "id": "lr-1", "agency_id": agency.id, "brand_id": brand.id, "status": "approved", "submission_id": "sub-1" }),
+    );
+    app.supabase.seed(
+        "agency_payment_links",
+        json!({
+            "agency_id": agency.id,
+            "licensing_request_id": "lr-1",
+            "status": "active",
+            "currency": "USD",
+            "total_amount_cents": 10000,
+            "platform_fee_cents": 1000,
+            "net_amount_cents": 9000,
+            "agency_amount_cents": 1800,
+            "talent_amount_cents": 7200,
+            "talent_splits": [{
+                "talent_id": "talent-1",
+                "creator_id": creator.id,
+                "amount_cents": 7200,
+                "stripe_connect_account_id": "acct_talent",
+            }],
+        }),
+    );
+    app.supabase.seed(
+        "agency_balances",
+        json!({ "agency_id": agency.id, "currency": "USD", "available_cents": 1800, "earned_cents": 1800, "updated_at": "2026-10-18T00:00:00Z" }),
+    );
+    app.supabase.seed(
+        "creator_balances",
+        json!({ "creator_id": creator.id, "currency": "USD", "available_cents": 7200, "earned_cents": 7200 }),
+    );
+    app.supabase
+        .on_rpc("record_stripe_transfer", |_| (StatusCode::OK, json!(null)));
+    fixtures::settle_payment_clawback(&app.supabase);
+}
+
+async fn checkout(app: &TestApp, agency: &TestUser) {
+    let event = stripe::event(
+        "checkout.session.completed",
+        json!({
+            "id": "cs_test_1",
+            "object": "checkout.session",
+            "amount_total": 10000,
+            "payment_intent": "pi_test_1",
+            "metadata": { "agency_id": agency.id, "licensing_request_ids": "lr-1" },
+        }),
+    );
+    let (status, body) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 200, "{body}");
+}
+
+async fn save_policy(app: &TestApp, admin: &TestUser, policy: Value) -> Value {
+    let resp = app
+        .request(Method::PUT, "/api/admin/payout-reserves/policies", admin)
+        .json(&policy)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    resp.json().await.unwrap()
+}
+
+/// Rates `brand` high risk and holds its payments until the contract is
+/// signed.
+async fn hold_until_signed(app: &TestApp, admin: &TestUser, brand: &TestUser) {
+    save_policy(
+        app,
+        admin,
+        json!({ "brand_risk_level": "high", "hold_until_contract_signed": true }),
+    )
+    .await;
+    let resp = app
+        .request(
+            Method::PUT,
+            &format!("/api/admin/payout-reserves/brands/{}", brand.id),
+            admin,
+        )
+        .json(&json!({ "risk_level": "high" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+fn reserve(app: &TestApp, recipient_type: &str) -> serde_json::Map<String, Value> {
+    app.supabase
+        .rows("payout_reserves")
+        .into_iter()
+        .find(|r| r["recipient_type"] == recipient_type)
+        .unwrap()
+}
+
+fn transfer_amounts(app: &TestApp) -> Vec<(String, String)> {
+    app.stripe
+        .calls_to(Method::POST, "/v1/transfers")
+        .iter()
+        .map(|c| {
+            (
+                c.form_value("destination").unwrap(),
+                c.form_value("amount").unwrap(),
+            )
+        })
+        .collect()
+}
+
+async fn run_job(app: &TestApp) -> Value {
+    jobs::find("payout_reserves")
+        .unwrap()
+        .run(&app.state)
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn high_risk_payments_are_held_until_the_contract_is_signed() {
+    let app = TestApp::spawn().await;
+    let (agency, creator, brand) = (
+        TestUser::agency(),
+        TestUser::creator(),
+        TestUser::new("brand"),
+    );
+    let admin = TestUser::new("admin");
+    seed_link(&app, &agency, &creator, &brand);
+
+    let resp = app
+        .request(Method::PUT, "/api/admin/payout-reserves/policies", &agency)
+        .json(&json!({ "reserve_bps": 1000, "hold_days": 30 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = app
+        .request(Method::PUT, "/api/admin/payout-reserves/policies", &admin)
+        .json(&json!({ "reserve_bps": 1000 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    save_policy(
+        &app,
+        &admin,
+        json!({ "reserve_bps": 1000, "hold_days": 30 }),
+    )
+    .await;
+    hold_until_signed(&app, &admin, &brand).await;
+
+    // Nothing is transferred while the contract is unsigned.
+    checkout(&app, &agency).await;
+    assert!(transfer_amounts(&app).is_empty());
+    assert_eq!(app.supabase.rows("payout_reserves").len(), 2);
+    let agency_reserve = reserve(&app, "agency");
+    assert_eq!(
+        (&agency_reserve["amount_cents"], &agency_reserve["reason"]),
+        (&json!(1800), &json!("contract"))
+    );
+    assert_eq!(agency_reserve["license_submission_id"], "sub-1");
+    assert_eq!(
+        reserve(&app, "creator")["recipient_id"],
+        creator.id.as_str()
+    );
+
+    let (_, balance) = app.get("/api/agency/payouts/balance", &agency).await;
+    assert_eq!(balance["available_balance"]["amount_cents"], 0, "{balance}");
+    assert_eq!(balance["reserved_balance"]["amount_cents"], 1800);
+    let (_, balance) = app.get("/api/talent/payouts/balance", &creator).await;
+    assert_eq!(balance["balances"][0]["available_cents"], 0, "{balance}");
+    assert_eq!(balance["balances"][0]["reserved_cents"], 7200);
+
+    assert_eq!(run_job(&app).await["released"], 0);
+
+    // Signing makes both reserves due; ops keep the agency's back.
+    let resp = app
+        .http
+        .post(format!("{}/webhooks/docuseal", app.url))
+        .header("X-Docuseal-Secret", DOCUSEAL_WEBHOOK_SECRET)
+        .json(&json!({
+            "event_type": "submission.completed",
+            "timestamp": "2026-10-18T09:00:00Z",
+            "data": { "id": 77 },
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(reserve(&app, "agency")["release_at"].is_string());
+    let agency_reserve_id = agency_reserve["id"].as_str().unwrap().to_string();
+    let (status, held) = app
+        .post(
+            &format!("/api/admin/payout-reserves/{agency_reserve_id}/hold"),
+            &admin,
+            json!({ "note": "Brand under review" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{held}");
+    assert_eq!(held["manual_hold"], true);
+
+    let summary = run_job(&app).await;
+    assert_eq!(
+        (summary["released"].as_i64(), summary["failed"].as_i64()),
+        (Some(1), Some(0)),
+        "{summary}"
+    );
+    assert_eq!(
+        transfer_amounts(&app),
+        vec![("acct_talent".to_string(), "7200".to_string())]
+    );
+    let recorded = app.supabase.rpc_calls("record_stripe_transfer");
+    assert_eq!(recorded[0]["p_reserve_id"], reserve(&app, "creator")["id"]);
+    assert_eq!(reserve(&app, "creator")["status"], "released");
+    assert_eq!(reserve(&app, "agency")["status"], "held");
+
+    let (status, released) = app
+        .post(
+            &format!("/api/admin/payout-reserves/{agency_reserve_id}/release"),
+            &admin,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 200, "{released}");
+    assert_eq!(released["status"], "released");
+    assert_eq!(released["manual_hold"], false);
+    assert_eq!(transfer_amounts(&app).len(), 2);
+    let (status, _) = app
+        .post(
+            &format!("/api/admin/payout-reserves/{agency_reserve_id}/release"),
+            &admin,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 409);
+
+    let events: Vec<Value> = app
+        .supabase
+        .rows("activity_events")
+        .into_iter()
+        .map(|e| e["type"].clone())
+        .collect();
+    assert!(events.contains(&json!("payout_reserve.held")));
+    assert!(events.contains(&json!("payout_reserve.released")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn syncing_a_signed_contract_makes_its_reserves_due() {
+    let app = TestApp::spawn().await;
+    let (agency, creator, brand) = (
+        TestUser::agency(),
+        TestUser::creator(),
+        TestUser::new("brand"),
+    );
+    seed_link(&app, &agency, &creator, &brand);
+    hold_until_signed(&app, &TestUser::new("admin"), &brand).await;
+    checkout(&app, &agency).await;
+    assert!(reserve(&app, "agency")["release_at"].is_null());
+
+    app.docuseal.on(
+        Method::GET,
+        "/submissions/77",
+        StatusCode::OK,
+        json!({
+            "id": 77,
+            "slug": "sub-77",
+            "status": "completed",
+            "submitters": [],
+            "documents": [{ "url": "https://docuseal.test/signed.pdf", "name": "License" }],
+        }),
+    );
+    let (status, synced) = app
+        .post(
+            "/api/license-submissions/sub-1/sync-status",
+            &agency,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 200, "{synced}");
+    assert_eq!(synced["status"], "completed");
+    let signed_at = synced["signed_at"].clone();
+    assert!(signed_at.is_string());
+    assert!(reserve(&app, "agency")["release_at"].is_string());
+    assert!(reserve(&app, "creator")["release_at"].is_string());
+
+    // Syncing again keeps the original signing time.
+    let (status, synced) = app
+        .post(
+            "/api/license-submissions/sub-1/sync-status",
+            &agency,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 200, "{synced}");
+    assert_eq!(synced["signed_at"], signed_at);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rolling_reserves_absorb_refunds_before_transfers_are_reversed() {
+    let app = TestApp::spawn().await;
+    let (agency, creator, brand) = (
+        TestUser::agency(),
+        TestUser::creator(),
+        TestUser::new("brand"),
+    );
+    let admin = TestUser::new("admin");
+    seed_link(&app, &agency, &creator, &brand);
+    save_policy(
+        &app,
+        &admin,
+        json!({ "agency_id": agency.id, "reserve_bps": 1000, "hold_days": 30 }),
+    )
+    .await;
+
+    checkout(&app, &agency).await;
+    assert_eq!(
+        transfer_amounts(&app),
+        vec![
+            ("acct_agency".to_string(), "1620".to_string()),
+            ("acct_talent".to_string(), "6480".to_string()),
+        ]
+    );
+    let agency_reserve = reserve(&app, "agency");
+    assert_eq!(agency_reserve["amount_cents"], 180);
+    assert_eq!(agency_reserve["reason"], "rolling");
+    assert!(agency_reserve["release_at"].as_str().unwrap() > "2026-10-18");
+    assert_eq!(run_job(&app).await["released"], 0);
+
+    // The checkout transfers, as recorded by `record_stripe_transfer`.
+    let link_id = agency_reserve["payment_link_id"].clone();
+    for (recipient_type, recipient_id, account, transfer, amount) in [
+        (
+            "agency",
+            agency.id.as_str(),
+            "acct_agency",
+            "tr_mock_1",
+            1620,
+        ),
+        (
+            "creator",
+            creator.id.as_str(),
+            "acct_talent",
+            "tr_mock_2",
+            6480,
+        ),
+    ] {
+        app.supabase.seed(
+            "agency_payment_link_transfers",
+            json!({
+                "payment_link_id": link_id,
+                "recipient_type": recipient_type,
+                "recipient_id": recipient_id,
+                "stripe_connect_account_id": account,
+                "amount_cents": amount,
+                "currency": "USD",
+                "stripe_transfer_id": transfer,
+                "status": "created",
+            }),
+        );
+    }
+
+    // Half refunded: each share's reserve covers its part first.
+    let (status, body) = app
+        .stripe_webhook(&stripe::event(
+            "charge.refunded",
+            json!({
+                "id": "ch_1",
+                "object": "charge",
+                "amount": 10000,
+                "amount_refunded": 5000,
+                "payment_intent": "pi_test_1",
+                "currency": "usd",
+            }),
+        ))
+        .await;
+    assert_eq!(status, 200, "{body}");
+    let reversed = |transfer: &str| {
+        app.stripe
+            .calls_to(Method::POST, &format!("/v1/transfers/{transfer}/reversals"))
+            .iter()
+            .map(|c| c.form_value("amount").unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(reversed("tr_mock_1"), vec!["720"]);
+    assert_eq!(reversed("tr_mock_2"), vec!["2880"]);
+    assert_eq!(reserve(&app, "agency")["clawback_cents"], 180);
+    assert_eq!(reserve(&app, "creator")["clawback_cents"], 720);
+    let clawbacks = fixtures::clawback_moves(&app.supabase);
+    assert!(clawbacks
+        .iter()
+        .any(|c| c["delta_cents"] == 180 && c["transferred"] == false));
+
+    let (_, balance) = app.get("/api/agency/payouts/balance", &agency).await;
+    assert_eq!(balance["reserved_balance"]["amount_cents"], 0, "{balance}");
+
+    // Nothing is left to pay out of a reserve the refund took.
+    let id = agency_reserve["id"].as_str().unwrap();
+    let (status, released) = app
+        .post(
+            &format!("/api/admin/payout-reserves/{id}/release"),
+            &admin,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 200, "{released}");
+    assert_eq!(released["status"], "forfeited");
+    assert_eq!(transfer_amounts(&app).len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unfinished_releases_are_taken_over_without_a_second_transfer() {
+    let app = TestApp::spawn().await;
+    let (agency, creator, brand) = (
+        TestUser::agency(),
+        TestUser::creator(),
+        TestUser::new("brand"),
+    );
+    seed_link(&app, &agency, &creator, &brand);
+    let link_id = app.supabase.rows("agency_payment_links")[0]["id"].clone();
+    for (id, recipient_type, recipient_id, account, status) in [
+        (
+            "reserve-agency",
+            "agency",
+            agency.id.as_str(),
+            "acct_agency",
+            "releasing",
+        ),
+        (
+            "reserve-creator",
+            "creator",
+            creator.id.as_str(),
+            "acct_talent",
+            "held",
+        ),
+    ] {
+        app.supabase.seed(
+            "payout_reserves",
+            json!({
+                "id": id,
+                "payment_link_id": link_id,
+                "agency_id": agency.id,
+                "recipient_type": recipient_type,
+                "recipient_id": recipient_id,
+                "stripe_connect_account_id": account,
+                "currency": "USD",
+                "amount_cents": 500,
+                "reason": "rolling",
+                "release_at": "2026-10-01T00:00:00Z",
+                "status": status,
+                "manual_hold": false,
+                "updated_at": "2026-10-01T00:00:00Z",
+            }),
+        );
+    }
+    // The agency's release transferred and recorded, then stopped before
+    // saving the reserve.
+    app.supabase.seed(
+        "agency_payment_link_transfers",
+        json!({
+            "payment_link_id": link_id,
+            "recipient_type": "agency",
+            "recipient_id": agency.id,
+            "stripe_connect_account_id": "acct_agency",
+            "amount_cents": 500,
+            "currency": "USD",
+            "stripe_transfer_id": "tr_earlier",
+            "status": "created",
+            "reserve_id": "reserve-agency",
+        }),
+    );
+    app.supabase.on_rpc("record_stripe_transfer", |_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "message": "boom" }),
+        )
+    });
+
+    // The creator's transfer cannot be recorded, so the reserve stays
+    // releasing with the transfer on it instead of counting as paid.
+    let summary = run_job(&app).await;
+    assert_eq!(
+        (summary["released"].as_i64(), summary["failed"].as_i64()),
+        (Some(1), Some(1)),
+        "{summary}"
+    );
+    let agency_reserve = reserve(&app, "agency");
+    assert_eq!(
+        (
+            &agency_reserve["status"],
+            &agency_reserve["stripe_transfer_id"]
+        ),
+        (&json!("released"), &json!("tr_earlier"))
+    );
+    let creator_reserve = reserve(&app, "creator");
+    assert_eq!(creator_reserve["status"], "releasing");
+    assert_eq!(creator_reserve["failure_reason"], "transfer_record_failed");
+    let transfer_id = creator_reserve["stripe_transfer_id"].clone();
+    assert!(transfer_id.is_string(), "{creator_reserve:?}");
+    assert_eq!(
+        transfer_amounts(&app),
+        vec![("acct_talent".to_string(), "500".to_string())]
+    );
+
+    // Not stale yet: left alone.
+    assert_eq!(run_job(&app).await["failed"], 0);
+
+    app.supabase
+        .on_rpc("record_stripe_transfer", |_| (StatusCode::OK, json!(null)));
+    app.supabase.update(
+        "payout_reserves",
+        "reserve-creator",
+        json!({ "updated_at": "2026-10-01T00:00:00Z" }),
+    );
+    assert_eq!(run_job(&app).await["released"], 1);
+    let creator_reserve = reserve(&app, "creator");
+    assert_eq!(creator_reserve["status"], "released");
+    assert!(creator_reserve["failure_reason"].is_null());
+    assert_eq!(transfer_amounts(&app).len(), 1);
+    let recorded = app.supabase.rpc_calls("record_stripe_transfer");
+    assert_eq!(recorded.len(), 2);
+    assert_eq!(recorded[1]["p_stripe_transfer_id"], transfer_id);
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use likelee_server::rate_limit::{DatabaseStore, MemoryStore, RateLimiter, RouteGroup};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+async fn public_get(
+    app: &TestApp,
+    path: &str,
+    ip: &str,
+    password: Option<&str>,
+) -> (u16, Option<String>, Value) {
+    let mut req = app
+        .http
+        .get(format!("{}{}", app.url, path))
+        .header("x-real-ip", ip);
+    if let Some(password) = password {
+        req = req.header("x-package-password", password);
+    }
+    let resp = req.send().await.expect("request to test server");
+    let status = resp.status().as_u16();
+    let retry_after = resp
+        .headers()
+        .get("retry-after")
+        .map(|v| v.to_str().unwrap().to_string());
+    let body = resp.json().await.unwrap_or(Value::Null);
+    (status, retry_after, body)
+}
+
+fn behind_proxy() -> RateLimiter {
+    RateLimiter::new(Arc::new(MemoryStore::default())).trust_proxy(true)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn repeated_wrong_package_passwords_lock_out_the_client() {
+    let app = TestApp::spawn_with(|state| state.rate_limiter = behind_proxy()).await;
+    app.supabase.seed(
+        "agency_talent_packages",
+        json!({
+            "access_token": "pkg-token",
+            "password_protected": true,
+            "password_hash": bcrypt::hash("letmein", 4).unwrap(),
+        }),
+    );
+    app.supabase.on_rpc("get_public_package_details", |_| {
+        (StatusCode::OK, json!({ "id": "pkg-1", "items": [] }))
+    });
+    let path = "/api/public/packages/pkg-token";
+
+    // Opening the link without a password is not a failed attempt.
+    for _ in 0..6 {
+        let (status, _, _) = public_get(&app, path, "10.0.0.1", None).await;
+        assert_eq!(status, 401);
+    }
+    for _ in 0..5 {
+        let (status, _, _) = public_get(&app, path, "10.0.0.1", Some("guess")).await;
+        assert_eq!(status, 401);
+    }
+
+    let (status, retry_after, body) = public_get(&app, path, "10.0.0.1", Some("letmein")).await;
+    assert_eq!(status, 429, "{body}");
+    assert_eq!(body["code"], "too_many_attempts");
+    let retry_after: i64 = retry_after.unwrap().parse().unwrap();
+    assert!(retry_after > 800 && retry_after <= 900);
+
+    // Other clients of the same link are not affected.
+    let (status, _, body) = public_get(&app, path, "10.0.0.2", Some("letmein")).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["id"], "pkg-1");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn guesses_from_rotating_addresses_lock_the_token_out() {
+    let app = TestApp::spawn_with(|state| {
+        let packages = RouteGroup::PublicPackages
+            .defaults()
+            .with_spec("ip=off,token=off,lockout=5/900,token_lockout=6/3600")
+            .unwrap();
+        state.rate_limiter = behind_proxy().with_group(RouteGroup::PublicPackages, packages);
+    })
+    .await;
+    app.supabase.seed(
+        "agency_talent_packages",
+        json!({
+            "access_token": "pkg-token",
+            "password_protected": true,
+            "password_hash": bcrypt::hash("letmein", 4).unwrap(),
+        }),
+    );
+    app.supabase.on_rpc("get_public_package_details", |_| {
+        (StatusCode::OK, json!({ "id": "pkg-1", "items": [] }))
+    });
+    let path = "/api/public/packages/pkg-token";
+
+    // Each address stays under its own lockout, but the token's count adds
+    // them up; a correct password from elsewhere does not reset it.
+    for i in 0..3 {
+        let (status, _, _) = public_get(&app, path, &format!("10.0.2.{i}"), Some("guess")).await;
+        assert_eq!(status, 401);
+    }
+    let (status, _, body) = public_get(&app, path, "10.0.3.1", Some("letmein")).await;
+    assert_eq!(status, 200, "{body}");
+    for i in 3..6 {
+        let (status, _, _) = public_get(&app, path, &format!("10.0.2.{i}"), Some("guess")).await;
+        assert_eq!(status, 401);
+    }
+
+    let (status, retry_after, body) = public_get(&app, path, "10.0.4.1", Some("letmein")).await;
+    assert_eq!(status, 429, "{body}");
+    assert_eq!(body["code"], "too_many_attempts");
+    let retry_after: i64 = retry_after.unwrap().parse().unwrap();
+    assert!(retry_after > 3500 && retry_after <= 3600);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn quotas_apply_per_ip_and_per_token() {
+    let app = TestApp::spawn_with(|state| {
+        let catalogs = RouteGroup::PublicCatalogs
+            .defaults()
+            .with_spec("ip=2/60,token=off")
+            .unwrap();
+        let invites = RouteGroup::TalentInvites
+            .defaults()
+            .with_spec("ip=off,token=2/60")
+            .unwrap();
+        state.rate_limiter = behind_proxy()
+            .with_group(RouteGroup::PublicCatalogs, catalogs)
+            .with_group(RouteGroup::TalentInvites, invites);
+    })
+    .await;
+
+    for token in ["cat-a", "cat-b"] {
+        let path = format!("/api/public/catalogs/{token}");
+        let (status, _, _) = public_get(&app, &path, "10.0.0.1", None).await;
+        assert_ne!(status, 429);
+    }
+    let (status, retry_after, body) =
+        public_get(&app, "/api/public/catalogs/cat-c", "10.0.0.1", None).await;
+    assert_eq!(status, 429, "{body}");
+    assert_eq!(body["code"], "rate_limited");
+    assert!(retry_after.is_some());
+    let (status, _, _) = public_get(&app, "/api/public/catalogs/cat-c", "10.0.0.2", None).await;
+    assert_ne!(status, 429);
+
+    // An invite token is throttled whichever address it is tried from.
+    let path = "/api/invites/agency-talent/inv-token/magic-link";
+    for ip in ["10.0.1.1", "10.0.1.2"] {
+        let (status, _, _) = public_get(&app, path, ip, None).await;
+        assert_ne!(status, 429);
+    }
+    let (status, _, _) = public_get(&app, path, "10.0.1.3", None).await;
+    assert_eq!(status, 429);
+    let (status, _, _) = public_get(
+        &app,
+        "/api/invites/agency-talent/other-token/magic-link",
+        "10.0.1.3",
+        None,
+    )
+    .await;
+    assert_ne!(status, 429);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn database_store_shares_counters_through_rpcs() {
+    let app = TestApp::spawn_with(|state| {
+        let catalogs = RouteGroup::PublicCatalogs
+            .defaults()
+            .with_spec("ip=1/60,token=off")
+            .unwrap();
+        state.rate_limiter = RateLimiter::new(Arc::new(DatabaseStore::new(state.pg.clone())))
+            .with_group(RouteGroup::PublicCatalogs, catalogs);
+    })
+    .await;
+    let hits = Arc::new(AtomicU32::new(0));
+    let counter = hits.clone();
+    app.supabase.on_rpc("rate_limit_hit", move |_| {
+        let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        let resets_at = chrono::Utc::now() + chrono::Duration::seconds(30);
+        (
+            StatusCode::OK,
+            json!({ "count": count, "resets_at": resets_at }),
+        )
+    });
+
+    let path = "/api/public/catalogs/secret-token";
+    let (status, _, _) = public_get(&app, path, "10.0.0.1", None).await;
+    assert_ne!(status, 429);
+    let (status, retry_after, _) = public_get(&app, path, "10.0.0.1", None).await;
+    assert_eq!(status, 429);
+    let retry_after: i64 = retry_after.unwrap().parse().unwrap();
+    assert!(retry_after > 0 && retry_after <= 30);
+
+    let calls = app.supabase.rpc_calls("rate_limit_hit");
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0]["p_window_secs"], 60);
+    let key = calls[0]["p_key"].as_str().unwrap();
+    assert!(key.starts_with("public_catalogs:ip:"), "{key}");
+    assert!(!key.contains("secret-token"));
+}
@@ -0,0 +1,291 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use chrono::{Duration, SecondsFormat, Utc};
+use common::{stripe, TestApp, TestUser};
+use likelee_server::jobs;
+use serde_json::{json, Value};
+
+fn an_hour_ago() -> String {
+    (Utc::now() - Duration::hours(1)).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn seed_transfer(
+    app: &TestApp,
+    link_id: &str,
+    recipient: (&str, &str),
+    transfer: &str,
+    amount: i64,
+) {
+    app.supabase.seed(
+        "agency_payment_link_transfers",
+        json!({
+            "payment_link_id": link_id,
+            "recipient_type": recipient.0,
+            "recipient_id": recipient.1,
+            "stripe_connect_account_id": "acct_x",
+            "amount_cents": amount,
+            "reversed_cents": 0,
+            "currency": "USD",
+            "stripe_transfer_id": transfer,
+            "status": "created",
+            "created_at": an_hour_ago(),
+        }),
+    );
+}
+
+fn seed_licensing_payout(app: &TestApp, agency: &TestUser, payment_intent: &str, total: i64) {
+    app.supabase.seed(
+        "licensing_payouts",
+        json!({
+            "agency_id": agency.id,
+            "stripe_payment_intent_id": payment_intent,
+            "amount_cents": total / 10,
+            "talent_earnings_cents": total - total / 10 - total / 20,
+            "platform_fee_cents": total / 20,
+            "currency": "USD",
+            "paid_at": an_hour_ago(),
+        }),
+    );
+}
+
+/// Transfers, payouts and charges that mostly agree with Stripe, with a few
+/// missing, extra and mismatched records among them.
+fn seed(app: &TestApp, agency: &TestUser, creator: &TestUser) {
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "stripe_connect_account_id": "acct_agency" }),
+    );
+    app.supabase.seed(
+        "creators",
+        json!({ "id": creator.id, "stripe_connect_account_id": "acct_creator" }),
+    );
+    for (id, pi) in [("link-1", "pi_1"), ("link-3", "pi_3")] {
+        app.supabase.seed(
+            "agency_payment_links",
+            json!({ "id": id, "agency_id": agency.id, "stripe_payment_intent_id": pi }),
+        );
+    }
+
+    seed_transfer(app, "link-1", ("agency", &agency.id), "tr_ok", 1800);
+    seed_transfer(app, "link-1", ("creator", "talent-1"), "tr_short", 7200);
+    seed_transfer(app, "link-1", ("creator", "talent-2"), "tr_gone", 500);
+    let link = |extra: Value| {
+        let mut metadata = json!({ "payment_link_id": "link-1", "agency_id": agency.id });
+        metadata
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        metadata
+    };
+    let mut reversed = stripe::transfer("tr_ok", 1800, "usd", "acct_agency", link(json!({})));
+    reversed["amount_reversed"] = json!(1800);
+    app.stripe.on(
+        Method::GET,
+        "/v1/transfers",
+        StatusCode::OK,
+        stripe::list(
+            "/v1/transfers",
+            vec![
+                reversed,
+                // Restores tr_ok after a won dispute.
+                stripe::transfer(
+                    "tr_restore",
+                    1800,
+                    "usd",
+                    "acct_agency",
+                    link(json!({ "restores_transfer_id": "tr_ok", "type": "dispute_won" })),
+                ),
+                stripe::transfer("tr_short", 7000, "usd", "acct_talent", link(json!({}))),
+                stripe::transfer("tr_stray", 300, "usd", "acct_talent", link(json!({}))),
+                // Not made for a payment link.
+                stripe::transfer("tr_other", 900, "usd", "acct_other", json!({})),
+            ],
+        ),
+    );
+
+    let requested_at = an_hour_ago();
+    app.supabase.seed(
+        "agency_payout_requests",
+        json!({
+            "id": "apr-1", "agency_id": agency.id, "amount_cents": 10000, "currency": "USD",
+            "status": "paid", "stripe_payout_id": "po_agency", "requested_at": requested_at,
+        }),
+    );
+    app.supabase.seed(
+        "creator_payout_requests",
+        json!({
+            "id": "cpr-1", "creator_id": creator.id, "amount_cents": 5000, "currency": "USD",
+            "status": "paid", "stripe_payout_id": "po_creator", "requested_at": requested_at,
+        }),
+    );
+    app.stripe.on_with(Method::GET, "/v1/payouts", |call| {
+        let payouts = match call
+            .headers
+            .get("stripe-account")
+            .and_then(|v| v.to_str().ok())
+        {
+            // 10000 less the 1% payout fee.
+            Some("acct_agency") => vec![stripe::payout("po_agency", 9900, "usd", false)],
+            Some("acct_creator") => vec![
+                stripe::payout("po_creator", 4500, "usd", false),
+                stripe::payout("po_auto", 1200, "usd", true),
+                stripe::payout("po_extra", 300, "usd", false),
+            ],
+            _ => vec![],
+        };
+        (StatusCode::OK, stripe::list("/v1/payouts", payouts))
+    });
+
+    seed_licensing_payout(app, agency, "pi_1", 10000);
+    seed_licensing_payout(app, agency, "pi_2", 5000);
+    app.stripe.on(
+        Method::GET,
+        "/v1/balance_transactions",
+        StatusCode::OK,
+        stripe::list(
+            "/v1/balance_transactions",
+            vec![
+                stripe::charge_balance_transaction("txn_1", 10000, "pi_1"),
+                stripe::charge_balance_transaction("txn_3", 2500, "pi_3"),
+                // A subscription charge, with no payment link behind it.
+                stripe::charge_balance_transaction("txn_sub", 4900, "pi_sub"),
+            ],
+        ),
+    );
+}
+
+fn described(body: &Value) -> Vec<String> {
+    let mut found: Vec<String> = body["discrepancies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| {
+            format!(
+                "{} {} {}",
+                d["kind"].as_str().unwrap(),
+                d["object"].as_str().unwrap(),
+                d["stripe_id"].as_str().or(d["local_id"].as_str()).unwrap()
+            )
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconciliation_reports_missing_extra_and_mismatched_records() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+    let admin = TestUser::new("admin");
+    seed(&app, &agency, &creator);
+
+    let output = jobs::find("stripe_reconciliation")
+        .unwrap()
+        .run(&app.state)
+        .await
+        .unwrap();
+    assert_eq!(output["missing"], 2, "{output}");
+    assert_eq!(output["extra"], 3, "{output}");
+    assert_eq!(output["amount_mismatch"], 2, "{output}");
+
+    let (status, body) = app.get("/api/admin/reconciliation", &admin).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["run"]["status"], "completed");
+    assert_eq!(body["run"]["extra_count"], 3);
+    assert_eq!(
+        described(&body),
+        vec![
+            "amount_mismatch payout po_creator",
+            "amount_mismatch transfer tr_short",
+            "extra charge pi_3",
+            "extra payout po_extra",
+            "extra transfer tr_stray",
+            "missing charge pi_2",
+            "missing transfer tr_gone",
+        ]
+    );
+    let short = body["discrepancies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["stripe_id"] == "tr_short")
+        .unwrap();
+    assert_eq!(short["expected_cents"], 7200);
+    assert_eq!(short["actual_cents"], 7000);
+    assert_eq!(short["local_table"], "agency_payment_link_transfers");
+
+    let calls = app.stripe.calls_to(Method::GET, "/v1/balance_transactions");
+    assert!(calls[0].query.contains("type=charge"), "{}", calls[0].query);
+
+    let (status, body) = app
+        .get("/api/admin/reconciliation?kind=extra", &admin)
+        .await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["discrepancies"].as_array().unwrap().len(), 3);
+
+    let (status, body) = app.get("/api/agency/reconciliation", &agency).await;
+    assert_eq!(status, 200, "{body}");
+    assert!(body["run"].get("errors").is_none());
+    assert_eq!(
+        described(&body),
+        vec![
+            "amount_mismatch transfer tr_short",
+            "extra charge pi_3",
+            "extra transfer tr_stray",
+            "missing charge pi_2",
+            "missing transfer tr_gone",
+        ]
+    );
+
+    let (status, body) = app.get("/api/admin/reconciliation/runs", &admin).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconciliation_endpoints_check_roles_and_filters() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+    let admin = TestUser::new("admin");
+
+    let (status, body) = app.get("/api/agency/reconciliation", &agency).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body, json!({ "run": null, "discrepancies": [] }));
+
+    let (status, _) = app.get("/api/agency/reconciliation", &creator).await;
+    assert_eq!(status, 403);
+    let (status, _) = app.get("/api/admin/reconciliation", &agency).await;
+    assert_eq!(status, 403);
+    let (status, body) = app
+        .get("/api/admin/reconciliation?kind=status", &admin)
+        .await;
+    assert_eq!(status, 400, "{body}");
+    let (status, _) = app
+        .get("/api/admin/reconciliation?run_id=nope", &admin)
+        .await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_stripe_lists_fail_the_run() {
+    let app = TestApp::spawn().await;
+    app.stripe.on(
+        Method::GET,
+        "/v1/transfers",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        json!({ "error": { "type": "api_error", "message": "boom" } }),
+    );
+
+    let result = jobs::find("stripe_reconciliation")
+        .unwrap()
+        .run(&app.state)
+        .await;
+    assert!(result.is_err());
+    let runs = app.supabase.rows("reconciliation_runs");
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0]["status"], "failed");
+    assert!(runs[0]["error"].is_string());
+}
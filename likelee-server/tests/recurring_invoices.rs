@@ -0,0 +1,272 @@
+mod common;
+
+use chrono::{Duration, Months, NaiveDate, Utc};
+use common::{fixtures::seed_agency, TestApp, TestUser};
+use likelee_server::jobs;
+use serde_json::{json, Value};
+
+fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+fn schedule_body(client_id: &str, frequency: &str, start: NaiveDate, auto_send: bool) -> Value {
+    json!({
+        "client_id": client_id,
+        "name": "Monthly retainer",
+        "frequency": frequency,
+        "start_date": start.to_string(),
+        "due_days": 14,
+        "auto_send": auto_send,
+        "agency_commission_bps": 2000,
+        "tax_rate_bps": 1000,
+        "items": [
+            { "description": "Retainer", "talent_name": "Ava", "unit_price_cents": 250000 },
+        ],
+        "expenses": [
+            { "description": "Agency admin", "amount_cents": 5000 },
+        ],
+    })
+}
+
+async fn run_job(app: &TestApp) -> Value {
+    jobs::find("recurring_invoices")
+        .unwrap()
+        .run(&app.state)
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn schedules_generate_and_send_invoices_once_per_occurrence() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+
+    let mut bad = schedule_body(&client_id, "fortnightly", today(), true);
+    let (status, body) = app
+        .post("/api/recurring-invoices", &agency, bad.clone())
+        .await;
+    assert_eq!(
+        (status, body["code"].as_str()),
+        (400, Some("invalid_frequency"))
+    );
+    bad["frequency"] = json!("monthly");
+    bad["items"] = json!([]);
+    let (status, body) = app.post("/api/recurring-invoices", &agency, bad).await;
+    assert_eq!(
+        (status, body["code"].as_str()),
+        (400, Some("missing_items"))
+    );
+
+    let (status, schedule) = app
+        .post(
+            "/api/recurring-invoices",
+            &agency,
+            schedule_body(&client_id, "monthly", today(), true),
+        )
+        .await;
+    assert_eq!(status, 200, "{schedule}");
+    assert_eq!(schedule["status"], "active");
+    assert_eq!(schedule["next_run_on"], today().to_string());
+    let id = schedule["id"].as_str().unwrap().to_string();
+
+    let (status, preview) = app
+        .get(
+            &format!("/api/recurring-invoices/{id}/preview?count=3"),
+            &agency,
+        )
+        .await;
+    assert_eq!(status, 200, "{preview}");
+    let dates: Vec<&str> = preview
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["invoice_date"].as_str().unwrap())
+        .collect();
+    let month = |n| (today() + Months::new(n)).to_string();
+    assert_eq!(dates, vec![month(0), month(1), month(2)]);
+    assert_eq!(
+        preview[0]["due_date"],
+        (today() + Duration::days(14)).to_string()
+    );
+    assert_eq!(preview[0]["total_cents"], 280000);
+
+    let output = run_job(&app).await;
+    assert_eq!(output["generated"], 1, "{output}");
+    assert_eq!(output["sent"], 1, "{output}");
+    let invoices = app.supabase.rows("agency_invoices");
+    assert_eq!(invoices.len(), 1);
+    assert_eq!(invoices[0]["invoice_number"], "INVCA0000001");
+    assert_eq!(invoices[0]["status"], "sent");
+    assert_eq!(invoices[0]["invoice_date"], today().to_string());
+    assert_eq!(invoices[0]["total_cents"], 280000);
+    assert_eq!(app.supabase.rows("agency_invoice_items").len(), 1);
+    let mails = app.mail.wait_for(1);
+    assert_eq!(
+        mails[0].subject().as_deref(),
+        Some("Invoice INVCA0000001 from North Studio")
+    );
+
+    let stored = app
+        .supabase
+        .find("agency_recurring_invoice_schedules", "id", &id)
+        .unwrap();
+    assert_eq!(stored["next_run_on"], month(1));
+    assert_eq!(stored["generated_count"], 1);
+    assert_eq!(stored["last_invoice_id"], invoices[0]["id"]);
+
+    // Nothing is due until next month.
+    let output = run_job(&app).await;
+    assert_eq!(output["generated"], 0, "{output}");
+    assert_eq!(app.supabase.rows("agency_invoices").len(), 1);
+
+    let (status, detail) = app
+        .get(&format!("/api/recurring-invoices/{id}"), &agency)
+        .await;
+    assert_eq!(status, 200, "{detail}");
+    assert_eq!(detail["runs"][0]["status"], "sent");
+    assert_eq!(detail["runs"][0]["invoice_id"], invoices[0]["id"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn paused_schedules_skip_generation_and_resume_without_back_billing() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+
+    let (status, schedule) = app
+        .post(
+            "/api/recurring-invoices",
+            &agency,
+            schedule_body(&client_id, "weekly", today(), false),
+        )
+        .await;
+    assert_eq!(status, 200, "{schedule}");
+    let id = schedule["id"].as_str().unwrap().to_string();
+
+    let (status, paused) = app
+        .post(
+            &format!("/api/recurring-invoices/{id}/pause"),
+            &agency,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 200, "{paused}");
+    assert_eq!(paused["status"], "paused");
+    let (status, _) = app
+        .post(
+            &format!("/api/recurring-invoices/{id}/pause"),
+            &agency,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 409);
+
+    let output = run_job(&app).await;
+    assert_eq!(output["generated"], 0, "{output}");
+
+    // Two weekly occurrences pass while paused.
+    app.supabase.update(
+        "agency_recurring_invoice_schedules",
+        &id,
+        json!({
+            "start_date": (today() - Duration::days(16)).to_string(),
+            "next_run_on": (today() - Duration::days(16)).to_string(),
+        }),
+    );
+    let (status, resumed) = app
+        .post(
+            &format!("/api/recurring-invoices/{id}/resume"),
+            &agency,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 200, "{resumed}");
+    assert_eq!(resumed["status"], "active");
+    assert_eq!(
+        resumed["next_run_on"],
+        (today() + Duration::days(5)).to_string()
+    );
+
+    let output = run_job(&app).await;
+    assert_eq!(output["generated"], 0, "{output}");
+    assert!(app.supabase.rows("agency_invoices").is_empty());
+
+    let other = TestUser::agency();
+    let (status, _) = app
+        .post(
+            &format!("/api/recurring-invoices/{id}/pause"),
+            &other,
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn missed_occurrences_are_caught_up_without_duplicates() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+
+    let (status, schedule) = app
+        .post(
+            "/api/recurring-invoices",
+            &agency,
+            schedule_body(&client_id, "weekly", today(), false),
+        )
+        .await;
+    assert_eq!(status, 200, "{schedule}");
+    let id = schedule["id"].as_str().unwrap().to_string();
+
+    // The job was down for two weeks; the first missed week was generated
+    // before it stopped.
+    let start = today() - Duration::days(14);
+    app.supabase.update(
+        "agency_recurring_invoice_schedules",
+        &id,
+        json!({ "start_date": start.to_string(), "next_run_on": start.to_string() }),
+    );
+    app.supabase.seed(
+        "agency_recurring_invoice_runs",
+        json!({
+            "agency_id": agency.id,
+            "schedule_id": id,
+            "occurrence_date": start.to_string(),
+            "invoice_id": null,
+            "status": "generated",
+            "error": null,
+        }),
+    );
+
+    let output = run_job(&app).await;
+    assert_eq!(output["already_generated"], 1, "{output}");
+    assert_eq!(output["generated"], 2, "{output}");
+    assert_eq!(output["sent"], 0, "{output}");
+    let mut dates: Vec<String> = app
+        .supabase
+        .rows("agency_invoices")
+        .iter()
+        .map(|i| {
+            assert_eq!(i["status"], "draft");
+            i["invoice_date"].as_str().unwrap().to_string()
+        })
+        .collect();
+    dates.sort();
+    assert_eq!(
+        dates,
+        vec![
+            (today() - Duration::days(7)).to_string(),
+            today().to_string()
+        ]
+    );
+    let stored = app
+        .supabase
+        .find("agency_recurring_invoice_schedules", "id", &id)
+        .unwrap();
+    assert_eq!(
+        stored["next_run_on"],
+        (today() + Duration::days(7)).to_string()
+    );
+    assert_eq!(stored["generated_count"], 2);
+}
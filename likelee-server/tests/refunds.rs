@@ -0,0 +1,423 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{fixtures, stripe, TestApp, TestUser};
+use serde_json::{json, Value};
+
+/// A paid payment link for 100.00 split into a 10.00 platform fee, an 18.00
+/// agency commission and a 72.00 talent share, both already transferred.
+fn seed_paid_link(app: &TestApp, agency: &TestUser, creator: &TestUser) -> String {
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "email": "agency@example.com", "agency_name": "Acme" }),
+    );
+    app.supabase.seed(
+        "licensing_requests",
+        json!({ "id": "lr-1", "agency_id": agency.id, "status": "archived" }),
+    );
+    let link = app.supabase.seed(
+        "agency_payment_links",
+        json!({
+            "agency_id": agency.id,
+            "licensing_request_id": "lr-1",
+            "status": "paid",
+            "currency": "USD",
+            "stripe_payment_intent_id": "pi_1",
+            "total_amount_cents": 10000,
+            "platform_fee_cents": 1000,
+            "net_amount_cents": 9000,
+            "agency_amount_cents": 1800,
+            "talent_amount_cents": 7200,
+            "talent_splits": [{
+                "talent_id": "talent-1",
+                "creator_id": creator.id,
+                "amount_cents": 7200,
+            }],
+        }),
+    );
+    let link_id = link["id"].as_str().unwrap().to_string();
+    for (recipient_type, recipient_id, account, transfer, amount) in [
+        (
+            "agency",
+            agency.id.as_str(),
+            "acct_agency",
+            "tr_agency",
+            1800,
+        ),
+        ("creator", "talent-1", "acct_talent", "tr_talent", 7200),
+    ] {
+        app.supabase.seed(
+            "agency_payment_link_transfers",
+            json!({
+                "payment_link_id": link_id,
+                "recipient_type": recipient_type,
+                "recipient_id": recipient_id,
+                "stripe_connect_account_id": account,
+                "amount_cents": amount,
+                "currency": "USD",
+                "stripe_transfer_id": transfer,
+                "status": "created",
+            }),
+        );
+    }
+    fixtures::settle_payment_clawback(&app.supabase);
+    fixtures::record_stripe_transfer(&app.supabase);
+    link_id
+}
+
+fn refunded(amount_refunded: i64) -> Value {
+    stripe::event(
+        "charge.refunded",
+        json!({
+            "id": "ch_1",
+            "object": "charge",
+            "amount": 10000,
+            "amount_refunded": amount_refunded,
+            "payment_intent": "pi_1",
+            "currency": "usd",
+        }),
+    )
+}
+
+fn dispute(event_type: &str, status: &str) -> Value {
+    stripe::event(
+        event_type,
+        json!({
+            "id": "dp_1",
+            "object": "dispute",
+            "amount": 10000,
+            "charge": "ch_1",
+            "payment_intent": "pi_1",
+            "currency": "usd",
+            "status": status,
+        }),
+    )
+}
+
+fn reversal_amounts(app: &TestApp, transfer: &str) -> Vec<String> {
+    app.stripe
+        .calls_to(Method::POST, &format!("/v1/transfers/{transfer}/reversals"))
+        .iter()
+        .map(|c| c.form_value("amount").unwrap())
+        .collect()
+}
+
+fn transfer_row(app: &TestApp, transfer: &str) -> serde_json::Map<String, Value> {
+    app.supabase
+        .find(
+            "agency_payment_link_transfers",
+            "stripe_transfer_id",
+            transfer,
+        )
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn refunds_reverse_transfers_in_proportion() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+    let link_id = seed_paid_link(&app, &agency, &creator);
+
+    let (status, body) = app.stripe_webhook(&refunded(5000)).await;
+    assert_eq!(status, 200, "{body}");
+
+    assert_eq!(reversal_amounts(&app, "tr_agency"), vec!["900"]);
+    assert_eq!(reversal_amounts(&app, "tr_talent"), vec!["3600"]);
+    let link = app
+        .supabase
+        .find("agency_payment_links", "id", &link_id)
+        .unwrap();
+    assert_eq!(link["status"], "partially_refunded");
+    assert_eq!(link["refunded_cents"], 5000);
+    assert_eq!(link["clawback_splits"]["platform_fee"], 500);
+    assert_eq!(link["stripe_charge_id"], "ch_1");
+    assert_eq!(
+        app.supabase
+            .find("licensing_requests", "id", "lr-1")
+            .unwrap()["payment_status"],
+        "partially_refunded"
+    );
+    assert_eq!(transfer_row(&app, "tr_agency")["reversed_cents"], 900);
+
+    let clawbacks = fixtures::clawback_moves(&app.supabase);
+    assert_eq!(clawbacks.len(), 2);
+    assert_eq!(clawbacks[0]["recipient_id"], agency.id.as_str());
+    assert_eq!(clawbacks[0]["delta_cents"], 900);
+    assert_eq!(clawbacks[0]["transferred"], true);
+    assert_eq!(clawbacks[1]["recipient_id"], creator.id.as_str());
+    assert_eq!(clawbacks[1]["delta_cents"], 3600);
+
+    let emails = app.supabase.rows("email_outbox");
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0]["to_email"], "agency@example.com");
+    assert_eq!(emails[0]["category"], "payment_refund");
+    let events = app.supabase.rows("activity_events");
+    assert_eq!(events[0]["type"], "payment_link.refunded");
+    assert_eq!(
+        events[0]["changes"]["status"],
+        json!({ "before": "paid", "after": "partially_refunded" })
+    );
+    let postings = app.supabase.rows("ledger_postings");
+    assert_eq!(
+        postings
+            .iter()
+            .map(|p| p["amount_cents"].as_i64().unwrap())
+            .sum::<i64>(),
+        0
+    );
+
+    // The same cumulative amount again (e.g. another refund event for the
+    // charge) changes nothing.
+    let (status, _) = app.stripe_webhook(&refunded(5000)).await;
+    assert_eq!(status, 200);
+    assert_eq!(reversal_amounts(&app, "tr_agency").len(), 1);
+    assert_eq!(fixtures::clawback_moves(&app.supabase).len(), 2);
+    assert_eq!(app.supabase.rows("email_outbox").len(), 1);
+
+    let (status, _) = app.stripe_webhook(&refunded(10000)).await;
+    assert_eq!(status, 200);
+    assert_eq!(reversal_amounts(&app, "tr_agency"), vec!["900", "900"]);
+    assert_eq!(reversal_amounts(&app, "tr_talent"), vec!["3600", "3600"]);
+    let link = app
+        .supabase
+        .find("agency_payment_links", "id", &link_id)
+        .unwrap();
+    assert_eq!(link["status"], "refunded");
+    assert!(link["refunded_at"].is_string());
+    assert_eq!(transfer_row(&app, "tr_talent")["status"], "reversed");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn won_disputes_restore_clawed_back_transfers() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+    let link_id = seed_paid_link(&app, &agency, &creator);
+
+    let (status, body) = app
+        .stripe_webhook(&dispute("charge.dispute.created", "needs_response"))
+        .await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(reversal_amounts(&app, "tr_agency"), vec!["1800"]);
+    assert_eq!(reversal_amounts(&app, "tr_talent"), vec!["7200"]);
+    let link = app
+        .supabase
+        .find("agency_payment_links", "id", &link_id)
+        .unwrap();
+    assert_eq!(link["status"], "disputed");
+    assert_eq!(link["stripe_dispute_id"], "dp_1");
+
+    let (status, body) = app
+        .stripe_webhook(&dispute("charge.dispute.closed", "won"))
+        .await;
+    assert_eq!(status, 200, "{body}");
+    let restores = app.stripe.calls_to(Method::POST, "/v1/transfers");
+    assert_eq!(restores.len(), 2);
+    assert_eq!(
+        restores[0].form_value("destination").as_deref(),
+        Some("acct_agency")
+    );
+    assert_eq!(restores[0].form_value("amount").as_deref(), Some("1800"));
+    assert_eq!(restores[1].form_value("amount").as_deref(), Some("7200"));
+    assert!(restores[0]
+        .headers
+        .get("idempotency-key")
+        .is_some_and(|k| k.to_str().unwrap().starts_with("restore-dp_1:closed:won")));
+
+    let link = app
+        .supabase
+        .find("agency_payment_links", "id", &link_id)
+        .unwrap();
+    assert_eq!(link["status"], "paid");
+    assert_eq!(link["dispute_status"], "won");
+    // Stripe keeps the original reversed; the restore is its own transfer.
+    let original = transfer_row(&app, "tr_agency");
+    assert_eq!(original["reversed_cents"], 1800);
+    assert_eq!(original["status"], "reversed");
+    let restores = app.supabase.rpc_calls("record_stripe_transfer");
+    assert_eq!(restores.len(), 2);
+    assert_eq!(restores[0]["p_restores_transfer_id"], original["id"]);
+    assert_eq!(restores[0]["p_amount_cents"], 1800);
+    let clawbacks = fixtures::clawback_moves(&app.supabase);
+    assert_eq!(clawbacks[2]["delta_cents"], -1800);
+    assert_eq!(clawbacks[3]["delta_cents"], -7200);
+
+    let kinds: Vec<Value> = app
+        .supabase
+        .rows("activity_events")
+        .into_iter()
+        .map(|e| e["type"].clone())
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            json!("payment_link.disputed"),
+            json!("payment_link.dispute_won")
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_reversals_fail_the_event_and_are_retried_alone() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+    seed_paid_link(&app, &agency, &creator);
+    app.stripe.on(
+        Method::POST,
+        "/v1/transfers/tr_talent/reversals",
+        StatusCode::BAD_REQUEST,
+        json!({ "error": { "type": "invalid_request_error", "message": "insufficient funds" } }),
+    );
+
+    let event = refunded(10000);
+    let (status, _) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 500);
+    assert_eq!(reversal_amounts(&app, "tr_agency"), vec!["1800"]);
+    assert!(transfer_row(&app, "tr_talent")
+        .get("reversed_cents")
+        .is_none());
+    assert_eq!(app.supabase.rows("webhook_events")[0]["status"], "failed");
+
+    app.stripe
+        .on_with(Method::POST, "/v1/transfers/tr_talent/reversals", |call| {
+            (
+                StatusCode::OK,
+                stripe::transfer_reversal(
+                    "trr_retry",
+                    "tr_talent",
+                    call.form_value("amount").unwrap().parse().unwrap(),
+                ),
+            )
+        });
+    let (status, body) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(reversal_amounts(&app, "tr_agency"), vec!["1800"]);
+    assert_eq!(reversal_amounts(&app, "tr_talent"), vec!["7200", "7200"]);
+    assert_eq!(transfer_row(&app, "tr_talent")["reversed_cents"], 7200);
+    assert_eq!(fixtures::clawback_moves(&app.supabase).len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unsaved_clawbacks_leave_the_link_for_the_redelivery() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+    let link_id = seed_paid_link(&app, &agency, &creator);
+    app.supabase.on_rpc("settle_payment_clawback", |_| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({ "message": "connection reset" }),
+        )
+    });
+
+    let event = refunded(5000);
+    let (status, _) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 500);
+    let link = app
+        .supabase
+        .find("agency_payment_links", "id", &link_id)
+        .unwrap();
+    assert_eq!(link["status"], "paid");
+    assert!(link.get("clawback_splits").is_none());
+    assert!(reversal_amounts(&app, "tr_agency").is_empty());
+    assert!(app.supabase.rows("ledger_entries").is_empty());
+
+    // Another event moved the link since it was read: every attempt loses.
+    app.supabase.on_rpc("settle_payment_clawback", |_| {
+        (StatusCode::OK, json!(false))
+    });
+    let (status, _) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 500);
+    assert_eq!(app.supabase.rpc_calls("settle_payment_clawback").len(), 4);
+    assert!(reversal_amounts(&app, "tr_agency").is_empty());
+
+    fixtures::settle_payment_clawback(&app.supabase);
+    let (status, body) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 200, "{body}");
+    let calls = app.supabase.rpc_calls("settle_payment_clawback");
+    assert_eq!(calls.len(), 5);
+    assert_eq!(calls[4]["p_expected_clawback_cents"], 0);
+    assert_eq!(calls[4]["p_moves"][0]["delta_cents"], 900);
+    assert_eq!(reversal_amounts(&app, "tr_agency"), vec!["900"]);
+    assert_eq!(
+        app.supabase
+            .find("agency_payment_links", "id", &link_id)
+            .unwrap()["clawback_cents"],
+        5000
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn refunds_after_a_won_dispute_reverse_the_restored_transfers() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+    seed_paid_link(&app, &agency, &creator);
+    for (event, status) in [
+        ("charge.dispute.created", "needs_response"),
+        ("charge.dispute.closed", "won"),
+    ] {
+        let (code, body) = app.stripe_webhook(&dispute(event, status)).await;
+        assert_eq!(code, 200, "{body}");
+    }
+    let restored: Vec<String> = app
+        .supabase
+        .rpc_calls("record_stripe_transfer")
+        .iter()
+        .map(|c| c["p_stripe_transfer_id"].as_str().unwrap().to_string())
+        .collect();
+
+    let (status, body) = app.stripe_webhook(&refunded(5000)).await;
+    assert_eq!(status, 200, "{body}");
+    // The originals were fully reversed by the dispute; only the restores
+    // still hold money.
+    assert_eq!(reversal_amounts(&app, "tr_agency"), vec!["1800"]);
+    assert_eq!(reversal_amounts(&app, "tr_talent"), vec!["7200"]);
+    assert_eq!(reversal_amounts(&app, &restored[0]), vec!["900"]);
+    assert_eq!(reversal_amounts(&app, &restored[1]), vec!["3600"]);
+    assert_eq!(transfer_row(&app, &restored[0])["reversed_cents"], 900);
+
+    // A redelivery finds nothing left to do.
+    let (status, _) = app.stripe_webhook(&refunded(5000)).await;
+    assert_eq!(status, 200);
+    assert_eq!(reversal_amounts(&app, &restored[0]), vec!["900"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn inquiries_claw_back_nothing_until_funds_are_withdrawn() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+    let link_id = seed_paid_link(&app, &agency, &creator);
+
+    let (status, body) = app
+        .stripe_webhook(&dispute("charge.dispute.created", "warning_needs_response"))
+        .await;
+    assert_eq!(status, 200, "{body}");
+    assert!(reversal_amounts(&app, "tr_agency").is_empty());
+    assert!(reversal_amounts(&app, "tr_talent").is_empty());
+    let link = app
+        .supabase
+        .find("agency_payment_links", "id", &link_id)
+        .unwrap();
+    assert_eq!(link["status"], "paid");
+    assert_eq!(link["disputed_cents"], 0);
+    assert_eq!(link["stripe_dispute_id"], "dp_1");
+    assert_eq!(link["dispute_status"], "warning_needs_response");
+    assert!(fixtures::clawback_moves(&app.supabase).is_empty());
+
+    // The inquiry became a chargeback.
+    let (status, body) = app
+        .stripe_webhook(&dispute("charge.dispute.funds_withdrawn", "needs_response"))
+        .await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(reversal_amounts(&app, "tr_agency"), vec!["1800"]);
+    assert_eq!(reversal_amounts(&app, "tr_talent"), vec!["7200"]);
+    let link = app
+        .supabase
+        .find("agency_payment_links", "id", &link_id)
+        .unwrap();
+    assert_eq!(link["status"], "disputed");
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::{TestApp, TestUser};
+use likelee_server::storage::LocalStorage;
+use reqwest::multipart::{Form, Part};
+use reqwest::Method;
+use std::sync::Arc;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\nnot-really-a-png";
+
+fn file_form(name: &str, bytes: &'static [u8]) -> Form {
+    Form::new().part("file", Part::bytes(bytes).file_name(name.to_string()))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn agency_file_upload_sign_and_delete_go_through_supabase_storage() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+
+    let resp = app
+        .request(Method::POST, "/api/agency/storage/files/upload", &agency)
+        .multipart(file_form("../Head shot.png", PNG))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let path = body["storage_path"].as_str().unwrap().to_string();
+    assert!(path.starts_with(&format!("agencies/{}/storage/root/", agency.id)));
+    assert!(path.ends_with("_Head_shot.png"), "{path}");
+
+    let object = app.supabase.object("likelee-private", &path).unwrap();
+    assert_eq!(object.content_type, "image/png");
+    assert_eq!(object.bytes, PNG);
+
+    let id = body["id"].as_str().unwrap();
+    let (status, signed) = app
+        .get(
+            &format!("/api/agency/storage/files/{id}/signed-url"),
+            &agency,
+        )
+        .await;
+    assert_eq!(status, 200, "{signed}");
+    assert!(signed["url"].as_str().unwrap().starts_with(&format!(
+        "{}/storage/v1/object/sign/likelee-private/",
+        app.supabase.url
+    )));
+
+    let resp = app
+        .request(
+            Method::DELETE,
+            &format!("/api/agency/storage/files/{id}"),
+            &agency,
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(app.supabase.object("likelee-private", &path).is_none());
+    assert!(app.supabase.rows("agency_files").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn local_backend_serves_signed_urls_only_with_a_valid_token() {
+    let root = std::env::temp_dir().join(format!("likelee-it-{}", uuid::Uuid::new_v4()));
+    let storage_root = root.clone();
+    let app = TestApp::spawn_with(move |state| {
+        // The base URL is only known after spawning; links are checked by path below.
+        state.storage = Arc::new(LocalStorage::new(
+            &storage_root,
+            "http://local.test",
+            "local-secret",
+            vec![state.supabase_bucket_public.clone()],
+        ));
+    })
+    .await;
+    let agency = TestUser::agency();
+
+    let resp = app
+        .request(Method::POST, "/api/agency/storage/files/upload", &agency)
+        .multipart(file_form("contract.pdf", b"%PDF-1.7 test"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let path = body["storage_path"].as_str().unwrap();
+    assert!(root.join("likelee-private").join(path).is_file());
+
+    let id = body["id"].as_str().unwrap();
+    let (_, signed) = app
+        .get(
+            &format!("/api/agency/storage/files/{id}/signed-url"),
+            &agency,
+        )
+        .await;
+    let signed = signed["url"]
+        .as_str()
+        .unwrap()
+        .replace("http://local.test", &app.url);
+
+    let resp = app.http.get(&signed).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "application/pdf");
+    assert_eq!(resp.bytes().await.unwrap().as_ref(), b"%PDF-1.7 test");
+
+    let unsigned = signed.split('?').next().unwrap();
+    let resp = app.http.get(unsigned).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let _ = std::fs::remove_dir_all(root);
+}
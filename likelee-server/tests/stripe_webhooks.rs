@@ -0,0 +1,109 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{stripe, TestApp, TestUser};
+use serde_json::json;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn payment_link_checkout_creates_transfers() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "stripe_connect_account_id": "acct_agency" }),
+    );
+    app.supabase.seed(
+        "licensing_requests",
+        json!({ "id": "lr-1", "agency_id": agency.id, "status": "approved", "submission_id": null }),
+    );
+    let link = app.supabase.seed(
+        "agency_payment_links",
+        json!({
+            "agency_id": agency.id,
+            "licensing_request_id": "lr-1",
+            "status": "active",
+            "currency": "USD",
+            "total_amount_cents": 10000,
+            "platform_fee_cents": 1000,
+            "net_amount_cents": 9000,
+            "agency_amount_cents": 1800,
+            "talent_amount_cents": 7200,
+            "talent_splits": [{
+                "talent_id": "talent-1",
+                "creator_id": creator.id,
+                "amount_cents": 7200,
+                "stripe_connect_account_id": "acct_talent",
+            }],
+        }),
+    );
+    app.supabase
+        .on_rpc("record_stripe_transfer", |_| (StatusCode::OK, json!(null)));
+
+    let event = stripe::event(
+        "checkout.session.completed",
+        json!({
+            "id": "cs_test_1",
+            "object": "checkout.session",
+            "amount_total": 10000,
+            "payment_intent": "pi_test_1",
+            "metadata": { "agency_id": agency.id, "licensing_request_ids": "lr-1" },
+        }),
+    );
+    let (status, body) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 200, "{body}");
+
+    let transfers = app.stripe.calls_to(Method::POST, "/v1/transfers");
+    assert_eq!(transfers.len(), 2);
+    assert_eq!(
+        transfers[0].form_value("destination").as_deref(),
+        Some("acct_agency")
+    );
+    assert_eq!(transfers[0].form_value("amount").as_deref(), Some("1800"));
+    assert_eq!(
+        transfers[1].form_value("destination").as_deref(),
+        Some("acct_talent")
+    );
+    assert_eq!(transfers[1].form_value("amount").as_deref(), Some("7200"));
+
+    let recorded = app.supabase.rpc_calls("record_stripe_transfer");
+    assert_eq!(recorded.len(), 2);
+    assert!(recorded.iter().all(|r| r["p_status"] == "created"));
+    assert_eq!(recorded[0]["p_stripe_transfer_id"], "tr_mock_1");
+
+    let link = app
+        .supabase
+        .find("agency_payment_links", "id", link["id"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(link["status"], "paid");
+    assert_eq!(link["stripe_payment_intent_id"], "pi_test_1");
+    assert_eq!(app.supabase.rows("licensing_payouts").len(), 1);
+    assert_eq!(
+        app.supabase
+            .find("licensing_requests", "id", "lr-1")
+            .unwrap()["status"],
+        "archived"
+    );
+    assert_eq!(app.supabase.rows("webhook_events").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn webhook_with_bad_signature_is_rejected() {
+    let app = TestApp::spawn().await;
+    let payload = stripe::event("payout.paid", json!({ "id": "po_1" })).to_string();
+    let resp = app
+        .http
+        .post(format!("{}/webhooks/stripe", app.url))
+        .header(
+            "Stripe-Signature",
+            stripe::sign_payload(&payload, "whsec_wrong"),
+        )
+        .body(payload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    assert!(app.supabase.rows("webhook_events").is_empty());
+    assert!(app.stripe.calls().is_empty());
+}
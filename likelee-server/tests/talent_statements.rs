@@ -0,0 +1,166 @@
+mod common;
+
+use axum::http::Method;
+use chrono::{Datelike, Utc};
+use common::{
+    fixtures::{seed_agency, seed_talent},
+    TestApp, TestUser,
+};
+use likelee_server::jobs;
+use serde_json::{json, Value};
+
+async fn download(app: &TestApp, agency: &TestUser, path: &str) -> (u16, Vec<u8>) {
+    let resp = app.request(Method::GET, path, agency).send().await.unwrap();
+    let status = resp.status().as_u16();
+    (status, resp.bytes().await.unwrap().to_vec())
+}
+
+async fn pay(app: &TestApp, agency: &TestUser, invoice_id: &str, cents: i64) {
+    let (status, body) = app
+        .post(
+            &format!("/api/invoices/{invoice_id}/payments"),
+            agency,
+            json!({ "amount_cents": cents, "paid_on": Utc::now().date_naive().to_string(), "method": "check" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{body}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn statements_export_and_send_each_version_once() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let client_id = seed_agency(&app, &agency);
+    let talent_id = seed_talent(&app, &agency, "Ava Stone");
+    let last_month = Utc::now().date_naive().with_day(1).unwrap() - chrono::Days::new(20);
+
+    let (status, invoice) = app
+        .post(
+            "/api/invoices",
+            &agency,
+            json!({
+                "client_id": client_id,
+                "invoice_date": last_month.to_string(),
+                "agency_commission_bps": 2000,
+                "items": [{
+                    "description": "Campaign shoot",
+                    "talent_id": talent_id,
+                    "talent_name": "Ava Stone",
+                    "unit_price_cents": 100000,
+                }],
+            }),
+        )
+        .await;
+    assert_eq!(status, 200, "{invoice}");
+    let invoice_id = invoice["id"].as_str().unwrap();
+    app.post(
+        &format!("/api/invoices/{invoice_id}/mark-sent"),
+        &agency,
+        json!({}),
+    )
+    .await;
+    pay(&app, &agency, invoice_id, 40_000).await;
+
+    // Defaults to last month.
+    let (status, csv) = download(&app, &agency, "/api/talent-statements/export?format=csv").await;
+    assert_eq!(status, 200);
+    let csv = String::from_utf8(csv).unwrap();
+    assert_eq!(csv.lines().count(), 2, "{csv}");
+    // Past its due date, so the partial payment leaves it overdue.
+    assert!(
+        csv.ends_with(",Acme Corp,Campaign shoot,USD,1000.00,200.00,800.00,320.00,overdue\r\n"),
+        "{csv}"
+    );
+
+    let (status, _) = download(&app, &agency, "/api/talent-statements/export").await;
+    assert_eq!(status, 400);
+    let (status, pdf) = download(
+        &app,
+        &agency,
+        &format!("/api/talent-statements/export?talent_id={talent_id}"),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert!(pdf.starts_with(b"%PDF"));
+
+    let (status, summary) = app
+        .post("/api/talent-statements/send", &agency, json!({}))
+        .await;
+    assert_eq!(status, 200, "{summary}");
+    assert_eq!(summary["sent"], 1, "{summary}");
+    // The invoice itself went to the client first.
+    let mails = app.mail.wait_for(2);
+    assert_eq!(mails[1].to, vec!["ava@talent.test".to_string()]);
+    assert!(mails[1].data.contains("statement-Ava_Stone-"));
+
+    let (_, again) = app
+        .post("/api/talent-statements/send", &agency, json!({}))
+        .await;
+    assert_eq!(
+        (again["sent"].clone(), again["already_sent"].clone()),
+        (json!(0), json!(1))
+    );
+
+    // New figures go out as the next version.
+    pay(&app, &agency, invoice_id, 60_000).await;
+    let (_, resent) = app
+        .post("/api/talent-statements/send", &agency, json!({}))
+        .await;
+    assert_eq!(resent["sent"], 1, "{resent}");
+    let (status, deliveries) = app.get("/api/talent-statements/deliveries", &agency).await;
+    assert_eq!(status, 200);
+    let versions: Vec<i64> = deliveries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["version"].as_i64().unwrap())
+        .collect();
+    assert_eq!(versions, vec![2, 1]);
+    let (status, sent_pdf) = download(
+        &app,
+        &agency,
+        &format!(
+            "/api/talent-statements/deliveries/{}/pdf",
+            deliveries[1]["id"].as_str().unwrap()
+        ),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert!(sent_pdf.starts_with(b"%PDF"));
+    let stored = app.supabase.objects("likelee-private");
+    assert_eq!(
+        stored
+            .iter()
+            .filter(|p| p.contains("/talent-statements/"))
+            .count(),
+        2
+    );
+
+    // The monthly job only covers agencies that opted in.
+    let run = |app: &TestApp| {
+        let state = app.state.clone();
+        async move {
+            jobs::find("talent_statements")
+                .unwrap()
+                .run(&state)
+                .await
+                .unwrap()
+        }
+    };
+    assert_eq!(run(&app).await["agencies"], 0);
+    let resp = app
+        .request(Method::PUT, "/api/talent-statements/settings", &agency)
+        .json(&json!({ "monthly_email_enabled": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    let summary: Value = run(&app).await;
+    assert_eq!(summary["agencies"], 1, "{summary}");
+    assert_eq!(summary["already_sent"], 1, "{summary}");
+
+    let (_, other) = app
+        .get("/api/talent-statements/deliveries", &TestUser::agency())
+        .await;
+    assert!(other.as_array().unwrap().is_empty(), "{other}");
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use chrono::{Datelike, Utc};
+use common::{TestApp, TestUser};
+use serde_json::{json, Value};
+
+fn seed_talent(app: &TestApp, agency: &TestUser, name: &str, country: &str) -> String {
+    let user = TestUser::creator();
+    let row = app.supabase.seed(
+        "agency_users",
+        json!({
+            "agency_id": agency.id,
+            "user_id": user.id,
+            "full_legal_name": name,
+            "stage_name": null,
+            "email": user.email,
+            "city": null,
+            "state_province": null,
+            "country": country,
+            "status": "active",
+        }),
+    );
+    row["id"].as_str().unwrap().to_string()
+}
+
+fn seed_irl(app: &TestApp, agency: &TestUser, talent_id: &str, cents: i64, status: &str, at: &str) {
+    app.supabase.seed(
+        "talent_irl_payments",
+        json!({
+            "agency_id": agency.id,
+            "talent_id": talent_id,
+            "amount_cents": cents,
+            "currency": "USD",
+            "status": status,
+            "paid_at": at,
+        }),
+    );
+}
+
+async fn generate(app: &TestApp, agency: &TestUser, body: Value) -> (u16, Value) {
+    app.post("/api/tax-documents/generate", agency, body).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn year_end_documents_are_generated_once_per_set_of_figures() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let year = Utc::now().year() - 1;
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "email": agency.email, "agency_name": "North Studio", "logo_url": null }),
+    );
+    let us = seed_talent(&app, &agency, "Ava Stone", "United States");
+    let uk = seed_talent(&app, &agency, "Ben Hale", "United Kingdom");
+    seed_talent(&app, &agency, "Cleo Park", "US");
+    app.supabase.seed(
+        "licensing_payouts",
+        json!({
+            "agency_id": agency.id,
+            "talent_id": us,
+            "amount_cents": 250_000,
+            "currency": "USD",
+            "talent_splits": null,
+            "paid_at": format!("{year}-03-01T10:00:00Z"),
+        }),
+    );
+    seed_irl(
+        &app,
+        &agency,
+        &us,
+        40_000,
+        "paid",
+        &format!("{year}-06-01T10:00:00Z"),
+    );
+    seed_irl(
+        &app,
+        &agency,
+        &us,
+        99_000,
+        "pending",
+        &format!("{year}-07-01T10:00:00Z"),
+    );
+    seed_irl(
+        &app,
+        &agency,
+        &uk,
+        12_000,
+        "paid",
+        &format!("{year}-08-01T10:00:00Z"),
+    );
+    // Paid the following year; not part of this tax year.
+    seed_irl(
+        &app,
+        &agency,
+        &uk,
+        7_000,
+        "paid",
+        &format!("{}-01-02T10:00:00Z", year + 1),
+    );
+
+    let (status, body) = generate(&app, &agency, json!({ "tax_year": year + 5 })).await;
+    assert_eq!(
+        (status, body["code"].as_str()),
+        (400, Some("invalid_tax_year"))
+    );
+
+    let (status, summary) = generate(&app, &agency, json!({ "tax_year": year })).await;
+    assert_eq!(status, 200, "{summary}");
+    let generated = summary["generated"].as_array().unwrap();
+    assert_eq!(generated.len(), 2, "{summary}");
+    let doc = |talent: &str| {
+        generated
+            .iter()
+            .find(|d| d["talent_id"] == talent)
+            .cloned()
+            .unwrap()
+    };
+    let us_doc = doc(&us);
+    assert_eq!(us_doc["doc_type"], "1099");
+    assert_eq!(us_doc["totals"][0]["total_cents"], 290_000);
+    assert_eq!(us_doc["totals"][0]["irl_cents"], 40_000);
+    assert!(us_doc["notified_at"].is_string());
+    assert_eq!(doc(&uk)["doc_type"], "earnings_statement");
+    assert_eq!(doc(&uk)["totals"][0]["total_cents"], 12_000);
+
+    let path = us_doc["storage_path"].as_str().unwrap();
+    let pdf = app.supabase.object("likelee-private", path).unwrap();
+    assert!(pdf.bytes.starts_with(b"%PDF"));
+    assert_eq!(app.supabase.rows("talent_notifications").len(), 2);
+    let mails = app.mail.wait_for(2);
+    assert!(mails
+        .iter()
+        .any(|m| m.subject().is_some_and(|s| s.contains("1099-NEC"))));
+
+    // Same figures: nothing new is written or sent.
+    let (_, again) = generate(&app, &agency, json!({ "tax_year": year })).await;
+    assert_eq!(again["unchanged"], 2, "{again}");
+    assert!(again["generated"].as_array().unwrap().is_empty());
+
+    // A late payment produces a corrected document for that talent only.
+    seed_irl(
+        &app,
+        &agency,
+        &uk,
+        3_000,
+        "paid",
+        &format!("{year}-12-30T10:00:00Z"),
+    );
+    let (_, corrected) =
+        generate(&app, &agency, json!({ "tax_year": year, "notify": false })).await;
+    assert_eq!(corrected["unchanged"], 1, "{corrected}");
+    let redo = &corrected["generated"][0];
+    assert_eq!(redo["talent_id"], uk.as_str());
+    assert_eq!(redo["supersedes_id"], doc(&uk)["id"]);
+    assert_eq!(redo["totals"][0]["total_cents"], 15_000);
+    assert!(redo["notified_at"].is_null());
+    assert_eq!(app.supabase.rows("talent_notifications").len(), 2);
+
+    let (status, list) = app
+        .get(&format!("/api/tax-documents?tax_year={year}"), &agency)
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(list.as_array().unwrap().len(), 3);
+    let (_, other) = app.get("/api/tax-documents", &TestUser::agency()).await;
+    assert!(other.as_array().unwrap().is_empty());
+}
@@ -0,0 +1,236 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{stripe, TestApp, TestUser};
+use serde_json::json;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn redelivered_stripe_event_is_processed_once() {
+    let app = TestApp::spawn().await;
+    let agency = TestUser::agency();
+    let creator = TestUser::creator();
+
+    app.supabase.seed(
+        "agencies",
+        json!({ "id": agency.id, "stripe_connect_account_id": "acct_agency" }),
+    );
+    app.supabase.seed(
+        "licensing_requests",
+        json!({ "id": "lr-1", "agency_id": agency.id, "status": "approved", "submission_id": null }),
+    );
+    app.supabase.seed(
+        "agency_payment_links",
+        json!({
+            "agency_id": agency.id,
+            "licensing_request_id": "lr-1",
+            "status": "active",
+            "currency": "USD",
+            "total_amount_cents": 10000,
+            "platform_fee_cents": 1000,
+            "net_amount_cents": 9000,
+            "agency_amount_cents": 1800,
+            "talent_amount_cents": 7200,
+            "talent_splits": [{
+                "talent_id": "talent-1",
+                "creator_id": creator.id,
+                "amount_cents": 7200,
+                "stripe_connect_account_id": "acct_talent",
+            }],
+        }),
+    );
+    app.supabase
+        .on_rpc("record_stripe_transfer", |_| (StatusCode::OK, json!(null)));
+
+    let event = stripe::event(
+        "checkout.session.completed",
+        json!({
+            "id": "cs_test_1",
+            "object": "checkout.session",
+            "amount_total": 10000,
+            "payment_intent": "pi_test_1",
+            "metadata": { "agency_id": agency.id, "licensing_request_ids": "lr-1" },
+        }),
+    );
+    let (status, body) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 200, "{body}");
+    // The payment link is active again, so only the event id stops a second run.
+    app.supabase.seed(
+        "agency_payment_links",
+        json!({
+            "agency_id": agency.id,
+            "licensing_request_id": "lr-1",
+            "status": "active",
+            "total_amount_cents": 10000,
+            "agency_amount_cents": 1800,
+            "talent_splits": [],
+        }),
+    );
+    let (status, body) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 200, "{body}");
+
+    assert_eq!(app.stripe.calls_to(Method::POST, "/v1/transfers").len(), 2);
+    let events = app.supabase.rows("webhook_events");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event_id"], event["id"]);
+    assert_eq!(events[0]["status"], "processed");
+    assert_eq!(events[0]["attempts"], 1);
+    assert!(events[0]["processed_at"].is_string());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_event_is_recorded_retried_and_replayed() {
+    let app = TestApp::spawn().await;
+    let admin = TestUser::new("admin");
+
+    // The subscription lookup 404s until the mock is taught about it.
+    let event = stripe::event(
+        "invoice.paid",
+        json!({ "id": "in_1", "object": "invoice", "subscription": "sub_test_1", "customer": "cus_1" }),
+    );
+    let (status, _) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 500);
+
+    let row = app
+        .supabase
+        .find("webhook_events", "event_id", event["id"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(row["status"], "failed");
+    assert_eq!(row["attempts"], 1);
+    assert!(row["error"].as_str().is_some_and(|e| !e.is_empty()));
+
+    // A provider redelivery of a failed event runs the handler again.
+    let (status, _) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 500);
+    let id = row["id"].as_str().unwrap().to_string();
+    assert_eq!(
+        app.supabase.find("webhook_events", "id", &id).unwrap()["attempts"],
+        2
+    );
+
+    let (status, failed) = app.get("/api/admin/webhooks?status=failed", &admin).await;
+    assert_eq!(status, 200, "{failed}");
+    assert_eq!(failed.as_array().unwrap().len(), 1);
+    assert_eq!(failed[0]["id"], id.as_str());
+
+    let now = chrono::Utc::now().timestamp();
+    app.stripe.on(
+        Method::GET,
+        "/v1/subscriptions/sub_test_1",
+        StatusCode::OK,
+        json!({
+            "id": "sub_test_1",
+            "object": "subscription",
+            "automatic_tax": { "enabled": false },
+            "billing_cycle_anchor": now,
+            "cancel_at_period_end": false,
+            "created": now,
+            "currency": "usd",
+            "current_period_start": now,
+            "current_period_end": now + 2_592_000,
+            "customer": "cus_1",
+            "items": { "object": "list", "data": [], "has_more": false, "url": "/v1/subscription_items" },
+            "livemode": false,
+            "metadata": {},
+            "start_date": now,
+            "status": "active",
+        }),
+    );
+    let path = format!("/api/admin/webhooks/{id}/replay");
+    let (status, replayed) = app.post(&path, &admin, json!({})).await;
+    assert_eq!(status, 200, "{replayed}");
+    assert_eq!(replayed["status"], "processed");
+    assert_eq!(replayed["attempts"], 3);
+    assert!(replayed["error"].is_null());
+
+    let (status, body) = app.post(&path, &admin, json!({})).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["code"], "webhook_event_not_failed");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unfinished_event_is_taken_over_once_stale() {
+    let app = TestApp::spawn().await;
+    let event = stripe::event(
+        "customer.created",
+        json!({ "id": "cus_1", "object": "customer" }),
+    );
+    // PostgREST returns timestamps with microseconds and a `+00:00` offset.
+    let postgrest_ts = |ago: chrono::Duration| {
+        (chrono::Utc::now() - ago)
+            .format("%Y-%m-%dT%H:%M:%S%.6f+00:00")
+            .to_string()
+    };
+    let row = app.supabase.seed(
+        "webhook_events",
+        json!({
+            "provider": "stripe",
+            "event_id": event["id"],
+            "event_type": "customer.created",
+            "payload": event,
+            "status": "received",
+            "attempts": 1,
+            "last_attempt_at": postgrest_ts(chrono::Duration::seconds(30)),
+            "created_at": postgrest_ts(chrono::Duration::seconds(30)),
+        }),
+    );
+    let id = row["id"].as_str().unwrap().to_string();
+
+    let (status, body) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 409, "{body}");
+    assert_eq!(body["code"], "webhook_in_progress");
+
+    app.supabase.update(
+        "webhook_events",
+        &id,
+        json!({ "last_attempt_at": postgrest_ts(chrono::Duration::minutes(10)) }),
+    );
+    let (status, body) = app.stripe_webhook(&event).await;
+    assert_eq!(status, 200, "{body}");
+    let row = app.supabase.find("webhook_events", "id", &id).unwrap();
+    assert_eq!(row["status"], "processed");
+    assert_eq!(row["attempts"], 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn docuseal_redelivery_is_deduplicated_and_admin_routes_are_guarded() {
+    let app = TestApp::spawn().await;
+    app.supabase.seed(
+        "license_submissions",
+        json!({ "id": "ls-1", "docuseal_submission_id": "77", "requires_agency_signature": false, "status": "sent" }),
+    );
+
+    let payload = json!({
+        "event_type": "submission.completed",
+        "timestamp": "2026-10-18T09:00:00Z",
+        "data": { "id": 77, "documents": [{ "url": "https://docs.example/signed.pdf" }] },
+    });
+    for _ in 0..2 {
+        let (status, body) = app
+            .docuseal_webhook("/api/webhooks/licenseContract", &payload)
+            .await;
+        assert_eq!(status, 200, "{body}");
+    }
+
+    let events = app.supabase.rows("webhook_events");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["provider"], "docuseal");
+    assert_eq!(events[0]["status"], "processed");
+    assert_eq!(events[0]["attempts"], 1);
+    let submission = app
+        .supabase
+        .find("license_submissions", "id", "ls-1")
+        .unwrap();
+    assert_eq!(submission["status"], "completed");
+
+    let (status, _) = app.get("/api/admin/webhooks", &TestUser::agency()).await;
+    assert_eq!(status, 403);
+    let id = events[0]["id"].as_str().unwrap();
+    let (status, _) = app
+        .post(
+            &format!("/api/admin/webhooks/{id}/replay"),
+            &TestUser::creator(),
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 403);
+}
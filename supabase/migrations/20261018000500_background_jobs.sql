@@ -0,0 +1,49 @@
+BEGIN;
+
+-- One row per registered job; the server keeps schedule/max_attempts in sync.
+-- locked_by/locked_until form the lease a replica holds while running the job.
+CREATE TABLE IF NOT EXISTS public.background_jobs (
+  name text PRIMARY KEY,
+  schedule text NOT NULL,
+  enabled boolean NOT NULL DEFAULT true,
+  next_run_at timestamptz,
+  locked_by text,
+  locked_until timestamptz,
+  attempts integer NOT NULL DEFAULT 0 CHECK (attempts >= 0),
+  max_attempts integer NOT NULL DEFAULT 3 CHECK (max_attempts >= 1),
+  last_run_at timestamptz,
+  last_status text CHECK (last_status IN ('succeeded', 'failed')),
+  last_error text,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_background_jobs_next_run_at
+  ON public.background_jobs (next_run_at)
+  WHERE enabled;
+
+CREATE TABLE IF NOT EXISTS public.background_job_runs (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  job_name text NOT NULL REFERENCES public.background_jobs(name) ON DELETE CASCADE,
+  trigger text NOT NULL CHECK (trigger IN ('schedule', 'manual')),
+  status text NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'succeeded', 'failed')),
+  attempt integer NOT NULL DEFAULT 1,
+  worker_id text,
+  triggered_by uuid,
+  started_at timestamptz NOT NULL DEFAULT now(),
+  finished_at timestamptz,
+  duration_ms bigint,
+  output jsonb,
+  error text
+);
+
+CREATE INDEX IF NOT EXISTS idx_background_job_runs_job_started
+  ON public.background_job_runs (job_name, started_at DESC);
+CREATE INDEX IF NOT EXISTS idx_background_job_runs_status
+  ON public.background_job_runs (status);
+
+-- Server-only tables: no policies, so only the service role can read or write.
+ALTER TABLE public.background_jobs ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.background_job_runs ENABLE ROW LEVEL SECURITY;
+
+COMMIT;
@@ -0,0 +1,47 @@
+BEGIN;
+
+-- Outgoing mail. Handlers insert rows in 'queued'; the server delivers them and
+-- retries transient SMTP failures with backoff until max_attempts.
+CREATE TABLE IF NOT EXISTS public.email_outbox (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid REFERENCES public.agencies(id) ON DELETE SET NULL,
+  client_id uuid,
+  talent_id uuid,
+  category text,
+  reference_type text,
+  reference_id text,
+  to_email text NOT NULL,
+  from_name text,
+  subject text NOT NULL,
+  body text NOT NULL,
+  is_html boolean NOT NULL DEFAULT false,
+  -- [{filename, content_type, content_base64}]
+  attachments jsonb NOT NULL DEFAULT '[]'::jsonb,
+  status text NOT NULL DEFAULT 'queued' CHECK (status IN ('queued', 'sending', 'sent', 'failed')),
+  attempts integer NOT NULL DEFAULT 0,
+  max_attempts integer NOT NULL DEFAULT 5,
+  next_attempt_at timestamptz NOT NULL DEFAULT now(),
+  locked_until timestamptz,
+  last_error text,
+  smtp_response text,
+  sent_at timestamptz,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_outbox_due
+  ON public.email_outbox (next_attempt_at)
+  WHERE status IN ('queued', 'sending');
+CREATE INDEX IF NOT EXISTS idx_email_outbox_agency_created
+  ON public.email_outbox (agency_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_email_outbox_agency_client
+  ON public.email_outbox (agency_id, client_id);
+CREATE INDEX IF NOT EXISTS idx_email_outbox_agency_talent
+  ON public.email_outbox (agency_id, talent_id);
+CREATE INDEX IF NOT EXISTS idx_email_outbox_reference
+  ON public.email_outbox (reference_type, reference_id);
+
+-- Written and read by the server only (delivery log is served by the API).
+ALTER TABLE public.email_outbox ENABLE ROW LEVEL SECURITY;
+
+COMMIT;
@@ -0,0 +1,34 @@
+BEGIN;
+
+-- Webhook deliveries are keyed by the provider's event id so redeliveries are
+-- not processed twice. Rows stored before this migration were handled inline
+-- and are marked processed.
+ALTER TABLE public.webhook_events
+  ADD COLUMN IF NOT EXISTS event_id text,
+  ADD COLUMN IF NOT EXISTS status text NOT NULL DEFAULT 'processed',
+  ADD COLUMN IF NOT EXISTS error text,
+  ADD COLUMN IF NOT EXISTS attempts integer NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS last_attempt_at timestamptz,
+  ADD COLUMN IF NOT EXISTS processed_at timestamptz;
+
+ALTER TABLE public.webhook_events
+  ALTER COLUMN status SET DEFAULT 'received';
+
+DO $$
+BEGIN
+  IF NOT EXISTS (
+    SELECT 1 FROM pg_constraint WHERE conname = 'webhook_events_status_check'
+  ) THEN
+    ALTER TABLE public.webhook_events
+      ADD CONSTRAINT webhook_events_status_check
+      CHECK (status IN ('received', 'processed', 'failed'));
+  END IF;
+END $$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS uq_webhook_events_provider_event_id
+  ON public.webhook_events(provider, event_id);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_events_status_created_at
+  ON public.webhook_events(status, created_at DESC);
+
+COMMIT;
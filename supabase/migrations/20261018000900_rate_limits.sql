@@ -0,0 +1,97 @@
+BEGIN;
+
+-- Shared counters for the public token endpoints when the server runs with
+-- RATE_LIMIT_STORE=database. Keys carry the route group, the client IP and a
+-- digest of the token, never the token itself. Only the service role touches
+-- this table.
+CREATE TABLE IF NOT EXISTS public.rate_limit_counters (
+  key text PRIMARY KEY,
+  hits integer NOT NULL DEFAULT 0,
+  window_ends_at timestamptz NOT NULL DEFAULT now(),
+  failures integer NOT NULL DEFAULT 0,
+  failure_window_ends_at timestamptz,
+  locked_until timestamptz,
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+ALTER TABLE public.rate_limit_counters ENABLE ROW LEVEL SECURITY;
+
+-- Counts one request in a fixed window and returns {count, resets_at}.
+CREATE OR REPLACE FUNCTION public.rate_limit_hit(p_key text, p_window_secs integer)
+RETURNS jsonb AS $$
+DECLARE
+  r public.rate_limit_counters;
+BEGIN
+  INSERT INTO public.rate_limit_counters AS c (key, hits, window_ends_at)
+  VALUES (p_key, 1, now() + make_interval(secs => p_window_secs))
+  ON CONFLICT (key) DO UPDATE SET
+    hits = CASE WHEN c.window_ends_at > now() THEN c.hits + 1 ELSE 1 END,
+    window_ends_at = CASE
+      WHEN c.window_ends_at > now() THEN c.window_ends_at
+      ELSE now() + make_interval(secs => p_window_secs)
+    END,
+    updated_at = now()
+  RETURNING * INTO r;
+
+  RETURN jsonb_build_object('count', r.hits, 'resets_at', r.window_ends_at);
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER SET search_path = public;
+
+-- Records a rejected credential. Returns the lock expiry once p_max_failures
+-- failures fall within p_lockout_secs, otherwise null.
+CREATE OR REPLACE FUNCTION public.rate_limit_record_failure(
+  p_key text,
+  p_max_failures integer,
+  p_lockout_secs integer
+)
+RETURNS timestamptz AS $$
+DECLARE
+  r public.rate_limit_counters;
+BEGIN
+  INSERT INTO public.rate_limit_counters AS c (key, failures, failure_window_ends_at)
+  VALUES (p_key, 1, now() + make_interval(secs => p_lockout_secs))
+  ON CONFLICT (key) DO UPDATE SET
+    failures = CASE WHEN c.failure_window_ends_at > now() THEN c.failures + 1 ELSE 1 END,
+    failure_window_ends_at = CASE
+      WHEN c.failure_window_ends_at > now() THEN c.failure_window_ends_at
+      ELSE now() + make_interval(secs => p_lockout_secs)
+    END,
+    updated_at = now()
+  RETURNING * INTO r;
+
+  IF r.failures < p_max_failures THEN
+    RETURN NULL;
+  END IF;
+
+  UPDATE public.rate_limit_counters
+  SET failures = 0,
+      failure_window_ends_at = NULL,
+      locked_until = now() + make_interval(secs => p_lockout_secs),
+      updated_at = now()
+  WHERE key = p_key
+  RETURNING locked_until INTO r.locked_until;
+
+  RETURN r.locked_until;
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER SET search_path = public;
+
+-- Deletes counters whose windows and lockouts have all expired.
+CREATE OR REPLACE FUNCTION public.rate_limit_prune()
+RETURNS bigint AS $$
+DECLARE
+  removed bigint;
+BEGIN
+  DELETE FROM public.rate_limit_counters
+  WHERE window_ends_at <= now()
+    AND coalesce(failure_window_ends_at, now()) <= now()
+    AND coalesce(locked_until, now()) <= now();
+  GET DIAGNOSTICS removed = ROW_COUNT;
+  RETURN removed;
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER SET search_path = public;
+
+REVOKE ALL ON FUNCTION public.rate_limit_hit(text, integer) FROM PUBLIC, anon, authenticated;
+REVOKE ALL ON FUNCTION public.rate_limit_record_failure(text, integer, integer) FROM PUBLIC, anon, authenticated;
+REVOKE ALL ON FUNCTION public.rate_limit_prune() FROM PUBLIC, anon, authenticated;
+
+COMMIT;
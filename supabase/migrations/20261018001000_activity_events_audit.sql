@@ -0,0 +1,20 @@
+BEGIN;
+
+-- activity_events becomes the agency audit log: every mutating agency action
+-- records who made it, the field-level diff and the API request id.
+ALTER TABLE public.activity_events
+  ADD COLUMN IF NOT EXISTS actor_id uuid,
+  ADD COLUMN IF NOT EXISTS actor_role text,
+  ADD COLUMN IF NOT EXISTS changes jsonb NOT NULL DEFAULT '{}'::jsonb,
+  ADD COLUMN IF NOT EXISTS request_id text;
+
+CREATE INDEX IF NOT EXISTS idx_activity_events_agency_created
+  ON public.activity_events(agency_id, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_activity_events_subject
+  ON public.activity_events(agency_id, subject_table, subject_id, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_activity_events_actor
+  ON public.activity_events(agency_id, actor_id, created_at DESC);
+
+COMMIT;
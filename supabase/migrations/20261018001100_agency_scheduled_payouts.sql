@@ -0,0 +1,47 @@
+BEGIN;
+
+-- Scheduled payouts are ordinary agency_payout_requests tagged with the payout
+-- period they settle (the last_payout_at they start from, or 'initial') and an
+-- attempt number. The unique index lets only one runner create each attempt.
+ALTER TABLE public.agency_payout_requests
+  ADD COLUMN IF NOT EXISTS source text NOT NULL DEFAULT 'manual',
+  ADD COLUMN IF NOT EXISTS schedule_period text,
+  ADD COLUMN IF NOT EXISTS schedule_attempt integer;
+
+DO $$
+BEGIN
+  IF NOT EXISTS (
+    SELECT 1 FROM pg_constraint WHERE conname = 'agency_payout_requests_source_check'
+  ) THEN
+    ALTER TABLE public.agency_payout_requests
+      ADD CONSTRAINT agency_payout_requests_source_check
+      CHECK (source IN ('manual', 'scheduled'));
+  END IF;
+END $$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS uq_agency_payout_requests_schedule
+  ON public.agency_payout_requests(agency_id, schedule_period, schedule_attempt)
+  WHERE schedule_period IS NOT NULL;
+
+-- One row per due agency per scheduler run, including skipped and failed ones.
+CREATE TABLE IF NOT EXISTS public.agency_payout_schedule_runs (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid NOT NULL REFERENCES public.agencies(id) ON DELETE CASCADE,
+  schedule_period text NOT NULL,
+  outcome text NOT NULL CHECK (outcome IN ('executed', 'pending_approval', 'skipped', 'failed')),
+  reason text,
+  amount_cents bigint NOT NULL DEFAULT 0,
+  payout_request_id uuid REFERENCES public.agency_payout_requests(id) ON DELETE SET NULL,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_agency_payout_schedule_runs_agency
+  ON public.agency_payout_schedule_runs(agency_id, created_at DESC);
+
+ALTER TABLE public.agency_payout_schedule_runs ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "Agencies can view their scheduled payout runs" ON public.agency_payout_schedule_runs;
+CREATE POLICY "Agencies can view their scheduled payout runs"
+  ON public.agency_payout_schedule_runs FOR SELECT USING (auth.uid() = agency_id);
+
+COMMIT;
@@ -0,0 +1,125 @@
+BEGIN;
+
+-- Double-entry ledger. Every money movement (client payment, platform fee,
+-- agency commission, talent share, transfer, payout, refund) is one journal
+-- entry whose postings sum to zero. Postings are signed: debits positive,
+-- credits negative. Accounts are text codes such as 'platform:cash',
+-- 'agency:<agency_id>', 'creator:<creator_id>' or 'connected:<acct_id>'.
+-- Entries are append-only; corrections are new entries.
+CREATE TABLE IF NOT EXISTS public.ledger_entries (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  idempotency_key text NOT NULL UNIQUE,
+  kind text NOT NULL CHECK (kind IN (
+    'client_payment', 'platform_fee', 'agency_commission', 'talent_share',
+    'transfer', 'payout', 'refund', 'adjustment'
+  )),
+  currency text NOT NULL,
+  memo text,
+  reference_type text,
+  reference_id text,
+  occurred_at timestamptz NOT NULL DEFAULT now(),
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ledger_entries_reference
+  ON public.ledger_entries(reference_type, reference_id);
+
+CREATE TABLE IF NOT EXISTS public.ledger_postings (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  entry_id uuid NOT NULL REFERENCES public.ledger_entries(id),
+  account text NOT NULL,
+  account_type text NOT NULL CHECK (account_type IN (
+    'platform_cash', 'platform_revenue', 'clearing', 'agency', 'creator', 'connected'
+  )),
+  owner_id text,
+  currency text NOT NULL,
+  amount_cents bigint NOT NULL CHECK (amount_cents <> 0),
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ledger_postings_account
+  ON public.ledger_postings(account, currency, created_at);
+CREATE INDEX IF NOT EXISTS idx_ledger_postings_entry
+  ON public.ledger_postings(entry_id);
+
+-- Entries and postings are immutable.
+CREATE OR REPLACE FUNCTION public.ledger_reject_mutation()
+RETURNS trigger
+LANGUAGE plpgsql
+AS $$
+BEGIN
+  RAISE EXCEPTION '% is append-only', TG_TABLE_NAME;
+END;
+$$;
+
+DROP TRIGGER IF EXISTS trg_ledger_entries_immutable ON public.ledger_entries;
+CREATE TRIGGER trg_ledger_entries_immutable
+  BEFORE UPDATE OR DELETE ON public.ledger_entries
+  FOR EACH ROW EXECUTE FUNCTION public.ledger_reject_mutation();
+
+DROP TRIGGER IF EXISTS trg_ledger_postings_immutable ON public.ledger_postings;
+CREATE TRIGGER trg_ledger_postings_immutable
+  BEFORE UPDATE OR DELETE ON public.ledger_postings
+  FOR EACH ROW EXECUTE FUNCTION public.ledger_reject_mutation();
+
+-- An entry's postings are written by a single statement that must balance,
+-- and an entry that already has postings cannot gain more.
+CREATE OR REPLACE FUNCTION public.ledger_check_balanced()
+RETURNS trigger
+LANGUAGE plpgsql
+AS $$
+DECLARE
+  bad uuid;
+BEGIN
+  SELECT n.entry_id INTO bad
+  FROM new_postings n
+  GROUP BY n.entry_id
+  HAVING sum(n.amount_cents) <> 0 OR count(DISTINCT n.currency) <> 1
+  LIMIT 1;
+  IF bad IS NOT NULL THEN
+    RAISE EXCEPTION 'ledger entry % does not balance', bad;
+  END IF;
+
+  SELECT p.entry_id INTO bad
+  FROM public.ledger_postings p
+  WHERE p.entry_id IN (SELECT entry_id FROM new_postings)
+    AND p.id NOT IN (SELECT id FROM new_postings)
+  LIMIT 1;
+  IF bad IS NOT NULL THEN
+    RAISE EXCEPTION 'ledger entry % is already posted', bad;
+  END IF;
+  RETURN NULL;
+END;
+$$;
+
+DROP TRIGGER IF EXISTS trg_ledger_postings_balanced ON public.ledger_postings;
+CREATE TRIGGER trg_ledger_postings_balanced
+  AFTER INSERT ON public.ledger_postings
+  REFERENCING NEW TABLE AS new_postings
+  FOR EACH STATEMENT EXECUTE FUNCTION public.ledger_check_balanced();
+
+-- Signed sum per account; the API flips credit-normal accounts for display.
+CREATE OR REPLACE VIEW public.ledger_account_balances AS
+SELECT account, account_type, owner_id, currency, sum(amount_cents)::bigint AS amount_cents
+FROM public.ledger_postings
+GROUP BY account, account_type, owner_id, currency;
+
+-- When the ledger started recording. Money that moved before it is not in
+-- the ledger, so the drift check compares only Stripe activity since then.
+CREATE TABLE IF NOT EXISTS public.ledger_settings (
+  id boolean PRIMARY KEY DEFAULT true CHECK (id),
+  cutover_at timestamptz NOT NULL DEFAULT now()
+);
+
+INSERT INTO public.ledger_settings (id) VALUES (true) ON CONFLICT (id) DO NOTHING;
+
+ALTER TABLE public.ledger_entries ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.ledger_postings ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.ledger_settings ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "Owners can view their ledger postings" ON public.ledger_postings;
+CREATE POLICY "Owners can view their ledger postings"
+  ON public.ledger_postings FOR SELECT
+  USING (account_type IN ('agency', 'creator') AND owner_id = auth.uid()::text);
+
+COMMIT;
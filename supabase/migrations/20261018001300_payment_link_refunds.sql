@@ -0,0 +1,119 @@
+BEGIN;
+
+-- Refunds and disputes on payment link charges. The link keeps how much has
+-- been refunded, how much is held by an open or lost dispute, and how much of
+-- each share (agency, talents, platform fee) has been clawed back so far.
+ALTER TABLE public.agency_payment_links
+  ADD COLUMN IF NOT EXISTS stripe_charge_id text,
+  ADD COLUMN IF NOT EXISTS refunded_cents bigint NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS disputed_cents bigint NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS clawback_cents bigint NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS clawback_splits jsonb NOT NULL DEFAULT '{}'::jsonb,
+  ADD COLUMN IF NOT EXISTS stripe_dispute_id text,
+  ADD COLUMN IF NOT EXISTS dispute_status text,
+  ADD COLUMN IF NOT EXISTS refunded_at timestamptz;
+
+ALTER TABLE public.agency_payment_links
+  DROP CONSTRAINT IF EXISTS agency_payment_links_status_check;
+ALTER TABLE public.agency_payment_links
+  ADD CONSTRAINT agency_payment_links_status_check
+  CHECK (status IN (
+    'active', 'paid', 'expired', 'cancelled',
+    'partially_refunded', 'refunded', 'disputed'
+  ));
+
+CREATE INDEX IF NOT EXISTS idx_agency_payment_links_payment_intent
+  ON public.agency_payment_links(stripe_payment_intent_id);
+
+-- Portion of each transfer taken back from the connected account, and the
+-- transfer a won dispute paid back. Stripe keeps the original reversed, so a
+-- restore is a further transfer to the same recipient.
+ALTER TABLE public.agency_payment_link_transfers
+  ADD COLUMN IF NOT EXISTS reversed_cents bigint NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS restores_transfer_id uuid REFERENCES public.agency_payment_link_transfers(id);
+
+DROP INDEX IF EXISTS public.idx_aplt_unique_recipient;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_aplt_unique_recipient
+  ON public.agency_payment_link_transfers (payment_link_id, recipient_type, recipient_id)
+  WHERE restores_transfer_id IS NULL;
+
+-- Mirrors the payment link status onto the request it paid for.
+ALTER TABLE public.licensing_requests
+  ADD COLUMN IF NOT EXISTS payment_status text;
+
+-- Takes back part of a recipient's share of a payment link; a negative delta
+-- gives it back (dispute won). Lifetime earnings always move; the available
+-- balance only moves for shares that were never transferred out.
+CREATE OR REPLACE FUNCTION public.apply_payment_clawback(
+    p_recipient_type text, -- 'agency' or 'creator'
+    p_recipient_id uuid,
+    p_delta_cents bigint,
+    p_transferred boolean
+)
+RETURNS void AS $$
+DECLARE
+    available_delta bigint := CASE WHEN p_transferred THEN 0 ELSE p_delta_cents END;
+BEGIN
+    IF p_recipient_type = 'agency' THEN
+        UPDATE public.agency_balances
+        SET earned_cents = GREATEST(earned_cents - p_delta_cents, 0),
+            available_cents = available_cents - available_delta,
+            updated_at = now()
+        WHERE agency_id = p_recipient_id;
+    ELSIF p_recipient_type = 'creator' THEN
+        UPDATE public.creator_balances
+        SET earned_cents = GREATEST(earned_cents - p_delta_cents, 0),
+            available_cents = available_cents - available_delta,
+            updated_at = now()
+        WHERE creator_id = p_recipient_id;
+    END IF;
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER SET search_path = public;
+
+-- Saves a payment link's new refund/dispute state and moves the balances in
+-- one transaction. The link is only updated while clawback_cents still holds
+-- the value the caller read; otherwise nothing changes and false is returned
+-- so the caller can reload and recompute. p_moves holds
+-- {recipient_type, recipient_id, delta_cents, transferred} objects.
+CREATE OR REPLACE FUNCTION public.settle_payment_clawback(
+    p_payment_link_id uuid,
+    p_expected_clawback_cents bigint,
+    p_link jsonb,
+    p_moves jsonb
+)
+RETURNS boolean AS $$
+DECLARE
+    m jsonb;
+BEGIN
+    UPDATE public.agency_payment_links
+    SET status = p_link->>'status',
+        refunded_cents = (p_link->>'refunded_cents')::bigint,
+        disputed_cents = (p_link->>'disputed_cents')::bigint,
+        clawback_cents = (p_link->>'clawback_cents')::bigint,
+        clawback_splits = p_link->'clawback_splits',
+        stripe_dispute_id = p_link->>'stripe_dispute_id',
+        dispute_status = p_link->>'dispute_status',
+        stripe_charge_id = COALESCE(p_link->>'stripe_charge_id', stripe_charge_id),
+        refunded_at = COALESCE((p_link->>'refunded_at')::timestamptz, refunded_at)
+    WHERE id = p_payment_link_id
+      AND clawback_cents = p_expected_clawback_cents;
+    IF NOT FOUND THEN
+        RETURN false;
+    END IF;
+
+    FOR m IN SELECT * FROM jsonb_array_elements(COALESCE(p_moves, '[]'::jsonb)) LOOP
+        PERFORM public.apply_payment_clawback(
+            m->>'recipient_type',
+            (m->>'recipient_id')::uuid,
+            (m->>'delta_cents')::bigint,
+            (m->>'transferred')::boolean
+        );
+    END LOOP;
+    RETURN true;
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER SET search_path = public;
+
+REVOKE ALL ON FUNCTION public.apply_payment_clawback(text, uuid, bigint, boolean) FROM PUBLIC, anon, authenticated;
+REVOKE ALL ON FUNCTION public.settle_payment_clawback(uuid, bigint, jsonb, jsonb) FROM PUBLIC, anon, authenticated;
+
+COMMIT;
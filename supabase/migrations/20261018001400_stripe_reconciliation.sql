@@ -0,0 +1,53 @@
+BEGIN;
+
+-- One row per reconciliation pass over a window of Stripe activity.
+CREATE TABLE IF NOT EXISTS public.reconciliation_runs (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  status text NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'completed', 'failed')),
+  window_start timestamptz NOT NULL,
+  window_end timestamptz NOT NULL,
+  missing_count integer NOT NULL DEFAULT 0,
+  extra_count integer NOT NULL DEFAULT 0,
+  amount_mismatch_count integer NOT NULL DEFAULT 0,
+  -- Connected accounts or lists that could not be read from Stripe.
+  errors jsonb NOT NULL DEFAULT '[]'::jsonb,
+  error text,
+  started_at timestamptz NOT NULL DEFAULT now(),
+  finished_at timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS idx_reconciliation_runs_started
+  ON public.reconciliation_runs(started_at DESC);
+
+-- A local record Stripe does not know about ('missing'), a Stripe object with
+-- no local record ('extra'), or a pair whose amounts disagree.
+CREATE TABLE IF NOT EXISTS public.reconciliation_discrepancies (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  run_id uuid NOT NULL REFERENCES public.reconciliation_runs(id) ON DELETE CASCADE,
+  kind text NOT NULL CHECK (kind IN ('missing', 'extra', 'amount_mismatch')),
+  object text NOT NULL CHECK (object IN ('charge', 'transfer', 'payout')),
+  local_table text,
+  local_id text,
+  stripe_id text,
+  stripe_account_id text,
+  agency_id uuid,
+  creator_id uuid,
+  currency text,
+  expected_cents bigint,
+  actual_cents bigint,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_reconciliation_discrepancies_run
+  ON public.reconciliation_discrepancies(run_id, kind);
+CREATE INDEX IF NOT EXISTS idx_reconciliation_discrepancies_agency
+  ON public.reconciliation_discrepancies(agency_id, created_at DESC);
+
+ALTER TABLE public.reconciliation_runs ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.reconciliation_discrepancies ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "Agencies can view their reconciliation discrepancies" ON public.reconciliation_discrepancies;
+CREATE POLICY "Agencies can view their reconciliation discrepancies"
+  ON public.reconciliation_discrepancies FOR SELECT USING (auth.uid() = agency_id);
+
+COMMIT;
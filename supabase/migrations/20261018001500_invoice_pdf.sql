@@ -0,0 +1,10 @@
+BEGIN;
+
+-- The PDF a client was sent, kept in the private bucket so resends and
+-- reminders carry the exact same document.
+ALTER TABLE public.agency_invoices
+  ADD COLUMN IF NOT EXISTS sent_pdf_path text,
+  ADD COLUMN IF NOT EXISTS sent_pdf_sha256 text,
+  ADD COLUMN IF NOT EXISTS sent_pdf_generated_at timestamptz;
+
+COMMIT;
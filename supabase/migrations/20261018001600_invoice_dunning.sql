@@ -0,0 +1,18 @@
+BEGIN;
+
+-- Sent invoices past their due date are moved to 'overdue' by the
+-- invoice_dunning job; they can still be reminded, paid or voided. This is
+-- the only definition of the status set, including 'partially_paid' and
+-- 'credited' from the invoice payments migration that follows; later
+-- migrations must not redefine it.
+ALTER TABLE public.agency_invoices
+  DROP CONSTRAINT IF EXISTS agency_invoices_status_check;
+ALTER TABLE public.agency_invoices
+  ADD CONSTRAINT agency_invoices_status_check
+  CHECK (status IN ('draft','sent','overdue','partially_paid','paid','credited','void'));
+
+-- Finds invoices due for a reminder or the overdue transition.
+CREATE INDEX IF NOT EXISTS idx_agency_invoices_status_due_date
+  ON public.agency_invoices(status, due_date);
+
+COMMIT;
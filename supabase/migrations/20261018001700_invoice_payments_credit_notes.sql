@@ -0,0 +1,81 @@
+BEGIN;
+
+-- Invoices can be settled in instalments and reduced by credit notes. The
+-- balance due is total_cents - amount_paid_cents - amount_credited_cents; the
+-- two sums are kept in step with the records below by the server. An invoice
+-- settled by credit notes alone is 'credited' rather than 'paid'. Both that
+-- and 'partially_paid' are part of agency_invoices_status_check as defined by
+-- the invoice dunning migration.
+
+ALTER TABLE public.agency_invoices
+  ADD COLUMN IF NOT EXISTS amount_paid_cents integer NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS amount_credited_cents integer NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS last_payment_at timestamptz;
+
+CREATE TABLE IF NOT EXISTS public.agency_invoice_payments (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid NOT NULL REFERENCES public.agencies(id) ON DELETE CASCADE,
+  invoice_id uuid NOT NULL REFERENCES public.agency_invoices(id) ON DELETE CASCADE,
+  amount_cents integer NOT NULL CHECK (amount_cents > 0),
+  paid_on date NOT NULL,
+  method text NOT NULL DEFAULT 'bank_transfer'
+    CHECK (method IN ('bank_transfer','card','check','cash','stripe','other')),
+  reference text,
+  notes text,
+  recorded_by uuid,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_agency_invoice_payments_invoice_id
+  ON public.agency_invoice_payments(invoice_id);
+CREATE INDEX IF NOT EXISTS idx_agency_invoice_payments_agency_paid_on
+  ON public.agency_invoice_payments(agency_id, paid_on);
+
+ALTER TABLE public.agency_invoice_payments ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "agency_invoice_payments select own" ON public.agency_invoice_payments;
+CREATE POLICY "agency_invoice_payments select own" ON public.agency_invoice_payments
+  FOR SELECT USING (auth.uid() = agency_id);
+
+CREATE TABLE IF NOT EXISTS public.agency_invoice_credit_notes (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid NOT NULL REFERENCES public.agencies(id) ON DELETE CASCADE,
+  invoice_id uuid NOT NULL REFERENCES public.agency_invoices(id) ON DELETE CASCADE,
+  -- Set when the credit disputes a single line; otherwise it applies to the
+  -- whole invoice.
+  invoice_item_id uuid REFERENCES public.agency_invoice_items(id) ON DELETE SET NULL,
+  credit_note_number text NOT NULL,
+  issued_on date NOT NULL,
+  amount_cents integer NOT NULL CHECK (amount_cents > 0),
+  reason text NOT NULL,
+  issued_by uuid,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  UNIQUE (agency_id, credit_note_number)
+);
+
+CREATE INDEX IF NOT EXISTS idx_agency_invoice_credit_notes_invoice_id
+  ON public.agency_invoice_credit_notes(invoice_id);
+CREATE INDEX IF NOT EXISTS idx_agency_invoice_credit_notes_agency_issued_on
+  ON public.agency_invoice_credit_notes(agency_id, issued_on);
+
+ALTER TABLE public.agency_invoice_credit_notes ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "agency_invoice_credit_notes select own" ON public.agency_invoice_credit_notes;
+CREATE POLICY "agency_invoice_credit_notes select own" ON public.agency_invoice_credit_notes
+  FOR SELECT USING (auth.uid() = agency_id);
+
+-- Invoices already marked paid get one payment for their total, so every
+-- balance is backed by payment records.
+INSERT INTO public.agency_invoice_payments (agency_id, invoice_id, amount_cents, paid_on, method, reference, created_at)
+SELECT i.agency_id, i.id, i.total_cents, COALESCE(i.paid_at, i.updated_at)::date, 'other', 'Recorded before payment tracking', COALESCE(i.paid_at, i.updated_at)
+FROM public.agency_invoices i
+WHERE i.status = 'paid'
+  AND i.total_cents > 0
+  AND NOT EXISTS (SELECT 1 FROM public.agency_invoice_payments p WHERE p.invoice_id = i.id);
+
+UPDATE public.agency_invoices
+SET amount_paid_cents = total_cents,
+    last_payment_at = COALESCE(paid_at, updated_at)
+WHERE status = 'paid' AND amount_paid_cents = 0;
+
+COMMIT;
@@ -0,0 +1,66 @@
+BEGIN;
+
+-- Retainer billing: a schedule holds an invoice template for one agency
+-- client and the recurring_invoices job turns it into an invoice on every
+-- occurrence. Occurrences are counted from start_date, so a schedule that
+-- starts on the 31st bills on the last day of shorter months.
+CREATE TABLE IF NOT EXISTS public.agency_recurring_invoice_schedules (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid NOT NULL REFERENCES public.agencies(id) ON DELETE CASCADE,
+  client_id uuid NOT NULL REFERENCES public.agency_clients(id) ON DELETE CASCADE,
+  name text NOT NULL,
+  frequency text NOT NULL
+    CHECK (frequency IN ('weekly','biweekly','monthly','quarterly','yearly')),
+  start_date date NOT NULL,
+  end_date date,
+  -- Next occurrence to generate; null once the schedule has ended.
+  next_run_on date,
+  due_days integer NOT NULL DEFAULT 30 CHECK (due_days >= 0),
+  auto_send boolean NOT NULL DEFAULT false,
+  status text NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active','paused','ended')),
+  -- Items, expenses and financial settings copied onto every invoice.
+  template jsonb NOT NULL DEFAULT '{}'::jsonb,
+  generated_count integer NOT NULL DEFAULT 0,
+  last_generated_at timestamptz,
+  last_invoice_id uuid REFERENCES public.agency_invoices(id) ON DELETE SET NULL,
+  created_by uuid,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now(),
+  CHECK (end_date IS NULL OR end_date >= start_date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_agency_recurring_invoice_schedules_agency_id
+  ON public.agency_recurring_invoice_schedules(agency_id);
+CREATE INDEX IF NOT EXISTS idx_agency_recurring_invoice_schedules_due
+  ON public.agency_recurring_invoice_schedules(status, next_run_on);
+
+ALTER TABLE public.agency_recurring_invoice_schedules ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "agency_recurring_invoice_schedules select own" ON public.agency_recurring_invoice_schedules;
+CREATE POLICY "agency_recurring_invoice_schedules select own" ON public.agency_recurring_invoice_schedules
+  FOR SELECT USING (auth.uid() = agency_id);
+
+-- One row per generated occurrence. The job claims the row before creating
+-- the invoice, so an occurrence is never billed twice; failed generations
+-- are retried on the next run.
+CREATE TABLE IF NOT EXISTS public.agency_recurring_invoice_runs (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid NOT NULL REFERENCES public.agencies(id) ON DELETE CASCADE,
+  schedule_id uuid NOT NULL REFERENCES public.agency_recurring_invoice_schedules(id) ON DELETE CASCADE,
+  occurrence_date date NOT NULL,
+  invoice_id uuid REFERENCES public.agency_invoices(id) ON DELETE SET NULL,
+  status text NOT NULL CHECK (status IN ('pending','generated','sent','failed')),
+  error text,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now(),
+  UNIQUE (schedule_id, occurrence_date)
+);
+
+ALTER TABLE public.agency_recurring_invoice_runs ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "agency_recurring_invoice_runs select own" ON public.agency_recurring_invoice_runs;
+CREATE POLICY "agency_recurring_invoice_runs select own" ON public.agency_recurring_invoice_runs
+  FOR SELECT USING (auth.uid() = agency_id);
+
+COMMIT;
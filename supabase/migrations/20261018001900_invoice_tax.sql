@@ -0,0 +1,38 @@
+BEGIN;
+
+-- Multi-rate invoice tax. tax_rate_bps on the invoice stays the default for
+-- lines that do not set their own rate; tax_exempt is kept in step with
+-- tax_treatment for older readers.
+ALTER TABLE public.agency_invoices
+  ADD COLUMN IF NOT EXISTS tax_treatment text NOT NULL DEFAULT 'standard',
+  ADD COLUMN IF NOT EXISTS prices_include_tax boolean NOT NULL DEFAULT false,
+  ADD COLUMN IF NOT EXISTS tax_label text,
+  ADD COLUMN IF NOT EXISTS tax_jurisdiction text,
+  ADD COLUMN IF NOT EXISTS customer_tax_id text,
+  -- Printed under the totals, e.g. the reverse-charge wording.
+  ADD COLUMN IF NOT EXISTS tax_note text;
+
+UPDATE public.agency_invoices SET tax_treatment = 'exempt' WHERE tax_exempt;
+
+ALTER TABLE public.agency_invoices
+  DROP CONSTRAINT IF EXISTS agency_invoices_tax_treatment_check;
+ALTER TABLE public.agency_invoices
+  ADD CONSTRAINT agency_invoices_tax_treatment_check
+  CHECK (tax_treatment IN ('standard','exempt','reverse_charge'));
+
+ALTER TABLE public.agency_invoice_items
+  ADD COLUMN IF NOT EXISTS tax_rate_bps integer CHECK (tax_rate_bps >= 0);
+
+ALTER TABLE public.agency_invoice_expenses
+  ADD COLUMN IF NOT EXISTS tax_rate_bps integer CHECK (tax_rate_bps >= 0);
+
+-- Overrides keyed by jurisdiction code, e.g.
+-- {"DE": {"rate_bps": 1900, "label": "MwSt"}, "FR": {"treatment": "reverse_charge"}}.
+ALTER TABLE public.agency_tax_currency_settings
+  ADD COLUMN IF NOT EXISTS tax_jurisdictions jsonb NOT NULL DEFAULT '{}'::jsonb;
+
+ALTER TABLE public.agency_clients
+  ADD COLUMN IF NOT EXISTS tax_jurisdiction text,
+  ADD COLUMN IF NOT EXISTS tax_id text;
+
+COMMIT;
@@ -0,0 +1,40 @@
+BEGIN;
+
+-- Ledger account names (or Xero account codes) the accounting export posts
+-- to. Missing keys fall back to the server's defaults.
+CREATE TABLE IF NOT EXISTS public.agency_accounting_settings (
+  agency_id uuid PRIMARY KEY REFERENCES public.agencies(id) ON DELETE CASCADE,
+  accounts jsonb NOT NULL DEFAULT '{}'::jsonb,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+ALTER TABLE public.agency_accounting_settings ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "agency_accounting_settings select own" ON public.agency_accounting_settings;
+CREATE POLICY "agency_accounting_settings select own" ON public.agency_accounting_settings
+  FOR SELECT USING (auth.uid() = agency_id);
+
+-- Journal entries of closed periods as first exported, with the account
+-- mapping used, so re-exporting a closed period gives the same file.
+CREATE TABLE IF NOT EXISTS public.agency_accounting_exports (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid NOT NULL REFERENCES public.agencies(id) ON DELETE CASCADE,
+  period_start date NOT NULL,
+  period_end date NOT NULL,
+  accounts jsonb NOT NULL,
+  entries jsonb NOT NULL,
+  entry_count integer NOT NULL DEFAULT 0,
+  created_by uuid,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  UNIQUE (agency_id, period_start, period_end),
+  CHECK (period_end >= period_start)
+);
+
+ALTER TABLE public.agency_accounting_exports ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "agency_accounting_exports select own" ON public.agency_accounting_exports;
+CREATE POLICY "agency_accounting_exports select own" ON public.agency_accounting_exports
+  FOR SELECT USING (auth.uid() = agency_id);
+
+COMMIT;
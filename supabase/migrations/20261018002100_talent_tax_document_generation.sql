@@ -0,0 +1,24 @@
+BEGIN;
+
+-- Generated year-end documents: a 1099-NEC style summary for US talent and
+-- an earnings statement for everyone else. Rerunning a year with changed
+-- figures adds a corrected row that supersedes the previous one.
+ALTER TABLE public.talent_tax_documents
+  DROP CONSTRAINT IF EXISTS talent_tax_documents_doc_type_check;
+ALTER TABLE public.talent_tax_documents
+  ADD CONSTRAINT talent_tax_documents_doc_type_check
+  CHECK (doc_type IN ('1099','w9','earnings_statement'));
+
+ALTER TABLE public.talent_tax_documents
+  ADD COLUMN IF NOT EXISTS agency_id uuid REFERENCES public.agencies(id) ON DELETE CASCADE,
+  -- [{currency, licensing_cents, irl_cents, invoice_cents, total_cents}]
+  ADD COLUMN IF NOT EXISTS totals jsonb NOT NULL DEFAULT '[]'::jsonb,
+  -- Hash of the printed figures; unchanged documents are not regenerated.
+  ADD COLUMN IF NOT EXISTS sha256 text,
+  ADD COLUMN IF NOT EXISTS supersedes_id uuid REFERENCES public.talent_tax_documents(id) ON DELETE SET NULL,
+  ADD COLUMN IF NOT EXISTS notified_at timestamptz;
+
+CREATE INDEX IF NOT EXISTS idx_talent_tax_documents_agency_year
+  ON public.talent_tax_documents (agency_id, tax_year, created_at DESC);
+
+COMMIT;
@@ -0,0 +1,49 @@
+BEGIN;
+
+-- Monthly talent statement emails; off unless the agency turns them on.
+CREATE TABLE IF NOT EXISTS public.agency_talent_statement_settings (
+  agency_id uuid PRIMARY KEY REFERENCES public.agencies(id) ON DELETE CASCADE,
+  monthly_email_enabled boolean NOT NULL DEFAULT false,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+ALTER TABLE public.agency_talent_statement_settings ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "agency_talent_statement_settings select own" ON public.agency_talent_statement_settings;
+CREATE POLICY "agency_talent_statement_settings select own" ON public.agency_talent_statement_settings
+  FOR SELECT USING (auth.uid() = agency_id);
+
+-- Every statement emailed to a talent. A version is one set of figures for
+-- the talent and period; the unique key keeps a version from going out twice.
+CREATE TABLE IF NOT EXISTS public.talent_statement_deliveries (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid NOT NULL REFERENCES public.agencies(id) ON DELETE CASCADE,
+  talent_id uuid NOT NULL REFERENCES public.agency_users(id) ON DELETE CASCADE,
+  period_start date NOT NULL,
+  period_end date NOT NULL,
+  version integer NOT NULL CHECK (version > 0),
+  sha256 text NOT NULL,
+  -- The PDF as attached, in the private bucket.
+  storage_path text,
+  to_email text,
+  status text NOT NULL CHECK (status IN ('sent','failed')),
+  error text,
+  sent_at timestamptz,
+  -- The agency user who sent it; null for the monthly job.
+  sent_by uuid,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  UNIQUE (agency_id, talent_id, period_start, period_end, version),
+  CHECK (period_end >= period_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_talent_statement_deliveries_agency_period
+  ON public.talent_statement_deliveries (agency_id, period_start DESC);
+
+ALTER TABLE public.talent_statement_deliveries ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "talent_statement_deliveries select own" ON public.talent_statement_deliveries;
+CREATE POLICY "talent_statement_deliveries select own" ON public.talent_statement_deliveries
+  FOR SELECT USING (auth.uid() = agency_id);
+
+COMMIT;
@@ -0,0 +1,71 @@
+BEGIN;
+
+-- Expense approval: submitted -> approved | rejected, approved -> reimbursed.
+-- Older clients sent 'pending' for an expense awaiting review.
+UPDATE public.agency_expenses SET status = 'submitted' WHERE status = 'pending';
+
+ALTER TABLE public.agency_expenses
+  ADD COLUMN IF NOT EXISTS notes text,
+  -- Tags. Booking talent ids are not constrained either, so neither is this.
+  ADD COLUMN IF NOT EXISTS talent_id uuid,
+  ADD COLUMN IF NOT EXISTS booking_id uuid REFERENCES public.bookings(id) ON DELETE SET NULL,
+  ADD COLUMN IF NOT EXISTS client_id uuid REFERENCES public.agency_clients(id) ON DELETE SET NULL,
+  -- Billable expenses are rebilled to their client.
+  ADD COLUMN IF NOT EXISTS billable boolean NOT NULL DEFAULT false,
+  ADD COLUMN IF NOT EXISTS review_note text,
+  ADD COLUMN IF NOT EXISTS reviewed_at timestamptz,
+  ADD COLUMN IF NOT EXISTS reimbursed_at timestamptz,
+  ADD COLUMN IF NOT EXISTS reimbursement_reference text,
+  -- Set when a rebill claims the expense, before its draft invoice exists.
+  ADD COLUMN IF NOT EXISTS rebilled_at timestamptz,
+  ADD COLUMN IF NOT EXISTS rebilled_invoice_id uuid REFERENCES public.agency_invoices(id) ON DELETE SET NULL,
+  ADD COLUMN IF NOT EXISTS updated_at timestamptz NOT NULL DEFAULT now();
+
+DO $$
+BEGIN
+  IF NOT EXISTS (
+    SELECT 1 FROM pg_constraint WHERE conname = 'agency_expenses_status_check'
+  ) THEN
+    ALTER TABLE public.agency_expenses
+      ADD CONSTRAINT agency_expenses_status_check
+      CHECK (status IN ('submitted','approved','rejected','reimbursed'));
+  END IF;
+  IF NOT EXISTS (
+    SELECT 1 FROM pg_constraint WHERE conname = 'agency_expenses_billable_client_check'
+  ) THEN
+    ALTER TABLE public.agency_expenses
+      ADD CONSTRAINT agency_expenses_billable_client_check
+      CHECK (NOT billable OR client_id IS NOT NULL);
+  END IF;
+END $$;
+
+CREATE INDEX IF NOT EXISTS idx_agency_expenses_client_billable
+  ON public.agency_expenses (agency_id, client_id)
+  WHERE billable AND rebilled_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_agency_expenses_rebilled_invoice
+  ON public.agency_expenses (rebilled_invoice_id)
+  WHERE rebilled_invoice_id IS NOT NULL;
+
+-- Receipt files (images or PDFs) in the private bucket.
+CREATE TABLE IF NOT EXISTS public.agency_expense_receipts (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid NOT NULL REFERENCES public.agencies(id) ON DELETE CASCADE,
+  expense_id uuid NOT NULL REFERENCES public.agency_expenses(id) ON DELETE CASCADE,
+  file_name text NOT NULL,
+  content_type text NOT NULL,
+  size_bytes bigint NOT NULL CHECK (size_bytes >= 0),
+  storage_bucket text NOT NULL,
+  storage_path text NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_agency_expense_receipts_expense
+  ON public.agency_expense_receipts (expense_id);
+
+ALTER TABLE public.agency_expense_receipts ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "agency_expense_receipts select own" ON public.agency_expense_receipts;
+CREATE POLICY "agency_expense_receipts select own" ON public.agency_expense_receipts
+  FOR SELECT USING (auth.uid() = agency_id);
+
+COMMIT;
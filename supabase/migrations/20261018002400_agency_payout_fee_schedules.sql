@@ -0,0 +1,32 @@
+BEGIN;
+
+-- Per-agency payout fee schedules. Each save is a new version; the rules are
+-- [{payout_method, currency?, flat_cents, percent_bps, min_fee_cents?, max_fee_cents?}].
+-- Agencies without a schedule pay the platform rate (PAYOUT_FEE_BPS).
+CREATE TABLE IF NOT EXISTS public.agency_payout_fee_schedules (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid NOT NULL REFERENCES public.agencies(id) ON DELETE CASCADE,
+  version integer NOT NULL CHECK (version > 0),
+  rules jsonb NOT NULL DEFAULT '[]'::jsonb,
+  note text,
+  -- The ops user who saved it.
+  created_by uuid,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  UNIQUE (agency_id, version)
+);
+
+ALTER TABLE public.agency_payout_fee_schedules ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "agency_payout_fee_schedules select own" ON public.agency_payout_fee_schedules;
+CREATE POLICY "agency_payout_fee_schedules select own" ON public.agency_payout_fee_schedules
+  FOR SELECT USING (auth.uid() = agency_id);
+
+-- The fee each request was charged and the schedule version it came from
+-- (0: the platform rate). Older requests leave these null.
+ALTER TABLE public.agency_payout_requests
+  ADD COLUMN IF NOT EXISTS fee_cents bigint CHECK (fee_cents >= 0),
+  ADD COLUMN IF NOT EXISTS net_cents bigint,
+  ADD COLUMN IF NOT EXISTS fee_schedule_id uuid REFERENCES public.agency_payout_fee_schedules(id) ON DELETE SET NULL,
+  ADD COLUMN IF NOT EXISTS fee_schedule_version integer;
+
+COMMIT;
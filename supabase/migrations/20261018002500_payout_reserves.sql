@@ -0,0 +1,174 @@
+BEGIN;
+
+-- Chargeback risk of a brand's payments; picks the payout reserve policy.
+ALTER TABLE public.brands
+  ADD COLUMN IF NOT EXISTS payout_risk_level text NOT NULL DEFAULT 'standard'
+    CHECK (payout_risk_level IN ('low', 'standard', 'high'));
+
+-- What to withhold from payment link shares: reserve_bps of each share for
+-- hold_days, or all of it until the license contract is signed. A row without
+-- an agency is the platform default; one without a risk level applies to
+-- brands of every level. The most specific row wins.
+CREATE TABLE IF NOT EXISTS public.payout_reserve_policies (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  agency_id uuid REFERENCES public.agencies(id) ON DELETE CASCADE,
+  brand_risk_level text CHECK (brand_risk_level IN ('low', 'standard', 'high')),
+  reserve_bps integer NOT NULL DEFAULT 0 CHECK (reserve_bps BETWEEN 0 AND 10000),
+  hold_days integer NOT NULL DEFAULT 0 CHECK (hold_days BETWEEN 0 AND 180),
+  hold_until_contract_signed boolean NOT NULL DEFAULT false,
+  note text,
+  -- The ops user who saved it.
+  updated_by uuid,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_payout_reserve_policies_scope
+  ON public.payout_reserve_policies (
+    COALESCE(agency_id::text, ''),
+    COALESCE(brand_risk_level, '')
+  );
+
+ALTER TABLE public.payout_reserve_policies ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "payout_reserve_policies select own" ON public.payout_reserve_policies;
+CREATE POLICY "payout_reserve_policies select own" ON public.payout_reserve_policies
+  FOR SELECT USING (auth.uid() = agency_id);
+
+-- One withheld part of a payment link share. It stays in the recipient's
+-- balance (the share was never transferred) until it is released.
+CREATE TABLE IF NOT EXISTS public.payout_reserves (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  payment_link_id uuid NOT NULL REFERENCES public.agency_payment_links(id) ON DELETE CASCADE,
+  agency_id uuid NOT NULL REFERENCES public.agencies(id) ON DELETE CASCADE,
+  recipient_type text NOT NULL CHECK (recipient_type IN ('agency', 'creator')),
+  -- The agency or creator id.
+  recipient_id uuid NOT NULL,
+  talent_id uuid,
+  stripe_connect_account_id text,
+  currency text NOT NULL DEFAULT 'USD',
+  amount_cents bigint NOT NULL CHECK (amount_cents > 0),
+  -- Part of it taken by refunds and disputes.
+  clawback_cents bigint NOT NULL DEFAULT 0 CHECK (clawback_cents >= 0),
+  reason text NOT NULL CHECK (reason IN ('rolling', 'contract')),
+  -- Null while waiting on the contract.
+  release_at timestamptz,
+  license_submission_id uuid REFERENCES public.license_submissions(id) ON DELETE SET NULL,
+  policy_id uuid REFERENCES public.payout_reserve_policies(id) ON DELETE SET NULL,
+  status text NOT NULL DEFAULT 'held'
+    CHECK (status IN ('held', 'releasing', 'released', 'forfeited')),
+  -- Set by ops to keep it from being released when due.
+  manual_hold boolean NOT NULL DEFAULT false,
+  hold_note text,
+  released_at timestamptz,
+  stripe_transfer_id text,
+  failure_reason text,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_payout_reserves_recipient
+  ON public.payout_reserves (payment_link_id, recipient_type, recipient_id);
+CREATE INDEX IF NOT EXISTS idx_payout_reserves_due
+  ON public.payout_reserves (release_at)
+  WHERE status = 'held' AND NOT manual_hold;
+CREATE INDEX IF NOT EXISTS idx_payout_reserves_balance
+  ON public.payout_reserves (recipient_type, recipient_id)
+  WHERE status IN ('held', 'releasing');
+CREATE INDEX IF NOT EXISTS idx_payout_reserves_submission
+  ON public.payout_reserves (license_submission_id)
+  WHERE license_submission_id IS NOT NULL;
+
+ALTER TABLE public.payout_reserves ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS "payout_reserves select own" ON public.payout_reserves;
+CREATE POLICY "payout_reserves select own" ON public.payout_reserves
+  FOR SELECT USING (auth.uid() = agency_id OR auth.uid() = recipient_id);
+
+-- A released reserve is a second transfer to the same recipient.
+ALTER TABLE public.agency_payment_link_transfers
+  ADD COLUMN IF NOT EXISTS reserve_id uuid REFERENCES public.payout_reserves(id) ON DELETE SET NULL;
+
+DROP INDEX IF EXISTS public.idx_aplt_unique_recipient;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_aplt_unique_recipient
+  ON public.agency_payment_link_transfers (payment_link_id, recipient_type, recipient_id)
+  WHERE reserve_id IS NULL AND restores_transfer_id IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_aplt_unique_reserve
+  ON public.agency_payment_link_transfers (reserve_id)
+  WHERE reserve_id IS NOT NULL;
+
+-- Same as before, plus the reserve a transfer released and the transfer a
+-- restore pays back. A restore leaves the balances alone: the clawback it
+-- undoes never touched the available balance of a transferred share. The old
+-- overloads are dropped rather than left callable next to the new one.
+DROP FUNCTION IF EXISTS public.record_stripe_transfer(uuid, text, uuid, text, bigint, text, text, text, uuid, text);
+DROP FUNCTION IF EXISTS public.record_stripe_transfer(uuid, text, uuid, text, bigint, text, text, text, uuid, text, uuid);
+CREATE OR REPLACE FUNCTION public.record_stripe_transfer(
+    p_payment_link_id uuid,
+    p_recipient_type text, -- 'agency' or 'creator'
+    p_recipient_id uuid,
+    p_stripe_connect_account_id text,
+    p_amount_cents bigint,
+    p_currency text,
+    p_stripe_transfer_id text,
+    p_status text,
+    p_source_agency_id uuid DEFAULT NULL,
+    p_failure_reason text DEFAULT NULL,
+    p_reserve_id uuid DEFAULT NULL,
+    p_restores_transfer_id uuid DEFAULT NULL
+)
+RETURNS void AS $$
+BEGIN
+    INSERT INTO public.agency_payment_link_transfers (
+        payment_link_id,
+        recipient_type,
+        recipient_id,
+        stripe_connect_account_id,
+        amount_cents,
+        currency,
+        stripe_transfer_id,
+        status,
+        failure_reason,
+        reserve_id,
+        restores_transfer_id
+    )
+    VALUES (
+        p_payment_link_id,
+        p_recipient_type,
+        p_recipient_id,
+        p_stripe_connect_account_id,
+        p_amount_cents,
+        p_currency,
+        p_stripe_transfer_id,
+        p_status,
+        p_failure_reason,
+        p_reserve_id,
+        p_restores_transfer_id
+    );
+
+    IF p_status = 'created' AND p_stripe_transfer_id IS NOT NULL AND p_restores_transfer_id IS NULL THEN
+        IF p_recipient_type = 'agency' THEN
+            UPDATE public.agency_balances
+            SET available_cents = available_cents - p_amount_cents,
+                updated_at = now()
+            WHERE agency_id = p_recipient_id;
+        ELSIF p_recipient_type = 'creator' THEN
+            UPDATE public.creator_balances
+            SET available_cents = available_cents - p_amount_cents,
+                updated_at = now()
+            WHERE creator_id = p_recipient_id;
+
+            IF p_source_agency_id IS NOT NULL THEN
+                UPDATE public.agency_balances
+                SET available_cents = available_cents - p_amount_cents,
+                    updated_at = now()
+                WHERE agency_id = p_source_agency_id;
+            END IF;
+        END IF;
+    END IF;
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER SET search_path = public;
+
+REVOKE ALL ON FUNCTION public.record_stripe_transfer(uuid, text, uuid, text, bigint, text, text, text, uuid, text, uuid, uuid) FROM PUBLIC, anon, authenticated;
+
+COMMIT;